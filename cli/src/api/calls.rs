@@ -1,10 +1,15 @@
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
 use std::time::Duration;
 
-use samizdat_common::{pow::ProofOfWork, Hash, Key, Signed};
+use samizdat_common::{attestation::BuildAttestation, pow::ProofOfWork, Hash, Key, Signed};
 
-use super::{access_token, delete, get, patch, post, ApiError, CLIENT};
+use super::{
+    access_token, delete, get, get_raw, get_raw_into, offline, patch, post, post_at, put, ApiError,
+    CLIENT,
+};
 
 // Objects:
 
@@ -14,21 +19,45 @@ pub async fn post_object(
     bookmark: bool,
     is_draft: bool,
 ) -> Result<String, anyhow::Error> {
-    let url = format!("{}/_objects", crate::server());
+    post_object_at(
+        &crate::api_server(),
+        access_token(),
+        content,
+        content_type,
+        bookmark,
+        is_draft,
+    )
+    .await
+}
+
+/// Uploads an object to the node whose HTTP API is at the given base URL, e.g., to a peer.
+pub async fn post_object_at(
+    server: &str,
+    token: &str,
+    content: Vec<u8>,
+    content_type: &str,
+    bookmark: bool,
+    is_draft: bool,
+) -> Result<String, anyhow::Error> {
+    let url = format!("{server}/_objects");
     let response = CLIENT
         .post(&format!(
-            "{}/_objects?bookmark={}&is-draft={}",
-            crate::server(),
-            bookmark,
-            is_draft,
+            "{server}/_objects?bookmark={}&is-draft={}",
+            bookmark, is_draft,
         ))
         .header("Content-Type", content_type)
-        .header("Authorization", format!("Bearer {}", access_token()))
+        .header("Authorization", format!("Bearer {token}"))
         .body(content)
         .send()
         .await
         .with_context(|| format!("error from samizdat-node request POST /_objects"))?;
     let status = response.status();
+
+    // The node tells when the content does not look like the content type:
+    for warning in response.headers().get_all("X-Samizdat-Warning") {
+        eprintln!("warning: {}", String::from_utf8_lossy(warning.as_bytes()));
+    }
+
     let text = response
         .text()
         .await
@@ -42,6 +71,107 @@ pub async fn post_object(
     Ok(content?)
 }
 
+#[derive(Debug, Serialize)]
+pub struct PostBatchDeleteRequest<'a> {
+    pub hashes: &'a [String],
+}
+
+/// Deletes objects in the local node, returning how many of them existed and were removed.
+pub async fn post_batch_delete(
+    request: PostBatchDeleteRequest<'_>,
+) -> Result<usize, anyhow::Error> {
+    post("/_objects/batch-delete", request).await
+}
+
+/// Deletes objects in the node whose HTTP API is at the given base URL, e.g., in a peer.
+pub async fn post_batch_delete_at(
+    server: &str,
+    token: &str,
+    request: PostBatchDeleteRequest<'_>,
+) -> Result<usize, anyhow::Error> {
+    post_at(server, token, "/_objects/batch-delete", request).await
+}
+
+#[derive(Debug, Serialize)]
+pub struct PostFetchRequest<'a> {
+    pub url: &'a str,
+    pub content_type: Option<&'a str>,
+    pub bookmark: bool,
+    pub is_draft: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PostFetchResponse {
+    pub hash: String,
+    pub content_type: String,
+}
+
+pub async fn post_fetch(request: PostFetchRequest<'_>) -> Result<PostFetchResponse, anyhow::Error> {
+    post("/_objects/fetch", request).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HubAvailability {
+    pub hub: String,
+    pub candidates: usize,
+    pub response_time: Option<Duration>,
+    pub first_candidate_after: Option<Duration>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetAvailabilityResponse {
+    pub peers: usize,
+    /// The availability as seen by each hub, by address.
+    pub hubs: BTreeMap<String, HubAvailability>,
+}
+
+pub async fn get_availability(hash: &str) -> Result<GetAvailabilityResponse, anyhow::Error> {
+    get(format!("/_objects/{hash}/availability")).await
+}
+
+/// Writes the content of an object in the node as it arrives. Returns `Ok(false)` if it is not
+/// there.
+pub async fn export_object(hash: &str, writer: impl Write) -> Result<bool, anyhow::Error> {
+    if crate::cli::cli().offline {
+        return Ok(samizdat_node_core::offline::export_object(hash, writer)?);
+    }
+
+    get_raw_into(
+        format!("{}/_objects/{hash}", crate::api_server()).parse()?,
+        writer,
+    )
+    .await
+}
+
+pub async fn get_object_torrent(
+    hash: &str,
+    name: Option<&str>,
+) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let mut url: reqwest::Url =
+        format!("{}/_objects/{hash}/torrent", crate::api_server()).parse()?;
+
+    if let Some(name) = name {
+        url.query_pairs_mut().append_pair("name", name);
+    }
+
+    get_raw(url).await
+}
+
+// Bookmarks:
+
+#[derive(Debug, Serialize)]
+pub struct PostBookmarkBatchRequest<'a> {
+    pub mark: &'a [String],
+    pub unmark: &'a [String],
+}
+
+pub async fn post_bookmark_batch(
+    request: PostBookmarkBatchRequest<'_>,
+) -> Result<(), anyhow::Error> {
+    post("/_bookmarks/batch", request).await
+}
+
 // Series owners:
 
 #[derive(Debug, Serialize)]
@@ -85,6 +215,49 @@ pub async fn get_all_series_owners() -> Result<Vec<GetSeriesOwnerResponse>, anyh
     get("/_seriesowners").await
 }
 
+#[derive(Deserialize)]
+pub struct ItemReadership {
+    pub path: String,
+    pub served: u64,
+    pub reported: u64,
+}
+
+#[derive(Deserialize)]
+pub struct GetSeriesReadershipResponse {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub items: Vec<ItemReadership>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PostReplyRequest<'a> {
+    pub series_owner_name: &'a str,
+}
+
+pub async fn post_reply(
+    locator: &str,
+    request: PostReplyRequest<'_>,
+) -> Result<String, anyhow::Error> {
+    post(format!("/_replies/{locator}"), request).await
+}
+
+#[derive(Deserialize)]
+pub struct GetRepliesResponse {
+    pub public_key: String,
+    pub collection: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Finds the replies to an item in the network, returning all the replies known to the node.
+pub async fn post_fetch_replies(locator: &str) -> Result<Vec<GetRepliesResponse>, anyhow::Error> {
+    post(format!("/_replies/{locator}/fetch"), ()).await
+}
+
+pub async fn get_series_readership(
+    series_name: &str,
+) -> Result<Vec<GetSeriesReadershipResponse>, anyhow::Error> {
+    get(format!("/_seriesowners/{series_name}/readership")).await
+}
+
 // Series:
 
 #[derive(Deserialize)]
@@ -93,9 +266,183 @@ pub struct GetSeriesResponse {
 }
 
 pub async fn get_all_series() -> Result<Vec<GetSeriesResponse>, anyhow::Error> {
+    if crate::cli::cli().offline {
+        return offline(samizdat_node_core::offline::series());
+    }
+
     get("/_series").await
 }
 
+// Series trust:
+
+#[derive(Debug, Serialize)]
+pub struct PutSeriesTrustRequest<'a> {
+    pub level: &'a str,
+    pub note: Option<&'a str>,
+}
+
+pub async fn put_series_trust(
+    public_key: &str,
+    request: PutSeriesTrustRequest<'_>,
+) -> Result<(), anyhow::Error> {
+    put(format!("/_seriestrust/{public_key}"), request).await
+}
+
+pub async fn delete_series_trust(public_key: &str) -> Result<bool, anyhow::Error> {
+    delete(format!("/_seriestrust/{public_key}")).await
+}
+
+#[derive(Deserialize)]
+pub struct GetSeriesTrustResponse {
+    pub public_key: String,
+    pub level: String,
+    pub note: Option<String>,
+    pub set_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub async fn get_all_series_trust() -> Result<Vec<GetSeriesTrustResponse>, anyhow::Error> {
+    get("/_seriestrust").await
+}
+
+// Hub routes:
+
+#[derive(Debug, Serialize)]
+pub struct PutHubRouteRequest<'a> {
+    pub hubs: &'a [String],
+}
+
+pub async fn put_hub_route(
+    public_key: &str,
+    request: PutHubRouteRequest<'_>,
+) -> Result<(), anyhow::Error> {
+    put(format!("/_hubroutes/{public_key}"), request).await
+}
+
+pub async fn delete_hub_route(public_key: &str) -> Result<bool, anyhow::Error> {
+    delete(format!("/_hubroutes/{public_key}")).await
+}
+
+#[derive(Deserialize)]
+pub struct GetHubRouteResponse {
+    pub public_key: String,
+    pub hubs: Vec<String>,
+}
+
+pub async fn get_all_hub_routes() -> Result<Vec<GetHubRouteResponse>, anyhow::Error> {
+    get("/_hubroutes").await
+}
+
+// Hub keys:
+
+#[derive(Deserialize)]
+pub struct GetHubKeyResponse {
+    pub hub: String,
+    pub pinned: String,
+    pub pinned_at: String,
+    pub presented: Option<String>,
+    pub presented_at: Option<String>,
+}
+
+pub async fn get_all_hub_keys() -> Result<Vec<GetHubKeyResponse>, anyhow::Error> {
+    get("/_hubs/keys").await
+}
+
+pub async fn post_hub_key_accept(name: &str) -> Result<GetHubKeyResponse, anyhow::Error> {
+    post(format!("/_hubs/{name}/key/accept"), ()).await
+}
+
+pub async fn delete_hub_key(name: &str) -> Result<bool, anyhow::Error> {
+    delete(format!("/_hubs/{name}/key")).await
+}
+
+// Connections:
+
+#[derive(Deserialize)]
+pub struct GetConnectionUsageResponse {
+    pub day: String,
+    pub counterpart: String,
+    pub addr: Option<String>,
+    pub sent: u64,
+    pub received: u64,
+}
+
+pub async fn get_connection_usage(
+    days: u32,
+) -> Result<Vec<GetConnectionUsageResponse>, anyhow::Error> {
+    get(format!("/_connections/usage?days={days}")).await
+}
+
+// Key-value store:
+
+#[derive(Deserialize)]
+pub struct GetKvstoreResponse {
+    pub entity: String,
+    pub keys: usize,
+    pub used: usize,
+}
+
+pub async fn get_all_kvstores() -> Result<Vec<GetKvstoreResponse>, anyhow::Error> {
+    get("/_kvstores").await
+}
+
+pub async fn delete_kvstore(entity: &str) -> Result<(), anyhow::Error> {
+    delete(format!("/_kvstores/{}", entity.trim_start_matches('/'))).await
+}
+
+// Hub directories:
+
+#[derive(Debug, Serialize)]
+pub struct PostHubDirectoryRequest<'a> {
+    pub public_key: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatchHubDirectoryRequest {
+    pub is_trusted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DirectoryEntry {
+    pub address: String,
+    pub key: Option<String>,
+    pub policies: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetHubDirectoryResponse {
+    pub public_key: Key,
+    pub is_trusted: bool,
+    pub hubs: Vec<DirectoryEntry>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+pub async fn post_hub_directory(
+    request: PostHubDirectoryRequest<'_>,
+) -> Result<String, anyhow::Error> {
+    post("/_hubdirectories", request).await
+}
+
+pub async fn patch_hub_directory(
+    public_key: &str,
+    request: PatchHubDirectoryRequest,
+) -> Result<GetHubDirectoryResponse, anyhow::Error> {
+    patch(format!("/_hubdirectories/{public_key}"), request).await
+}
+
+pub async fn delete_hub_directory(public_key: &str) -> Result<bool, anyhow::Error> {
+    delete(format!("/_hubdirectories/{public_key}")).await
+}
+
+pub async fn get_hub_directory(
+    public_key: &str,
+) -> Result<Option<GetHubDirectoryResponse>, anyhow::Error> {
+    get(format!("/_hubdirectories/{public_key}")).await
+}
+
+pub async fn get_all_hub_directories() -> Result<Vec<GetHubDirectoryResponse>, anyhow::Error> {
+    get("/_hubdirectories").await
+}
+
 // Editions:
 
 #[derive(Deserialize)]
@@ -107,9 +454,65 @@ pub struct GetEditionResponse {
 }
 
 pub async fn get_all_editions() -> Result<Vec<GetEditionResponse>, anyhow::Error> {
+    if crate::cli::cli().offline {
+        return offline(samizdat_node_core::offline::editions());
+    }
+
     get("/_editions").await
 }
 
+/// An item of a series, together with the collection from which the node took it.
+pub struct SeriesItem {
+    pub content: Vec<u8>,
+    pub collection: Option<String>,
+}
+
+pub async fn get_series_item(
+    series: &str,
+    path: &str,
+) -> Result<Option<SeriesItem>, anyhow::Error> {
+    let mut url: reqwest::Url = crate::api_server().parse()?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow::anyhow!("server url cannot be a base"))?
+        .push("_series")
+        .push(series)
+        .extend(path.split('/'));
+
+    let response = CLIENT
+        .get(url.clone())
+        .header("Authorization", format!("Bearer {}", access_token()))
+        .send()
+        .await
+        .with_context(|| format!("error from samizdat-node request GET {}", url.path()))?;
+    let status = response.status();
+
+    log::info!("{} GET {}", status, url);
+
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    if !status.is_success() {
+        anyhow::bail!("samizdat-node responded {status} to GET {}", url.path());
+    }
+
+    let collection = response
+        .headers()
+        .get("X-Samizdat-Collection")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let content = response
+        .bytes()
+        .await
+        .with_context(|| format!("error from samizdat-node response GET {}", url.path()))?
+        .to_vec();
+
+    Ok(Some(SeriesItem {
+        content,
+        collection,
+    }))
+}
+
 // Auth:
 
 #[derive(Serialize)]
@@ -127,25 +530,184 @@ pub async fn delete_auth(scope: &str) -> Result<bool, anyhow::Error> {
 
 // Collections:
 
+#[derive(Debug, Serialize)]
+pub struct ItemMetadata {
+    pub cache_control: Option<String>,
+    pub content_language: Option<String>,
+    pub charset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_security_policy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_options: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referrer_policy: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct PostCollectionRequest<'a> {
     pub hashes: &'a [(String, String)],
     pub is_draft: bool,
+    pub metadata: &'a BTreeMap<String, ItemMetadata>,
+    pub content_warnings: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inventory_shard_size: Option<usize>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub prefetch: &'a [String],
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub mounts: &'a BTreeMap<String, Mount>,
+}
+
+/// Another collection to mount under a path prefix of a collection.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mount {
+    /// A fixed collection, by hash.
+    Collection(String),
+    /// The latest edition of a series, by public key.
+    Series(String),
+}
+
+/// Collections with more items than this are sent to the node a batch at a time, through a
+/// collection builder.
+const MAX_ITEMS_PER_REQUEST: usize = 5_000;
+
+#[derive(Debug, Serialize)]
+pub struct PostBuilderItemsRequest<'a> {
+    pub hashes: &'a [(String, String)],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PostBuilderItemsResponse {
+    pub missing: Vec<String>,
+    pub total: usize,
 }
 
 pub async fn post_collection(request: PostCollectionRequest<'_>) -> Result<String, anyhow::Error> {
-    post("/_collections", request).await
+    if request.hashes.len() <= MAX_ITEMS_PER_REQUEST {
+        return post("/_collections", request).await;
+    }
+
+    let builder: String = post("/_collections/builder", ()).await?;
+
+    for batch in request.hashes.chunks(MAX_ITEMS_PER_REQUEST) {
+        let response: PostBuilderItemsResponse = post(
+            format!("/_collections/builder/{builder}/items"),
+            PostBuilderItemsRequest { hashes: batch },
+        )
+        .await?;
+
+        log::info!(
+            "Sent {} of {} items to collection builder {builder}",
+            response.total,
+            request.hashes.len()
+        );
+
+        if !response.missing.is_empty() {
+            delete::<_, ()>(format!("/_collections/builder/{builder}")).await?;
+            anyhow::bail!(
+                "objects missing in the node for items: {}",
+                response.missing.join(", ")
+            );
+        }
+    }
+
+    post(
+        format!("/_collections/builder/{builder}/finalize"),
+        PostCollectionRequest {
+            hashes: &[],
+            ..request
+        },
+    )
+    .await
+}
+
+pub async fn get_collection_item(
+    collection: &str,
+    path: &str,
+) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let mut url: reqwest::Url = crate::api_server().parse()?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow::anyhow!("server url cannot be a base"))?
+        .push("_collections")
+        .push(collection)
+        .extend(path.split('/'));
+
+    get_raw(url).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Inventory {
+    pub inventory: BTreeMap<String, Hash>,
+    /// The shards of a sharded inventory. Only how many there are matters here.
+    #[serde(default)]
+    pub shards: Vec<serde::de::IgnoredAny>,
+}
+
+/// Gets the inventory of a collection, putting together the shards of sharded inventories.
+pub async fn get_collection_inventory(
+    collection: &str,
+) -> Result<Option<Inventory>, anyhow::Error> {
+    let Some(content) = get_collection_item(collection, "_inventory").await? else {
+        return Ok(None);
+    };
+    let mut inventory: Inventory = serde_json::from_slice(&content)
+        .with_context(|| format!("error deserializing inventory of {collection}"))?;
+
+    for index in 0..inventory.shards.len() {
+        let Some(content) = get_collection_item(collection, &format!("_inventory/{index}")).await?
+        else {
+            return Ok(None);
+        };
+        let shard: Inventory = serde_json::from_slice(&content).with_context(|| {
+            format!("error deserializing inventory shard {index} of {collection}")
+        })?;
+        inventory.inventory.extend(shard.inventory);
+    }
+
+    Ok(Some(inventory))
 }
 
 pub async fn get_collection_list(collection: &str) -> Result<Vec<String>, anyhow::Error> {
     get(format!("/_collections/{collection}/_list")).await
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DiffEntry {
+    pub path: String,
+    pub hash: String,
+    pub size: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangedDiffEntry {
+    pub path: String,
+    pub old_hash: String,
+    pub old_size: Option<usize>,
+    pub new_hash: String,
+    pub new_size: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetCollectionDiffResponse {
+    pub added: Vec<DiffEntry>,
+    pub removed: Vec<DiffEntry>,
+    pub changed: Vec<ChangedDiffEntry>,
+}
+
+pub async fn get_collection_diff(
+    old: &str,
+    new: &str,
+) -> Result<GetCollectionDiffResponse, anyhow::Error> {
+    get(format!("/_collections/{old}/diff/{new}")).await
+}
+
 // Subscriptions:
 
 #[derive(Debug, Serialize)]
 pub struct PostSubscriptionRequest<'a> {
     pub public_key: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<&'a str>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -168,6 +730,20 @@ pub async fn get_all_subscriptions() -> Result<Vec<GetSubscriptionResponse>, any
     get("/_subscriptions").await
 }
 
+#[derive(Debug, Deserialize)]
+pub struct EditionCompleteness {
+    pub collection: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub present: usize,
+    pub total: Option<usize>,
+}
+
+pub async fn get_mirror_status(
+    public_key: &str,
+) -> Result<Vec<EditionCompleteness>, anyhow::Error> {
+    get(format!("/_subscriptions/{public_key}/mirror")).await
+}
+
 // Editions:
 
 #[derive(Debug, Serialize)]
@@ -175,14 +751,15 @@ pub struct PostEditionRequest<'a> {
     pub collection: &'a str,
     pub ttl: Option<&'a str>,
     pub no_announce: bool,
+    pub release_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CollectionRef {
     pub hash: Hash,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct EditionContent {
     pub collection: CollectionRef,
     pub timestamp: chrono::DateTime<chrono::Utc>,
@@ -202,6 +779,25 @@ pub async fn post_edition(
     post(format!("/_seriesowners/{series_name}/editions",), request).await
 }
 
+#[derive(Debug, Serialize)]
+pub struct PostAttestationRequest<'a> {
+    pub command: &'a str,
+    pub source: Hash,
+    pub toolchain: &'a str,
+    pub outputs: Hash,
+}
+
+pub async fn post_attestation(
+    series_name: &str,
+    request: PostAttestationRequest<'_>,
+) -> Result<Signed<BuildAttestation>, anyhow::Error> {
+    post(
+        format!("/_seriesowners/{series_name}/attestations"),
+        request,
+    )
+    .await
+}
+
 #[derive(Debug, Serialize)]
 pub struct PostIdentityRequest<'a> {
     pub identity: &'a str,
@@ -233,3 +829,37 @@ pub async fn post_identity(request: PostIdentityRequest<'_>) -> Result<bool, any
 pub async fn get_all_identities() -> Result<Vec<GetIdentityResponse>, anyhow::Error> {
     get("/_identities").await
 }
+
+#[derive(Debug, Serialize)]
+pub struct PostWipeRequest<'a> {
+    pub everything: bool,
+    pub decoy: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PostWipeResponse {
+    pub entries: usize,
+    pub profiles: Vec<String>,
+}
+
+pub async fn post_wipe(request: PostWipeRequest<'_>) -> Result<PostWipeResponse, anyhow::Error> {
+    post("/_wipe", request).await
+}
+
+#[derive(Debug, Serialize)]
+pub struct PostSyncRequest<'a> {
+    pub peer: &'a str,
+    pub token: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PostSyncResponse {
+    pub different_buckets: usize,
+    pub objects: usize,
+    pub items: usize,
+    pub failed: Vec<(serde_json::Value, String)>,
+}
+
+pub async fn post_sync(request: PostSyncRequest<'_>) -> Result<PostSyncResponse, anyhow::Error> {
+    post("/_sync", request).await
+}
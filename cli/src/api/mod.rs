@@ -5,6 +5,7 @@ pub use calls::*;
 use anyhow::Context;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::io::Write;
 
 //use samizdat_common::Hash;
 
@@ -42,7 +43,7 @@ where
     R: AsRef<str>,
     Q: for<'a> Deserialize<'a>,
 {
-    let url = format!("{}{}", crate::server(), route.as_ref());
+    let url = format!("{}{}", crate::api_server(), route.as_ref());
     let response = CLIENT
         .get(&url)
         .header("Authorization", format!("Bearer {}", access_token()))
@@ -67,16 +68,108 @@ where
     Ok(content?)
 }
 
+/// Decodes what the database of the node gives with `--offline`, which is the same JSON the
+/// node would respond with.
+fn offline<Q>(
+    value: Result<serde_json::Value, samizdat_node_core::Error>,
+) -> Result<Q, anyhow::Error>
+where
+    Q: for<'a> Deserialize<'a>,
+{
+    let value = value.map_err(|err| anyhow::anyhow!("error reading the database: {err}"))?;
+    serde_json::from_value(value).context("error deserializing from the database")
+}
+
+/// Gets the raw content of a URL in the local node. Returns `Ok(None)` if the content was not
+/// found.
+async fn get_raw(url: reqwest::Url) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let response = CLIENT
+        .get(url.clone())
+        .header("Authorization", format!("Bearer {}", access_token()))
+        .send()
+        .await
+        .with_context(|| format!("error from samizdat-node request GET {}", url.path()))?;
+    let status = response.status();
+
+    log::info!("{} GET {}", status, url);
+
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    if !status.is_success() {
+        anyhow::bail!("samizdat-node responded {status} to GET {}", url.path());
+    }
+
+    let bytes = response
+        .bytes()
+        .await
+        .with_context(|| format!("error from samizdat-node response GET {}", url.path()))?;
+
+    Ok(Some(bytes.to_vec()))
+}
+
+/// Writes the raw content of a URL in the local node as it arrives, without holding all of it
+/// in memory. Returns `Ok(false)` if the content was not found.
+async fn get_raw_into(url: reqwest::Url, mut writer: impl Write) -> Result<bool, anyhow::Error> {
+    let mut response = CLIENT
+        .get(url.clone())
+        .header("Authorization", format!("Bearer {}", access_token()))
+        .send()
+        .await
+        .with_context(|| format!("error from samizdat-node request GET {}", url.path()))?;
+    let status = response.status();
+
+    log::info!("{} GET {}", status, url);
+
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+
+    if !status.is_success() {
+        anyhow::bail!("samizdat-node responded {status} to GET {}", url.path());
+    }
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .with_context(|| format!("error from samizdat-node response GET {}", url.path()))?
+    {
+        writer
+            .write_all(&chunk)
+            .context("failed to write content")?;
+    }
+
+    writer.flush().context("failed to write content")?;
+
+    Ok(true)
+}
+
 async fn post<R, P, Q>(route: R, payload: P) -> Result<Q, anyhow::Error>
 where
     R: AsRef<str>,
     P: Serialize + std::fmt::Debug,
     Q: for<'a> Deserialize<'a>,
 {
-    let url = format!("{}{}", crate::server(), route.as_ref());
+    post_at(&crate::api_server(), access_token(), route, payload).await
+}
+
+/// Posts to the node whose HTTP API is at the given base URL, e.g., to a peer.
+async fn post_at<R, P, Q>(
+    server: &str,
+    token: &str,
+    route: R,
+    payload: P,
+) -> Result<Q, anyhow::Error>
+where
+    R: AsRef<str>,
+    P: Serialize + std::fmt::Debug,
+    Q: for<'a> Deserialize<'a>,
+{
+    let url = format!("{server}{}", route.as_ref());
     let response = CLIENT
         .post(&url)
-        .header("Authorization", format!("Bearer {}", access_token()))
+        .header("Authorization", format!("Bearer {token}"))
         .json(&payload)
         .send()
         .await
@@ -105,7 +198,7 @@ where
     P: Serialize,
     Q: for<'a> Deserialize<'a>,
 {
-    let url = format!("{}{}", crate::server(), route.as_ref());
+    let url = format!("{}{}", crate::api_server(), route.as_ref());
     let response = CLIENT
         .patch(&url)
         .header("Authorization", format!("Bearer {}", access_token()))
@@ -131,12 +224,44 @@ where
     Ok(content?)
 }
 
+async fn put<R, P, Q>(route: R, payload: P) -> Result<Q, anyhow::Error>
+where
+    R: AsRef<str>,
+    P: Serialize,
+    Q: for<'a> Deserialize<'a>,
+{
+    let url = format!("{}{}", crate::api_server(), route.as_ref());
+    let response = CLIENT
+        .put(&url)
+        .header("Authorization", format!("Bearer {}", access_token()))
+        .json(&payload)
+        .send()
+        .await
+        .with_context(|| format!("error from samizdat-node request PUT {}", route.as_ref()))?;
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .with_context(|| format!("error from samizdat-node response PUT {}", route.as_ref()))?;
+
+    log::info!("{} PUT {} {}", status, url, text);
+
+    let content: Result<Q, ApiError> = serde_json::from_str(&text).with_context(|| {
+        format!(
+            "error deserializing response from PUT {}: {text}",
+            route.as_ref()
+        )
+    })?;
+
+    Ok(content?)
+}
+
 async fn delete<R, Q>(route: R) -> Result<Q, anyhow::Error>
 where
     R: AsRef<str>,
     Q: for<'a> Deserialize<'a>,
 {
-    let url = format!("{}{}", crate::server(), route.as_ref());
+    let url = format!("{}{}", crate::api_server(), route.as_ref());
     let response = CLIENT
         .delete(&url)
         .header("Authorization", format!("Bearer {}", access_token()))
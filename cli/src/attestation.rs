@@ -0,0 +1,97 @@
+//! Build attestations (see [`samizdat_common::attestation`]). Sources and outputs are hashed as
+//! trees: the sorted list of the relative paths of all files, each with the hash of its content.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use samizdat_common::attestation::BuildAttestation;
+use samizdat_common::{Hash, Signed};
+
+use crate::api;
+use crate::manifest::Target;
+
+/// Drops the `.` components of a path, so that `./dist` and `dist` compare equal.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+/// Hashes all files under `root`, but hidden files and the paths (relative to `root`) in
+/// `exclude`.
+fn tree_hash(root: &Path, exclude: &[PathBuf]) -> Result<Hash, anyhow::Error> {
+    fn walk(
+        root: &Path,
+        path: &Path,
+        exclude: &[PathBuf],
+        files: &mut Vec<PathBuf>,
+    ) -> Result<(), anyhow::Error> {
+        for entry in fs::read_dir(path)? {
+            let subpath = entry?.path();
+            let relative = normalize(subpath.strip_prefix(root)?);
+
+            let is_hidden = subpath
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if is_hidden || exclude.contains(&relative) {
+                continue;
+            }
+
+            if subpath.is_dir() {
+                walk(root, &subpath, exclude, files)?;
+            } else {
+                files.push(relative);
+            }
+        }
+
+        Ok(())
+    }
+
+    let exclude = exclude
+        .iter()
+        .map(|path| normalize(path))
+        .collect::<Vec<_>>();
+    let mut files = vec![];
+    walk(root, root, &exclude, &mut files)?;
+    files.sort();
+
+    let mut listing = String::new();
+    for file in files {
+        let content_hash = Hash::hash(fs::read(root.join(&file))?);
+        listing += &format!("{}\t{content_hash}\n", file.to_string_lossy());
+    }
+
+    Ok(Hash::hash(listing))
+}
+
+/// The hash of the sources of a target of the project in the current directory.
+pub fn source_hash(target: &Target) -> Result<Hash, anyhow::Error> {
+    let mut exclude = target.build.source_exclude.clone();
+    exclude.push(target.build.base.clone());
+    tree_hash(Path::new("."), &exclude)
+}
+
+/// The hash of the outputs of the last build of a target.
+pub fn outputs_hash(target: &Target) -> Result<Hash, anyhow::Error> {
+    tree_hash(&target.build.base, &[])
+}
+
+/// Has the node sign, with the key of the series owner, an attestation of the build just run,
+/// given the hash of the sources before the build.
+pub async fn attest(
+    target: &Target,
+    series_name: &str,
+    is_release: bool,
+    source: Hash,
+) -> Result<Signed<BuildAttestation>, anyhow::Error> {
+    api::post_attestation(
+        series_name,
+        api::PostAttestationRequest {
+            command: target.build.script(is_release),
+            source,
+            toolchain: &target.build.toolchain_fingerprint()?,
+            outputs: outputs_hash(target)?,
+        },
+    )
+    .await
+}
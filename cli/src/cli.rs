@@ -1,17 +1,31 @@
 use std::path::PathBuf;
 use structopt::StructOpt;
 
-use samizdat_common::{Hash, Key};
+use samizdat_common::{profiles, Hash, Key};
 
 use crate::commands;
 
 static mut CLI: Option<Cli> = None;
 
 pub fn init_cli() -> Result<(), anyhow::Error> {
-    let cli = Cli::from_args();
+    let mut cli = Cli::from_args();
 
     log::debug!("Arguments from command line: {:#?}", cli);
 
+    // Talk to the node of the chosen profile instead:
+    cli.base_data = cli.data.clone();
+    let profile = match &cli.profile {
+        Some(profile) => Some(profile.clone()),
+        None => profiles::current(&cli.base_data)?,
+    };
+
+    if let Some(name) = profile {
+        let profile = profiles::get(&cli.base_data, &name)?
+            .ok_or_else(|| anyhow::anyhow!("profile {name:?} does not exist"))?;
+        cli.data = profile.data(&cli.base_data);
+        cli.port = profile.port;
+    }
+
     unsafe {
         CLI = Some(cli);
     }
@@ -27,6 +41,11 @@ pub fn server() -> String {
     format!("http://localhost:{}", cli().port)
 }
 
+/// The root of the version of the node API this CLI speaks.
+pub fn api_server() -> String {
+    format!("{}/v1", server())
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub struct Cli {
     #[structopt(
@@ -40,6 +59,17 @@ pub struct Cli {
     pub verbose: bool,
     #[structopt(long, short, env = "SAMIZDAT_PORT", default_value = "4510")]
     pub port: u16,
+    /// The profile of the node to talk to. Defaults to the one set with `samizdat profile
+    /// switch`.
+    #[structopt(long, env = "SAMIZDAT_PROFILE")]
+    pub profile: Option<String>,
+    /// Reads the database of the node directly, for when the node is not running. Only
+    /// `series ls-cached`, `edition ls` and `object export` work offline.
+    #[structopt(long)]
+    pub offline: bool,
+    /// The data folder of the default profile, under which all other profiles live.
+    #[structopt(skip)]
+    pub base_data: PathBuf,
     #[structopt(subcommand)]
     pub command: Command,
 }
@@ -50,8 +80,12 @@ pub enum Command {
     Init {
         #[structopt(long)]
         name: Option<String>,
+        /// Adds a new target (e.g., `docs`) to the project in this folder instead, i.e., another
+        /// series published from the same project.
+        #[structopt(long, conflicts_with = "name")]
+        target: Option<String>,
     },
-    /// Imports a series from a `Samizdat.toml` in the current directory.
+    /// Imports the series of all targets from a `Samizdat.toml` in the current directory.
     Import {
         /// The private key of the series.
         #[structopt(long)]
@@ -68,6 +102,28 @@ pub enum Command {
         /// Whether to announce this new edition to he network or to keep quiet.
         #[structopt(long)]
         no_announce: bool,
+        /// Publish the edition sealed, so that nobody (not even mirrors) can read it before
+        /// this time (e.g., `2026-12-31T12:00:00Z`). The node must be running at this time to
+        /// release it.
+        #[structopt(long)]
+        release_at: Option<chrono::DateTime<chrono::Utc>>,
+        /// The targets to commit, as named in `Samizdat.toml`. Defaults to the main target.
+        #[structopt(long)]
+        target: Vec<String>,
+        /// Commit all targets. Files shared by targets are uploaded only once.
+        #[structopt(long, conflicts_with = "target")]
+        all_targets: bool,
+    },
+    /// Builds the project in the current directory again and checks that the outputs match the
+    /// build attestation in the latest edition of its series. Attestations are embedded by
+    /// setting `attest = true` in the `[build]` section of `Samizdat.toml`.
+    VerifyBuild {
+        /// The public key of the series. Defaults to the series of the current directory.
+        #[structopt(long)]
+        series: Option<String>,
+        /// The target to build, as named in `Samizdat.toml`. Defaults to the main target.
+        #[structopt(long)]
+        target: Option<String>,
     },
     /// Watches the current directory for changes, rebuilding and committing at
     /// every change.
@@ -75,6 +131,12 @@ pub enum Command {
         /// Set a custom time-to-leave for the commits.
         #[structopt(long)]
         ttl: Option<String>,
+        /// The targets to commit, as named in `Samizdat.toml`. Defaults to the main target.
+        #[structopt(long)]
+        target: Vec<String>,
+        /// Commit all targets.
+        #[structopt(long, conflicts_with = "target")]
+        all_targets: bool,
     },
     /// Uploads a single file as an object.
     Upload {
@@ -89,6 +151,85 @@ pub enum Command {
         draft: bool,
         file: PathBuf,
     },
+    /// Writes the contents of a series into a plain directory tree. Items missing from an
+    /// edition are taken from the previous editions, the same way the node resolves them.
+    Export {
+        /// The public key of the series.
+        series: String,
+        /// The directory to which the content will be written.
+        dir: PathBuf,
+        /// The collection hash of the edition to export, instead of the latest one.
+        #[structopt(long)]
+        collection: Option<String>,
+    },
+    /// Updates the Samizdat binaries in this machine to the latest release, downloaded
+    /// through the local node from the official release series.
+    SelfUpdate {
+        /// Only check whether an update is available, without installing it.
+        #[structopt(long)]
+        check: bool,
+    },
+    /// Copies into the local node all objects and collection items of a trusted peer node that
+    /// it has not, e.g., to replicate everything from your home node to a VPS. Since nodes only
+    /// accept local connections, the peer must be reached through a tunnel (e.g., `ssh -L`).
+    Sync {
+        /// The base URL of the HTTP API of the peer, e.g., `http://localhost:4610`.
+        #[structopt(long)]
+        peer: String,
+        /// The access token of the peer, found in the `access-token` file in its data folder.
+        #[structopt(long, env = "SAMIZDAT_PEER_TOKEN")]
+        peer_token: String,
+    },
+    /// Publishes a random draft object in a peer and queries it back through each hub the node
+    /// is connected to, without looking into the local database, showing how long each hub
+    /// took and what probably went wrong. Hubs never pair a node with itself, hence the peer.
+    Selftest {
+        /// The base URL of the HTTP API of a peer in which to publish the object, e.g.,
+        /// `http://localhost:4610`.
+        #[structopt(long)]
+        peer: String,
+        /// The access token of the peer, found in the `access-token` file in its data folder.
+        #[structopt(long, env = "SAMIZDAT_PEER_TOKEN")]
+        peer_token: String,
+        /// Keeps the object in the peer afterwards, instead of deleting it.
+        #[structopt(long)]
+        keep: bool,
+    },
+    /// Securely deletes the series owner keys, identities, subscriptions and all content of the
+    /// node, right away and without confirmation. Meant for when you are in physical danger.
+    Wipe {
+        /// Also wipe all other profiles in this machine.
+        #[structopt(long)]
+        everything: bool,
+        /// A profile to be kept, to which the CLI is switched afterwards.
+        #[structopt(long)]
+        decoy: Option<String>,
+    },
+    /// Commands for importing content from web archives.
+    Archive {
+        #[structopt(subcommand)]
+        command: ArchiveCommand,
+    },
+    /// Commands for publishing git repositories.
+    Git {
+        #[structopt(subcommand)]
+        command: GitCommand,
+    },
+    /// Commands for publishing received emails.
+    Mail {
+        #[structopt(subcommand)]
+        command: MailCommand,
+    },
+    /// Commands for moving content between IPFS and Samizdat.
+    Ipfs {
+        #[structopt(subcommand)]
+        command: IpfsCommand,
+    },
+    /// Commands for managing objects.
+    Object {
+        #[structopt(subcommand)]
+        command: ObjectCommand,
+    },
     /// Commands for managing series.
     Series {
         #[structopt(subcommand)]
@@ -109,11 +250,58 @@ pub enum Command {
         #[structopt(subcommand)]
         command: SubscriptionCommand,
     },
+    /// Commands for managing hub directories, i.e., series listing hubs to connect to.
+    HubDirectory {
+        #[structopt(subcommand)]
+        command: HubDirectoryCommand,
+    },
+    /// Commands for routing series exclusively through some hubs, e.g., to keep an
+    /// identity-bearing series off public hubs.
+    HubRoute {
+        #[structopt(subcommand)]
+        command: HubRouteCommand,
+    },
+    /// Commands for managing the keys pinned for hubs, i.e., the certificates this node
+    /// trusts each hub to present.
+    Hub {
+        #[structopt(subcommand)]
+        command: HubCommand,
+    },
+    /// Commands for inspecting the connections to hubs and peers.
+    Connection {
+        #[structopt(subcommand)]
+        command: ConnectionCommand,
+    },
+    /// Commands for managing what applications keep in the key-value store of this node.
+    Kvstore {
+        #[structopt(subcommand)]
+        command: KvstoreCommand,
+    },
+    /// Commands for mirroring series, i.e., keeping and serving all of their editions.
+    Mirror {
+        #[structopt(subcommand)]
+        command: MirrorCommand,
+    },
+    /// Commands for bridging objects to and from BitTorrent.
+    Torrent {
+        #[structopt(subcommand)]
+        command: TorrentCommand,
+    },
     /// Commands for managing identities.
     Identity {
         #[structopt(subcommand)]
         command: IdentityCommand,
     },
+    /// Commands for managing the profiles of the node in this machine.
+    Profile {
+        #[structopt(subcommand)]
+        command: ProfileCommand,
+    },
+    /// Commands for managing the data of a node that is not running.
+    Node {
+        #[structopt(subcommand)]
+        command: NodeCommand,
+    },
     /// Commands for managing authentication of scopes.
     Auth {
         #[structopt(subcommand)]
@@ -122,16 +310,40 @@ pub enum Command {
 }
 
 impl Command {
+    /// Whether this command works with `--offline`.
+    pub fn works_offline(&self) -> bool {
+        matches!(
+            self,
+            Command::Series {
+                command: SeriesCommand::LsCached { series_name: None }
+            } | Command::Edition {
+                command: EditionCommand::Ls { series_key: None }
+            } | Command::Object {
+                command: ObjectCommand::Export { .. }
+            }
+        )
+    }
+
     pub async fn execute(self) -> Result<(), anyhow::Error> {
         match self {
-            Command::Init { name } => commands::init(name).await,
+            Command::Init { name, target } => commands::init(name, target).await,
             Command::Import { private_key } => commands::import(private_key).await,
             Command::Commit {
                 ttl,
                 release,
                 no_announce,
-            } => commands::commit(&ttl, release, no_announce).await,
-            Command::Watch { ttl } => commands::watch(&ttl).await,
+                release_at,
+                target,
+                all_targets,
+            } => {
+                commands::commit(&ttl, release, no_announce, release_at, &target, all_targets).await
+            }
+            Command::VerifyBuild { series, target } => commands::verify_build(series, target).await,
+            Command::Watch {
+                ttl,
+                target,
+                all_targets,
+            } => commands::watch(&ttl, &target, all_targets).await,
             Command::Upload {
                 file,
                 content_type,
@@ -145,16 +357,351 @@ impl Command {
                 });
                 commands::upload(&file, content_type, !no_bookmark, draft).await
             }
+            Command::Export {
+                series,
+                dir,
+                collection,
+            } => commands::export(series, dir, collection).await,
+            Command::SelfUpdate { check } => commands::self_update(check).await,
+            Command::Sync { peer, peer_token } => commands::sync(peer, peer_token).await,
+            Command::Selftest {
+                peer,
+                peer_token,
+                keep,
+            } => commands::selftest(peer, peer_token, keep).await,
+            Command::Wipe { everything, decoy } => commands::wipe(everything, decoy).await,
+            Command::Archive { command } => command.execute().await,
+            Command::Git { command } => command.execute().await,
+            Command::Mail { command } => command.execute().await,
+            Command::Ipfs { command } => command.execute().await,
+            Command::Object { command } => command.execute().await,
             Command::Series { command } => command.execute().await,
             Command::Edition { command } => command.execute().await,
             Command::Collection { command } => command.execute().await,
             Command::Subscription { command } => command.execute().await,
+            Command::HubDirectory { command } => command.execute().await,
+            Command::HubRoute { command } => command.execute().await,
+            Command::Hub { command } => command.execute().await,
+            Command::Connection { command } => command.execute().await,
+            Command::Kvstore { command } => command.execute().await,
+            Command::Mirror { command } => command.execute().await,
+            Command::Torrent { command } => command.execute().await,
             Command::Identity { command } => command.execute().await,
+            Command::Profile { command } => command.execute(),
+            Command::Node { command } => command.execute(),
             Command::Auth { command } => command.execute().await,
         }
     }
 }
 
+#[derive(Clone, Debug, StructOpt)]
+pub enum ArchiveCommand {
+    /// Imports the pages captured in a WARC file (plain or gzipped) or in a WACZ file into a new
+    /// collection, keeping their paths and content types.
+    Import {
+        /// The archive to be imported.
+        file: PathBuf,
+        /// Sets the collection and its objects as drafts. Drafts are not public to the network.
+        #[structopt(long)]
+        draft: bool,
+    },
+}
+
+impl ArchiveCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            ArchiveCommand::Import { file, draft } => commands::archive::import(file, draft).await,
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum GitCommand {
+    /// Publishes the history of the branches and tags of a git repository to a series, one
+    /// edition per commit. Each commit becomes a collection of its files, with the raw commit
+    /// object at `.git/commit`. A last edition holds all branches and tags under `.git/`.
+    Publish {
+        /// The name of the series owner to which the edition will be posted.
+        series_name: String,
+        /// The path to the git repository.
+        #[structopt(long, default_value = ".")]
+        repo: PathBuf,
+        /// The branches or tags to be published. All local branches and tags are published if
+        /// none is given.
+        #[structopt(long = "ref")]
+        refs: Vec<String>,
+        /// Only publishes the commits after this one, e.g., the last commit published before.
+        /// The whole history is published if not given.
+        #[structopt(long)]
+        since: Option<String>,
+        /// Set a custom time-to-leave for this edition.
+        #[structopt(long)]
+        ttl: Option<String>,
+        /// Sets the collections and their objects as drafts. Drafts are not public to the
+        /// network.
+        #[structopt(long)]
+        draft: bool,
+        /// Whether to announce this new edition to he network or to keep quiet.
+        #[structopt(long)]
+        no_announce: bool,
+    },
+}
+
+impl GitCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            GitCommand::Publish {
+                series_name,
+                repo,
+                refs,
+                since,
+                ttl,
+                draft,
+                no_announce,
+            } => {
+                commands::git::publish(repo, series_name, refs, since, ttl, draft, no_announce)
+                    .await
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum MailCommand {
+    /// Publishes the messages in a Maildir folder as a new edition of a series, keeping all
+    /// messages of the previous editions. Use a tool such as `fetchmail` or `mbsync` to
+    /// deliver mail from IMAP into the folder.
+    Ingest {
+        /// The name of the series owner to which the edition will be posted.
+        series_name: String,
+        /// The Maildir folder with the messages, i.e., the one containing `new` and `cur`.
+        #[structopt(long)]
+        maildir: PathBuf,
+        /// The title of the archive. Defaults to the name of the series owner.
+        #[structopt(long)]
+        title: Option<String>,
+        /// Keeps running, ingesting new messages periodically (e.g., `1h`).
+        #[structopt(long)]
+        every: Option<humantime::Duration>,
+        /// Set a custom time-to-leave for the editions.
+        #[structopt(long)]
+        ttl: Option<String>,
+        /// Sets the collections and their objects as drafts. Drafts are not public to the
+        /// network.
+        #[structopt(long)]
+        draft: bool,
+        /// Whether to announce the new editions to he network or to keep quiet.
+        #[structopt(long)]
+        no_announce: bool,
+    },
+}
+
+impl MailCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            MailCommand::Ingest {
+                series_name,
+                maildir,
+                title,
+                every,
+                ttl,
+                draft,
+                no_announce,
+            } => {
+                commands::mail::ingest(series_name, maildir, title, every, ttl, draft, no_announce)
+                    .await
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum IpfsCommand {
+    /// Imports a file from IPFS by its CID, through an IPFS gateway, as an object. The content
+    /// is checked against the CID when it was added to IPFS with the default settings.
+    Import {
+        /// The CID of the file, either a CIDv0 or a base32 CIDv1.
+        cid: String,
+        /// The IPFS gateway from which to download the file.
+        #[structopt(long, default_value = "https://ipfs.io")]
+        gateway: String,
+        /// The content-type of this file. Will be taken from the gateway if unspecified.
+        #[structopt(long)]
+        content_type: Option<String>,
+        /// Don't bookmark this object. This makes is eligible for automatic deletion.
+        #[structopt(long)]
+        no_bookmark: bool,
+        /// Sets this object as drafts. Drafts are not public to the network.
+        #[structopt(long)]
+        draft: bool,
+        /// A tab-separated file of CIDs, object hashes and paths. CIDs already in this file are
+        /// not imported again and new imports are appended to it.
+        #[structopt(long)]
+        mapping: Option<PathBuf>,
+    },
+    /// Exports a collection as a CAR file, which can be imported into IPFS with
+    /// `ipfs dag import`. Files are laid out as IPFS does it by default for CIDv1, so the CIDs
+    /// are the same as if they were added to IPFS directly.
+    Export {
+        /// The hash of the collection.
+        collection: String,
+        /// The CAR file to be written.
+        out: PathBuf,
+        /// A tab-separated file to which the CIDs, object hashes and paths of the exported files
+        /// will be appended.
+        #[structopt(long)]
+        mapping: Option<PathBuf>,
+    },
+}
+
+impl IpfsCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            IpfsCommand::Import {
+                cid,
+                gateway,
+                content_type,
+                no_bookmark,
+                draft,
+                mapping,
+            } => {
+                commands::ipfs::import(cid, gateway, content_type, !no_bookmark, draft, mapping)
+                    .await
+            }
+            IpfsCommand::Export {
+                collection,
+                out,
+                mapping,
+            } => commands::ipfs::export(collection, out, mapping).await,
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum TorrentCommand {
+    /// Writes a `.torrent` file for an object in the local node. The torrent advertises the web
+    /// seeds configured in the node, if any.
+    Export {
+        /// The hash of the object.
+        hash: String,
+        /// The name of the file in the torrent. Defaults to the object hash.
+        #[structopt(long)]
+        name: Option<String>,
+        /// The file to be written. Defaults to `<hash>.torrent`.
+        #[structopt(long, short)]
+        out: Option<PathBuf>,
+    },
+    /// Imports the file of a single-file torrent as an object. The file is checked against the
+    /// torrent before being imported.
+    Import {
+        /// The `.torrent` file.
+        torrent: PathBuf,
+        /// The file, already downloaded by a BitTorrent client. If not given, the file is
+        /// downloaded from the web seeds of the torrent.
+        #[structopt(long)]
+        data: Option<PathBuf>,
+        /// The content-type of this file. Will be guessed from the name in the torrent if
+        /// unspecified.
+        #[structopt(long)]
+        content_type: Option<String>,
+        /// Don't bookmark this object. This makes is eligible for automatic deletion.
+        #[structopt(long)]
+        no_bookmark: bool,
+        /// Sets this object as drafts. Drafts are not public to the network.
+        #[structopt(long)]
+        draft: bool,
+    },
+}
+
+impl TorrentCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            TorrentCommand::Export { hash, name, out } => {
+                commands::torrent::export(hash, name, out).await
+            }
+            TorrentCommand::Import {
+                torrent,
+                data,
+                content_type,
+                no_bookmark,
+                draft,
+            } => commands::torrent::import(torrent, data, content_type, !no_bookmark, draft).await,
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum ObjectCommand {
+    /// Removes objects from the local database.
+    Rm {
+        /// The hashes of the objects to be removed.
+        hashes: Vec<String>,
+        /// Reads more hashes from a file, one per line. Use `-` to read from the standard input.
+        #[structopt(long)]
+        from_file: Option<PathBuf>,
+    },
+    /// Bookmarks objects, preventing them from being automatically removed.
+    Bookmark {
+        /// The hashes of the objects to be bookmarked.
+        hashes: Vec<String>,
+        /// Reads more hashes from a file, one per line. Use `-` to read from the standard input.
+        #[structopt(long)]
+        from_file: Option<PathBuf>,
+        /// Removes the bookmarks instead, making the objects eligible for automatic deletion.
+        #[structopt(long)]
+        remove: bool,
+    },
+    /// Makes the node download a URL and store it as an object.
+    Fetch {
+        /// The URL to be downloaded.
+        url: String,
+        /// The content-type of the object. The one sent by the server is used if unspecified.
+        #[structopt(long)]
+        content_type: Option<String>,
+        /// Don't bookmark this object. This makes is eligible for automatic deletion.
+        #[structopt(long)]
+        no_bookmark: bool,
+        /// Sets this object as drafts. Drafts are not public to the network.
+        #[structopt(long)]
+        draft: bool,
+    },
+    /// Estimates how many peers in the network have an object, without downloading it.
+    Availability {
+        /// The hash of the object.
+        hash: String,
+    },
+    /// Writes the content of an object in the node to a file.
+    Export {
+        /// The hash of the object.
+        hash: String,
+        /// The file to which the content will be written. Defaults to the standard output.
+        file: Option<PathBuf>,
+    },
+}
+
+impl ObjectCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            ObjectCommand::Rm { hashes, from_file } => {
+                commands::object::rm(hashes, from_file).await
+            }
+            ObjectCommand::Bookmark {
+                hashes,
+                from_file,
+                remove,
+            } => commands::object::bookmark(hashes, from_file, remove).await,
+            ObjectCommand::Fetch {
+                url,
+                content_type,
+                no_bookmark,
+                draft,
+            } => commands::object::fetch(url, content_type, !no_bookmark, draft).await,
+            ObjectCommand::Availability { hash } => commands::object::availability(hash).await,
+            ObjectCommand::Export { hash, file } => commands::object::export(hash, file).await,
+        }
+    }
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum CollectionCommand {
     /// Shows details on a particular collection.
@@ -189,6 +736,34 @@ pub enum SeriesCommand {
     Ls { series_owner_name: Option<String> },
     /// Lists all known public keys the node has seen, be they locally owned or not.
     LsCached { series_name: Option<String> },
+    /// Shows how many times each item of each edition of a locally owned series was served,
+    /// by this node and by the mirrors reporting back to it.
+    Readership { series_owner_name: String },
+    /// Publishes a locally owned series as a reply to an item, e.g., as comments on a page.
+    Reply {
+        series_owner_name: String,
+        /// The locator hash of the item being replied to.
+        locator: String,
+    },
+    /// Finds the series replying to an item in the network.
+    Replies {
+        /// The locator hash of the item.
+        locator: String,
+    },
+    /// Sets how much a series is trusted: `verified` if its key was obtained out-of-band,
+    /// `tofu` if trusted on first use or `unverified`. Readers are shown this level.
+    Trust {
+        public_key: String,
+        #[structopt(possible_values = &["verified", "tofu", "unverified"])]
+        level: String,
+        /// How the key was obtained, for your own reference.
+        #[structopt(long)]
+        note: Option<String>,
+    },
+    /// Forgets the trust level set for a series, going back to the default one.
+    Untrust { public_key: String },
+    /// Lists the trust levels set for series.
+    LsTrust,
 }
 
 impl SeriesCommand {
@@ -212,6 +787,21 @@ impl SeriesCommand {
             SeriesCommand::LsCached { series_name } => {
                 commands::series::ls_cached(series_name).await
             }
+            SeriesCommand::Readership { series_owner_name } => {
+                commands::series::readership(series_owner_name).await
+            }
+            SeriesCommand::Reply {
+                series_owner_name,
+                locator,
+            } => commands::series::reply(series_owner_name, locator).await,
+            SeriesCommand::Replies { locator } => commands::series::replies(locator).await,
+            SeriesCommand::Trust {
+                public_key,
+                level,
+                note,
+            } => commands::series::trust(public_key, level, note).await,
+            SeriesCommand::Untrust { public_key } => commands::series::untrust(public_key).await,
+            SeriesCommand::LsTrust => commands::series::ls_trust().await,
         }
     }
 }
@@ -220,12 +810,24 @@ impl SeriesCommand {
 pub enum EditionCommand {
     /// Lists all known editions or all known editions for a given series public key, if supplied.
     Ls { series_key: Option<String> },
+    /// Shows which items were added, removed or changed between the collections of two
+    /// editions.
+    Diff {
+        /// The collection hash of the older edition.
+        old_collection: String,
+        /// The collection hash of the newer edition.
+        new_collection: String,
+    },
 }
 
 impl EditionCommand {
     async fn execute(self) -> Result<(), anyhow::Error> {
         match self {
             EditionCommand::Ls { series_key } => commands::edition::ls(series_key).await,
+            EditionCommand::Diff {
+                old_collection,
+                new_collection,
+            } => commands::edition::diff(old_collection, new_collection).await,
         }
     }
 }
@@ -234,7 +836,13 @@ impl EditionCommand {
 pub enum SubscriptionCommand {
     /// Subscribe to a series. This tells the node to listen to announcements
     /// and to _actively_ keep in sync with the series.
-    New { public_key: String },
+    New {
+        public_key: String,
+        /// Only keep track of new editions, downloading items when they are first requested.
+        /// Good for devices short on disk following many large series.
+        #[structopt(long)]
+        lazy: bool,
+    },
     /// Removes an existing subscription.
     Rm { public_key: String },
     // /// Shows details on a particular subscription.
@@ -246,8 +854,8 @@ pub enum SubscriptionCommand {
 impl SubscriptionCommand {
     async fn execute(self) -> Result<(), anyhow::Error> {
         match self {
-            SubscriptionCommand::New { public_key } => {
-                commands::subscription::new(public_key).await
+            SubscriptionCommand::New { public_key, lazy } => {
+                commands::subscription::new(public_key, lazy).await
             }
             SubscriptionCommand::Rm { public_key } => commands::subscription::rm(public_key).await,
             // SubscriptionCommand::Show { public_key } => todo!(),
@@ -256,6 +864,174 @@ impl SubscriptionCommand {
     }
 }
 
+#[derive(Clone, Debug, StructOpt)]
+pub enum HubDirectoryCommand {
+    /// Subscribes to a hub directory. Its hubs are only connected to once the directory is
+    /// trusted.
+    New { public_key: String },
+    /// Trusts a hub directory, connecting to all hubs it lists, now and in the future. Shows
+    /// the hubs currently listed and asks for confirmation.
+    Trust {
+        public_key: String,
+        /// Do not ask for confirmation.
+        #[structopt(long, short)]
+        yes: bool,
+    },
+    /// Stops trusting a hub directory, disconnecting from the hubs only it lists.
+    Distrust { public_key: String },
+    /// Unsubscribes from a hub directory.
+    Rm { public_key: String },
+    /// Lists all hub directories or, if a public key is given, the hubs of a directory.
+    Ls { public_key: Option<String> },
+}
+
+impl HubDirectoryCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            HubDirectoryCommand::New { public_key } => {
+                commands::hub_directory::new(public_key).await
+            }
+            HubDirectoryCommand::Trust { public_key, yes } => {
+                commands::hub_directory::trust(public_key, yes).await
+            }
+            HubDirectoryCommand::Distrust { public_key } => {
+                commands::hub_directory::distrust(public_key).await
+            }
+            HubDirectoryCommand::Rm { public_key } => commands::hub_directory::rm(public_key).await,
+            HubDirectoryCommand::Ls { public_key } => commands::hub_directory::ls(public_key).await,
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum HubRouteCommand {
+    /// Routes all traffic concerning a series only through the given hubs, replacing any
+    /// existing route.
+    Set {
+        /// The public key of the series.
+        series: String,
+        /// The names of the hubs, as given in `--hubs` to the node.
+        #[structopt(required = true)]
+        hubs: Vec<String>,
+    },
+    /// Lets a series go through all hubs again.
+    Rm {
+        /// The public key of the series.
+        series: String,
+    },
+    /// Lists all hub routes.
+    Ls,
+}
+
+impl HubRouteCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            HubRouteCommand::Set { series, hubs } => commands::hub_route::set(series, hubs).await,
+            HubRouteCommand::Rm { series } => commands::hub_route::rm(series).await,
+            HubRouteCommand::Ls => commands::hub_route::ls().await,
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum HubCommand {
+    /// Lists the keys pinned for all hubs ever connected to, with the new keys presented by
+    /// hubs whose keys changed.
+    Keys,
+    /// Accepts the new key presented by a hub whose key changed. Only do this if you know why
+    /// the key changed (e.g., the hub lost its data); otherwise, someone may be passing for
+    /// the hub.
+    AcceptKey {
+        /// The name of the hub, as given in `--hubs` to the node.
+        name: String,
+    },
+    /// Forgets the key pinned for a hub, so that the next key it presents is pinned. This is
+    /// for hubs that cannot keep their key (e.g., hubs that lose their data on every restart).
+    /// The same warning as for `accept-key` applies.
+    ForgetKey {
+        /// The name of the hub, as given in `--hubs` to the node.
+        name: String,
+    },
+}
+
+impl HubCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            HubCommand::Keys => commands::hub::keys().await,
+            HubCommand::AcceptKey { name } => commands::hub::accept_key(name).await,
+            HubCommand::ForgetKey { name } => commands::hub::forget_key(name).await,
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum ConnectionCommand {
+    /// Shows how many bytes were sent to and received from each hub and from all peers, largest
+    /// first.
+    Usage {
+        /// The number of days to show, today included.
+        #[structopt(long, default_value = "30")]
+        days: u32,
+        /// Shows each day separately, instead of the totals for the whole period.
+        #[structopt(long)]
+        daily: bool,
+    },
+}
+
+impl ConnectionCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            ConnectionCommand::Usage { days, daily } => {
+                commands::connection::usage(days, daily).await
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum KvstoreCommand {
+    /// Lists the applications using the key-value store and how much each one uses.
+    Ls,
+    /// Removes everything an application keeps in the key-value store.
+    Rm {
+        /// The application, e.g., `/_series/<public key>`.
+        entity: String,
+    },
+}
+
+impl KvstoreCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            KvstoreCommand::Ls => commands::kvstore::ls().await,
+            KvstoreCommand::Rm { entity } => commands::kvstore::rm(entity).await,
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum MirrorCommand {
+    /// Mirrors a series. All current and future editions of the series are downloaded,
+    /// bookmarked and served to the network.
+    Add {
+        /// The public key of the series.
+        series: String,
+    },
+    /// Shows how complete each edition of the mirrored series is in the local node.
+    Status {
+        /// The public key of the series. If not given, shows all mirrored series.
+        series: Option<String>,
+    },
+}
+
+impl MirrorCommand {
+    async fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            MirrorCommand::Add { series } => commands::mirror::add(series).await,
+            MirrorCommand::Status { series } => commands::mirror::status(series).await,
+        }
+    }
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum IdentityCommand {
     /// Creates a new identity, putting the work to create a proof-of-work for it.
@@ -307,6 +1083,48 @@ impl IdentityCommand {
     }
 }
 
+#[derive(Clone, Debug, StructOpt)]
+pub enum ProfileCommand {
+    /// Lists all profiles. Profiles are created by running the node with `--profile`.
+    List,
+    /// Sets the profile used by the next commands. Use `default` for the default profile.
+    Switch { name: String },
+}
+
+impl ProfileCommand {
+    /// Runs the command. Profiles are managed without talking to the node.
+    pub fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            ProfileCommand::List => commands::profile::list(),
+            ProfileCommand::Switch { name } => commands::profile::switch(name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum NodeCommand {
+    /// Moves the data folder of a stopped node to a new folder, e.g., one to be copied to
+    /// another machine. The database is rebuilt entry by entry, so that it opens under another
+    /// OS or architecture, and a new access token is generated when the node starts.
+    Migrate {
+        /// The data folder of the node.
+        #[structopt(long)]
+        from: PathBuf,
+        /// The new data folder, which must be empty or not exist yet.
+        #[structopt(long)]
+        to: PathBuf,
+    },
+}
+
+impl NodeCommand {
+    /// Runs the command. These work on the files of the node, which must not be running.
+    pub fn execute(self) -> Result<(), anyhow::Error> {
+        match self {
+            NodeCommand::Migrate { from, to } => commands::node::migrate(from, to),
+        }
+    }
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum AuthCommand {
     Grant {
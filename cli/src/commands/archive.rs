@@ -0,0 +1,114 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+use crate::api;
+use crate::html::proxy_html;
+use crate::warc;
+
+/// The names under which a captured URL path is found in the collection.
+fn names_for(path: &str) -> Vec<String> {
+    if path.is_empty() || path.ends_with('/') {
+        vec![
+            format!("{path}index.html"),
+            path.trim_end_matches('/').to_owned(),
+        ]
+    } else {
+        vec![path.to_owned()]
+    }
+}
+
+pub async fn import(file: PathBuf, is_draft: bool) -> Result<(), anyhow::Error> {
+    let (sender, mut receiver) = mpsc::channel(16);
+    let reader = tokio::task::spawn_blocking(move || warc::read_records(&file, sender));
+
+    // Later captures of the same URL replace the earlier ones:
+    let mut captured = BTreeMap::new();
+
+    while let Some(record) = receiver.recv().await {
+        let capture = match record.capture() {
+            Ok(Some(capture)) => capture,
+            Ok(None) => continue,
+            Err(err) => {
+                println!("WARNING: skipping record: {err}");
+                continue;
+            }
+        };
+
+        let url = match reqwest::Url::parse(&capture.uri) {
+            Ok(url) => url,
+            Err(err) => {
+                println!("WARNING: skipping capture of {}: {err}", capture.uri);
+                continue;
+            }
+        };
+
+        let content_type = capture.content_type.unwrap_or_else(|| {
+            mime_guess::from_path(url.path())
+                .first_or_octet_stream()
+                .to_string()
+        });
+        let content = if content_type.starts_with("text/html") {
+            proxy_html(&capture.body)
+        } else {
+            capture.body
+        };
+
+        log::info!("Creating object for {url}");
+        let hash = api::post_object(content, &content_type, true, is_draft).await?;
+        captured.insert(
+            (
+                url.host_str().unwrap_or_default().to_owned(),
+                url.path().trim_start_matches('/').to_owned(),
+            ),
+            hash,
+        );
+    }
+
+    reader.await??;
+
+    if captured.is_empty() {
+        anyhow::bail!("no successful captures found in archive");
+    }
+
+    // Paths are only prefixed by the host when the archive spans many hosts:
+    let hosts = captured
+        .keys()
+        .map(|(host, _)| host.as_str())
+        .collect::<BTreeSet<_>>();
+    let is_multihost = hosts.len() > 1;
+
+    let hashes = captured
+        .iter()
+        .flat_map(|((host, path), hash)| {
+            let path = if is_multihost {
+                format!("{host}/{path}")
+            } else {
+                path.clone()
+            };
+            names_for(&path)
+                .into_iter()
+                .map(move |name| (name, hash.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    let collection = api::post_collection(api::PostCollectionRequest {
+        hashes: &hashes,
+        is_draft,
+        metadata: &BTreeMap::new(),
+        content_warnings: &[],
+        inventory_shard_size: None,
+        prefetch: &[],
+        mounts: &BTreeMap::new(),
+    })
+    .await?;
+
+    println!(
+        "Imported {} captures from {} hosts",
+        captured.len(),
+        hosts.len()
+    );
+    println!("Collection hash: {collection}");
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use std::collections::BTreeMap;
+use tabled::Tabled;
+
+use crate::api;
+
+use super::show_table;
+
+pub async fn usage(days: u32, daily: bool) -> Result<(), anyhow::Error> {
+    #[derive(Tabled)]
+    struct Row {
+        day: String,
+        counterpart: String,
+        addr: String,
+        sent: u64,
+        received: u64,
+    }
+
+    let mut rows = BTreeMap::<_, Row>::new();
+
+    for usage in api::get_connection_usage(days).await? {
+        let day = if daily {
+            usage.day
+        } else {
+            format!("last {days} days")
+        };
+        let addr = usage.addr.unwrap_or_else(|| "(all peers)".to_owned());
+        let row = rows
+            .entry((day.clone(), usage.counterpart.clone(), addr.clone()))
+            .or_insert_with(|| Row {
+                day,
+                counterpart: usage.counterpart,
+                addr,
+                sent: 0,
+                received: 0,
+            });
+        row.sent += usage.sent;
+        row.received += usage.received;
+    }
+
+    let mut rows = rows.into_values().collect::<Vec<_>>();
+    rows.sort_by(|a, b| {
+        a.day
+            .cmp(&b.day)
+            .then((b.sent + b.received).cmp(&(a.sent + a.received)))
+    });
+
+    show_table(rows);
+
+    Ok(())
+}
@@ -45,3 +45,59 @@ pub async fn ls(series_key: Option<String>) -> Result<(), anyhow::Error> {
         ls_all().await
     }
 }
+
+pub async fn diff(old_collection: String, new_collection: String) -> Result<(), anyhow::Error> {
+    let response = api::get_collection_diff(&old_collection, &new_collection).await?;
+
+    #[derive(Tabled)]
+    struct Row {
+        change: &'static str,
+        path: String,
+        old_hash: String,
+        old_size: String,
+        new_hash: String,
+        new_size: String,
+    }
+
+    fn show_size(size: Option<usize>) -> String {
+        size.map(|size| size.to_string())
+            .unwrap_or_else(|| "?".to_owned())
+    }
+
+    let mut rows = Vec::new();
+
+    rows.extend(response.added.into_iter().map(|entry| Row {
+        change: "added",
+        path: entry.path,
+        old_hash: String::new(),
+        old_size: String::new(),
+        new_hash: entry.hash,
+        new_size: show_size(entry.size),
+    }));
+    rows.extend(response.removed.into_iter().map(|entry| Row {
+        change: "removed",
+        path: entry.path,
+        old_hash: entry.hash,
+        old_size: show_size(entry.size),
+        new_hash: String::new(),
+        new_size: String::new(),
+    }));
+    rows.extend(response.changed.into_iter().map(|entry| Row {
+        change: "changed",
+        path: entry.path,
+        old_hash: entry.old_hash,
+        old_size: show_size(entry.old_size),
+        new_hash: entry.new_hash,
+        new_size: show_size(entry.new_size),
+    }));
+
+    rows.sort_by(|a, b| a.path.cmp(&b.path));
+
+    if rows.is_empty() {
+        println!("No changes between {old_collection} and {new_collection}");
+    } else {
+        show_table(rows);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use anyhow::Context;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use samizdat_common::Hash;
+
+use crate::api;
+
+/// Whether an item path can be safely written under the export directory.
+fn is_safe(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+pub async fn export(
+    series: String,
+    dir: PathBuf,
+    collection: Option<String>,
+) -> Result<(), anyhow::Error> {
+    // Find all editions of the series, latest first:
+    let mut editions = api::get_all_editions()
+        .await?
+        .into_iter()
+        .filter(|edition| edition.public_key.to_string() == series)
+        .collect::<Vec<_>>();
+    editions.sort_by_key(|edition| Reverse(edition.signed.timestamp));
+
+    // Skip editions newer than the chosen one:
+    if let Some(collection) = &collection {
+        let position = editions
+            .iter()
+            .position(|edition| edition.signed.collection.hash.to_string() == *collection)
+            .ok_or_else(|| {
+                anyhow::anyhow!("collection {collection} is not a known edition of {series}")
+            })?;
+        editions.drain(..position);
+    }
+
+    if editions.is_empty() {
+        anyhow::bail!(
+            "no editions of {series} known to the local node. Hint: subscribe to the series \
+            or visit it first."
+        );
+    }
+
+    // Layer the inventories: newer editions take precedence over the older ones.
+    let mut layered: BTreeMap<String, (String, Hash)> = BTreeMap::new();
+
+    for edition in &editions {
+        let collection = edition.signed.collection.hash.to_string();
+        let inventory = match api::get_collection_inventory(&collection).await? {
+            Some(inventory) => inventory,
+            None => {
+                println!("WARNING: inventory for edition {collection} not found. Skipping");
+                continue;
+            }
+        };
+
+        for (path, hash) in inventory.inventory {
+            layered
+                .entry(path)
+                .or_insert_with(|| (collection.clone(), hash));
+        }
+    }
+
+    // Item paths such as `dir` are aliases to `dir/index.html`. They cannot be written as
+    // files if there is a directory with the same name.
+    let is_alias = |path: &str| {
+        let prefix = format!("{path}/");
+        layered
+            .range(prefix.clone()..)
+            .next()
+            .map(|(next, _)| next.starts_with(&prefix))
+            .unwrap_or(false)
+    };
+
+    let mut exported = 0;
+
+    for (path, (collection, hash)) in &layered {
+        if !is_safe(path) {
+            println!("WARNING: refusing to write unsafe path {path:?}");
+            continue;
+        }
+
+        if is_alias(path) {
+            continue;
+        }
+
+        let content = api::get_collection_item(collection, path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("item {collection}/{path} ({hash}) not found"))?;
+
+        let target = dir.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create directory {parent:?}"))?;
+        }
+        fs::write(&target, content).with_context(|| format!("failed to write {target:?}"))?;
+
+        exported += 1;
+    }
+
+    println!("Exported {exported} items to {dir:?}");
+
+    Ok(())
+}
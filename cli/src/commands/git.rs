@@ -0,0 +1,348 @@
+use anyhow::Context;
+use futures::prelude::*;
+use futures::stream;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tabled::Tabled;
+use tokio::time::Instant;
+
+use crate::api;
+
+use super::show_table;
+
+/// Runs a git command in a repository, returning its standard output.
+fn git(repo: &Path, args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .context("failed to run `git`. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(output.stdout)
+}
+
+/// Runs a git command in a repository, returning its standard output as text.
+fn git_text(repo: &Path, args: &[&str]) -> Result<String, anyhow::Error> {
+    Ok(String::from_utf8(git(repo, args)?)?)
+}
+
+/// A file in the tree of a commit.
+struct TreeEntry {
+    /// The git hash of the blob.
+    blob: String,
+    path: String,
+}
+
+impl TreeEntry {
+    /// The object of this file: its blob together with the content type guessed from its path.
+    /// The content type is part of the object, so the same blob may become many objects.
+    fn object(&self) -> (&str, String) {
+        let content_type = mime_guess::from_path(&self.path)
+            .first_or_octet_stream()
+            .to_string();
+        (&self.blob, content_type)
+    }
+}
+
+/// Lists all files in the tree of a commit. Submodules are not part of the tree.
+fn ls_tree(repo: &Path, commit: &str) -> Result<Vec<TreeEntry>, anyhow::Error> {
+    let listing = git(repo, &["ls-tree", "-r", "-z", "--full-tree", commit])?;
+
+    listing
+        .split(|&byte| byte == 0)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let line = String::from_utf8_lossy(line);
+            let (info, path) = line
+                .split_once('\t')
+                .with_context(|| format!("bad `git ls-tree` line: {line:?}"))?;
+            let mut info = info.split_whitespace().skip(1);
+            Ok(match (info.next(), info.next()) {
+                (Some("blob"), Some(blob)) => Some(TreeEntry {
+                    blob: blob.to_owned(),
+                    path: path.to_owned(),
+                }),
+                _ => None,
+            })
+        })
+        .filter_map(Result::transpose)
+        .collect()
+}
+
+/// A reference (branch or tag) to be published.
+struct Reference {
+    name: String,
+    commit: String,
+}
+
+/// Finds the references to be published: the given ones or else all local branches and tags.
+fn references(repo: &Path, refs: &[String]) -> Result<Vec<Reference>, anyhow::Error> {
+    let names = if refs.is_empty() {
+        git_text(
+            repo,
+            &[
+                "for-each-ref",
+                "--format=%(refname)",
+                "refs/heads",
+                "refs/tags",
+            ],
+        )?
+        .lines()
+        .map(str::to_owned)
+        .collect::<Vec<_>>()
+    } else {
+        refs.iter()
+            .map(|name| git_text(repo, &["rev-parse", "--symbolic-full-name", name]))
+            .map(|full| full.map(|full| full.trim().to_owned()))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    names
+        .into_iter()
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let commit = git_text(repo, &["rev-parse", &format!("{name}^{{commit}}")])?
+                .trim()
+                .to_owned();
+            Ok(Reference { name, commit })
+        })
+        .collect()
+}
+
+/// Editions are identified by their timestamps, to the second, so they must be posted at least
+/// this long after the previous one returns.
+const EDITION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Lists the commits reachable from the given ones and not from `since`, parents first.
+fn history(
+    repo: &Path,
+    tips: &BTreeSet<String>,
+    since: Option<&str>,
+) -> Result<Vec<String>, anyhow::Error> {
+    let since = since.map(|since| format!("^{since}"));
+    let args = ["rev-list", "--reverse", "--topo-order"]
+        .into_iter()
+        .chain(tips.iter().map(String::as_str))
+        .chain(since.as_deref())
+        .collect::<Vec<_>>();
+
+    Ok(git_text(repo, &args)?.lines().map(str::to_owned).collect())
+}
+
+/// Posts the collection of a commit: the files in its tree and the raw commit object at
+/// `.git/commit`. Git trees cannot have `.git` entries, so this never collides with a file.
+/// Blobs already in `objects` are not uploaded again. Returns the items and the collection.
+async fn publish_commit(
+    repo: &Path,
+    commit: &str,
+    objects: &mut BTreeMap<(String, String), String>,
+    is_draft: bool,
+) -> Result<(Vec<(String, String)>, String), anyhow::Error> {
+    let tree = ls_tree(repo, commit)?;
+    let files = tree
+        .iter()
+        .map(|entry| {
+            let (blob, content_type) = entry.object();
+            (blob.to_owned(), content_type)
+        })
+        .filter(|file| !objects.contains_key(file))
+        .collect::<BTreeSet<_>>();
+    let new_objects = stream::iter(files)
+        .map(|(blob, content_type)| async move {
+            log::info!("Creating object for blob {blob} as {content_type}");
+            let content = git(repo, &["cat-file", "blob", &blob])?;
+            let hash = api::post_object(content, &content_type, true, is_draft).await?;
+            Ok(((blob, content_type), hash)) as Result<_, anyhow::Error>
+        })
+        .buffer_unordered(num_cpus::get())
+        .try_collect::<Vec<_>>()
+        .await?;
+    objects.extend(new_objects);
+
+    let raw = git(repo, &["cat-file", "commit", commit])?;
+    let commit_object = api::post_object(raw, "text/plain", true, is_draft).await?;
+    let items = tree
+        .iter()
+        .map(|entry| {
+            let (blob, content_type) = entry.object();
+            (
+                entry.path.clone(),
+                objects[&(blob.to_owned(), content_type)].clone(),
+            )
+        })
+        .chain([(".git/commit".to_owned(), commit_object)])
+        .collect::<Vec<_>>();
+
+    let collection = api::post_collection(api::PostCollectionRequest {
+        hashes: &items,
+        is_draft,
+        metadata: &BTreeMap::new(),
+        content_warnings: &[],
+        inventory_shard_size: None,
+        prefetch: &[],
+        mounts: &BTreeMap::new(),
+    })
+    .await?;
+
+    Ok((items, collection))
+}
+
+#[derive(Tabled)]
+struct PublishedRow {
+    reference: String,
+    commit: String,
+    collection: String,
+}
+
+/// Publishes the history of the branches and tags of a git repository to a series, one
+/// edition per commit, parents first. Every commit becomes a collection of the files in its
+/// tree, with the raw commit object at `.git/commit`, so that the history can be verified
+/// against the git hashes. A last edition holds the checked out commit at the root, every
+/// reference under `.git/` and its name (e.g., `.git/refs/heads/main/README.md`) and a
+/// `.git/show-ref` listing in the format of `git show-ref`.
+pub async fn publish(
+    repo: PathBuf,
+    series_name: String,
+    refs: Vec<String>,
+    since: Option<String>,
+    ttl: Option<String>,
+    is_draft: bool,
+    no_announce: bool,
+) -> Result<(), anyhow::Error> {
+    let references = references(&repo, &refs)?;
+
+    if references.is_empty() {
+        anyhow::bail!("no branches or tags to publish in {repo:?}");
+    }
+
+    let head = git_text(
+        &repo,
+        &["rev-parse", "--verify", "--quiet", "HEAD^{commit}"],
+    )
+    .ok()
+    .map(|head| head.trim().to_owned());
+
+    let tips = references
+        .iter()
+        .map(|reference| reference.commit.clone())
+        .chain(head.clone())
+        .collect::<BTreeSet<_>>();
+    let history = history(&repo, &tips, since.as_deref())?;
+
+    // Upload each object only once, however many trees it appears in, and post one edition
+    // per commit. Only the items of the references are kept, for the last edition:
+    let mut objects = BTreeMap::new();
+    let mut collections = BTreeMap::new();
+    let mut tip_items = BTreeMap::new();
+    let mut last_edition = None;
+    for (i, commit) in history.iter().enumerate() {
+        log::info!(
+            "Publishing commit {commit} ({} of {})",
+            i + 1,
+            history.len()
+        );
+
+        let (items, collection) = publish_commit(&repo, commit, &mut objects, is_draft).await?;
+
+        if let Some(last_edition) = last_edition {
+            tokio::time::sleep_until(last_edition + EDITION_INTERVAL).await;
+        }
+
+        api::post_edition(
+            &series_name,
+            api::PostEditionRequest {
+                collection: &collection,
+                ttl: ttl.as_deref(),
+                no_announce: true,
+                release_at: None,
+            },
+        )
+        .await?;
+        last_edition = Some(Instant::now());
+
+        if tips.contains(commit) {
+            tip_items.insert(commit.clone(), items);
+        }
+
+        collections.insert(commit.clone(), collection);
+    }
+
+    // References published before still go in the last edition:
+    for tip in &tips {
+        if !tip_items.contains_key(tip) {
+            let (items, collection) = publish_commit(&repo, tip, &mut objects, is_draft).await?;
+            tip_items.insert(tip.clone(), items);
+            collections.insert(tip.clone(), collection);
+        }
+    }
+
+    // And the last edition, with everything:
+    let mut hashes = Vec::new();
+    let mut listing = String::new();
+
+    if let Some(head) = &head {
+        hashes.extend(tip_items[head].iter().cloned());
+    }
+
+    for reference in &references {
+        hashes.extend(
+            tip_items[&reference.commit]
+                .iter()
+                .map(|(path, hash)| (format!(".git/{}/{path}", reference.name), hash.clone())),
+        );
+
+        listing += &format!("{} {}\n", reference.commit, reference.name);
+    }
+
+    let listing = api::post_object(listing.into_bytes(), "text/plain", true, is_draft).await?;
+    hashes.push((".git/show-ref".to_owned(), listing));
+
+    let collection = api::post_collection(api::PostCollectionRequest {
+        hashes: &hashes,
+        is_draft,
+        metadata: &BTreeMap::new(),
+        content_warnings: &[],
+        inventory_shard_size: None,
+        prefetch: &[],
+        mounts: &BTreeMap::new(),
+    })
+    .await?;
+
+    if let Some(last_edition) = last_edition {
+        tokio::time::sleep_until(last_edition + EDITION_INTERVAL).await;
+    }
+
+    let edition = api::post_edition(
+        &series_name,
+        api::PostEditionRequest {
+            collection: &collection,
+            ttl: ttl.as_deref(),
+            no_announce,
+            release_at: None,
+        },
+    )
+    .await?;
+
+    show_table(references.iter().map(|reference| PublishedRow {
+        reference: reference.name.clone(),
+        commit: reference.commit.clone(),
+        collection: collections[&reference.commit].clone(),
+    }));
+
+    println!("Commits published: {}", history.len());
+    println!("Edition collection: {collection}");
+    println!("Edition timestamp: {}", edition.signed.timestamp);
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use tabled::Tabled;
+
+use crate::api;
+
+use super::show_table;
+
+pub async fn keys() -> Result<(), anyhow::Error> {
+    #[derive(Tabled)]
+    struct Row {
+        hub: String,
+        pinned: String,
+        pinned_at: String,
+        changed_to: String,
+    }
+
+    show_table(api::get_all_hub_keys().await?.into_iter().map(|key| Row {
+        hub: key.hub,
+        pinned: key.pinned,
+        pinned_at: key.pinned_at,
+        changed_to: match (key.presented, key.presented_at) {
+            (Some(presented), Some(presented_at)) => format!("{presented} (at {presented_at})"),
+            _ => "-".to_owned(),
+        },
+    }));
+
+    Ok(())
+}
+
+pub async fn accept_key(name: String) -> Result<(), anyhow::Error> {
+    let key = api::post_hub_key_accept(&name).await?;
+    println!("Key of hub {name} is now {}.", key.pinned);
+
+    Ok(())
+}
+
+pub async fn forget_key(name: String) -> Result<(), anyhow::Error> {
+    let forgotten = api::delete_hub_key(&name).await?;
+
+    if !forgotten {
+        println!("NOTE: no key was pinned for hub {name}.");
+    }
+
+    Ok(())
+}
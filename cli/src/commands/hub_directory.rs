@@ -0,0 +1,132 @@
+use std::io::{self, Write};
+use tabled::Tabled;
+
+use crate::api;
+
+use super::show_table;
+
+pub async fn new(public_key: String) -> Result<(), anyhow::Error> {
+    api::post_hub_directory(api::PostHubDirectoryRequest {
+        public_key: &public_key,
+    })
+    .await?;
+
+    println!(
+        "NOTE: hubs from this directory will only be used after you run \
+        `samizdat hub-directory trust {public_key}`."
+    );
+
+    Ok(())
+}
+
+fn show_hubs(directory: &api::GetHubDirectoryResponse) {
+    #[derive(Tabled)]
+    struct Row<'a> {
+        address: &'a str,
+        key: &'a str,
+        policies: String,
+    }
+
+    show_table(directory.hubs.iter().map(|hub| {
+        Row {
+            address: &hub.address,
+            key: hub.key.as_deref().unwrap_or("-"),
+            policies: hub
+                .policies
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }));
+}
+
+pub async fn trust(public_key: String, yes: bool) -> Result<(), anyhow::Error> {
+    let directory = api::get_hub_directory(&public_key)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("not subscribed to hub directory {public_key}"))?;
+
+    if !yes {
+        if directory.updated_at.is_none() {
+            println!("NOTE: this directory was not fetched yet. No hubs are known so far.");
+        } else {
+            println!("This directory currently lists the following hubs:\n");
+            show_hubs(&directory);
+        }
+
+        print!("\nConnect to all hubs listed by {public_key}, now and in the future? [y/N] ");
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    api::patch_hub_directory(
+        &public_key,
+        api::PatchHubDirectoryRequest { is_trusted: true },
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn distrust(public_key: String) -> Result<(), anyhow::Error> {
+    api::patch_hub_directory(
+        &public_key,
+        api::PatchHubDirectoryRequest { is_trusted: false },
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn rm(public_key: String) -> Result<(), anyhow::Error> {
+    let removed = api::delete_hub_directory(&public_key).await?;
+
+    if !removed {
+        println!("NOTE: hub directory {public_key} does not exist.");
+    }
+
+    Ok(())
+}
+
+pub async fn ls(public_key: Option<String>) -> Result<(), anyhow::Error> {
+    if let Some(public_key) = public_key {
+        let directory = api::get_hub_directory(&public_key)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("not subscribed to hub directory {public_key}"))?;
+        show_hubs(&directory);
+
+        return Ok(());
+    }
+
+    #[derive(Tabled)]
+    struct Row {
+        public_key: String,
+        is_trusted: bool,
+        hubs: usize,
+        updated_at: String,
+    }
+
+    show_table(
+        api::get_all_hub_directories()
+            .await?
+            .into_iter()
+            .map(|directory| Row {
+                public_key: directory.public_key.to_string(),
+                is_trusted: directory.is_trusted,
+                hubs: directory.hubs.len(),
+                updated_at: directory
+                    .updated_at
+                    .map(|updated_at| updated_at.to_string())
+                    .unwrap_or_else(|| "never".to_owned()),
+            }),
+    );
+
+    Ok(())
+}
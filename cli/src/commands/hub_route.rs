@@ -0,0 +1,45 @@
+use tabled::Tabled;
+
+use crate::api;
+
+use super::show_table;
+
+pub async fn set(series: String, hubs: Vec<String>) -> Result<(), anyhow::Error> {
+    api::put_hub_route(&series, api::PutHubRouteRequest { hubs: &hubs }).await?;
+
+    println!(
+        "NOTE: if none of these hubs is connected, nothing about {series} is sent to any hub."
+    );
+
+    Ok(())
+}
+
+pub async fn rm(series: String) -> Result<(), anyhow::Error> {
+    let removed = api::delete_hub_route(&series).await?;
+
+    if !removed {
+        println!("NOTE: series {series} was not routed.");
+    }
+
+    Ok(())
+}
+
+pub async fn ls() -> Result<(), anyhow::Error> {
+    #[derive(Tabled)]
+    struct Row {
+        series: String,
+        hubs: String,
+    }
+
+    show_table(
+        api::get_all_hub_routes()
+            .await?
+            .into_iter()
+            .map(|route| Row {
+                series: route.public_key,
+                hubs: route.hubs.join(", "),
+            }),
+    );
+
+    Ok(())
+}
@@ -0,0 +1,201 @@
+use anyhow::Context;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tabled::Tabled;
+
+use crate::api;
+use crate::ipfs::{self, Cid, Tree};
+
+use super::show_table;
+
+/// A line in a mapping table between IPFS and Samizdat.
+#[derive(Tabled)]
+struct MappingRow {
+    cid: String,
+    hash: String,
+    path: String,
+}
+
+/// Reads a mapping table, a tab-separated file of CIDs, object hashes and paths. A missing
+/// file is an empty table.
+fn read_mapping(path: &Path) -> Result<Vec<MappingRow>, anyhow::Error> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let mapping = fs::read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+
+    Ok(mapping
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t');
+            Some(MappingRow {
+                cid: columns.next()?.to_owned(),
+                hash: columns.next()?.to_owned(),
+                path: columns.next().unwrap_or_default().to_owned(),
+            })
+        })
+        .collect())
+}
+
+/// Appends rows to a mapping table.
+fn append_mapping(path: &Path, rows: &[MappingRow]) -> Result<(), anyhow::Error> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {path:?}"))?;
+
+    for row in rows {
+        writeln!(file, "{}\t{}\t{}", row.cid, row.hash, row.path)?;
+    }
+
+    Ok(())
+}
+
+pub async fn import(
+    cid: String,
+    gateway: String,
+    content_type: Option<String>,
+    bookmark: bool,
+    is_draft: bool,
+    mapping: Option<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    let parsed = cid.parse::<Cid>()?;
+
+    if let Some(mapping) = &mapping {
+        if let Some(row) = read_mapping(mapping)?
+            .into_iter()
+            .find(|row| row.cid == cid)
+        {
+            println!("Already imported. Object hash: {}", row.hash);
+            return Ok(());
+        }
+    }
+
+    let url = format!("{}/ipfs/{cid}", gateway.trim_end_matches('/'));
+    let response = reqwest::get(&url)
+        .await
+        .with_context(|| format!("failed to request {url}"))?;
+    let status = response.status();
+
+    if !status.is_success() {
+        anyhow::bail!("gateway responded {status} to GET {url}");
+    }
+
+    let content_type = content_type
+        .or_else(|| {
+            response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .map(str::to_owned)
+        })
+        .unwrap_or_else(|| "application/octet-stream".to_owned());
+    let content = response
+        .bytes()
+        .await
+        .with_context(|| format!("failed to read response from {url}"))?
+        .to_vec();
+
+    // Gateways are not to be trusted, but content added with non-default settings cannot be
+    // checked against its CID:
+    let computed = ipfs::add_file(&content, parsed.default_leaves(), &mut ())?;
+    if computed.cid.is_same_block(&parsed) {
+        log::info!("Content from {url} matches {cid}");
+    } else {
+        println!(
+            "WARNING: could not verify content against {cid}. It may have been added with \
+            non-default settings or the gateway may be lying."
+        );
+    }
+
+    let hash = api::post_object(content, &content_type, bookmark, is_draft).await?;
+    println!("Object hash: {hash}");
+
+    if let Some(mapping) = &mapping {
+        append_mapping(
+            mapping,
+            &[MappingRow {
+                cid,
+                hash,
+                path: String::new(),
+            }],
+        )?;
+    }
+
+    Ok(())
+}
+
+pub async fn export(
+    collection: String,
+    out: PathBuf,
+    mapping: Option<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    let inventory = api::get_collection_inventory(&collection)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("inventory for collection {collection} not found"))?
+        .inventory;
+
+    // Item paths such as `dir` and `dir/` are aliases to `dir/index.html`. They cannot be files
+    // if there is a directory with the same name.
+    let is_alias = |path: &str| {
+        let prefix = format!("{path}/");
+        path.is_empty()
+            || path.ends_with('/')
+            || inventory
+                .range(prefix.clone()..)
+                .next()
+                .map(|(next, _)| next.starts_with(&prefix))
+                .unwrap_or(false)
+    };
+
+    // The root is only known at the end, so blocks go to a temporary file first:
+    let blocks_path = out.with_extension("blocks");
+    let blocks_file =
+        File::create(&blocks_path).with_context(|| format!("failed to create {blocks_path:?}"))?;
+    let mut blocks = ipfs::CarBlocks::new(BufWriter::new(blocks_file));
+    let mut tree = Tree::default();
+    let mut rows = Vec::new();
+
+    for (path, hash) in &inventory {
+        if is_alias(path) {
+            continue;
+        }
+
+        log::info!("Exporting {collection}/{path}");
+        let content = api::get_collection_item(&collection, path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("item {collection}/{path} ({hash}) not found"))?;
+        let node = ipfs::add_file(&content, ipfs::Leaves::Raw, &mut blocks)?;
+
+        rows.push(MappingRow {
+            cid: node.cid.to_string(),
+            hash: hash.to_string(),
+            path: path.clone(),
+        });
+        tree.insert(path, node);
+    }
+
+    let root = tree.add(&mut blocks)?;
+    blocks.into_inner().flush()?;
+
+    let car = File::create(&out).with_context(|| format!("failed to create {out:?}"))?;
+    ipfs::write_car(
+        BufWriter::new(car),
+        &root.cid,
+        File::open(&blocks_path).with_context(|| format!("failed to open {blocks_path:?}"))?,
+    )
+    .with_context(|| format!("failed to write {out:?}"))?;
+    fs::remove_file(&blocks_path)?;
+
+    if let Some(mapping) = &mapping {
+        append_mapping(mapping, &rows)?;
+    }
+
+    show_table(rows);
+    println!("Root CID: {}", root.cid);
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use tabled::Tabled;
+
+use crate::api;
+
+use super::show_table;
+
+pub async fn ls() -> Result<(), anyhow::Error> {
+    #[derive(Tabled)]
+    struct Row {
+        entity: String,
+        keys: usize,
+        used: usize,
+    }
+
+    show_table(
+        api::get_all_kvstores()
+            .await?
+            .into_iter()
+            .map(|kvstore| Row {
+                entity: kvstore.entity,
+                keys: kvstore.keys,
+                used: kvstore.used,
+            }),
+    );
+
+    Ok(())
+}
+
+pub async fn rm(entity: String) -> Result<(), anyhow::Error> {
+    api::delete_kvstore(&entity).await
+}
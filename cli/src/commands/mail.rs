@@ -0,0 +1,309 @@
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tabled::Tabled;
+
+use samizdat_common::Key;
+
+use crate::api;
+use crate::mail::Entity;
+
+use super::show_table;
+
+/// The item with the index of the archive, which is read back from the latest edition, so
+/// that each edition holds all messages ever ingested.
+const INDEX_ITEM: &str = "messages.json";
+
+/// A message in the archive.
+#[derive(Debug, Clone, Serialize, Deserialize, Tabled)]
+struct Entry {
+    path: String,
+    subject: String,
+    from: String,
+    /// The date of the message, in RFC 3339.
+    date: String,
+}
+
+#[derive(Template)]
+#[template(path = "mail-index.html")]
+struct IndexTemplate<'a> {
+    title: &'a str,
+    entries: &'a [Entry],
+}
+
+#[derive(Template)]
+#[template(path = "mail-message.html")]
+struct MessageTemplate<'a> {
+    entry: &'a Entry,
+    has_html: bool,
+    text: &'a str,
+    attachments: &'a [String],
+}
+
+/// Makes a file name safe to be used in item paths and links.
+fn sanitize(filename: &str) -> String {
+    let sanitized = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    sanitized.trim_start_matches('.').to_owned()
+}
+
+/// Lists the messages in a Maildir folder, both new and already seen. The folder is only read,
+/// so that it can still be used by other mail readers.
+fn maildir_messages(maildir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut messages = Vec::new();
+
+    for subdir in ["new", "cur"] {
+        let dir = maildir.join(subdir);
+        let listing = fs::read_dir(&dir)
+            .with_context(|| format!("failed to read {dir:?}. Is it a Maildir?"))?;
+
+        for entry in listing {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                messages.push(entry.path());
+            }
+        }
+    }
+
+    Ok(messages)
+}
+
+/// The date of a message, from its `Date` header, if it can be understood.
+fn date_of(message: &Entity) -> Option<DateTime<Utc>> {
+    // Drop comments, such as in `Tue, 1 Jul 2003 10:52:37 +0200 (CEST)`:
+    let date = message.header("Date")?.split('(').next()?.trim();
+    DateTime::parse_from_rfc2822(date)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Uploads the items of a message under the path of its entry, adding them to `hashes`.
+async fn upload_message(
+    raw: Vec<u8>,
+    message: &Entity,
+    entry: &Entry,
+    hashes: &mut Vec<(String, String)>,
+    is_draft: bool,
+) -> Result<(), anyhow::Error> {
+    let path = &entry.path;
+    let content = message.content();
+
+    let mut attachments = Vec::new();
+    for attachment in content.attachments {
+        let mut name = sanitize(&attachment.filename);
+        if name.is_empty() || attachments.contains(&name) {
+            name = format!("{}-{name}", attachments.len() + 1);
+        }
+
+        let hash =
+            api::post_object(attachment.content, &attachment.content_type, true, is_draft).await?;
+        hashes.push((format!("{path}/attachments/{name}"), hash));
+        attachments.push(name);
+    }
+
+    if let Some(html) = &content.html {
+        let hash = api::post_object(
+            html.clone().into_bytes(),
+            "text/html; charset=utf-8",
+            true,
+            is_draft,
+        )
+        .await?;
+        hashes.push((format!("{path}/body.html"), hash));
+    }
+
+    let page = MessageTemplate {
+        entry,
+        has_html: content.html.is_some(),
+        text: content.text.as_deref().unwrap_or_default(),
+        attachments: &attachments,
+    }
+    .render()?;
+    let hash = api::post_object(page.into_bytes(), "text/html", true, is_draft).await?;
+    hashes.push((format!("{path}/index.html"), hash));
+
+    let hash = api::post_object(raw, "message/rfc822", true, is_draft).await?;
+    hashes.push((format!("{path}/message.eml"), hash));
+
+    Ok(())
+}
+
+/// Ingests the new messages in a Maildir folder and publishes a new edition with them, if
+/// there are any.
+async fn ingest_once(
+    series_name: &str,
+    maildir: &Path,
+    title: &str,
+    ttl: Option<&str>,
+    is_draft: bool,
+    no_announce: bool,
+) -> Result<(), anyhow::Error> {
+    let series_owner = api::get_series_owner(series_name).await?;
+    let series = Key::from(series_owner.keypair.public).to_string();
+
+    // Start from what is in the latest edition:
+    let (mut entries, mut hashes) = match api::get_series_item(&series, INDEX_ITEM).await? {
+        Some(api::SeriesItem {
+            content,
+            collection: Some(collection),
+        }) => {
+            let entries: Vec<Entry> = serde_json::from_slice(&content)
+                .with_context(|| format!("failed to read {INDEX_ITEM} of {collection}"))?;
+            let inventory = api::get_collection_inventory(&collection)
+                .await?
+                .with_context(|| format!("inventory for collection {collection} not found"))?
+                .inventory;
+            let hashes = inventory
+                .into_iter()
+                .filter(|(path, _)| path.starts_with("messages/"))
+                .map(|(path, hash)| (path, hash.to_string()))
+                .collect::<Vec<_>>();
+
+            (entries, hashes)
+        }
+        _ => (vec![], vec![]),
+    };
+
+    let mut known = entries
+        .iter()
+        .map(|entry| entry.path.clone())
+        .collect::<BTreeSet<_>>();
+    let mut new_entries = Vec::new();
+
+    for file in maildir_messages(maildir)? {
+        let raw = fs::read(&file).with_context(|| format!("failed to read {file:?}"))?;
+        let message = Entity::parse(&raw);
+        let date = date_of(&message)
+            .or_else(|| Some(fs::metadata(&file).ok()?.modified().ok()?.into()))
+            .unwrap_or_else(Utc::now);
+
+        // The same message may be delivered many times, but keeps its id:
+        let id = message
+            .header("Message-ID")
+            .map(|id| Sha256::digest(id.as_bytes()))
+            .unwrap_or_else(|| Sha256::digest(&raw));
+        let path = format!(
+            "messages/{}-{}",
+            date.format("%Y-%m-%d"),
+            &format!("{id:x}")[..16]
+        );
+
+        if !known.insert(path.clone()) {
+            continue;
+        }
+
+        let entry = Entry {
+            path,
+            subject: message
+                .decoded_header("Subject")
+                .unwrap_or_else(|| "(no subject)".to_owned()),
+            from: message.decoded_header("From").unwrap_or_default(),
+            date: date.to_rfc3339(),
+        };
+
+        log::info!("Ingesting {file:?} as {}", entry.path);
+        upload_message(raw, &message, &entry, &mut hashes, is_draft).await?;
+        new_entries.push(entry);
+    }
+
+    if new_entries.is_empty() {
+        println!("No new messages in {maildir:?}");
+        return Ok(());
+    }
+
+    entries.extend(new_entries.iter().cloned());
+    entries.sort_by(|a, b| b.date.cmp(&a.date));
+
+    let index = IndexTemplate {
+        title,
+        entries: &entries,
+    }
+    .render()?;
+    let hash = api::post_object(index.into_bytes(), "text/html", true, is_draft).await?;
+    hashes.push(("index.html".to_owned(), hash));
+
+    let listing = serde_json::to_vec(&entries)?;
+    let hash = api::post_object(listing, "application/json", true, is_draft).await?;
+    hashes.push((INDEX_ITEM.to_owned(), hash));
+
+    let collection = api::post_collection(api::PostCollectionRequest {
+        hashes: &hashes,
+        is_draft,
+        metadata: &Default::default(),
+        content_warnings: &[],
+        inventory_shard_size: None,
+        prefetch: &[],
+        mounts: &Default::default(),
+    })
+    .await?;
+
+    let edition = api::post_edition(
+        series_name,
+        api::PostEditionRequest {
+            collection: &collection,
+            ttl,
+            no_announce,
+            release_at: None,
+        },
+    )
+    .await?;
+
+    show_table(new_entries);
+    println!("Edition collection: {collection}");
+    println!("Edition timestamp: {}", edition.signed.timestamp);
+
+    Ok(())
+}
+
+/// Publishes the messages received in a Maildir folder as a series, e.g., to archive a
+/// newsletter. Each message gets a page with its content and attachments, along with the
+/// original message, and the root of the edition lists all messages. Messages are identified
+/// by their `Message-ID`, so each is published only once, however many times it is ingested.
+pub async fn ingest(
+    series_name: String,
+    maildir: PathBuf,
+    title: Option<String>,
+    every: Option<humantime::Duration>,
+    ttl: Option<String>,
+    is_draft: bool,
+    no_announce: bool,
+) -> Result<(), anyhow::Error> {
+    let title = title.unwrap_or_else(|| series_name.clone());
+
+    loop {
+        let outcome = ingest_once(
+            &series_name,
+            &maildir,
+            &title,
+            ttl.as_deref(),
+            is_draft,
+            no_announce,
+        )
+        .await;
+
+        match every {
+            Some(every) => {
+                if let Err(err) = outcome {
+                    println!("WARNING: failed to ingest messages: {err}");
+                }
+
+                tokio::time::sleep(*every).await;
+            }
+            None => return outcome,
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use tabled::Tabled;
+
+use crate::api;
+
+use super::show_table;
+
+pub async fn add(series: String) -> Result<(), anyhow::Error> {
+    api::post_subscription(api::PostSubscriptionRequest {
+        public_key: &series,
+        kind: Some("Mirror"),
+    })
+    .await?;
+
+    println!("Mirroring {series}. Editions will be downloaded in the background.");
+
+    Ok(())
+}
+
+pub async fn status(series: Option<String>) -> Result<(), anyhow::Error> {
+    let all_series = if let Some(series) = series {
+        vec![series]
+    } else {
+        api::get_all_subscriptions()
+            .await?
+            .into_iter()
+            .filter(|subscription| subscription.kind == "Mirror")
+            .map(|subscription| subscription.public_key.to_string())
+            .collect()
+    };
+
+    #[derive(Tabled)]
+    struct Row {
+        series: String,
+        collection: String,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        items: String,
+        complete: String,
+    }
+
+    let mut rows = vec![];
+
+    for series in all_series {
+        for edition in api::get_mirror_status(&series).await? {
+            let (items, complete) = match edition.total {
+                Some(0) => ("0/0".to_owned(), "100.0%".to_owned()),
+                Some(total) => (
+                    format!("{}/{total}", edition.present),
+                    format!("{:.1}%", 100.0 * edition.present as f64 / total as f64),
+                ),
+                None => ("?".to_owned(), "unknown".to_owned()),
+            };
+
+            rows.push(Row {
+                series: series.clone(),
+                collection: edition.collection,
+                timestamp: edition.timestamp,
+                items,
+                complete,
+            });
+        }
+    }
+
+    show_table(rows);
+
+    Ok(())
+}
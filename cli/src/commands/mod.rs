@@ -1,14 +1,42 @@
+pub mod archive;
 pub mod auth;
 pub mod collection;
+pub mod connection;
 pub mod edition;
+mod export;
+pub mod git;
+pub mod hub;
+pub mod hub_directory;
+pub mod hub_route;
 pub mod identity;
+pub mod ipfs;
+pub mod kvstore;
+pub mod mail;
+pub mod mirror;
+pub mod node;
+pub mod object;
+pub mod profile;
+mod self_update;
+mod selftest;
 pub mod series;
 pub mod subscription;
+mod sync;
+pub mod torrent;
+mod verify_build;
+mod wipe;
+
+pub use export::export;
+pub use self_update::self_update;
+pub use selftest::selftest;
+pub use sync::sync;
+pub use verify_build::verify_build;
+pub use wipe::wipe;
 
 use anyhow::Context;
 use futures::prelude::*;
 use futures::stream;
 use notify::{RecursiveMode, Watcher};
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
@@ -16,10 +44,13 @@ use std::{env, fs, io};
 use tabled::{Table, Tabled};
 use tokio::sync::mpsc;
 
+use samizdat_common::attestation::ATTESTATION_ITEM;
 use samizdat_common::{Hash, PrivateKey};
 
 use crate::api;
+use crate::attestation;
 use crate::html::maybe_proxy_page;
+use crate::manifest::{Headers, Target};
 use crate::{Manifest, PrivateManifest};
 
 fn show_table<T: Tabled>(t: impl IntoIterator<Item = T>) {
@@ -38,7 +69,11 @@ pub async fn upload(
     Ok(())
 }
 
-pub async fn init(name: Option<String>) -> Result<(), anyhow::Error> {
+pub async fn init(name: Option<String>, target: Option<String>) -> Result<(), anyhow::Error> {
+    if let Some(target) = target {
+        return init_target(&target).await;
+    }
+
     let pwd = env::current_dir()?;
     let name = name.unwrap_or_else(|| {
         pwd.iter()
@@ -51,7 +86,7 @@ pub async fn init(name: Option<String>) -> Result<(), anyhow::Error> {
     let (manifest, private_key) = Manifest::create(&name)
         .await
         .context("failed to create `Manifest.toml`")?;
-    PrivateManifest::create(&manifest.debug.name, Some(&private_key))
+    PrivateManifest::create(&manifest.main.debug.name, Some(&private_key))
         .await
         .context("failed to create `.Samizdat.priv`")?;
 
@@ -64,6 +99,31 @@ pub async fn init(name: Option<String>) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Adds a new target to the project in the current directory.
+async fn init_target(target: &str) -> Result<(), anyhow::Error> {
+    let manifest = Manifest::find_opt()
+        .context("failed to find `Samizdat.toml`")?
+        .ok_or_else(|| {
+            anyhow::anyhow!("`Samizdat.toml` does not exist. Hint: run `samizdat init` first.")
+        })?;
+
+    let (debug_name, private_key) = manifest
+        .add_target(target)
+        .await
+        .context("failed to add target to `Samizdat.toml`")?;
+    PrivateManifest::add_target(target, &debug_name, Some(&private_key))
+        .await
+        .context("failed to add target to `.Samizdat.priv`")?;
+
+    println!(
+        "NOTE: Your private key for the target `{target}` is \n\n\t{}
+        \n\nStore it somewhere safe! (you were warned)",
+        private_key
+    );
+
+    Ok(())
+}
+
 pub async fn import(private_key: Option<String>) -> Result<(), anyhow::Error> {
     let manifest = Manifest::find_opt()
         .context("failed to find `Samizdat.toml`")?
@@ -74,7 +134,7 @@ pub async fn import(private_key: Option<String>) -> Result<(), anyhow::Error> {
         private_manifest
     } else {
         PrivateManifest::create(
-            &manifest.debug.name,
+            &manifest.main.debug.name,
             private_key
                 .map(|pk| pk.parse::<PrivateKey>())
                 .transpose()?
@@ -84,40 +144,118 @@ pub async fn import(private_key: Option<String>) -> Result<(), anyhow::Error> {
         .context("failed to create `.Samizdat.priv`")?
     };
 
-    // Import debug series owner.
-    api::post_series_owner(api::PostSeriesOwnerRequest {
-        series_owner_name: &manifest.debug.name,
-        keypair: Some(api::Keypair {
-            private_key: private_manifest.private_key_debug,
-            public_key: private_manifest.public_key_debug,
-        }),
-        is_draft: false,
-    })
-    .await
-    .context("failed to import series keypair")?;
+    for (name, target) in manifest.select_targets(&[], true)? {
+        let private_target = private_manifest.target(name)?;
 
-    // Import series owners if its private key present in the private manifest.
-    if let Some(private_key) = private_manifest.private_key {
+        // Import debug series owner.
         api::post_series_owner(api::PostSeriesOwnerRequest {
-            series_owner_name: &manifest.series.name,
+            series_owner_name: &target.debug.name,
             keypair: Some(api::Keypair {
-                private_key,
-                public_key: manifest.series.public_key,
+                private_key: private_target.private_key_debug.clone(),
+                public_key: private_target.public_key_debug.clone(),
             }),
             is_draft: false,
         })
         .await
         .context("failed to import series keypair")?;
+
+        // Import series owners if its private key present in the private manifest.
+        if let Some(private_key) = &private_target.private_key {
+            api::post_series_owner(api::PostSeriesOwnerRequest {
+                series_owner_name: &target.series.name,
+                keypair: Some(api::Keypair {
+                    private_key: private_key.clone(),
+                    public_key: target.series.public_key.clone(),
+                }),
+                is_draft: false,
+            })
+            .await
+            .context("failed to import series keypair")?;
+        }
     }
 
     Ok(())
 }
 
+/// The objects already uploaded in a commit, by hash and content type of their contents, so
+/// that files shared by many targets are uploaded only once.
+type Uploaded = BTreeMap<(Hash, String), String>;
+
 pub async fn commit(
     ttl: &Option<String>,
     is_release: bool,
     no_announce: bool,
+    release_at: Option<chrono::DateTime<chrono::Utc>>,
+    targets: &[String],
+    all_targets: bool,
 ) -> Result<(), anyhow::Error> {
+    #[derive(Tabled)]
+    struct Row {
+        series: String,
+        // public_key: Key,
+        collection: Hash,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        ttl: String,
+    }
+
+    let manifest =
+        Manifest::find_opt()?.ok_or_else(|| anyhow::anyhow!("`Samizdat.toml` does not exst"))?;
+    let private_manifest = PrivateManifest::find_opt()?.ok_or_else(|| {
+        anyhow::anyhow!("Private manifest `.Samizdat.priv` not found. Hint: run `samizdat import`.")
+    })?;
+
+    let mut uploaded = Uploaded::new();
+    let mut rows = vec![];
+
+    for (name, target) in manifest.select_targets(targets, all_targets)? {
+        if is_release && private_manifest.target(name)?.private_key.is_none() {
+            anyhow::bail!(
+                "Cannot run release mode without a private key. Hint: put your private key \
+                in `.Samizdat.priv` and then run `samizdat import`."
+            )
+        }
+
+        let series_name = if is_release {
+            &target.series.name
+        } else {
+            &target.debug.name
+        };
+
+        let edition = commit_target(
+            target,
+            series_name,
+            ttl,
+            is_release,
+            no_announce,
+            release_at,
+            &mut uploaded,
+        )
+        .await?;
+
+        rows.push(Row {
+            series: series_name.to_owned(),
+            // public_key: item.public_key,
+            collection: edition.signed.collection.hash,
+            timestamp: edition.signed.timestamp,
+            ttl: format!("{:?}", edition.signed.ttl),
+        });
+    }
+
+    show_table(rows);
+
+    Ok(())
+}
+
+/// Builds a target and publishes it as a new edition of the given series.
+async fn commit_target(
+    target: &Target,
+    series_name: &str,
+    ttl: &Option<String>,
+    is_release: bool,
+    no_announce: bool,
+    release_at: Option<chrono::DateTime<chrono::Utc>>,
+    uploaded: &mut Uploaded,
+) -> Result<api::PostEditionResponse, anyhow::Error> {
     // Oh, generators would be so nice now...
     fn walk(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
         for entry in fs::read_dir(path)? {
@@ -152,103 +290,131 @@ pub async fn commit(
         names
     }
 
-    let manifest =
-        Manifest::find_opt()?.ok_or_else(|| anyhow::anyhow!("`Samizdat.toml` does not exst"))?;
-    let private_manifest = PrivateManifest::find_opt()?.ok_or_else(|| {
-        anyhow::anyhow!("Private manifest `.Samizdat.priv` not found. Hint: run `samizdat import`.")
-    })?;
-
-    if is_release && private_manifest.private_key.is_none() {
-        anyhow::bail!(
-            "Cannot run release mode without a private key. Hint: put your private key \
-            in `.Samizdat.priv` and then run `samizdat import`."
-        )
-    }
-
-    let base = &manifest.build.base;
-    manifest.run_build(is_release)?;
+    let base = &target.build.base;
+    let header_rules = target.header_rules()?;
+    let source_hash = target
+        .build
+        .attest
+        .then(|| attestation::source_hash(target))
+        .transpose()?;
+    target.run_build(is_release)?;
 
     let mut all_files = vec![];
     walk(base, &mut all_files)?;
 
     log::debug!("committing: {:#?}", all_files);
 
-    let hashes = stream::iter(&all_files)
-        .map(|path| async move {
-            log::info!("Creating object for {:?}", path);
-            let content_type = mime_guess::from_path(&path)
-                .first_or_octet_stream()
-                .to_string();
-
-            let hash = api::post_object(
-                maybe_proxy_page(path, &fs::read(&path)?).into_owned(),
-                &content_type,
-                is_release,
-                !is_release,
-            )
-            .await?;
+    // Files are only uploaded once per content, even across targets:
+    let mut to_upload = BTreeMap::new();
+    let mut file_keys = vec![];
+    for path in &all_files {
+        let content_type = mime_guess::from_path(path)
+            .first_or_octet_stream()
+            .to_string();
+        let content = maybe_proxy_page(path, &fs::read(path)?).into_owned();
+        let key = (Hash::hash(&content), content_type);
+
+        if !uploaded.contains_key(&key) {
+            to_upload.insert(key.clone(), (path, content));
+        }
 
-            let names = names_from_path(path, base);
+        file_keys.push((path, key));
+    }
 
-            Ok((names, hash)) as Result<(Vec<String>, String), anyhow::Error>
+    let new_uploads = stream::iter(to_upload)
+        .map(|(key, (path, content))| async move {
+            log::info!("Creating object for {:?}", path);
+            let hash = api::post_object(content, &key.1, is_release, !is_release).await?;
+            Ok((key, hash)) as Result<_, anyhow::Error>
         })
-        .buffer_unordered(all_files.len())
+        .buffer_unordered(all_files.len().max(1))
         .try_collect::<Vec<_>>()
-        .await?
+        .await?;
+    uploaded.extend(new_uploads);
+
+    let mut hashes = file_keys
         .into_iter()
-        .flat_map(|(names, hash)| names.into_iter().map(move |name| (name, hash.clone())))
+        .flat_map(|(path, key)| {
+            let hash = uploaded[&key].clone();
+            names_from_path(path, base)
+                .into_iter()
+                .map(move |name| (name, hash.clone()))
+        })
         .collect::<Vec<_>>();
 
+    if let Some(source_hash) = source_hash {
+        let attestation = attestation::attest(target, series_name, is_release, source_hash).await?;
+        let hash = api::post_object(
+            serde_json::to_vec_pretty(&attestation)?,
+            "application/json",
+            is_release,
+            !is_release,
+        )
+        .await?;
+        hashes.push((ATTESTATION_ITEM.to_owned(), hash));
+    }
+
     log::debug!("hashes: {:#?}", hashes);
 
+    // Every name of a file gets the metadata of the file:
+    let metadata = all_files
+        .iter()
+        .filter_map(|path| {
+            let relative = path.strip_prefix(base).unwrap().to_string_lossy();
+            let headers = Headers::for_path(&header_rules, &relative)?;
+            Some((path, headers))
+        })
+        .flat_map(|(path, headers)| {
+            names_from_path(path, base).into_iter().map(move |name| {
+                let item_metadata = api::ItemMetadata {
+                    cache_control: headers.cache_control.clone(),
+                    content_language: headers.content_language.clone(),
+                    charset: headers.charset.clone(),
+                    content_security_policy: headers.content_security_policy.clone(),
+                    frame_options: headers.frame_options.clone(),
+                    referrer_policy: headers.referrer_policy.clone(),
+                };
+                (name, item_metadata)
+            })
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    log::debug!("metadata: {:#?}", metadata);
+
     let collection = api::post_collection(api::PostCollectionRequest {
         hashes: &hashes,
         is_draft: !is_release,
+        metadata: &metadata,
+        content_warnings: &target.series.content_warnings,
+        inventory_shard_size: target.series.inventory_shard_size,
+        prefetch: &target.series.prefetch,
+        mounts: &target.series.mounts,
     })
     .await?;
 
-    let series_name = if is_release {
-        manifest.series.name
-    } else {
-        manifest.debug.name
-    };
     let ttl = ttl.clone().or(if is_release {
-        manifest.series.ttl
+        target.series.ttl.clone()
     } else {
         None
     });
 
-    let edition = api::post_edition(
-        &series_name,
+    api::post_edition(
+        series_name,
         api::PostEditionRequest {
             collection: &collection,
             ttl: ttl.as_deref(),
             no_announce,
+            release_at,
         },
     )
-    .await?;
-
-    #[derive(Tabled)]
-    struct Row {
-        series: String,
-        // public_key: Key,
-        collection: Hash,
-        timestamp: chrono::DateTime<chrono::Utc>,
-        ttl: String,
-    }
-
-    show_table([Row {
-        series: series_name.to_owned(),
-        // public_key: item.public_key,
-        collection: edition.signed.collection.hash,
-        timestamp: edition.signed.timestamp,
-        ttl: format!("{:?}", edition.signed.ttl),
-    }]);
-
-    Ok(())
+    .await
 }
 
-pub async fn watch(ttl: &Option<String>) -> Result<(), anyhow::Error> {
+pub async fn watch(
+    ttl: &Option<String>,
+    targets: &[String],
+    all_targets: bool,
+) -> Result<(), anyhow::Error> {
     /// Minimum time you have to wait to trigger rebuild.
     const MIN_WAIT: Duration = Duration::from_secs(1);
 
@@ -257,15 +423,21 @@ pub async fn watch(ttl: &Option<String>) -> Result<(), anyhow::Error> {
     let private_manifest = PrivateManifest::find_opt()?.ok_or_else(|| {
         anyhow::anyhow!("Private manifest `.Samizdat.priv` not found. Hint: run `samizdat import`.")
     })?;
-
-    // Ignore the output folder.
-    let base = if manifest.build.base.is_absolute() {
-        manifest.build.base.clone()
-    } else {
-        std::env::current_dir()
-            .expect("current dir exists")
-            .join(&manifest.build.base)
-    };
+    let selected = manifest.select_targets(targets, all_targets)?;
+
+    // Ignore the output folders.
+    let bases = selected
+        .iter()
+        .map(|(_, target)| {
+            if target.build.base.is_absolute() {
+                target.build.base.clone()
+            } else {
+                std::env::current_dir()
+                    .expect("current dir exists")
+                    .join(&target.build.base)
+            }
+        })
+        .collect::<Vec<_>>();
 
     // Spawn file watcher.
     let (send, mut recv) = mpsc::unbounded_channel();
@@ -288,17 +460,19 @@ pub async fn watch(ttl: &Option<String>) -> Result<(), anyhow::Error> {
     // Print watch banner:
     const MARKER: &str = "\u{001b}[1m\u{001b}[31m*\u{001b}[0m";
     println!();
-    println!(
-        "{MARKER} Publishing series at \u{001b}[1mhttp://localhost:{}/_series/{}\u{001b}[0m",
-        crate::cli::cli().port,
-        private_manifest.public_key_debug
-    );
+    for (name, _) in &selected {
+        println!(
+            "{MARKER} Publishing series at \u{001b}[1mhttp://localhost:{}/_series/{}\u{001b}[0m",
+            crate::cli::cli().port,
+            private_manifest.target(*name)?.public_key_debug
+        );
+    }
     println!();
 
     log::info!("Starting rebuild loop");
 
     // Run the commit for the first time.
-    if let Err(err) = commit(ttl, false, true).await {
+    if let Err(err) = commit(ttl, false, true, None, targets, all_targets).await {
         println!("Error while rebuilding: {err:?}");
     }
 
@@ -308,11 +482,14 @@ pub async fn watch(ttl: &Option<String>) -> Result<(), anyhow::Error> {
     // The commit loop.
     while let Some(event) = recv.recv().await {
         let now = Instant::now();
-        let watched_files_changed = event.paths.iter().any(|path| !path.starts_with(&base));
+        let watched_files_changed = event
+            .paths
+            .iter()
+            .any(|path| !bases.iter().any(|base| path.starts_with(base)));
 
         if watched_files_changed && now > last_exec + MIN_WAIT {
             log::info!("Rebuild triggered");
-            if let Err(err) = commit(ttl, false, true).await {
+            if let Err(err) = commit(ttl, false, true, None, targets, all_targets).await {
                 println!("Error while rebuilding: {err:?}");
             }
 
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+use tabled::Tabled;
+
+use samizdat_node_core::relocation::{self, RelocationReport};
+
+use super::show_table;
+
+pub fn migrate(from: PathBuf, to: PathBuf) -> Result<(), anyhow::Error> {
+    let report = relocation::migrate(&from, &to)?;
+
+    #[derive(Tabled)]
+    struct Row {
+        data: String,
+        table: String,
+        entries: usize,
+    }
+
+    fn rows(report: RelocationReport) -> Box<dyn Iterator<Item = Row>> {
+        let data = report.data.display().to_string();
+        let tables = report.tables.into_iter().map(move |(table, entries)| Row {
+            data: data.clone(),
+            table,
+            entries,
+        });
+
+        Box::new(tables.chain(report.profiles.into_iter().flat_map(rows)))
+    }
+
+    show_table(rows(report));
+    println!("Data moved to {to:?}. A new access token will be generated when the node starts.");
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use anyhow::Context;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use tabled::Tabled;
+
+use crate::api;
+
+use super::show_table;
+
+/// Collects the hashes passed in the command line together with the hashes listed in a
+/// file (or in the standard input), if any.
+fn collect_hashes(
+    mut hashes: Vec<String>,
+    from_file: Option<PathBuf>,
+) -> Result<Vec<String>, anyhow::Error> {
+    if let Some(path) = from_file {
+        let contents = if path.to_str() == Some("-") {
+            let mut contents = String::new();
+            io::stdin()
+                .read_to_string(&mut contents)
+                .context("failed to read hashes from standard input")?;
+            contents
+        } else {
+            fs::read_to_string(&path)
+                .with_context(|| format!("failed to read hashes from {path:?}"))?
+        };
+
+        hashes.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_owned),
+        );
+    }
+
+    Ok(hashes)
+}
+
+pub async fn rm(hashes: Vec<String>, from_file: Option<PathBuf>) -> Result<(), anyhow::Error> {
+    let hashes = collect_hashes(hashes, from_file)?;
+    let removed = api::post_batch_delete(api::PostBatchDeleteRequest { hashes: &hashes }).await?;
+
+    println!("Removed {removed} objects");
+
+    Ok(())
+}
+
+pub async fn bookmark(
+    hashes: Vec<String>,
+    from_file: Option<PathBuf>,
+    remove: bool,
+) -> Result<(), anyhow::Error> {
+    let hashes = collect_hashes(hashes, from_file)?;
+    let request = if remove {
+        api::PostBookmarkBatchRequest {
+            mark: &[],
+            unmark: &hashes,
+        }
+    } else {
+        api::PostBookmarkBatchRequest {
+            mark: &hashes,
+            unmark: &[],
+        }
+    };
+
+    api::post_bookmark_batch(request).await?;
+
+    Ok(())
+}
+
+pub async fn fetch(
+    url: String,
+    content_type: Option<String>,
+    bookmark: bool,
+    is_draft: bool,
+) -> Result<(), anyhow::Error> {
+    let response = api::post_fetch(api::PostFetchRequest {
+        url: &url,
+        content_type: content_type.as_deref(),
+        bookmark,
+        is_draft,
+    })
+    .await?;
+
+    println!("Object hash: {} ({})", response.hash, response.content_type);
+
+    Ok(())
+}
+
+pub async fn availability(hash: String) -> Result<(), anyhow::Error> {
+    let response = api::get_availability(&hash).await?;
+
+    #[derive(Tabled)]
+    struct Row {
+        hub: String,
+        address: String,
+        candidates: usize,
+        error: String,
+    }
+
+    show_table(
+        response
+            .hubs
+            .into_iter()
+            .map(|(address, availability)| Row {
+                hub: availability.hub,
+                address,
+                candidates: availability.candidates,
+                error: availability.error.unwrap_or_default(),
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    println!("Found {} distinct peers with {hash}", response.peers);
+
+    Ok(())
+}
+
+pub async fn export(hash: String, file: Option<PathBuf>) -> Result<(), anyhow::Error> {
+    let found = if let Some(file) = &file {
+        let writer =
+            fs::File::create(file).with_context(|| format!("failed to write to {file:?}"))?;
+        api::export_object(&hash, writer).await?
+    } else {
+        api::export_object(&hash, io::stdout().lock()).await?
+    };
+
+    if !found {
+        // Do not leave an empty file behind:
+        if let Some(file) = file {
+            fs::remove_file(file).ok();
+        }
+
+        anyhow::bail!("object {hash} not found");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use tabled::Tabled;
+
+use samizdat_common::profiles;
+
+use crate::cli::cli;
+
+use super::show_table;
+
+#[derive(Tabled)]
+struct ProfileRow {
+    current: &'static str,
+    name: String,
+    port: u16,
+    data: String,
+}
+
+pub fn list() -> Result<(), anyhow::Error> {
+    let base = &cli().base_data;
+    let current = profiles::current(base)?;
+    let mark = |is_current: bool| if is_current { "*" } else { "" };
+
+    let default = ProfileRow {
+        current: mark(current.is_none()),
+        name: "default".to_owned(),
+        port: cli().port,
+        data: base.display().to_string(),
+    };
+    let others = profiles::list(base)?.into_iter().map(|profile| ProfileRow {
+        current: mark(current.as_ref() == Some(&profile.name)),
+        data: profile.data(base).display().to_string(),
+        name: profile.name,
+        port: profile.port,
+    });
+
+    show_table([default].into_iter().chain(others));
+
+    Ok(())
+}
+
+pub fn switch(name: String) -> Result<(), anyhow::Error> {
+    let name = Some(name).filter(|name| name != "default");
+    profiles::set_current(&cli().base_data, name.as_deref())?;
+    println!(
+        "Now using the {} profile",
+        name.as_deref().unwrap_or("default")
+    );
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+//! Updates the Samizdat binaries using Samizdat itself. Releases are published as editions of
+//! a well-known series, whose public key is hard-coded here. The node checks that every item
+//! belongs to the collection it was served from; here, we check that this collection belongs to
+//! an edition correctly signed by the release key. The timestamp of the newest release seen is
+//! kept in the data folder, so that older (e.g., vulnerable) releases are never installed back.
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use samizdat_common::Key;
+
+use crate::api;
+use crate::cli::cli;
+
+/// The public key of the series where Samizdat releases are published.
+const RELEASE_SERIES: &str = "r0Km0HptEt6Fhosmy7qxaKxyDtwHkzi0-eYbt1WatdM";
+/// The version of the releases to follow.
+const RELEASE_VERSION: &str = "latest";
+/// The binaries that are updated, if found next to this executable.
+const BINARIES: &[&str] = &["samizdat", "samizdat-node", "samizdat-service"];
+
+/// The target triple for which the releases for this machine are built.
+fn release_target() -> Result<&'static str, anyhow::Error> {
+    match (std::env::consts::ARCH, std::env::consts::OS) {
+        ("x86_64", "linux") => Ok("x86_64-unknown-linux-gnu"),
+        ("x86_64", "windows") => Ok("x86_64-pc-windows-gnu"),
+        ("aarch64", "macos") => anyhow::bail!(
+            "releases for macOS are distributed through Homebrew. Hint: use `brew upgrade \
+            samizdat` instead"
+        ),
+        (arch, os) => anyhow::bail!("there are no releases for {arch} on {os}"),
+    }
+}
+
+/// The file where the timestamp of the newest release seen is kept.
+fn last_release_path() -> PathBuf {
+    cli().data.join("last-release")
+}
+
+/// The timestamp of the newest release seen, if any.
+fn last_release() -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    match fs::read_to_string(last_release_path()) {
+        Ok(timestamp) => Ok(Some(timestamp.trim().parse()?)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Checks that a collection belongs to an edition of the release series signed by the release
+/// key and not older than the newest release seen, returning the timestamp of the edition.
+async fn verify_collection(
+    release_key: &Key,
+    collection: &str,
+    last_release: Option<DateTime<Utc>>,
+) -> Result<DateTime<Utc>, anyhow::Error> {
+    let timestamp = api::get_all_editions()
+        .await?
+        .into_iter()
+        .filter(|edition| edition.public_key.to_string() == RELEASE_SERIES)
+        .filter(|edition| edition.signed.collection.hash.to_string() == collection)
+        .filter(|edition| edition.signed.verify(&release_key.clone().into_inner()))
+        .map(|edition| edition.signed.timestamp)
+        .max();
+
+    let timestamp = timestamp.ok_or_else(|| {
+        anyhow::anyhow!("collection {collection} is not signed by the release key {release_key}")
+    })?;
+
+    if let Some(last_release) = last_release {
+        if timestamp < last_release {
+            anyhow::bail!(
+                "collection {collection} is from a release of {timestamp}, older than the \
+                release of {last_release} seen before. Refusing to roll back"
+            );
+        }
+    }
+
+    Ok(timestamp)
+}
+
+/// Atomically replaces a binary by a new version, keeping its permissions.
+fn replace_binary(path: &Path, content: &[u8]) -> Result<(), anyhow::Error> {
+    let new_path = path.with_extension("new");
+    fs::write(&new_path, content)?;
+    fs::set_permissions(&new_path, fs::metadata(path)?.permissions())?;
+
+    // Windows doesn't let running executables be replaced, but lets them be renamed:
+    if cfg!(windows) {
+        let old_path = path.with_extension("old");
+        fs::remove_file(&old_path).ok();
+        fs::rename(path, &old_path)?;
+    }
+
+    fs::rename(&new_path, path)?;
+
+    Ok(())
+}
+
+pub async fn self_update(check: bool) -> Result<(), anyhow::Error> {
+    let target = release_target()?;
+    let release_key: Key = RELEASE_SERIES.parse()?;
+    let current_exe = std::env::current_exe()?;
+    let install_dir = current_exe
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("executable has no parent directory"))?;
+
+    let mut updated = vec![];
+    let last_release = last_release()?;
+    let mut newest_release = last_release;
+
+    for binary in BINARIES {
+        let file_name = format!("{binary}{}", std::env::consts::EXE_SUFFIX);
+        let path = install_dir.join(&file_name);
+
+        if !path.exists() {
+            log::info!("{} not installed. Skipping", path.display());
+            continue;
+        }
+
+        println!("Checking {file_name}...");
+
+        let release_path = format!("{RELEASE_VERSION}/node/{target}/{file_name}");
+        let item = api::get_series_item(RELEASE_SERIES, &release_path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("release {release_path} not found in the network"))?;
+        let collection = item.collection.ok_or_else(|| {
+            anyhow::anyhow!("node did not tell from which collection {release_path} came")
+        })?;
+        let timestamp = verify_collection(&release_key, &collection, last_release).await?;
+        newest_release = newest_release.max(Some(timestamp));
+
+        let current = fs::read(&path).with_context(|| format!("failed to read {file_name}"))?;
+
+        if current == item.content {
+            println!("{file_name} is up to date");
+            continue;
+        }
+
+        if check {
+            println!("An update is available for {file_name} (collection {collection})");
+            continue;
+        }
+
+        replace_binary(&path, &item.content)
+            .with_context(|| format!("failed to replace {}", path.display()))?;
+        println!("Updated {file_name} from collection {collection}");
+        updated.push(*binary);
+    }
+
+    match newest_release {
+        Some(newest_release) if Some(newest_release) > last_release => {
+            fs::write(last_release_path(), newest_release.to_rfc3339())
+                .context("failed to record the newest release seen")?;
+        }
+        _ => {}
+    }
+
+    if updated.contains(&"samizdat-node") || updated.contains(&"samizdat-service") {
+        println!("Restart your node for the update to take effect");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! The self test: publishes a random draft object in a peer node (e.g., on another machine,
+//! reached through a tunnel) and queries it back through each hub, the way any other node
+//! would, without looking into the local database. Hubs never pair a node with itself, so
+//! publishing in this node would tell nothing about finding content.
+
+use std::time::{Duration, Instant};
+use tabled::Tabled;
+
+use samizdat_common::Hash;
+
+use crate::api;
+
+use super::show_table;
+
+#[derive(Tabled)]
+struct Row {
+    hub: String,
+    address: String,
+    answered_in: String,
+    candidates: usize,
+    found_after: String,
+    diagnosis: String,
+}
+
+fn show_duration(duration: Option<Duration>) -> String {
+    duration
+        .map(|duration| format!("{}ms", duration.as_millis()))
+        .unwrap_or_default()
+}
+
+/// What probably went right or wrong with a hub.
+fn diagnose(availability: &api::HubAvailability) -> String {
+    if let Some(error) = &availability.error {
+        return format!("query failed: {error}");
+    }
+
+    if availability.candidates == 0 {
+        "the hub answered, but the peer was not found; check whether the peer is connected to \
+            this hub and reachable by other nodes"
+            .to_owned()
+    } else {
+        "ok".to_owned()
+    }
+}
+
+pub async fn selftest(peer: String, peer_token: String, keep: bool) -> Result<(), anyhow::Error> {
+    let content = format!("Samizdat self test {}", Hash::rand()).into_bytes();
+    let peer_api = format!("{peer}/v1");
+
+    let start = Instant::now();
+    let hash =
+        api::post_object_at(&peer_api, &peer_token, content, "text/plain", false, true).await?;
+
+    println!(
+        "Published draft object {hash} in {peer} in {}ms",
+        start.elapsed().as_millis()
+    );
+
+    let outcome = find(&hash).await;
+
+    if !keep {
+        let request = api::PostBatchDeleteRequest {
+            hashes: std::slice::from_ref(&hash),
+        };
+
+        if let Err(err) = api::post_batch_delete_at(&peer_api, &peer_token, request).await {
+            println!("Failed to delete {hash} in {peer}: {err}");
+        }
+    }
+
+    outcome
+}
+
+/// Queries an object through each hub and shows what each found.
+async fn find(hash: &str) -> Result<(), anyhow::Error> {
+    let start = Instant::now();
+    let availability = api::get_availability(hash).await?;
+    let elapsed = start.elapsed();
+
+    if availability.hubs.is_empty() {
+        println!("This node is not connected to any hub");
+        return Ok(());
+    }
+
+    show_table(
+        availability
+            .hubs
+            .iter()
+            .map(|(address, hub_availability)| Row {
+                hub: hub_availability.hub.clone(),
+                address: address.clone(),
+                answered_in: show_duration(hub_availability.response_time),
+                candidates: hub_availability.candidates,
+                found_after: show_duration(hub_availability.first_candidate_after),
+                diagnosis: diagnose(hub_availability),
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    println!(
+        "Found {} distinct peers with {hash} in {}ms",
+        availability.peers,
+        elapsed.as_millis()
+    );
+
+    Ok(())
+}
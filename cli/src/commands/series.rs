@@ -119,3 +119,111 @@ pub async fn ls_cached(series_name: Option<String>) -> Result<(), anyhow::Error>
         ls_cached_all().await
     }
 }
+
+pub async fn readership(series_name: String) -> Result<(), anyhow::Error> {
+    let response = api::get_series_readership(&series_name).await?;
+
+    #[derive(Tabled)]
+    struct Row {
+        edition: String,
+        path: String,
+        served: u64,
+        reported: u64,
+    }
+
+    show_table(response.into_iter().flat_map(|edition| {
+        let timestamp = edition.timestamp.to_string();
+        edition.items.into_iter().map(move |item| Row {
+            edition: timestamp.clone(),
+            path: item.path,
+            served: item.served,
+            reported: item.reported,
+        })
+    }));
+
+    println!("NOTE: counts reported by mirrors are coarse and may lag by some hours.");
+
+    Ok(())
+}
+
+pub async fn reply(series_name: String, locator: String) -> Result<(), anyhow::Error> {
+    api::post_reply(
+        &locator,
+        api::PostReplyRequest {
+            series_owner_name: &series_name,
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn replies(locator: String) -> Result<(), anyhow::Error> {
+    let response = api::post_fetch_replies(&locator).await?;
+
+    #[derive(Tabled)]
+    struct Row {
+        public_key: String,
+        collection: String,
+        timestamp: String,
+    }
+
+    show_table(response.into_iter().map(|reply| Row {
+        public_key: reply.public_key,
+        collection: reply.collection,
+        timestamp: reply.timestamp.to_string(),
+    }));
+
+    Ok(())
+}
+
+pub async fn trust(
+    public_key: String,
+    level: String,
+    note: Option<String>,
+) -> Result<(), anyhow::Error> {
+    api::put_series_trust(
+        &public_key,
+        api::PutSeriesTrustRequest {
+            level: &level,
+            note: note.as_deref(),
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn untrust(public_key: String) -> Result<(), anyhow::Error> {
+    let removed = api::delete_series_trust(&public_key).await?;
+
+    if !removed {
+        println!("NOTE: no trust level was set for series {public_key}.");
+    }
+
+    Ok(())
+}
+
+pub async fn ls_trust() -> Result<(), anyhow::Error> {
+    #[derive(Tabled)]
+    struct Row {
+        series: String,
+        level: String,
+        note: String,
+        set_at: String,
+    }
+
+    show_table(
+        api::get_all_series_trust()
+            .await?
+            .into_iter()
+            .map(|trust| Row {
+                series: trust.public_key,
+                level: trust.level,
+                note: trust.note.unwrap_or_default(),
+                set_at: trust.set_at.to_string(),
+            }),
+    );
+
+    Ok(())
+}
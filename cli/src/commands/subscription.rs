@@ -6,9 +6,10 @@ use crate::api;
 
 use super::show_table;
 
-pub async fn new(public_key: String) -> Result<(), anyhow::Error> {
+pub async fn new(public_key: String, lazy: bool) -> Result<(), anyhow::Error> {
     api::post_subscription(api::PostSubscriptionRequest {
         public_key: &public_key,
+        kind: lazy.then_some("Lazy"),
     })
     .await?;
 
@@ -0,0 +1,45 @@
+use tabled::Tabled;
+
+use crate::api;
+
+use super::show_table;
+
+#[derive(Tabled)]
+struct FailedRow {
+    entry: String,
+    reason: String,
+}
+
+/// Copies into the local node everything a trusted peer node has that it has not.
+pub async fn sync(peer: String, peer_token: String) -> Result<(), anyhow::Error> {
+    let response = api::post_sync(api::PostSyncRequest {
+        peer: &peer,
+        token: &peer_token,
+    })
+    .await?;
+
+    if response.different_buckets == 0 && response.failed.is_empty() {
+        println!("Already in sync with {peer}");
+        return Ok(());
+    }
+
+    println!(
+        "Copied {} objects and {} collection items from {peer}",
+        response.objects, response.items
+    );
+
+    if !response.failed.is_empty() {
+        println!("Failed to copy {} entries:", response.failed.len());
+        show_table(
+            response
+                .failed
+                .into_iter()
+                .map(|(entry, reason)| FailedRow {
+                    entry: entry.to_string(),
+                    reason,
+                }),
+        );
+    }
+
+    Ok(())
+}
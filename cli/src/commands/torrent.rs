@@ -0,0 +1,88 @@
+use anyhow::Context;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::api;
+use crate::torrent::Torrent;
+
+pub async fn export(
+    hash: String,
+    name: Option<String>,
+    out: Option<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    let torrent = api::get_object_torrent(&hash, name.as_deref())
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("object {hash} not found in the local node"))?;
+    let out = out.unwrap_or_else(|| PathBuf::from(format!("{hash}.torrent")));
+    fs::write(&out, torrent).with_context(|| format!("failed to write {out:?}"))?;
+
+    println!("Torrent written to {out:?}");
+
+    Ok(())
+}
+
+/// Downloads the file of a torrent from its web seeds, returning the first content that matches
+/// the torrent.
+async fn download(torrent: &Torrent) -> Result<Vec<u8>, anyhow::Error> {
+    for webseed in &torrent.webseeds {
+        log::info!("Downloading from web seed {webseed}");
+        let downloaded = async {
+            let response = reqwest::get(webseed).await?.error_for_status()?;
+            let content = response.bytes().await?.to_vec();
+            torrent.check(&content)?;
+            Ok(content) as Result<_, anyhow::Error>
+        };
+
+        match downloaded.await {
+            Ok(content) => return Ok(content),
+            Err(err) => println!("WARNING: web seed {webseed} failed: {err}"),
+        }
+    }
+
+    anyhow::bail!(
+        "no web seed could provide the file. Hint: download it with a BitTorrent client and \
+        pass it with `--data`."
+    )
+}
+
+pub async fn import(
+    torrent: PathBuf,
+    data: Option<PathBuf>,
+    content_type: Option<String>,
+    bookmark: bool,
+    is_draft: bool,
+) -> Result<(), anyhow::Error> {
+    let torrent =
+        Torrent::parse(&fs::read(&torrent).with_context(|| format!("failed to read {torrent:?}"))?)
+            .with_context(|| format!("failed to parse {torrent:?}"))?;
+
+    let content = if let Some(data) = data {
+        let content = fs::read(&data).with_context(|| format!("failed to read {data:?}"))?;
+        torrent
+            .check(&content)
+            .with_context(|| format!("{data:?} is not the file of the torrent"))?;
+        content
+    } else {
+        download(&torrent).await?
+    };
+
+    let content_type = content_type.unwrap_or_else(|| {
+        mime_guess::from_path(&torrent.name)
+            .first_or_octet_stream()
+            .to_string()
+    });
+    let hash = api::post_object(content, &content_type, bookmark, is_draft).await?;
+
+    if let Some(object) = &torrent.object {
+        if *object != hash {
+            println!(
+                "NOTE: the torrent was created from object {object}, which has a different \
+                content type or draft status"
+            );
+        }
+    }
+
+    println!("Object hash: {hash}");
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! Audits of reproducible publication: builds the project in the current directory again and
+//! checks the outputs against the attestation embedded in the latest edition of its series.
+
+use tabled::Tabled;
+
+use samizdat_common::attestation::{BuildAttestation, ATTESTATION_ITEM};
+use samizdat_common::{Key, Signed};
+
+use crate::{api, attestation, Manifest};
+
+use super::show_table;
+
+pub async fn verify_build(
+    series: Option<String>,
+    target: Option<String>,
+) -> Result<(), anyhow::Error> {
+    #[derive(Tabled)]
+    struct Row {
+        what: &'static str,
+        attested: String,
+        rebuilt: String,
+        matches: bool,
+    }
+
+    let manifest =
+        Manifest::find_opt()?.ok_or_else(|| anyhow::anyhow!("`Samizdat.toml` does not exist"))?;
+    let target = manifest.target(target.as_deref())?;
+    let series = series.unwrap_or_else(|| target.series.public_key.clone());
+    let series_key = series.parse::<Key>()?;
+
+    let item = api::get_series_item(&series, ATTESTATION_ITEM)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("the latest edition of {series} has no attestation"))?;
+    let attestation: Signed<BuildAttestation> = serde_json::from_slice(&item.content)?;
+
+    if attestation.series != series_key || !attestation.verify(series_key.as_ref()) {
+        anyhow::bail!("the attestation in the latest edition of {series} was not signed by it");
+    }
+
+    if let Some(collection) = &item.collection {
+        println!("Verifying the build of collection {collection}");
+    }
+
+    // Run our own build command, never the one from the network:
+    let source = attestation::source_hash(target)?;
+    let toolchain = target.build.toolchain_fingerprint()?;
+    let command = target.build.script(true);
+    target.build.run(&series, true)?;
+    let outputs = attestation::outputs_hash(target)?;
+
+    let rows = [
+        Row {
+            what: "source",
+            attested: attestation.source.to_string(),
+            rebuilt: source.to_string(),
+            matches: attestation.source == source,
+        },
+        Row {
+            what: "command",
+            attested: attestation.command.clone(),
+            rebuilt: command.to_owned(),
+            matches: attestation.command == command,
+        },
+        Row {
+            what: "toolchain",
+            attested: attestation.toolchain.clone(),
+            rebuilt: toolchain.clone(),
+            matches: attestation.toolchain == toolchain,
+        },
+        Row {
+            what: "outputs",
+            attested: attestation.outputs.to_string(),
+            rebuilt: outputs.to_string(),
+            matches: attestation.outputs == outputs,
+        },
+    ];
+
+    show_table(rows);
+
+    if attestation.outputs != outputs {
+        anyhow::bail!("the build is not reproducible: outputs differ from the attested ones");
+    }
+
+    println!("The build is reproducible");
+
+    Ok(())
+}
@@ -0,0 +1,22 @@
+//! The panic wipe. This is for when there is no time to lose, so it asks no questions.
+
+use crate::api;
+
+pub async fn wipe(everything: bool, decoy: Option<String>) -> Result<(), anyhow::Error> {
+    let response = api::post_wipe(api::PostWipeRequest {
+        everything,
+        decoy: decoy.as_deref(),
+    })
+    .await?;
+
+    println!("Deleted {} entries from the node", response.entries);
+    for profile in response.profiles {
+        println!("Shredded profile {profile}");
+    }
+
+    if let Some(decoy) = decoy {
+        println!("Now using the {decoy} profile");
+    }
+
+    Ok(())
+}
@@ -19,3 +19,8 @@ pub fn maybe_proxy_page(path: impl AsRef<Path>, raw: &'_ [u8]) -> Cow<'_, [u8]>
         Cow::Borrowed(raw)
     }
 }
+
+/// Rewrites an HTML page so that absolute links work when served from a collection.
+pub fn proxy_html(raw: &[u8]) -> Vec<u8> {
+    proxy_page(String::from_utf8_lossy(raw)).into_bytes()
+}
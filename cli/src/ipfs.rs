@@ -0,0 +1,498 @@
+//! Interoperation with IPFS: laying out content as UnixFS DAGs, the way IPFS does it by
+//! default (chunks of 256KiB and balanced trees), to find its CIDs and to write CAR files.
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+/// The size of the leaves of a file.
+const CHUNK_SIZE: usize = 256 * 1024;
+/// The maximum number of children of an inner node of a file.
+const MAX_LINKS: usize = 174;
+
+/// The multicodec of protobuf-encoded DAG nodes.
+const DAG_PB: u64 = 0x70;
+/// The multicodec of raw blocks.
+const RAW: u64 = 0x55;
+/// The multihash code of SHA2-256.
+const SHA2_256: u8 = 0x12;
+
+/// UnixFS node types.
+const DIRECTORY: u64 = 1;
+const FILE: u64 = 2;
+
+const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+fn push_varint(mut n: u64, out: &mut Vec<u8>) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut n = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        n |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return Some(n);
+        }
+    }
+
+    None
+}
+
+/// Appends a varint field to a protobuf message.
+fn push_varint_field(field: u64, n: u64, out: &mut Vec<u8>) {
+    push_varint(field << 3, out);
+    push_varint(n, out);
+}
+
+/// Appends a length-delimited field to a protobuf message.
+fn push_bytes_field(field: u64, bytes: &[u8], out: &mut Vec<u8>) {
+    push_varint(field << 3 | 2, out);
+    push_varint(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+
+    for &byte in bytes {
+        buffer = buffer << 8 | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+
+    encoded
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+
+    for c in encoded.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&digit| digit == c.to_ascii_lowercase())?;
+        buffer = buffer << 5 | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(bytes)
+}
+
+fn base58_encode(bytes: &[u8]) -> String {
+    let mut digits: Vec<u8> = Vec::new();
+
+    for &byte in bytes {
+        let mut carry = u32::from(byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+    let mut encoded = "1".repeat(zeros);
+    encoded.extend(
+        digits
+            .iter()
+            .rev()
+            .map(|&digit| BASE58_ALPHABET[digit as usize] as char),
+    );
+    encoded
+}
+
+fn base58_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+
+    for c in encoded.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&digit| digit == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    let zeros = encoded.bytes().take_while(|&c| c == b'1').count();
+    let mut decoded = vec![0; zeros];
+    decoded.extend(bytes.into_iter().rev());
+    Some(decoded)
+}
+
+/// A content identifier, as used by IPFS. Only SHA2-256 digests are supported, since this is
+/// what IPFS uses by default.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cid {
+    version: u8,
+    codec: u64,
+    digest: [u8; 32],
+}
+
+impl Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.version == 0 {
+            write!(f, "{}", base58_encode(&self.to_bytes()))
+        } else {
+            write!(f, "b{}", base32_encode(&self.to_bytes()))
+        }
+    }
+}
+
+impl FromStr for Cid {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Cid, anyhow::Error> {
+        let multihash = |mut bytes: &[u8]| {
+            let (&code, rest) = bytes.split_first().context("missing multihash")?;
+            bytes = rest;
+            let size = read_varint(&mut bytes).context("bad multihash size")?;
+            anyhow::ensure!(
+                code == SHA2_256 && size == 32,
+                "only SHA2-256 CIDs are supported"
+            );
+            bytes.try_into().context("bad SHA2-256 digest")
+        };
+
+        if s.len() == 46 && s.starts_with("Qm") {
+            let bytes = base58_decode(s).context("bad base58 in CIDv0")?;
+            Ok(Cid {
+                version: 0,
+                codec: DAG_PB,
+                digest: multihash(&bytes)?,
+            })
+        } else if let Some(encoded) = s.strip_prefix('b') {
+            let bytes = base32_decode(encoded).context("bad base32 in CID")?;
+            let mut bytes = &bytes[..];
+            let version = read_varint(&mut bytes).context("missing CID version")?;
+            anyhow::ensure!(version == 1, "unsupported CID version {version}");
+            let codec = read_varint(&mut bytes).context("missing CID codec")?;
+            Ok(Cid {
+                version: 1,
+                codec,
+                digest: multihash(bytes)?,
+            })
+        } else {
+            anyhow::bail!("unsupported CID encoding for {s:?}: use CIDv0 or base32 CIDv1")
+        }
+    }
+}
+
+impl Cid {
+    /// The CIDv1 of a block.
+    fn of(codec: u64, block: &[u8]) -> Cid {
+        Cid {
+            version: 1,
+            codec,
+            digest: Sha256::digest(block).into(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        if self.version != 0 {
+            push_varint(u64::from(self.version), &mut bytes);
+            push_varint(self.codec, &mut bytes);
+        }
+
+        bytes.extend([SHA2_256, 32]);
+        bytes.extend(self.digest);
+        bytes
+    }
+
+    /// Whether both CIDs point to the same block, whatever their versions.
+    pub fn is_same_block(&self, other: &Cid) -> bool {
+        self.codec == other.codec && self.digest == other.digest
+    }
+
+    /// The leaves IPFS uses by default for files added with this CID version.
+    pub fn default_leaves(&self) -> Leaves {
+        if self.version == 0 {
+            Leaves::Pb
+        } else {
+            Leaves::Raw
+        }
+    }
+}
+
+/// How the content of files is stored in the leaves of their DAGs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Leaves {
+    /// As raw blocks, the default for CIDv1.
+    Raw,
+    /// Wrapped in UnixFS nodes, the default for CIDv0.
+    Pb,
+}
+
+/// Somewhere to put the blocks of a DAG as they are created.
+pub trait Blocks {
+    fn put(&mut self, cid: &Cid, block: &[u8]) -> io::Result<()>;
+}
+
+/// Discards the blocks, when only the CIDs are wanted.
+impl Blocks for () {
+    fn put(&mut self, _: &Cid, _: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The root of a DAG.
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub cid: Cid,
+    /// The size of all blocks in the DAG.
+    tsize: u64,
+    /// The size of the file in the DAG, if it is a file.
+    filesize: u64,
+}
+
+/// Encodes a protobuf DAG node and puts it in the blocks.
+fn put_pb_node(
+    links: &[(&str, &Node)],
+    data: &[u8],
+    blocks: &mut impl Blocks,
+) -> io::Result<(Cid, u64)> {
+    let mut block = Vec::new();
+
+    for (name, node) in links {
+        let mut link = Vec::new();
+        push_bytes_field(1, &node.cid.to_bytes(), &mut link);
+        push_bytes_field(2, name.as_bytes(), &mut link);
+        push_varint_field(3, node.tsize, &mut link);
+        push_bytes_field(2, &link, &mut block);
+    }
+
+    push_bytes_field(1, data, &mut block);
+
+    let cid = Cid::of(DAG_PB, &block);
+    blocks.put(&cid, &block)?;
+    let tsize = block.len() as u64 + links.iter().map(|(_, node)| node.tsize).sum::<u64>();
+
+    Ok((cid, tsize))
+}
+
+fn put_leaf(chunk: &[u8], leaves: Leaves, blocks: &mut impl Blocks) -> io::Result<Node> {
+    let filesize = chunk.len() as u64;
+
+    match leaves {
+        Leaves::Raw => {
+            let cid = Cid::of(RAW, chunk);
+            blocks.put(&cid, chunk)?;
+            Ok(Node {
+                cid,
+                tsize: filesize,
+                filesize,
+            })
+        }
+        Leaves::Pb => {
+            let mut unixfs = Vec::new();
+            push_varint_field(1, FILE, &mut unixfs);
+            push_bytes_field(2, chunk, &mut unixfs);
+            push_varint_field(3, filesize, &mut unixfs);
+            let (cid, tsize) = put_pb_node(&[], &unixfs, blocks)?;
+            Ok(Node {
+                cid,
+                tsize,
+                filesize,
+            })
+        }
+    }
+}
+
+/// Lays out the content of a file as a DAG, returning its root.
+pub fn add_file(content: &[u8], leaves: Leaves, blocks: &mut impl Blocks) -> io::Result<Node> {
+    let mut nodes = if content.is_empty() {
+        vec![put_leaf(content, leaves, blocks)?]
+    } else {
+        content
+            .chunks(CHUNK_SIZE)
+            .map(|chunk| put_leaf(chunk, leaves, blocks))
+            .collect::<io::Result<Vec<_>>>()?
+    };
+
+    while nodes.len() > 1 {
+        nodes = nodes
+            .chunks(MAX_LINKS)
+            .map(|children| {
+                let filesize = children.iter().map(|child| child.filesize).sum();
+                let mut unixfs = Vec::new();
+                push_varint_field(1, FILE, &mut unixfs);
+                push_varint_field(3, filesize, &mut unixfs);
+                for child in children {
+                    push_varint_field(4, child.filesize, &mut unixfs);
+                }
+
+                let links = children.iter().map(|child| ("", child)).collect::<Vec<_>>();
+                let (cid, tsize) = put_pb_node(&links, &unixfs, blocks)?;
+                Ok(Node {
+                    cid,
+                    tsize,
+                    filesize,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+    }
+
+    Ok(nodes.pop().expect("there is always one leaf"))
+}
+
+/// Lays out a directory, returning its root.
+fn add_directory(entries: &BTreeMap<&str, Node>, blocks: &mut impl Blocks) -> io::Result<Node> {
+    let mut unixfs = Vec::new();
+    push_varint_field(1, DIRECTORY, &mut unixfs);
+
+    let links = entries
+        .iter()
+        .map(|(name, node)| (*name, node))
+        .collect::<Vec<_>>();
+    let (cid, tsize) = put_pb_node(&links, &unixfs, blocks)?;
+
+    Ok(Node {
+        cid,
+        tsize,
+        filesize: 0,
+    })
+}
+
+/// A tree of files, to be laid out as UnixFS directories.
+#[derive(Debug, Default)]
+pub struct Tree {
+    files: BTreeMap<String, Node>,
+    directories: BTreeMap<String, Tree>,
+}
+
+impl Tree {
+    /// Inserts a file in the tree, creating the directories in its path.
+    pub fn insert(&mut self, path: &str, node: Node) {
+        match path.split_once('/') {
+            Some((directory, rest)) => self
+                .directories
+                .entry(directory.to_owned())
+                .or_default()
+                .insert(rest, node),
+            None => {
+                self.files.insert(path.to_owned(), node);
+            }
+        }
+    }
+
+    /// Lays out the tree as directories, returning the root.
+    pub fn add(&self, blocks: &mut impl Blocks) -> io::Result<Node> {
+        let mut entries = self
+            .files
+            .iter()
+            .map(|(name, node)| (name.as_str(), node.clone()))
+            .collect::<BTreeMap<_, _>>();
+
+        for (name, directory) in &self.directories {
+            entries.insert(name, directory.add(blocks)?);
+        }
+
+        add_directory(&entries, blocks)
+    }
+}
+
+/// Writes the blocks of a CAR file (version 1), each block once.
+pub struct CarBlocks<W> {
+    out: W,
+    seen: BTreeSet<Cid>,
+}
+
+impl<W: Write> Blocks for CarBlocks<W> {
+    fn put(&mut self, cid: &Cid, block: &[u8]) -> io::Result<()> {
+        if self.seen.insert(cid.clone()) {
+            let cid = cid.to_bytes();
+            let mut length = Vec::new();
+            push_varint((cid.len() + block.len()) as u64, &mut length);
+            self.out.write_all(&length)?;
+            self.out.write_all(&cid)?;
+            self.out.write_all(block)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> CarBlocks<W> {
+    pub fn new(out: W) -> CarBlocks<W> {
+        CarBlocks {
+            out,
+            seen: BTreeSet::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Writes a CAR file (version 1) with the given root. The blocks come after the header, but
+/// the root is only known after all blocks were written, so they are taken from elsewhere.
+pub fn write_car(mut out: impl Write, root: &Cid, mut blocks: impl Read) -> io::Result<()> {
+    // The DAG-CBOR header `{"roots": [root], "version": 1}`:
+    let mut cid = vec![0]; // the multibase prefix for binary.
+    cid.extend(root.to_bytes());
+
+    let mut header = vec![0xa2, 0x65];
+    header.extend(b"roots");
+    header.extend([0x81, 0xd8, 0x2a, 0x58, cid.len() as u8]);
+    header.extend(cid);
+    header.push(0x67);
+    header.extend(b"version");
+    header.push(0x01);
+
+    let mut length = Vec::new();
+    push_varint(header.len() as u64, &mut length);
+    out.write_all(&length)?;
+    out.write_all(&header)?;
+    io::copy(&mut blocks, &mut out)?;
+    out.flush()
+}
+
+#[test]
+fn lays_out_directories() {
+    let empty = Tree::default().add(&mut ()).unwrap();
+    assert_eq!(
+        empty.cid.to_string(),
+        "bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354"
+    );
+
+    let v0 = "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn"
+        .parse::<Cid>()
+        .unwrap();
+    assert!(v0.is_same_block(&empty.cid));
+    assert_eq!(
+        v0.to_string(),
+        "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn"
+    );
+}
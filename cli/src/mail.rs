@@ -0,0 +1,320 @@
+//! Reading of email messages (RFC 5322, with MIME), as found in Maildir folders.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref ENCODED_WORD: Regex =
+        Regex::new(r"=\?([^?\s]+)\?([bBqQ])\?([^?\s]*)\?=").expect("valid regex");
+}
+
+/// Decodes text in a given charset. Only Unicode and the Latin-1 family are understood; text
+/// in other charsets is read as UTF-8, at a loss.
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset.to_ascii_lowercase().as_str() {
+        "iso-8859-1" | "latin1" | "windows-1252" | "cp1252" => {
+            bytes.iter().map(|&byte| byte as char).collect()
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Decodes the quoted-printable encoding. In headers, underscores also stand for spaces.
+fn decode_quoted_printable(encoded: &[u8], is_header: bool) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+
+    while i < encoded.len() {
+        match encoded[i] {
+            b'=' => {
+                let rest = &encoded[i + 1..];
+                let byte = rest
+                    .get(..2)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+                if let Some(byte) = byte {
+                    decoded.push(byte);
+                    i += 3;
+                } else if rest.starts_with(b"\r\n") {
+                    // A soft line break:
+                    i += 3;
+                } else if rest.starts_with(b"\n") {
+                    i += 2;
+                } else {
+                    decoded.push(b'=');
+                    i += 1;
+                }
+            }
+            b'_' if is_header => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    decoded
+}
+
+fn decode_base64(encoded: &[u8]) -> Vec<u8> {
+    let cleaned = encoded
+        .iter()
+        .copied()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+    base64::decode(cleaned).unwrap_or_default()
+}
+
+/// Decodes the encoded words of a header (RFC 2047), e.g., `=?UTF-8?Q?Ol=C3=A1?=`.
+fn decode_header(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut last_end = 0;
+
+    for captures in ENCODED_WORD.captures_iter(value) {
+        let whole = captures.get(0).expect("group 0 always exists");
+        let between = &value[last_end..whole.start()];
+
+        // Whitespace between two encoded words is not part of the text:
+        if last_end == 0 || !between.trim().is_empty() {
+            decoded += between;
+        }
+
+        let text = captures[3].as_bytes();
+        let bytes = if captures[2].eq_ignore_ascii_case("b") {
+            decode_base64(text)
+        } else {
+            decode_quoted_printable(text, true)
+        };
+        decoded += &decode_charset(&bytes, &captures[1]);
+        last_end = whole.end();
+    }
+
+    decoded += &value[last_end..];
+    decoded
+}
+
+/// Splits an entity at the blank line between the headers and the body.
+fn split_head(raw: &[u8]) -> (&[u8], &[u8]) {
+    for (i, window) in raw.windows(2).enumerate() {
+        if window == b"\n\n" {
+            return (&raw[..i + 1], &raw[i + 2..]);
+        } else if window == b"\n\r" && raw.get(i + 2) == Some(&b'\n') {
+            return (&raw[..i + 1], &raw[i + 3..]);
+        }
+    }
+
+    (raw, &[])
+}
+
+/// A MIME entity: either a whole message or a part of a multipart message.
+#[derive(Debug)]
+pub struct Entity {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// A file attached to a message.
+#[derive(Debug)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// The readable content of a message.
+#[derive(Debug, Default)]
+pub struct Content {
+    /// The HTML version of the message, if any.
+    pub html: Option<String>,
+    /// The plain text version of the message, if any.
+    pub text: Option<String>,
+    pub attachments: Vec<Attachment>,
+}
+
+impl Entity {
+    pub fn parse(raw: &[u8]) -> Entity {
+        let (head, body) = split_head(raw);
+        let mut headers: Vec<(String, String)> = Vec::new();
+
+        for line in String::from_utf8_lossy(head).lines() {
+            if line.starts_with([' ', '\t']) {
+                // A folded line continues the previous header:
+                if let Some((_, value)) = headers.last_mut() {
+                    *value += " ";
+                    *value += line.trim();
+                }
+            } else if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_owned(), value.trim().to_owned()));
+            }
+        }
+
+        Entity {
+            headers,
+            body: body.to_vec(),
+        }
+    }
+
+    /// Gets the raw value of a header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Gets the value of a header, with its encoded words decoded.
+    pub fn decoded_header(&self, name: &str) -> Option<String> {
+        self.header(name).map(decode_header)
+    }
+
+    /// The value of a header without its parameters, in lowercase.
+    fn header_value(&self, name: &str) -> Option<String> {
+        self.header(name)
+            .map(|value| value.split(';').next().unwrap_or_default().trim())
+            .map(str::to_ascii_lowercase)
+    }
+
+    /// Gets a parameter of a header, e.g., the `charset` in `Content-Type`.
+    fn header_parameter(&self, name: &str, parameter: &str) -> Option<String> {
+        self.header(name)?
+            .split(';')
+            .skip(1)
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(parameter))
+            .map(|(_, value)| decode_header(value.trim().trim_matches('"')))
+    }
+
+    fn content_type(&self) -> String {
+        self.header_value("Content-Type")
+            .unwrap_or_else(|| "text/plain".to_owned())
+    }
+
+    /// The body, without its transfer encoding.
+    fn decoded_body(&self) -> Vec<u8> {
+        match self.header_value("Content-Transfer-Encoding").as_deref() {
+            Some("base64") => decode_base64(&self.body),
+            Some("quoted-printable") => decode_quoted_printable(&self.body, false),
+            _ => self.body.clone(),
+        }
+    }
+
+    fn text(&self) -> String {
+        let charset = self
+            .header_parameter("Content-Type", "charset")
+            .unwrap_or_else(|| "utf-8".to_owned());
+        decode_charset(&self.decoded_body(), &charset)
+    }
+
+    /// The parts of a multipart entity.
+    fn parts(&self) -> Vec<Entity> {
+        let boundary = match self.header_parameter("Content-Type", "boundary") {
+            Some(boundary) => format!("--{boundary}"),
+            None => return vec![],
+        };
+
+        let mut parts = Vec::new();
+        let mut current: Option<Vec<u8>> = None;
+
+        for line in self.body.split_inclusive(|&byte| byte == b'\n') {
+            let trimmed = line.strip_suffix(b"\n").unwrap_or(line);
+            let trimmed = trimmed.strip_suffix(b"\r").unwrap_or(trimmed);
+
+            if trimmed.starts_with(boundary.as_bytes()) {
+                if let Some(part) = current.take() {
+                    // The line break before the boundary belongs to the boundary:
+                    let part = part.strip_suffix(b"\n").unwrap_or(&part);
+                    let part = part.strip_suffix(b"\r").unwrap_or(part);
+                    parts.push(Entity::parse(part));
+                }
+
+                if trimmed[boundary.len()..].starts_with(b"--") {
+                    break;
+                }
+
+                current = Some(Vec::new());
+            } else if let Some(part) = &mut current {
+                part.extend(line);
+            }
+        }
+
+        parts
+    }
+
+    /// Collects the readable content and the attachments of this entity.
+    pub fn content(&self) -> Content {
+        let mut content = Content::default();
+        self.collect_content(&mut content);
+        content
+    }
+
+    fn collect_content(&self, content: &mut Content) {
+        let content_type = self.content_type();
+        let filename = self
+            .header_parameter("Content-Disposition", "filename")
+            .or_else(|| self.header_parameter("Content-Type", "name"));
+        let is_attachment = self.header_value("Content-Disposition").as_deref()
+            == Some("attachment")
+            || filename.is_some();
+
+        if content_type.starts_with("multipart/") {
+            for part in self.parts() {
+                part.collect_content(content);
+            }
+        } else if content_type == "text/html" && !is_attachment && content.html.is_none() {
+            content.html = Some(self.text());
+        } else if content_type == "text/plain" && !is_attachment && content.text.is_none() {
+            content.text = Some(self.text());
+        } else {
+            content.attachments.push(Attachment {
+                filename: filename
+                    .unwrap_or_else(|| format!("attachment-{}", content.attachments.len() + 1)),
+                content_type,
+                content: self.decoded_body(),
+            });
+        }
+    }
+}
+
+#[test]
+fn reads_multipart_messages() {
+    let raw = b"From: =?UTF-8?Q?Jo=C3=A3o?= <joao@example.com>\r\n\
+        Subject: =?UTF-8?B?T2zDoQ==?=\r\n \
+        =?UTF-8?Q?_mundo?=\r\n\
+        Content-Type: multipart/mixed; boundary=\"xyz\"\r\n\
+        \r\n\
+        preamble\r\n\
+        --xyz\r\n\
+        Content-Type: text/plain; charset=iso-8859-1\r\n\
+        Content-Transfer-Encoding: quoted-printable\r\n\
+        \r\n\
+        Ol=E1, =\r\n\
+        mundo\r\n\
+        --xyz\r\n\
+        Content-Type: application/pdf\r\n\
+        Content-Disposition: attachment; filename=\"doc.pdf\"\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        JVBERg==\r\n\
+        --xyz--\r\n";
+
+    let message = Entity::parse(raw);
+    assert_eq!(
+        message.decoded_header("from").as_deref(),
+        Some("João <joao@example.com>")
+    );
+    assert_eq!(
+        message.decoded_header("subject").as_deref(),
+        Some("Olá mundo")
+    );
+
+    let content = message.content();
+    assert_eq!(content.text.as_deref(), Some("Olá, mundo"));
+    assert!(content.html.is_none());
+    assert_eq!(content.attachments.len(), 1);
+    assert_eq!(content.attachments[0].filename, "doc.pdf");
+    assert_eq!(content.attachments[0].content, b"%PDF");
+}
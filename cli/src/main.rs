@@ -2,16 +2,21 @@
 
 mod access_token;
 mod api;
+mod attestation;
 mod cli;
 mod commands;
 // mod error;
 mod html;
+mod ipfs;
 mod logger;
+mod mail;
 mod manifest;
+mod torrent;
 mod util;
+mod warc;
 
 pub use access_token::access_token;
-pub use cli::server;
+pub use cli::{api_server, server};
 // pub use error::Error;
 pub use manifest::{Manifest, PrivateManifest};
 
@@ -21,9 +26,26 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let _ = logger::init_logger(cli::cli().verbose);
 
-    access_token::init_access_token()?;
+    // Profiles and node data are managed without a running node:
+    match &cli::cli().command {
+        cli::Command::Profile { command } => return command.clone().execute(),
+        cli::Command::Node { command } => return command.clone().execute(),
+        _ => {}
+    }
+
+    // Offline, the node is replaced by its database:
+    let _scratch = if cli::cli().offline {
+        if !cli::cli().command.works_offline() {
+            anyhow::bail!("this command does not work with --offline");
+        }
+
+        Some(samizdat_node_core::offline::open(&cli::cli().data)?)
+    } else {
+        access_token::init_access_token()?;
+        api::validate_node_is_up().await?;
+        None
+    };
 
-    api::validate_node_is_up().await?;
     if let Err(err) = cli::cli().clone().command.execute().await {
         println!("Error: {err:?}");
     }
@@ -1,8 +1,11 @@
 //! The `Samizdat.toml` manifest format.
 //!
 
+use anyhow::Context;
 use askama::Template;
+use regex::Regex;
 use serde_derive::Deserialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::process::Command;
 use std::{fs, io};
@@ -20,12 +23,39 @@ pub struct ManifestTemplate<'a> {
     pub debug_name: &'a str,
 }
 
+#[derive(askama::Template)]
+#[template(path = "Samizdat.target.toml.txt")]
+pub struct TargetTemplate<'a> {
+    pub target: &'a str,
+    pub name: &'a str,
+    pub public_key: &'a Key,
+    pub debug_name: &'a str,
+}
+
+/// The manifest of a project. The top-level sections define the main target of the project;
+/// other series published from the same project (e.g., documentation next to a website) are
+/// defined as named targets, in `[targets.<name>.series]`, `[targets.<name>.build]` and so on.
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Manifest {
+    #[serde(flatten)]
+    pub main: Target,
+    #[serde(default)]
+    pub targets: BTreeMap<String, Target>,
+    /// Where this manifest was found.
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+/// A series published from a project, together with how to build it.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Target {
     pub series: Series,
     pub debug: Debug,
     pub build: Build,
+    #[serde(default)]
+    pub headers: BTreeMap<String, Headers>,
 }
 
 impl Manifest {
@@ -39,7 +69,11 @@ impl Manifest {
     pub fn find_opt() -> Result<Option<Manifest>, anyhow::Error> {
         for filename in Manifest::FILENAME_HIERARCHY {
             match fs::read(filename) {
-                Ok(contents) => return Ok(Some(toml::from_slice(&contents)?)),
+                Ok(contents) => {
+                    let mut manifest: Manifest = toml::from_slice(&contents)?;
+                    manifest.path = filename.into();
+                    return Ok(Some(manifest));
+                }
                 Err(err) if err.kind() == io::ErrorKind::NotFound => {}
                 Err(err) => return Err(err.into()),
             }
@@ -48,6 +82,40 @@ impl Manifest {
         Ok(None)
     }
 
+    /// The target with the given name, or the main target if no name is given.
+    pub fn target(&self, name: Option<&str>) -> Result<&Target, anyhow::Error> {
+        match name {
+            Some(name) => self
+                .targets
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("no target `{name}` in `Samizdat.toml`")),
+            None => Ok(&self.main),
+        }
+    }
+
+    /// The targets with the given names or, if no names are given, the main target. With
+    /// `all`, all targets, the main one first.
+    pub fn select_targets<'a>(
+        &'a self,
+        names: &'a [String],
+        all: bool,
+    ) -> Result<Vec<(Option<&'a str>, &'a Target)>, anyhow::Error> {
+        if all {
+            let named = self
+                .targets
+                .iter()
+                .map(|(name, target)| (Some(name.as_str()), target));
+            Ok([(None, &self.main)].into_iter().chain(named).collect())
+        } else if names.is_empty() {
+            Ok(vec![(None, &self.main)])
+        } else {
+            names
+                .iter()
+                .map(|name| Ok((Some(name.as_str()), self.target(Some(name))?)))
+                .collect()
+        }
+    }
+
     /// Creates a new manifest and associated debug keypair, given debug series owner name and
     /// optionally production private key.
     pub async fn create(name: &str) -> Result<(Manifest, PrivateKey), anyhow::Error> {
@@ -74,14 +142,130 @@ impl Manifest {
         .expect("can render");
 
         fs::write("./Samizdat.toml", rendered)?;
-        let manifest = toml::from_str(&fs::read_to_string("./Samizdat.toml")?)?;
+        let manifest = Manifest::find_opt()?.expect("manifest was just written");
 
         Ok((manifest, PrivateKey::from(response.keypair.secret)))
     }
 
+    /// Adds a new target to the manifest, with a new series owner named after the project and
+    /// the target. Returns the name of the debug series owner and the private key.
+    pub async fn add_target(&self, target: &str) -> Result<(String, PrivateKey), anyhow::Error> {
+        if self.targets.contains_key(target) {
+            anyhow::bail!("target `{target}` already exists in `Samizdat.toml`.");
+        }
+
+        let name = format!("{}-{target}", self.main.series.name);
+        let debug_name = format!("{name}-debug");
+
+        let response = api::post_series_owner(api::PostSeriesOwnerRequest {
+            series_owner_name: &name,
+            keypair: None,
+            is_draft: false,
+        })
+        .await?;
+
+        let rendered = TargetTemplate {
+            target,
+            name: &name,
+            public_key: &Key::from(response.keypair.public),
+            debug_name: &debug_name,
+        }
+        .render()
+        .expect("can render");
+
+        let mut contents = fs::read_to_string(&self.path)?;
+        contents += "\n";
+        contents += &rendered;
+        fs::write(&self.path, contents)?;
+
+        Ok((debug_name, PrivateKey::from(response.keypair.secret)))
+    }
+}
+
+impl Target {
     pub fn run_build(&self, is_release: bool) -> Result<(), anyhow::Error> {
         self.build.run(&self.series.public_key, is_release)
     }
+
+    /// Compiles the patterns of the `[headers]` section, from the shortest to the longest.
+    pub fn header_rules(&self) -> Result<Vec<(Regex, &Headers)>, anyhow::Error> {
+        let mut rules = self.headers.iter().collect::<Vec<_>>();
+        rules.sort_by_key(|(pattern, _)| pattern.len());
+
+        rules
+            .into_iter()
+            .map(|(pattern, headers)| {
+                let regex = Regex::new(&glob_to_regex(pattern))
+                    .with_context(|| format!("bad pattern in `[headers]`: {pattern:?}"))?;
+                Ok((regex, headers))
+            })
+            .collect()
+    }
+}
+
+/// Translates a glob pattern into a regular expression matching whole paths. Patterns without
+/// a slash match file names in any directory.
+fn glob_to_regex(pattern: &str) -> String {
+    let escaped = regex::escape(pattern.trim_start_matches('/'))
+        .replace(r"\*\*", ".*")
+        .replace(r"\*", "[^/]*");
+
+    if pattern.contains('/') {
+        format!("^{escaped}$")
+    } else {
+        format!("^(.*/)?{escaped}$")
+    }
+}
+
+/// Information on how the files matching a pattern are served.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Headers {
+    pub cache_control: Option<String>,
+    pub content_language: Option<String>,
+    pub charset: Option<String>,
+    /// Overrides the `Content-Security-Policy` set by the node. Empty for none.
+    pub content_security_policy: Option<String>,
+    /// Overrides the `X-Frame-Options` set by the node. Empty for none.
+    pub frame_options: Option<String>,
+    /// Overrides the `Referrer-Policy` set by the node. Empty for none.
+    pub referrer_policy: Option<String>,
+}
+
+impl Headers {
+    /// Merges the settings of all rules matching a path. Later rules override earlier ones.
+    pub fn for_path(rules: &[(Regex, &Headers)], path: &str) -> Option<Headers> {
+        let mut merged = None::<Headers>;
+
+        for (regex, headers) in rules {
+            if regex.is_match(path) {
+                let merged = merged.get_or_insert_with(Headers::default);
+                merged.cache_control = headers
+                    .cache_control
+                    .clone()
+                    .or(merged.cache_control.take());
+                merged.content_language = headers
+                    .content_language
+                    .clone()
+                    .or(merged.content_language.take());
+                merged.charset = headers.charset.clone().or(merged.charset.take());
+                merged.content_security_policy = headers
+                    .content_security_policy
+                    .clone()
+                    .or(merged.content_security_policy.take());
+                merged.frame_options = headers
+                    .frame_options
+                    .clone()
+                    .or(merged.frame_options.take());
+                merged.referrer_policy = headers
+                    .referrer_policy
+                    .clone()
+                    .or(merged.referrer_policy.take());
+            }
+        }
+
+        merged
+    }
 }
 
 #[derive(Deserialize)]
@@ -90,6 +274,21 @@ pub struct Series {
     pub name: String,
     pub public_key: String,
     pub ttl: Option<String>,
+    /// Categories of sensitive content (e.g., `nsfw`) to flag every edition with.
+    #[serde(default)]
+    pub content_warnings: Vec<String>,
+    /// Shard the inventory of every edition in shards of at most this many items, so that
+    /// subscribers can go through huge collections one shard at a time.
+    pub inventory_shard_size: Option<usize>,
+    /// Items for subscribers to fetch before the rest of each edition (e.g., `index.html` and
+    /// its stylesheets), in order.
+    #[serde(default)]
+    pub prefetch: Vec<String>,
+    /// Other collections (e.g., shared fonts and frameworks) to mount in every edition, by
+    /// path prefix. Items under a prefix are served from the mounted collection, unless the
+    /// edition has an item with the same path.
+    #[serde(default)]
+    pub mounts: BTreeMap<String, api::Mount>,
 }
 
 #[derive(Deserialize)]
@@ -111,19 +310,37 @@ pub struct Build {
     pub run_debug: Option<String>,
     #[serde(default = "default_shell")]
     pub shell: String,
+    /// Embed in each edition an attestation of how it was built, signed by the series key,
+    /// which `samizdat verify-build` checks by building again.
+    #[serde(default)]
+    pub attest: bool,
+    /// Commands describing the tools used in the build (e.g., `node --version`), whose outputs
+    /// are recorded in attestations.
+    #[serde(default)]
+    pub toolchain: Vec<String>,
+    /// Paths left out of the hash of the sources in attestations (e.g., `node_modules`),
+    /// besides `base` and hidden files.
+    #[serde(default)]
+    pub source_exclude: Vec<PathBuf>,
 }
 
 impl Build {
-    pub fn run(&self, public_key: &str, is_release: bool) -> Result<(), anyhow::Error> {
+    /// The build command for release or debug mode.
+    pub fn script(&self, is_release: bool) -> &str {
         let script = if is_release {
             self.run.as_ref()
         } else {
             self.run.as_ref().or_else(|| self.run_debug.as_ref())
         };
+
+        script.map(String::as_str).unwrap_or_default()
+    }
+
+    pub fn run(&self, public_key: &str, is_release: bool) -> Result<(), anyhow::Error> {
         let mut command = Command::new(&self.shell);
         command
             .arg("-c")
-            .arg(script.map(String::as_str).unwrap_or_default())
+            .arg(self.script(is_release))
             .env("SAMIZDAT_PUBLIC_KEY", public_key)
             .env("SAMIZDAT_RELEASE", if is_release { "release" } else { "" });
 
@@ -140,6 +357,31 @@ impl Build {
             ))
         }
     }
+
+    /// Describes the tools used in the build: the shell, the platform and the output of each
+    /// command in `toolchain`, one per line.
+    pub fn toolchain_fingerprint(&self) -> Result<String, anyhow::Error> {
+        let mut lines = vec![
+            format!("shell: {}", self.shell),
+            format!(
+                "platform: {}-{}",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            ),
+        ];
+
+        for tool in &self.toolchain {
+            let output = Command::new(&self.shell)
+                .arg("-c")
+                .arg(tool)
+                .output()
+                .with_context(|| format!("failed to run toolchain command {tool:?}"))?;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            lines.push(format!("{tool}: {}", stdout.trim()));
+        }
+
+        Ok(lines.join("\n"))
+    }
 }
 
 #[derive(askama::Template)]
@@ -150,9 +392,19 @@ pub struct PrivateManifestTemplate<'a> {
     pub public_key_debug: &'a Key,
 }
 
+/// The private counterpart of [`Manifest`], with the keys of each target.
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct PrivateManifest {
+    #[serde(flatten)]
+    pub main: PrivateTarget,
+    #[serde(default)]
+    pub targets: BTreeMap<String, PrivateTarget>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PrivateTarget {
     pub private_key: Option<String>,
     pub private_key_debug: String,
     /// If `private_key_debug` is set, then also is this field.
@@ -175,24 +427,32 @@ impl PrivateManifest {
         Ok(None)
     }
 
-    /// Creates a new manifest and associated debug keypair, given debug series owner name and
-    /// optionally production private key.
-    pub async fn create(
-        debug_name: &str,
-        private_key: Option<&PrivateKey>,
-    ) -> Result<PrivateManifest, anyhow::Error> {
-        if PrivateManifest::find_opt()?.is_some() {
-            anyhow::bail!("`.Samizdat.priv` already exists.");
+    /// The keys of the target with the given name, or of the main target if no name is given.
+    pub fn target(&self, name: Option<&str>) -> Result<&PrivateTarget, anyhow::Error> {
+        match name {
+            Some(name) => self.targets.get(name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "no keys for target `{name}` in `.Samizdat.priv`. Hint: run `samizdat \
+                    init --target {name}`."
+                )
+            }),
+            None => Ok(&self.main),
         }
+    }
 
+    /// Creates the debug series owner of a target, rendering its keys.
+    async fn render(
+        debug_name: &str,
+        private_key: Option<&PrivateKey>,
+    ) -> Result<String, anyhow::Error> {
         let response = api::post_series_owner(api::PostSeriesOwnerRequest {
-            series_owner_name: &debug_name,
+            series_owner_name: debug_name,
             keypair: None,
             is_draft: true,
         })
         .await?;
 
-        let rendered_private = crate::manifest::PrivateManifestTemplate {
+        let rendered = crate::manifest::PrivateManifestTemplate {
             private_key,
             private_key_debug: &PrivateKey::from(response.keypair.secret),
             public_key_debug: &Key::from(response.keypair.public),
@@ -200,9 +460,40 @@ impl PrivateManifest {
         .render()
         .expect("can render");
 
+        Ok(rendered)
+    }
+
+    /// Creates a new manifest and associated debug keypair, given debug series owner name and
+    /// optionally production private key.
+    pub async fn create(
+        debug_name: &str,
+        private_key: Option<&PrivateKey>,
+    ) -> Result<PrivateManifest, anyhow::Error> {
+        if PrivateManifest::find_opt()?.is_some() {
+            anyhow::bail!("`.Samizdat.priv` already exists.");
+        }
+
+        let rendered_private = PrivateManifest::render(debug_name, private_key).await?;
+
         fs::write("./.Samizdat.priv", rendered_private)?;
         let manifest = toml::from_str(&fs::read_to_string("./.Samizdat.priv")?)?;
 
         Ok(manifest)
     }
+
+    /// Adds the keys of a new target to the private manifest, creating its debug keypair.
+    pub async fn add_target(
+        target: &str,
+        debug_name: &str,
+        private_key: Option<&PrivateKey>,
+    ) -> Result<(), anyhow::Error> {
+        let rendered_private = PrivateManifest::render(debug_name, private_key).await?;
+
+        let mut contents = fs::read_to_string("./.Samizdat.priv")?;
+        contents += &format!("\n[targets.{target}]\n");
+        contents += &rendered_private;
+        fs::write("./.Samizdat.priv", contents)?;
+
+        Ok(())
+    }
 }
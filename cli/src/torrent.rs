@@ -0,0 +1,198 @@
+//! Reading of `.torrent` files and checking of downloaded content against them.
+
+use anyhow::Context;
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+
+/// A bencoded value.
+#[derive(Debug)]
+enum Bencode {
+    Integer(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Bencode>),
+    Dictionary(BTreeMap<Vec<u8>, Bencode>),
+}
+
+impl Bencode {
+    fn decode(input: &mut &[u8]) -> Result<Bencode, anyhow::Error> {
+        let (&first, rest) = input.split_first().context("unexpected end of bencode")?;
+
+        match first {
+            b'i' => {
+                let end = rest
+                    .iter()
+                    .position(|&byte| byte == b'e')
+                    .context("unterminated integer")?;
+                let integer = std::str::from_utf8(&rest[..end])?.parse()?;
+                *input = &rest[end + 1..];
+                Ok(Bencode::Integer(integer))
+            }
+            b'l' | b'd' => {
+                *input = rest;
+                let mut items = Vec::new();
+                while input.first() != Some(&b'e') {
+                    items.push(Bencode::decode(input)?);
+                }
+                *input = &input[1..];
+
+                if first == b'l' {
+                    return Ok(Bencode::List(items));
+                }
+
+                anyhow::ensure!(items.len() % 2 == 0, "dictionary key without value");
+                let mut dictionary = BTreeMap::new();
+                let mut items = items.into_iter();
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    match key {
+                        Bencode::Bytes(key) => dictionary.insert(key, value),
+                        _ => anyhow::bail!("dictionary key is not a string"),
+                    };
+                }
+
+                Ok(Bencode::Dictionary(dictionary))
+            }
+            b'0'..=b'9' => {
+                let colon = input
+                    .iter()
+                    .position(|&byte| byte == b':')
+                    .context("string without length")?;
+                let length: usize = std::str::from_utf8(&input[..colon])?.parse()?;
+                let start = colon + 1;
+                anyhow::ensure!(input.len() >= start + length, "truncated string");
+                let bytes = input[start..start + length].to_vec();
+                *input = &input[start + length..];
+                Ok(Bencode::Bytes(bytes))
+            }
+            other => anyhow::bail!("unexpected byte {other:#x} in bencode"),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Bencode> {
+        match self {
+            Bencode::Dictionary(dictionary) => dictionary.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+
+    fn as_integer(&self) -> Option<i64> {
+        match self {
+            Bencode::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Bencode::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    fn as_string(&self) -> Option<String> {
+        self.as_bytes()
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// A single-file torrent.
+#[derive(Debug)]
+pub struct Torrent {
+    /// The suggested name of the file.
+    pub name: String,
+    length: usize,
+    piece_length: usize,
+    pieces: Vec<[u8; 20]>,
+    /// The web seeds of the torrent, from which the file can be downloaded over HTTP.
+    pub webseeds: Vec<String>,
+    /// The Samizdat object this torrent was created from, if any.
+    pub object: Option<String>,
+}
+
+impl Torrent {
+    pub fn parse(mut encoded: &[u8]) -> Result<Torrent, anyhow::Error> {
+        let torrent = Bencode::decode(&mut encoded)?;
+        let info = torrent.get("info").context("torrent has no info")?;
+
+        if info.get("files").is_some() {
+            anyhow::bail!("only single-file torrents are supported");
+        }
+
+        let pieces = info
+            .get("pieces")
+            .and_then(Bencode::as_bytes)
+            .context("torrent has no pieces")?;
+        anyhow::ensure!(pieces.len() % 20 == 0, "bad piece hashes in torrent");
+
+        let webseeds = match torrent.get("url-list") {
+            Some(Bencode::List(list)) => list.iter().filter_map(Bencode::as_string).collect(),
+            Some(url) => url.as_string().into_iter().collect(),
+            None => vec![],
+        };
+
+        Ok(Torrent {
+            name: info
+                .get("name")
+                .and_then(Bencode::as_string)
+                .context("torrent has no name")?,
+            length: info
+                .get("length")
+                .and_then(Bencode::as_integer)
+                .context("torrent has no length")?
+                .try_into()?,
+            piece_length: info
+                .get("piece length")
+                .and_then(Bencode::as_integer)
+                .filter(|&length| length > 0)
+                .context("torrent has no piece length")?
+                .try_into()?,
+            pieces: pieces
+                .chunks(20)
+                .map(|piece| piece.try_into().expect("chunk has 20 bytes"))
+                .collect(),
+            webseeds,
+            object: torrent
+                .get("samizdat")
+                .and_then(|samizdat| samizdat.get("object"))
+                .and_then(Bencode::as_string),
+        })
+    }
+
+    /// Checks whether some content is the file of this torrent.
+    pub fn check(&self, content: &[u8]) -> Result<(), anyhow::Error> {
+        anyhow::ensure!(
+            content.len() == self.length,
+            "content has {} bytes, but torrent has {}",
+            content.len(),
+            self.length
+        );
+
+        let pieces = content.chunks(self.piece_length).collect::<Vec<_>>();
+        anyhow::ensure!(
+            pieces.len() == self.pieces.len(),
+            "torrent has the wrong number of pieces"
+        );
+
+        for (i, (piece, expected)) in pieces.iter().zip(&self.pieces).enumerate() {
+            anyhow::ensure!(
+                Sha1::digest(piece)[..] == expected[..],
+                "piece {i} does not match the torrent"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn checks_torrents() {
+    let content = b"hello, torrent";
+    let mut encoded = b"d4:infod6:lengthi14e4:name5:hello12:piece lengthi8e6:pieces40:".to_vec();
+    encoded.extend(Sha1::digest(&content[..8]));
+    encoded.extend(Sha1::digest(&content[8..]));
+    encoded.extend(b"ee");
+
+    let torrent = Torrent::parse(&encoded).unwrap();
+    assert_eq!(torrent.name, "hello");
+    assert!(torrent.check(content).is_ok());
+    assert!(torrent.check(b"hello, torrenT").is_err());
+}
@@ -0,0 +1,273 @@
+//! Reading of web archives in the WARC format, as written by standard crawlers, either plain,
+//! gzipped or packaged in a WACZ file.
+
+use anyhow::Context;
+use flate2::bufread::{MultiGzDecoder, ZlibDecoder};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use tokio::sync::mpsc;
+
+/// A record in a WARC file.
+#[derive(Debug)]
+pub struct Record {
+    headers: Vec<(String, String)>,
+    block: Vec<u8>,
+}
+
+/// A page captured from the web, as stored in a WARC response record.
+#[derive(Debug)]
+pub struct Capture {
+    /// The URL of the page.
+    pub uri: String,
+    /// The content type sent by the server, if any.
+    pub content_type: Option<String>,
+    /// The content, already without transfer and content encodings.
+    pub body: Vec<u8>,
+}
+
+impl Record {
+    /// Gets the value of a header of this record.
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The page captured in this record, if this is a successful HTTP response. Redirects,
+    /// errors, requests, metadata and revisits are not captures.
+    pub fn capture(&self) -> Result<Option<Capture>, anyhow::Error> {
+        let is_response = self.header("WARC-Type") == Some("response")
+            && self
+                .header("Content-Type")
+                .is_some_and(|content_type| content_type.starts_with("application/http"));
+        let uri = match (is_response, self.header("WARC-Target-URI")) {
+            (true, Some(uri)) => uri.trim_matches(|c| c == '<' || c == '>').to_owned(),
+            _ => return Ok(None),
+        };
+
+        let (head, payload) = split_head(&self.block)
+            .with_context(|| format!("response for {uri} has no end of headers"))?;
+        let mut lines = head.lines();
+        let status = lines
+            .next()
+            .and_then(|status_line| status_line.split_whitespace().nth(1))
+            .with_context(|| format!("response for {uri} has no status"))?;
+
+        if status != "200" {
+            return Ok(None);
+        }
+
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_owned()))
+            .collect::<Vec<_>>();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let payload = if header("transfer-encoding").is_some_and(|te| te.contains("chunked")) {
+            dechunk(payload).with_context(|| format!("bad chunked response for {uri}"))?
+        } else {
+            payload.to_vec()
+        };
+
+        let body = match header("content-encoding")
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            None | Some("identity") => payload,
+            Some("gzip" | "x-gzip") => decode(MultiGzDecoder::new(&*payload))?,
+            Some("deflate") => decode(ZlibDecoder::new(&*payload))?,
+            Some(other) => anyhow::bail!("unsupported content encoding {other:?} for {uri}"),
+        };
+
+        Ok(Some(Capture {
+            uri,
+            content_type: header("content-type").map(str::to_owned),
+            body,
+        }))
+    }
+}
+
+/// Splits an HTTP message in its head and its payload.
+fn split_head(message: &[u8]) -> Option<(String, &[u8])> {
+    let (end, separator) = [&b"\r\n\r\n"[..], &b"\n\n"[..]]
+        .into_iter()
+        .filter_map(|separator| {
+            message
+                .windows(separator.len())
+                .position(|window| window == separator)
+                .map(|position| (position, separator.len()))
+        })
+        .min()?;
+
+    Some((
+        String::from_utf8_lossy(&message[..end]).into_owned(),
+        &message[end + separator..],
+    ))
+}
+
+/// Undoes the chunked transfer encoding.
+fn dechunk(mut payload: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut body = Vec::new();
+
+    loop {
+        let line_end = payload
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .context("missing chunk size")?;
+        let size_line = String::from_utf8_lossy(&payload[..line_end]);
+        let size = usize::from_str_radix(size_line.split(';').next().unwrap_or("").trim(), 16)
+            .with_context(|| format!("bad chunk size {size_line:?}"))?;
+        payload = &payload[line_end + 1..];
+
+        if size == 0 {
+            return Ok(body);
+        }
+
+        anyhow::ensure!(payload.len() >= size, "truncated chunk");
+        body.extend_from_slice(&payload[..size]);
+        payload = &payload[size..];
+        payload = payload
+            .strip_prefix(b"\r\n")
+            .or_else(|| payload.strip_prefix(b"\n"))
+            .unwrap_or(payload);
+    }
+}
+
+/// Reads a whole decoder into memory.
+fn decode(mut decoder: impl Read) -> Result<Vec<u8>, anyhow::Error> {
+    let mut decoded = Vec::new();
+    decoder
+        .read_to_end(&mut decoded)
+        .context("failed to decode response")?;
+    Ok(decoded)
+}
+
+/// Reads one record from a WARC stream, if there is any left.
+fn read_record(reader: &mut impl BufRead) -> Result<Option<Record>, anyhow::Error> {
+    let mut line = String::new();
+
+    // Skip the blank lines between records:
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if !line.trim().is_empty() {
+            break;
+        }
+    }
+
+    anyhow::ensure!(
+        line.starts_with("WARC/"),
+        "expected WARC record, got {:?}",
+        line.trim()
+    );
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let trimmed = line.trim_end();
+
+        if trimmed.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = trimmed.split_once(':') {
+            headers.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+
+    let mut record = Record {
+        headers,
+        block: Vec::new(),
+    };
+    let length = record
+        .header("Content-Length")
+        .and_then(|length| length.parse::<u64>().ok())
+        .context("WARC record without Content-Length")?;
+    reader
+        .take(length)
+        .read_to_end(&mut record.block)
+        .context("failed to read WARC record")?;
+
+    Ok(Some(record))
+}
+
+/// Sends all records of a WARC stream.
+fn send_all(mut reader: impl BufRead, sender: &mpsc::Sender<Record>) -> Result<(), anyhow::Error> {
+    while let Some(record) = read_record(&mut reader)? {
+        if sender.blocking_send(record).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends all records of a WARC stream, which may be gzipped.
+fn send_records(
+    mut reader: impl BufRead,
+    sender: &mpsc::Sender<Record>,
+) -> Result<(), anyhow::Error> {
+    if reader.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        send_all(BufReader::new(MultiGzDecoder::new(reader)), sender)
+    } else {
+        send_all(reader, sender)
+    }
+}
+
+/// Reads all records of a WARC file (plain or gzipped) or of all the WARC files in a WACZ file,
+/// sending them down a channel. This blocks, so run it apart from the async code.
+pub fn read_records(path: &Path, sender: mpsc::Sender<Record>) -> Result<(), anyhow::Error> {
+    let file = File::open(path).with_context(|| format!("failed to open {path:?}"))?;
+
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "wacz")
+    {
+        let mut wacz = zip::ZipArchive::new(file).context("failed to open WACZ")?;
+        let mut warcs = wacz
+            .file_names()
+            .filter(|name| {
+                name.starts_with("archive/")
+                    && (name.ends_with(".warc") || name.ends_with(".warc.gz"))
+            })
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        warcs.sort();
+
+        for name in warcs {
+            let warc = wacz.by_name(&name)?;
+            send_records(BufReader::new(warc), &sender)
+                .with_context(|| format!("failed to read {name} in WACZ"))?;
+        }
+
+        Ok(())
+    } else {
+        send_records(BufReader::new(file), &sender)
+    }
+}
+
+#[test]
+fn reads_captures() {
+    let warc = "WARC/1.0\r\nWARC-Type: response\r\nWARC-Target-URI: http://example.com/\r\n\
+        Content-Type: application/http; msgtype=response\r\nContent-Length: 87\r\n\r\n\
+        HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nTransfer-Encoding: chunked\r\n\r\n\
+        5\r\nhello\r\n0\r\n\r\n\r\n\r\n";
+    let record = read_record(&mut std::io::Cursor::new(warc))
+        .unwrap()
+        .expect("there is a record");
+    let capture = record.capture().unwrap().expect("record is a capture");
+
+    assert_eq!(capture.uri, "http://example.com/");
+    assert_eq!(capture.content_type.as_deref(), Some("text/html"));
+    assert_eq!(capture.body, b"hello");
+}
@@ -0,0 +1,223 @@
+//! Descriptions of HTTP APIs as OpenAPI documents, served at `/_api-docs/openapi.json` and
+//! listed in a page at `/_api-docs`.
+//!
+//! Warp filters cannot be inspected, so each API lists its routes as [`Endpoint`]s, next to
+//! its entrypoint. A test of each API checks with [`unlisted_routes`] that no route is missing
+//! from the list.
+
+use serde_json::{json, Map, Value};
+use warp::Filter;
+
+/// A route of an HTTP API.
+#[derive(Debug, Clone, Copy)]
+pub struct Endpoint {
+    /// The HTTP method, in lowercase.
+    pub method: &'static str,
+    /// The path of the route, with parameters in braces, e.g. `/_objects/{hash}`. A
+    /// parameter named `path` stands for the rest of the path, slashes included.
+    pub path: &'static str,
+    /// What the route does.
+    pub summary: &'static str,
+    /// The access rights (any of which) an application needs to use the route. `None` if the
+    /// route is public and `Some(&[])` if only the access token will do.
+    pub rights: Option<&'static [&'static str]>,
+}
+
+impl Endpoint {
+    /// The names of the parameters in the path.
+    fn parameters(&self) -> impl '_ + Iterator<Item = &'static str> {
+        self.path.split('/').filter_map(|segment| {
+            segment
+                .strip_prefix('{')
+                .and_then(|segment| segment.strip_suffix('}'))
+        })
+    }
+
+    fn operation(&self) -> Value {
+        let mut operation = json!({
+            "summary": self.summary,
+            "parameters": self.parameters().map(|name| json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": { "type": "string" },
+            })).collect::<Vec<_>>(),
+            "responses": {
+                "default": { "description": "The result, as JSON, or the content requested." },
+            },
+        });
+
+        if let Some(rights) = self.rights {
+            operation["security"] = json!([{ "token": [] }]);
+            operation["description"] = if rights.is_empty() {
+                json!("Needs the access token.")
+            } else {
+                json!(format!(
+                    "Needs the access token or an application granted any of: {}.",
+                    rights.join(", ")
+                ))
+            };
+        }
+
+        operation
+    }
+}
+
+/// The OpenAPI document describing an API.
+pub fn document(title: &str, version: &str, endpoints: &[Endpoint]) -> Value {
+    let mut paths = Map::new();
+
+    for endpoint in endpoints {
+        let path = paths
+            .entry(endpoint.path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        path[endpoint.method] = endpoint.operation();
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": title, "version": version },
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "token": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
+
+/// Escapes text to be put in HTML.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The page listing the routes of an API. The page is self-contained, so that it works in
+/// nodes without an Internet connection.
+pub fn page(title: &str, endpoints: &[Endpoint]) -> String {
+    let rows = endpoints
+        .iter()
+        .map(|endpoint| {
+            let rights = match endpoint.rights {
+                None => "public".to_owned(),
+                Some([]) => "access token".to_owned(),
+                Some(rights) => rights.join(", "),
+            };
+            format!(
+                "      <tr><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td></tr>\n",
+                endpoint.method.to_uppercase(),
+                escape_html(endpoint.path),
+                escape_html(&rights),
+                escape_html(endpoint.summary),
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        r##"<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset="UTF-8">
+    <title>{title} API</title>
+    <style>
+      body {{ font-family: sans-serif; margin: 2em; }}
+      table {{ border-collapse: collapse; }}
+      td, th {{ border-bottom: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }}
+    </style>
+  </head>
+  <body>
+    <h1>{title} API</h1>
+    <p>The OpenAPI document is at <a href="/_api-docs/openapi.json">/_api-docs/openapi.json</a>.</p>
+    <table>
+      <tr><th>Method</th><th>Path</th><th>Needs</th><th>Summary</th></tr>
+{rows}    </table>
+  </body>
+</html>
+"##,
+        title = escape_html(title),
+    )
+}
+
+/// The paths of the routes declared with `warp::path!` in some source files that are not
+/// listed among the endpoints of an API, with parameters as `{}`. Tests of each API use this
+/// to keep the list of endpoints in sync with the routes.
+pub fn unlisted_routes(sources: &[&str], endpoints: &[Endpoint]) -> Vec<String> {
+    let normalize = |path: &str| {
+        path.split('/')
+            .map(|segment| {
+                if segment.starts_with('{') {
+                    "{}"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+    let listed = endpoints
+        .iter()
+        .map(|endpoint| normalize(endpoint.path))
+        .collect::<Vec<_>>();
+
+    sources
+        .iter()
+        .flat_map(|source| source.lines())
+        .filter(|line| !line.trim_start().starts_with("//"))
+        .filter_map(|line| {
+            let (_, route) = line.split_once("warp::path!(")?;
+            let (route, _) = route.split_once(')')?;
+            let path = route
+                .split('/')
+                .map(|segment| {
+                    let segment = segment.trim();
+                    segment
+                        .strip_prefix('"')
+                        .and_then(|segment| segment.strip_suffix('"'))
+                        .unwrap_or("{}")
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            Some(format!("/{path}"))
+        })
+        .filter(|path| !listed.contains(path))
+        .collect()
+}
+
+/// Serves the OpenAPI document of an API and the page listing its routes.
+pub fn serve(
+    title: &'static str,
+    version: &'static str,
+    endpoints: &'static [Endpoint],
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let page = page(title, endpoints);
+    let ui = warp::path!("_api-docs")
+        .and(warp::get())
+        .map(move || warp::reply::html(page.clone()));
+    let openapi = warp::path!("_api-docs" / "openapi.json")
+        .and(warp::get())
+        .map(move || warp::reply::json(&document(title, version, endpoints)));
+
+    ui.or(openapi)
+}
+
+#[test]
+fn test_unlisted_routes() {
+    let endpoints = [Endpoint {
+        method: "get",
+        path: "/_objects/{hash}",
+        summary: "",
+        rights: None,
+    }];
+    let source = r#"
+        warp::path!("_objects" / Hash).and(warp::get())
+        // warp::path!("_commented" / Hash)
+        warp::path!("_series" / Key / ..)
+    "#;
+
+    assert_eq!(
+        unlisted_routes(&[source], &endpoints),
+        vec!["/_series/{}/{}"]
+    );
+}
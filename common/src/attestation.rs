@@ -0,0 +1,27 @@
+//! Build attestations, for reproducible publication audits. Publishers may embed in each
+//! edition, under [`ATTESTATION_ITEM`], an attestation of how the edition was built, signed by
+//! the key of the series. Anyone with the same sources can then re-run the build and check that
+//! it gives the same outputs.
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{Hash, Key};
+
+/// The item of a collection holding the JSON of the [`Signed`](crate::Signed) attestation of
+/// how the collection was built.
+pub const ATTESTATION_ITEM: &str = "_build";
+
+/// How the content of an edition was built.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildAttestation {
+    /// The series that signed this attestation.
+    pub series: Key,
+    /// The build command, as run by the shell.
+    pub command: String,
+    /// The hash of the source tree before the build.
+    pub source: Hash,
+    /// A description of the tools used in the build, e.g., their versions.
+    pub toolchain: String,
+    /// The hash of the tree of outputs of the build.
+    pub outputs: Hash,
+}
@@ -0,0 +1,135 @@
+//! Rotating Bloom filters, to remember what was seen recently in constant memory. This is used
+//! to drop messages that loop around the network, such as edition announcements re-broadcast
+//! between interconnected hubs.
+
+use crate::Hash;
+
+/// A plain Bloom filter over hashes.
+#[derive(Debug, Clone)]
+struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    fn new(n_bits: usize) -> BloomFilter {
+        BloomFilter {
+            bits: vec![0; n_bits / 64 + 1],
+        }
+    }
+
+    fn contains(&self, positions: &[usize]) -> bool {
+        positions
+            .iter()
+            .all(|&position| self.bits[position / 64] & (1 << (position % 64)) != 0)
+    }
+
+    fn insert(&mut self, positions: &[usize]) {
+        for &position in positions {
+            self.bits[position / 64] |= 1 << (position % 64);
+        }
+    }
+}
+
+/// A pair of Bloom filters, of which the older is discarded once the newer is full. Items are
+/// remembered for at least `capacity` insertions and at most twice as many.
+///
+/// Bit positions are taken from the hash of the item salted with a random value, so that nobody
+/// can craft items to collide with each other in a given filter.
+#[derive(Debug, Clone)]
+pub struct RotatingBloomFilter {
+    current: BloomFilter,
+    previous: BloomFilter,
+    /// The number of items inserted in the current filter.
+    inserted: usize,
+    /// The number of items inserted in each filter before rotation.
+    capacity: usize,
+    /// The number of bits in each filter.
+    n_bits: usize,
+    /// The number of bit positions set for each item.
+    n_positions: usize,
+    salt: Hash,
+}
+
+impl RotatingBloomFilter {
+    /// Creates a filter remembering at least `capacity` items, with the given rate of false
+    /// positives.
+    pub fn new(capacity: usize, false_positive_rate: f64) -> RotatingBloomFilter {
+        let capacity = capacity.max(1);
+        // The optimal sizes, from the literature:
+        let ln_2 = std::f64::consts::LN_2;
+        let n_bits = (-(capacity as f64) * false_positive_rate.ln() / (ln_2 * ln_2)).ceil();
+        let n_positions = (n_bits / capacity as f64 * ln_2).round().max(1.0);
+        let n_bits = n_bits as usize;
+
+        RotatingBloomFilter {
+            current: BloomFilter::new(n_bits),
+            previous: BloomFilter::new(n_bits),
+            inserted: 0,
+            capacity,
+            n_bits,
+            n_positions: n_positions as usize,
+            salt: Hash::rand(),
+        }
+    }
+
+    /// The bit positions of an item, by double hashing.
+    fn positions(&self, item: &Hash) -> Vec<usize> {
+        let salted = item.rehash(&self.salt);
+        let first = u64::from_le_bytes(salted[..8].try_into().expect("has 8 bytes"));
+        let second = u64::from_le_bytes(salted[8..16].try_into().expect("has 8 bytes")) | 1;
+        let n_bits = self.n_bits as u64;
+
+        (0..self.n_positions as u64)
+            .map(|i| (first.wrapping_add(i.wrapping_mul(second)) % n_bits) as usize)
+            .collect()
+    }
+
+    /// Whether an item was (probably) seen recently.
+    pub fn contains(&self, item: &Hash) -> bool {
+        let positions = self.positions(item);
+        self.current.contains(&positions) || self.previous.contains(&positions)
+    }
+
+    /// Remembers an item, returning `true` if it was not seen recently. Once in a while, an
+    /// item never seen before is mistaken for one already seen, with the configured rate of
+    /// false positives.
+    pub fn insert(&mut self, item: &Hash) -> bool {
+        let positions = self.positions(item);
+
+        if self.current.contains(&positions) || self.previous.contains(&positions) {
+            return false;
+        }
+
+        if self.inserted >= self.capacity {
+            self.previous = std::mem::replace(&mut self.current, BloomFilter::new(self.n_bits));
+            self.inserted = 0;
+        }
+
+        self.current.insert(&positions);
+        self.inserted += 1;
+
+        true
+    }
+}
+
+#[test]
+fn remembers_recent_items() {
+    let mut filter = RotatingBloomFilter::new(100, 1e-6);
+    let items = (0..300).map(|_| Hash::rand()).collect::<Vec<_>>();
+
+    for item in &items[..100] {
+        assert!(filter.insert(item));
+    }
+
+    for item in &items[..100] {
+        assert!(!filter.insert(item));
+    }
+
+    // After two rotations, the first items are forgotten:
+    for item in &items[100..] {
+        assert!(filter.insert(item));
+    }
+
+    assert!(filter.contains(&items[299]));
+    assert!(filter.insert(&items[0]));
+}
@@ -1,7 +1,9 @@
 use base64_url::base64;
 use failure_derive::Fail;
 use std::io;
+use std::time::Duration;
 use tarpc::client::RpcError;
+use warp::http::StatusCode;
 
 #[derive(Debug, Fail)]
 #[non_exhaustive]
@@ -24,6 +26,8 @@ pub enum Error {
     QuicConnectionError(quinn::ConnectionError),
     #[fail(display = "All candidates failed")]
     AllCandidatesFailed,
+    #[fail(display = "no peer answered")]
+    NoCandidates,
     #[fail(display = "invalid collection item")]
     InvalidCollectionItem,
     #[fail(display = "invalid edition")]
@@ -34,6 +38,107 @@ pub enum Error {
     NoHeaderRead,
     #[fail(display = "timeout")]
     Timeout,
+    #[fail(display = "not found: {}", _0)]
+    NotFound(String),
+    #[fail(display = "validation failed: {}", _0)]
+    ValidationFailed(String),
+    #[fail(display = "peer misbehavior: {}", _0)]
+    PeerMisbehavior(String),
+    #[fail(display = "storage error: {}", _0)]
+    Storage(String),
+    #[fail(display = "refused: {}", _0)]
+    Refused(String),
+    #[fail(display = "quota exceeded: {}", _0)]
+    QuotaExceeded(String),
+    #[fail(display = "overloaded: retry after {:?}", retry_after)]
+    Overloaded { retry_after: Duration },
+    #[fail(display = "work required: {}", work)]
+    WorkRequired { work: f64 },
+    #[fail(
+        display = "key of hub {} changed from {} to {}; if this is expected, accept the new key \
+        with `samizdat hub accept-key {}`",
+        hub, pinned, presented, hub
+    )]
+    HubKeyChanged {
+        hub: String,
+        pinned: String,
+        presented: String,
+    },
+}
+
+/// What to do with an operation that failed with a given error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryPolicy {
+    /// Trying again will fail the same way.
+    Never,
+    /// Try again right away with another peer or hub.
+    Elsewhere,
+    /// Try again later, with backoff.
+    Later,
+}
+
+impl Error {
+    /// The HTTP status code that best describes this error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::Message(_) => StatusCode::BAD_REQUEST,
+            Error::Rpc(_) => StatusCode::BAD_GATEWAY,
+            Error::Base64(_) => StatusCode::BAD_REQUEST,
+            Error::Db(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::BadHashLength(_) => StatusCode::BAD_REQUEST,
+            Error::Bincode(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::QuicConnectionError(_) => StatusCode::BAD_GATEWAY,
+            Error::AllCandidatesFailed => StatusCode::BAD_GATEWAY,
+            Error::NoCandidates => StatusCode::NOT_FOUND,
+            Error::InvalidCollectionItem => StatusCode::BAD_REQUEST,
+            Error::InvalidEdition => StatusCode::BAD_REQUEST,
+            Error::DifferentPublicKeys => StatusCode::BAD_REQUEST,
+            Error::NoHeaderRead => StatusCode::BAD_GATEWAY,
+            Error::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::ValidationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::PeerMisbehavior(_) => StatusCode::BAD_GATEWAY,
+            Error::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::QuotaExceeded(_) => StatusCode::INSUFFICIENT_STORAGE,
+            Error::Refused(_) => StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            Error::Overloaded { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Error::WorkRequired { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Error::HubKeyChanged { .. } => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// Whether and how the operation that failed with this error should be retried.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        match self {
+            // Nobody knows what went wrong; maybe someone else can do better.
+            Error::Message(_)
+            | Error::PeerMisbehavior(_)
+            | Error::NoHeaderRead
+            | Error::Bincode(_)
+            | Error::InvalidCollectionItem
+            | Error::InvalidEdition => RetryPolicy::Elsewhere,
+            Error::Rpc(_)
+            | Error::QuicConnectionError(_)
+            | Error::Timeout
+            | Error::AllCandidatesFailed
+            | Error::NoCandidates
+            | Error::Overloaded { .. }
+            | Error::WorkRequired { .. }
+            // Until the user accepts the new key:
+            | Error::HubKeyChanged { .. }
+            | Error::Io(_) => RetryPolicy::Later,
+            Error::Base64(_)
+            | Error::Db(_)
+            | Error::BadHashLength(_)
+            | Error::DifferentPublicKeys
+            | Error::NotFound(_)
+            | Error::ValidationFailed(_)
+            | Error::Storage(_)
+            | Error::QuotaExceeded(_)
+            | Error::Refused(_) => RetryPolicy::Never,
+        }
+    }
 }
 
 impl warp::reject::Reject for crate::Error {}
@@ -86,6 +191,12 @@ impl From<quinn::ConnectionError> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Message(e.to_string())
+    }
+}
+
 impl From<Error> for io::Error {
     fn from(e: Error) -> io::Error {
         io::Error::new(io::ErrorKind::Other, e.to_string())
@@ -0,0 +1,334 @@
+//! The protocol handshake. Right after a QUIC connection is established, each side opens a
+//! bidirectional stream, sends a [`Hello`] through it and reads the [`Hello`] the other side
+//! sent through the stream it opened. RPC and transfers only use unidirectional streams, so
+//! peers predating the handshake never open nor accept the stream. The handshake runs in the
+//! background (see [`spawn`]) and the connection is used right away, so that these peers are
+//! not kept waiting: until the other side's [`Hello`] arrives (or after
+//! [`HANDSHAKE_TIMEOUT`]), it is taken to speak [`LEGACY_VERSION`], with no capabilities.
+//!
+//! An unknown RPC message breaks the whole connection. Therefore, new message types must only
+//! be sent to peers that advertise the capability (or the version) for them.
+//!
+//! Nodes send an [`Advertisement`] to hubs right after their [`Hello`], in the same message.
+//! Hubs predating it ignore anything past the [`Hello`], as they would any appended field.
+
+use ed25519_dalek::Keypair;
+use futures::prelude::*;
+use quinn::{Connection, IncomingBiStreams};
+use serde_derive::{Deserialize, Serialize};
+use std::ops::BitOr;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::timeout;
+
+use crate::{Key, Signed};
+
+/// The version of the protocol spoken by peers predating the handshake.
+pub const LEGACY_VERSION: u32 = 0;
+
+/// The version of the protocol spoken by this build.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How long to wait for the [`Hello`] of the other side before taking it for a legacy peer.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The maximum size of a serialized [`Hello`], with the [`Advertisement`] following it.
+const MAX_HELLO_LENGTH: usize = 1_024;
+
+/// Optional features of the protocol, as bit flags. Unknown flags are ignored, so that new
+/// ones can be added at any time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities(u64);
+
+impl Capabilities {
+    /// No optional features.
+    pub const NONE: Capabilities = Capabilities(0);
+    /// Answers many queries in a single call (see [`crate::rpc::Hub::query_many`]).
+    pub const QUERY_BATCHES: Capabilities = Capabilities(1 << 0);
+    /// Presents the same TLS certificate across restarts, so that nodes can pin it.
+    pub const PERSISTENT_KEY: Capabilities = Capabilities(1 << 1);
+
+    /// Whether all the given capabilities are present.
+    pub fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// All the capabilities in either set. This is `|`, for constants.
+    pub const fn union(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 | other.0)
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Capabilities;
+    fn bitor(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 | other.0)
+    }
+}
+
+/// What each side tells the other in the handshake. Fields may only be appended, since older
+/// peers ignore anything past the fields they know.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hello {
+    /// The latest version of the protocol spoken.
+    pub version: u32,
+    /// The optional features supported.
+    pub capabilities: Capabilities,
+}
+
+/// What was agreed with the other side of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Protocol {
+    /// The version of the protocol to speak, the latest both sides know.
+    pub version: u32,
+    /// The optional features supported by the other side.
+    pub capabilities: Capabilities,
+}
+
+/// What a node tells hubs about itself, so that hubs can avoid pairing peers that cannot
+/// transfer content to each other and operators can see which versions are around.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeInfo {
+    /// The version of the node software.
+    pub software_version: String,
+    /// The versions of the protocol used to transfer content between peers that the node
+    /// speaks.
+    pub transfer_versions: Vec<u32>,
+    /// Whether the node forwards resolutions to other peers, as hubs connected as nodes do.
+    pub relays: bool,
+    /// The maximum size of content peers may send to the node, in bytes.
+    pub max_object_size: u64,
+}
+
+impl NodeInfo {
+    /// Whether content can be transferred between the two nodes, i.e., whether they share a
+    /// version of the transfer protocol.
+    pub fn can_transfer_with(&self, other: &NodeInfo) -> bool {
+        self.transfer_versions
+            .iter()
+            .any(|version| other.transfer_versions.contains(version))
+    }
+}
+
+/// A [`NodeInfo`] signed by the node. Nodes sign with a fresh key for each connection to a hub,
+/// so that a hub can tell the direct and reverse connections of the same node apart from other
+/// nodes, while no hub can link what it sees to the same node over time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Advertisement {
+    pub public_key: Key,
+    pub info: Signed<NodeInfo>,
+}
+
+impl Advertisement {
+    pub fn new(info: NodeInfo, keypair: &Keypair) -> Advertisement {
+        Advertisement {
+            public_key: Key::new(keypair.public),
+            info: Signed::new(info, keypair),
+        }
+    }
+
+    /// Whether the signature matches the public key.
+    pub fn is_valid(&self) -> bool {
+        self.info.verify(self.public_key.as_ref())
+    }
+}
+
+/// Reads a [`Hello`] and, if the other side sent one, the [`Advertisement`] following it. A
+/// missing or invalid advertisement is ignored.
+fn read_hello(mut serialized: &[u8]) -> Result<(Hello, Option<Advertisement>), crate::Error> {
+    let hello: Hello = bincode::deserialize_from(&mut serialized)?;
+    let advertisement = bincode::deserialize_from::<_, Option<Advertisement>>(serialized)
+        .ok()
+        .flatten()
+        .filter(Advertisement::is_valid);
+
+    Ok((hello, advertisement))
+}
+
+impl Hello {
+    /// The hello of this build, advertising the given capabilities.
+    pub fn ours(capabilities: Capabilities) -> Hello {
+        Hello {
+            version: PROTOCOL_VERSION,
+            capabilities,
+        }
+    }
+}
+
+impl Protocol {
+    /// The protocol of peers predating the handshake.
+    pub const LEGACY: Protocol = Protocol {
+        version: LEGACY_VERSION,
+        capabilities: Capabilities::NONE,
+    };
+
+    /// What is agreed from the hellos of both sides.
+    pub fn agree(ours: &Hello, theirs: &Hello) -> Protocol {
+        Protocol {
+            version: u32::min(ours.version, theirs.version),
+            capabilities: theirs.capabilities,
+        }
+    }
+
+    /// Whether the other side supports all the given capabilities.
+    pub fn supports(&self, capabilities: Capabilities) -> bool {
+        self.capabilities.contains(capabilities)
+    }
+}
+
+/// The outcome of a handshake, which may still be running in the background.
+#[derive(Debug, Clone)]
+pub struct Agreement(watch::Receiver<Option<(Protocol, Option<Advertisement>)>>);
+
+impl Agreement {
+    /// An agreement reached beforehand, e.g., through the TCP fallback, where hellos are
+    /// exchanged before anything else.
+    pub fn reached(protocol: Protocol, advertisement: Option<Advertisement>) -> Agreement {
+        let (_, receiver) = watch::channel(Some((protocol, advertisement)));
+        Agreement(receiver)
+    }
+
+    /// Whether the handshake is over.
+    pub fn is_over(&self) -> bool {
+        self.0.borrow().is_some()
+    }
+
+    /// What was agreed so far: the other side is taken for a legacy peer until the handshake
+    /// is over.
+    pub fn protocol(&self) -> Protocol {
+        self.0
+            .borrow()
+            .as_ref()
+            .map_or(Protocol::LEGACY, |(protocol, _)| *protocol)
+    }
+
+    /// The advertisement of the other side, if it has arrived.
+    pub fn advertisement(&self) -> Option<Advertisement> {
+        self.0
+            .borrow()
+            .as_ref()
+            .and_then(|(_, advertisement)| advertisement.clone())
+    }
+
+    /// Waits for the handshake to be over, which takes at most [`HANDSHAKE_TIMEOUT`].
+    pub async fn agreed(&self) -> (Protocol, Option<Advertisement>) {
+        let mut receiver = self.0.clone();
+
+        loop {
+            if let Some(agreed) = receiver.borrow().clone() {
+                return agreed;
+            }
+
+            if receiver.changed().await.is_err() {
+                return (Protocol::LEGACY, None);
+            }
+        }
+    }
+}
+
+/// Exchanges [`Hello`]s with the other side of a connection in the background, advertising
+/// the given capabilities and sending the given advertisement after the hello. This never
+/// fails: if anything goes wrong, the other side is taken for a legacy peer.
+pub fn spawn(
+    connection: Connection,
+    mut bi_streams: IncomingBiStreams,
+    capabilities: Capabilities,
+    advertisement: Option<Advertisement>,
+) -> Agreement {
+    let (sender, receiver) = watch::channel(None);
+
+    tokio::spawn(async move {
+        let agreed = exchange(
+            &connection,
+            &mut bi_streams,
+            capabilities,
+            advertisement.as_ref(),
+        )
+        .await;
+        sender.send(Some(agreed)).ok();
+    });
+
+    Agreement(receiver)
+}
+
+/// Exchanges [`Hello`]s with the other side of a connection. Returns the advertisement of the
+/// other side, if any.
+async fn exchange(
+    connection: &Connection,
+    bi_streams: &mut IncomingBiStreams,
+    capabilities: Capabilities,
+    advertisement: Option<&Advertisement>,
+) -> (Protocol, Option<Advertisement>) {
+    let ours = Hello::ours(capabilities);
+
+    let send = async {
+        let (mut send, _recv) = connection.open_bi().await?;
+        send.write_all(&bincode::serialize(&(&ours, advertisement))?)
+            .await
+            .map_err(|err| format!("failed to write hello: {err}"))?;
+        send.finish()
+            .await
+            .map_err(|err| format!("failed to write hello: {err}"))?;
+        Ok(()) as Result<(), crate::Error>
+    };
+
+    let receive = async {
+        let (_send, recv) = match bi_streams.next().await {
+            Some(streams) => streams?,
+            None => return Err("connection closed before handshake".into()),
+        };
+        let serialized = recv
+            .read_to_end(MAX_HELLO_LENGTH)
+            .await
+            .map_err(|err| format!("failed to read hello: {err}"))?;
+        read_hello(&serialized)
+    };
+
+    let peer_addr = connection.remote_address();
+
+    match timeout(HANDSHAKE_TIMEOUT, future::join(send, receive)).await {
+        Ok((_, Ok((theirs, advertisement)))) => {
+            log::debug!("{peer_addr} speaks {theirs:?}");
+            (Protocol::agree(&ours, &theirs), advertisement)
+        }
+        Ok((_, Err(err))) => {
+            log::info!("handshake with {peer_addr} failed ({err}); taking it for a legacy peer");
+            (Protocol::LEGACY, None)
+        }
+        Err(_) => {
+            log::info!("no handshake from {peer_addr}; taking it for a legacy peer");
+            (Protocol::LEGACY, None)
+        }
+    }
+}
+
+#[test]
+fn test_hello_with_advertisement() {
+    let keypair = Keypair::generate(&mut rand::rngs::OsRng {});
+    let info = NodeInfo {
+        software_version: "0.1.0".to_owned(),
+        transfer_versions: vec![1],
+        relays: false,
+        max_object_size: 1_000_000,
+    };
+    let advertisement = Advertisement::new(info.clone(), &keypair);
+    let ours = Hello::ours(Capabilities::NONE);
+
+    // Both with and without the advertisement:
+    let serialized = bincode::serialize(&(&ours, Some(&advertisement))).unwrap();
+    let (_, read) = read_hello(&serialized).unwrap();
+    assert_eq!(
+        read.map(|advertisement| advertisement.info.into_inner()),
+        Some(info)
+    );
+
+    let serialized = bincode::serialize(&ours).unwrap();
+    let (hello, read) = read_hello(&serialized).unwrap();
+    assert_eq!(hello.version, PROTOCOL_VERSION);
+    assert!(read.is_none());
+
+    // Peers predating advertisements still read the hello:
+    let serialized = bincode::serialize(&(&ours, Some(&advertisement))).unwrap();
+    let hello: Hello = bincode::deserialize(&serialized).unwrap();
+    assert_eq!(hello.version, PROTOCOL_VERSION);
+}
@@ -1,10 +1,17 @@
+pub mod api_docs;
+pub mod attestation;
+pub mod bloom;
 pub mod cipher;
+pub mod handshake;
 pub mod heap_entry;
 pub mod keyed_channel;
 pub mod logger;
 pub mod pow;
+pub mod profiles;
 pub mod quic;
+pub mod request_id;
 pub mod rpc;
+pub mod tcp_fallback;
 
 mod channel_address;
 mod error;
@@ -15,7 +22,7 @@ mod riddles;
 mod transport;
 
 pub use channel_address::ChannelAddr;
-pub use error::Error;
+pub use error::{Error, RetryPolicy};
 pub use hash::{Hash, InclusionProof, MerkleTree};
 pub use patricia_map::{PatriciaMap, PatriciaProof};
 pub use pki::{Key, PrivateKey, Signed};
@@ -1,26 +1,256 @@
+//! Logging for all Samizdat executables. Besides the console, logs can optionally be written
+//! to a file, which is rotated when it gets too big, and in JSON format. The level of each
+//! module can be changed at runtime, without restarting the process. Lines logged while handling
+//! a request are prefixed by the [`RequestId`] of the request.
+
+use log::{LevelFilter, Record};
 use log4rs::append::console::{ConsoleAppender, Target};
+use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRoller;
+use log4rs::append::rolling_file::policy::compound::trigger::size::SizeTrigger;
+use log4rs::append::rolling_file::policy::compound::CompoundPolicy;
+use log4rs::append::rolling_file::RollingFileAppender;
 use log4rs::config::{Appender, Config, Logger, Root};
+use log4rs::encode::json::JsonEncoder;
 use log4rs::encode::pattern::PatternEncoder;
+use log4rs::encode::{self, Encode};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::request_id::RequestId;
+
+/// The pattern used for logs in text format.
+const PATTERN: &str = "{d(%Y-%m-%d %H:%M:%S%.3f)} [{M}:{L} {T}] {h({l})} {m}{n}";
+
+lazy_static::lazy_static! {
+    /// The running logger and how it was configured.
+    static ref LOGGER: Mutex<Option<(log4rs::Handle, LoggerConfig)>> = Mutex::default();
+}
+
+/// The format in which logs are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<LogFormat, String> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            invalid => Err(format!(
+                "invalid log format `{invalid}`: must be `text` or `json`"
+            )),
+        }
+    }
+}
+
+/// A list of per-module log levels, in the form `module=level,other::module=level`. A level
+/// without a module sets the default level.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilters(pub BTreeMap<String, LevelFilter>);
+
+impl FromStr for LogFilters {
+    type Err = String;
+    fn from_str(s: &str) -> Result<LogFilters, String> {
+        let mut filters = BTreeMap::new();
+
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (module, level) = match directive.split_once('=') {
+                Some((module, level)) => (module.trim(), level.trim()),
+                None => ("", directive),
+            };
+            let level = level
+                .parse::<LevelFilter>()
+                .map_err(|_| format!("invalid log level `{level}` in `{directive}`"))?;
+
+            filters.insert(module.to_owned(), level);
+        }
+
+        Ok(LogFilters(filters))
+    }
+}
+
+/// Prefixes messages with the ID of the request being handled, if any.
+#[derive(Debug)]
+struct WithRequestId(Box<dyn Encode>);
+
+impl Encode for WithRequestId {
+    fn encode(&self, w: &mut dyn encode::Write, record: &Record) -> anyhow::Result<()> {
+        match RequestId::current() {
+            Some(request_id) => self.0.encode(
+                w,
+                &Record::builder()
+                    .args(format_args!("[{request_id}] {}", record.args()))
+                    .level(record.level())
+                    .target(record.target())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            ),
+            None => self.0.encode(w, record),
+        }
+    }
+}
+
+/// How the logger is set up.
+#[derive(Debug, Clone)]
+pub struct LoggerConfig {
+    /// The default level for all modules.
+    pub level: LevelFilter,
+    /// The levels of specific modules, overriding the default level.
+    pub filters: BTreeMap<String, LevelFilter>,
+    /// The format in which logs are written.
+    pub format: LogFormat,
+    /// A file to which logs are also written, if any.
+    pub file: Option<PathBuf>,
+    /// The size in bytes after which the log file is rotated.
+    pub max_file_size: u64,
+    /// The number of rotated log files that are kept.
+    pub max_files: u32,
+}
+
+impl LoggerConfig {
+    /// The configuration for the console only, in text format.
+    pub fn console(verbose: bool) -> LoggerConfig {
+        LoggerConfig {
+            level: if verbose {
+                LevelFilter::Debug
+            } else {
+                LevelFilter::Info
+            },
+            filters: BTreeMap::new(),
+            format: LogFormat::Text,
+            file: None,
+            max_file_size: 10_000_000,
+            max_files: 5,
+        }
+    }
+
+    /// Adds per-module levels to this configuration. A filter for the empty module sets the
+    /// default level.
+    pub fn with_filters(mut self, LogFilters(filters): LogFilters) -> LoggerConfig {
+        for (module, level) in filters {
+            if module.is_empty() {
+                self.level = level;
+            } else {
+                self.filters.insert(module, level);
+            }
+        }
+
+        self
+    }
+
+    fn encoder(&self) -> Box<dyn Encode> {
+        let encoder: Box<dyn Encode> = match self.format {
+            LogFormat::Text => Box::new(PatternEncoder::new(PATTERN)),
+            LogFormat::Json => Box::new(JsonEncoder::new()),
+        };
+
+        Box::new(WithRequestId(encoder))
+    }
+
+    /// Builds the `log4rs` configuration.
+    fn build(&self) -> Result<Config, crate::Error> {
+        let console = ConsoleAppender::builder()
+            .target(Target::Stderr)
+            .encoder(self.encoder())
+            .build();
+
+        let mut builder =
+            Config::builder().appender(Appender::builder().build("stderr", Box::new(console)));
+        let mut root = Root::builder().appender("stderr");
+
+        if let Some(file) = &self.file {
+            let roll_pattern = format!("{}.{{}}", file.display());
+            let roller = FixedWindowRoller::builder()
+                .build(&roll_pattern, self.max_files)
+                .map_err(|err| format!("bad log rotation setup: {err}"))?;
+            let policy = CompoundPolicy::new(
+                Box::new(SizeTrigger::new(self.max_file_size)),
+                Box::new(roller),
+            );
+            let appender = RollingFileAppender::builder()
+                .encoder(self.encoder())
+                .build(file, Box::new(policy))?;
+
+            builder = builder.appender(Appender::builder().build("file", Box::new(appender)));
+            root = root.appender("file");
+        }
+
+        // Quieter defaults for noisy dependencies, which can be overridden:
+        let mut levels = BTreeMap::from([
+            ("quinn".to_owned(), LevelFilter::Warn),
+            ("tarpc".to_owned(), LevelFilter::Off),
+        ]);
+        levels.extend(self.filters.clone());
+
+        for (module, level) in levels {
+            builder = builder.logger(Logger::builder().build(module, level));
+        }
+
+        builder
+            .build(root.build(self.level))
+            .map_err(|err| format!("bad logger configuration: {err}").into())
+    }
+}
+
+/// Starts logging to the console, in text format.
+pub fn init_logger(verbose: bool) -> Result<(), crate::Error> {
+    init_logger_with(LoggerConfig::console(verbose))
+}
+
+/// Starts logging with the given configuration.
+pub fn init_logger_with(config: LoggerConfig) -> Result<(), crate::Error> {
+    let handle = log4rs::init_config(config.build()?)
+        .map_err(|err| format!("could not start logger: {err}"))?;
+    *LOGGER.lock().expect("poisoned") = Some((handle, config));
+
+    Ok(())
+}
+
+/// The current log levels: the default level, under the empty module name, and the levels of
+/// specific modules.
+pub fn log_levels() -> BTreeMap<String, LevelFilter> {
+    let logger = LOGGER.lock().expect("poisoned");
+    let mut levels = BTreeMap::new();
+
+    if let Some((_, config)) = logger.as_ref() {
+        levels.insert(String::new(), config.level);
+        levels.extend(config.filters.clone());
+    }
+
+    levels
+}
+
+/// Changes the log level of a module at runtime. If no module is given, the default level is
+/// changed. If no level is given, the module goes back to the default level.
+pub fn set_log_level(module: Option<&str>, level: Option<LevelFilter>) -> Result<(), crate::Error> {
+    let mut logger = LOGGER.lock().expect("poisoned");
+    let (handle, config) = logger
+        .as_mut()
+        .ok_or_else(|| crate::Error::from("logger not initialized".to_owned()))?;
+    let mut new_config = config.clone();
+
+    match (module, level) {
+        (None | Some(""), Some(level)) => new_config.level = level,
+        (None | Some(""), None) => return Err("the default level must be set".to_owned().into()),
+        (Some(module), Some(level)) => {
+            new_config.filters.insert(module.to_owned(), level);
+        }
+        (Some(module), None) => {
+            new_config.filters.remove(module);
+        }
+    }
+
+    handle.set_config(new_config.build()?);
+    *config = new_config;
 
-pub fn init_logger(verbose: bool) -> log4rs::Handle {
-    let pattern = PatternEncoder::new("{d(%Y-%m-%d %H:%M:%S%.3f)} [{M}:{L} {T}] {h({l})} {m}{n}");
-
-    let console = ConsoleAppender::builder()
-        .target(Target::Stderr)
-        .encoder(Box::new(pattern))
-        .build();
-
-    let config = Config::builder()
-        .appender(Appender::builder().build("stderr", Box::new(console)))
-        .logger(Logger::builder().build("quinn", log::LevelFilter::Warn))
-        .logger(Logger::builder().build("tarpc", log::LevelFilter::Off))
-        // .logger(Logger::builder().build("sled_to_postgres", log::LevelFilter::Trace))
-        .build(Root::builder().appender("stderr").build(if verbose {
-            log::LevelFilter::Debug
-        } else {
-            log::LevelFilter::Info
-        }))
-        .expect("could not config logger");
-
-    log4rs::init_config(config).expect("could not start logger")
+    Ok(())
 }
@@ -33,6 +33,18 @@ impl ProofOfWork {
         1.0 / inv_work_done - 1.0
     }
 
+    /// Finds a proof of at least the given work for some information. This takes, on average,
+    /// as many hashes as the work demanded, so better not run it in an async context.
+    pub fn solve(information: Hash, work: f64) -> ProofOfWork {
+        let mut proof = ProofOfWork::new(information);
+
+        while proof.work_done() < work {
+            proof.solution = Hash::rand();
+        }
+
+        proof
+    }
+
     pub fn improve(&self) -> ProofOfWork {
         let threshold_work = self.work_done();
         let mut improved = self.clone();
@@ -0,0 +1,162 @@
+//! Profiles let many nodes run in the same machine, each with its own data folder, access
+//! token, port and keys, so that content and identities of different personas never mix. All
+//! profiles live under the data folder of the default node, which also holds the ports
+//! assigned to each profile and the profile currently used by the CLI.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The port of the first profile. Ports below it are used by the default node and hub.
+const FIRST_PORT: u16 = 4520;
+
+/// A profile, with its assigned port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub port: u16,
+}
+
+impl Profile {
+    /// The data folder of this profile.
+    pub fn data(&self, base: &Path) -> PathBuf {
+        base.join("profiles").join(&self.name)
+    }
+}
+
+/// The file listing all profiles and their ports, one `name port` pair per line.
+fn ports_path(base: &Path) -> PathBuf {
+    base.join("profiles").join("ports")
+}
+
+/// The file with the name of the profile currently used by the CLI.
+fn current_path(base: &Path) -> PathBuf {
+    base.join("profiles").join("current")
+}
+
+fn validate_name(name: &str) -> Result<(), crate::Error> {
+    let is_valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(format!("invalid profile name {name:?}: use only letters, digits, `-` and `_`").into())
+    }
+}
+
+fn write_list<'a>(
+    base: &Path,
+    profiles: impl Iterator<Item = &'a Profile>,
+) -> Result<(), crate::Error> {
+    fs::write(
+        ports_path(base),
+        profiles
+            .map(|profile| format!("{} {}\n", profile.name, profile.port))
+            .collect::<String>(),
+    )?;
+
+    Ok(())
+}
+
+/// Lists all profiles created so far.
+pub fn list(base: &Path) -> Result<Vec<Profile>, crate::Error> {
+    let ports = match fs::read_to_string(ports_path(base)) {
+        Ok(ports) => ports,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(ports
+        .lines()
+        .filter_map(|line| {
+            let (name, port) = line.split_once(' ')?;
+            Some(Profile {
+                name: name.to_owned(),
+                port: port.trim().parse().ok()?,
+            })
+        })
+        .collect())
+}
+
+/// Gets a profile by name, if it exists.
+pub fn get(base: &Path, name: &str) -> Result<Option<Profile>, crate::Error> {
+    Ok(list(base)?.into_iter().find(|profile| profile.name == name))
+}
+
+/// Gets a profile by name, creating it with the next free port if it does not exist.
+pub fn get_or_create(base: &Path, name: &str) -> Result<Profile, crate::Error> {
+    validate_name(name)?;
+
+    let mut profiles = list(base)?;
+    if let Some(profile) = profiles.iter().find(|profile| profile.name == name) {
+        return Ok(profile.clone());
+    }
+
+    let port = profiles
+        .iter()
+        .map(|profile| profile.port + 1)
+        .max()
+        .unwrap_or(FIRST_PORT)
+        .max(FIRST_PORT);
+    let profile = Profile {
+        name: name.to_owned(),
+        port,
+    };
+    profiles.push(profile.clone());
+
+    fs::create_dir_all(profile.data(base))?;
+    write_list(base, profiles.iter())?;
+
+    Ok(profile)
+}
+
+/// Removes a profile from the list of profiles. Its data folder is left as it is.
+pub fn remove(base: &Path, name: &str) -> Result<(), crate::Error> {
+    let profiles = list(base)?;
+    write_list(base, profiles.iter().filter(|profile| profile.name != name))?;
+
+    if current(base)?.as_deref() == Some(name) {
+        set_current(base, None)?;
+    }
+
+    Ok(())
+}
+
+/// The profile currently used by the CLI, if not the default one.
+pub fn current(base: &Path) -> Result<Option<String>, crate::Error> {
+    match fs::read_to_string(current_path(base)) {
+        Ok(name) if !name.trim().is_empty() => Ok(Some(name.trim().to_owned())),
+        Ok(_) => Ok(None),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Sets the profile used by the CLI. `None` is the default profile.
+pub fn set_current(base: &Path, name: Option<&str>) -> Result<(), crate::Error> {
+    match name {
+        Some(name) => {
+            if get(base, name)?.is_none() {
+                return Err(format!(
+                    "profile {name:?} does not exist. Start a node with `--profile {name}` to \
+                    create it"
+                )
+                .into());
+            }
+
+            fs::write(current_path(base), name)?;
+        }
+        None => {
+            if let Err(err) = fs::remove_file(current_path(base)) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    return Err(err.into());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
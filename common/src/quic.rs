@@ -2,21 +2,42 @@ use quinn::{
     ClientConfig, Endpoint, IdleTimeout, Incoming, NewConnection, ServerConfig, TransportConfig,
     VarInt,
 };
+use serde_derive::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
+use crate::Hash;
+
 /// "I am Spartacus!"
-const DEFAULT_SERVER_NAME: &str = "spartacus";
+pub(crate) const DEFAULT_SERVER_NAME: &str = "spartacus";
+
+lazy_static::lazy_static! {
+    /// The TLS configuration shared by all clients in this process. Sharing it means sharing the
+    /// cache of session tickets, which allows a reconnect to a known server to be resumed even
+    /// if it happens through a new endpoint.
+    static ref CLIENT_CRYPTO: Arc<rustls::ClientConfig> = {
+        let crypto = rustls::ClientConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .expect("TLS 1.3 is supported")
+            .with_custom_certificate_verifier(SkipServerVerification::new())
+            .with_no_client_auth();
+
+        Arc::new(crypto)
+    };
+}
 
 // We don't need all trust built into QUIC. Using "dangerous configuration", which is simpler.
 // Taken from the tutorial: https://quinn-rs.github.io/quinn/quinn/certificate.html
 
-// Implementation of `ServerCertVerifier` that verifies everything as trustworthy.
-struct SkipServerVerification;
+// Implementation of `ServerCertVerifier` that verifies everything as trustworthy. Nodes pin the
+// certificates of hubs by themselves, after connecting (see `peer_fingerprint`).
+pub(crate) struct SkipServerVerification;
 
 impl SkipServerVerification {
-    fn new() -> Arc<Self> {
+    pub(crate) fn new() -> Arc<Self> {
         Arc::new(Self)
     }
 }
@@ -35,6 +56,8 @@ impl rustls::client::ServerCertVerifier for SkipServerVerification {
     }
 }
 
+// Note: this version of QUIC never sends packets bigger than the protocol minimum of 1200 bytes,
+// which fits the MTU of virtually every network path. Therefore, datagrams are never fragmented.
 fn transport_config() -> TransportConfig {
     const IDLE_TIMEOUT_MS: u32 = 10_000;
 
@@ -46,43 +69,115 @@ fn transport_config() -> TransportConfig {
 }
 
 fn client_config() -> ClientConfig {
-    let crypto = rustls::ClientConfig::builder()
-        .with_safe_defaults()
-        .with_custom_certificate_verifier(SkipServerVerification::new())
-        .with_no_client_auth();
-
-    let mut client_config = ClientConfig::new(Arc::new(crypto));
+    let mut client_config = ClientConfig::new(CLIENT_CRYPTO.clone());
     client_config.transport = Arc::new(transport_config());
 
     client_config
 }
 
-fn server_config() -> ServerConfig {
-    let cert = rcgen::generate_simple_self_signed(vec![DEFAULT_SERVER_NAME.into()]).unwrap();
-    let key = rustls::PrivateKey(cert.serialize_private_key_der());
-    let cert = rustls::Certificate(cert.serialize_der().unwrap());
+/// A self-signed TLS certificate, with its private key, both DER-encoded. Clients do not verify
+/// certificates (see [`SkipServerVerification`]), but nodes pin the certificates of hubs on
+/// first use. Therefore, hubs keep theirs across restarts.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TlsCertificate {
+    certificate: Vec<u8>,
+    private_key: Vec<u8>,
+}
+
+impl TlsCertificate {
+    /// A fresh self-signed certificate.
+    pub fn generate() -> TlsCertificate {
+        let cert = rcgen::generate_simple_self_signed(vec![DEFAULT_SERVER_NAME.into()]).unwrap();
+
+        TlsCertificate {
+            certificate: cert.serialize_der().unwrap(),
+            private_key: cert.serialize_private_key_der(),
+        }
+    }
+
+    /// The fingerprint by which the certificate is pinned.
+    pub fn fingerprint(&self) -> Hash {
+        Hash::hash(&self.certificate)
+    }
+
+    pub(crate) fn into_rustls(self) -> (rustls::Certificate, rustls::PrivateKey) {
+        (
+            rustls::Certificate(self.certificate),
+            rustls::PrivateKey(self.private_key),
+        )
+    }
+}
+
+/// The fingerprint of the certificate presented by the other side of a connection, if any.
+pub fn peer_fingerprint(connection: &quinn::Connection) -> Option<Hash> {
+    let certificates = connection
+        .peer_identity()?
+        .downcast::<Vec<rustls::Certificate>>()
+        .ok()?;
+
+    certificates
+        .first()
+        .map(|certificate| Hash::hash(&certificate.0))
+}
 
-    let mut server_config =
-        quinn::ServerConfig::with_single_cert(vec![cert], key).expect("can build server config");
+fn server_config(certificate: TlsCertificate) -> ServerConfig {
+    let (cert, key) = certificate.into_rustls();
+
+    let mut crypto = rustls::ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .expect("TLS 1.3 is supported")
+        .with_no_client_auth()
+        .with_single_cert(vec![cert], key)
+        .expect("can build server config");
+    // Allow sessions to be resumed. Tickets are stateless, so that any number of clients can
+    // resume their sessions. Ticket keys are not kept across restarts, which only means that
+    // clients do a full handshake after that. There is no 0-RTT: data sent in 0-RTT can be
+    // replayed, and it would be lost whenever the server does not take the ticket.
+    crypto.ticketer = rustls::Ticketer::new().expect("can create ticketer");
+
+    let mut server_config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
     server_config.transport = Arc::new(transport_config());
 
     server_config
 }
 
+/// An endpoint with a fresh self-signed certificate.
 pub fn new_default(bind_addr: SocketAddr) -> (Endpoint, Incoming) {
+    new_with_certificate(bind_addr, TlsCertificate::generate())
+}
+
+/// An endpoint presenting the given certificate.
+pub fn new_with_certificate(
+    bind_addr: SocketAddr,
+    certificate: TlsCertificate,
+) -> (Endpoint, Incoming) {
     let (mut endpoint, incoming) =
-        Endpoint::server(server_config(), bind_addr).expect("can bind endpoint");
+        Endpoint::server(server_config(certificate), bind_addr).expect("can bind endpoint");
     endpoint.set_default_client_config(client_config());
 
     (endpoint, incoming)
 }
 
+/// The server name used to connect to a given address. Session tickets are cached by server
+/// name, so each server must have its own name for sessions to be resumed.
+fn server_name_for(remote_addr: SocketAddr) -> String {
+    let label = remote_addr
+        .to_string()
+        .replace(|ch: char| !ch.is_ascii_alphanumeric(), "-");
+    format!("a{label}.{DEFAULT_SERVER_NAME}")
+}
+
+/// Connects to a server. If this process has connected to the same server before, the session
+/// is resumed, which makes reconnects faster.
 pub async fn connect(
     endpoint: &Endpoint,
     remote_addr: SocketAddr,
 ) -> Result<NewConnection, crate::Error> {
-    Ok(endpoint
-        .connect(remote_addr, DEFAULT_SERVER_NAME)
-        .expect("failed to start connecting")
-        .await?)
+    let connecting = endpoint
+        .connect(remote_addr, &server_name_for(remote_addr))
+        .expect("failed to start connecting");
+
+    Ok(connecting.await?)
 }
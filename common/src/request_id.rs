@@ -0,0 +1,118 @@
+//! Request IDs, for following a request around the network. Each user request gets an ID in the
+//! node and every log line emitted while handling a request is prefixed by its ID. The ID never
+//! leaves the machine: each RPC call gets a fresh random trace id, which the machine receiving
+//! the call uses as the ID of its own part of the request. The link between the two is logged
+//! (at the debug level) by the caller, so that a failed page load can still be followed
+//! hop-by-hop through the logs of all machines involved, but nobody can tag the traffic of a
+//! node, e.g., by setting the request ID of a page load.
+
+use futures::Future;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use tarpc::context::{self, Context};
+use tarpc::server::Serve;
+use tokio::task::futures::TaskLocalFuture;
+use tokio::task::JoinHandle;
+
+tokio::task_local! {
+    /// The ID of the request being handled by the current task.
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+/// Identifies a request from a user, across all machines that take part in answering it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RequestId(u64);
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for RequestId {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> Result<RequestId, crate::Error> {
+        u64::from_str_radix(s, 16)
+            .map(RequestId)
+            .map_err(|err| crate::Error::ValidationFailed(format!("bad request id `{s}`: {err}")))
+    }
+}
+
+impl RequestId {
+    /// Creates a new random request ID.
+    pub fn new() -> RequestId {
+        RequestId(rand::random())
+    }
+
+    /// The ID of the request being handled by the current task, if any.
+    pub fn current() -> Option<RequestId> {
+        CURRENT_REQUEST_ID.try_with(|id| *id).ok()
+    }
+
+    /// The ID of the request being handled by the current task or a new one, if none.
+    pub fn current_or_new() -> RequestId {
+        RequestId::current().unwrap_or_default()
+    }
+
+    /// The request ID sent in an RPC context. Callers that do not send request IDs get a new
+    /// one.
+    pub fn from_context(ctx: &Context) -> RequestId {
+        let trace_id = ctx.trace_context.trace_id;
+
+        if trace_id.is_none() {
+            RequestId::new()
+        } else {
+            // Request IDs use only the lower half of the trace id.
+            RequestId(u128::from(trace_id) as u64)
+        }
+    }
+
+    /// Runs a future as part of this request.
+    pub fn scope<F: Future>(self, future: F) -> TaskLocalFuture<RequestId, F> {
+        CURRENT_REQUEST_ID.scope(self, future)
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> RequestId {
+        RequestId::new()
+    }
+}
+
+/// An RPC context with a fresh trace id, logged as part of the current request. Use this
+/// instead of [`context::current`] for all RPC calls.
+pub fn context() -> Context {
+    let trace_id = RequestId::new();
+    log::debug!("calling with trace id {trace_id}");
+
+    let mut ctx = context::current();
+    ctx.trace_context.trace_id = u128::from(trace_id.0).into();
+    ctx
+}
+
+/// Spawns a task that is part of the current request, if any.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: 'static + Send + Future,
+    F::Output: 'static + Send,
+{
+    tokio::spawn(RequestId::current_or_new().scope(future))
+}
+
+/// An RPC server that handles each call as part of the request whose ID is in the call context.
+#[derive(Debug, Clone)]
+pub struct Traced<S>(pub S);
+
+impl<Req, S: Serve<Req>> Serve<Req> for Traced<S> {
+    type Resp = S::Resp;
+    type Fut = TaskLocalFuture<RequestId, S::Fut>;
+
+    fn method(&self, request: &Req) -> Option<&'static str> {
+        self.0.method(request)
+    }
+
+    fn serve(self, ctx: Context, request: Req) -> Self::Fut {
+        RequestId::from_context(&ctx).scope(self.0.serve(ctx, request))
+    }
+}
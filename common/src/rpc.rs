@@ -1,13 +1,26 @@
+use chrono::{DateTime, Utc};
 use serde_derive::{Deserialize, Serialize};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::cipher::OpaqueEncrypted;
-use crate::{Hash, MessageRiddle, Riddle};
+use crate::pow::ProofOfWork;
+use crate::{Hash, Key, MessageRiddle, Riddle, Signed};
 
 pub type CandidateChannelId = u32;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// The maximum number of queries in a single call to [`Hub::query_many`]. Any queries beyond
+/// this limit are not answered.
+pub const MAX_QUERY_BATCH_SIZE: usize = 16;
+/// The maximum number of content riddles in a [`Query`].
+pub const MAX_QUERY_RIDDLES: usize = 16;
+/// The maximum size of a serialized [`Query`], with [`MAX_QUERY_RIDDLES`] riddles and a proof
+/// of work, with some room to spare. Hubs size their limit for messages from nodes with this.
+pub const MAX_QUERY_LENGTH: usize = 1_280;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum QueryKind {
     /// The hash corresponds to an object hash.
     Object,
@@ -15,7 +28,35 @@ pub enum QueryKind {
     Item,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Where a node prefers the peers resolving its queries to be, for hubs that know where nodes
+/// are (see the `--geoip-database` option of the hub).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeoPreference {
+    /// Wherever answers best.
+    #[default]
+    Any,
+    /// In the same country as the node, for lower latency.
+    Near,
+    /// In other countries than the node's, for jurisdiction diversity.
+    Far,
+}
+
+impl FromStr for GeoPreference {
+    type Err = String;
+    fn from_str(s: &str) -> Result<GeoPreference, String> {
+        match s {
+            "any" => Ok(GeoPreference::Any),
+            "near" => Ok(GeoPreference::Near),
+            "far" => Ok(GeoPreference::Far),
+            invalid => Err(format!(
+                "invalid geo preference `{invalid}`: must be `any`, `near` or `far`"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Query {
     /// The riddles the resolver can use to find the content hash.
     pub content_riddles: Vec<Riddle>,
@@ -23,6 +64,10 @@ pub struct Query {
     pub location_riddle: Riddle,
     /// The kind of entity being requested.
     pub kind: QueryKind,
+    /// A proof of work on the nonce of the location riddle, for hubs under stress.
+    pub proof_of_work: Option<ProofOfWork>,
+    /// Where the node prefers the peers resolving this query to be.
+    pub geo_preference: GeoPreference,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,7 +78,8 @@ pub enum QueryResponse {
     Replayed,
     /// Query was empty, i.e., `content_riddles` was empty.
     EmptyQuery,
-    /// You do not have a reverse connection to the hub (i.e. you are not connected as a server).
+    /// You do not have a reverse connection to the hub (i.e. you are not connected as a server)
+    /// which peers can reach. Peers cannot reach nodes connected over TCP.
     NoReverseConnection,
     /// Query was run and returned and candidates may be following (watch `recv_candidate`).
     Resolved {
@@ -42,6 +88,11 @@ pub enum QueryResponse {
         /// The channel to be used to to transport the payload.
         channel_id: u32,
     },
+    /// The hub is overloaded and did not run the query. Try again after the given time.
+    Overloaded { retry_after: Duration },
+    /// The hub is under stress and will only run the query with a proof of (at least) this
+    /// much work. See [`Query::proof_of_work`].
+    WorkRequired { work: f64 },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -62,6 +113,14 @@ pub struct EditionAnnouncement {
     pub rand: Hash,
 }
 
+impl EditionAnnouncement {
+    /// Identifies this announcement, so that copies of it arriving through different paths can
+    /// be dropped.
+    pub fn hash(&self) -> Hash {
+        Hash::hash(bincode::serialize(self).expect("can serialize"))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct IdentityRequest {
     pub identity_riddle: Riddle,
@@ -76,10 +135,45 @@ pub struct IdentityResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct IdentityAnnouncement {}
 
+/// An address banned from a hub, as shared with cooperating hubs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlacklistEntry {
+    pub ip: IpAddr,
+    /// Why the address was banned, for the operators of other hubs to judge.
+    pub reason: String,
+    pub added_at: DateTime<Utc>,
+    /// When the ban ends. If not set, the ban lasts until the entry is removed.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl BlacklistEntry {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+/// The blacklist of a hub, signed with the blacklist key of that hub.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlacklistFeed {
+    pub public_key: Key,
+    pub entries: Signed<Vec<BlacklistEntry>>,
+}
+
+impl BlacklistFeed {
+    /// Whether the entries were signed by the key in the feed.
+    pub fn is_valid(&self) -> bool {
+        self.entries.verify(self.public_key.as_ref())
+    }
+}
+
 #[tarpc::service]
 pub trait Hub {
     /// Returns a response resolving (or not) the supplied object query.
     async fn query(query: Query) -> QueryResponse;
+    /// Returns a response for each of the supplied queries, in the same order, in a single
+    /// round-trip. See [`MAX_QUERY_BATCH_SIZE`].
+    async fn query_many(queries: Vec<Query>) -> Vec<QueryResponse>;
     /// Sends a candidate for a previously returned redirect for a resolution.
     async fn recv_candidate(candidate_channel: CandidateChannelId, candidate: Candidate);
     /// Gets the latest version of a series.
@@ -90,6 +184,10 @@ pub trait Hub {
     async fn get_identity(request: IdentityRequest) -> Vec<IdentityResponse>;
     /// Announces a new identity to the network.
     async fn announce_identity(announcement: IdentityAnnouncement);
+    /// Answers immediately. Used to probe the health of the connection.
+    async fn ping();
+    /// Gets the blacklist of the hub, if the hub shares it with the caller.
+    async fn get_blacklist() -> Option<BlacklistFeed>;
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -134,3 +232,20 @@ pub trait Node {
     /// Receives the announcement of a new identity.
     async fn announce_identity(announcement: Arc<IdentityAnnouncement>);
 }
+
+#[test]
+fn test_max_query_length() {
+    let riddle = || Riddle {
+        rand: Hash::rand(),
+        hash: Hash::rand(),
+    };
+    let query = Query {
+        content_riddles: (0..MAX_QUERY_RIDDLES).map(|_| riddle()).collect(),
+        location_riddle: riddle(),
+        kind: QueryKind::Item,
+        proof_of_work: Some(ProofOfWork::new(Hash::rand())),
+        geo_preference: GeoPreference::Far,
+    };
+
+    assert!(bincode::serialize(&query).unwrap().len() <= MAX_QUERY_LENGTH);
+}
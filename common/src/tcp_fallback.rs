@@ -0,0 +1,471 @@
+//! A fallback for networks that block UDP, and QUIC with it. Nodes connect to the hub through
+//! TLS over TCP, which looks like any HTTPS connection to whoever is watching, down to the ALPN.
+//! Hubs in networks where only HTTPS gets through may serve it on port 443. Each TCP connection carries a single RPC channel, just like a
+//! QUIC connection, so that a node opens two of them: a [`Role::Direct`] and a
+//! [`Role::Reverse`] one.
+//!
+//! Right after the TLS handshake, the node sends an [`Opening`] and the hub answers with a
+//! [`Welcome`]. These carry the [`Hello`]s of the protocol handshake (see [`crate::handshake`])
+//! and pair the two connections of a node: the hub gives the node a random nonce in the direct
+//! connection and the node repeats it in the reverse one, so that the hub knows both by the
+//! address of the direct one, as it does with QUIC. Nobody else can pass for the reverse
+//! connection of a node, since nobody else knows the nonce. The [`Advertisement`] of the node
+//! follows its [`Opening`], in the same message.
+
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tarpc::serde_transport::Transport;
+use tarpc::tokio_serde::formats::Bincode;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio::time::timeout;
+use tokio_rustls::{TlsAcceptor, TlsConnector, TlsStream};
+
+use crate::handshake::{Advertisement, Capabilities, Hello, Protocol};
+use crate::quic::{SkipServerVerification, TlsCertificate, DEFAULT_SERVER_NAME};
+use crate::Hash;
+
+/// The port hubs listen to by default. This is not the one of HTTPS, which is privileged.
+pub const DEFAULT_PORT: u16 = 4513;
+
+/// The application protocol negotiated in TLS, the one of HTTPS.
+const ALPN: &[u8] = b"http/1.1";
+
+/// How long to wait for a connection to be established, TLS and preamble included.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the nonce pairing the connections of a node lasts. The reverse connection is opened
+/// right after the direct one.
+const PAIRING_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The maximum size of a serialized [`Opening`] or [`Welcome`].
+const MAX_PREAMBLE_LENGTH: usize = 1_024;
+
+/// What anything that is not a node gets from a hub, as from any web server.
+const NOT_FOUND: &[u8] =
+    b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+/// An RPC transport over TLS over TCP.
+pub type BincodeOverTls<S, R> = Transport<TlsStream<Counted<TcpStream>>, R, S, Bincode<R, S>>;
+
+lazy_static::lazy_static! {
+    /// The TLS configuration shared by all clients in this process.
+    static ref CLIENT_CRYPTO: Arc<rustls::ClientConfig> = {
+        let mut crypto = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(SkipServerVerification::new())
+            .with_no_client_auth();
+        crypto.alpn_protocols = vec![ALPN.to_vec()];
+
+        Arc::new(crypto)
+    };
+}
+
+/// The bytes sent and received through a connection, TLS included.
+#[derive(Debug, Default)]
+pub struct ByteCount {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl ByteCount {
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+}
+
+/// A stream that counts the bytes going through it.
+pub struct Counted<S> {
+    inner: S,
+    count: Arc<ByteCount>,
+}
+
+impl<S> Counted<S> {
+    fn new(inner: S) -> Counted<S> {
+        Counted {
+            inner,
+            count: Arc::default(),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Counted<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let outcome = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = buf.filled().len() - before;
+        self.count
+            .received
+            .fetch_add(read as u64, Ordering::Relaxed);
+
+        outcome
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Counted<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let outcome = Pin::new(&mut self.inner).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(written)) = outcome {
+            self.count.sent.fetch_add(written as u64, Ordering::Relaxed);
+        }
+
+        outcome
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Which side of the RPC channel carried by a connection the node is.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Role {
+    /// The node is the client.
+    Direct,
+    /// The node is the server. The nonce is the one the hub gave in the direct connection.
+    Reverse { pairing: Hash },
+}
+
+/// What the node sends first.
+#[derive(Debug, Serialize, Deserialize)]
+struct Opening {
+    hello: Hello,
+    role: Role,
+}
+
+/// What the hub answers to an [`Opening`].
+#[derive(Debug, Serialize, Deserialize)]
+struct Welcome {
+    hello: Hello,
+    /// The nonce to be repeated in the reverse connection, if this is the direct one.
+    pairing: Option<Hash>,
+}
+
+async fn write_message<T: serde::Serialize>(
+    stream: &mut (impl AsyncWrite + Unpin),
+    message: &T,
+) -> Result<(), crate::Error> {
+    let serialized = bincode::serialize(message)?;
+    stream.write_u16(serialized.len() as u16).await?;
+    stream.write_all(&serialized).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+async fn read_bytes(stream: &mut (impl AsyncRead + Unpin)) -> Result<Vec<u8>, crate::Error> {
+    let length = stream.read_u16().await? as usize;
+
+    if length > MAX_PREAMBLE_LENGTH {
+        return Err(format!("preamble of {length} bytes is too long").into());
+    }
+
+    let mut serialized = vec![0; length];
+    stream.read_exact(&mut serialized).await?;
+
+    Ok(serialized)
+}
+
+async fn read_message<T: DeserializeOwned>(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> Result<T, crate::Error> {
+    Ok(bincode::deserialize(&read_bytes(stream).await?)?)
+}
+
+/// Reads an [`Opening`] and, if the node sent one, the [`Advertisement`] following it. A
+/// missing or invalid advertisement is ignored.
+async fn read_opening(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> Result<(Opening, Option<Advertisement>), crate::Error> {
+    let serialized = read_bytes(stream).await?;
+    let mut serialized = serialized.as_slice();
+    let opening: Opening = bincode::deserialize_from(&mut serialized)?;
+    let advertisement = bincode::deserialize_from::<_, Option<Advertisement>>(serialized)
+        .ok()
+        .flatten()
+        .filter(Advertisement::is_valid);
+
+    Ok((opening, advertisement))
+}
+
+/// A connection established with a hub.
+pub struct Connected<S, R> {
+    /// The transport for the RPC channel.
+    pub transport: BincodeOverTls<S, R>,
+    /// What was agreed with the hub.
+    pub protocol: Protocol,
+    /// The nonce to be sent with [`Role::Reverse`], if this is the direct connection.
+    pub pairing: Option<Hash>,
+    /// The bytes sent and received through this connection so far.
+    pub bytes: Arc<ByteCount>,
+    /// The fingerprint of the certificate presented by the hub, if any.
+    pub fingerprint: Option<Hash>,
+}
+
+/// Connects to a hub from the given local address, taking the given role in the RPC channel
+/// and sending the given advertisement, if any.
+pub async fn connect<S, R>(
+    bind_addr: IpAddr,
+    remote_addr: SocketAddr,
+    role: Role,
+    capabilities: Capabilities,
+    advertisement: Option<&Advertisement>,
+) -> Result<Connected<S, R>, crate::Error>
+where
+    S: serde::Serialize,
+    R: DeserializeOwned,
+{
+    let connect = async {
+        let socket = if remote_addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        socket.bind((bind_addr, 0).into())?;
+        let stream = socket.connect(remote_addr).await?;
+        stream.set_nodelay(true)?;
+        let stream = Counted::new(stream);
+        let bytes = stream.count.clone();
+
+        let server_name = rustls::ServerName::try_from(DEFAULT_SERVER_NAME).expect("valid name");
+        let mut stream = TlsConnector::from(CLIENT_CRYPTO.clone())
+            .connect(server_name, stream)
+            .await?;
+
+        let ours = Hello::ours(capabilities);
+        write_message(
+            &mut stream,
+            &(
+                Opening {
+                    hello: ours.clone(),
+                    role,
+                },
+                advertisement,
+            ),
+        )
+        .await?;
+        let welcome: Welcome = read_message(&mut stream).await?;
+        let fingerprint = stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certificates| certificates.first())
+            .map(|certificate| Hash::hash(&certificate.0));
+
+        Ok(Connected {
+            transport: Transport::from((TlsStream::from(stream), Bincode::default())),
+            protocol: Protocol::agree(&ours, &welcome.hello),
+            pairing: welcome.pairing,
+            bytes,
+            fingerprint,
+        }) as Result<_, crate::Error>
+    };
+
+    timeout(CONNECT_TIMEOUT, connect)
+        .await
+        .map_err(|_| format!("connection to {remote_addr} over TCP timed out"))?
+}
+
+/// The nonces given in direct connections, with the address of the node and when they were
+/// given.
+type Pairings = Arc<Mutex<BTreeMap<Hash, (SocketAddr, Instant)>>>;
+
+/// Gives a new nonce to the direct connection of a node at the given address.
+fn issue_pairing(pairings: &Pairings, client_addr: SocketAddr) -> Hash {
+    let pairing = Hash::rand();
+    let mut pairings = pairings.lock().expect("poisoned");
+    pairings.retain(|_, (_, issued)| issued.elapsed() < PAIRING_TIMEOUT);
+    pairings.insert(pairing, (client_addr, Instant::now()));
+
+    pairing
+}
+
+/// Finds the address of the direct connection that was given a nonce. Each nonce can be used
+/// only once and only from the same IP.
+fn redeem_pairing(
+    pairings: &Pairings,
+    pairing: &Hash,
+    peer_addr: SocketAddr,
+) -> Result<SocketAddr, crate::Error> {
+    let paired = pairings.lock().expect("poisoned").remove(pairing);
+
+    match paired {
+        Some((client_addr, issued))
+            if issued.elapsed() < PAIRING_TIMEOUT && client_addr.ip() == peer_addr.ip() =>
+        {
+            Ok(client_addr)
+        }
+        _ => Err(format!("reverse connection from {peer_addr} has no direct one").into()),
+    }
+}
+
+/// Listens to connections from nodes.
+pub struct Listener {
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    pairings: Pairings,
+}
+
+impl Listener {
+    /// Starts listening at the given address, presenting the given certificate.
+    pub async fn bind(
+        addr: SocketAddr,
+        certificate: TlsCertificate,
+    ) -> Result<Listener, io::Error> {
+        let (cert, key) = certificate.into_rustls();
+        let mut crypto = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert], key)
+            .expect("can build server config");
+        crypto.alpn_protocols = vec![ALPN.to_vec()];
+
+        Ok(Listener {
+            listener: TcpListener::bind(addr).await?,
+            acceptor: TlsAcceptor::from(Arc::new(crypto)),
+            pairings: Pairings::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        self.listener.local_addr()
+    }
+
+    /// Waits for the next TCP connection. The handshakes are left to [`Incoming::accept`], so
+    /// that they can be done concurrently.
+    pub async fn incoming(&self) -> Result<Incoming, io::Error> {
+        let (stream, peer_addr) = self.listener.accept().await?;
+        stream.set_nodelay(true)?;
+
+        Ok(Incoming {
+            stream,
+            peer_addr,
+            acceptor: self.acceptor.clone(),
+            pairings: self.pairings.clone(),
+        })
+    }
+}
+
+/// A TCP connection not yet known to be from a node.
+pub struct Incoming {
+    stream: TcpStream,
+    peer_addr: SocketAddr,
+    acceptor: TlsAcceptor,
+    pairings: Pairings,
+}
+
+/// A connection accepted from a node.
+pub struct Accepted {
+    /// The address by which the hub knows the node, the one of the direct connection for both
+    /// of its connections.
+    pub client_addr: SocketAddr,
+    /// Which side of the RPC channel the node is.
+    pub role: Role,
+    /// What was agreed with the node.
+    pub protocol: Protocol,
+    /// The round-trip time to the node, as estimated from the TLS handshake.
+    pub rtt: Duration,
+    /// What the node advertised about itself, if anything.
+    pub advertisement: Option<Advertisement>,
+    stream: TlsStream<Counted<TcpStream>>,
+}
+
+impl Accepted {
+    /// The transport for the RPC channel, whose types depend on the role of the node.
+    pub fn into_transport<S, R>(self) -> BincodeOverTls<S, R>
+    where
+        S: serde::Serialize,
+        R: DeserializeOwned,
+    {
+        Transport::from((self.stream, Bincode::default()))
+    }
+}
+
+impl Incoming {
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    /// Does the TLS handshake and exchanges the preamble. Anything else than a node gets a
+    /// `404 Not Found`.
+    pub async fn accept(self, capabilities: Capabilities) -> Result<Accepted, crate::Error> {
+        let peer_addr = self.peer_addr;
+        let accept = async {
+            // The server flight is answered after a round-trip:
+            let start = Instant::now();
+            let mut stream = self.acceptor.accept(Counted::new(self.stream)).await?;
+            let rtt = start.elapsed();
+
+            let (opening, advertisement) = match read_opening(&mut stream).await {
+                Ok(read) => read,
+                Err(err) => {
+                    stream.write_all(NOT_FOUND).await.ok();
+                    stream.shutdown().await.ok();
+                    return Err(err);
+                }
+            };
+
+            let (client_addr, pairing) = match opening.role {
+                Role::Direct => (peer_addr, Some(issue_pairing(&self.pairings, peer_addr))),
+                Role::Reverse { pairing } => {
+                    match redeem_pairing(&self.pairings, &pairing, peer_addr) {
+                        Ok(client_addr) => (client_addr, None),
+                        Err(err) => {
+                            stream.write_all(NOT_FOUND).await.ok();
+                            stream.shutdown().await.ok();
+                            return Err(err);
+                        }
+                    }
+                }
+            };
+
+            let ours = Hello::ours(capabilities);
+            write_message(
+                &mut stream,
+                &Welcome {
+                    hello: ours.clone(),
+                    pairing,
+                },
+            )
+            .await?;
+
+            Ok(Accepted {
+                client_addr,
+                role: opening.role,
+                protocol: Protocol::agree(&ours, &opening.hello),
+                rtt,
+                advertisement,
+                stream: TlsStream::from(stream),
+            }) as Result<_, crate::Error>
+        };
+
+        timeout(CONNECT_TIMEOUT, accept)
+            .await
+            .map_err(|_| format!("connection from {peer_addr} over TCP timed out"))?
+    }
+}
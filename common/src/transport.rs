@@ -41,6 +41,10 @@ where
         }
     }
 
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
     pub fn into_inner(self) -> (Connection, IncomingUniStreams) {
         (self.connection, self.incoming)
     }
@@ -0,0 +1,349 @@
+//! The addresses banned from this hub and the sharing of bans between cooperating hubs. Each
+//! hub signs its own blacklist with a key of its own and shares it with the hubs in
+//! `--share-blacklist-with`. A hub following the blacklist of a partner (see
+//! `--blacklist-feeds`) pins the key of the partner on first use and refuses the addresses the
+//! partner bans, unless they are in its own allowlist, for as long as the partner keeps
+//! publishing its blacklist (see `--shared-blacklist-ttl`). Only the bans made by the operator
+//! of a hub are shared, never the ones it got from other hubs. Nodes already connected are
+//! disconnected as soon as they get banned.
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::Keypair;
+use rocksdb::IteratorMode;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::RwLock;
+use std::time::Duration;
+use tarpc::context;
+use tokio::sync::Notify;
+use tokio::time;
+
+use samizdat_common::rpc::{BlacklistEntry, BlacklistFeed, HubClient};
+use samizdat_common::{Key, Signed};
+
+use crate::cli::AddrToResolve;
+use crate::db::{db, Table};
+use crate::CLI;
+
+/// The key under which the keypair signing the blacklist is kept in [`Table::Global`].
+const BLACKLIST_KEYPAIR_KEY: &[u8] = b"blacklist_keypair";
+
+/// A ban received from another hub, with the hub it came from and when its blacklist was last
+/// fetched.
+type SharedBan = (String, DateTime<Utc>, BlacklistEntry);
+
+lazy_static::lazy_static! {
+    /// The bans in the blacklists received from other hubs, by address.
+    static ref SHARED_BANS: RwLock<BTreeMap<IpAddr, Vec<SharedBan>>> = RwLock::default();
+    /// Wakes the connections waiting to be dropped if their nodes get banned.
+    static ref BANS_CHANGED: Notify = Notify::new();
+}
+
+/// An address never banned because of the blacklists of other hubs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllowlistEntry {
+    pub ip: IpAddr,
+    /// Why the address is allowed, for the operator's own reference.
+    pub note: Option<String>,
+    pub added_at: DateTime<Utc>,
+}
+
+/// The blacklist received from a partner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedBlacklist {
+    /// The partner the blacklist came from.
+    pub source: String,
+    /// The key pinned for the partner on first use. Blacklists signed with other keys are
+    /// refused until the pinned key is forgotten.
+    pub public_key: Key,
+    pub entries: Vec<BlacklistEntry>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The blacklist of this hub, as shown to its operator.
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalBlacklist {
+    /// The key signing the blacklist, for the operators of other hubs to check what they
+    /// pinned.
+    pub public_key: Key,
+    pub entries: Vec<BlacklistEntry>,
+}
+
+/// The keypair signing the blacklist of this hub, created on first use.
+fn keypair() -> Result<Keypair, crate::Error> {
+    if let Some(serialized) = db().get_cf(Table::Global.get(), BLACKLIST_KEYPAIR_KEY)? {
+        return Ok(Keypair::from_bytes(&serialized)
+            .map_err(|err| format!("bad blacklist keypair: {err}"))?);
+    }
+
+    let keypair = Keypair::generate(&mut rand::rngs::OsRng {});
+    db().put_cf(
+        Table::Global.get(),
+        BLACKLIST_KEYPAIR_KEY,
+        keypair.to_bytes(),
+    )?;
+
+    Ok(keypair)
+}
+
+fn get<T: DeserializeOwned>(
+    table: Table,
+    key: impl AsRef<[u8]>,
+) -> Result<Option<T>, crate::Error> {
+    let maybe_value = db().get_cf(table.get(), key)?;
+    Ok(maybe_value
+        .map(|value| bincode::deserialize(&value))
+        .transpose()?)
+}
+
+fn get_all<T: DeserializeOwned>(table: Table) -> Result<Vec<T>, crate::Error> {
+    db().iterator_cf(table.get(), IteratorMode::Start)
+        .map(|(_, value)| Ok(bincode::deserialize(&value)?))
+        .collect()
+}
+
+/// Loads the blacklists received from other hubs. Must be called after the database is
+/// initialized.
+pub fn init() -> Result<(), crate::Error> {
+    log::info!("Blacklist key is {}", Key::from(keypair()?.public));
+
+    index_shared_bans()
+}
+
+/// Rebuilds the index of the bans received from other hubs from the database.
+fn index_shared_bans() -> Result<(), crate::Error> {
+    let mut bans = BTreeMap::<_, Vec<SharedBan>>::new();
+
+    for shared in shared_blacklists()? {
+        for entry in shared.entries {
+            bans.entry(entry.ip).or_default().push((
+                shared.source.clone(),
+                shared.updated_at,
+                entry,
+            ));
+        }
+    }
+
+    *SHARED_BANS.write().expect("poisoned") = bans;
+    BANS_CHANGED.notify_waiters();
+
+    Ok(())
+}
+
+/// Why an address is banned from this hub, if it is. The allowlist overrides the bans
+/// received from other hubs, but not the bans made by the operator of this hub.
+pub fn ban_reason(ip: IpAddr) -> Result<Option<String>, crate::Error> {
+    if let Some(entry) = get::<BlacklistEntry>(Table::Blacklist, ip.to_string())? {
+        if !entry.is_expired() {
+            return Ok(Some(entry.reason));
+        }
+    }
+
+    if get::<AllowlistEntry>(Table::Allowlist, ip.to_string())?.is_some() {
+        return Ok(None);
+    }
+
+    let followed_since = Utc::now() - chrono::Duration::seconds(CLI.shared_blacklist_ttl as i64);
+    let bans = SHARED_BANS.read().expect("poisoned");
+    let shared_ban = bans
+        .get(&ip)
+        .into_iter()
+        .flatten()
+        .find(|(_, updated_at, entry)| *updated_at > followed_since && !entry.is_expired())
+        .map(|(source, _, entry)| format!("{} (banned by {source})", entry.reason));
+
+    Ok(shared_ban)
+}
+
+/// Whether a connection from a node is to be refused, because the node is banned.
+pub fn refuses(client_addr: SocketAddr) -> bool {
+    match ban_reason(client_addr.ip()) {
+        Ok(Some(reason)) => {
+            log::info!("Refusing connection from banned node {client_addr}: {reason}");
+            true
+        }
+        Ok(None) => false,
+        Err(err) => {
+            log::error!("failed to check whether {client_addr} is banned: {err}");
+            false
+        }
+    }
+}
+
+/// Resolves once a node is banned, for its connection to be dropped. Bans are checked again
+/// whenever the blacklists change and at every feed interval, when partner blacklists may
+/// have gone stale.
+pub async fn until_banned(client_addr: SocketAddr) {
+    let mut interval = time::interval(Duration::from_secs(CLI.blacklist_feed_interval));
+
+    loop {
+        let changed = BANS_CHANGED.notified();
+
+        match ban_reason(client_addr.ip()) {
+            Ok(Some(reason)) => {
+                log::info!("Dropping connection from banned node {client_addr}: {reason}");
+                return;
+            }
+            Ok(None) => {}
+            Err(err) => log::error!("failed to check whether {client_addr} is banned: {err}"),
+        }
+
+        tokio::select! {
+            _ = changed => {}
+            _ = interval.tick() => {}
+        }
+    }
+}
+
+/// The blacklist of this hub, including expired bans.
+pub fn blacklist() -> Result<LocalBlacklist, crate::Error> {
+    Ok(LocalBlacklist {
+        public_key: Key::from(keypair()?.public),
+        entries: get_all(Table::Blacklist)?,
+    })
+}
+
+/// Bans an address from this hub, replacing any previous ban of the same address.
+pub fn ban(entry: BlacklistEntry) -> Result<(), crate::Error> {
+    db().put_cf(
+        Table::Blacklist.get(),
+        entry.ip.to_string(),
+        bincode::serialize(&entry).expect("can serialize"),
+    )?;
+
+    BANS_CHANGED.notify_waiters();
+
+    Ok(())
+}
+
+/// Lifts the ban of an address made by the operator of this hub.
+pub fn unban(ip: IpAddr) -> Result<(), crate::Error> {
+    db().delete_cf(Table::Blacklist.get(), ip.to_string())?;
+    Ok(())
+}
+
+pub fn allowlist() -> Result<Vec<AllowlistEntry>, crate::Error> {
+    get_all(Table::Allowlist)
+}
+
+/// Makes an address immune to the bans received from other hubs.
+pub fn allow(entry: AllowlistEntry) -> Result<(), crate::Error> {
+    db().put_cf(
+        Table::Allowlist.get(),
+        entry.ip.to_string(),
+        bincode::serialize(&entry).expect("can serialize"),
+    )?;
+
+    Ok(())
+}
+
+pub fn disallow(ip: IpAddr) -> Result<(), crate::Error> {
+    db().delete_cf(Table::Allowlist.get(), ip.to_string())?;
+    BANS_CHANGED.notify_waiters();
+    Ok(())
+}
+
+pub fn shared_blacklists() -> Result<Vec<SharedBlacklist>, crate::Error> {
+    get_all(Table::SharedBlacklists)
+}
+
+/// Forgets the blacklist received from a partner and the key pinned for it. The next
+/// blacklist received from the partner is trusted on first use again.
+pub fn forget_shared_blacklist(source: &str) -> Result<(), crate::Error> {
+    db().delete_cf(Table::SharedBlacklists.get(), source)?;
+    index_shared_bans()
+}
+
+/// Whether the blacklist of this hub is shared with a given hub.
+pub fn shares_with(ip: IpAddr) -> bool {
+    CLI.share_blacklist_with.contains(&ip)
+}
+
+/// The current bans of this hub, signed, to be sent to the hubs following it.
+pub fn feed() -> Result<BlacklistFeed, crate::Error> {
+    let keypair = keypair()?;
+    let entries = get_all::<BlacklistEntry>(Table::Blacklist)?
+        .into_iter()
+        .filter(|entry| !entry.is_expired())
+        .collect();
+
+    Ok(BlacklistFeed {
+        public_key: Key::from(keypair.public),
+        entries: Signed::new(entries, &keypair),
+    })
+}
+
+/// Replaces the blacklist received from a partner with a new one, if it is signed with the
+/// key pinned for the partner.
+fn receive(source: &str, feed: BlacklistFeed) -> Result<(), crate::Error> {
+    if !feed.is_valid() {
+        return Err(crate::Error::PeerMisbehavior(format!(
+            "blacklist of {source} has a bad signature"
+        )));
+    }
+
+    match get::<SharedBlacklist>(Table::SharedBlacklists, source)? {
+        Some(pinned) if pinned.public_key != feed.public_key => {
+            return Err(crate::Error::PeerMisbehavior(format!(
+                "blacklist of {source} signed with {} instead of the pinned key {}; if this is \
+                expected, forget the pinned key",
+                feed.public_key, pinned.public_key
+            )));
+        }
+        Some(_) => {}
+        None => log::info!("Pinning blacklist key {} for {source}", feed.public_key),
+    }
+
+    let shared = SharedBlacklist {
+        source: source.to_owned(),
+        public_key: feed.public_key,
+        entries: feed
+            .entries
+            .into_inner()
+            .into_iter()
+            .filter(|entry| !entry.is_expired())
+            .collect(),
+        updated_at: Utc::now(),
+    };
+
+    log::debug!("Received {} bans from {source}", shared.entries.len());
+
+    db().put_cf(
+        Table::SharedBlacklists.get(),
+        source,
+        bincode::serialize(&shared).expect("can serialize"),
+    )?;
+
+    index_shared_bans()
+}
+
+/// Whether this hub follows the blacklist of a partner.
+pub fn follows(partner: &AddrToResolve) -> bool {
+    CLI.blacklist_feeds
+        .iter()
+        .any(|feed| feed.to_string() == partner.to_string())
+}
+
+/// Fetches the blacklist of a partner periodically, until the connection to it is lost.
+pub async fn follow(source: &'static str, client: HubClient) {
+    let mut interval = time::interval(Duration::from_secs(CLI.blacklist_feed_interval));
+
+    loop {
+        interval.tick().await;
+
+        match client.get_blacklist(context::current()).await {
+            Ok(Some(feed)) => {
+                if let Err(err) = receive(source, feed) {
+                    log::warn!("Refused blacklist of {source}: {err}");
+                }
+            }
+            Ok(None) => log::warn!("{source} does not share its blacklist with this hub"),
+            Err(err) => {
+                log::info!("Stopped following blacklist of {source}: {err}");
+                return;
+            }
+        }
+    }
+}
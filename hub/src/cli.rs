@@ -1,9 +1,12 @@
 use std::fmt::{self, Display};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::num::ParseIntError;
+use std::path::PathBuf;
 use std::str::FromStr;
 use structopt::StructOpt;
 
+use crate::rpc::node_sampler::SamplerKind;
+
 #[derive(StructOpt)]
 pub struct Cli {
     /// Set logging level.
@@ -15,6 +18,18 @@ pub struct Cli {
     /// The port for nodes to connect as servers.
     #[structopt(env = "SAMIZDAT_REVERSE_ADDRESSES", long, default_value = "[::]:4512")]
     pub reverse_addresses: Vec<SocketAddr>,
+    /// The socket addresses for nodes to connect through TLS over TCP, for when UDP is blocked
+    /// in their networks. Nodes look for it in port 4513 by default. Where only HTTPS gets
+    /// through, serve it on port 443, which needs privileges.
+    #[structopt(
+        env = "SAMIZDAT_TCP_FALLBACK_ADDRESSES",
+        long,
+        default_value = "[::]:4513"
+    )]
+    pub tcp_fallback_addresses: Vec<SocketAddr>,
+    /// Do not serve nodes over TCP.
+    #[structopt(env = "SAMIZDAT_NO_TCP_FALLBACK", long)]
+    pub no_tcp_fallback: bool,
     #[structopt(env = "SAMIZDAT_DATA", long, default_value = "data/hub")]
     pub data: String,
     /// Maximum number of simultaneous connections.
@@ -33,9 +48,66 @@ pub struct Cli {
     /// The maximum number of candidates to return to the client.
     #[structopt(env = "SAMIZDAT_MAX_CANDIDATES", long, default_value = "3")]
     pub max_candidates: usize,
+    /// The number of queries being resolved at the same time above which the hub is
+    /// overloaded and starts rejecting queries.
+    #[structopt(env = "SAMIZDAT_MAX_PENDING_QUERIES", long, default_value = "1024")]
+    pub max_pending_queries: usize,
+    /// The time in milliseconds peers should take to answer a resolution. Above this, the hub
+    /// is overloaded and starts rejecting queries.
+    #[structopt(
+        env = "SAMIZDAT_TARGET_RESOLUTION_LATENCY",
+        long,
+        default_value = "1000"
+    )]
+    pub target_resolution_latency: u64,
+    /// The load (relative to the capacity) above which nodes must send a proof of work with
+    /// each query. The work demanded grows with the load, up to `--max-query-work` when the
+    /// hub is overloaded.
+    #[structopt(env = "SAMIZDAT_QUERY_WORK_THRESHOLD", long, default_value = "0.75")]
+    pub query_work_threshold: f64,
+    /// The most work (in expected number of hashes) demanded from each query.
+    #[structopt(env = "SAMIZDAT_MAX_QUERY_WORK", long, default_value = "100000")]
+    pub max_query_work: f64,
+    /// IPs of nodes (e.g., your own) never asked for a proof of work.
+    #[structopt(env = "SAMIZDAT_QUERY_WORK_EXEMPT", long)]
+    pub query_work_exempt: Vec<IpAddr>,
+    /// The strategy used to choose the peers asked to resolve queries: `uniform`, `query` or
+    /// `locality`. This can be changed at runtime.
+    #[structopt(env = "SAMIZDAT_QUERY_SAMPLER", long, default_value = "query")]
+    pub query_sampler: SamplerKind,
+    /// A CSV file of address ranges and their countries (`first,last,country`), used to
+    /// prefer peers near to or far from the nodes querying, as the nodes ask. If not set, the
+    /// location of nodes is ignored.
+    #[structopt(env = "SAMIZDAT_GEOIP_DATABASE", long)]
+    pub geoip_database: Option<PathBuf>,
     /// Other servers to which to listen to.
     #[structopt(env = "SAMIZDAT_PARTNERS", long)]
     pub partners: Option<Vec<AddrToResolve>>,
+    /// Partners whose blacklists this hub follows, banning the addresses they ban (unless
+    /// allowed locally). The partners must share their blacklists with this hub.
+    #[structopt(env = "SAMIZDAT_BLACKLIST_FEEDS", long)]
+    pub blacklist_feeds: Vec<AddrToResolve>,
+    /// IPs of the hubs allowed to follow the blacklist of this hub.
+    #[structopt(env = "SAMIZDAT_SHARE_BLACKLIST_WITH", long)]
+    pub share_blacklist_with: Vec<IpAddr>,
+    /// (s) How often the blacklists of the partners in `--blacklist-feeds` are fetched.
+    #[structopt(env = "SAMIZDAT_BLACKLIST_FEED_INTERVAL", long, default_value = "600")]
+    pub blacklist_feed_interval: u64,
+    /// (s) For how long the bans of a partner are followed after its blacklist was last
+    /// fetched. Bans of partners that stopped publishing their blacklists are dropped after
+    /// that.
+    #[structopt(env = "SAMIZDAT_SHARED_BLACKLIST_TTL", long, default_value = "86400")]
+    pub shared_blacklist_ttl: u64,
+    /// A file shared by all replicas of this hub, holding the lease of the leader. Only the
+    /// leader connects to the partners. If not set, this hub is not replicated.
+    #[structopt(env = "SAMIZDAT_LEADER_LEASE", long)]
+    pub leader_lease: Option<PathBuf>,
+    /// (s) For how long the lease of the leader lasts without being renewed.
+    #[structopt(env = "SAMIZDAT_LEADER_LEASE_DURATION", long, default_value = "30")]
+    pub leader_lease_duration: u64,
+    /// The identifier of this replica in the leader lease. Defaults to the host name.
+    #[structopt(env = "SAMIZDAT_REPLICA_ID", long)]
+    pub replica_id: Option<String>,
     /// The port for the monitoring http server.
     #[structopt(env = "SAMIZDAT_HTTP_PORT", long, default_value = "45180")]
     pub http_port: u16,
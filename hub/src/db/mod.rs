@@ -68,6 +68,12 @@ pub enum Table {
     Migrations,
     /// The list of all recent nonces. This is to mitigate replay attacks.
     RecentNonces,
+    /// The addresses banned by the operator of this hub.
+    Blacklist,
+    /// The addresses never banned, whatever the blacklists of other hubs say.
+    Allowlist,
+    /// The blacklists received from other hubs, with their pinned keys.
+    SharedBlacklists,
 }
 
 impl Display for Table {
@@ -0,0 +1,91 @@
+//! Where nodes are, as far as a GeoIP database tells. The database is a CSV file of address
+//! ranges and the countries they are in (`first,last,country`), such as the free IP to
+//! country databases of DB-IP. Without a database, the location of nodes is unknown and
+//! queries ignore the geographic preference of nodes.
+
+use serde::{Serialize, Serializer};
+use std::fmt::{self, Display};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::RwLock;
+
+lazy_static::lazy_static! {
+    /// The address ranges in the GeoIP database, sorted by their first address.
+    static ref RANGES: RwLock<Vec<(IpAddr, IpAddr, Country)>> = RwLock::default();
+}
+
+/// An ISO 3166-1 alpha-2 country code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Country([u8; 2]);
+
+impl Display for Country {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.0[0] as char, self.0[1] as char)
+    }
+}
+
+impl Serialize for Country {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Parses a line of the database into an address range and its country.
+fn parse_line(line: &str) -> Result<(IpAddr, IpAddr, Country), String> {
+    let mut fields = line.split(',').map(|field| field.trim().trim_matches('"'));
+    let mut next_field = || fields.next().ok_or_else(|| "missing field".to_owned());
+
+    let first = next_field()?
+        .parse::<IpAddr>()
+        .map_err(|err| err.to_string())?;
+    let last = next_field()?
+        .parse::<IpAddr>()
+        .map_err(|err| err.to_string())?;
+    let country = match next_field()?.as_bytes() {
+        &[a, b] if a.is_ascii_alphabetic() && b.is_ascii_alphabetic() => {
+            Country([a.to_ascii_uppercase(), b.to_ascii_uppercase()])
+        }
+        _ => return Err("bad country code".to_owned()),
+    };
+
+    if first.is_ipv4() != last.is_ipv4() || first > last {
+        return Err(format!("bad range {first}-{last}"));
+    }
+
+    Ok((first, last, country))
+}
+
+/// Loads the GeoIP database in the given file.
+pub fn init(path: &Path) -> Result<(), crate::Error> {
+    let contents = fs::read_to_string(path)?;
+    let mut ranges = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            parse_line(line).map_err(|err| {
+                crate::Error::ValidationFailed(format!("{}, line {}: {err}", path.display(), i + 1))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    ranges.sort_unstable_by_key(|&(first, _, _)| first);
+    log::info!(
+        "Loaded {} address ranges from GeoIP database {}",
+        ranges.len(),
+        path.display()
+    );
+    *RANGES.write().expect("poisoned") = ranges;
+
+    Ok(())
+}
+
+/// The country an address is in, if known.
+pub fn country(ip: IpAddr) -> Option<Country> {
+    let ranges = RANGES.read().expect("poisoned");
+    let after = ranges.partition_point(|&(first, _, _)| first <= ip);
+    let &(_, last, country) = ranges.get(after.checked_sub(1)?)?;
+
+    (ip <= last).then_some(country)
+}
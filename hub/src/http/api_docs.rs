@@ -0,0 +1,54 @@
+//! The description of the HTTP API of the hub (see [`samizdat_common::api_docs`]).
+
+use warp::Filter;
+
+use samizdat_common::api_docs::{self, Endpoint};
+
+/// Shorthand for an [`Endpoint`]. The API of the hub is only open to the loopback, so no
+/// route asks for access rights.
+const fn endpoint(method: &'static str, path: &'static str, summary: &'static str) -> Endpoint {
+    Endpoint {
+        method,
+        path,
+        summary,
+        rights: None,
+    }
+}
+
+/// All the routes of the hub.
+#[rustfmt::skip]
+const ENDPOINTS: &[Endpoint] = &[
+    endpoint("get", "/healthz", "Tells whether the hub is alive (open outside the loopback)."),
+    endpoint("get", "/readyz", "Tells whether the hub is ready (open outside the loopback)."),
+    endpoint("get", "/connected-ips", "Lists the addresses of the connected nodes."),
+    endpoint("get", "/node-versions", "Counts the connected nodes advertising each version of the software and of the transfer protocol."),
+    endpoint("get", "/resolution-order", "Lists the peers, in the order they would be asked to resolve a query from `addr`, preferring peers as in `geo` (`any`, `near` or `far`)."),
+    endpoint("get", "/partner-policy", "Shows the policy restricting which resolutions get forwarded between partners."),
+    endpoint("put", "/partner-policy", "Replaces the policy restricting which resolutions get forwarded between partners, by rate, kind of query or sampling."),
+    endpoint("get", "/load", "Shows the current load of the hub, on which admission control is based."),
+    endpoint("get", "/leader", "Shows whether this replica is the leader and the current leader lease."),
+    endpoint("get", "/query-sampler", "Shows which sampler is used to choose the peers asked to resolve queries."),
+    endpoint("put", "/query-sampler", "Changes the sampler used to choose the peers asked to resolve queries."),
+    endpoint("get", "/blacklist", "Shows the addresses banned by this hub and the key signing its blacklist."),
+    endpoint("put", "/blacklist/{ip}", "Bans an address from this hub. The ban is shared with the hubs following this one."),
+    endpoint("delete", "/blacklist/{ip}", "Lifts the ban of an address."),
+    endpoint("get", "/allowlist", "Shows the addresses never banned because of the blacklists of other hubs."),
+    endpoint("put", "/allowlist/{ip}", "Makes an address immune to the blacklists of other hubs."),
+    endpoint("delete", "/allowlist/{ip}", "Removes an address from the allowlist."),
+    endpoint("get", "/blacklist-feeds", "Shows the blacklists received from partners, with the keys pinned for them."),
+    endpoint("delete", "/blacklist-feeds/{source}", "Forgets the blacklist received from a partner and the key pinned for it."),
+];
+
+/// Serves the OpenAPI document of the hub and the page showing it.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    api_docs::serve("Samizdat Hub", env!("CARGO_PKG_VERSION"), ENDPOINTS)
+}
+
+#[test]
+fn test_all_routes_listed() {
+    let sources = [include_str!("mod.rs")];
+    assert_eq!(
+        api_docs::unlisted_routes(&sources, ENDPOINTS),
+        Vec::<String>::new()
+    );
+}
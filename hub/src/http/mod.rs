@@ -1,40 +1,30 @@
+mod api_docs;
 mod auth;
 
+use chrono::{DateTime, Utc};
 use futures::{Future, StreamExt};
-use serde_derive::Deserialize;
-use std::net::SocketAddr;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
 use warp::Filter;
 
-use crate::rpc::node_sampler::QuerySampler;
+use samizdat_common::rpc::{BlacklistEntry, GeoPreference};
+
+use crate::blacklist::{self, AllowlistEntry};
+use crate::leader;
+use crate::rpc::admission;
+use crate::rpc::node_sampler::{self, GeoSampler, SamplerKind};
+use crate::rpc::partner_policy::{self, PartnerPolicy};
 use crate::rpc::ROOM;
 use crate::{balanced_or_tree, CLI};
 
-fn error_status_code(err: &crate::Error) -> http::StatusCode {
-    match err {
-        crate::Error::Message(_) => http::StatusCode::BAD_REQUEST,
-        crate::Error::Rpc(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
-        crate::Error::Base64(_) => http::StatusCode::BAD_REQUEST,
-        crate::Error::Db(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
-        crate::Error::Io(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
-        crate::Error::BadHashLength(_) => http::StatusCode::BAD_REQUEST,
-        crate::Error::Bincode(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
-        crate::Error::QuicConnectionError(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
-        crate::Error::AllCandidatesFailed => http::StatusCode::BAD_GATEWAY,
-        crate::Error::InvalidCollectionItem => http::StatusCode::BAD_REQUEST,
-        crate::Error::InvalidEdition => http::StatusCode::BAD_REQUEST,
-        crate::Error::DifferentPublicKeys => http::StatusCode::BAD_REQUEST,
-        crate::Error::NoHeaderRead => http::StatusCode::INTERNAL_SERVER_ERROR,
-        _ => http::StatusCode::INTERNAL_SERVER_ERROR,
-    }
-}
-
 fn api_reply<T>(t: Result<T, crate::Error>) -> impl warp::Reply
 where
     T: serde::Serialize,
 {
     let status = t
         .as_ref()
-        .map_err(error_status_code)
+        .map_err(crate::Error::status_code)
         .err()
         .unwrap_or_default();
     let json = t.map_err(|err| err.to_string());
@@ -54,8 +44,8 @@ fn tuple<T>(t: T) -> (T,) {
 }
 
 pub fn serve() -> impl Future<Output = ()> {
-    let server = warp::filters::addr::remote()
-        .and_then(|addr: Option<std::net::SocketAddr>| async move {
+    let outside_loopback =
+        warp::filters::addr::remote().and_then(|addr: Option<std::net::SocketAddr>| async move {
             if let Some(addr) = addr {
                 if addr.ip().to_canonical().is_loopback() {
                     return Err(warp::reject::not_found());
@@ -66,7 +56,12 @@ pub fn serve() -> impl Future<Output = ()> {
                 "cannot connect outside loopback",
                 ::http::StatusCode::FORBIDDEN,
             ))
-        })
+        });
+
+    // Health checks come before the loopback check, since they are meant for load balancers
+    // and container orchestrators:
+    let server = health()
+        .or(outside_loopback)
         .or(warp::get().and(warp::path::end()).map(|| {
             warp::reply::with_header(include_str!("../index.html"), "Content-Type", "text/html")
         }))
@@ -77,8 +72,45 @@ pub fn serve() -> impl Future<Output = ()> {
     warp::serve(server).run(([0; 16], CLI.http_port))
 }
 
+/// The liveness (`/healthz`) and readiness (`/readyz`) checks. The hub is ready when its
+/// database is readable. These need no authentication and tell nothing but `ok` or what is
+/// wrong.
+fn health() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let healthz = warp::path!("healthz").map(|| Ok::<_, String>("ok".to_owned()));
+    let readyz = warp::path!("readyz").map(|| match crate::db::db().get(b"") {
+        Ok(_) => Ok("ok".to_owned()),
+        Err(err) => Err(format!("database not readable: {err}")),
+    });
+
+    warp::get()
+        .and(healthz.or(readyz).unify())
+        .map(|readiness: Result<String, String>| match readiness {
+            Ok(ok) => warp::reply::with_status(ok, http::StatusCode::OK),
+            Err(err) => warp::reply::with_status(err, http::StatusCode::SERVICE_UNAVAILABLE),
+        })
+}
+
 fn api() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    balanced_or_tree!(connected_ips(), resolution_order())
+    balanced_or_tree!(
+        connected_ips(),
+        node_versions(),
+        resolution_order(),
+        get_partner_policy(),
+        put_partner_policy(),
+        get_load(),
+        get_leader(),
+        get_query_sampler(),
+        put_query_sampler(),
+        get_blacklist(),
+        put_blacklist(),
+        delete_blacklist(),
+        get_allowlist(),
+        put_allowlist(),
+        delete_allowlist(),
+        get_blacklist_feeds(),
+        delete_blacklist_feed(),
+        api_docs::api()
+    )
 }
 
 fn connected_ips() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
@@ -96,19 +128,76 @@ fn connected_ips() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::R
         .map(tuple)
 }
 
+/// How many of the connected nodes advertise each version of the software and of the transfer
+/// protocol. The direct and reverse connections of a node are counted once, but nodes connected
+/// through many addresses are counted once for each.
+fn node_versions() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Debug, Default, Serialize)]
+    struct NodeVersions {
+        /// Nodes that advertised what they are.
+        advertised: usize,
+        /// Connections from nodes that advertised nothing, e.g., because they predate it.
+        unadvertised: usize,
+        software_versions: BTreeMap<String, usize>,
+        transfer_versions: BTreeMap<u32, usize>,
+        relays: usize,
+    }
+
+    warp::path!("node-versions")
+        .and(warp::get())
+        .and_then(|| async {
+            let mut versions = NodeVersions::default();
+            let mut by_key = BTreeMap::new();
+
+            for node in ROOM.raw_participants().await.values() {
+                match node.advertisement() {
+                    Some(advertisement) => {
+                        by_key.insert(
+                            advertisement.public_key.to_string(),
+                            advertisement.info.clone(),
+                        );
+                    }
+                    None => versions.unadvertised += 1,
+                }
+            }
+
+            for info in by_key.values() {
+                versions.advertised += 1;
+                *versions
+                    .software_versions
+                    .entry(info.software_version.clone())
+                    .or_default() += 1;
+
+                for &version in &info.transfer_versions {
+                    *versions.transfer_versions.entry(version).or_default() += 1;
+                }
+
+                if info.relays {
+                    versions.relays += 1;
+                }
+            }
+
+            Ok(api_reply(Ok(versions))) as Result<_, warp::Rejection>
+        })
+        .map(tuple)
+}
+
 fn resolution_order() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
 {
     #[derive(Deserialize)]
     struct QueryParameters {
         addr: SocketAddr,
+        #[serde(default)]
+        geo: GeoPreference,
     }
 
     warp::path!("resolution-order")
         .and(warp::get())
         .and(warp::query())
-        .and_then(|QueryParameters { addr }| async move {
+        .and_then(|QueryParameters { addr, geo }| async move {
+            let sampler = GeoSampler::new(node_sampler::query_sampler(), geo, addr);
             let resolution_order = ROOM
-                .stream_peers(QuerySampler, addr)
+                .stream_peers(sampler, addr)
                 .await
                 .map(|(peer_ip, _)| peer_ip)
                 .collect::<Vec<_>>()
@@ -117,3 +206,152 @@ fn resolution_order() -> impl Filter<Extract = (impl warp::Reply,), Error = warp
         })
         .map(tuple)
 }
+
+/// Shows the policy restricting which resolutions get forwarded between partners.
+fn get_partner_policy(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("partner-policy")
+        .and(warp::get())
+        .map(|| api_reply(Ok(partner_policy::partner_policy())))
+}
+
+/// Replaces the policy restricting which resolutions get forwarded between partners.
+fn put_partner_policy(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("partner-policy")
+        .and(warp::put())
+        .and(warp::body::json())
+        .map(|policy: PartnerPolicy| api_reply(partner_policy::set_partner_policy(policy)))
+}
+
+/// Shows the current load of the hub, on which admission control is based.
+fn get_load() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("load")
+        .and(warp::get())
+        .map(|| api_reply(Ok(admission::load())))
+}
+
+/// Shows whether this replica is the leader, which connects to the partners, and the current
+/// leader lease, if the hub is replicated.
+fn get_leader() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Serialize)]
+    struct Leadership {
+        is_leader: bool,
+        lease: Option<leader::Lease>,
+    }
+
+    warp::path!("leader").and(warp::get()).map(|| {
+        api_reply(leader::current_lease().map(|lease| Leadership {
+            is_leader: leader::is_leader(),
+            lease,
+        }))
+    })
+}
+
+/// Shows which sampler is used to choose the peers asked to resolve queries.
+fn get_query_sampler() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("query-sampler")
+        .and(warp::get())
+        .map(|| api_reply(Ok(node_sampler::query_sampler_kind())))
+}
+
+/// Changes the sampler used to choose the peers asked to resolve queries.
+fn put_query_sampler() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("query-sampler")
+        .and(warp::put())
+        .and(warp::body::json())
+        .map(|kind: SamplerKind| {
+            node_sampler::set_query_sampler(kind);
+            api_reply(Ok(()))
+        })
+}
+
+/// Shows the addresses banned by this hub and the key signing its blacklist.
+fn get_blacklist() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("blacklist")
+        .and(warp::get())
+        .map(|| api_reply(blacklist::blacklist()))
+}
+
+/// Bans an address from this hub. The ban is shared with the hubs following this one.
+fn put_blacklist() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        reason: String,
+        #[serde(default)]
+        expires_at: Option<DateTime<Utc>>,
+    }
+
+    warp::path!("blacklist" / IpAddr)
+        .and(warp::put())
+        .and(warp::body::json())
+        .map(|ip, request: Request| {
+            api_reply(blacklist::ban(BlacklistEntry {
+                ip,
+                reason: request.reason,
+                added_at: Utc::now(),
+                expires_at: request.expires_at,
+            }))
+        })
+}
+
+/// Lifts the ban of an address.
+fn delete_blacklist() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("blacklist" / IpAddr)
+        .and(warp::delete())
+        .map(|ip| api_reply(blacklist::unban(ip)))
+}
+
+/// Shows the addresses never banned because of the blacklists of other hubs.
+fn get_allowlist() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("allowlist")
+        .and(warp::get())
+        .map(|| api_reply(blacklist::allowlist()))
+}
+
+/// Makes an address immune to the blacklists of other hubs.
+fn put_allowlist() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        #[serde(default)]
+        note: Option<String>,
+    }
+
+    warp::path!("allowlist" / IpAddr)
+        .and(warp::put())
+        .and(warp::body::json())
+        .map(|ip, request: Request| {
+            api_reply(blacklist::allow(AllowlistEntry {
+                ip,
+                note: request.note,
+                added_at: Utc::now(),
+            }))
+        })
+}
+
+/// Removes an address from the allowlist.
+fn delete_allowlist() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("allowlist" / IpAddr)
+        .and(warp::delete())
+        .map(|ip| api_reply(blacklist::disallow(ip)))
+}
+
+/// Shows the blacklists received from partners, with the keys pinned for them.
+fn get_blacklist_feeds(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("blacklist-feeds")
+        .and(warp::get())
+        .map(|| api_reply(blacklist::shared_blacklists()))
+}
+
+/// Forgets the blacklist received from a partner and the key pinned for it.
+fn delete_blacklist_feed(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("blacklist-feeds" / String)
+        .and(warp::delete())
+        .map(|source: String| api_reply(blacklist::forget_shared_blacklist(&source)))
+}
@@ -0,0 +1,145 @@
+//! Leader election between replicas of the same hub. Every replica serves queries, but only
+//! the leader connects to the partner hubs, so that partners see a single hub-as-node and
+//! queries are not forwarded once per replica.
+//!
+//! The election is a lease in a file shared by all replicas (e.g., a volume mounted in every
+//! pod). The leader renews the lease periodically; the other replicas take it over once it
+//! expires. Writes are atomic renames, and a replica only considers itself the leader after
+//! reading the lease back, so that concurrent takeovers are settled by whoever wrote last.
+//! Clocks of the replicas must agree to well within the lease duration.
+
+use serde_derive::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time;
+
+use crate::CLI;
+
+/// The time to wait after writing the lease before reading it back.
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// The content of the lease file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lease {
+    /// The replica holding the lease.
+    pub holder: String,
+    /// When the lease expires, if not renewed.
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+lazy_static::lazy_static! {
+    /// Whether this replica is the leader. Without a lease, every replica leads.
+    static ref IS_LEADER: (watch::Sender<bool>, watch::Receiver<bool>) =
+        watch::channel(CLI.leader_lease.is_none());
+    /// The identifier of this replica in the lease. In Kubernetes, the host name is the name
+    /// of the pod.
+    static ref REPLICA_ID: String = CLI
+        .replica_id
+        .clone()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+}
+
+/// The identifier of this replica in the lease.
+fn replica_id() -> &'static str {
+    &REPLICA_ID
+}
+
+/// Whether this replica is currently the leader.
+pub fn is_leader() -> bool {
+    *IS_LEADER.1.borrow()
+}
+
+/// Waits until this replica becomes (or stops being) the leader.
+pub async fn wait_for_leadership(is_leader: bool) {
+    let mut receiver = IS_LEADER.1.clone();
+    while *receiver.borrow_and_update() != is_leader {
+        if receiver.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Reads the current lease, if any.
+pub fn current_lease() -> Result<Option<Lease>, crate::Error> {
+    let path = match &CLI.leader_lease {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+
+    match std::fs::read(path) {
+        Ok(content) => Ok(serde_json::from_slice(&content).ok()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Writes a lease held by this replica, atomically.
+fn write_lease(path: &Path, duration: Duration) -> Result<(), crate::Error> {
+    let lease = Lease {
+        holder: replica_id().to_owned(),
+        expires_at: chrono::Utc::now()
+            + chrono::Duration::from_std(duration).expect("lease duration is not too long"),
+    };
+
+    let temp_path = path.with_extension(format!("{}.tmp", replica_id()));
+    std::fs::write(
+        &temp_path,
+        serde_json::to_vec(&lease).expect("can serialize"),
+    )?;
+    std::fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
+
+/// Takes or renews the lease, if possible, returning whether this replica holds it.
+async fn try_lead(path: &Path, duration: Duration) -> Result<bool, crate::Error> {
+    let can_take = match current_lease()? {
+        Some(lease) => lease.holder == replica_id() || lease.expires_at < chrono::Utc::now(),
+        None => true,
+    };
+
+    if !can_take {
+        return Ok(false);
+    }
+
+    write_lease(path, duration)?;
+
+    // Someone else may have taken the lease at the same time. The last to write wins:
+    time::sleep(SETTLE_TIME).await;
+    Ok(current_lease()?.map(|lease| lease.holder == replica_id()) == Some(true))
+}
+
+/// Runs the election forever, if a lease file was configured.
+pub async fn run_election() {
+    let path = match &CLI.leader_lease {
+        Some(path) => path,
+        None => return,
+    };
+
+    let duration = Duration::from_secs(CLI.leader_lease_duration);
+    let mut interval = time::interval(duration / 3);
+
+    loop {
+        interval.tick().await;
+
+        let is_leader = match try_lead(path, duration).await {
+            Ok(is_leader) => is_leader,
+            Err(err) => {
+                // Without being able to renew, one cannot be sure to still hold the lease:
+                log::error!("Failed to access leader lease at {path:?}: {err}");
+                false
+            }
+        };
+
+        if is_leader != self::is_leader() {
+            log::info!(
+                "Replica {} {} the leader",
+                replica_id(),
+                if is_leader { "is now" } else { "is no longer" }
+            );
+            IS_LEADER.0.send(is_leader).ok();
+        }
+    }
+}
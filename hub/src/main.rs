@@ -1,8 +1,11 @@
 #![feature(ip)]
 
+mod blacklist;
 mod cli;
 mod db;
+mod geoip;
 mod http;
+mod leader;
 mod replay_resistance;
 mod rpc;
 mod slow_compiler_workaround;
@@ -40,22 +43,44 @@ async fn main() -> Result<(), crate::Error> {
     let _ = logger::init_logger(CLI.verbose);
 
     db::init_db()?;
+    blacklist::init()?;
+
+    if let Some(path) = &CLI.geoip_database {
+        geoip::init(path)?;
+    }
+    let certificate = crate::rpc::tls_certificate()?;
 
     // Spawn services:
     let candidate_channels = KeyedChannel::new();
     let direct_rpc_server = tokio::spawn(crate::rpc::run_direct(
         CLI.direct_addresses.clone(),
+        certificate.clone(),
+        candidate_channels.clone(),
+    ));
+    let reverse_rpc_server = tokio::spawn(crate::rpc::run_reverse(
+        CLI.reverse_addresses.clone(),
+        certificate.clone(),
+    ));
+    let tcp_fallback_server = tokio::spawn(crate::rpc::run_tcp_fallback(
+        if CLI.no_tcp_fallback {
+            vec![]
+        } else {
+            CLI.tcp_fallback_addresses.clone()
+        },
+        certificate,
         candidate_channels.clone(),
     ));
-    let reverse_rpc_server = tokio::spawn(crate::rpc::run_reverse(CLI.reverse_addresses.clone()));
+    let election = tokio::spawn(crate::leader::run_election());
     let partners = tokio::spawn(crate::rpc::run_partners());
     let http_server = tokio::spawn(http::serve());
 
     // Await for services to end:
     maybe_resume_panic(direct_rpc_server.await);
     maybe_resume_panic(reverse_rpc_server.await);
+    maybe_resume_panic(tcp_fallback_server.await);
     maybe_resume_panic(http_server.await);
     maybe_resume_panic(partners.await);
+    maybe_resume_panic(election.await);
 
     // Exit:
     Ok(())
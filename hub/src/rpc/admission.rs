@@ -0,0 +1,145 @@
+//! Admission control for queries. The load of the hub is measured by the number of queries
+//! still being resolved and by how long peers take to answer resolutions. As the load grows, the
+//! hub first asks fewer peers to resolve each query and then, when it is overloaded, rejects
+//! queries altogether, telling nodes when to come back. In between, nodes may be asked to pay
+//! for their queries with a proof of work, which costs little to honest nodes but much to
+//! whoever is flooding the hub.
+
+use serde_derive::Serialize;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::CLI;
+
+/// The load above which the fan-out of queries starts to be reduced.
+const SHEDDING_THRESHOLD: f64 = 0.5;
+/// How long overloaded nodes are asked to wait, per unit of load.
+const RETRY_AFTER_UNIT: Duration = Duration::from_secs(1);
+/// The longest time overloaded nodes are asked to wait.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// The weight of a new sample in the smoothed resolution latency.
+const LATENCY_SMOOTHING: f64 = 1.0 / 8.0;
+
+/// The number of queries still being resolved.
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static::lazy_static! {
+    /// The exponentially smoothed time peers take to answer a resolution.
+    static ref LATENCY: Mutex<Option<Duration>> = Mutex::default();
+}
+
+/// Accounts for a query while it is being resolved.
+#[derive(Debug)]
+pub struct Permit(());
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        PENDING.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Whether a query was let in.
+#[derive(Debug)]
+pub enum Admission {
+    /// The query may be resolved by asking at most `fan_out` peers at a time.
+    Admitted { fan_out: usize, permit: Permit },
+    /// The hub is overloaded. The node should try again after some time.
+    Rejected { retry_after: Duration },
+}
+
+/// The current load of the hub, as shown in the API.
+#[derive(Debug, Clone, Serialize)]
+pub struct Load {
+    /// The number of queries still being resolved.
+    pub pending: usize,
+    /// The smoothed time peers take to answer a resolution.
+    pub latency: Option<Duration>,
+    /// The load relative to the configured capacity. The hub is overloaded above `1.0`.
+    pub load: f64,
+    /// The work demanded from each query, if any.
+    pub work: Option<f64>,
+}
+
+/// The current load of the hub.
+pub fn load() -> Load {
+    let pending = PENDING.load(Ordering::Relaxed);
+    let latency = *LATENCY.lock().expect("poisoned");
+    let queue_load = pending as f64 / CLI.max_pending_queries as f64;
+    // Slow peers only matter while there are queries waiting for them:
+    let latency_load = latency
+        .filter(|_| pending > 0)
+        .map(|latency| latency.as_secs_f64() * 1e3 / CLI.target_resolution_latency as f64)
+        .unwrap_or_default();
+
+    let load = queue_load.max(latency_load);
+
+    Load {
+        pending,
+        latency,
+        load,
+        work: work_for(load),
+    }
+}
+
+/// The work demanded from each query at a given load: nothing below the threshold, then
+/// growing linearly up to the maximum at full load.
+fn work_for(load: f64) -> Option<f64> {
+    let threshold = CLI.query_work_threshold;
+
+    if load < threshold {
+        return None;
+    }
+
+    let stress = ((load - threshold) / (1.0 - threshold)).min(1.0);
+    Some((CLI.max_query_work * stress).max(1.0))
+}
+
+/// The work a node must prove to have a new query let in, if any.
+pub fn work_required(addr: IpAddr) -> Option<f64> {
+    if CLI.query_work_exempt.contains(&addr) {
+        return None;
+    }
+
+    load().work
+}
+
+/// Decides whether a new query is let in and, if so, how many peers may be asked at a time
+/// to resolve it.
+pub fn admit() -> Admission {
+    let Load { load, .. } = load();
+
+    if load >= 1.0 {
+        let retry_after = RETRY_AFTER_UNIT.mul_f64(load).min(MAX_RETRY_AFTER);
+        log::warn!("hub overloaded (load {load:.2}): rejecting query for {retry_after:?}");
+        return Admission::Rejected { retry_after };
+    }
+
+    let full = CLI.max_resolutions_per_query;
+    let fan_out = if load <= SHEDDING_THRESHOLD {
+        full
+    } else {
+        // Goes linearly from the full fan-out at the threshold to one peer at full load.
+        let spare = (1.0 - load) / (1.0 - SHEDDING_THRESHOLD);
+        ((full as f64 * spare).ceil() as usize).clamp(1, full)
+    };
+
+    PENDING.fetch_add(1, Ordering::Relaxed);
+
+    Admission::Admitted {
+        fan_out,
+        permit: Permit(()),
+    }
+}
+
+/// Records how long a peer took to answer a resolution.
+pub fn observe_latency(latency: Duration) {
+    let mut smoothed = LATENCY.lock().expect("poisoned");
+    *smoothed = Some(match *smoothed {
+        Some(smoothed) => {
+            smoothed.mul_f64(1.0 - LATENCY_SMOOTHING) + latency.mul_f64(LATENCY_SMOOTHING)
+        }
+        None => latency,
+    });
+}
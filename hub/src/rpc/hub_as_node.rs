@@ -12,12 +12,16 @@ use tokio::sync::oneshot;
 use tokio::task::{JoinError, JoinHandle};
 use tokio::time;
 
+use samizdat_common::request_id::{self, Traced};
 use samizdat_common::rpc::*;
 use samizdat_common::BincodeOverQuic;
 
+use crate::blacklist;
+
+use super::admission::{self, Admission};
 use super::{
     announce_edition, candidates_for_resolution, edition_for_request, get_identity,
-    REPLAY_RESISTANCE,
+    is_new_announcement, partner_policy, REPLAY_RESISTANCE,
 };
 
 const MAX_TRANSFER_SIZE: usize = 2_048;
@@ -66,14 +70,29 @@ impl Node for HubAsNodeServer {
             return ResolutionResponse::EmptyResolution;
         }
 
+        // See if the operator wants this forwarded:
+        if !partner_policy::admits(self.partner, &resolution) {
+            return ResolutionResponse::NotFound;
+        }
+
+        // Partners get no special treatment when the hub is overloaded:
+        let (fan_out, permit) = match admission::admit() {
+            Admission::Admitted { fan_out, permit } => (fan_out, permit),
+            Admission::Rejected { .. } => return ResolutionResponse::NotFound,
+        };
+
         let candidate_channel: CandidateChannelId = rand::random();
 
-        tokio::spawn(async move {
+        request_id::spawn(async move {
             let candidates = candidates_for_resolution(
                 ctx,
                 self.partner,
+                None,
                 Resolution::clone(&resolution),
+                // Partners do not forward the preferences of their nodes:
+                GeoPreference::Any,
                 self.candidate_channels.clone(),
+                fan_out,
             );
             let mut pinned = Box::pin(candidates);
 
@@ -87,6 +106,8 @@ impl Node for HubAsNodeServer {
                     log::error!("Failed to send candidate to channel {candidate_channel}: {err}");
                 }
             }
+
+            drop(permit);
         });
 
         ResolutionResponse::Redirect(candidate_channel)
@@ -120,6 +141,10 @@ impl Node for HubAsNodeServer {
     }
 
     async fn announce_edition(self, ctx: context::Context, announcement: Arc<EditionAnnouncement>) {
+        if !is_new_announcement(&announcement) {
+            return;
+        }
+
         // Se if you are not being replayed:
         match REPLAY_RESISTANCE.lock().await.check(&*announcement) {
             Ok(true) => { /* valid */ }
@@ -211,8 +236,9 @@ async fn connect_reverse(
         MAX_TRANSFER_SIZE,
     );
 
-    let server_task = server::BaseChannel::with_defaults(transport)
-        .execute(HubAsNodeServer::new(reverse_addr, client, candidate_channels).serve());
+    let server_task = server::BaseChannel::with_defaults(transport).execute(Traced(
+        HubAsNodeServer::new(reverse_addr, client, candidate_channels).serve(),
+    ));
 
     Ok(tokio::spawn(server_task))
 }
@@ -222,9 +248,15 @@ async fn connect(
     endpoint: &Endpoint,
     direct_addr: SocketAddr,
     reverse_addr: SocketAddr,
+    blacklist_source: Option<&'static str>,
 ) -> Result<impl Future<Output = Result<(), JoinError>>, crate::Error> {
     let candidate_channels = KeyedChannel::new();
     let (client, client_reset_recv) = connect_direct(direct_addr, endpoint).await?;
+
+    if let Some(source) = blacklist_source {
+        tokio::spawn(blacklist::follow(source, client.clone()));
+    }
+
     let server_reset_recv =
         connect_reverse(reverse_addr, endpoint, client, candidate_channels.clone()).await?;
 
@@ -240,7 +272,8 @@ async fn connect(
 /// Runs a hub-as-node server forever.
 pub async fn run(partner: &crate::cli::AddrToResolve, endpoint: &Endpoint) {
     // Set up addresses
-    let (_, partner) = match partner.resolve().await {
+    let blacklist_source = blacklist::follows(partner);
+    let (name, partner) = match partner.resolve().await {
         Ok(resolved) => resolved,
         Err(err) => {
             log::error!("Failed to connect to partner {partner}: {err}");
@@ -257,7 +290,14 @@ pub async fn run(partner: &crate::cli::AddrToResolve, endpoint: &Endpoint) {
 
     // Exponential backoff
     loop {
-        match connect(endpoint, direct_addr, reverse_addr).await {
+        match connect(
+            endpoint,
+            direct_addr,
+            reverse_addr,
+            blacklist_source.then_some(name),
+        )
+        .await
+        {
             Ok(handle) => match handle.await {
                 Ok(()) => {
                     log::info!("Hub-as-node server finished for {partner}");
@@ -6,15 +6,19 @@ use tarpc::context;
 use tokio::sync::{Mutex, Semaphore};
 use tokio::time::{interval, Duration, Interval, MissedTickBehavior};
 
+use samizdat_common::handshake::NodeInfo;
+use samizdat_common::request_id;
 use samizdat_common::rpc::*;
 use samizdat_common::ChannelAddr;
 
+use crate::blacklist;
 use crate::rpc::ROOM;
 use crate::CLI;
 
+use super::admission::{self, Admission};
 use super::{
     announce_edition, candidates_for_resolution, edition_for_request, get_identity,
-    REPLAY_RESISTANCE,
+    is_new_announcement, REPLAY_RESISTANCE,
 };
 
 struct HubServerInner {
@@ -63,87 +67,157 @@ impl HubServer {
         drop(permit);
         outcome
     }
-}
 
-#[tarpc::server]
-impl Hub for HubServer {
-    async fn query(self, ctx: context::Context, query: Query) -> QueryResponse {
+    /// Resolves a single query, without any throttling.
+    async fn resolve_query(&self, ctx: context::Context, query: Query) -> QueryResponse {
         let client_addr = self.0.addr;
-        self.throttle(move |server| async move {
-            log::debug!("got {:?}", query);
 
-            // Create a channel address from peer address:
-            let channel_id = rand::random();
-            let channel_addr = ChannelAddr::new(server.0.addr, channel_id);
+        log::debug!("got {:?}", query);
 
-            // Se if you are not being replayed:
-            match REPLAY_RESISTANCE.lock().await.check(&query) {
-                Ok(false) => return QueryResponse::Replayed,
-                Err(err) => {
-                    log::error!("error while checking for replay: {}", err);
-                    return QueryResponse::InternalError;
-                }
-                _ => {}
+        // Create a channel address from peer address:
+        let channel_id = rand::random();
+        let channel_addr = ChannelAddr::new(self.0.addr, channel_id);
+
+        // Under stress, make the node pay for the query. This comes before the replay check,
+        // so that the node can send the same query again, with the proof:
+        if let Some(work) = admission::work_required(client_addr.ip()) {
+            let is_proven = query.proof_of_work.as_ref().is_some_and(|proof| {
+                proof.information == query.location_riddle.rand && proof.work_done() >= work
+            });
+
+            if !is_proven {
+                log::debug!("demanding work {work:.0} from {client_addr}");
+                return QueryResponse::WorkRequired { work };
             }
+        }
 
-            // If query is empty, nothing to be done:
-            if query.content_riddles.is_empty() {
-                log::debug!("query riddle empty");
-                return QueryResponse::EmptyQuery;
+        // Se if you are not being replayed:
+        match REPLAY_RESISTANCE.lock().await.check(&query) {
+            Ok(false) => return QueryResponse::Replayed,
+            Err(err) => {
+                log::error!("error while checking for replay: {}", err);
+                return QueryResponse::InternalError;
             }
+            _ => {}
+        }
 
-            // Now, prepare resolution request:
-            let location_message_riddle = query.location_riddle.riddle_for(channel_addr);
-            let resolution = Resolution {
-                content_riddles: query.content_riddles,
-                location_message_riddle,
-                validation_nonces: vec![],
-                kind: query.kind,
-            };
-
-            // And then create a candidate channel to forward candidate peers:
-            let candidate_channel: CandidateChannelId = rand::random();
-
-            let node = if let Some(node) = ROOM.get(client_addr).await {
-                node
-            } else {
-                return QueryResponse::NoReverseConnection;
-            };
-
-            // Forward all candidate peers:
-            let candidate_channels = server.0.candidate_channels.clone();
-            tokio::spawn(async move {
-                // TODO: maybe wait some millis to make sure query response has arrived?
-                let candidates = candidates_for_resolution(
-                    ctx,
-                    client_addr,
-                    resolution,
-                    candidate_channels.clone(),
-                );
-                let mut pinned = Box::pin(candidates);
-
-                while let Some(candidate) = pinned.next().await {
-                    let socket_addr = candidate.socket_addr;
-                    let outcome = node
-                        .client
-                        .recv_candidate(ctx.clone(), candidate_channel, candidate)
-                        .await;
-
-                    if let Err(err) = outcome {
-                        log::warn!(
-                            "Error sending candidate {socket_addr} to {}: {err}",
-                            node.addr
-                        );
-                    }
-                }
-            });
+        // If query is empty, nothing to be done:
+        if query.content_riddles.is_empty() {
+            log::debug!("query riddle empty");
+            return QueryResponse::EmptyQuery;
+        }
+
+        // See if there is capacity left for this query:
+        let (fan_out, permit) = match admission::admit() {
+            Admission::Admitted { fan_out, permit } => (fan_out, permit),
+            Admission::Rejected { retry_after } => {
+                return QueryResponse::Overloaded { retry_after }
+            }
+        };
+
+        // Now, prepare resolution request:
+        let location_message_riddle = query.location_riddle.riddle_for(channel_addr);
+        let geo_preference = query.geo_preference;
+        let resolution = Resolution {
+            content_riddles: query.content_riddles,
+            location_message_riddle,
+            validation_nonces: vec![],
+            kind: query.kind,
+        };
+
+        // And then create a candidate channel to forward candidate peers:
+        let candidate_channel: CandidateChannelId = rand::random();
+
+        let node = if let Some(node) = ROOM.get(client_addr).await {
+            node
+        } else {
+            return QueryResponse::NoReverseConnection;
+        };
+
+        // Peers would be sent a TCP address to connect to over QUIC:
+        if node.is_over_tcp() {
+            log::debug!("{client_addr} is connected over TCP and cannot receive content");
+            return QueryResponse::NoReverseConnection;
+        }
 
-            log::debug!("query done");
+        // Forward all candidate peers:
+        let candidate_channels = self.0.candidate_channels.clone();
+        request_id::spawn(async move {
+            // TODO: maybe wait some millis to make sure query response has arrived?
+            let client_info = node
+                .advertisement()
+                .map(|advertisement| NodeInfo::clone(&advertisement.info));
+            let candidates = candidates_for_resolution(
+                ctx,
+                client_addr,
+                client_info,
+                resolution,
+                geo_preference,
+                candidate_channels.clone(),
+                fan_out,
+            );
+            let mut pinned = Box::pin(candidates);
 
-            QueryResponse::Resolved {
-                candidate_channel,
-                channel_id,
+            while let Some(candidate) = pinned.next().await {
+                let socket_addr = candidate.socket_addr;
+                let outcome = node
+                    .client
+                    .recv_candidate(ctx, candidate_channel, candidate)
+                    .await;
+
+                if let Err(err) = outcome {
+                    log::warn!(
+                        "Error sending candidate {socket_addr} to {}: {err}",
+                        node.addr
+                    );
+                }
             }
+
+            // The query is done only when all candidates were sent:
+            drop(permit);
+        });
+
+        log::debug!("query done");
+
+        QueryResponse::Resolved {
+            candidate_channel,
+            channel_id,
+        }
+    }
+
+    /// Resolves a query of a batch concurrently with the others, once it gets through the rate
+    /// limit. Each query in the batch counts towards it, but the first, which was already
+    /// counted for the call.
+    async fn resolve_in_batch(
+        &self,
+        ctx: context::Context,
+        query: Query,
+        is_first: bool,
+    ) -> QueryResponse {
+        if !is_first {
+            self.0.call_throttle.lock().await.tick().await;
+        }
+
+        self.resolve_query(ctx, query).await
+    }
+}
+
+#[tarpc::server]
+impl Hub for HubServer {
+    async fn query(self, ctx: context::Context, query: Query) -> QueryResponse {
+        self.throttle(move |server| server.resolve_query(ctx, query))
+            .await
+    }
+
+    async fn query_many(self, ctx: context::Context, queries: Vec<Query>) -> Vec<QueryResponse> {
+        self.throttle(move |server| async move {
+            let resolutions = queries
+                .into_iter()
+                .take(MAX_QUERY_BATCH_SIZE)
+                .enumerate()
+                .map(|(i, query)| server.resolve_in_batch(ctx, query, i == 0));
+
+            future::join_all(resolutions).await
         })
         .await
     }
@@ -189,6 +263,10 @@ impl Hub for HubServer {
     async fn announce_edition(self, ctx: context::Context, announcement: EditionAnnouncement) {
         let client_addr = self.0.addr;
         self.throttle(|_| async move {
+            if !is_new_announcement(&announcement) {
+                return;
+            }
+
             // Se if you are not being replayed:
             match REPLAY_RESISTANCE.lock().await.check(&announcement) {
                 Ok(false) => return,
@@ -231,4 +309,24 @@ impl Hub for HubServer {
     async fn announce_identity(self, _ctx: context::Context, _announcement: IdentityAnnouncement) {
         unimplemented!()
     }
+
+    async fn ping(self, _: context::Context) {
+        // Not throttled, so as not to distort the measured round-trip time.
+    }
+
+    async fn get_blacklist(self, _: context::Context) -> Option<BlacklistFeed> {
+        self.throttle(|server| async move {
+            let client_addr = server.0.addr;
+
+            if !blacklist::shares_with(client_addr.ip()) {
+                log::debug!("{client_addr} asked for the blacklist, which is not shared with it");
+                return None;
+            }
+
+            blacklist::feed()
+                .map_err(|err| log::error!("failed to sign blacklist for {client_addr}: {err}"))
+                .ok()
+        })
+        .await
+    }
 }
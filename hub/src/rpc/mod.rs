@@ -1,4 +1,6 @@
+pub mod admission;
 pub mod node_sampler;
+pub mod partner_policy;
 
 mod hub_as_node;
 mod hub_server;
@@ -11,27 +13,67 @@ use std::io;
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tarpc::context;
 use tarpc::server::{self, Channel};
 use tokio::sync::Mutex;
 
+use samizdat_common::bloom::RotatingBloomFilter;
+use samizdat_common::handshake::{self, Advertisement, Agreement, Capabilities, NodeInfo};
+use samizdat_common::quic::TlsCertificate;
+use samizdat_common::request_id::Traced;
 use samizdat_common::rpc::*;
+use samizdat_common::tcp_fallback::{self, Role};
 use samizdat_common::BincodeOverQuic;
 use samizdat_common::{quic, Riddle};
 
+use crate::blacklist;
+use crate::db::{db, Table};
+use crate::geoip::{self, Country};
+use crate::leader;
 use crate::replay_resistance::ReplayResistance;
 use crate::utils;
 use crate::CLI;
 
 use self::hub_server::HubServer;
-use self::node_sampler::{EditionSampler, QuerySampler, Statistics, UniformSampler};
+use self::node_sampler::{EditionSampler, ExperimentGroup, GeoSampler, Statistics, UniformSampler};
 use self::room::Room;
 
-const MAX_LENGTH: usize = 2_048;
+/// The maximum size of a message from a node: a full batch of queries, plus what was the limit
+/// before batches, for the envelope of the call and for all other messages.
+const MAX_LENGTH: usize = 2_048 + MAX_QUERY_BATCH_SIZE * MAX_QUERY_LENGTH;
+/// The optional features of the protocol this hub supports.
+const CAPABILITIES: Capabilities = Capabilities::QUERY_BATCHES.union(Capabilities::PERSISTENT_KEY);
+/// The number of recent edition announcements remembered, to drop copies looping around.
+const ANNOUNCEMENT_DEDUP_CAPACITY: usize = 100_000;
+/// The rate at which new edition announcements are mistaken for copies of recent ones.
+const ANNOUNCEMENT_DEDUP_ERROR_RATE: f64 = 1e-6;
+
+/// The key under which the TLS certificate of the hub is kept in [`Table::Global`].
+const TLS_CERTIFICATE_KEY: &[u8] = b"tls_certificate";
 
 lazy_static! {
     pub static ref ROOM: Room = Room::new();
     pub static ref REPLAY_RESISTANCE: Mutex<ReplayResistance> = Mutex::new(ReplayResistance::new());
+    static ref RECENT_ANNOUNCEMENTS: std::sync::Mutex<RotatingBloomFilter> = std::sync::Mutex::new(
+        RotatingBloomFilter::new(ANNOUNCEMENT_DEDUP_CAPACITY, ANNOUNCEMENT_DEDUP_ERROR_RATE)
+    );
+}
+
+/// Whether an edition announcement was not seen recently, remembering it if so. When hubs and
+/// partners are interconnected, the same announcement may come back through many paths. This
+/// is checked before anything else, so that copies are neither stored nor re-broadcast.
+fn is_new_announcement(announcement: &EditionAnnouncement) -> bool {
+    let is_new = RECENT_ANNOUNCEMENTS
+        .lock()
+        .expect("poisoned")
+        .insert(&announcement.hash());
+
+    if !is_new {
+        log::debug!("dropping repeated edition announcement");
+    }
+
+    is_new
 }
 
 #[derive(Debug)]
@@ -40,25 +82,78 @@ pub struct Node {
     edition_statistics: Statistics,
     client: NodeClient,
     addr: SocketAddr,
+    link: Link,
+    /// What was agreed with the node, including what it advertised about itself.
+    agreement: Agreement,
+    /// The country the node is in, if known (see [`geoip`]).
+    country: Option<Country>,
+}
+
+/// How a node is connected to the hub.
+#[derive(Debug)]
+enum Link {
+    Quic(quinn::Connection),
+    /// The fallback for when UDP is blocked (see [`tcp_fallback`]).
+    Tcp {
+        rtt: Duration,
+    },
 }
 
 impl Node {
-    fn new(addr: SocketAddr, client: NodeClient) -> Node {
+    fn new(addr: SocketAddr, client: NodeClient, link: Link, agreement: Agreement) -> Node {
         Node {
             query_statistics: Statistics::default(),
             edition_statistics: Statistics::default(),
             client,
             // Make tunneled IPv4 addresses actual IPv4 addresses.
             addr,
+            link,
+            agreement,
+            country: geoip::country(addr.ip()),
+        }
+    }
+
+    /// What the node advertised about itself, if anything. Nodes predating advertisements (and
+    /// nodes which have not finished the handshake yet) are taken to be compatible with
+    /// everybody.
+    pub fn advertisement(&self) -> Option<Advertisement> {
+        self.agreement.advertisement()
+    }
+
+    /// Whether content can be transferred between this node and a node with the given info, as
+    /// far as the hub can tell.
+    fn can_transfer_with(&self, info: Option<&NodeInfo>) -> bool {
+        match (self.advertisement(), info) {
+            (Some(advertisement), Some(info)) => advertisement.info.can_transfer_with(info),
+            _ => true,
+        }
+    }
+
+    /// Whether the node is connected over TCP. Its address is then of no use to peers, which
+    /// only speak QUIC to each other.
+    fn is_over_tcp(&self) -> bool {
+        matches!(self.link, Link::Tcp { .. })
+    }
+
+    /// The round-trip time between the hub and the node, as estimated by QUIC or, over TCP,
+    /// when connecting.
+    pub fn rtt(&self) -> Duration {
+        match &self.link {
+            Link::Quic(connection) => connection.rtt(),
+            Link::Tcp { rtt } => *rtt,
         }
     }
 }
 
+/// The candidates for a resolution from a client, which advertised the given info, if any.
 fn candidates_for_resolution(
     ctx: context::Context,
     client_addr: SocketAddr,
+    client_info: Option<NodeInfo>,
     mut resolution: Resolution,
+    geo_preference: GeoPreference,
     candidate_channels: KeyedChannel<Candidate>,
+    fan_out: usize,
 ) -> impl Send + Stream<Item = Candidate> {
     log::debug!("Client {client_addr} requested {resolution:?}");
 
@@ -75,31 +170,52 @@ fn candidates_for_resolution(
     //     // now what?
     // }
 
+    // Failures only count if somebody has the content (i.e., this is not cover traffic):
+    let experiment_group = ExperimentGroup::default();
+
     // Then query peers:
-    ROOM.with_peers(QuerySampler, client_addr, move |peer_id, peer| {
-        log::debug!("Pairing client {client_addr} with peer {peer_id}");
-        let resolution = resolution.clone();
-        let validation_riddle = validation_riddle.clone();
-        let candidate_channels = candidate_channels.clone();
+    ROOM.with_peers(
+        GeoSampler::new(node_sampler::query_sampler(), geo_preference, client_addr),
+        client_addr,
+        fan_out,
+        move |peer_id, peer| {
+            log::debug!("Pairing client {client_addr} with peer {peer_id}");
+            let is_compatible = peer.can_transfer_with(client_info.as_ref());
+            let resolution = resolution.clone();
+            let experiment_group = experiment_group.clone();
+            let validation_riddle = validation_riddle.clone();
+            let candidate_channels = candidate_channels.clone();
 
-        async move {
-            log::debug!("starting resolve for {peer_id}");
-            let experiment = peer.query_statistics.start_experiment();
-            let outcome = peer.client.resolve(ctx, resolution.clone()).await;
+            async move {
+                if !is_compatible {
+                    log::debug!("{peer_id} cannot transfer content to {client_addr}");
+                    return None;
+                }
 
-            let response = match outcome {
-                Ok(response) => response,
-                Err(err) => {
-                    log::warn!("error asking {peer_id} to resolve: {err}");
+                if peer.is_over_tcp() {
+                    log::debug!("{peer_id} is connected over TCP and cannot be a candidate");
                     return None;
                 }
-            };
 
-            log::debug!("resolve done for {peer_id}");
+                log::debug!("starting resolve for {peer_id}");
+                let experiment = peer.query_statistics.start_experiment_in(&experiment_group);
+                let start = Instant::now();
+                let outcome = peer.client.resolve(ctx, resolution.clone()).await;
+                admission::observe_latency(start.elapsed());
+
+                let response = match outcome {
+                    Ok(response) => response,
+                    Err(err) => {
+                        log::warn!("error asking {peer_id} to resolve: {err}");
+                        return None;
+                    }
+                };
+
+                log::debug!("resolve done for {peer_id}");
 
-            let validate_riddles = move |riddles: &[Riddle]| {
-                // `>=`: there can be more added nonces down the line because of further redirects.
-                riddles.len() >= resolution.validation_nonces.len()
+                let validate_riddles = move |riddles: &[Riddle]| {
+                    // `>=`: there can be more added nonces down the line because of further redirects.
+                    riddles.len() >= resolution.validation_nonces.len()
                     // Check that *your* riddle is correct
                     && &riddles[resolution.validation_nonces.len() - 1] == &validation_riddle
                     // Although you don't know the riddles before you, at least check that the nonces
@@ -108,49 +224,48 @@ fn candidates_for_resolution(
                         .iter()
                         .zip(&resolution.validation_nonces)
                         .all(|(riddle, nonce)| riddle.rand == *nonce)
-            };
+                };
 
-            match response {
-                ResolutionResponse::Found(validation_riddles)
-                    if validate_riddles(&validation_riddles) =>
-                {
-                    experiment.end_with_success();
-                    Some(Box::pin(stream::once(async move {
-                        Candidate {
-                            socket_addr:peer.addr,
-                            validation_riddles,
-                        }
-                    }))
-                        as Pin<Box<dyn Send + Stream<Item = Candidate>>>)
-                }
-                ResolutionResponse::Redirect(candidate_channel) => {
-                    let mut maybe_experiment = Some(experiment);
-                    let valid_candidates =
-                        candidate_channels
+                match response {
+                    ResolutionResponse::Found(validation_riddles)
+                        if validate_riddles(&validation_riddles) =>
+                    {
+                        experiment.end_with_success();
+                        Some(Box::pin(stream::once(async move {
+                            Candidate {
+                                socket_addr: peer.addr,
+                                validation_riddles,
+                            }
+                        }))
+                            as Pin<Box<dyn Send + Stream<Item = Candidate>>>)
+                    }
+                    ResolutionResponse::Redirect(candidate_channel) => {
+                        let mut maybe_experiment = Some(experiment);
+                        let valid_candidates = candidate_channels
                             .recv_stream(candidate_channel)
                             .filter(move |candidate| {
                                 let is_valid = validate_riddles(&candidate.validation_riddles);
                                 // IPv6 with IPv6; IPv4 with IPv4!
-                                let ip_version_matches =
-                                    candidate.socket_addr.ip().is_ipv6()
-                                        == client_addr.ip().is_ipv6();
+                                let ip_version_matches = candidate.socket_addr.ip().is_ipv6()
+                                    == client_addr.ip().is_ipv6();
 
                                 async move { is_valid && ip_version_matches }
-                            }).inspect(move |_| {
+                            })
+                            .inspect(move |_| {
                                 // End experiment with success on first received candidate
                                 if let Some(experiment) = maybe_experiment.take() {
                                     experiment.end_with_success();
                                 }
                             });
 
-                    Some(Box::pin(valid_candidates) as Pin<Box<dyn Send + Stream<Item = Candidate>>>)
-                }
-                _ => {
-                    None
+                        Some(Box::pin(valid_candidates)
+                            as Pin<Box<dyn Send + Stream<Item = Candidate>>>)
+                    }
+                    _ => None,
                 }
             }
-        }
-    })
+        },
+    )
     .flatten_unordered(10)
 }
 
@@ -160,32 +275,37 @@ async fn edition_for_request(
     latest: Arc<EditionRequest>,
 ) -> Vec<EditionResponse> {
     let responses = ROOM
-        .with_peers(EditionSampler, client_addr, |peer_id, peer| {
-            let latest = latest.clone();
-            async move {
-                log::debug!("starting resolve latest edition for {peer_id}");
-                let experiment = peer.edition_statistics.start_experiment();
-                let outcome = peer.client.get_edition(ctx, latest).await;
-
-                let response = match outcome {
-                    Ok(response) => {
-                        // Empty response is not a valid candidate.
-                        if !response.is_empty() {
-                            experiment.end_with_success();
-                            Some(response)
-                        } else {
+        .with_peers(
+            EditionSampler,
+            client_addr,
+            CLI.max_resolutions_per_query,
+            |peer_id, peer| {
+                let latest = latest.clone();
+                async move {
+                    log::debug!("starting resolve latest edition for {peer_id}");
+                    let experiment = peer.edition_statistics.start_experiment();
+                    let outcome = peer.client.get_edition(ctx, latest).await;
+
+                    let response = match outcome {
+                        Ok(response) => {
+                            // Empty response is not a valid candidate.
+                            if !response.is_empty() {
+                                experiment.end_with_success();
+                                Some(response)
+                            } else {
+                                None
+                            }
+                        }
+                        Err(err) => {
+                            log::warn!("error asking {peer_id} for latest: {err}");
                             None
                         }
-                    }
-                    Err(err) => {
-                        log::warn!("error asking {peer_id} for latest: {err}");
-                        None
-                    }
-                };
+                    };
 
-                response
-            }
-        })
+                    response
+                }
+            },
+        )
         .collect::<Vec<_>>()
         .await
         .into_iter()
@@ -202,20 +322,25 @@ async fn announce_edition(
     client_addr: SocketAddr,
     announcement: Arc<EditionAnnouncement>,
 ) {
-    ROOM.with_peers(UniformSampler, client_addr, |peer_id, peer| {
-        let announcement = announcement.clone();
-        async move {
-            let outcome = peer.client.announce_edition(ctx, announcement).await;
+    ROOM.with_peers(
+        UniformSampler,
+        client_addr,
+        CLI.max_resolutions_per_query,
+        |peer_id, peer| {
+            let announcement = announcement.clone();
+            async move {
+                let outcome = peer.client.announce_edition(ctx, announcement).await;
 
-            match outcome {
-                Ok(_) => Some(()),
-                Err(err) => {
-                    log::warn!("error announcing to peer {peer_id}: {err}");
-                    None
+                match outcome {
+                    Ok(_) => Some(()),
+                    Err(err) => {
+                        log::warn!("error announcing to peer {peer_id}: {err}");
+                        None
+                    }
                 }
             }
-        }
-    })
+        },
+    )
     .collect::<Vec<_>>()
     .await;
 }
@@ -226,31 +351,36 @@ async fn get_identity(
     request: Arc<IdentityRequest>,
 ) -> Vec<IdentityResponse> {
     // TODO: create dedicated sampler....
-    ROOM.with_peers(EditionSampler, client_addr, |peer_id, peer| {
-        let request = request.clone();
-        async move {
-            let experiment = peer.edition_statistics.start_experiment();
-            let outcome = peer.client.get_identity(ctx, request).await;
+    ROOM.with_peers(
+        EditionSampler,
+        client_addr,
+        CLI.max_resolutions_per_query,
+        |peer_id, peer| {
+            let request = request.clone();
+            async move {
+                let experiment = peer.edition_statistics.start_experiment();
+                let outcome = peer.client.get_identity(ctx, request).await;
 
-            let response = match outcome {
-                Ok(response) => {
-                    // Empty response is not a valid candidate.
-                    if !response.is_empty() {
-                        experiment.end_with_success();
-                        Some(response)
-                    } else {
-                        None
+                let response = match outcome {
+                    Ok(response) => {
+                        // Empty response is not a valid candidate.
+                        if !response.is_empty() {
+                            experiment.end_with_success();
+                            Some(response)
+                        } else {
+                            None
+                        }
                     }
-                }
-                Err(err) => {
-                    log::warn!("error asking {peer_id} for latest: {err}");
-                    return None;
-                }
-            };
+                    Err(err) => {
+                        log::warn!("error asking {peer_id} for latest: {err}");
+                        return None;
+                    }
+                };
 
-            response
-        }
-    })
+                response
+            }
+        },
+    )
     .collect::<Vec<_>>()
     .await
     .into_iter()
@@ -258,14 +388,39 @@ async fn get_identity(
     .collect::<Vec<_>>()
 }
 
+/// The TLS certificate this hub presents to nodes, created on first use. It is kept across
+/// restarts, since nodes pin it and refuse to connect to a hub presenting another one.
+pub fn tls_certificate() -> Result<TlsCertificate, crate::Error> {
+    let certificate = match db().get_cf(Table::Global.get(), TLS_CERTIFICATE_KEY)? {
+        Some(serialized) => bincode::deserialize(&serialized)?,
+        None => {
+            let certificate = TlsCertificate::generate();
+            db().put_cf(
+                Table::Global.get(),
+                TLS_CERTIFICATE_KEY,
+                bincode::serialize(&certificate).expect("can serialize"),
+            )?;
+            certificate
+        }
+    };
+
+    log::info!(
+        "TLS certificate fingerprint is {}",
+        certificate.fingerprint()
+    );
+
+    Ok(certificate)
+}
+
 pub async fn run_direct(
     addrs: Vec<impl Into<SocketAddr>>,
+    certificate: TlsCertificate,
     candidate_channels: KeyedChannel<Candidate>,
 ) -> Result<(), io::Error> {
     let all_incoming = addrs
         .into_iter()
         .map(|addr| {
-            let (endpoint, incoming) = samizdat_common::quic::new_default(addr.into());
+            let (endpoint, incoming) = quic::new_with_certificate(addr.into(), certificate.clone());
             log::info!("Direct server started at {}", endpoint.local_addr()?);
 
             Ok(incoming)
@@ -281,27 +436,31 @@ pub async fn run_direct(
                 .ok()
         })
         .map(|new_connection| {
-            // Get peer address:
-            let client_addr =
-                utils::socket_to_canonical(new_connection.connection.remote_address());
-
-            log::debug!("Incoming connection from {client_addr}");
-
-            let transport = BincodeOverQuic::new(
-                new_connection.connection,
-                new_connection.uni_streams,
-                MAX_LENGTH,
-            );
-
-            // Set up server:
-            let server = HubServer::new(client_addr, candidate_channels.clone());
-            let server_task = server::BaseChannel::with_defaults(transport).execute(server.serve());
-
-            log::info!("Connection from node (as server) {client_addr} accepted");
-
-            Some(server_task)
+            let candidate_channels = candidate_channels.clone();
+            async move {
+                // Get peer address:
+                let client_addr =
+                    utils::socket_to_canonical(new_connection.connection.remote_address());
+
+                log::debug!("Incoming connection from {client_addr}");
+
+                // Nothing depends on what the node speaks, as a client:
+                handshake::spawn(
+                    new_connection.connection.clone(),
+                    new_connection.bi_streams,
+                    CAPABILITIES,
+                    None,
+                );
+
+                let transport = BincodeOverQuic::new(
+                    new_connection.connection,
+                    new_connection.uni_streams,
+                    MAX_LENGTH,
+                );
+
+                serve_direct(client_addr, transport, candidate_channels).await
+            }
         })
-        .filter_map(|maybe_server| async move { maybe_server })
         // Max number of channels.
         .buffer_unordered(CLI.max_connections)
         .for_each(|_| async {})
@@ -310,11 +469,14 @@ pub async fn run_direct(
     Ok(())
 }
 
-pub async fn run_reverse(addrs: Vec<impl Into<SocketAddr>>) -> Result<(), io::Error> {
+pub async fn run_reverse(
+    addrs: Vec<impl Into<SocketAddr>>,
+    certificate: TlsCertificate,
+) -> Result<(), io::Error> {
     let all_incoming = addrs
         .into_iter()
         .map(|addr| {
-            let (endpoint, incoming) = samizdat_common::quic::new_default(addr.into());
+            let (endpoint, incoming) = quic::new_with_certificate(addr.into(), certificate.clone());
             log::info!("Reverse server started at {}", endpoint.local_addr()?);
 
             Ok(incoming)
@@ -336,32 +498,161 @@ pub async fn run_reverse(addrs: Vec<impl Into<SocketAddr>>) -> Result<(), io::Er
 
             log::debug!("Incoming connection from {client_addr}");
 
+            let agreement = handshake::spawn(
+                new_connection.connection.clone(),
+                new_connection.bi_streams,
+                CAPABILITIES,
+                None,
+            );
+
+            let connection = new_connection.connection.clone();
             let transport = BincodeOverQuic::new(
                 new_connection.connection,
                 new_connection.uni_streams,
                 MAX_LENGTH,
             );
 
-            // Set up client (remember to drop it when connection is severed):
-            let uninstrumented_client =
-                NodeClient::new(tarpc::client::Config::default(), transport);
-            let client = tarpc::client::NewClient {
-                client: uninstrumented_client.client,
-                dispatch: uninstrumented_client
-                    .dispatch
-                    .then(move |outcome| async move {
-                        ROOM.remove(client_addr).await;
-                        outcome
-                    }),
+            accept_reverse(client_addr, transport, Link::Quic(connection), agreement).await;
+        })
+        .await;
+
+    Ok(())
+}
+
+/// Serves a node as the client of an RPC channel.
+async fn serve_direct<T>(
+    client_addr: SocketAddr,
+    transport: T,
+    candidate_channels: KeyedChannel<Candidate>,
+) where
+    T: 'static
+        + Send
+        + tarpc::Transport<tarpc::Response<HubResponse>, tarpc::ClientMessage<HubRequest>>,
+{
+    if blacklist::refuses(client_addr) {
+        return;
+    }
+
+    // Set up server:
+    let server = HubServer::new(client_addr, candidate_channels);
+    let server_task = server::BaseChannel::with_defaults(transport).execute(Traced(server.serve()));
+
+    log::info!("Connection from node (as server) {client_addr} accepted");
+
+    // Dropping the server drops the connection, if the node gets banned meanwhile:
+    future::select(
+        Box::pin(server_task),
+        Box::pin(blacklist::until_banned(client_addr)),
+    )
+    .await;
+}
+
+/// Puts a node, as the server of an RPC channel, in the [`ROOM`].
+async fn accept_reverse<T>(client_addr: SocketAddr, transport: T, link: Link, agreement: Agreement)
+where
+    T: 'static
+        + Send
+        + tarpc::Transport<tarpc::ClientMessage<NodeRequest>, tarpc::Response<NodeResponse>>,
+{
+    if blacklist::refuses(client_addr) {
+        return;
+    }
+
+    // Set up client (remember to drop it when connection is severed or when the node gets
+    // banned meanwhile):
+    let uninstrumented_client = NodeClient::new(tarpc::client::Config::default(), transport);
+    let dispatch = future::select(
+        Box::pin(uninstrumented_client.dispatch),
+        Box::pin(blacklist::until_banned(client_addr)),
+    );
+    let client = tarpc::client::NewClient {
+        client: uninstrumented_client.client,
+        dispatch: dispatch.then(move |outcome| async move {
+            ROOM.remove(client_addr).await;
+
+            match outcome {
+                future::Either::Left((outcome, _)) => outcome,
+                future::Either::Right(_) => Ok(()),
             }
-            .spawn();
+        }),
+    }
+    .spawn();
 
-            log::info!("Connection from node (as client) {client_addr} accepted");
+    log::info!("Connection from node (as client) {client_addr} accepted");
 
-            ROOM.insert(client_addr, Node::new(client_addr, client))
-                .await;
-        })
+    ROOM.insert(client_addr, Node::new(client_addr, client, link, agreement))
         .await;
+}
+
+/// Serves nodes over TLS over TCP, for networks where UDP is blocked. Both RPC channels of a
+/// node arrive through the same addresses (see [`tcp_fallback`]).
+pub async fn run_tcp_fallback(
+    addrs: Vec<SocketAddr>,
+    certificate: TlsCertificate,
+    candidate_channels: KeyedChannel<Candidate>,
+) -> Result<(), io::Error> {
+    let mut listeners = vec![];
+
+    for addr in addrs {
+        let listener = tcp_fallback::Listener::bind(addr, certificate.clone())
+            .await
+            .map_err(|err| {
+                log::error!("failed to listen over TCP at {addr}: {err}");
+                err
+            })?;
+        log::info!("TCP fallback server started at {}", listener.local_addr()?);
+        listeners.push(listener);
+    }
+
+    stream::select_all(listeners.into_iter().map(|listener| {
+        Box::pin(stream::unfold(listener, |listener| async move {
+            let incoming = listener.incoming().await;
+            Some((incoming, listener))
+        }))
+    }))
+    .filter_map(|incoming| async move {
+        incoming
+            .map_err(|err| log::warn!("failed to accept TCP connection: {err}"))
+            .ok()
+    })
+    .for_each_concurrent(Some(CLI.max_connections), |incoming| {
+        let candidate_channels = candidate_channels.clone();
+        async move {
+            let peer_addr = utils::socket_to_canonical(incoming.peer_addr());
+            log::debug!("Incoming TCP connection from {peer_addr}");
+
+            let accepted = match incoming.accept(CAPABILITIES).await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    log::debug!("failed to accept node from {peer_addr}: {err}");
+                    return;
+                }
+            };
+
+            let client_addr = utils::socket_to_canonical(accepted.client_addr);
+
+            match accepted.role {
+                Role::Direct => {
+                    log::debug!(
+                        "Node {client_addr} (as client) speaks {:?}",
+                        accepted.protocol
+                    );
+                    serve_direct(client_addr, accepted.into_transport(), candidate_channels).await
+                }
+                Role::Reverse { .. } => {
+                    log::debug!(
+                        "Node {client_addr} (as server) speaks {:?}",
+                        accepted.protocol
+                    );
+                    let link = Link::Tcp { rtt: accepted.rtt };
+                    let agreement =
+                        Agreement::reached(accepted.protocol, accepted.advertisement.clone());
+                    accept_reverse(client_addr, accepted.into_transport(), link, agreement).await
+                }
+            }
+        }
+    })
+    .await;
 
     Ok(())
 }
@@ -374,13 +665,23 @@ pub async fn run_partners() {
         endpoint.local_addr().expect("local address exists")
     );
 
-    // Resolve partner addresses (`CLI.partners` is an `Option`. Therefore, we flatten it!):
-    let partners = stream::iter(CLI.partners.iter().flatten())
-        .map(|partner| hub_as_node::run(partner, &endpoint))
-        .collect::<Vec<_>>()
+    // Only the leader among the replicas of this hub connects to the partners:
+    loop {
+        leader::wait_for_leadership(true).await;
+
+        // Resolve partner addresses (`CLI.partners` is an `Option`. Therefore, we flatten it!):
+        let partners = future::join_all(
+            CLI.partners
+                .iter()
+                .flatten()
+                .map(|partner| hub_as_node::run(partner, &endpoint)),
+        );
+
+        future::select(
+            Box::pin(partners),
+            Box::pin(leader::wait_for_leadership(false)),
+        )
         .await;
-
-    for partner in partners {
-        partner.await
+        log::info!("Disconnecting from partners");
     }
 }
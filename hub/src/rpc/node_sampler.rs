@@ -1,11 +1,16 @@
 use rand::distributions::Distribution;
+use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BinaryHeap};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 use std::sync::Arc;
-use std::sync::RwLock;
+use std::sync::{Mutex, RwLock};
 use std::time::{Duration, Instant};
 
 use samizdat_common::heap_entry::HeapEntry;
+use samizdat_common::rpc::GeoPreference;
+
+use crate::geoip::{self, Country};
 
 use super::Node;
 
@@ -116,17 +121,61 @@ impl Statistics {
         Experiment {
             statistics: self.clone(),
             start: Instant::now(),
+            group: None,
         }
     }
+
+    /// Starts an experiment whose request is only accounted for if some experiment in the
+    /// group succeeds. See [`ExperimentGroup`] for details.
+    pub fn start_experiment_in(&self, group: &ExperimentGroup) -> Experiment {
+        let mut state = group.0.lock().expect("poisoned");
+
+        if state.any_success {
+            self.start_request();
+        } else {
+            state.tried.push(self.clone());
+        }
+
+        Experiment {
+            statistics: self.clone(),
+            start: Instant::now(),
+            group: Some(group.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ExperimentGroupState {
+    any_success: bool,
+    /// The experiments started before the first success, not yet accounted for.
+    tried: Vec<Statistics>,
 }
 
+/// A set of experiments for the same resolution. If no experiment in the group succeeds, the
+/// resolution was probably for content that does not exist in the network (e.g., cover traffic
+/// from a node) and tells nothing about the peers. Therefore, requests are only accounted for
+/// once an experiment in the group succeeds: the ones started before, right then, and the ones
+/// started after, right away.
+#[derive(Debug, Clone, Default)]
+pub struct ExperimentGroup(Arc<Mutex<ExperimentGroupState>>);
+
 pub struct Experiment {
     statistics: Statistics,
     start: Instant,
+    group: Option<ExperimentGroup>,
 }
 
 impl Experiment {
     pub fn end_with_success(self) {
+        if let Some(group) = &self.group {
+            let mut state = group.0.lock().expect("poisoned");
+            state.any_success = true;
+
+            for statistics in state.tried.drain(..) {
+                statistics.start_request();
+            }
+        }
+
         self.statistics
             .end_request_with_success(self.start.elapsed());
     }
@@ -134,13 +183,14 @@ impl Experiment {
 
 pub(super) fn sample(
     sampler: impl PrioritySampler,
+    client: SocketAddr,
     nodes: &BTreeMap<SocketAddr, Arc<Node>>,
 ) -> impl Iterator<Item = (SocketAddr, Arc<Node>)> {
     let mut queue = BinaryHeap::new();
 
     // Thompson sampling solution to find the most successful peers.
     for (&node_addr, node) in nodes {
-        let priority = (sampler.sample_priority(&node) * 1e6) as i64;
+        let priority = (sampler.sample_priority(node, client) * 1e6) as i64;
 
         queue.push(HeapEntry {
             priority,
@@ -151,15 +201,23 @@ pub(super) fn sample(
     std::iter::from_fn(move || queue.pop().map(|entry| entry.content))
 }
 
-pub trait PrioritySampler {
-    fn sample_priority(&self, node: &Node) -> f64;
+/// A strategy for choosing which peers are asked first on behalf of a client. Peers are asked
+/// in decreasing order of priority.
+pub trait PrioritySampler: Send + Sync {
+    fn sample_priority(&self, node: &Node, client: SocketAddr) -> f64;
+}
+
+impl<S: PrioritySampler + ?Sized> PrioritySampler for Arc<S> {
+    fn sample_priority(&self, node: &Node, client: SocketAddr) -> f64 {
+        S::sample_priority(&**self, node, client)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct UniformSampler;
 
 impl PrioritySampler for UniformSampler {
-    fn sample_priority(&self, _node: &Node) -> f64 {
+    fn sample_priority(&self, _node: &Node, _client: SocketAddr) -> f64 {
         1.0
     }
 }
@@ -168,7 +226,7 @@ impl PrioritySampler for UniformSampler {
 pub struct QuerySampler;
 
 impl PrioritySampler for QuerySampler {
-    fn sample_priority(&self, node: &Node) -> f64 {
+    fn sample_priority(&self, node: &Node, _client: SocketAddr) -> f64 {
         node.query_statistics.rand_priority()
     }
 }
@@ -177,7 +235,142 @@ impl PrioritySampler for QuerySampler {
 pub struct EditionSampler;
 
 impl PrioritySampler for EditionSampler {
-    fn sample_priority(&self, node: &Node) -> f64 {
+    fn sample_priority(&self, node: &Node, _client: SocketAddr) -> f64 {
         node.edition_statistics.rand_priority()
     }
 }
+
+/// How much more likely a peer in the same network as the client is to be asked first.
+const SAME_NETWORK_BONUS: f64 = 4.0;
+/// The round-trip time to the hub at which a peer is half as likely to be asked first.
+const RTT_SCALE: Duration = Duration::from_millis(100);
+
+/// Like [`QuerySampler`], but prefers peers close to the client: peers in the same network
+/// (same /16 for IPv4 or same /32 for IPv6) and peers with a low round-trip time to the hub.
+/// This makes the transfer faster once a candidate is chosen.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalitySampler;
+
+impl LocalitySampler {
+    /// Whether two addresses are in the same network, as far as we can tell.
+    fn same_network(a: IpAddr, b: IpAddr) -> bool {
+        match (a, b) {
+            (IpAddr::V4(a), IpAddr::V4(b)) => a.octets()[..2] == b.octets()[..2],
+            (IpAddr::V6(a), IpAddr::V6(b)) => a.octets()[..4] == b.octets()[..4],
+            _ => false,
+        }
+    }
+}
+
+impl PrioritySampler for LocalitySampler {
+    fn sample_priority(&self, node: &Node, client: SocketAddr) -> f64 {
+        let network_bonus = if Self::same_network(node.addr.ip(), client.ip()) {
+            SAME_NETWORK_BONUS
+        } else {
+            1.0
+        };
+        let rtt_penalty = 1.0 + node.rtt().as_secs_f64() / RTT_SCALE.as_secs_f64();
+
+        node.query_statistics.rand_priority() * network_bonus / rtt_penalty
+    }
+}
+
+/// How much more likely a peer where the client prefers is to be asked first.
+const GEO_PREFERENCE_BONUS: f64 = 4.0;
+
+/// Wraps another sampler, preferring peers in the same country as the client or in other
+/// countries, as the client asked (see [`GeoPreference`]). Peers and clients whose country is
+/// unknown are neither preferred nor avoided.
+#[derive(Debug, Clone)]
+pub struct GeoSampler<S> {
+    inner: S,
+    preference: GeoPreference,
+    client_country: Option<Country>,
+}
+
+impl<S> GeoSampler<S> {
+    pub fn new(inner: S, preference: GeoPreference, client: SocketAddr) -> GeoSampler<S> {
+        GeoSampler {
+            inner,
+            preference,
+            client_country: geoip::country(client.ip()),
+        }
+    }
+}
+
+impl<S: PrioritySampler> PrioritySampler for GeoSampler<S> {
+    fn sample_priority(&self, node: &Node, client: SocketAddr) -> f64 {
+        let priority = self.inner.sample_priority(node, client);
+        let is_preferred = match (self.preference, self.client_country, node.country) {
+            (GeoPreference::Near, Some(client), Some(node)) => client == node,
+            (GeoPreference::Far, Some(client), Some(node)) => client != node,
+            _ => false,
+        };
+
+        if is_preferred {
+            priority * GEO_PREFERENCE_BONUS
+        } else {
+            priority
+        }
+    }
+}
+
+/// The samplers that can be chosen for queries at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SamplerKind {
+    /// Asks peers in random order. See [`UniformSampler`].
+    Uniform,
+    /// Prefers peers that answered queries well in the past. See [`QuerySampler`].
+    Query,
+    /// Prefers peers close to the client. See [`LocalitySampler`].
+    Locality,
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<SamplerKind, String> {
+        match s {
+            "uniform" => Ok(SamplerKind::Uniform),
+            "query" => Ok(SamplerKind::Query),
+            "locality" => Ok(SamplerKind::Locality),
+            invalid => Err(format!(
+                "invalid sampler `{invalid}`: must be `uniform`, `query` or `locality`"
+            )),
+        }
+    }
+}
+
+impl SamplerKind {
+    fn sampler(self) -> Arc<dyn PrioritySampler> {
+        match self {
+            SamplerKind::Uniform => Arc::new(UniformSampler),
+            SamplerKind::Query => Arc::new(QuerySampler),
+            SamplerKind::Locality => Arc::new(LocalitySampler),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    /// The sampler currently used for queries.
+    static ref QUERY_SAMPLER: RwLock<(SamplerKind, Arc<dyn PrioritySampler>)> = {
+        let kind = crate::CLI.query_sampler;
+        RwLock::new((kind, kind.sampler()))
+    };
+}
+
+/// The kind of sampler currently used for queries.
+pub fn query_sampler_kind() -> SamplerKind {
+    QUERY_SAMPLER.read().expect("poisoned").0
+}
+
+/// The sampler currently used for queries.
+pub fn query_sampler() -> Arc<dyn PrioritySampler> {
+    QUERY_SAMPLER.read().expect("poisoned").1.clone()
+}
+
+/// Changes the sampler used for queries from now on.
+pub fn set_query_sampler(kind: SamplerKind) {
+    log::info!("query sampler set to {kind:?}");
+    *QUERY_SAMPLER.write().expect("poisoned") = (kind, kind.sampler());
+}
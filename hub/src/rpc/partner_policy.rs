@@ -0,0 +1,149 @@
+//! Policies restricting which resolutions get forwarded between this hub and its partners.
+//! By default, everything is forwarded.
+//!
+//! Resolutions carry no hints of what they are looking for, only riddles, so there is no way
+//! of filtering them by hint prefix. Policies with a `hint_prefixes` allowlist are therefore
+//! rejected instead of silently forwarding everything: use `allowed_kinds`, which is the only
+//! thing about the content a hub gets to see, to restrict by what is being queried.
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::RwLock;
+use std::time::Instant;
+
+use samizdat_common::rpc::{QueryKind, Resolution};
+
+lazy_static::lazy_static! {
+    /// The policy currently in place for all partners.
+    static ref PARTNER_POLICY: RwLock<PartnerPolicy> = RwLock::default();
+    /// The rate limiting state for each partner.
+    static ref BUCKETS: RwLock<BTreeMap<SocketAddr, TokenBucket>> = RwLock::default();
+}
+
+/// The rules that a resolution coming from a partner must satisfy to be forwarded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PartnerPolicy {
+    /// The maximum number of resolutions per second forwarded for each partner. Bursts of at
+    /// most this number of resolutions are allowed. If not set, the rate is unlimited.
+    #[serde(default)]
+    pub max_rate: Option<f64>,
+    /// The kinds of queries that can be forwarded. If not set, all kinds are forwarded.
+    #[serde(default)]
+    pub allowed_kinds: Option<Vec<QueryKind>>,
+    /// The probability with which each resolution is forwarded. If not set, all resolutions are
+    /// forwarded.
+    #[serde(default)]
+    pub sampling: Option<f64>,
+    /// An allowlist of hint prefixes, which cannot be enforced (see the module documentation).
+    /// Only accepted so that setting it fails loudly.
+    #[serde(default, skip_serializing)]
+    pub hint_prefixes: Option<Vec<String>>,
+}
+
+impl PartnerPolicy {
+    /// Checks whether the values in this policy make sense.
+    pub fn validate(&self) -> Result<(), crate::Error> {
+        if self.hint_prefixes.is_some() {
+            return Err(crate::Error::ValidationFailed(
+                "resolutions carry no hints to filter by; use `allowed_kinds` instead".to_owned(),
+            ));
+        }
+
+        if let Some(max_rate) = self.max_rate {
+            if !(max_rate >= 0.0 && max_rate.is_finite()) {
+                return Err(crate::Error::ValidationFailed(format!(
+                    "invalid max rate {max_rate}"
+                )));
+            }
+        }
+
+        if let Some(sampling) = self.sampling {
+            if !(0.0..=1.0).contains(&sampling) {
+                return Err(crate::Error::ValidationFailed(format!(
+                    "sampling {sampling} is not a probability"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A token bucket that refills continuously with time.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64) -> TokenBucket {
+        TokenBucket {
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Tries to take one token from the bucket.
+    fn take(&mut self, rate: f64) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = f64::min(rate, self.tokens + elapsed * rate);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Retrieves the policy currently in place.
+pub fn partner_policy() -> PartnerPolicy {
+    PARTNER_POLICY.read().expect("poisoned").clone()
+}
+
+/// Replaces the policy currently in place. The rate limiting state for all partners is reset.
+pub fn set_partner_policy(policy: PartnerPolicy) -> Result<(), crate::Error> {
+    policy.validate()?;
+    *PARTNER_POLICY.write().expect("poisoned") = policy;
+    BUCKETS.write().expect("poisoned").clear();
+
+    Ok(())
+}
+
+/// Whether a resolution coming from a given partner is to be forwarded.
+pub fn admits(partner: SocketAddr, resolution: &Resolution) -> bool {
+    let policy = PARTNER_POLICY.read().expect("poisoned");
+
+    if let Some(allowed_kinds) = &policy.allowed_kinds {
+        if !allowed_kinds.contains(&resolution.kind) {
+            log::debug!("resolution from {partner} rejected by kind");
+            return false;
+        }
+    }
+
+    if let Some(sampling) = policy.sampling {
+        if rand::random::<f64>() >= sampling {
+            log::debug!("resolution from {partner} rejected by sampling");
+            return false;
+        }
+    }
+
+    if let Some(max_rate) = policy.max_rate {
+        let mut buckets = BUCKETS.write().expect("poisoned");
+        let bucket = buckets
+            .entry(partner)
+            .or_insert_with(|| TokenBucket::new(max_rate));
+
+        if !bucket.take(max_rate) {
+            log::debug!("resolution from {partner} rejected by rate limit");
+            return false;
+        }
+    }
+
+    true
+}
@@ -44,7 +44,7 @@ impl Room {
         current: SocketAddr,
     ) -> impl 'a + Stream<Item = (SocketAddr, Arc<Node>)> {
         let peers = self.participants.read().await;
-        let sampler = node_sampler::sample(sampler, &peers).filter(move |(_, peer)| {
+        let sampler = node_sampler::sample(sampler, current, &peers).filter(move |(_, peer)| {
             let peer_ip = peer.addr.ip();
             let current_ip = current.ip();
 
@@ -65,10 +65,13 @@ impl Room {
         futures::stream::iter(sampler)
     }
 
+    /// Maps the peers of `current`, in the order given by the sampler, asking at most
+    /// `concurrency` peers at a time.
     pub fn with_peers<'a, F, FFut, U>(
         &'a self,
         sampler: impl 'a + PrioritySampler,
         current: SocketAddr,
+        concurrency: usize,
         map: F,
     ) -> impl 'a + Stream<Item = U>
     where
@@ -93,7 +96,7 @@ impl Room {
                     filter_map
                 }
             })
-            .buffer_unordered(CLI.max_resolutions_per_query)
+            .buffer_unordered(concurrency)
             .filter_map(|outcome| async move { outcome })
             .take(CLI.max_candidates)
     }
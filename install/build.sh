@@ -49,6 +49,11 @@ do
         cargo build --release --bin samizdat-node --target $arch
         cargo build --release --bin samizdat --target $arch
 
+        if [[ $arch == *windows* ]]
+        then
+            cargo build --release --bin samizdat-service --target $arch
+        fi
+
         for artifact in $(cat ./install/node/$arch/artifacts.txt)
         do
             echo "Loading artifact $artifact"
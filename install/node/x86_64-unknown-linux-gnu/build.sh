@@ -1,4 +1,5 @@
 #! /usr/bin/env bash
 
 envsubst '$SAMIZDAT_PUBLIC_KEY,$VERSION' < install.sh > $OUTPUT/install.sh &&
-cp samizdat-node.service $OUTPUT
+cp samizdat-node.service $OUTPUT &&
+cp samizdat-node.socket $OUTPUT
@@ -14,12 +14,15 @@ cd $tmpdir &&
 curl $urlprefix/samizdat-node > samizdat-node &&
 curl $urlprefix/samizdat > samizdat &&
 curl $urlprefix/samizdat-node.service > samizdat-node.service &&
+curl $urlprefix/samizdat-node.socket > samizdat-node.socket &&
 
 (systemctl stop samizdat-node || echo 'No running node detected') &&
 cp samizdat-node /usr/local/bin &&
 cp samizdat /usr/local/bin &&
 cp samizdat-node.service /etc/systemd/system/samizdat-node.service &&
+cp samizdat-node.socket /etc/systemd/system/samizdat-node.socket &&
 systemctl daemon-reload &&
+systemctl enable --now samizdat-node.socket &&
 systemctl enable --now samizdat-node &&
 
 rm -rf $tmpdir
@@ -1,5 +1,5 @@
 export async function call(method: string, route: string, payload?: Object) {
-  return await fetch(`http://localhost:4510${route}`, {
+  return await fetch(`http://localhost:4510/v1${route}`, {
     method,
     headers: {
       "Content-Type": "application/json",
@@ -9,7 +9,7 @@ export async function call(method: string, route: string, payload?: Object) {
 }
 
 export async function callRaw(method: string, route: string, body: BodyInit) {
-  return await fetch(`http://localhost:4510${route}`, {
+  return await fetch(`http://localhost:4510/v1${route}`, {
     method,
     body,
   });
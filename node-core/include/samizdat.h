@@ -0,0 +1,19 @@
+/* C API of the Samizdat node core. See `node-core/src/ffi.rs` for documentation. */
+
+#ifndef SAMIZDAT_H
+#define SAMIZDAT_H
+
+#include <stdbool.h>
+
+#define SAMIZDAT_OK 0
+#define SAMIZDAT_INVALID_ARGUMENTS -1
+#define SAMIZDAT_ALREADY_STARTED -2
+#define SAMIZDAT_FAILED -3
+
+int samizdat_node_start(int argc, const char *const *argv);
+bool samizdat_node_is_running(void);
+void samizdat_node_pause(void);
+void samizdat_node_resume(void);
+void samizdat_node_set_metered(bool is_metered);
+
+#endif
@@ -56,6 +56,10 @@ pub enum AccessRight {
     ManageSeries,
     ManageSubscriptions,
     ManageIdentities,
+    ManageWebhooks,
+    GetConnectionStatus,
+    ManageLogging,
+    ManageLifecycle,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -0,0 +1,139 @@
+//! Integration with service managers that start the node on demand. With _socket activation_,
+//! the service manager (systemd or launchd) owns the HTTP listener and hands it over to the node
+//! when it starts, so that requests made while the node is booting wait instead of failing. The
+//! node also tells systemd when it is ready to serve requests, so that units depending on it
+//! (such as a local proxy) are only started afterwards.
+//!
+//! For launchd, the socket must be declared under the `Listeners` key of the `Sockets`
+//! dictionary in the job plist.
+
+use std::net::TcpListener;
+
+/// The name of the socket in the launchd job plist.
+#[cfg(target_os = "macos")]
+const LAUNCHD_SOCKET_NAME: &str = "Listeners";
+
+/// Takes the HTTP listener passed by systemd, if any (see `sd_listen_fds(3)`).
+#[cfg(unix)]
+fn systemd_listener() -> Option<TcpListener> {
+    use std::os::unix::io::FromRawFd;
+
+    /// The first file descriptor passed by systemd.
+    const SD_LISTEN_FDS_START: i32 = 3;
+
+    let listen_pid = std::env::var("LISTEN_PID").ok()?;
+    let listen_fds = std::env::var("LISTEN_FDS").ok()?;
+
+    // The variables are not meant for any children of this process:
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    if listen_pid.parse::<u32>().ok()? != std::process::id() {
+        return None;
+    }
+
+    match listen_fds.parse::<i32>().ok()? {
+        0 => None,
+        1 => Some(unsafe { TcpListener::from_raw_fd(SD_LISTEN_FDS_START) }),
+        n => {
+            log::warn!("systemd passed {n} sockets; only the first one will be used");
+            Some(unsafe { TcpListener::from_raw_fd(SD_LISTEN_FDS_START) })
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn systemd_listener() -> Option<TcpListener> {
+    None
+}
+
+/// Takes the HTTP listener passed by launchd, if any (see `launch_activate_socket(3)`).
+#[cfg(target_os = "macos")]
+fn launchd_listener() -> Option<TcpListener> {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::os::unix::io::FromRawFd;
+
+    extern "C" {
+        fn launch_activate_socket(
+            name: *const c_char,
+            fds: *mut *mut c_int,
+            cnt: *mut usize,
+        ) -> c_int;
+        fn free(ptr: *mut c_void);
+    }
+
+    let name = CString::new(LAUNCHD_SOCKET_NAME).expect("no nul bytes");
+    let mut fds: *mut c_int = std::ptr::null_mut();
+    let mut count = 0;
+
+    // Fails with `ESRCH` if not running under launchd, which is fine.
+    if unsafe { launch_activate_socket(name.as_ptr(), &mut fds, &mut count) } != 0 {
+        return None;
+    }
+
+    let fds_slice = unsafe { std::slice::from_raw_parts(fds, count) };
+    let listener = fds_slice
+        .first()
+        .map(|&fd| unsafe { TcpListener::from_raw_fd(fd) });
+
+    if count > 1 {
+        log::warn!("launchd passed {count} sockets; only the first one will be used");
+    }
+
+    unsafe { free(fds as *mut c_void) };
+
+    listener
+}
+
+#[cfg(not(target_os = "macos"))]
+fn launchd_listener() -> Option<TcpListener> {
+    None
+}
+
+/// Takes the HTTP listener passed by the service manager, if the node was socket-activated.
+pub fn inherited_listener() -> Option<TcpListener> {
+    let listener = systemd_listener().or_else(launchd_listener)?;
+
+    match listener.local_addr() {
+        Ok(addr) => log::info!("using socket-activated listener on {addr}"),
+        Err(err) => log::warn!("socket-activated listener has no address: {err}"),
+    }
+
+    Some(listener)
+}
+
+/// Tells systemd that the node is ready to serve requests, if it is running under systemd with
+/// `Type=notify` (see `sd_notify(3)`). This is a no-op elsewhere.
+pub fn notify_ready() {
+    if let Err(err) = sd_notify("READY=1") {
+        log::warn!("failed to notify readiness to systemd: {err}");
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn sd_notify(state: &str) -> Result<(), std::io::Error> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let notify_socket = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(notify_socket) => notify_socket,
+        None => return Ok(()),
+    };
+
+    // Names starting with `@` live in the abstract namespace:
+    let addr = match notify_socket.to_str().and_then(|s| s.strip_prefix('@')) {
+        Some(abstract_name) => SocketAddr::from_abstract_name(abstract_name)?,
+        None => SocketAddr::from_pathname(&notify_socket)?,
+    };
+
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sd_notify(_state: &str) -> Result<(), std::io::Error> {
+    Ok(())
+}
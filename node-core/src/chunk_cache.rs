@@ -0,0 +1,166 @@
+//! An in-memory cache of the chunks used most recently, in front of the database, shared by
+//! everything that reads and writes objects. Chunks are addressed by their hashes and never
+//! change, so the cache only forgets chunks to make room for others or when they are deleted
+//! from the database.
+
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use samizdat_common::Hash;
+
+use crate::cli;
+
+/// The number of reads answered by the cache.
+static HITS: AtomicU64 = AtomicU64::new(0);
+/// The number of reads that had to go to the database.
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+lazy_static::lazy_static! {
+    static ref CACHE: Mutex<ChunkCache> = Mutex::default();
+}
+
+#[derive(Debug, Default)]
+struct ChunkCache {
+    /// The chunks in the cache, with the tick of their last use.
+    chunks: BTreeMap<Hash, (Arc<Vec<u8>>, u64)>,
+    /// The chunks in the cache, by the tick of their last use.
+    by_use: BTreeMap<u64, Hash>,
+    /// The total size of the chunks in the cache, in bytes.
+    size: usize,
+    /// Advances with each use of the cache.
+    tick: u64,
+}
+
+impl ChunkCache {
+    fn touch(&mut self, hash: Hash) -> Option<Arc<Vec<u8>>> {
+        self.tick += 1;
+        let (chunk, last_use) = self.chunks.get_mut(&hash)?;
+        self.by_use.remove(last_use);
+        self.by_use.insert(self.tick, hash);
+        *last_use = self.tick;
+
+        Some(chunk.clone())
+    }
+
+    fn remove(&mut self, hash: &Hash) {
+        if let Some((chunk, last_use)) = self.chunks.remove(hash) {
+            self.by_use.remove(&last_use);
+            self.size -= chunk.len();
+        }
+    }
+
+    fn insert(&mut self, hash: Hash, chunk: Vec<u8>, capacity: usize) {
+        if chunk.len() > capacity || self.touch(hash).is_some() {
+            return;
+        }
+
+        self.size += chunk.len();
+        self.chunks.insert(hash, (Arc::new(chunk), self.tick));
+        self.by_use.insert(self.tick, hash);
+
+        while self.size > capacity {
+            let Some((_, evicted)) = self.by_use.pop_first() else {
+                break;
+            };
+
+            if let Some((chunk, _)) = self.chunks.remove(&evicted) {
+                self.size -= chunk.len();
+            }
+        }
+    }
+}
+
+/// The most memory the cache may use, in bytes.
+fn capacity() -> usize {
+    cli().chunk_cache_size * 1_000_000
+}
+
+/// Gets a chunk from the cache, if it is there.
+pub fn get(hash: Hash) -> Option<Arc<Vec<u8>>> {
+    let found = CACHE.lock().expect("poisoned").touch(hash);
+
+    if found.is_some() {
+        HITS.fetch_add(1, Ordering::Relaxed);
+    } else {
+        MISSES.fetch_add(1, Ordering::Relaxed);
+    }
+
+    found
+}
+
+/// Puts a chunk just read from or written to the database in the cache, making room for it
+/// by forgetting the chunks used least recently.
+pub fn insert(hash: Hash, chunk: Vec<u8>) {
+    let capacity = capacity();
+
+    if capacity > 0 {
+        CACHE
+            .lock()
+            .expect("poisoned")
+            .insert(hash, chunk, capacity);
+    }
+}
+
+/// Forgets a chunk, which is being deleted from the database.
+pub fn remove(hash: &Hash) {
+    CACHE.lock().expect("poisoned").remove(hash);
+}
+
+/// Forgets all chunks.
+pub fn clear() {
+    *CACHE.lock().expect("poisoned") = ChunkCache::default();
+}
+
+/// How the cache is being used.
+#[derive(Debug, Serialize)]
+pub struct ChunkCacheStats {
+    /// The most memory the cache may use, in bytes.
+    pub capacity: usize,
+    /// The memory used by the chunks in the cache, in bytes.
+    pub size: usize,
+    /// The number of chunks in the cache.
+    pub chunks: usize,
+    /// The number of reads answered by the cache since the node started.
+    pub hits: u64,
+    /// The number of reads that had to go to the database since the node started.
+    pub misses: u64,
+    /// The fraction of the reads answered by the cache, if there were any reads.
+    pub hit_rate: Option<f64>,
+}
+
+/// How the cache is being used.
+pub fn stats() -> ChunkCacheStats {
+    let (size, chunks) = {
+        let cache = CACHE.lock().expect("poisoned");
+        (cache.size, cache.chunks.len())
+    };
+    let hits = HITS.load(Ordering::Relaxed);
+    let misses = MISSES.load(Ordering::Relaxed);
+
+    ChunkCacheStats {
+        capacity: capacity(),
+        size,
+        chunks,
+        hits,
+        misses,
+        hit_rate: (hits + misses > 0).then(|| hits as f64 / (hits + misses) as f64),
+    }
+}
+
+#[test]
+fn evicts_least_recently_used() {
+    let mut cache = ChunkCache::default();
+    let [a, b, c] = [b"a", b"b", b"c"].map(Hash::hash);
+
+    cache.insert(a, vec![0; 4], 8);
+    cache.insert(b, vec![0; 4], 8);
+    assert!(cache.touch(a).is_some());
+    cache.insert(c, vec![0; 4], 8);
+
+    assert!(cache.touch(a).is_some());
+    assert!(cache.touch(b).is_none());
+    assert!(cache.touch(c).is_some());
+    assert_eq!(cache.size, 8);
+}
@@ -0,0 +1,533 @@
+//! Command line interface for the Samizdat node.
+
+use chrono::NaiveTime;
+use std::ffi::OsString;
+use std::net::{IpAddr, SocketAddr};
+use std::num::ParseIntError;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+use samizdat_common::logger::{LogFilters, LogFormat, LoggerConfig};
+use samizdat_common::profiles;
+use samizdat_common::rpc::GeoPreference;
+
+/// The CLI parameters.
+#[derive(Debug, StructOpt)]
+pub struct Cli {
+    /// Set logging level.
+    #[structopt(short = "v")]
+    pub verbose: bool,
+    /// Path to the locally stored program data.
+    #[structopt(env = "SAMIZDAT_DATA", long, default_value = "data/node")]
+    pub data: PathBuf,
+    /// Runs the node for a separate profile, with its own data folder (under the folder in
+    /// `--data`), access token, port and keys. Profiles keep different personas apart. The
+    /// port of a profile is assigned when it is created and overrides `--port`.
+    #[structopt(env = "SAMIZDAT_PROFILE", long)]
+    pub profile: Option<String>,
+    /// The data folder of the default profile, under which all other profiles live.
+    #[structopt(skip)]
+    pub base_data: PathBuf,
+    /// Runs as a read-only replica of the node whose data folder is given, sharing the load of
+    /// serving content from a single machine. Replicas serve content to the local API and to
+    /// peers from the database of the primary node, but never write: all other requests
+    /// must go to the primary. Replicas need their own `--data` and `--port`.
+    #[structopt(env = "SAMIZDAT_REPLICA_OF", long)]
+    pub replica_of: Option<PathBuf>,
+    /// Shows what each pending migration of the database would change and exits, without
+    /// changing anything.
+    #[structopt(long)]
+    pub migrations_dry_run: bool,
+    /// Rolls back this migration of the database and all that were applied after it, then
+    /// exits. Only reversible migrations can be rolled back.
+    #[structopt(long)]
+    pub rollback_migration: Option<String>,
+    /// Does not snapshot the database into `backups/` in the data folder before migrating it.
+    #[structopt(env = "SAMIZDAT_NO_MIGRATION_BACKUP", long)]
+    pub no_migration_backup: bool,
+    /// The port on which to sever the local HTTP proxy. This is the port you will use to access in
+    ///  your browser.
+    #[structopt(env = "SAMIZDAT_PORT", long, default_value = "4510")]
+    pub port: u16,
+    /// (MB) The maximum size in bytes of the content that can be sent from a peer to this machine.
+    #[structopt(env = "SAMIZDAT_MAX_CONTENT_SIZE", long, default_value = "1000")]
+    pub max_content_size: usize,
+    /// (MB) The maximum size of the content that can be sent from a given peer, overriding
+    /// `--max-content-size`, as `IP=SIZE`, e.g., to exchange bigger objects with trusted peers.
+    /// Can be given many times.
+    #[structopt(env = "SAMIZDAT_PEER_MAX_CONTENT_SIZE", long)]
+    pub peer_max_content_size: Vec<PeerContentSize>,
+    /// (MB) The maximum size of an object uploaded through the HTTP API.
+    #[structopt(env = "SAMIZDAT_MAX_UPLOAD_SIZE", long, default_value = "10000")]
+    pub max_upload_size: usize,
+    /// Stores uploads with the content type given by the client, without looking at their
+    /// content to fill in a missing content type or charset.
+    #[structopt(env = "SAMIZDAT_NO_CONTENT_SNIFFING", long)]
+    pub no_content_sniffing: bool,
+    /// (s) The maximum time to download a URL that the node was asked to store as an object.
+    #[structopt(env = "SAMIZDAT_FETCH_TIMEOUT", long, default_value = "60")]
+    pub fetch_timeout: u64,
+    /// Base URLs of servers exposing this node's objects (e.g., Samizdat proxies) to be
+    /// advertised as web seeds in the torrents created for objects. BitTorrent clients fetch
+    /// pieces from web seeds with range requests, so these servers must support them.
+    #[structopt(env = "SAMIZDAT_TORRENT_WEBSEEDS", long)]
+    pub torrent_webseeds: Vec<String>,
+    /// URLs of Nostr relays (e.g., `wss://relay.example.com`) where edition announcements are
+    /// also published and watched for. This is an alternative way of finding new editions when
+    /// the hubs are unreachable.
+    #[structopt(env = "SAMIZDAT_NOSTR_RELAYS", long)]
+    pub nostr_relays: Vec<String>,
+    /// Report, from time to time, how many times each object of the series mirrored by this
+    /// node was served to peers back to their publishers. Counts are coarse and are sent
+    /// through the Nostr relays, which need to be set with `--nostr-relays`.
+    #[structopt(env = "SAMIZDAT_REPORT_READERSHIP", long)]
+    pub report_readership: bool,
+    /// Signs every object served through HTTP with a key only this node knows, so that the
+    /// users of a public gateway fronting this node can check that the gateway did not tamper
+    /// with the content.
+    #[structopt(env = "SAMIZDAT_SIGN_RESPONSES", long)]
+    pub sign_responses: bool,
+    /// The default `Content-Security-Policy` of the content served by this node, which
+    /// publishers may override for their items. An empty value sends no header. Note that all
+    /// content shares the origin of the node unless `--isolate-origins` is set, so that
+    /// `'self'` stands for everything this node serves.
+    #[structopt(
+        env = "SAMIZDAT_CONTENT_SECURITY_POLICY",
+        long,
+        default_value = "frame-ancestors 'self'; object-src 'none'; base-uri 'self'"
+    )]
+    pub content_security_policy: String,
+    /// The default `X-Frame-Options` of the content served by this node, which publishers may
+    /// override for their items. An empty value sends no header.
+    #[structopt(env = "SAMIZDAT_FRAME_OPTIONS", long, default_value = "SAMEORIGIN")]
+    pub frame_options: String,
+    /// The default `Referrer-Policy` of the content served by this node, which publishers may
+    /// override for their items. An empty value sends no header. Apps are told apart by the
+    /// `Referer` of their calls to the node API, so policies stripping it from same-origin
+    /// requests (e.g., `no-referrer`) break them.
+    #[structopt(env = "SAMIZDAT_REFERRER_POLICY", long, default_value = "same-origin")]
+    pub referrer_policy: String,
+    /// Serves each series, collection and object on its own subdomain of `localhost` (e.g.,
+    /// `series-{key}.localhost`), so that browsers isolate the apps served by this node from
+    /// each other and from the node API. Browsers navigating to entities by path are redirected
+    /// to their subdomains.
+    #[structopt(env = "SAMIZDAT_ISOLATE_ORIGINS", long)]
+    pub isolate_origins: bool,
+    /// (s) The maximum time to receive content from a peer, once the peer is found. Big content
+    /// gets more time, enough for it to arrive at 64KiB/s.
+    #[structopt(env = "SAMIZDAT_TRANSFER_BUDGET", long, default_value = "120")]
+    pub transfer_budget: u64,
+    /// A list of hubs to which to connect.
+    #[structopt(env = "SAMIZDAT_HUBS", long, default_value = "[::1]:4511")]
+    pub hubs: Vec<AddrToResolve>,
+    /// The port through which to connect to hubs over TLS over TCP, when QUIC does not get
+    /// through, e.g., in networks that block UDP.
+    #[structopt(env = "SAMIZDAT_HUB_TCP_FALLBACK_PORT", long, default_value = "4513")]
+    pub hub_tcp_fallback_port: u16,
+    /// (s) For how long a hub must be down before a `hub-down` event is sent to the webhooks.
+    /// Set to 0 to never send it.
+    #[structopt(env = "SAMIZDAT_HUB_DOWN_ALERT_AFTER", long, default_value = "300")]
+    pub hub_down_alert_after: u64,
+    /// The number of QUIC connection attempts to a hub in a row that must time out before
+    /// falling back to TCP. Set to 0 to never fall back.
+    #[structopt(
+        env = "SAMIZDAT_QUIC_TIMEOUTS_BEFORE_TCP_FALLBACK",
+        long,
+        default_value = "3"
+    )]
+    pub quic_timeouts_before_tcp_fallback: usize,
+    /// Considers the node ready (see `/readyz`) even if no hub is reachable, e.g., for nodes
+    /// that only serve local content.
+    #[structopt(env = "SAMIZDAT_STANDALONE", long)]
+    pub standalone: bool,
+    /// The local addresses to which to bind the endpoints used to talk to hubs and peers. The
+    /// node connects to each hub through each address of the same IP version (the IPv6 wildcard
+    /// `::` is compatible with both versions), so that peers can reach it through any of them,
+    /// e.g., LAN, VPN and public addresses at once. Ports are always chosen by the system.
+    #[structopt(env = "SAMIZDAT_BIND_ADDRESSES", long, default_value = "::")]
+    pub bind_addresses: Vec<IpAddr>,
+    /// The mode of resolution to be used with domain names. Must be one of `ensure-ipv4`,
+    /// `ensure-ipv6`, `prefer-ipv6`, `prefer-ipv4` or `use-both`. Note that the `prefer-*` options
+    /// will resolve to the other IP version if no address is available for the current version.
+    #[structopt(env = "SAMIZDAT_RESOLUTION_MODE", long, default_value = "use-both")]
+    pub resolution_mode: AddrResolutionMode,
+    /// The maximum number of hubs to be queried simultaneously per query.
+    #[structopt(env = "SAMIZDAT_MAX_PARALLEL_HUBS", long, default_value = "3")]
+    pub max_parallel_hubs: usize,
+    /// Where the peers resolving queries should preferably be, for hubs that know where nodes
+    /// are: `any`, `near` (in the same country as this node, for lower latency) or `far` (in
+    /// other countries, for jurisdiction diversity).
+    #[structopt(env = "SAMIZDAT_GEO_PREFERENCE", long, default_value = "any")]
+    pub geo_preference: GeoPreference,
+    /// (s) How long to remember that content was not found in the network, answering queries
+    /// for it right away instead of waiting for the whole query deadline again. Set to `0` to
+    /// always ask the network.
+    #[structopt(env = "SAMIZDAT_NEGATIVE_CACHE_TTL", long, default_value = "60")]
+    pub negative_cache_ttl: u64,
+    /// (MB) The maximum total size of all cached files and _disposable_ files. Note that the total
+    /// size may still exceed this value, since some of the allocated space is used to store
+    /// data that is valuable to you.
+    #[structopt(env = "SAMIZDAT_MAX_STORAGE", long, default_value = "1000")]
+    pub max_storage: usize,
+    /// (s) The minimum age of an object before the vacuum may delete it, so that content just
+    /// fetched for a page being loaded is not deleted before it is served.
+    #[structopt(env = "SAMIZDAT_VACUUM_MIN_AGE", long, default_value = "600")]
+    pub vacuum_min_age: i64,
+    /// (MB) The most memory used to keep the chunks of objects read or written most recently,
+    /// so that popular content is served without going to the disk. Set to `0` to disable.
+    #[structopt(env = "SAMIZDAT_CHUNK_CACHE_SIZE", long, default_value = "64")]
+    pub chunk_cache_size: usize,
+    /// (kB) The maximum total size of the keys and values that each application can keep in
+    /// the key-value store.
+    #[structopt(env = "SAMIZDAT_KVSTORE_QUOTA", long, default_value = "1024")]
+    pub kvstore_quota: usize,
+    /// Categories of content warnings (e.g., `nsfw`) that publishers flag their collections
+    /// with. Content flagged with any of these is neither served, nor mirrored, nor seeded to
+    /// peers by this node.
+    #[structopt(env = "SAMIZDAT_REFUSE_CONTENT_WARNINGS", long)]
+    pub refuse_content_warnings: Vec<String>,
+    /// The number of riddles to be sent on each query. This gives the maximum number of hops that a
+    /// query can propagate inside a network, with 2 being the absolute minimum to get a result.
+    #[structopt(env = "SAMIZDAT_RIDDLES_PER_QUERY", long, default_value = "6")]
+    pub riddles_per_query: usize,
+    /// The number of riddles to be sent on each collection item query. Defaults to
+    /// `--riddles-per-query`.
+    #[structopt(env = "SAMIZDAT_RIDDLES_PER_ITEM_QUERY", long)]
+    pub riddles_per_item_query: Option<usize>,
+    /// The maximum number of queries per minute before the node starts reducing the number of
+    /// riddles sent on each query, to make its interests harder to link. Set to zero to disable.
+    #[structopt(env = "SAMIZDAT_PRIVACY_BUDGET", long, default_value = "120")]
+    pub privacy_budget: usize,
+    /// The average number of dummy queries for random hashes sent per minute. Dummy queries
+    /// make it harder for observers of the hub traffic to infer which content this node actually
+    /// reads. Set to zero to disable.
+    #[structopt(env = "SAMIZDAT_COVER_TRAFFIC", long, default_value = "0")]
+    pub cover_traffic: f64,
+    /// Daily periods, in local time, during which the node is quiet (see `/_quiet`), e.g.,
+    /// `23:00-07:00`: it does not seed content to peers and defers subscription refreshes and
+    /// edition announcements until the period is over, but still serves local reads.
+    #[structopt(env = "SAMIZDAT_QUIET_HOURS", long)]
+    pub quiet_hours: Vec<QuietHours>,
+    /// Makes the node quiet while the device is running on battery. This is only detected in
+    /// Linux.
+    #[structopt(env = "SAMIZDAT_QUIET_ON_BATTERY", long)]
+    pub quiet_on_battery: bool,
+    /// A shell command that is run every minute and that makes the node quiet while it exits
+    /// successfully, e.g., to detect that the device is on a hotspot or on a metered network
+    /// (`nmcli -t -g GENERAL.METERED dev show wlan0 | grep -q yes`).
+    #[structopt(env = "SAMIZDAT_QUIET_COMMAND", long)]
+    pub quiet_command: Option<String>,
+    /// Asks the local router, through UPnP, to forward inbound traffic to the ports used by this
+    /// node. This makes it easier for peers to connect to nodes behind consumer routers.
+    #[structopt(env = "SAMIZDAT_PORT_MAPPING", long)]
+    pub port_mapping: bool,
+    /// Exit gracefully when the standard input is closed. This is used by service managers that
+    /// cannot send signals, such as the Windows Service Control Manager.
+    #[structopt(long)]
+    pub exit_on_stdin_close: bool,
+    /// Also write the logs to this file, which is rotated when it gets too big.
+    #[structopt(env = "SAMIZDAT_LOG_FILE", long)]
+    pub log_file: Option<PathBuf>,
+    /// (MB) The size after which the log file is rotated.
+    #[structopt(env = "SAMIZDAT_LOG_MAX_SIZE", long, default_value = "10")]
+    pub log_max_size: u64,
+    /// The number of rotated log files to keep.
+    #[structopt(env = "SAMIZDAT_LOG_MAX_FILES", long, default_value = "5")]
+    pub log_max_files: u32,
+    /// The format of the logs. Must be one of `text` or `json`.
+    #[structopt(env = "SAMIZDAT_LOG_FORMAT", long, default_value = "text")]
+    pub log_format: LogFormat,
+    /// Per-module log levels, e.g., `samizdat_node_core::system=debug,quinn=info`. A level
+    /// without a module sets the default level. These can be changed at runtime through the
+    /// `/_log-level` route.
+    #[structopt(env = "SAMIZDAT_LOG", long, default_value = "")]
+    pub log: LogFilters,
+    /// A token that is accepted in place of the access token, but that first wipes all profiles
+    /// in this machine (but the one in `--decoy-profile`). This is meant to be handed out
+    /// under duress. Keep it as long as the access token, so that it cannot be told apart.
+    #[structopt(env = "SAMIZDAT_DURESS_TOKEN", long)]
+    pub duress_token: Option<String>,
+    /// A profile to be kept when the node is wiped, to which the CLI is switched afterwards.
+    #[structopt(env = "SAMIZDAT_DECOY_PROFILE", long)]
+    pub decoy_profile: Option<String>,
+    /// Don't warn on startup about crashes that happened since the node was last started.
+    #[structopt(env = "SAMIZDAT_NO_CRASH_HINT", long)]
+    pub no_crash_hint: bool,
+}
+
+impl Cli {
+    /// The maximum size in bytes of the content that can be sent from a peer to this machine.
+    pub fn max_content_size_for(&self, peer: IpAddr) -> usize {
+        let peer = peer.to_canonical();
+        let max_content_size = self
+            .peer_max_content_size
+            .iter()
+            .rev()
+            .find(|peer_size| peer_size.peer == peer)
+            .map_or(self.max_content_size, |peer_size| {
+                peer_size.max_content_size
+            });
+
+        max_content_size * 1_000_000
+    }
+
+    /// The configuration of the logger, as set in the command line.
+    pub fn logger_config(&self) -> LoggerConfig {
+        LoggerConfig {
+            file: self.log_file.clone(),
+            max_file_size: self.log_max_size * 1_000_000,
+            max_files: self.log_max_files,
+            format: self.log_format,
+            ..LoggerConfig::console(self.verbose)
+        }
+        .with_filters(self.log.clone())
+    }
+}
+
+/// The handle to the CLI parameters.
+static mut CLI: Option<Cli> = None;
+
+/// Initializes the [`CLI`] with the values from the command line.
+pub fn init_cli() -> Result<(), crate::Error> {
+    set_cli(Cli::from_args())
+}
+
+/// Initializes the [`CLI`] with the values from a list of arguments, as if they were passed in
+/// the command line (the first argument is the program name). This is meant for embedders.
+pub fn init_cli_from<I>(args: I) -> Result<(), crate::Error>
+where
+    I: IntoIterator,
+    I::Item: Into<OsString> + Clone,
+{
+    set_cli(Cli::from_iter_safe(args).map_err(|err| err.to_string())?)
+}
+
+fn set_cli(mut cli: Cli) -> Result<(), crate::Error> {
+    log::info!("Arguments from command line: {:#?}", cli);
+
+    cli.base_data = cli.data.clone();
+    if let Some(name) = &cli.profile {
+        let profile = profiles::get_or_create(&cli.data, name)?;
+        log::info!("Using profile {name} on port {}", profile.port);
+        cli.data = profile.data(&cli.data);
+        cli.port = profile.port;
+    }
+
+    for value in [
+        &cli.content_security_policy,
+        &cli.frame_options,
+        &cli.referrer_policy,
+    ] {
+        if http::HeaderValue::from_str(value).is_err() {
+            return Err(format!("bad security header value: {value:?}").into());
+        }
+    }
+
+    if !cli.cover_traffic.is_finite() || cli.cover_traffic < 0.0 {
+        return Err(format!(
+            "cover traffic must be a non-negative number, got {}",
+            cli.cover_traffic
+        )
+        .into());
+    }
+
+    std::fs::create_dir_all(&cli.data)?;
+
+    log::debug!("Initialized data folder");
+
+    unsafe {
+        CLI = Some(cli);
+    }
+
+    Ok(())
+}
+
+/// Returns a handle to the CLI arguments. Only use this after initialization.
+pub fn cli<'a>() -> &'a Cli {
+    unsafe { CLI.as_ref().expect("cli not initialized") }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AddrResolutionMode {
+    EnsureIpv6,
+    EnsureIpv4,
+    PreferIpv6,
+    PreferIpv4,
+    UseBoth,
+}
+
+impl FromStr for AddrResolutionMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ensure-ipv4" => Ok(Self::EnsureIpv4),
+            "ensure-ipv6" => Ok(Self::EnsureIpv6),
+            "prefer-ipv4" => Ok(Self::PreferIpv4),
+            "prefer-ipv6" => Ok(Self::PreferIpv6),
+            "use-both" => Ok(Self::UseBoth),
+            invalid => Err(format!("Invalid address resolution mode `{invalid}`")),
+        }
+    }
+}
+
+impl AddrResolutionMode {
+    fn filter_hosts(self, hosts: &[SocketAddr]) -> Vec<SocketAddr> {
+        // Iterator factory (makes IPs canonical).
+        let iter_hosts = || {
+            hosts
+                .iter()
+                .map(|addr| (addr.ip().to_canonical(), addr.port()))
+                .map(SocketAddr::from)
+        };
+
+        match self {
+            Self::EnsureIpv6 => iter_hosts().filter(SocketAddr::is_ipv6).take(1).collect(),
+            Self::EnsureIpv4 => iter_hosts().filter(SocketAddr::is_ipv4).take(1).collect(),
+            Self::PreferIpv6 => iter_hosts()
+                .max_by_key(|addr| if addr.is_ipv6() { 1 } else { 0 })
+                .into_iter()
+                .collect(),
+            Self::PreferIpv4 => iter_hosts()
+                .max_by_key(|addr| if addr.is_ipv4() { 1 } else { 0 })
+                .into_iter()
+                .collect(),
+            Self::UseBoth => {
+                let an_ipv6 = iter_hosts().filter(SocketAddr::is_ipv6).take(1);
+                // Loopbacks are coerced to IPv6.
+                let an_ipv4 = iter_hosts()
+                    .filter(SocketAddr::is_ipv4)
+                    .filter(|socket| !socket.ip().is_loopback())
+                    .take(1);
+
+                an_ipv4.chain(an_ipv6).collect()
+            }
+        }
+    }
+}
+
+/// A daily period, in local time, in the `HH:MM-HH:MM` format. The period wraps around
+/// midnight if it ends before it starts.
+#[derive(Debug, Clone, Copy)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl FromStr for QuietHours {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|err| format!("invalid time `{time}` in quiet hours: {err}"))
+        };
+
+        match s.split_once('-') {
+            Some((start, end)) => Ok(QuietHours {
+                start: parse(start)?,
+                end: parse(end)?,
+            }),
+            None => Err(format!("invalid quiet hours `{s}`: expected `HH:MM-HH:MM`")),
+        }
+    }
+}
+
+impl QuietHours {
+    /// Whether a time of the day falls within this period.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+/// A maximum content size (in MB) for the content sent from a peer, in the `IP=SIZE` format.
+#[derive(Debug, Clone, Copy)]
+pub struct PeerContentSize {
+    peer: IpAddr,
+    max_content_size: usize,
+}
+
+impl FromStr for PeerContentSize {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (peer, size) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid peer content size `{s}`: expected `IP=SIZE`"))?;
+
+        Ok(PeerContentSize {
+            peer: peer
+                .trim()
+                .parse::<IpAddr>()
+                .map_err(|err| format!("invalid peer `{peer}`: {err}"))?
+                .to_canonical(),
+            max_content_size: size
+                .trim()
+                .parse()
+                .map_err(|err| format!("invalid size `{size}` for peer {peer}: {err}"))?,
+        })
+    }
+}
+
+/// A flexible representation of an address in the internet.
+#[derive(Debug)]
+pub enum AddrToResolve {
+    /// A raw `ip:port` address.
+    SocketAddr(SocketAddr),
+    /// A `domain:port` (or `domain`, only) address.
+    DomainAndPort(String, u16),
+}
+
+impl FromStr for AddrToResolve {
+    type Err = ParseIntError;
+    fn from_str(s: &str) -> Result<Self, ParseIntError> {
+        if let Ok(socket_addr) = s.parse::<SocketAddr>() {
+            Ok(AddrToResolve::SocketAddr(socket_addr))
+        } else if let Some(pos) = s.find(':') {
+            Ok(AddrToResolve::DomainAndPort(
+                s[0..pos].to_owned(),
+                s[pos + 1..].parse::<u16>()?,
+            ))
+        } else {
+            Ok(AddrToResolve::DomainAndPort(s.to_owned(), 4511))
+        }
+    }
+}
+
+impl AddrToResolve {
+    fn name(&self) -> String {
+        match self {
+            AddrToResolve::SocketAddr(addr) => addr.to_string(),
+            AddrToResolve::DomainAndPort(domain, port) if *port == 4511 => domain.to_owned(),
+            AddrToResolve::DomainAndPort(domain, port) => format!("{}:{}", domain, port),
+        }
+    }
+
+    pub async fn resolve(
+        &self,
+        resolution_mode: AddrResolutionMode,
+    ) -> Result<impl Iterator<Item = (&'static str, SocketAddr)>, crate::Error> {
+        let name: &'static str = Box::leak(self.name().into_boxed_str());
+
+        let addrs = match self {
+            AddrToResolve::SocketAddr(addr) => vec![*addr],
+            AddrToResolve::DomainAndPort(domain, port) => {
+                let hosts = resolution_mode.filter_hosts(
+                    &tokio::net::lookup_host((&**domain, *port))
+                        .await?
+                        .collect::<Vec<_>>(),
+                );
+
+                if hosts.is_empty() {
+                    return Err(format!("no such host {}", domain).into());
+                } else {
+                    hosts
+                }
+            }
+        };
+
+        Ok(addrs.into_iter().map(move |addr| (name, addr)))
+    }
+}
@@ -0,0 +1,184 @@
+//! Content warnings: publishers may flag a collection with categories of sensitive content
+//! (e.g., `nsfw` or `gore`), kept in the inventory of the collection. Since the inventory is
+//! part of the collection, which the edition signs, nobody but the publisher can add or strip
+//! warnings.
+//!
+//! Operators, e.g. of public gateways, may refuse to serve, mirror or seed content flagged with
+//! some categories (see `--refuse-content-warnings`). Content whose inventory cannot be found
+//! is not refused.
+//!
+//! Collections never change, so the verdict on each collection is kept in memory: the
+//! inventory is looked at once and the network is asked for a missing inventory at most once
+//! every [`INVENTORY_RETRY`]. Objects asked for by hash do not tell which collection they are
+//! from; they are refused if they were listed in the inventory of a refused collection.
+
+use lazy_static::lazy_static;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use samizdat_common::rpc::QueryKind;
+use samizdat_common::Hash;
+
+use crate::cli;
+use crate::hubs;
+use crate::models::{CollectionRef, Inventory, ObjectRef};
+
+/// How long to wait before asking the network again for an inventory it did not have.
+const INVENTORY_RETRY: Duration = Duration::from_secs(600);
+
+/// The maximum number of verdicts kept. Past that, all are forgotten and found anew.
+const MAX_VERDICTS: usize = 100_000;
+
+/// What was found about a collection.
+#[derive(Debug, Clone)]
+enum Verdict {
+    /// The inventory was found and the collection is not refused.
+    Accepted,
+    /// The inventory was found and the collection is refused, for this reason.
+    Refused(String),
+    /// The inventory was not found, neither locally nor, at the given instant, in the network.
+    Unknown(Option<Instant>),
+}
+
+lazy_static! {
+    /// The verdicts on collections, indexed by collection hash.
+    static ref VERDICTS: Mutex<BTreeMap<Hash, Verdict>> = Mutex::default();
+    /// The objects listed in the inventories of refused collections.
+    static ref REFUSED_OBJECTS: RwLock<BTreeSet<Hash>> = RwLock::default();
+}
+
+/// Whether any content warnings are refused by this node at all.
+fn is_filtering() -> bool {
+    !cli().refuse_content_warnings.is_empty()
+}
+
+/// Fails if this node refuses the content of a collection, given its inventory.
+pub fn check_inventory(
+    collection: &CollectionRef,
+    inventory: &Inventory,
+) -> Result<(), crate::Error> {
+    match verdict(collection, inventory) {
+        Verdict::Refused(reason) => Err(crate::Error::Refused(reason)),
+        _ => Ok(()),
+    }
+}
+
+/// Judges a collection by its inventory, remembering the verdict.
+fn verdict(collection: &CollectionRef, inventory: &Inventory) -> Verdict {
+    let refused = inventory.content_warnings().iter().find(|warning| {
+        cli()
+            .refuse_content_warnings
+            .iter()
+            .any(|refused| refused.eq_ignore_ascii_case(warning))
+    });
+
+    let verdict = match refused {
+        Some(warning) => {
+            REFUSED_OBJECTS
+                .write()
+                .expect("poisoned")
+                .extend(inventory.iter().map(|(_, &hash)| hash));
+            Verdict::Refused(format!(
+                "collection {} is flagged as {warning:?}",
+                collection.hash()
+            ))
+        }
+        None => Verdict::Accepted,
+    };
+
+    remember(collection, verdict.clone());
+    verdict
+}
+
+/// Remembers the verdict on a collection.
+fn remember(collection: &CollectionRef, verdict: Verdict) {
+    let mut verdicts = VERDICTS.lock().expect("poisoned");
+
+    if verdicts.len() >= MAX_VERDICTS {
+        verdicts.clear();
+    }
+
+    verdicts.insert(collection.hash(), verdict);
+}
+
+/// The verdict on a collection, looking only at what is present locally.
+fn local_verdict(collection: &CollectionRef) -> Result<Verdict, crate::Error> {
+    let asked_at = match VERDICTS.lock().expect("poisoned").get(&collection.hash()) {
+        Some(Verdict::Unknown(asked_at)) => *asked_at,
+        Some(verdict) => return Ok(verdict.clone()),
+        None => None,
+    };
+
+    // The inventory may have arrived since (e.g., by a subscription refresh).
+    // Content warnings are in the index of sharded inventories, so no need for the shards
+    // (but they list more of the objects to refuse, if present):
+    let inventory = match collection.inventory()? {
+        Some(inventory) => Some(inventory),
+        None => collection.inventory_index()?,
+    };
+
+    Ok(match inventory {
+        Some(inventory) => verdict(collection, &inventory),
+        None => Verdict::Unknown(asked_at),
+    })
+}
+
+/// Fails if this node refuses the content of a collection, looking only at what is present
+/// locally. Use this when the network must not be queried, e.g., when answering peers.
+pub fn check_local(collection: &CollectionRef) -> Result<(), crate::Error> {
+    if !is_filtering() {
+        return Ok(());
+    }
+
+    match local_verdict(collection)? {
+        Verdict::Refused(reason) => Err(crate::Error::Refused(reason)),
+        _ => Ok(()),
+    }
+}
+
+/// Fails if this node refuses the content of a collection, looking for the inventory of the
+/// collection in the network if it is not present locally.
+pub async fn check(collection: &CollectionRef) -> Result<(), crate::Error> {
+    if !is_filtering() {
+        return Ok(());
+    }
+
+    let asked_at = match local_verdict(collection)? {
+        Verdict::Accepted => return Ok(()),
+        Verdict::Refused(reason) => return Err(crate::Error::Refused(reason)),
+        Verdict::Unknown(asked_at) => asked_at,
+    };
+
+    if asked_at.is_none_or(|asked_at| asked_at.elapsed() > INVENTORY_RETRY) {
+        let inventory_hash = collection.locator_for("_inventory".into()).hash();
+        hubs().query(inventory_hash, QueryKind::Item).await;
+
+        // The inventory may still not be there; if so, remember not to ask again so soon:
+        if let Some(inventory) = collection.inventory_index()? {
+            return check_inventory(collection, &inventory);
+        }
+
+        remember(collection, Verdict::Unknown(Some(Instant::now())));
+    }
+
+    Ok(())
+}
+
+/// Fails if this node refuses an object asked for by hash, i.e., if the object was listed in
+/// the inventory of a refused collection.
+pub fn check_object(object: &ObjectRef) -> Result<(), crate::Error> {
+    if is_filtering()
+        && REFUSED_OBJECTS
+            .read()
+            .expect("poisoned")
+            .contains(object.hash())
+    {
+        return Err(crate::Error::Refused(format!(
+            "object {} is in a refused collection",
+            object.hash()
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+//! Crash reports. Panics and fatal errors are recorded in the `crashes` folder of the data
+//! directory, which keeps only the most recent reports. They can be read through the
+//! `/_crashes` route, and the node warns about new reports when it starts.
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic;
+use std::path::PathBuf;
+
+use crate::cli;
+
+/// The maximum number of crash reports kept on disk.
+const MAX_CRASH_REPORTS: usize = 32;
+
+/// What made the node crash.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum CrashKind {
+    Panic,
+    Fatal,
+}
+
+/// A record of a crash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub timestamp: DateTime<Utc>,
+    pub kind: CrashKind,
+    pub message: String,
+    /// Where in the code the panic happened.
+    pub location: Option<String>,
+    /// The name of the thread that panicked.
+    pub thread: Option<String>,
+    pub backtrace: String,
+    /// The version of the node that crashed.
+    pub version: String,
+    /// Whether the node already warned about this report on startup.
+    #[serde(default)]
+    pub is_reported: bool,
+}
+
+fn crashes_dir() -> PathBuf {
+    cli().data.join("crashes")
+}
+
+/// Writes a new report to disk, removing the oldest ones if there are too many.
+fn save(report: &CrashReport) -> Result<(), crate::Error> {
+    let dir = crashes_dir();
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join(format!(
+        "{}.json",
+        report.timestamp.format("%Y%m%d%H%M%S%9f")
+    ));
+    fs::write(
+        path,
+        serde_json::to_vec_pretty(report).map_err(|e| e.to_string())?,
+    )?;
+
+    let mut paths = report_paths()?;
+    if paths.len() > MAX_CRASH_REPORTS {
+        for old in paths.drain(..paths.len() - MAX_CRASH_REPORTS) {
+            fs::remove_file(old)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The paths of all reports, oldest first.
+fn report_paths() -> Result<Vec<PathBuf>, crate::Error> {
+    let dir = crashes_dir();
+
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+    // File names are fixed-width timestamps, so they sort chronologically.
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Reads all crash reports, oldest first. Unreadable reports are skipped.
+pub fn crash_reports() -> Result<Vec<CrashReport>, crate::Error> {
+    Ok(report_paths()?
+        .into_iter()
+        .filter_map(|path| {
+            let read = fs::read(&path).ok()?;
+            serde_json::from_slice(&read).ok()
+        })
+        .collect())
+}
+
+/// Removes all crash reports.
+pub fn clear_crash_reports() -> Result<(), crate::Error> {
+    for path in report_paths()? {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+fn record(kind: CrashKind, message: String, location: Option<String>) {
+    let report = CrashReport {
+        timestamp: Utc::now(),
+        kind,
+        message,
+        location,
+        thread: std::thread::current().name().map(str::to_owned),
+        backtrace: Backtrace::force_capture().to_string(),
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        is_reported: false,
+    };
+
+    if let Err(err) = save(&report) {
+        eprintln!("could not save crash report: {err}");
+    }
+}
+
+/// Records an error that made the node stop.
+pub fn record_fatal(error: &crate::Error) {
+    record(CrashKind::Fatal, error.to_string(), None);
+}
+
+/// Records all panics from now on. The panic hook in place beforehand still gets called.
+pub fn install_panic_hook() {
+    let previous = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic payload".to_owned()
+        };
+
+        record(
+            CrashKind::Panic,
+            message,
+            info.location().map(ToString::to_string),
+        );
+
+        previous(info);
+    }));
+}
+
+/// Warns about crashes that happened since the node was last started.
+pub fn warn_about_new_crashes() -> Result<(), crate::Error> {
+    let mut new = 0;
+
+    for path in report_paths()? {
+        let mut report: CrashReport = match fs::read(&path)
+            .ok()
+            .and_then(|read| serde_json::from_slice(&read).ok())
+        {
+            Some(report) => report,
+            None => continue,
+        };
+
+        if !report.is_reported {
+            new += 1;
+            report.is_reported = true;
+            fs::write(
+                &path,
+                serde_json::to_vec_pretty(&report).map_err(|e| e.to_string())?,
+            )?;
+        }
+    }
+
+    if new > 0 {
+        log::warn!(
+            "the node crashed {new} time(s) since it was last started. Crash reports are in {} \
+             and can be seen through the `/_crashes` route",
+            crashes_dir().display()
+        );
+    }
+
+    Ok(())
+}
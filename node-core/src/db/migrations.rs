@@ -0,0 +1,232 @@
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use crate::cli;
+
+use super::Table;
+
+/// The number of backups kept in `backups/`. Older ones are removed.
+const KEPT_BACKUPS: usize = 3;
+/// The prefix of the name of the backups.
+const BACKUP_PREFIX: &str = "before-migrations-";
+
+/// The `&mut DB` guarantees exclusive access to the db, since this type is not clonable.
+///
+/// Before applying any migration, the database is snapshotted (see [`backup`]), unless
+/// `--no-migration-backup` is set or the database was just created.
+pub(super) fn migrate(db: &mut rocksdb::DB) -> Result<(), crate::Error> {
+    let pending = pending(db)?;
+
+    if pending.is_empty() {
+        log::info!("No pending migrations.");
+        return Ok(());
+    }
+
+    // A new database has nothing worth backing up:
+    let is_new = pending.len() == all().len();
+    if !cli().no_migration_backup && !is_new {
+        backup(db)?;
+    }
+
+    for migration in pending {
+        migration.apply(db)?;
+    }
+
+    Ok(())
+}
+
+/// Reports what each pending migration would change, without changing anything. This is
+/// `--migrations-dry-run`.
+pub(super) fn dry_run(db: &rocksdb::DB) -> Result<(), crate::Error> {
+    let pending = pending(db)?;
+
+    if pending.is_empty() {
+        println!("No pending migrations.");
+    }
+
+    for migration in pending {
+        let reversible = if migration.is_reversible() {
+            "reversible"
+        } else {
+            "irreversible"
+        };
+        println!("Migration {migration:?} ({reversible}):");
+
+        for change in migration.plan(db)? {
+            println!("    - {change}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Rolls back a migration and all migrations applied after it, latest first. Nothing is
+/// rolled back if any of them is irreversible. This is `--rollback-migration`.
+pub(super) fn rollback(db: &mut rocksdb::DB, name: &str) -> Result<(), crate::Error> {
+    let all = all();
+    let position = all
+        .iter()
+        .position(|migration| format!("{migration:?}") == name)
+        .ok_or_else(|| format!("no migration named {name:?}"))?;
+
+    let mut to_roll_back = vec![];
+    for migration in all.into_iter().skip(position).rev() {
+        if migration.is_up(db)? {
+            to_roll_back.push(migration);
+        }
+    }
+
+    if let Some(irreversible) = to_roll_back.iter().find(|m| !m.is_reversible()) {
+        return Err(format!("migration {irreversible:?} cannot be rolled back").into());
+    }
+
+    if to_roll_back.is_empty() {
+        log::info!("Nothing to roll back.");
+        return Ok(());
+    }
+
+    if !cli().no_migration_backup {
+        backup(db)?;
+    }
+
+    for migration in to_roll_back {
+        migration.revert(db)?;
+    }
+
+    Ok(())
+}
+
+/// Snapshots the database into `backups/` in the data folder. Snapshots are RocksDB
+/// checkpoints, which hard-link the files of the database whenever possible, so they are
+/// cheap to make. To restore one, stop the node and put it in the place of `db/`.
+///
+/// Only the latest [`KEPT_BACKUPS`] backups are kept, since each keeps alive files that the
+/// database itself has long compacted away.
+fn backup(db: &rocksdb::DB) -> Result<PathBuf, crate::Error> {
+    let backups = cli().data.join("backups");
+    std::fs::create_dir_all(&backups)?;
+
+    let path = backups.join(format!(
+        "{BACKUP_PREFIX}{}",
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+    ));
+    rocksdb::checkpoint::Checkpoint::new(db)?.create_checkpoint(&path)?;
+    log::info!("Database backed up to {path:?}");
+
+    // Names sort as the timestamps in them:
+    let mut existing = std::fs::read_dir(&backups)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    existing.retain(|backup| {
+        backup
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(BACKUP_PREFIX))
+    });
+    existing.sort();
+
+    for old in &existing[..existing.len().saturating_sub(KEPT_BACKUPS)] {
+        log::info!("Removing old backup {old:?}");
+        std::fs::remove_dir_all(old)?;
+    }
+
+    Ok(path)
+}
+
+/// All migrations, in the order in which they are applied.
+fn all() -> Vec<Box<dyn Migration>> {
+    let mut all: Vec<Box<dyn Migration>> = vec![Box::new(BaseMigration)];
+
+    while let Some(next) = all.last().expect("never empty").next() {
+        all.push(next);
+    }
+
+    all
+}
+
+/// The migrations not yet applied, in the order in which they are to be applied.
+fn pending(db: &rocksdb::DB) -> Result<Vec<Box<dyn Migration>>, crate::Error> {
+    let mut pending = vec![];
+
+    for migration in all() {
+        if !migration.is_up(db)? {
+            pending.push(migration);
+        }
+    }
+
+    Ok(pending)
+}
+
+trait Migration: Debug {
+    fn next(&self) -> Option<Box<dyn Migration>>;
+    fn up(&self, db: &mut rocksdb::DB) -> Result<(), crate::Error>;
+
+    /// Describes what `up` will change in the database as it is now, for dry runs.
+    fn plan(&self, _db: &rocksdb::DB) -> Result<Vec<String>, crate::Error> {
+        Ok(vec![])
+    }
+
+    /// Whether `down` undoes this migration.
+    fn is_reversible(&self) -> bool {
+        false
+    }
+
+    /// Undoes `up`, for reversible migrations.
+    fn down(&self, _db: &mut rocksdb::DB) -> Result<(), crate::Error> {
+        Err(format!("migration {self:?} cannot be rolled back").into())
+    }
+
+    fn is_up(&self, db: &rocksdb::DB) -> Result<bool, crate::Error> {
+        let migration_key = format!("{self:?}");
+        let value = db.get_cf(Table::Migrations.get(), migration_key.as_bytes())?;
+        Ok(value.is_some())
+    }
+
+    fn apply(&self, db: &mut rocksdb::DB) -> Result<(), crate::Error> {
+        let migration_key = format!("{self:?}");
+
+        // This should be atomic, but... oh! dear...
+        log::info!("Applying migration {self:?}...");
+        self.up(db)?;
+        db.put_cf(Table::Migrations.get(), migration_key.as_bytes(), [])?;
+        log::info!("... done.");
+
+        Ok(())
+    }
+
+    fn revert(&self, db: &mut rocksdb::DB) -> Result<(), crate::Error> {
+        let migration_key = format!("{self:?}");
+
+        log::info!("Rolling back migration {self:?}...");
+        self.down(db)?;
+        db.delete_cf(Table::Migrations.get(), migration_key.as_bytes())?;
+        log::info!("... done.");
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct BaseMigration;
+
+impl Migration for BaseMigration {
+    fn next(&self) -> Option<Box<dyn Migration>> {
+        None
+    }
+
+    fn up(&self, _db: &mut rocksdb::DB) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    fn plan(&self, _db: &rocksdb::DB) -> Result<Vec<String>, crate::Error> {
+        Ok(vec!["marks the database as initialized".to_owned()])
+    }
+
+    fn is_reversible(&self) -> bool {
+        true
+    }
+
+    fn down(&self, _db: &mut rocksdb::DB) -> Result<(), crate::Error> {
+        Ok(())
+    }
+}
@@ -1,10 +1,15 @@
 //! Application-specific management of the RocksDB database.
 
 mod migrations;
+mod page;
+
+pub use page::{Page, PageQuery};
 
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 use std::fmt::Display;
+use std::path::Path;
+use std::time::Duration;
 use strum::IntoEnumIterator;
 use strum_macros::{EnumIter, IntoStaticStr};
 
@@ -18,8 +23,20 @@ pub fn db<'a>() -> &'a rocksdb::DB {
     unsafe { DB.as_ref().expect("db not initialized") }
 }
 
+/// The time between attempts of a replica to catch up with its primary.
+const CATCH_UP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Whether this node is a read-only replica of another node. See `--replica-of`.
+pub fn is_replica() -> bool {
+    cli().replica_of.is_some()
+}
+
 /// Initializes the RocksDB for use by the Samizdat node.
 pub fn init_db() -> Result<(), crate::Error> {
+    if let Some(primary) = &cli().replica_of {
+        return init_replica_db(primary);
+    }
+
     log::info!("Starting RocksDB");
 
     let db_path = format!("{}/db", cli().data.to_str().expect("path is not a string"));
@@ -55,14 +72,80 @@ pub fn init_db() -> Result<(), crate::Error> {
         DB = Some(db);
 
         // Run possible migrations (needs DB set, but still requires exclusive access):
-        log::info!("RocksDB up. Running migrations...");
-        migrations::migrate(DB.as_mut().expect("option was just set"))?;
-        log::info!("... done running all migrations.");
+        let db = (*std::ptr::addr_of_mut!(DB))
+            .as_mut()
+            .expect("option was just set");
+        if cli().migrations_dry_run {
+            migrations::dry_run(db)?;
+        } else if let Some(name) = &cli().rollback_migration {
+            migrations::rollback(db, name)?;
+        } else {
+            log::info!("RocksDB up. Running migrations...");
+            migrations::migrate(db)?;
+            log::info!("... done running all migrations.");
+        }
     }
 
     Ok(())
 }
 
+/// Opens the database of the primary node as a secondary instance, which reads the files of
+/// the primary and must catch up with its writes periodically (see [`run_catch_up_daemon`]).
+fn init_replica_db(primary: &Path) -> Result<(), crate::Error> {
+    log::info!("Starting RocksDB as a replica of {primary:?}");
+
+    let primary_path = primary.join("db");
+    let secondary_path = cli().data.join("db-secondary");
+
+    // Migrations are up to the primary, so it must run the same version as this node:
+    let existing_cf_names = rocksdb::DB::list_cf(&rocksdb::Options::default(), &primary_path)?
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    if let Some(missing) = Table::names().find(|name| !existing_cf_names.contains(name)) {
+        return Err(format!(
+            "table {missing} not found in the database of the primary. Is the primary running \
+            the same version as this node?"
+        )
+        .into());
+    }
+
+    // Secondary instances must keep all files open:
+    let mut db_opts = rocksdb::Options::default();
+    db_opts.set_max_open_files(-1);
+
+    let db = rocksdb::DB::open_cf_descriptors_as_secondary(
+        &db_opts,
+        &primary_path,
+        &secondary_path,
+        existing_cf_names.into_iter().map(Table::descriptor_by_name),
+    )?;
+
+    // SAFETY: as in `init_db`.
+    unsafe {
+        DB = Some(db);
+    }
+
+    Ok(())
+}
+
+/// Keeps a replica up to date with the writes of the primary. This runs forever and does
+/// nothing if this node is not a replica.
+pub async fn run_catch_up_daemon() {
+    if !is_replica() {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(CATCH_UP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = db().try_catch_up_with_primary() {
+            log::warn!("failed to catch up with primary: {err}");
+        }
+    }
+}
+
 /// All column families in the RocksDB database.
 #[derive(Debug, Clone, Copy, EnumIter, IntoStaticStr)]
 #[non_exhaustive]
@@ -77,10 +160,20 @@ pub enum Table {
     ObjectMetadata,
     /// The table of all chunks, indexed by chunk hash.
     ObjectChunks,
+    /// Chunks that failed an integrity check, indexed by chunk hash.
+    QuarantinedChunks,
+    /// Bookmarked objects purged because of a corrupted chunk, to be fetched again from the
+    /// network, indexed by object hash.
+    PendingRefetches,
     /// Statistics on object usage.
     ObjectStatistics,
+    /// The times after which objects are to be deleted, set at upload, indexed by object hash.
+    ObjectExpiries,
     /// List of dependencies on objects, which prevent automatic deletion.
     Bookmarks,
+    /// The objects pinned by each edition of the mirrored series, indexed by collection hash
+    /// and object hash (see [`crate::models::BookmarkType::Mirror`]).
+    MirrorPins,
     /// The list of all collection items, indexed by item hash.
     CollectionItems,
     /// The lit of all collection item hashes, indexed by locator.
@@ -102,8 +195,34 @@ pub enum Table {
     RecentNonces,
     /// Access rights granted for each entity to the local Samizdat node.
     AccessRights,
+    /// Webhooks registered to receive events from this node, indexed by a random id.
+    Webhooks,
     /// General key-value store for application (because `LocalStorage` is broken in Samizdat).
     KVStore,
+    /// Series listing hubs to connect to, indexed by public key.
+    HubDirectories,
+    /// Number of times each object was served to peers, indexed by object hash.
+    ServedCounts,
+    /// Readership reports from mirrors, indexed by series public key and reporter.
+    ReadershipReports,
+    /// Series replying to items, indexed by reply topic and series public key.
+    Replies,
+    /// Hubs through which series are exclusively routed, indexed by series public key.
+    HubRoutes,
+    /// Time-locked editions waiting for release by this node, indexed by sealed collection.
+    TimeLocks,
+    /// The collections unsealed by key collections, indexed by key collection.
+    Unsealed,
+    /// Bytes sent and received through connections, indexed by day, hub or peer and address of
+    /// the hub (peers are accounted for all together).
+    ConnectionUsage,
+    /// The keys of hubs, pinned on first use, indexed by hub name.
+    HubKeys,
+    /// The trust levels set by the user for series, indexed by series public key.
+    SeriesTrust,
+    /// Collections being built a batch of items at a time, indexed by builder id (and then by
+    /// item path, for the items).
+    CollectionBuilders,
 }
 
 impl Display for Table {
@@ -128,6 +247,7 @@ impl Table {
     fn merge_operator(self) -> Option<MergeFunction> {
         match self {
             Table::Bookmarks => Some(MergeOperation::full_merge),
+            Table::ServedCounts => Some(crate::readership::merge_counts),
             _ => None,
         }
     }
@@ -144,6 +264,15 @@ impl Table {
         rocksdb::ColumnFamilyDescriptor::new(name, column_opts)
     }
 
+    /// Descriptor for a column family found in a database, which may be of a table of an
+    /// older or newer version of the node.
+    pub(crate) fn descriptor_by_name(name: String) -> rocksdb::ColumnFamilyDescriptor {
+        match Table::iter().find(|table| table.to_string() == name) {
+            Some(table) => table.descriptor(),
+            None => rocksdb::ColumnFamilyDescriptor::new(name, rocksdb::Options::default()),
+        }
+    }
+
     /// Gets the underlying column family after database initialization.
     pub fn get<'a>(self) -> &'a rocksdb::ColumnFamily {
         let db = db();
@@ -236,7 +365,7 @@ mod tests {
 
     #[test]
     fn test_merge() {
-        let _ = crate::logger::init_logger(true);
+        let _ = samizdat_common::logger::init_logger(true);
 
         crate::cli::init_cli().unwrap();
         init_db().unwrap();
@@ -0,0 +1,94 @@
+//! Cursor-based pagination over the tables of the database. Records are listed in the order of
+//! their keys (either way) and a page ends with a cursor, the last key listed, from where the
+//! next page starts. Cursors stay good as records are added or removed, unlike offsets.
+
+use rocksdb::{Direction, IteratorMode};
+use serde_derive::Deserialize;
+
+use super::{db, Table};
+
+/// The most records in a single page.
+const MAX_LIMIT: usize = 1_000;
+
+/// The order in which records are listed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// In the order of the keys.
+    #[default]
+    Asc,
+    /// In the reverse order of the keys.
+    Desc,
+}
+
+/// Which page of records to list.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PageQuery {
+    /// Where the previous page ended, as given by [`Page::next`].
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// The maximum number of records in the page (up to 1000). Without it, all records
+    /// are listed, as in one big page.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// The order of the records.
+    #[serde(default)]
+    pub order: Order,
+}
+
+/// A page of records.
+#[derive(Debug)]
+pub struct Page<T> {
+    /// The records in this page.
+    pub items: Vec<T>,
+    /// The cursor of the next page, if there may be one.
+    pub next: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Lists a page of the records in a table, decoding each record from its key and value.
+    /// Records for which `decode` returns `None` are filtered out and do not count towards the
+    /// limit.
+    pub fn list<F>(table: Table, query: &PageQuery, mut decode: F) -> Result<Page<T>, crate::Error>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<Option<T>, crate::Error>,
+    {
+        let cursor = query
+            .cursor
+            .as_deref()
+            .map(base64_url::decode)
+            .transpose()?;
+        let direction = match query.order {
+            Order::Asc => Direction::Forward,
+            Order::Desc => Direction::Reverse,
+        };
+        let mode = match (&cursor, query.order) {
+            (Some(cursor), _) => IteratorMode::From(cursor, direction),
+            (None, Order::Asc) => IteratorMode::Start,
+            (None, Order::Desc) => IteratorMode::End,
+        };
+        let limit = query.limit.map(|limit| limit.clamp(1, MAX_LIMIT));
+
+        let mut items = vec![];
+
+        for (key, value) in db().iterator_cf(table.get(), mode) {
+            // The cursor itself was in the previous page:
+            if cursor.as_deref() == Some(&*key) {
+                continue;
+            }
+
+            if let Some(item) = decode(&key, &value)? {
+                items.push(item);
+
+                if Some(items.len()) == limit {
+                    return Ok(Page {
+                        items,
+                        next: Some(base64_url::encode(&key)),
+                    });
+                }
+            }
+        }
+
+        Ok(Page { items, next: None })
+    }
+}
@@ -0,0 +1,163 @@
+//! A bus of events happening inside the node, which can be observed by other parts of the system.
+//! Events are, e.g., delivered to user-registered webhooks (see [`crate::models::Webhook`]).
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use strum_macros::IntoStaticStr;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::models::WebhookRef;
+use crate::vacuum::VacuumStatus;
+
+/// How many events can be buffered for each slow receiver before it starts losing events.
+const EVENT_BUFFER_SIZE: usize = 1_024;
+/// How many [`Event::ImportProgress`] can be buffered for each slow receiver. Losing progress
+/// is harmless, since the next one supersedes it.
+const PROGRESS_BUFFER_SIZE: usize = 64;
+
+lazy_static::lazy_static! {
+    /// The sending end of the event bus.
+    static ref EVENTS: broadcast::Sender<Event> = broadcast::channel(EVENT_BUFFER_SIZE).0;
+    /// The sending end of the bus of [`Event::ImportProgress`], kept apart so that many
+    /// concurrent imports cannot make the listeners of [`EVENTS`] lose other events.
+    static ref PROGRESS: broadcast::Sender<Event> = broadcast::channel(PROGRESS_BUFFER_SIZE).0;
+}
+
+/// Something noteworthy that happened inside the node.
+#[derive(Debug, Clone, Serialize, Deserialize, IntoStaticStr)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum Event {
+    /// A new valid edition of a subscribed series was announced to this node.
+    EditionReceived {
+        /// The public key of the series.
+        series: String,
+        /// The hash of the collection of the new edition.
+        collection: String,
+        /// The timestamp of the new edition.
+        timestamp: DateTime<Utc>,
+    },
+    /// An object (or collection item) was downloaded from the network.
+    ObjectDownloaded {
+        /// The hash of the downloaded object.
+        object: String,
+    },
+    /// An object is being downloaded from the network. Emitted about once a second while the
+    /// download lasts. Only delivered to webhooks that ask for it explicitly and only seen by
+    /// listeners of [`subscribe_progress`].
+    ImportProgress {
+        /// The hash of the object being downloaded.
+        object: String,
+        /// How many bytes were received so far.
+        received: usize,
+        /// How many bytes the object has.
+        total: usize,
+        /// The average download speed so far.
+        bytes_per_sec: f64,
+        /// An estimate of how long until the download is over, in seconds.
+        eta_secs: Option<f64>,
+    },
+    /// The connection to a hub was lost. The node will try to reconnect.
+    HubDisconnected {
+        /// The name of the hub, as supplied in the command line.
+        hub: String,
+        /// The address of the hub.
+        addr: SocketAddr,
+    },
+    /// A hub has been down for longer than `--hub-down-alert-after`. Sent once per outage.
+    HubDown {
+        /// The name of the hub, as supplied in the command line.
+        hub: String,
+        /// Since when the hub is down.
+        since: DateTime<Utc>,
+        /// What took the hub down.
+        reason: String,
+    },
+    /// A hub for which [`Event::HubDown`] was sent is up again.
+    HubRecovered {
+        /// The name of the hub, as supplied in the command line.
+        hub: String,
+        /// Since when the hub was down.
+        down_since: DateTime<Utc>,
+    },
+    /// A vacuum round ended, having removed objects from the database.
+    VacuumCompleted {
+        /// The outcome of the vacuum round.
+        status: VacuumStatus,
+    },
+}
+
+impl Event {
+    /// A kebab-case name for the kind of this event.
+    pub fn kind(&self) -> &'static str {
+        self.into()
+    }
+}
+
+/// Emits an event to all current listeners. This is a no-op if nobody is listening.
+pub fn emit(event: Event) {
+    log::debug!("emitting event {:?}", event);
+
+    if let Event::ImportProgress { .. } = event {
+        PROGRESS.send(event).ok();
+    } else {
+        EVENTS.send(event).ok();
+    }
+}
+
+/// Starts listening to all events emitted from now on, except for [`Event::ImportProgress`].
+pub fn subscribe() -> broadcast::Receiver<Event> {
+    EVENTS.subscribe()
+}
+
+/// Starts listening to the [`Event::ImportProgress`] emitted from now on.
+pub fn subscribe_progress() -> broadcast::Receiver<Event> {
+    PROGRESS.subscribe()
+}
+
+/// Delivers all events emitted in the node to the interested webhooks, forever.
+pub async fn run_webhook_daemon() {
+    let mut events = subscribe();
+    let mut progress = subscribe_progress();
+
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => event,
+                Err(RecvError::Lagged(lost)) => {
+                    log::warn!("webhook daemon is lagging behind: lost {lost} events");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            event = progress.recv() => match event {
+                Ok(event) => event,
+                Err(RecvError::Lagged(lost)) => {
+                    log::debug!("webhook daemon lost {lost} progress events");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        let webhooks = match WebhookRef::get_all() {
+            Ok(webhooks) => webhooks,
+            Err(err) => {
+                log::error!("could not load webhooks: {err}");
+                continue;
+            }
+        };
+
+        for (webhook_ref, webhook) in webhooks {
+            if webhook.accepts(&event) {
+                let event = event.clone();
+                crate::tasks::spawn(
+                    format!("deliver {} event to {webhook_ref}", event.kind()),
+                    async move { webhook.deliver(&event).await },
+                );
+            }
+        }
+    }
+}
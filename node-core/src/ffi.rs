@@ -0,0 +1,110 @@
+//! A C API for embedding the node in applications written in other languages, e.g., mobile
+//! wrappers in Kotlin or Swift. The node runs in its own thread, with its own runtime, and is
+//! accessed through the HTTP API, as usual. A node can only be started once per process.
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use samizdat_common::logger;
+
+use crate::{cli, init_cli_from, lifecycle};
+
+/// Returned when the node was started.
+pub const SAMIZDAT_OK: c_int = 0;
+/// Returned when the arguments passed to the node are invalid.
+pub const SAMIZDAT_INVALID_ARGUMENTS: c_int = -1;
+/// Returned when the node was already started in this process.
+pub const SAMIZDAT_ALREADY_STARTED: c_int = -2;
+/// Returned when the node could not be started for other reasons.
+pub const SAMIZDAT_FAILED: c_int = -3;
+
+/// Whether the node was already started in this process. Reset if starting fails before the
+/// node thread is up, so that it can be tried again.
+static STARTED: AtomicBool = AtomicBool::new(false);
+/// Whether the node is still running.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Starts the node in a background thread. The arguments are the same as those of the
+/// `samizdat-node` executable, starting with the program name. Returns [`SAMIZDAT_OK`] on
+/// success or a negative error code.
+///
+/// # Safety
+///
+/// `argv` must point to `argc` valid nul-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn samizdat_node_start(argc: c_int, argv: *const *const c_char) -> c_int {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return SAMIZDAT_ALREADY_STARTED;
+    }
+
+    let args = (0..argc.max(0) as usize)
+        .map(|i| CStr::from_ptr(*argv.add(i)).to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    if let Err(err) = init_cli_from(args) {
+        eprintln!("invalid arguments for samizdat node: {err}");
+        STARTED.store(false, Ordering::SeqCst);
+        return SAMIZDAT_INVALID_ARGUMENTS;
+    }
+
+    if let Err(err) = logger::init_logger_with(cli().logger_config()) {
+        eprintln!("could not start logger: {err}");
+    }
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            log::error!("could not start runtime: {err}");
+            STARTED.store(false, Ordering::SeqCst);
+            return SAMIZDAT_FAILED;
+        }
+    };
+
+    RUNNING.store(true, Ordering::SeqCst);
+
+    let spawned = std::thread::Builder::new()
+        .name("samizdat-node".to_owned())
+        .spawn(move || {
+            if let Err(err) = runtime.block_on(crate::run()) {
+                log::error!("samizdat node exited with error: {err}");
+            }
+
+            RUNNING.store(false, Ordering::SeqCst);
+        });
+
+    if let Err(err) = spawned {
+        log::error!("could not spawn node thread: {err}");
+        RUNNING.store(false, Ordering::SeqCst);
+        STARTED.store(false, Ordering::SeqCst);
+        return SAMIZDAT_FAILED;
+    }
+
+    SAMIZDAT_OK
+}
+
+/// Whether the node is running.
+#[no_mangle]
+pub extern "C" fn samizdat_node_is_running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+/// Suspends all background work in the node, e.g., when the application goes to the
+/// background. See [`lifecycle::pause`].
+#[no_mangle]
+pub extern "C" fn samizdat_node_pause() {
+    lifecycle::pause();
+}
+
+/// Resumes all background work in the node. See [`lifecycle::resume`].
+#[no_mangle]
+pub extern "C" fn samizdat_node_resume() {
+    lifecycle::resume();
+}
+
+/// Tells the node whether the device is on a metered network. See
+/// [`lifecycle::set_metered`].
+#[no_mangle]
+pub extern "C" fn samizdat_node_set_metered(is_metered: bool) {
+    lifecycle::set_metered(is_metered);
+}
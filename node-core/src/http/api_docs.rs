@@ -0,0 +1,194 @@
+//! The description of the HTTP API of the node (see [`samizdat_common::api_docs`]).
+
+use warp::Filter;
+
+use samizdat_common::api_docs::{self, Endpoint};
+
+/// Shorthand for an [`Endpoint`].
+const fn endpoint(
+    method: &'static str,
+    path: &'static str,
+    rights: Option<&'static [&'static str]>,
+    summary: &'static str,
+) -> Endpoint {
+    Endpoint {
+        method,
+        path,
+        summary,
+        rights,
+    }
+}
+
+/// Public routes.
+const PUBLIC: Option<&[&str]> = None;
+/// Routes for the access token (or, for some, trusted contexts) only.
+const TOKEN: Option<&[&str]> = Some(&[]);
+
+/// All the routes of the node.
+#[rustfmt::skip]
+const ENDPOINTS: &[Endpoint] = &[
+    // Content:
+    endpoint("get", "/_objects/{hash}", PUBLIC, "Gets the content of an object."),
+    endpoint("get", "/_collections/{hash}/{path}", PUBLIC, "Gets the content of a collection item."),
+    endpoint("get", "/_collections/{hash}/proof/{path}", PUBLIC, "Gets the proof that an item is in a collection."),
+    endpoint("get", "/_series/{key}/{path}", PUBLIC, "Gets the content of an item in the latest edition of a series that has it."),
+    endpoint("get", "/_series/{key}/_editions", PUBLIC, "Lists the public editions of a series known to this node, latest first."),
+    endpoint("get", "/_series/{key}/_live", PUBLIC, "Opens a WebSocket telling when a new edition of a series arrives."),
+    endpoint("get", "/_series/{key}/_offline/sw.js", PUBLIC, "Gets a service worker keeping the latest edition of a series available offline in the browser."),
+    endpoint("get", "/_series/{key}/_offline/manifest.webmanifest", PUBLIC, "Gets the web app manifest of a series."),
+    endpoint("get", "/{identity}/{path}", PUBLIC, "Gets the content of an item of the series of an identity."),
+    endpoint("get", "/_replies/{hash}", PUBLIC, "Lists the replies to an item known to this node, latest first."),
+    endpoint("get", "/_signingkey", PUBLIC, "Gets the public key with which this node signs its responses."),
+    endpoint("get", "/healthz", PUBLIC, "Tells whether the node is alive."),
+    endpoint("get", "/readyz", PUBLIC, "Tells whether the node is ready to serve content."),
+    // Objects:
+    endpoint("get", "/_objects", Some(&["ManageObjects"]), "Lists the objects in this node, paginated."),
+    endpoint("get", "/_objects/{hash}/torrent", Some(&["ManageObjects"]), "Gets a `.torrent` file for an object in this node."),
+    endpoint("post", "/_objects", Some(&["ManageObjects"]), "Uploads a new object, optionally deleted after a `ttl` (e.g., `1h`)."),
+    endpoint("post", "/_objects/fetch", Some(&["ManageObjects"]), "Downloads a URL and stores it as a new object."),
+    endpoint("delete", "/_objects/{hash}", Some(&["ManageObjects"]), "Deletes an object from this node (not from the network)."),
+    endpoint("post", "/_objects/batch-delete", Some(&["ManageObjects"]), "Deletes a list of objects from this node atomically."),
+    endpoint("post", "/_objects/{hash}/reissue", Some(&["ManageObjects"]), "Reissues an object, with a new header."),
+    endpoint("get", "/_objects/{hash}/bookmark", Some(&["ManageBookmarks"]), "Tells whether an object is bookmarked."),
+    endpoint("post", "/_objects/{hash}/bookmark", Some(&["ManageBookmarks"]), "Bookmarks an object, so that it is never vacuumed."),
+    endpoint("delete", "/_objects/{hash}/bookmark", Some(&["ManageBookmarks"]), "Removes the bookmark from an object."),
+    endpoint("post", "/_bookmarks/batch", Some(&["ManageBookmarks"]), "Bookmarks and removes bookmarks from a list of objects atomically."),
+    endpoint("get", "/_objects/{hash}/reference-count", Some(&["GetObjectStats"]), "Gets the internal reference count of an object."),
+    endpoint("get", "/_objects/{hash}/stats", Some(&["GetObjectStats"]), "Gets the usage statistics of an object."),
+    endpoint("get", "/_objects/{hash}/stats/byte-usefulness", Some(&["GetObjectStats"]), "Gets how useful each byte of an object is to keep."),
+    endpoint("get", "/_objects/{hash}/expiry", Some(&["GetObjectStats"]), "Gets when an object uploaded with a `ttl` is deleted."),
+    endpoint("get", "/_objects/{hash}/availability", Some(&["GetObjectStats"]), "Estimates how many peers in the network have an object."),
+    // Collections:
+    endpoint("post", "/_collections", Some(&["ManageCollections"]), "Builds a new collection from objects."),
+    endpoint("post", "/_collections/builder", Some(&["ManageCollections"]), "Starts building a collection a batch of items at a time. Builders expire after a day."),
+    endpoint("get", "/_collections/builder/{id}", Some(&["ManageCollections"]), "Shows how many items a collection builder has."),
+    endpoint("post", "/_collections/builder/{id}/items", Some(&["ManageCollections"]), "Adds objects or the items of a collection to a collection builder, streaming progress with `?progress=true`."),
+    endpoint("post", "/_collections/builder/{id}/finalize", Some(&["ManageCollections"]), "Builds the collection from a collection builder."),
+    endpoint("delete", "/_collections/builder/{id}", Some(&["ManageCollections"]), "Discards a collection builder."),
+    endpoint("get", "/_collections/{hash}/diff/{other}", Some(&["ManageCollections"]), "Shows which items changed from one collection to another."),
+    // Series:
+    endpoint("get", "/_series", Some(&["ManageSeries"]), "Lists all series known to this node, paginated."),
+    endpoint("get", "/_seriesowners", Some(&["ManageSeries"]), "Lists the series owners, paginated."),
+    endpoint("post", "/_seriesowners", Some(&["ManageSeries"]), "Creates a new series owner."),
+    endpoint("get", "/_seriesowners/{name}", Some(&["ManageSeries"]), "Gets a series owner."),
+    endpoint("delete", "/_seriesowners/{name}", Some(&["ManageSeries"]), "Removes a series owner."),
+    endpoint("post", "/_seriesowners/{name}/editions", Some(&["ManageSeries"]), "Publishes a collection as a new edition of a series."),
+    endpoint("post", "/_seriesowners/{name}/attestations", Some(&["ManageSeries"]), "Signs an attestation of how an edition was built, to be embedded in its collection."),
+    endpoint("get", "/_seriesowners/{name}/readership", Some(&["ManageSeries"]), "Gets how many times each item of each edition was served."),
+    endpoint("get", "/_editions", Some(&["ManageSeries"]), "Lists the editions known to this node."),
+    endpoint("get", "/_seriestrust", Some(&["ManageSeries"]), "Lists the trust levels set for series."),
+    endpoint("get", "/_seriestrust/{key}", Some(&["ManageSeries"]), "Gets the trust level of a series."),
+    endpoint("put", "/_seriestrust/{key}", TOKEN, "Marks a series as verified, trusted on first use or unverified."),
+    endpoint("delete", "/_seriestrust/{key}", TOKEN, "Forgets the trust level set for a series."),
+    endpoint("post", "/_replies/{hash}", Some(&["ManageSeries"]), "Registers a locally owned series as a reply to an item."),
+    endpoint("post", "/_replies/{hash}/fetch", Some(&["ManageSeries"]), "Finds the replies to an item in the network and lists the ones known, latest first."),
+    endpoint("delete", "/_replies/{hash}/{key}", Some(&["ManageSeries"]), "Forgets a reply to an item."),
+    // Identities:
+    endpoint("get", "/_identities", Some(&["ManageIdentities"]), "Lists the identities known to this node."),
+    endpoint("post", "/_identities", Some(&["ManageIdentities"]), "Registers an identity for a series."),
+    // Subscriptions:
+    endpoint("get", "/_subscriptions", Some(&["ManageSubscriptions"]), "Lists the subscriptions, paginated."),
+    endpoint("post", "/_subscriptions", Some(&["ManageSubscriptions"]), "Subscribes to a series."),
+    endpoint("get", "/_subscriptions/{key}", Some(&["ManageSubscriptions"]), "Gets a subscription."),
+    endpoint("delete", "/_subscriptions/{key}", Some(&["ManageSubscriptions"]), "Removes a subscription."),
+    endpoint("get", "/_subscriptions/{key}/mirror", Some(&["ManageSubscriptions"]), "Shows how much of each edition of a series is present locally."),
+    // Hubs:
+    endpoint("get", "/_connections", Some(&["GetConnectionStatus"]), "Gets the status of the connections to the hubs and to the peers."),
+    endpoint("get", "/_connections/usage", Some(&["GetConnectionStatus"]), "Gets the bandwidth used with each hub and with all peers per day."),
+    endpoint("get", "/_hubs/{name}/history", Some(&["GetConnectionStatus"]), "Gets the changes in the state of a hub, with why they happened."),
+    endpoint("get", "/_hubs/keys", Some(&["GetConnectionStatus"]), "Lists the keys pinned for all hubs ever connected to."),
+    endpoint("get", "/_hubs/{name}/key", Some(&["GetConnectionStatus"]), "Gets the key pinned for a hub and the new key it presented, if it changed."),
+    endpoint("post", "/_hubs/{name}/key/accept", TOKEN, "Accepts the new key a hub presented, connecting to it again."),
+    endpoint("delete", "/_hubs/{name}/key", TOKEN, "Forgets the key pinned for a hub, so that the next one it presents is pinned."),
+    endpoint("get", "/_queries", Some(&["GetConnectionStatus"]), "Lists the queries to the network in flight, with their download progress."),
+    endpoint("get", "/_queries/{hash}", Some(&["GetConnectionStatus"]), "Lists the queries in flight for some content."),
+    endpoint("get", "/_peers/connectivity", Some(&["GetConnectionStatus"]), "Gets the status of the port mappings in the local router."),
+    endpoint("get", "/_hubdirectories", Some(&["GetConnectionStatus"]), "Lists the hub directories."),
+    endpoint("post", "/_hubdirectories", TOKEN, "Subscribes to a hub directory."),
+    endpoint("get", "/_hubdirectories/{key}", Some(&["GetConnectionStatus"]), "Gets a hub directory, with the hubs it lists."),
+    endpoint("patch", "/_hubdirectories/{key}", TOKEN, "Confirms (or revokes) a hub directory as a trust root."),
+    endpoint("delete", "/_hubdirectories/{key}", TOKEN, "Unsubscribes from a hub directory."),
+    endpoint("get", "/_hubroutes", Some(&["GetConnectionStatus"]), "Lists the hub routes."),
+    endpoint("put", "/_hubroutes/{key}", TOKEN, "Routes a series exclusively through some hubs."),
+    endpoint("delete", "/_hubroutes/{key}", TOKEN, "Lets a series go through all hubs again."),
+    // Webhooks:
+    endpoint("get", "/_webhooks", Some(&["ManageWebhooks"]), "Lists the webhooks."),
+    endpoint("post", "/_webhooks", Some(&["ManageWebhooks"]), "Registers a new webhook."),
+    endpoint("get", "/_webhooks/{hash}", Some(&["ManageWebhooks"]), "Gets a webhook."),
+    endpoint("delete", "/_webhooks/{hash}", Some(&["ManageWebhooks"]), "Removes a webhook."),
+    // Key-value store:
+    endpoint("get", "/_kvstore", TOKEN, "Lists the keys of the calling application."),
+    endpoint("delete", "/_kvstore", TOKEN, "Removes all entries of the calling application."),
+    endpoint("get", "/_kvstore/_events", TOKEN, "Streams the changes to the entries of the calling application as server-sent events."),
+    endpoint("get", "/_kvstore/{path}", TOKEN, "Gets an entry of the calling application."),
+    endpoint("put", "/_kvstore/{path}", TOKEN, "Sets an entry of the calling application."),
+    endpoint("delete", "/_kvstore/{path}", TOKEN, "Removes an entry of the calling application."),
+    endpoint("post", "/_kvstore/batch", TOKEN, "Applies a batch of operations atomically."),
+    endpoint("get", "/_kvstores", TOKEN, "Lists the applications using the key-value store."),
+    endpoint("delete", "/_kvstores/{type}/{identifier}", TOKEN, "Removes all entries of an application."),
+    // Access rights:
+    endpoint("get", "/_auth", TOKEN, "Lists the access rights granted to applications."),
+    endpoint("get", "/_auth/_current", PUBLIC, "Gets the access rights of the calling application."),
+    endpoint("get", "/_auth/{path}", TOKEN, "Gets the access rights granted to an application."),
+    endpoint("patch", "/_auth/{path}", TOKEN, "Grants access rights to an application."),
+    endpoint("delete", "/_auth/{path}", TOKEN, "Revokes the access rights of an application."),
+    endpoint("get", "/_register", PUBLIC, "Shows the page where the user grants access rights to an application."),
+    // Synchronization between nodes:
+    endpoint("get", "/_sync/summary", TOKEN, "Gets the summary of the content of this node."),
+    endpoint("post", "/_sync/buckets", TOKEN, "Lists the entries of this node in the requested buckets."),
+    endpoint("get", "/_sync/objects/{hash}", TOKEN, "Gets the raw content of an object, header included."),
+    endpoint("get", "/_sync/items/{hash}", TOKEN, "Gets a collection item, by the hash of its locator."),
+    endpoint("post", "/_sync", TOKEN, "Copies everything a peer node has that this node has not."),
+    // Maintenance:
+    endpoint("post", "/_vacuum", PUBLIC, "Triggers a vacuum round."),
+    endpoint("get", "/_vacuum/policy", Some(&["GetObjectStats"]), "Gets how the vacuum chooses what to delete."),
+    endpoint("put", "/_vacuum/policy", Some(&["ManageObjects"]), "Changes how the vacuum treats referenced content and stale drafts."),
+    endpoint("post", "/_wipe", TOKEN, "Wipes the node."),
+    endpoint("get", "/_chunk-cache", Some(&["GetObjectStats"]), "Gets how the in-memory cache of chunks is being used, with its hit rate."),
+    endpoint("get", "/_scrub/status", Some(&["GetObjectStats"]), "Gets the progress and the findings of the integrity scrubber."),
+    endpoint("get", "/_tasks", Some(&["ManageLogging"]), "Lists the background tasks running and the ones that failed lately."),
+    endpoint("get", "/_log-level", Some(&["ManageLogging"]), "Gets the current log levels."),
+    endpoint("put", "/_log-level", Some(&["ManageLogging"]), "Changes the log level of a module while the node is running."),
+    endpoint("get", "/_quiet", Some(&["ManageLifecycle"]), "Gets whether the node is quiet (not seeding, deferring refreshes and announcements) and why."),
+    endpoint("put", "/_quiet", Some(&["ManageLifecycle"]), "Turns quiet mode on or off, or back to automatic, overriding quiet hours and detection."),
+    endpoint("get", "/_crashes", Some(&["ManageLogging"]), "Gets the reports of the last crashes of the node."),
+    endpoint("delete", "/_crashes", Some(&["ManageLogging"]), "Removes all crash reports."),
+];
+
+/// Serves the OpenAPI document of the node and the page showing it.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    api_docs::serve("Samizdat Node", env!("CARGO_PKG_VERSION"), ENDPOINTS)
+}
+
+#[test]
+fn test_all_routes_listed() {
+    let sources = [
+        include_str!("auth.rs"),
+        include_str!("collections.rs"),
+        include_str!("compression.rs"),
+        include_str!("editions.rs"),
+        include_str!("hub_directories.rs"),
+        include_str!("hub_routes.rs"),
+        include_str!("identities.rs"),
+        include_str!("kvstore.rs"),
+        include_str!("mod.rs"),
+        include_str!("objects.rs"),
+        include_str!("offline_bundle.rs"),
+        include_str!("redirects.rs"),
+        include_str!("replies.rs"),
+        include_str!("resolvers.rs"),
+        include_str!("series.rs"),
+        include_str!("series_trust.rs"),
+        include_str!("signing.rs"),
+        include_str!("subdomains.rs"),
+        include_str!("subscriptions.rs"),
+        include_str!("sync.rs"),
+        include_str!("version.rs"),
+        include_str!("webhooks.rs"),
+    ];
+
+    assert_eq!(
+        api_docs::unlisted_routes(&sources, ENDPOINTS),
+        Vec::<String>::new()
+    );
+}
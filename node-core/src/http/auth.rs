@@ -6,10 +6,10 @@ use url::{Host, Url};
 use warp::Filter;
 
 use crate::access::{access_token, AccessRight, Entity};
-use crate::balanced_or_tree;
 use crate::db::{db, Table};
+use crate::{balanced_or_tree, cli};
 
-use super::{api_reply, html};
+use super::{api_reply, html, subdomains};
 
 /// The authentication management API.
 pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
@@ -192,6 +192,7 @@ fn check_origin(referrer: &Url) -> Result<(), Forbidden> {
     match dbg!(&origin) {
         url::Origin::Tuple(http, host, _) if http == "http" || http == "https" => match host {
             Host::Domain(domain) if domain == "localhost" => return Ok(()),
+            Host::Domain(domain) if subdomains::entity_for_host(domain).is_some() => return Ok(()),
             Host::Ipv4(ip) if ip.is_loopback() => return Ok(()),
             Host::Ipv6(ip) if ip.to_canonical().is_loopback() => return Ok(()),
             _ => {}
@@ -211,7 +212,10 @@ fn is_trusted_context(referrer: &Url) -> bool {
 fn entity_from_referrer(referrer: &Url) -> Result<Option<Entity>, Forbidden> {
     check_origin(&referrer)?;
 
-    if is_trusted_context(&referrer) {
+    if let Some(entity) = referrer.host_str().and_then(subdomains::entity_for_host) {
+        // Entities on their own subdomains are known by the host alone.
+        Ok(Some(entity))
+    } else if is_trusted_context(referrer) {
         Ok(None)
     } else {
         // Find which entity is requesting authorization.
@@ -221,7 +225,8 @@ fn entity_from_referrer(referrer: &Url) -> Result<Option<Entity>, Forbidden> {
     }
 }
 
-/// Extracts the "security scope" (akin to "origin" in the normal Web) from the Referer header.
+/// Extracts the "security scope" (akin to "origin" in the normal Web) from the Referer header:
+/// the entity of the subdomain of the referrer or else the entity at the start of its path.
 pub fn security_scope() -> impl Filter<Extract = (Entity,), Error = warp::Rejection> + Clone {
     warp::header::optional("Referer").and_then(|maybe_referrer: Option<Url>| async move {
         let referrer =
@@ -239,16 +244,24 @@ fn authenticate_authorization(
 ) -> impl Filter<Extract = (Option<Forbidden>,), Error = warp::Rejection> + Clone {
     warp::header("Authorization")
         .or_else(|_| async { Err(warp::reject::custom(Unauthorized::Unauthorized)) })
-        .map(|authorization: String| {
+        .and_then(|authorization: String| async move {
             let token = authorization
                 .trim_start_matches("Bearer ")
                 .trim_start_matches("bearer ");
 
-            if token == access_token() {
+            let forbidden = if token == access_token() {
+                None
+            } else if cli().duress_token.as_deref() == Some(token) {
+                // Behave as the access token would, but only once there is nothing to be seen:
+                tokio::task::spawn_blocking(crate::wipe::wipe_under_duress)
+                    .await
+                    .ok();
                 None
             } else {
                 Some(Forbidden::BadToken(token.to_owned()))
-            }
+            };
+
+            Ok(forbidden) as Result<_, warp::Rejection>
         })
 }
 
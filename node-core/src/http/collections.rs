@@ -0,0 +1,485 @@
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use warp::path::Tail;
+use warp::{Filter, Reply};
+
+use samizdat_common::rpc::QueryKind;
+use samizdat_common::{Hash, PatriciaProof};
+
+use crate::access::AccessRight;
+use crate::balanced_or_tree;
+use crate::hubs;
+use crate::models::{
+    AddedItems, CollectionBuilder, CollectionRef, Droppable, Inventory, ItemMetadata, ItemPath,
+    ItemPathBuf, Mount, ObjectRef,
+};
+
+use super::resolvers::{resolve_item, Conditions};
+use super::{api_reply, authenticate, conditions, riddles, tuple};
+
+/// The entrypoint of the collection public API.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        post_builder(),
+        get_builder(),
+        post_builder_items(),
+        post_builder_finalize(),
+        delete_builder(),
+        get_diff(),
+        get_proof(),
+        get_item(),
+        post_collection()
+    )
+}
+
+/// A collection mounted under a path prefix, as given in requests.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MountRequest {
+    Collection(String),
+    Series(String),
+}
+
+impl MountRequest {
+    fn parse(self) -> Result<Mount, crate::Error> {
+        Ok(match self {
+            MountRequest::Collection(hash) => Mount::Collection(hash.parse()?),
+            MountRequest::Series(key) => Mount::Series(key.parse()?),
+        })
+    }
+}
+
+/// Everything in a new collection, besides its items.
+#[derive(Deserialize)]
+struct CollectionOptions {
+    #[serde(default)]
+    is_draft: bool,
+    #[serde(default)]
+    metadata: BTreeMap<String, ItemMetadata>,
+    #[serde(default)]
+    content_warnings: BTreeSet<String>,
+    /// Shard the inventory in shards of at most this many items.
+    #[serde(default)]
+    inventory_shard_size: Option<usize>,
+    /// Items for subscribers to fetch first, in order.
+    #[serde(default)]
+    prefetch: Vec<String>,
+    /// Other collections to mount, by path prefix.
+    #[serde(default)]
+    mounts: BTreeMap<String, MountRequest>,
+}
+
+impl CollectionOptions {
+    fn build(self, objects: Vec<(ItemPathBuf, ObjectRef)>) -> Result<CollectionRef, crate::Error> {
+        CollectionRef::build_with(
+            self.is_draft,
+            objects,
+            self.metadata
+                .into_iter()
+                .map(|(name, metadata)| (ItemPathBuf::from(name), metadata))
+                .collect(),
+            self.content_warnings,
+            self.inventory_shard_size,
+            self.prefetch.into_iter().map(ItemPathBuf::from).collect(),
+            self.mounts
+                .into_iter()
+                .map(|(prefix, mount)| Ok((ItemPathBuf::from(prefix), mount.parse()?)))
+                .collect::<Result<_, crate::Error>>()?,
+        )
+    }
+}
+
+/// Uploads a new collection.
+pub fn post_collection(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        #[serde(default)]
+        is_draft: bool,
+        hashes: Vec<(String, String)>,
+        #[serde(default)]
+        metadata: BTreeMap<String, ItemMetadata>,
+        #[serde(default)]
+        content_warnings: BTreeSet<String>,
+        /// Shard the inventory in shards of at most this many items.
+        #[serde(default)]
+        inventory_shard_size: Option<usize>,
+        /// Items for subscribers to fetch first, in order.
+        #[serde(default)]
+        prefetch: Vec<String>,
+        /// Other collections to mount, by path prefix.
+        #[serde(default)]
+        mounts: BTreeMap<String, MountRequest>,
+    }
+
+    warp::path!("_collections")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageCollections]))
+        .and(warp::body::json())
+        .map(|request: Request| {
+            let objects = request
+                .hashes
+                .into_iter()
+                .map(|(name, hash)| Ok((ItemPathBuf::from(name), ObjectRef::new(hash.parse()?))))
+                .collect::<Result<Vec<_>, crate::Error>>()?;
+            let collection = CollectionOptions {
+                is_draft: request.is_draft,
+                metadata: request.metadata,
+                content_warnings: request.content_warnings,
+                inventory_shard_size: request.inventory_shard_size,
+                prefetch: request.prefetch,
+                mounts: request.mounts,
+            }
+            .build(objects)?;
+            Ok(collection.hash().to_string())
+        })
+        .map(api_reply)
+}
+
+/// Gets a collection builder, failing if it does not exist.
+fn builder(id: Hash) -> Result<CollectionBuilder, crate::Error> {
+    CollectionBuilder::get(id)?
+        .ok_or_else(|| crate::Error::NotFound(format!("collection builder {id}")))
+}
+
+/// Starts building a collection from objects already in this node, a batch of items at a time.
+pub fn post_builder() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("_collections" / "builder")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageCollections]))
+        .map(|| Ok(CollectionBuilder::create()?.id.to_string()))
+        .map(api_reply)
+}
+
+/// Shows how many items a collection builder has so far.
+pub fn get_builder() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    #[derive(Serialize)]
+    struct Response {
+        id: String,
+        created_at: DateTime<Utc>,
+        total: usize,
+    }
+
+    warp::path!("_collections" / "builder" / Hash)
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageCollections]))
+        .map(|id: Hash| {
+            let builder = builder(id)?;
+            Ok(Response {
+                id: builder.id.to_string(),
+                created_at: builder.created_at,
+                total: builder.total,
+            })
+        })
+        .map(api_reply)
+}
+
+/// A batch of items to add to a collection builder.
+#[derive(Deserialize)]
+struct BuilderItems {
+    /// Where to put the items in the collection being built.
+    #[serde(default)]
+    prefix: String,
+    #[serde(default)]
+    hashes: Vec<(String, String)>,
+    /// A collection whose items are to be added, if any.
+    #[serde(default)]
+    collection: Option<String>,
+}
+
+/// Adds a batch of items to a collection builder, reporting progress as in
+/// [`CollectionBuilder::add`].
+fn add_items(
+    id: Hash,
+    items: BuilderItems,
+    progress: impl FnMut(usize),
+) -> Result<AddedItems, crate::Error> {
+    let builder = builder(id)?;
+    let prefix = ItemPathBuf::from(items.prefix);
+
+    if let Some(collection) = items.collection {
+        return builder.add_collection(&CollectionRef::new(collection.parse()?), &prefix, progress);
+    }
+
+    let hashes = items
+        .hashes
+        .into_iter()
+        .map(|(name, hash)| Ok((ItemPathBuf::from(name), hash.parse()?)))
+        .collect::<Result<Vec<_>, crate::Error>>()?;
+
+    builder.add(&prefix, hashes, progress)
+}
+
+/// Adds a batch of items to a collection builder: either named objects or all the items of
+/// an existing collection, under a path prefix. Items whose objects are not in this node are
+/// reported back and left out. With `?progress=true`, the response is a stream of JSON lines
+/// with the number of items checked so far, ending with the outcome, as a JSON result. The
+/// status of such responses is always OK, since it is sent before the outcome is known.
+pub fn post_builder_items(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Query {
+        #[serde(default)]
+        progress: bool,
+    }
+
+    #[derive(Serialize)]
+    struct Progress {
+        checked: usize,
+    }
+
+    warp::path!("_collections" / "builder" / Hash / "items")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageCollections]))
+        .and(warp::query())
+        .and(warp::body::json())
+        .map(|id: Hash, query: Query, items: BuilderItems| {
+            if !query.progress {
+                return api_reply(add_items(id, items, |_| {})).into_response();
+            }
+
+            let (send, receive) = tokio::sync::mpsc::unbounded_channel();
+            tokio::task::spawn_blocking(move || {
+                let outcome = add_items(id, items, |checked| {
+                    let progress = Progress { checked };
+                    send.send(serde_json::to_string(&progress).expect("can serialize JSON"))
+                        .ok();
+                });
+                let outcome = outcome.map_err(|err| err.to_string());
+                send.send(serde_json::to_string(&outcome).expect("can serialize JSON"))
+                    .ok();
+            });
+
+            let lines =
+                UnboundedReceiverStream::new(receive).map(|line| Ok::<_, Infallible>(line + "\n"));
+
+            warp::reply::with_header(
+                warp::reply::Response::new(hyper::Body::wrap_stream(lines)),
+                http::header::CONTENT_TYPE,
+                "application/x-ndjson",
+            )
+            .into_response()
+        })
+}
+
+/// Builds the collection from the items added to a collection builder, which is then
+/// discarded. This fails if objects were removed from the node since they were added.
+pub fn post_builder_finalize(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_collections" / "builder" / Hash / "finalize")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageCollections]))
+        .and(warp::body::json())
+        .map(|id: Hash, options: CollectionOptions| {
+            let builder = builder(id)?;
+            let objects = builder.items()?;
+
+            for (path, object) in &objects {
+                if object.metadata()?.is_none() {
+                    return Err(crate::Error::NotFound(format!(
+                        "object {} of item {path}",
+                        object.hash()
+                    )));
+                }
+            }
+
+            let collection = options.build(objects)?;
+            builder.drop_if_exists()?;
+
+            Ok(collection.hash().to_string())
+        })
+        .map(api_reply)
+}
+
+/// Discards a collection builder.
+pub fn delete_builder(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_collections" / "builder" / Hash)
+        .and(warp::delete())
+        .and(authenticate([AccessRight::ManageCollections]))
+        .map(|id: Hash| builder(id)?.drop_if_exists())
+        .map(api_reply)
+}
+
+/// Gets the inventory of a collection, looking for it in the network if it is not present
+/// locally.
+pub(super) async fn get_inventory(collection: &CollectionRef) -> Result<Inventory, crate::Error> {
+    if let Some(inventory) = collection.inventory()? {
+        return Ok(inventory);
+    }
+
+    if collection.inventory_index()?.is_none() {
+        let inventory_hash = collection.locator_for("_inventory".into()).hash();
+        hubs().query(inventory_hash, QueryKind::Item).await;
+    }
+
+    // Sharded inventories need their shards too:
+    if let Some(index) = collection.inventory_index()? {
+        let queries = (0..index.shards().len())
+            .map(|index| {
+                (
+                    collection.inventory_shard_locator(index).hash(),
+                    QueryKind::Item,
+                )
+            })
+            .collect::<Vec<_>>();
+        hubs().query_many(&queries).await;
+    }
+
+    collection.inventory()?.ok_or_else(|| {
+        crate::Error::NotFound(format!("inventory for collection {}", collection.hash()))
+    })
+}
+
+/// Shows which items were added, removed or changed from one collection to another, using the
+/// collections' inventories.
+pub fn get_diff() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Serialize)]
+    struct Entry {
+        path: String,
+        hash: String,
+        size: Option<usize>,
+    }
+
+    #[derive(Serialize)]
+    struct ChangedEntry {
+        path: String,
+        old_hash: String,
+        old_size: Option<usize>,
+        new_hash: String,
+        new_size: Option<usize>,
+    }
+
+    #[derive(Serialize)]
+    struct Response {
+        added: Vec<Entry>,
+        removed: Vec<Entry>,
+        changed: Vec<ChangedEntry>,
+    }
+
+    /// The size of an object, if it is present locally.
+    fn size_of(hash: Hash) -> Result<Option<usize>, crate::Error> {
+        Ok(ObjectRef::new(hash)
+            .metadata()?
+            .map(|metadata| metadata.content_size))
+    }
+
+    fn entry((path, hash): (ItemPathBuf, Hash)) -> Result<Entry, crate::Error> {
+        Ok(Entry {
+            path: path.to_string(),
+            hash: hash.to_string(),
+            size: size_of(hash)?,
+        })
+    }
+
+    async fn diff(old: Hash, new: Hash) -> Result<Response, crate::Error> {
+        let old = get_inventory(&CollectionRef::new(old)).await?;
+        let new = get_inventory(&CollectionRef::new(new)).await?;
+        let diff = old.diff(&new);
+
+        Ok(Response {
+            added: diff
+                .added
+                .into_iter()
+                .map(entry)
+                .collect::<Result<_, _>>()?,
+            removed: diff
+                .removed
+                .into_iter()
+                .map(entry)
+                .collect::<Result<_, _>>()?,
+            changed: diff
+                .changed
+                .into_iter()
+                .map(|(path, old_hash, new_hash)| {
+                    Ok(ChangedEntry {
+                        path: path.to_string(),
+                        old_hash: old_hash.to_string(),
+                        old_size: size_of(old_hash)?,
+                        new_hash: new_hash.to_string(),
+                        new_size: size_of(new_hash)?,
+                    })
+                })
+                .collect::<Result<_, crate::Error>>()?,
+        })
+    }
+
+    warp::path!("_collections" / Hash / "diff" / Hash)
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageCollections]))
+        .and_then(|old: Hash, new: Hash| async move {
+            Ok(diff(old, new).await) as Result<_, warp::Rejection>
+        })
+        .map(api_reply)
+}
+
+/// Gets the proof that an item is in a collection, so that anyone can check it against the
+/// hash of the collection alone. The proof is looked for in the network if not found locally.
+/// Note that this hides items under `proof/` from the route above.
+pub fn get_proof() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Serialize)]
+    struct Response {
+        collection: String,
+        path: String,
+        object: String,
+        proof: PatriciaProof,
+    }
+
+    async fn proof(
+        collection: CollectionRef,
+        name: ItemPath<'_>,
+    ) -> Result<Response, crate::Error> {
+        let mut proof = collection.proof_for(name.clone())?;
+
+        if proof.is_none() {
+            let locator_hash = collection.locator_for(name.clone()).hash();
+            hubs().query(locator_hash, QueryKind::Item).await;
+            proof = collection.proof_for(name.clone())?;
+        }
+
+        let proof = proof.ok_or_else(|| {
+            crate::Error::NotFound(format!("item {name} in collection {}", collection.hash()))
+        })?;
+
+        Ok(Response {
+            collection: collection.hash().to_string(),
+            path: name.to_string(),
+            object: proof.claimed_value().to_string(),
+            proof,
+        })
+    }
+
+    warp::path!("_collections" / Hash / "proof" / ..)
+        .and(warp::path::tail())
+        .and(warp::get())
+        .and_then(|hash: Hash, name: Tail| async move {
+            Ok(proof(CollectionRef::new(hash), name.as_str().into()).await)
+                as Result<_, warp::Rejection>
+        })
+        .map(api_reply)
+}
+
+/// Gets the contents of a collection item.
+pub fn get_item() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_collections" / Hash / ..)
+        .and(warp::path::tail())
+        .and(warp::get())
+        .and(riddles())
+        .and(conditions())
+        .and_then(
+            |hash: Hash, name: Tail, riddles: Option<usize>, conditions: Conditions| async move {
+                let collection = CollectionRef::new(hash);
+                let path = name.as_str().into();
+                let locator = collection.locator_for(path);
+                Ok(resolve_item(locator, riddles, &conditions, []).await?)
+                    as Result<_, warp::Rejection>
+            },
+        )
+        .map(tuple)
+}
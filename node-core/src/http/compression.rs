@@ -0,0 +1,204 @@
+//! Compression of the responses of the HTTP API, negotiated with the client through the
+//! `Accept-Encoding` header. Content is stored uncompressed, so it is compressed on the fly as
+//! the body is streamed.
+
+use brotli::CompressorWriter;
+use flate2::write::GzEncoder;
+use futures::prelude::*;
+use futures::stream;
+use http::header::{self, HeaderValue};
+use hyper::{Body, Response};
+use std::io::{self, Write};
+
+/// The brotli quality used for responses. Compression happens as the content is served, so
+/// this trades some ratio for speed.
+const BROTLI_QUALITY: u32 = 5;
+/// The brotli window size, in bits.
+const BROTLI_WINDOW: u32 = 22;
+/// The size of the internal buffer of the brotli compressor.
+const BROTLI_BUFFER: usize = 4096;
+
+/// A content encoding supported by the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// Chooses the encoding the client likes best, given its `Accept-Encoding` header. Brotli
+    /// wins ties, since it compresses better.
+    pub fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+        accept_encoding
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                let encoding = match parts.next()? {
+                    "br" => Encoding::Brotli,
+                    "gzip" | "x-gzip" => Encoding::Gzip,
+                    _ => return None,
+                };
+                let quality = parts
+                    .find_map(|param| param.strip_prefix("q="))
+                    .map(|quality| quality.parse::<f64>().unwrap_or(0.0))
+                    .unwrap_or(1.0);
+
+                (quality > 0.0).then_some((encoding, quality))
+            })
+            .max_by(|(this, this_q), (other, other_q)| {
+                this_q
+                    .partial_cmp(other_q)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| (*this == Encoding::Brotli).cmp(&(*other == Encoding::Brotli)))
+            })
+            .map(|(encoding, _)| encoding)
+    }
+}
+
+/// Whether content of a given type is worth compressing. Most media types are compressed
+/// already.
+fn is_compressible(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/wasm"
+                | "image/svg+xml"
+        )
+}
+
+/// A compressor writing into memory, from which the compressed bytes are taken as they come.
+enum Encoder {
+    Brotli(Box<CompressorWriter<Vec<u8>>>),
+    Gzip(GzEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(encoding: Encoding) -> Encoder {
+        match encoding {
+            Encoding::Brotli => Encoder::Brotli(Box::new(CompressorWriter::new(
+                Vec::new(),
+                BROTLI_BUFFER,
+                BROTLI_QUALITY,
+                BROTLI_WINDOW,
+            ))),
+            Encoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), Default::default())),
+        }
+    }
+
+    /// Compresses a chunk, returning whatever compressed output is ready.
+    fn write(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Brotli(writer) => {
+                writer.write_all(chunk)?;
+                Ok(std::mem::take(writer.get_mut()))
+            }
+            Encoder::Gzip(writer) => {
+                writer.write_all(chunk)?;
+                Ok(std::mem::take(writer.get_mut()))
+            }
+        }
+    }
+
+    /// Ends the compressed stream, returning the remaining output.
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Brotli(writer) => Ok(writer.into_inner()),
+            Encoder::Gzip(writer) => writer.finish(),
+        }
+    }
+}
+
+/// Compresses the body of a response with the given encoding, if it is worth it.
+pub fn compress(encoding: Option<Encoding>, response: Response<Body>) -> Response<Body> {
+    let encoding = match encoding {
+        Some(encoding) => encoding,
+        None => return response,
+    };
+
+    // Byte ranges are of the object, not of the encoded body:
+    let is_eligible = response.status() != http::StatusCode::NOT_MODIFIED
+        && response.status() != http::StatusCode::PARTIAL_CONTENT
+        && !response.headers().contains_key(header::CONTENT_RANGE)
+        && !response.headers().contains_key(header::CONTENT_ENCODING)
+        && response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(is_compressible);
+
+    if !is_eligible {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.name()),
+    );
+    parts.headers.append(
+        header::VARY,
+        HeaderValue::from_static(header::ACCEPT_ENCODING.as_str()),
+    );
+
+    // The encoded body is not the same bytes as the object, so its entity tag can only be weak.
+    // Weak tags still match in `If-None-Match`, but not in `If-Range`:
+    if let Some(etag) = parts.headers.get(header::ETAG) {
+        if !etag.as_bytes().starts_with(b"W/") {
+            let mut weak = b"W/".to_vec();
+            weak.extend_from_slice(etag.as_bytes());
+            let weak = HeaderValue::from_bytes(&weak).expect("prefix keeps the value valid");
+            parts.headers.insert(header::ETAG, weak);
+        }
+    }
+
+    let compressed = stream::unfold(
+        (body, Some(Encoder::new(encoding))),
+        |(mut body, encoder)| async move {
+            let mut encoder = encoder?;
+            let output = match body.next().await {
+                Some(Ok(chunk)) => encoder.write(&chunk),
+                Some(Err(err)) => return Some((Err(io::Error::other(err)), (body, None))),
+                None => return Some((encoder.finish(), (body, None))),
+            };
+
+            Some((output, (body, Some(encoder))))
+        },
+    )
+    .try_filter(|output| future::ready(!output.is_empty()));
+
+    Response::from_parts(parts, Body::wrap_stream(compressed))
+}
+
+#[test]
+fn negotiates_encodings() {
+    assert_eq!(
+        Encoding::negotiate("gzip, deflate, br"),
+        Some(Encoding::Brotli)
+    );
+    assert_eq!(
+        Encoding::negotiate("gzip;q=1.0, br;q=0.5"),
+        Some(Encoding::Gzip)
+    );
+    assert_eq!(Encoding::negotiate("br;q=0, gzip;q=0"), None);
+    assert_eq!(Encoding::negotiate("identity"), None);
+}
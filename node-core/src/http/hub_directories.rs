@@ -0,0 +1,126 @@
+use serde_derive::Deserialize;
+use warp::Filter;
+
+use samizdat_common::Key;
+
+use crate::access::AccessRight;
+use crate::balanced_or_tree;
+use crate::hub_directory;
+use crate::models::{Droppable, HubDirectory, HubDirectoryRef};
+
+use super::{api_reply, authenticate};
+
+/// The entrypoint of the hub directories API. Since directories decide which hubs this node
+/// talks to, only the access token can change them.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        get_hub_directory(),
+        get_hub_directories(),
+        post_hub_directory(),
+        patch_hub_directory(),
+        delete_hub_directory(),
+    )
+}
+
+/// Refreshes the directories and the hubs in the background.
+fn spawn_refresh() {
+    crate::tasks::spawn("refresh hub directories", async {
+        hub_directory::refresh_all().await;
+        Ok(())
+    });
+}
+
+/// Subscribes to a hub directory. New directories are not trusted: their hubs are only
+/// connected to once the user confirms the directory as a trust root.
+fn post_hub_directory(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        public_key: String,
+    }
+
+    warp::path!("_hubdirectories")
+        .and(warp::post())
+        .and(authenticate([]))
+        .and(warp::body::json())
+        .map(|request: Request| {
+            let directory_ref = HubDirectoryRef::new(request.public_key.parse()?);
+
+            if directory_ref.get()?.is_none() {
+                HubDirectoryRef::build(HubDirectory::new(directory_ref.public_key.clone()))?;
+                spawn_refresh();
+            }
+
+            Ok(directory_ref.public_key.to_string())
+        })
+        .map(api_reply)
+}
+
+/// Confirms (or revokes) a hub directory as a trust root.
+fn patch_hub_directory(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        is_trusted: bool,
+    }
+
+    warp::path!("_hubdirectories" / Key)
+        .and(warp::patch())
+        .and(authenticate([]))
+        .and(warp::body::json())
+        .map(|public_key: Key, request: Request| {
+            let directory_ref = HubDirectoryRef::new(public_key);
+            let mut directory = directory_ref
+                .get()?
+                .ok_or_else(|| crate::Error::NotFound(directory_ref.to_string()))?;
+
+            if directory.is_trusted != request.is_trusted {
+                directory.is_trusted = request.is_trusted;
+                HubDirectoryRef::build(directory.clone())?;
+                spawn_refresh();
+            }
+
+            Ok(directory)
+        })
+        .map(api_reply)
+}
+
+/// Unsubscribes from a hub directory. Hubs only listed by it are disconnected from.
+fn delete_hub_directory(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_hubdirectories" / Key)
+        .and(warp::delete())
+        .and(authenticate([]))
+        .map(|public_key: Key| {
+            let directory_ref = HubDirectoryRef::new(public_key);
+            let existed = directory_ref.get()?.is_some();
+            directory_ref.drop_if_exists()?;
+
+            if existed {
+                spawn_refresh();
+            }
+
+            Ok(existed)
+        })
+        .map(api_reply)
+}
+
+/// Gets a hub directory, with the hubs it lists.
+fn get_hub_directory() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("_hubdirectories" / Key)
+        .and(warp::get())
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .map(|public_key: Key| HubDirectoryRef::new(public_key).get())
+        .map(api_reply)
+}
+
+/// Lists all hub directories.
+fn get_hub_directories(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_hubdirectories")
+        .and(warp::get())
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .map(HubDirectoryRef::get_all)
+        .map(api_reply)
+}
@@ -0,0 +1,69 @@
+use serde_derive::Deserialize;
+use std::collections::BTreeSet;
+use warp::Filter;
+
+use samizdat_common::Key;
+
+use crate::access::AccessRight;
+use crate::balanced_or_tree;
+use crate::models::{Droppable, HubRoute, HubRouteRef};
+
+use super::{api_reply, authenticate};
+
+/// The entrypoint of the hub routes API. Since routes decide which hubs learn about which
+/// series, only the access token can change them.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(get_hub_routes(), put_hub_route(), delete_hub_route())
+}
+
+/// Routes a series exclusively through some hubs, replacing any existing route.
+fn put_hub_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        hubs: BTreeSet<String>,
+    }
+
+    warp::path!("_hubroutes" / Key)
+        .and(warp::put())
+        .and(authenticate([]))
+        .and(warp::body::json())
+        .map(|public_key: Key, request: Request| {
+            if request.hubs.is_empty() {
+                return Err(crate::Error::ValidationFailed(
+                    "a route needs at least one hub".to_owned(),
+                ));
+            }
+
+            HubRouteRef::build(HubRoute {
+                public_key,
+                hubs: request.hubs,
+            })?;
+
+            Ok(())
+        })
+        .map(api_reply)
+}
+
+/// Lets a series go through all hubs again.
+fn delete_hub_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("_hubroutes" / Key)
+        .and(warp::delete())
+        .and(authenticate([]))
+        .map(|public_key: Key| {
+            let route_ref = HubRouteRef::new(public_key);
+            let existed = route_ref.get()?.is_some();
+            route_ref.drop_if_exists()?;
+            Ok(existed)
+        })
+        .map(api_reply)
+}
+
+/// Lists all hub routes.
+fn get_hub_routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_hubroutes")
+        .and(warp::get())
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .map(HubRouteRef::get_all)
+        .map(api_reply)
+}
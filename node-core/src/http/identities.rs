@@ -9,8 +9,8 @@ use crate::balanced_or_tree;
 use crate::db;
 use crate::models::{Identity, IdentityRef};
 
-use super::resolvers::resolve_identity;
-use super::{api_reply, authenticate, tuple};
+use super::resolvers::{resolve_identity, Conditions};
+use super::{api_reply, authenticate, conditions, riddles, tuple};
 
 /// The entrypoint of the object API.
 pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
@@ -30,10 +30,16 @@ fn get_item() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Reject
     warp::path!(IdentityRef / ..)
         .and(warp::path::tail())
         .and(warp::get())
-        .and_then(|identity, name: Tail| async move {
-            Ok(resolve_identity(identity, name.as_str().into(), []).await?)
-                as Result<_, warp::Rejection>
-        })
+        .and(riddles())
+        .and(conditions())
+        .and_then(
+            |identity, name: Tail, riddles: Option<usize>, conditions: Conditions| async move {
+                Ok(
+                    resolve_identity(identity, name.as_str().into(), riddles, &conditions, [])
+                        .await?,
+                ) as Result<_, warp::Rejection>
+            },
+        )
         .map(tuple)
 }
 
@@ -74,8 +80,8 @@ fn post_identity() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::R
                     Ok(false)
                 }
             } else {
-                Err(crate::Error::Message(format!(
-                    "Invalid identity: {identity:?}"
+                Err(crate::Error::ValidationFailed(format!(
+                    "invalid identity: {identity:?}"
                 )))
             }
         })
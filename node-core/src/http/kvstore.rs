@@ -0,0 +1,456 @@
+//! The key-value store for applications. Each application (the [`Entity`] of the page making
+//! the request) has its own namespace, which no other application can read or write, and a
+//! quota on the total size of its keys and values. Applications may listen to the changes to
+//! their entries, e.g., to keep many tabs in sync.
+
+use futures::stream;
+use rocksdb::WriteBatch;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Mutex;
+use tokio::sync::broadcast::{self, error::RecvError};
+use warp::sse;
+use warp::Filter;
+
+use crate::access::Entity;
+use crate::balanced_or_tree;
+use crate::cli;
+use crate::db::{db, Table};
+
+use super::{api_reply, auth, authenticate};
+
+/// How many changes may be waiting for a listener before it starts missing them.
+const CHANGE_BACKLOG: usize = 256;
+
+lazy_static::lazy_static! {
+    /// Serializes writes, so that quotas and compare-and-swaps are checked against a consistent
+    /// view of the store. Holds the bytes used by each entity (by [`prefix`]), counted from its
+    /// entries once and then kept up to date by every write, so that checking the quota does
+    /// not need to go through all entries.
+    static ref USAGE: Mutex<BTreeMap<Vec<u8>, usize>> = Mutex::default();
+    /// The changes to the store, for the listeners of all applications.
+    static ref CHANGES: broadcast::Sender<Change> = broadcast::channel(CHANGE_BACKLOG).0;
+}
+
+/// A change to an entry, as sent to the listeners of the application.
+#[derive(Debug, Clone, Serialize)]
+struct Change {
+    /// The prefix of the keys of the application (see [`prefix`]).
+    #[serde(skip)]
+    namespace: Vec<u8>,
+    key: String,
+    /// The new value, or `null` if the entry was removed.
+    value: Option<String>,
+}
+
+/// Tells the listeners of an application that an entry changed. This is a no-op if nobody is
+/// listening.
+fn notify(entity: &Entity, key: &str, value: Option<&str>) {
+    CHANGES
+        .send(Change {
+            namespace: prefix(entity),
+            key: key.to_owned(),
+            value: value.map(str::to_owned),
+        })
+        .ok();
+}
+
+/// The key-value store API.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        list(),
+        post_batch(),
+        get_events(), // before `get`, which would take `_events` for a key.
+        get(),
+        put(),
+        delete(),
+        clear(),
+        get_all_namespaces(),
+        delete_namespace(),
+    )
+}
+
+fn key(entity: &Entity, name: &str) -> Vec<u8> {
+    bincode::serialize(&(entity, name)).expect("can serialize")
+}
+
+/// All keys of an entity start with this prefix.
+fn prefix(entity: &Entity) -> Vec<u8> {
+    bincode::serialize(entity).expect("can serialize")
+}
+
+/// The keys of an entity, with the size each entry takes from the quota.
+fn entries(entity: &Entity) -> Result<Vec<(Vec<u8>, String, usize)>, crate::Error> {
+    let prefix = prefix(entity);
+
+    db().prefix_iterator_cf(Table::KVStore.get(), &prefix)
+        .take_while(|(key, _)| key.starts_with(&prefix))
+        .map(|(key, value)| {
+            let (_, name): (Entity, String) = bincode::deserialize(&key)?;
+            let size = name.len() + value.len();
+            Ok((key.into_vec(), name, size))
+        })
+        .collect()
+}
+
+/// The bytes used by an entity, counting its entries only the first time.
+fn used(usage: &mut BTreeMap<Vec<u8>, usize>, entity: &Entity) -> Result<usize, crate::Error> {
+    let prefix = prefix(entity);
+
+    if let Some(&used) = usage.get(&prefix) {
+        return Ok(used);
+    }
+
+    let used = entries(entity)?.iter().map(|(_, _, size)| size).sum();
+    usage.insert(prefix, used);
+
+    Ok(used)
+}
+
+/// The size an entry takes from the quota, or zero if the entry is not set.
+fn size(entity: &Entity, name: &str) -> Result<usize, crate::Error> {
+    Ok(db()
+        .get_pinned_cf(Table::KVStore.get(), key(entity, name))?
+        .map_or(0, |value| name.len() + value.len()))
+}
+
+/// Forgets the bytes used by each entity, which are counted anew on the next write. This is
+/// for when entries are removed by other means, e.g., by a wipe.
+pub(crate) fn forget_usage() {
+    USAGE.lock().expect("poisoned").clear();
+}
+
+/// The quota of each entity, in bytes.
+fn quota() -> usize {
+    cli().kvstore_quota * 1_000
+}
+
+/// The usage of the key-value store by an application.
+#[derive(Serialize)]
+struct Usage {
+    keys: Vec<String>,
+    used: usize,
+    quota: usize,
+}
+
+/// Lists the keys of the calling application.
+pub fn list() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_kvstore")
+        .and(warp::get())
+        .and(auth::security_scope())
+        .map(|entity: Entity| {
+            let entries = entries(&entity)?;
+
+            Ok(Usage {
+                used: entries.iter().map(|(_, _, size)| size).sum(),
+                keys: entries.into_iter().map(|(_, name, _)| name).collect(),
+                quota: quota(),
+            })
+        })
+        .map(api_reply)
+}
+
+pub fn get() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_kvstore" / ..)
+        .and(warp::get())
+        .and(warp::path::tail())
+        .and(auth::security_scope())
+        .map(|tail: warp::path::Tail, entity: Entity| {
+            let maybe_value_encoded =
+                db().get_cf(Table::KVStore.get(), key(&entity, tail.as_str()))?;
+            let maybe_value =
+                maybe_value_encoded.map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+
+            Ok(maybe_value)
+        })
+        .map(api_reply)
+}
+
+/// Streams the changes to the entries of the calling application, as server-sent `change`
+/// events, each with the key and the new value. Listeners falling too far behind get a `lagged`
+/// event instead of the changes they missed, after which they should read the entries anew.
+/// This hides any entry called `_events` from [`get`].
+pub fn get_events() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_kvstore" / "_events")
+        .and(warp::get())
+        .and(auth::security_scope())
+        .map(|entity: Entity| {
+            let namespace = prefix(&entity);
+            let changes = stream::unfold(CHANGES.subscribe(), move |mut changes| {
+                let namespace = namespace.clone();
+                async move {
+                    loop {
+                        let event = match changes.recv().await {
+                            Ok(change) if change.namespace == namespace => sse::Event::default()
+                                .event("change")
+                                .data(serde_json::to_string(&change).expect("can serialize")),
+                            Ok(_) => continue,
+                            Err(RecvError::Lagged(_)) => {
+                                sse::Event::default().event("lagged").data("")
+                            }
+                            Err(RecvError::Closed) => return None,
+                        };
+
+                        return Some((Ok::<_, Infallible>(event), changes));
+                    }
+                }
+            });
+
+            sse::reply(sse::keep_alive().stream(changes))
+        })
+}
+
+pub fn put() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        value: String,
+    }
+
+    warp::path!("_kvstore" / ..)
+        .and(warp::put())
+        .and(warp::path::tail())
+        .and(auth::security_scope())
+        .and(warp::body::content_length_limit(8_192))
+        .and(warp::body::json())
+        .map(|tail: warp::path::Tail, entity: Entity, request: Request| {
+            let mut usage = USAGE.lock().expect("poisoned");
+            let name = tail.as_str();
+
+            // The entry being replaced does not count:
+            let used = used(&mut usage, &entity)?.saturating_sub(size(&entity, name)?);
+            let size = name.len() + request.value.len();
+
+            if used + size > quota() {
+                return Err(crate::Error::QuotaExceeded(format!(
+                    "{entity} uses {used} of {} bytes and cannot store {size} more",
+                    quota()
+                )));
+            }
+
+            db().put_cf(
+                Table::KVStore.get(),
+                key(&entity, name),
+                request.value.as_bytes(),
+            )?;
+            usage.insert(prefix(&entity), used + size);
+            notify(&entity, name, Some(&request.value));
+            Ok(())
+        })
+        .map(api_reply)
+}
+
+pub fn delete() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_kvstore" / ..)
+        .and(warp::delete())
+        .and(warp::path::tail())
+        .and(auth::security_scope())
+        .map(|tail: warp::path::Tail, entity: Entity| {
+            let mut usage = USAGE.lock().expect("poisoned");
+            let name = tail.as_str();
+            let freed = size(&entity, name)?;
+
+            db().delete_cf(Table::KVStore.get(), key(&entity, name))?;
+            if let Some(used) = usage.get_mut(&prefix(&entity)) {
+                *used = used.saturating_sub(freed);
+            }
+            notify(&entity, name, None);
+            Ok(())
+        })
+        .map(api_reply)
+}
+
+/// An operation in a batch.
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Operation {
+    Put {
+        key: String,
+        value: String,
+    },
+    Delete {
+        key: String,
+    },
+    /// Sets the value of the key (or deletes it, if `value` is `null`) only if the current
+    /// value is `expected` (or, if `expected` is `null`, only if the key is not set). Otherwise,
+    /// the whole batch fails.
+    CompareAndSwap {
+        key: String,
+        expected: Option<String>,
+        value: Option<String>,
+    },
+}
+
+/// Applies a batch of operations atomically: either all operations are applied or none is.
+/// Operations see the effects of the operations before them in the batch.
+pub fn post_batch() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        operations: Vec<Operation>,
+    }
+
+    warp::path!("_kvstore" / "batch")
+        .and(warp::post())
+        .and(auth::security_scope())
+        .and(warp::body::content_length_limit(65_536))
+        .and(warp::body::json())
+        .map(|entity: Entity, request: Request| {
+            let mut usage = USAGE.lock().expect("poisoned");
+
+            // The final value of each key touched by the batch:
+            let mut changes = BTreeMap::<String, Option<String>>::new();
+
+            for operation in request.operations {
+                match operation {
+                    Operation::Put { key, value } => {
+                        changes.insert(key, Some(value));
+                    }
+                    Operation::Delete { key } => {
+                        changes.insert(key, None);
+                    }
+                    Operation::CompareAndSwap {
+                        key: name,
+                        expected,
+                        value,
+                    } => {
+                        let current = match changes.get(&name) {
+                            Some(value) => value.clone(),
+                            None => db()
+                                .get_cf(Table::KVStore.get(), key(&entity, &name))?
+                                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+                        };
+
+                        if current != expected {
+                            return Err(crate::Error::ValidationFailed(format!(
+                                "compare-and-swap failed for key {name:?}"
+                            )));
+                        }
+
+                        changes.insert(name, value);
+                    }
+                }
+            }
+
+            // The entries being replaced do not count:
+            let replaced = changes
+                .keys()
+                .map(|name| size(&entity, name))
+                .sum::<Result<usize, _>>()?;
+            let used = used(&mut usage, &entity)?.saturating_sub(replaced);
+            let size = changes
+                .iter()
+                .filter_map(|(name, value)| Some(name.len() + value.as_ref()?.len()))
+                .sum::<usize>();
+
+            if used + size > quota() {
+                return Err(crate::Error::QuotaExceeded(format!(
+                    "{entity} uses {used} of {} bytes and cannot store {size} more",
+                    quota()
+                )));
+            }
+
+            let mut batch = WriteBatch::default();
+
+            for (name, value) in &changes {
+                match value {
+                    Some(value) => batch.put_cf(Table::KVStore.get(), key(&entity, name), value),
+                    None => batch.delete_cf(Table::KVStore.get(), key(&entity, name)),
+                }
+            }
+
+            db().write(batch)?;
+            usage.insert(prefix(&entity), used + size);
+
+            for (name, value) in &changes {
+                notify(&entity, name, value.as_deref());
+            }
+
+            Ok(())
+        })
+        .map(api_reply)
+}
+
+/// Removes all entries of an entity.
+fn clear_entity(entity: &Entity) -> Result<(), crate::Error> {
+    let mut usage = USAGE.lock().expect("poisoned");
+    let mut batch = WriteBatch::default();
+
+    let entries = entries(entity)?;
+
+    for (key, _, _) in &entries {
+        batch.delete_cf(Table::KVStore.get(), key);
+    }
+
+    db().write(batch)?;
+    usage.insert(prefix(entity), 0);
+
+    for (_, name, _) in &entries {
+        notify(entity, name, None);
+    }
+
+    Ok(())
+}
+
+pub fn clear() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_kvstore")
+        .and(warp::delete())
+        .and(auth::security_scope())
+        .map(|entity: Entity| clear_entity(&entity))
+        .map(api_reply)
+}
+
+/// The usage of the key-value store by an application, as seen by the user.
+#[derive(Serialize)]
+struct NamespaceUsage {
+    entity: String,
+    keys: usize,
+    used: usize,
+}
+
+/// Lists the applications using the key-value store. Only the user can see this.
+pub fn get_all_namespaces(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_kvstores")
+        .and(warp::get())
+        .and(authenticate([]))
+        .map(|| {
+            let mut namespaces: Vec<NamespaceUsage> = vec![];
+
+            // Keys of the same entity are contiguous:
+            for (key, value) in db().iterator_cf(Table::KVStore.get(), rocksdb::IteratorMode::Start)
+            {
+                let (entity, name): (Entity, String) = bincode::deserialize(&key)?;
+                let entity = entity.to_string();
+                let size = name.len() + value.len();
+
+                match namespaces.last_mut() {
+                    Some(last) if last.entity == entity => {
+                        last.keys += 1;
+                        last.used += size;
+                    }
+                    _ => namespaces.push(NamespaceUsage {
+                        entity,
+                        keys: 1,
+                        used: size,
+                    }),
+                }
+            }
+
+            Ok(namespaces)
+        })
+        .map(api_reply)
+}
+
+/// Removes all entries of an application. Only the user can do this.
+pub fn delete_namespace(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_kvstores" / String / String)
+        .and(warp::delete())
+        .and(authenticate([]))
+        .map(|r#type: String, identifier: String| {
+            let entity = Entity::from_path(&format!("/{type}/{identifier}"))
+                .ok_or_else(|| format!("bad entity /{type}/{identifier}"))?;
+            clear_entity(&entity)
+        })
+        .map(api_reply)
+}
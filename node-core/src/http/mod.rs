@@ -0,0 +1,666 @@
+//! HTTP API for the Samizdat Node.
+
+mod api_docs;
+mod auth;
+mod collections;
+mod compression;
+mod editions;
+mod hub_directories;
+mod hub_routes;
+mod identities;
+mod kvstore;
+mod objects;
+mod offline_bundle;
+mod redirects;
+mod replies;
+mod resolvers;
+mod series;
+mod series_trust;
+mod signing;
+mod subdomains;
+mod subscriptions;
+mod sync;
+mod version;
+mod webhooks;
+
+pub use auth::authenticate;
+pub(crate) use kvstore::forget_usage;
+
+use futures::{future, Future, TryFutureExt};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn, Service};
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use warp::Filter;
+
+use samizdat_common::logger;
+use samizdat_common::request_id::RequestId;
+use samizdat_common::Hash;
+
+use crate::access::AccessRight;
+use crate::db::{Page, PageQuery};
+use crate::{balanced_or_tree, cli};
+
+fn api_reply<T>(t: Result<T, crate::Error>) -> impl warp::Reply
+where
+    T: serde::Serialize,
+{
+    let status = t
+        .as_ref()
+        .map_err(crate::Error::status_code)
+        .err()
+        .unwrap_or_default();
+    let json = t.map_err(|err| err.to_string());
+    warp::reply::with_header(
+        warp::reply::with_status(
+            serde_json::to_string_pretty(&json).expect("can serialize JSON"),
+            status,
+        ),
+        http::header::CONTENT_TYPE,
+        "application/json",
+    )
+}
+
+/// Like [`api_reply`], for a page of a list. The list is the body, as for unpaginated lists,
+/// and the cursor of the next page, if any, goes in the `X-Samizdat-Next-Cursor` header.
+fn page_reply<T>(page: Result<Page<T>, crate::Error>) -> impl warp::Reply
+where
+    T: serde::Serialize,
+{
+    let next = page.as_ref().ok().and_then(|page| page.next.clone());
+    let mut response = warp::Reply::into_response(api_reply(page.map(|page| page.items)));
+
+    if let Some(next) = next {
+        response.headers_mut().insert(
+            "X-Samizdat-Next-Cursor",
+            http::HeaderValue::from_str(&next).expect("base64url is a valid header value"),
+        );
+    }
+
+    response
+}
+
+/// Which page of a list to show, through the `cursor`, `limit` and `order` (`asc` or `desc`)
+/// query parameters. Lists are not paginated if no `limit` is given.
+fn page_query() -> impl Filter<Extract = (PageQuery,), Error = warp::Rejection> + Clone {
+    warp::query()
+}
+
+/// Utility to create a tuple of one value _very explicitly_.
+fn tuple<T>(t: T) -> (T,) {
+    (t,)
+}
+
+/// The number of riddles requested for the queries made on behalf of a request, through the
+/// `X-Samizdat-Riddles` header. If not set, the default for each kind of query is used. The
+/// header is ignored unless the request has the access token: otherwise, any page could make
+/// the queries of this node travel further in the network than the user wants.
+fn riddles() -> impl Filter<Extract = (Option<usize>,), Error = Infallible> + Clone {
+    warp::header("X-Samizdat-Riddles")
+        .and(authenticate([]))
+        .map(Some)
+        .or(warp::any().map(|| None))
+        .unify()
+}
+
+/// The conditional and range headers of a request for content.
+fn conditions() -> impl Filter<Extract = (resolvers::Conditions,), Error = warp::Rejection> + Clone
+{
+    warp::header::optional("If-None-Match")
+        .and(warp::header::optional("If-Modified-Since"))
+        .and(warp::header::optional("Range"))
+        .and(warp::header::optional("If-Range"))
+        .map(
+            |if_none_match, if_modified_since, range, if_range| resolvers::Conditions {
+                if_none_match,
+                if_modified_since,
+                range,
+                if_range,
+            },
+        )
+}
+
+fn html(rendered: String) -> impl warp::Reply {
+    warp::reply::with_header(
+        warp::reply::with_status(rendered, http::StatusCode::OK),
+        http::header::CONTENT_TYPE,
+        "text/html; charset=UTF-8",
+    )
+}
+
+/// The entrypoint of the Samizdat node public HTTP API. All routes are served either with or
+/// without a version prefix (see [`version`]).
+fn api() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let routes = balanced_or_tree!(
+        kvstore::api(),                // kvstore not subject to redirect rules.
+        redirects::general_redirect(), // redirect rules here...
+        objects::api(),
+        collections::api(),
+        series::api(),
+        series_trust::api(),
+        editions::api(),
+        identities::api(),
+        subscriptions::api(),
+        hub_directories::api(),
+        hub_routes::api(),
+        replies::api(),
+        signing::get_signing_key(),
+        webhooks::api(),
+        sync::api(),
+        auth::api(),
+        post_vacuum(),
+        get_vacuum_policy(),
+        put_vacuum_policy(),
+        post_wipe(),
+        get_scrub_status(),
+        get_chunk_cache(),
+        get_connections(),
+        get_connection_usage(),
+        get_hub_history(),
+        get_hub_keys(),
+        get_hub_key(),
+        post_hub_key_accept(),
+        delete_hub_key(),
+        get_connectivity(),
+        get_queries(),
+        get_queries_for(),
+        get_tasks(),
+        get_log_level(),
+        put_log_level(),
+        get_quiet(),
+        put_quiet(),
+        get_crashes(),
+        delete_crashes(),
+        api_docs::api(),
+    );
+
+    version::api_version()
+        .and(routes)
+        .map(version::with_version)
+        .recover(|rejection: warp::Rejection| async move {
+            if let Some(forbidden) = rejection.find::<auth::Forbidden>() {
+                Ok(warp::reply::with_status(
+                    forbidden.to_string(),
+                    http::StatusCode::FORBIDDEN,
+                ))
+            } else if let Some(unauthorized) = rejection.find::<auth::Unauthorized>() {
+                Ok(warp::reply::with_status(
+                    unauthorized.to_string(),
+                    http::StatusCode::UNAUTHORIZED,
+                ))
+            } else if let Some(unsupported) = rejection.find::<version::UnsupportedVersion>() {
+                Ok(warp::reply::with_status(
+                    unsupported.to_string(),
+                    http::StatusCode::BAD_REQUEST,
+                ))
+            } else if let Some(error) = rejection.find::<crate::Error>() {
+                Ok(warp::reply::with_status(
+                    error.to_string(),
+                    http::StatusCode::BAD_REQUEST,
+                ))
+            } else {
+                Err(rejection)
+            }
+        })
+}
+
+/// The liveness (`/healthz`) and readiness (`/readyz`) checks. The node is ready when the
+/// database is readable and at least one hub answers its health probes, unless the node is
+/// standalone. These need no authentication and tell nothing but `ok` or what is wrong.
+fn health() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let healthz = warp::path!("healthz").map(|| Ok::<_, String>("ok".to_owned()));
+    let readyz = warp::path!("readyz").map(|| {
+        if let Err(err) = crate::db().get(b"") {
+            Err(format!("database not readable: {err}"))
+        } else if !cli().standalone && !crate::hubs().is_any_reachable() {
+            Err("no hub is reachable".to_owned())
+        } else {
+            Ok("ok".to_owned())
+        }
+    });
+
+    warp::get()
+        .and(healthz.or(readyz).unify())
+        .map(|readiness: Result<String, String>| match readiness {
+            Ok(ok) => warp::reply::with_status(ok, http::StatusCode::OK),
+            Err(err) => warp::reply::with_status(err, http::StatusCode::SERVICE_UNAVAILABLE),
+        })
+}
+
+/// Triggers a manual vacuum round.
+fn post_vacuum() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("_vacuum"))
+        .map(|| crate::vacuum::vacuum())
+        .map(api_reply)
+}
+
+/// Gets how the vacuum chooses what to delete.
+fn get_vacuum_policy() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::get()
+        .and(warp::path!("_vacuum" / "policy"))
+        .and(authenticate([AccessRight::GetObjectStats]))
+        .map(crate::vacuum::policy)
+        .map(api_reply)
+}
+
+/// Changes how the vacuum chooses what to delete, from the next vacuum round on.
+fn put_vacuum_policy() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::put()
+        .and(warp::path!("_vacuum" / "policy"))
+        .and(authenticate([AccessRight::ManageObjects]))
+        .and(warp::body::json())
+        .map(crate::vacuum::set_policy)
+        .map(api_reply)
+}
+
+/// Wipes the node and, optionally, all other profiles in this machine. This needs the access
+/// token: no application can ever be granted the right to do this.
+fn post_wipe() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("_wipe"))
+        .and(authenticate([]))
+        .and(warp::body::json())
+        .and_then(|request: crate::wipe::WipeRequest| async move {
+            let wiped = tokio::task::spawn_blocking(move || crate::wipe::wipe(&request))
+                .await
+                .unwrap_or_else(|err| Err(format!("wipe task panicked: {err}").into()));
+
+            Ok(wiped) as Result<_, warp::Rejection>
+        })
+        .map(api_reply)
+}
+
+/// Gets how the in-memory cache of chunks is being used.
+fn get_chunk_cache() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::get()
+        .and(warp::path!("_chunk-cache"))
+        .and(authenticate([AccessRight::GetObjectStats]))
+        .map(|| Ok(crate::chunk_cache::stats()))
+        .map(api_reply)
+}
+
+/// Gets the progress and the findings of the integrity scrubber.
+fn get_scrub_status() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::get()
+        .and(warp::path!("_scrub" / "status"))
+        .and(authenticate([AccessRight::GetObjectStats]))
+        .map(|| Ok(crate::scrub::scrub_status()))
+        .map(api_reply)
+}
+
+/// Gets the status of the connections to the hubs and to the peers.
+fn get_connections() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::get()
+        .and(warp::path!("_connections"))
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .and_then(
+            || async move { Ok(Ok(crate::hubs().status().await)) as Result<_, warp::Rejection> },
+        )
+        .map(api_reply)
+}
+
+/// Gets the bandwidth used with each hub and with all peers per day, for the last `days` days
+/// (30 by default), today included.
+fn get_connection_usage(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Query {
+        #[serde(default)]
+        days: Option<u32>,
+    }
+
+    warp::get()
+        .and(warp::path!("_connections" / "usage"))
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .and(warp::query())
+        .map(|query: Query| {
+            let days = query.days.unwrap_or(30).max(1) as i64;
+            let since = chrono::Utc::now().naive_utc().date() - chrono::Duration::days(days - 1);
+            crate::system::usage::usage(since)
+        })
+        .map(api_reply)
+}
+
+/// Gets the changes in the state of a hub, most recent first, with why they happened.
+fn get_hub_history() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::get()
+        .and(warp::path!("_hubs" / String / "history"))
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .map(|name: String| {
+            crate::hubs()
+                .state_history(&name)
+                .ok_or_else(|| crate::Error::NotFound(format!("hub {name}")))
+        })
+        .map(api_reply)
+}
+
+/// Lists the keys pinned for all hubs ever connected to.
+fn get_hub_keys() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("_hubs" / "keys"))
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .map(crate::models::HubKeyRef::get_all)
+        .map(api_reply)
+}
+
+/// Gets the key pinned for a hub and, if the hub presented another one since, that key.
+fn get_hub_key() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("_hubs" / String / "key"))
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .map(|name: String| crate::models::HubKeyRef::new(name).get())
+        .map(api_reply)
+}
+
+/// Accepts the key a hub presented after its key changed, connecting to the hub if it was
+/// left out because of that. Since keys decide who this node takes for its hubs, only the
+/// access token can accept them.
+fn post_hub_key_accept(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("_hubs" / String / "key" / "accept"))
+        .and(authenticate([]))
+        .and_then(|name: String| async move {
+            let outcome = crate::models::HubKeyRef::new(&name).accept();
+
+            if outcome.is_ok() {
+                crate::hubs().connect_left_out(&name).await;
+            }
+
+            Ok(outcome) as Result<_, warp::Rejection>
+        })
+        .map(api_reply)
+}
+
+/// Forgets the key pinned for a hub, connecting to the hub if it was left out because its key
+/// changed. The next key the hub presents is pinned. Like accepting keys, only the access token
+/// can do this.
+fn delete_hub_key() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::delete()
+        .and(warp::path!("_hubs" / String / "key"))
+        .and(authenticate([]))
+        .and_then(|name: String| async move {
+            let outcome = crate::models::HubKeyRef::new(&name).forget();
+
+            if outcome.is_ok() {
+                crate::hubs().connect_left_out(&name).await;
+            }
+
+            Ok(outcome) as Result<_, warp::Rejection>
+        })
+        .map(api_reply)
+}
+
+/// Gets the status of the port mappings in the local router.
+fn get_connectivity() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::get()
+        .and(warp::path!("_peers" / "connectivity"))
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .map(|| Ok(crate::system::connectivity()))
+        .map(api_reply)
+}
+
+/// Gets the queries this node is making to the network right now, with how far along their
+/// downloads are.
+fn get_queries() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("_queries"))
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .map(|| Ok(crate::system::queries::in_flight(None)))
+        .map(api_reply)
+}
+
+/// Gets the queries this node is making to the network right now for some content, either
+/// the hash queried for or the object being downloaded for it.
+fn get_queries_for() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::get()
+        .and(warp::path!("_queries" / Hash))
+        .and(authenticate([AccessRight::GetConnectionStatus]))
+        .map(|content_hash: Hash| Ok(crate::system::queries::in_flight(Some(content_hash))))
+        .map(api_reply)
+}
+
+/// Gets the background tasks running in the node and the ones that failed lately.
+fn get_tasks() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("_tasks"))
+        .and(authenticate([AccessRight::ManageLogging]))
+        .map(|| Ok(crate::tasks::status()))
+        .map(api_reply)
+}
+
+/// Gets the current log levels. The default level is under the empty module name.
+fn get_log_level() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("_log-level"))
+        .and(authenticate([AccessRight::ManageLogging]))
+        .map(|| {
+            let levels = logger::log_levels()
+                .into_iter()
+                .map(|(module, level)| (module, level.to_string()))
+                .collect::<BTreeMap<_, _>>();
+            Ok(levels)
+        })
+        .map(api_reply)
+}
+
+/// Changes the log level of a module, or the default level if no module is given, while the
+/// node is running. Removing the level of a module makes it go back to the default level.
+fn put_log_level() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        #[serde(default)]
+        module: Option<String>,
+        #[serde(default)]
+        level: Option<String>,
+    }
+
+    warp::put()
+        .and(warp::path!("_log-level"))
+        .and(authenticate([AccessRight::ManageLogging]))
+        .and(warp::body::json())
+        .map(|request: Request| {
+            let level = request
+                .level
+                .map(|level| {
+                    level
+                        .parse::<log::LevelFilter>()
+                        .map_err(|_| format!("invalid log level `{level}`"))
+                })
+                .transpose()?;
+            logger::set_log_level(request.module.as_deref(), level)
+        })
+        .map(api_reply)
+}
+
+/// Gets whether the node is quiet, i.e., not seeding content to peers and deferring
+/// subscription refreshes and edition announcements, and why.
+fn get_quiet() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("_quiet"))
+        .and(authenticate([AccessRight::ManageLifecycle]))
+        .map(|| Ok(crate::lifecycle::quiet_status()))
+        .map(api_reply)
+}
+
+/// Turns quiet mode on or off, overriding quiet hours and detection. Setting `quiet` to `null`
+/// goes back to deciding automatically.
+fn put_quiet() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        #[serde(default)]
+        quiet: Option<bool>,
+    }
+
+    warp::put()
+        .and(warp::path!("_quiet"))
+        .and(authenticate([AccessRight::ManageLifecycle]))
+        .and(warp::body::json())
+        .map(|request: Request| Ok(crate::lifecycle::set_quiet(request.quiet)))
+        .map(api_reply)
+}
+
+/// Gets the reports of the last crashes of the node, oldest first.
+fn get_crashes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("_crashes"))
+        .and(authenticate([AccessRight::ManageLogging]))
+        .map(crate::crashes::crash_reports)
+        .map(api_reply)
+}
+
+/// Removes all crash reports.
+fn delete_crashes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::delete()
+        .and(warp::path!("_crashes"))
+        .and(authenticate([AccessRight::ManageLogging]))
+        .map(crate::crashes::clear_crash_reports)
+        .map(api_reply)
+}
+
+/// The header with the ID of the request. Clients may set it to follow their requests through
+/// the logs of this node; it is always set in the response. It is never sent to the network
+/// (see [`samizdat_common::request_id`]).
+const REQUEST_ID_HEADER: &str = "X-Samizdat-Request-Id";
+
+/// The address of the client of a connection, which warp cannot see by itself when not
+/// serving the connections itself.
+#[derive(Debug, Clone, Copy)]
+struct RemoteAddr(SocketAddr);
+
+/// Extracts the address of the client.
+fn remote() -> impl Filter<Extract = (Option<SocketAddr>,), Error = Infallible> + Clone {
+    warp::addr::remote()
+        .and(warp::ext::optional::<RemoteAddr>())
+        .map(|direct: Option<SocketAddr>, remote: Option<RemoteAddr>| {
+            direct.or(remote.map(|RemoteAddr(addr)| addr))
+        })
+}
+
+/// Binds the HTTP server, either to the listener handed over by the service manager or to the
+/// port given in the command line, and returns the future running it. Each request is handled
+/// under its own [`RequestId`].
+pub fn serve() -> impl Future<Output = ()> {
+    let outside_loopback = remote().and_then(|addr: Option<SocketAddr>| async move {
+        if let Some(addr) = addr {
+            if addr.ip().to_canonical().is_loopback() {
+                return Err(warp::reject::not_found());
+            }
+        }
+
+        Ok(warp::reply::with_status(
+            "cannot connect outside loopback",
+            ::http::StatusCode::FORBIDDEN,
+        ))
+    });
+
+    // Replicas can only read:
+    let read_only = warp::method().and_then(|method: ::http::Method| async move {
+        if !crate::db::is_replica()
+            || method == ::http::Method::GET
+            || method == ::http::Method::HEAD
+        {
+            return Err(warp::reject::not_found());
+        }
+
+        Ok(warp::reply::with_status(
+            "this node is a read-only replica. Send writes to the primary",
+            ::http::StatusCode::METHOD_NOT_ALLOWED,
+        ))
+    });
+
+    // Health checks come before the loopback check, since they are meant for load balancers
+    // and container orchestrators:
+    let public_server = health()
+        .or(outside_loopback)
+        .or(read_only)
+        .or(warp::get().and(warp::path::end()).map(|| {
+            warp::reply::with_header(include_str!("../index.html"), "Content-Type", "text/html")
+        }))
+        .or(self::api())
+        .with(warp::log("api"));
+
+    let service = warp::service(public_server);
+    let make_service = make_service_fn(move |conn: &AddrStream| {
+        let remote_addr = RemoteAddr(conn.remote_addr());
+        let service = service.clone();
+
+        future::ok::<_, Infallible>(service_fn(move |mut request: hyper::Request<_>| {
+            request.extensions_mut().insert(remote_addr);
+            let encoding = request
+                .headers()
+                .get(http::header::ACCEPT_ENCODING)
+                .and_then(|value| value.to_str().ok())
+                .and_then(compression::Encoding::negotiate);
+            let request_id = request
+                .headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<RequestId>().ok())
+                .unwrap_or_default();
+
+            // Entities navigated to by path go to their own subdomains, if isolated:
+            let response = if subdomains::is_enabled() {
+                subdomains::redirect_for(&request).map(|url| {
+                    hyper::Response::builder()
+                        .status(http::StatusCode::FOUND)
+                        .header(http::header::LOCATION, url.as_str())
+                        .body(hyper::Body::empty())
+                        .expect("valid redirect")
+                })
+            } else {
+                None
+            };
+            let response = if let Some(response) = response {
+                future::Either::Left(future::ok(response))
+            } else {
+                if subdomains::is_enabled() {
+                    subdomains::rewrite(&mut request);
+                }
+
+                future::Either::Right(service.clone().call(request))
+            };
+
+            request_id.scope(response).map_ok(move |mut response| {
+                response.headers_mut().insert(
+                    REQUEST_ID_HEADER,
+                    request_id.to_string().parse().expect("valid header value"),
+                );
+                compression::compress(encoding, response)
+            })
+        }))
+    });
+
+    let builder = if let Some(listener) = crate::activation::inherited_listener() {
+        match hyper::Server::from_tcp(listener) {
+            Ok(builder) => builder,
+            Err(err) => panic!("cannot use socket-activated listener: {err}"),
+        }
+    } else {
+        let addr = SocketAddr::from(([0; 16], cli().port));
+        match hyper::Server::try_bind(&addr) {
+            Ok(builder) => builder,
+            Err(err) => panic!("cannot bind to {addr}: {err}"),
+        }
+    };
+    let server = builder.serve(make_service);
+
+    crate::activation::notify_ready();
+
+    async move {
+        if let Err(err) = server.await {
+            log::error!("server error: {err}");
+        }
+    }
+}
@@ -0,0 +1,741 @@
+use bytes::{Buf, Bytes};
+use futures::prelude::*;
+use futures::stream;
+use rocksdb::WriteBatch;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use warp::Filter;
+
+use samizdat_common::Hash;
+
+use crate::access::AccessRight;
+use crate::balanced_or_tree;
+use crate::cli;
+use crate::db::{db, PageQuery};
+use crate::models::{BookmarkType, Droppable, ObjectHeader, ObjectRef};
+use crate::sniff::{self, Sniffed};
+
+use super::resolvers::{resolve_object, Conditions};
+use super::{api_reply, authenticate, conditions, page_query, page_reply, riddles, tuple};
+
+/// The entrypoint of the object API.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        // Object CRUD
+        get_objects(),
+        get_object(),
+        post_object(),
+        post_fetch(),
+        delete_object(),
+        post_batch_delete(),
+        // Bookmark CRUD:
+        get_bookmark(),
+        post_bookmark(),
+        delete_bookmark(),
+        post_bookmark_batch(),
+        // Statistics:
+        get_stats(),
+        get_byte_usefulness(),
+        get_expiry(),
+        get_availability(),
+        // Bridges:
+        get_torrent(),
+        // Utils:
+        post_reissue(),
+        get_reference_count(),
+    )
+}
+
+/// Gets the contents of an object.
+fn get_object() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_objects" / Hash)
+        .and(warp::get())
+        .and(riddles())
+        .and(conditions())
+        .and_then(
+            |hash: Hash, riddles: Option<usize>, conditions: Conditions| async move {
+                Ok(resolve_object(ObjectRef::new(hash), riddles, &conditions, vec![]).await?)
+                    as Result<_, warp::Rejection>
+            },
+        )
+        .map(tuple)
+}
+
+/// Lists the objects in this node, optionally only those whose content type starts with a
+/// prefix (e.g., `image/`) and only the draft (or non-draft) ones.
+fn get_objects() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Query {
+        #[serde(default)]
+        content_type: Option<String>,
+        #[serde(default)]
+        is_draft: Option<bool>,
+    }
+
+    #[derive(Serialize)]
+    struct ObjectSummary {
+        hash: Hash,
+        content_type: String,
+        content_size: usize,
+        is_draft: bool,
+        received_at: chrono::DateTime<chrono::Utc>,
+    }
+
+    warp::path!("_objects")
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageObjects]))
+        .and(page_query())
+        .and(warp::query())
+        .map(|page: PageQuery, query: Query| {
+            let page = ObjectRef::get_page(&page, query.content_type.as_deref(), query.is_draft)?;
+
+            Ok(crate::db::Page {
+                items: page
+                    .items
+                    .into_iter()
+                    .map(|(object, metadata)| ObjectSummary {
+                        hash: *object.hash(),
+                        content_type: metadata.header.content_type().to_owned(),
+                        content_size: metadata.content_size,
+                        is_draft: metadata.header.is_draft(),
+                        received_at: metadata.received_at,
+                    })
+                    .collect(),
+                next: page.next,
+            })
+        })
+        .map(page_reply)
+}
+
+/// Uploads a new object to the database.
+fn post_object() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    #[serde(rename = "kebab-case")]
+    struct Query {
+        #[serde(default)]
+        bookmark: bool,
+        #[serde(default)]
+        is_draft: bool,
+        /// How long the object is kept before the vacuum deletes it, e.g., `1h` or `7days`.
+        #[serde(default, with = "humantime_serde")]
+        ttl: Option<Duration>,
+    }
+
+    warp::path!("_objects")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageObjects]))
+        .and(warp::header::optional("content-type"))
+        .and(warp::query())
+        .and(warp::body::stream())
+        .and_then(
+            |content_type: Option<String>, query: Query, body| async move {
+                let body = body_content(body);
+                if query.bookmark && query.ttl.is_some() {
+                    return Ok(Err(crate::Error::ValidationFailed(
+                        "bookmarked objects never expire; choose either bookmark or ttl".to_owned(),
+                    )));
+                }
+
+                let expires_at = match query.ttl.map(expiry_after).transpose() {
+                    Ok(expires_at) => expires_at,
+                    Err(err) => return Ok(Err(err)),
+                };
+                let options = UploadOptions {
+                    is_draft: query.is_draft,
+                    bookmark: query.bookmark,
+                    expires_at,
+                };
+
+                let uploaded = match content_type {
+                    Some(content_type) if content_type.starts_with("multipart/form-data") => {
+                        upload_form(&content_type, options, body).await
+                    }
+                    content_type => upload(content_type, options, body).await,
+                };
+
+                Ok(uploaded) as Result<_, warp::Rejection>
+            },
+        )
+        .map(|uploaded: Result<(ObjectRef, Sniffed), crate::Error>| {
+            let warnings = uploaded
+                .as_ref()
+                .map(|(_, sniffed)| sniffed.warnings.clone())
+                .unwrap_or_default();
+            let mut response = warp::Reply::into_response(api_reply(
+                uploaded.map(|(object, _)| object.hash().to_string()),
+            ));
+
+            // Content sniffing warnings go in headers, so as not to change the response:
+            for warning in warnings {
+                if let Ok(value) = http::HeaderValue::from_str(&warning) {
+                    response.headers_mut().append("X-Samizdat-Warning", value);
+                }
+            }
+
+            response
+        })
+}
+
+/// The time at which an object uploaded now expires, given its time to live.
+fn expiry_after(ttl: Duration) -> Result<chrono::DateTime<chrono::Utc>, crate::Error> {
+    chrono::Duration::from_std(ttl)
+        .ok()
+        .and_then(|ttl| chrono::Utc::now().checked_add_signed(ttl))
+        .ok_or_else(|| crate::Error::ValidationFailed(format!("ttl too long: {ttl:?}")))
+}
+
+/// The content of a request body, as it arrives.
+fn body_content(
+    body: impl Stream<Item = Result<impl Buf, warp::Error>>,
+) -> impl Stream<Item = Result<Bytes, crate::Error>> {
+    body.map_ok(|mut buf| buf.copy_to_bytes(buf.remaining()))
+        .map_err(|err| crate::Error::from(format!("failed to read body: {err}")))
+}
+
+/// Downloads a URL and stores it as a new object, so that clients do not need to download and
+/// upload it themselves. The download is subject to the same size limit as uploads and to a
+/// time limit. Addresses in this machine or in private networks are refused.
+fn post_fetch() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        url: String,
+        /// Overrides the content type sent by the server.
+        #[serde(default)]
+        content_type: Option<String>,
+        #[serde(default)]
+        bookmark: bool,
+        #[serde(default)]
+        is_draft: bool,
+    }
+
+    #[derive(Serialize)]
+    struct Response {
+        hash: String,
+        content_type: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+    }
+
+    warp::path!("_objects" / "fetch")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageObjects]))
+        .and(warp::body::json())
+        .and_then(|request: Request| async move {
+            let fetched = fetch(&request.url, request.content_type).await;
+            let uploaded = match fetched {
+                Ok((content_type, content)) => {
+                    let options = UploadOptions {
+                        is_draft: request.is_draft,
+                        bookmark: request.bookmark,
+                        expires_at: None,
+                    };
+
+                    upload(content_type, options, content)
+                        .await
+                        .map(|(object, sniffed)| Response {
+                            hash: object.hash().to_string(),
+                            content_type: sniffed.content_type,
+                            warnings: sniffed.warnings,
+                        })
+                }
+                Err(err) => Err(err),
+            };
+
+            Ok(uploaded) as Result<_, warp::Rejection>
+        })
+        .map(api_reply)
+}
+
+/// Whether an address may be fetched on behalf of a client, i.e., whether it is neither this
+/// machine nor in a private network. Otherwise, clients could use the node to reach services
+/// that are not meant to be exposed.
+fn is_public(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast())
+        }
+        IpAddr::V6(ip) => {
+            !(ip.is_loopback()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+                || ip.is_unspecified())
+        }
+    }
+}
+
+/// Checks whether a URL may be fetched: it must be http or https and, if its host is an
+/// address, the address must be public. Names are checked when resolved (see
+/// [`PublicResolver`]).
+fn check_fetch_url(url: &url::Url) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("can only fetch http and https URLs, got {url}"));
+    }
+
+    let ip = match url.host() {
+        Some(url::Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(url::Host::Ipv6(ip)) => IpAddr::V6(ip),
+        Some(url::Host::Domain(_)) => return Ok(()),
+        None => return Err(format!("no host in {url}")),
+    };
+
+    if !is_public(ip) {
+        return Err(format!("cannot fetch from non-public address {ip}"));
+    }
+
+    Ok(())
+}
+
+/// Resolves the names of the URLs to be fetched, refusing names that resolve to any address
+/// that is not public. Since this is what the client connects to, this also holds after
+/// redirects and if the name is changed to resolve elsewhere in between.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: hyper::client::connect::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .collect::<Vec<_>>();
+
+            if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(format!(
+                    "{} resolves to non-public address {}",
+                    name.as_str(),
+                    addr.ip()
+                )
+                .into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Starts downloading a URL, giving its content type, if known, and its content, as it
+/// arrives. Only public addresses are fetched, also after redirects.
+async fn fetch(
+    url: &str,
+    content_type: Option<String>,
+) -> Result<
+    (
+        Option<String>,
+        impl Stream<Item = Result<Bytes, crate::Error>>,
+    ),
+    crate::Error,
+> {
+    const MAX_REDIRECTS: usize = 10;
+
+    lazy_static::lazy_static! {
+        static ref CLIENT: reqwest::Client = reqwest::Client::builder()
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(reqwest::redirect::Policy::custom(|attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if let Err(err) = check_fetch_url(attempt.url()) {
+                    attempt.error(err)
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()
+            .expect("can build client");
+    }
+
+    let parsed = url::Url::parse(url)
+        .map_err(|err| crate::Error::ValidationFailed(format!("bad URL {url:?}: {err}")))?;
+    check_fetch_url(&parsed).map_err(crate::Error::ValidationFailed)?;
+
+    let response = CLIENT
+        .get(parsed)
+        .timeout(Duration::from_secs(cli().fetch_timeout))
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|err| crate::Error::from(format!("failed to fetch {url}: {err}")))?;
+
+    let max_size = cli().max_upload_size * 1_000_000;
+    if response
+        .content_length()
+        .is_some_and(|length| length as usize > max_size)
+    {
+        return Err(crate::Error::ValidationFailed(format!(
+            "{url} is too big: max size is {max_size}"
+        )));
+    }
+
+    let content_type = content_type.or_else(|| {
+        response
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    });
+
+    let url = url.to_owned();
+    let content = stream::unfold(Some(response), move |response| {
+        let url = url.clone();
+        async move {
+            let mut response = response?;
+            match response.chunk().await {
+                Ok(Some(chunk)) => Some((Ok(chunk), Some(response))),
+                Ok(None) => None,
+                Err(err) => Some((
+                    Err(crate::Error::from(format!("failed to fetch {url}: {err}"))),
+                    None,
+                )),
+            }
+        }
+    });
+
+    Ok((content_type, content))
+}
+
+/// Fails the upload once the content goes beyond the maximum upload size.
+fn limit_size(
+    content: impl Stream<Item = Result<Bytes, crate::Error>>,
+) -> impl Stream<Item = Result<Bytes, crate::Error>> {
+    let max_size = cli().max_upload_size * 1_000_000;
+    content.scan(0, move |size, piece| {
+        let piece = piece.and_then(|piece| {
+            *size += piece.len();
+            if *size > max_size {
+                Err(crate::Error::ValidationFailed(format!(
+                    "upload too big: max size is {max_size}"
+                )))
+            } else {
+                Ok(piece)
+            }
+        });
+
+        future::ready(Some(piece))
+    })
+}
+
+/// What to make of an uploaded object.
+#[derive(Debug, Clone, Copy)]
+struct UploadOptions {
+    is_draft: bool,
+    bookmark: bool,
+    /// When the object expires, if it is new (see [`ObjectRef::expires_at`]).
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Streams an upload straight into a new object. The content type of the object is decided by
+/// sniffing the beginning of the content (see [`sniff`]).
+async fn upload(
+    content_type: Option<String>,
+    options: UploadOptions,
+    content: impl Stream<Item = Result<Bytes, crate::Error>>,
+) -> Result<(ObjectRef, Sniffed), crate::Error> {
+    let mut content = Box::pin(limit_size(content));
+    let mut head = Vec::with_capacity(sniff::SNIFF_SIZE);
+
+    while head.len() < sniff::SNIFF_SIZE {
+        match content.next().await {
+            Some(piece) => head.extend_from_slice(&piece?),
+            None => break,
+        }
+    }
+
+    let sniffed = sniff::sniff(content_type.as_deref(), &head);
+    let header = ObjectHeader::new(sniffed.content_type.clone(), options.is_draft)?;
+    let content = stream::once(future::ready(Ok(Bytes::from(head)))).chain(content);
+    let object = ObjectRef::build_from_stream(
+        header,
+        options.bookmark,
+        options.expires_at,
+        Box::pin(content),
+    )
+    .await?;
+
+    Ok((object, sniffed))
+}
+
+/// Streams the file in a `multipart/form-data` upload into a new object. The file is the part
+/// named `file` or, if there is none, the first part with a file name.
+async fn upload_form(
+    content_type: &str,
+    options: UploadOptions,
+    body: impl 'static + Send + Stream<Item = Result<Bytes, crate::Error>>,
+) -> Result<(ObjectRef, Sniffed), crate::Error> {
+    let boundary = multer::parse_boundary(content_type)
+        .map_err(|err| crate::Error::ValidationFailed(format!("bad multipart upload: {err}")))?;
+    let body = body.map_err(|err| io::Error::other(err.to_string()));
+    let mut form = multer::Multipart::new(body, boundary);
+
+    while let Some(field) = form
+        .next_field()
+        .await
+        .map_err(|err| crate::Error::ValidationFailed(format!("bad multipart upload: {err}")))?
+    {
+        if field.name() != Some("file") && field.file_name().is_none() {
+            continue;
+        }
+
+        let content_type = field.content_type().map(ToString::to_string);
+        let content = field
+            .map_err(|err| crate::Error::from(format!("failed to read multipart upload: {err}")));
+
+        return upload(content_type, options, content).await;
+    }
+
+    Err(crate::Error::ValidationFailed(
+        "multipart upload has no file".to_owned(),
+    ))
+}
+
+/// Explicitly deletes an object from the local database. This does not have the
+/// effect of deleting it from the whole network. It only clears a local buffer.
+fn delete_object() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_objects" / Hash)
+        .and(authenticate([AccessRight::ManageObjects]))
+        .and(warp::delete())
+        .map(|hash| ObjectRef::new(hash).drop_if_exists())
+        .map(api_reply)
+}
+
+/// Explicitly deletes a list of objects from the local database in a single atomic
+/// operation, returning how many of them existed and were removed. See [`delete_object`] for
+/// details.
+fn post_batch_delete() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    #[derive(Deserialize)]
+    struct Request {
+        hashes: Vec<String>,
+    }
+
+    warp::path!("_objects" / "batch-delete")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageObjects]))
+        .and(warp::body::json())
+        .map(|request: Request| {
+            let mut batch = WriteBatch::default();
+            let mut removed = 0;
+
+            // Parse all first, so that repeated hashes are only counted once:
+            let hashes = request
+                .hashes
+                .iter()
+                .map(|hash| hash.parse())
+                .collect::<Result<BTreeSet<Hash>, _>>()?;
+
+            for hash in hashes {
+                let object = ObjectRef::new(hash);
+                if object.metadata()?.is_some() {
+                    removed += 1;
+                }
+
+                object.drop_if_exists_with(&mut batch)?;
+            }
+
+            db().write(batch)?;
+
+            Ok(removed)
+        })
+        .map(api_reply)
+}
+
+/// Bookmarks an object. This will prevent the object from being automatically removed
+/// by the vacuum daemon.
+fn post_bookmark() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_objects" / Hash / "bookmark")
+        .and(authenticate([AccessRight::ManageBookmarks]))
+        .and(warp::post())
+        .map(|hash| ObjectRef::new(hash).bookmark(BookmarkType::User).mark())
+        .map(api_reply)
+}
+
+/// Returns whether an object is bookmarked or not.
+///
+/// # Warning
+///
+/// By now, this returns `200 OK` even if the object does not exist.
+fn get_bookmark() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_objects" / Hash / "bookmark")
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageBookmarks]))
+        .map(|hash| {
+            ObjectRef::new(hash)
+                .bookmark(BookmarkType::User)
+                .is_marked()
+        })
+        .map(api_reply)
+}
+
+/// Returns the internal reference count on the object.
+///
+/// # Warning
+///
+/// By now, this returns `200 OK` even if the object does not exist.
+fn get_reference_count(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_objects" / Hash / "reference-count")
+        .and(warp::get())
+        .and(authenticate([AccessRight::GetObjectStats]))
+        .map(|hash| {
+            ObjectRef::new(hash)
+                .bookmark(BookmarkType::Reference)
+                .get_count()
+        })
+        .map(api_reply)
+}
+
+/// Removes the bookmark from an object, allowing the vacuum daemon to gobble it up.
+fn delete_bookmark() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("_objects" / Hash / "bookmark")
+        .and(warp::delete())
+        .and(authenticate([AccessRight::ManageBookmarks]))
+        .map(|hash| ObjectRef::new(hash).bookmark(BookmarkType::User).unmark())
+        .map(api_reply)
+}
+
+/// Bookmarks and removes bookmarks from a list of objects in a single atomic operation.
+fn post_bookmark_batch(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        #[serde(default)]
+        mark: Vec<String>,
+        #[serde(default)]
+        unmark: Vec<String>,
+    }
+
+    warp::path!("_bookmarks" / "batch")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageBookmarks]))
+        .and(warp::body::json())
+        .map(|request: Request| {
+            let mut batch = WriteBatch::default();
+
+            for hash in request.mark {
+                ObjectRef::new(hash.parse()?)
+                    .bookmark(BookmarkType::User)
+                    .mark_with(&mut batch);
+            }
+
+            for hash in request.unmark {
+                ObjectRef::new(hash.parse()?)
+                    .bookmark(BookmarkType::User)
+                    .unmark_with(&mut batch);
+            }
+
+            db().write(batch)?;
+
+            Ok(())
+        })
+        .map(api_reply)
+}
+
+/// Removes the bookmark from an object, allowing the vacuum daemon to gobble it up.
+fn post_reissue() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    #[serde(rename = "kebab-case")]
+    struct Query {
+        #[serde(default)]
+        bookmark: bool,
+    }
+
+    warp::path!("_objects" / Hash / "reissue")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageObjects]))
+        .and(warp::query())
+        .map(|hash, query: Query| {
+            ObjectRef::new(hash)
+                .reissue(query.bookmark)
+                .map(|reissued| reissued.map(|reissued| reissued.hash().to_string()))
+        })
+        .map(api_reply)
+}
+
+/// Removes the bookmark from an object, allowing the vacuum daemon to gobble it up.
+fn get_stats() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_objects" / Hash / "stats")
+        .and(warp::get())
+        .and(authenticate([AccessRight::GetObjectStats]))
+        .map(|hash| ObjectRef::new(hash).statistics())
+        .map(api_reply)
+}
+
+/// Removes the bookmark from an object, allowing the vacuum daemon to gobble it up.
+fn get_byte_usefulness(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_objects" / Hash / "stats" / "byte-usefulness")
+        .and(warp::get())
+        .and(authenticate([AccessRight::GetObjectStats]))
+        .map(|hash| {
+            ObjectRef::new(hash).statistics().map(|stats| {
+                stats.map(|stats| stats.byte_usefulness(&crate::models::UsePrior::default()))
+            })
+        })
+        .map(api_reply)
+}
+
+/// Gets the time after which an object is deleted, if it was uploaded with a time to live.
+fn get_expiry() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_objects" / Hash / "expiry")
+        .and(warp::get())
+        .and(authenticate([AccessRight::GetObjectStats]))
+        .map(|hash| ObjectRef::new(hash).expires_at())
+        .map(api_reply)
+}
+
+/// Estimates how many peers in the network have an object, without downloading it. This takes
+/// as long as a query can take, since peers keep answering until the query deadline.
+fn get_availability() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("_objects" / Hash / "availability")
+        .and(warp::get())
+        .and(authenticate([AccessRight::GetObjectStats]))
+        .and_then(|hash| async move {
+            Ok(Ok(crate::hubs().availability(hash).await)) as Result<_, warp::Rejection>
+        })
+        .map(api_reply)
+}
+
+#[derive(Deserialize)]
+struct TorrentQuery {
+    name: Option<String>,
+}
+
+/// Gets a `.torrent` file for an object in this node. The name of the file in the torrent can
+/// be set in the query string and defaults to the object hash. Hashing the whole object is
+/// costly and tells what is in this node, so this is not public.
+fn get_torrent() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_objects" / Hash / "torrent")
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageObjects]))
+        .and(warp::query())
+        .and_then(|hash: Hash, query: TorrentQuery| async move {
+            let object = ObjectRef::new(hash);
+            let torrent =
+                tokio::task::spawn_blocking(move || crate::torrent::torrent(&object, query.name))
+                    .await
+                    .map_err(|err| crate::Error::from(format!("torrent task panicked: {err}")))??;
+
+            let response = match torrent {
+                Some(torrent) => http::Response::builder()
+                    .header("Content-Type", "application/x-bittorrent")
+                    .header(
+                        "Content-Disposition",
+                        format!("attachment; filename=\"{hash}.torrent\""),
+                    )
+                    .body(hyper::Body::from(torrent)),
+                None => http::Response::builder()
+                    .header("Content-Type", "text/plain")
+                    .status(http::StatusCode::NOT_FOUND)
+                    .body(hyper::Body::from(format!("Object {hash} not found"))),
+            };
+
+            Ok(response) as Result<_, warp::Rejection>
+        })
+        .map(tuple)
+}
@@ -0,0 +1,159 @@
+//! Service workers keeping series available offline in browsers, for readers who cannot
+//! install a node (e.g., readers behind a Samizdat proxy). A page of a series registers the
+//! worker with, e.g.,
+//!
+//! ```js
+//! navigator.serviceWorker.register("/_series/{key}/_offline/sw.js", { scope: "/_series/{key}/" });
+//! ```
+//!
+//! and links to `/_series/{key}/_offline/manifest.webmanifest` as its `manifest`, so that
+//! browsers can install the series as an app.
+
+use askama::Template;
+use serde_derive::Serialize;
+use std::collections::BTreeSet;
+use url::Url;
+use warp::Filter;
+
+use samizdat_common::Key;
+
+use crate::models::{ObjectRef, SeriesRef};
+
+use super::collections::get_inventory;
+use super::resolvers::{ensure_fresh, latest_collection};
+use super::{api_reply, tuple};
+
+/// The most bytes of content cached when the worker is installed. Items beyond this are only
+/// cached once visited.
+const MAX_PRECACHE_SIZE: usize = 50_000_000;
+
+#[derive(Template)]
+#[template(path = "offline-sw.js", escape = "none")]
+struct ServiceWorker {
+    series: String,
+    collection: String,
+    scope: String,
+    precache: Vec<String>,
+}
+
+/// The path under which all the content of a series is served.
+fn scope_for(series: &Key) -> String {
+    format!("/_series/{series}/")
+}
+
+/// The URL path of an item of a series, with each segment percent-encoded.
+fn path_for(series: &Key, item: &str) -> String {
+    let mut url = Url::parse("http://localhost/").expect("valid URL");
+    url.path_segments_mut()
+        .expect("URL can be a base")
+        .extend(["_series", &series.to_string()])
+        .extend(item.split('/'));
+
+    url.path().to_owned()
+}
+
+/// The service worker for a series, caching the items of its latest edition, the ones the
+/// publisher wants prefetched first, up to [`MAX_PRECACHE_SIZE`]. Only items whose objects the
+/// node has are cached, since the size of the others is not known; the rest is cached once
+/// visited.
+async fn service_worker(series_key: Key) -> Result<ServiceWorker, crate::Error> {
+    let series = SeriesRef::new(series_key.clone());
+    ensure_fresh(&series).await?;
+
+    let collection = latest_collection(&series)?
+        .ok_or_else(|| crate::Error::NotFound(format!("editions of series {series}")))?;
+    // This also brings in all the shards of sharded inventories:
+    let inventory = get_inventory(&collection).await?;
+
+    let prefetched = inventory.prefetch().iter().collect::<BTreeSet<_>>();
+    let prefetch = inventory
+        .prefetch()
+        .iter()
+        .filter_map(|path| Some((path, *inventory.get(path)?)));
+    let rest = inventory
+        .iter()
+        .filter(|(path, _)| !prefetched.contains(path))
+        .map(|(path, hash)| (path, *hash));
+
+    let mut precache = vec![];
+    let mut total_size = 0;
+
+    for (path, hash) in prefetch.chain(rest) {
+        let Some(metadata) = ObjectRef::new(hash).metadata()? else {
+            continue;
+        };
+        let size = metadata.content_size;
+
+        if total_size + size > MAX_PRECACHE_SIZE {
+            continue;
+        }
+
+        total_size += size;
+        precache.push(path_for(&series_key, path.as_str()));
+    }
+
+    Ok(ServiceWorker {
+        series: series_key.to_string(),
+        collection: collection.hash().to_string(),
+        scope: scope_for(&series_key),
+        precache,
+    })
+}
+
+/// Gets the service worker keeping a series available offline. Series content is public, so
+/// this needs no authentication.
+pub fn get_service_worker(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_series" / Key / "_offline" / "sw.js")
+        .and(warp::get())
+        .and_then(|series_key: Key| async move {
+            let scope = scope_for(&series_key);
+            let response = match service_worker(series_key).await {
+                Ok(worker) => http::Response::builder()
+                    .header("Content-Type", "text/javascript; charset=utf-8")
+                    .header("Cache-Control", "no-cache")
+                    // Lets the worker control the whole series, not only `_offline/`:
+                    .header("Service-Worker-Allowed", scope)
+                    .body(
+                        worker
+                            .render()
+                            .expect("can always render service worker")
+                            .into(),
+                    ),
+                Err(err) => Ok(warp::Reply::into_response(api_reply::<()>(Err(err)))),
+            };
+
+            Ok(response) as Result<_, warp::Rejection>
+        })
+        .map(tuple)
+}
+
+/// Gets the web app manifest of a series, with which browsers can install it as an app.
+pub fn get_manifest() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    #[derive(Serialize)]
+    struct Manifest {
+        name: String,
+        start_url: String,
+        scope: String,
+        display: &'static str,
+    }
+
+    warp::path!("_series" / Key / "_offline" / "manifest.webmanifest")
+        .and(warp::get())
+        .map(|series_key: Key| {
+            let scope = scope_for(&series_key);
+            let manifest = Manifest {
+                name: format!("Samizdat series {series_key}"),
+                start_url: scope.clone(),
+                scope,
+                display: "standalone",
+            };
+
+            warp::reply::with_header(
+                warp::reply::json(&manifest),
+                "Content-Type",
+                "application/manifest+json",
+            )
+        })
+}
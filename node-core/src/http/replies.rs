@@ -0,0 +1,101 @@
+use serde_derive::{Deserialize, Serialize};
+use warp::Filter;
+
+use samizdat_common::{Hash, Key};
+
+use crate::access::AccessRight;
+use crate::balanced_or_tree;
+use crate::models::{Droppable, Reply, SeriesOwner};
+use crate::replies;
+
+use super::{api_reply, authenticate};
+
+/// The entrypoint of the replies API.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        get_replies(),
+        post_fetch_replies(),
+        post_reply(),
+        delete_reply()
+    )
+}
+
+/// The latest edition of a series replying to an item.
+#[derive(Serialize)]
+struct ReplySummary {
+    public_key: String,
+    collection: String,
+    timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Summarizes the replies to an item known to this node, latest first.
+fn known_replies(locator: Hash) -> Result<Vec<ReplySummary>, crate::Error> {
+    Ok(replies::known(locator)?
+        .into_iter()
+        .map(|edition| ReplySummary {
+            public_key: edition.public_key().to_string(),
+            collection: edition.collection().hash().to_string(),
+            timestamp: edition.timestamp(),
+        })
+        .collect())
+}
+
+/// Lists the replies to an item known to this node, latest first. Whoever can read an item
+/// can also read its replies, so this needs no authentication. This never looks for replies
+/// in the network; see [`post_fetch_replies`].
+fn get_replies() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_replies" / Hash)
+        .and(warp::get())
+        .map(known_replies)
+        .map(api_reply)
+}
+
+/// Finds the replies to an item in the network and lists the ones known to this node, latest
+/// first.
+fn post_fetch_replies(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_replies" / Hash / "fetch")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .and_then(|locator: Hash| async move {
+            let summaries = async move {
+                replies::fetch(locator).await?;
+                known_replies(locator)
+            }
+            .await;
+
+            Ok(summaries) as Result<_, warp::Rejection>
+        })
+        .map(api_reply)
+}
+
+/// Registers a locally owned series as a reply to an item.
+fn post_reply() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        series_owner_name: String,
+    }
+
+    warp::path!("_replies" / Hash)
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .and(warp::body::json())
+        .map(|locator: Hash, request: Request| {
+            let owner = SeriesOwner::get(&request.series_owner_name)?.ok_or_else(|| {
+                crate::Error::NotFound(format!("series owner {}", request.series_owner_name))
+            })?;
+            let reply = replies::register(locator, &owner)?;
+
+            Ok(reply.public_key.to_string())
+        })
+        .map(api_reply)
+}
+
+/// Forgets a reply to an item.
+fn delete_reply() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_replies" / Hash / Key)
+        .and(warp::delete())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .map(|locator: Hash, public_key: Key| Reply::new(locator, public_key).drop_if_exists())
+        .map(api_reply)
+}
@@ -0,0 +1,862 @@
+//! Bridges from the Samizdat world to the HTTP world.
+
+use chrono::{DateTime, Utc};
+use futures::stream;
+use http::Response;
+use hyper::Body;
+use lazy_static::lazy_static;
+use rocksdb::WriteBatch;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use samizdat_common::rpc::QueryKind;
+use samizdat_common::Hash;
+
+use crate::cli;
+use crate::content_filter;
+use crate::hubs;
+use crate::models::{
+    CollectionItem, CollectionRef, IdentityRef, ItemMetadata, ItemPath, ItemPathBuf, Locator,
+    Mount, ObjectRef, SeriesRef, SeriesTrustRef,
+};
+use crate::system::{self, routing};
+use crate::time_lock;
+
+use super::signing;
+
+/// The conditional headers of a request for content, telling what the client already has,
+/// and the part of the content it wants.
+#[derive(Debug, Clone, Default)]
+pub struct Conditions {
+    /// The `If-None-Match` header.
+    pub if_none_match: Option<String>,
+    /// The `If-Modified-Since` header.
+    pub if_modified_since: Option<String>,
+    /// The `Range` header.
+    pub range: Option<String>,
+    /// The `If-Range` header.
+    pub if_range: Option<String>,
+}
+
+impl Conditions {
+    /// Whether the client already has the content. As in the HTTP spec, `If-Modified-Since` is
+    /// only taken into account when there is no `If-None-Match`. The `*` tag is not honored,
+    /// since the content may not even exist.
+    fn is_cached(&self, etag: &str, last_modified: Option<DateTime<Utc>>) -> bool {
+        if let Some(if_none_match) = &self.if_none_match {
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag.trim_start_matches("W/") == etag);
+        }
+
+        let if_modified_since = self
+            .if_modified_since
+            .as_deref()
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok());
+
+        match (if_modified_since, last_modified) {
+            (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+
+    /// The byte range of the content the client wants, inclusive on both ends, if not the
+    /// whole content, or `Err(())` if the range is not satisfiable. Only single ranges are
+    /// honored; for anything else, the whole content is served, as the HTTP spec allows. So
+    /// is it if `If-Range` does not match the content, which only works with entity tags.
+    fn range(&self, etag: &str, content_size: usize) -> Option<Result<(usize, usize), ()>> {
+        if let Some(if_range) = &self.if_range {
+            if if_range.trim() != etag {
+                return None;
+            }
+        }
+
+        parse_range(self.range.as_deref()?, content_size)
+    }
+}
+
+/// Parses a single range in a `Range` header, as in [`Conditions::range`].
+fn parse_range(range: &str, content_size: usize) -> Option<Result<(usize, usize), ()>> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", "") => return None,
+        ("", suffix) => {
+            let suffix = suffix.parse::<usize>().ok()?;
+            if suffix == 0 || content_size == 0 {
+                return Some(Err(()));
+            }
+
+            (content_size.saturating_sub(suffix), content_size - 1)
+        }
+        (start, "") => (start.parse().ok()?, usize::MAX),
+        (start, end) => (start.parse().ok()?, end.parse().ok()?),
+    };
+
+    if start > end || start >= content_size {
+        return Some(Err(()));
+    }
+
+    Some(Ok((start, end.min(content_size - 1))))
+}
+
+/// The strong ETag of an object. Objects are immutable, so their hash will do.
+fn etag(object: &ObjectRef) -> String {
+    format!("\"{}\"", object.hash())
+}
+
+/// Formats a timestamp as an HTTP date.
+fn http_date(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+pub struct Resolved {
+    body: Body,
+    content_type: String,
+    content_size: usize,
+    /// The `Content-Range` of the body, if not the whole content.
+    content_range: Option<String>,
+    etag: String,
+    last_modified: Option<DateTime<Utc>>,
+    ext_headers: Vec<(&'static str, String)>,
+}
+
+impl TryInto<Response<Body>> for Resolved {
+    type Error = http::Error;
+    fn try_into(self) -> Result<Response<Body>, http::Error> {
+        let mut builder = http::Response::builder()
+            .header("Content-Type", self.content_type)
+            .header("Content-Size", self.content_size)
+            .header("Accept-Ranges", "bytes")
+            .header("ETag", self.etag);
+
+        builder = if let Some(content_range) = self.content_range {
+            builder
+                .header("Content-Range", content_range)
+                .status(http::StatusCode::PARTIAL_CONTENT)
+        } else {
+            builder.status(http::StatusCode::OK)
+        };
+
+        if let Some(last_modified) = self.last_modified {
+            builder = builder.header("Last-Modified", http_date(last_modified));
+        }
+
+        for (header, value) in self.ext_headers {
+            builder = builder.header(header, value);
+        }
+
+        // TODO: Bleh! Tidy-up this mess!
+        builder.body(self.body)
+    }
+}
+
+/// The client asked for a part of the content that does not exist.
+pub struct RangeNotSatisfiable {
+    content_size: usize,
+}
+
+impl TryInto<Response<Body>> for RangeNotSatisfiable {
+    type Error = http::Error;
+    fn try_into(self) -> Result<Response<Body>, http::Error> {
+        http::Response::builder()
+            .status(http::StatusCode::RANGE_NOT_SATISFIABLE)
+            .header("Content-Range", format!("bytes */{}", self.content_size))
+            .header("Content-Type", "text/plain")
+            .body(Body::from("Range not satisfiable"))
+    }
+}
+
+/// The client already has the content it asked for.
+pub struct NotModified {
+    etag: String,
+    last_modified: Option<DateTime<Utc>>,
+    ext_headers: Vec<(&'static str, String)>,
+}
+
+impl TryInto<Response<Body>> for NotModified {
+    type Error = http::Error;
+    fn try_into(self) -> Result<Response<Body>, http::Error> {
+        let mut builder = http::Response::builder().header("ETag", self.etag);
+
+        if let Some(last_modified) = self.last_modified {
+            builder = builder.header("Last-Modified", http_date(last_modified));
+        }
+
+        for (header, value) in self.ext_headers {
+            builder = builder.header(header, value);
+        }
+
+        builder
+            .status(http::StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+    }
+}
+
+/// The directory of a collection holding the pages to show when its items cannot be served.
+const ERROR_PAGES: &str = "_errors";
+
+pub struct NotResolved {
+    status: http::StatusCode,
+    message: String,
+    /// A page from the collection to show instead of the message, with its content type.
+    page: Option<(String, Vec<u8>)>,
+}
+
+impl NotResolved {
+    fn new(message: String) -> NotResolved {
+        NotResolved {
+            status: http::StatusCode::NOT_FOUND,
+            message,
+            page: None,
+        }
+    }
+
+    /// Something was not found, neither locally nor in the network. If the network was not
+    /// asked, because it recently did not have the content, the message says when it will be
+    /// asked again.
+    fn not_found(what: String, content_hash: &Hash) -> NotResolved {
+        match system::retry_after(content_hash) {
+            Some(retry_after) => NotResolved::new(format!(
+                "{what} not found, will retry after {}s",
+                retry_after.as_secs().max(1)
+            )),
+            None => NotResolved::new(format!("{what} not found")),
+        }
+    }
+
+    fn from_error(error: &crate::Error) -> NotResolved {
+        NotResolved {
+            status: error.status_code(),
+            message: error.to_string(),
+            page: None,
+        }
+    }
+
+    /// Finds the error page of a collection for this error, trying `_errors/<status>.html`
+    /// (e.g., `_errors/404.html`), then `_errors/<class>xx.html` (e.g., `_errors/4xx.html`)
+    /// and then `_errors/default.html`, locally first and then in the network. If the
+    /// inventory of the collection is here, only the pages it lists are looked for, so that
+    /// collections without error pages never cost a query. The network is not asked if the
+    /// error was the network timing out. Without a page, the plain message is shown.
+    async fn with_page(mut self, collection: &CollectionRef, riddles: Option<usize>) -> Self {
+        let status = self.status.as_u16();
+        let mut candidates = vec![
+            format!("{ERROR_PAGES}/{status}.html"),
+            format!("{ERROR_PAGES}/{}xx.html", status / 100),
+            format!("{ERROR_PAGES}/default.html"),
+        ];
+        let ask_network = self.status != http::StatusCode::GATEWAY_TIMEOUT;
+
+        let page = async {
+            // Refused collections do not get to show anything, not even their error pages.
+            content_filter::check(collection).await?;
+
+            if let Some(inventory) = collection.inventory()? {
+                candidates.retain(|candidate| inventory.contains(&candidate.as_str().into()));
+            }
+
+            for is_local in [true, false] {
+                if !is_local && !ask_network {
+                    break;
+                }
+
+                for candidate in &candidates {
+                    let locator = collection.locator_for(candidate.as_str().into());
+
+                    if !is_local {
+                        hubs()
+                            .query_with(locator.hash(), QueryKind::Item, riddles)
+                            .await;
+                    }
+
+                    let Some(object) = locator.get_object()? else {
+                        continue;
+                    };
+
+                    if let Some((metadata, content)) = object.metadata()?.zip(object.content()?) {
+                        let item_metadata = collection
+                            .item_metadata(candidate.as_str().into())?
+                            .unwrap_or_default();
+                        let content_type =
+                            item_metadata.content_type(metadata.header.content_type());
+                        return Ok(Some((content_type, content)));
+                    }
+                }
+            }
+
+            Ok(None) as Result<_, crate::Error>
+        };
+
+        match page.await {
+            Ok(page) => self.page = page,
+            Err(err) => log::warn!("failed to find error page in {collection:?}: {err}"),
+        }
+
+        self
+    }
+}
+
+impl TryInto<Response<Body>> for NotResolved {
+    type Error = http::Error;
+    fn try_into(self) -> Result<Response<Body>, http::Error> {
+        let builder = http::Response::builder().status(self.status);
+
+        if let Some((content_type, content)) = self.page {
+            builder
+                .header("Content-Type", content_type)
+                .header("Cache-Control", "no-store")
+                .body(Body::from(content))
+        } else {
+            builder
+                .header("Content-Type", "text/plain")
+                .body(Body::from(self.message))
+        }
+    }
+}
+
+/// The security headers to serve an item with: the ones set by the publisher or else the
+/// defaults of the node. Everything served by the node shares the same origin, so these are
+/// what keeps apps from framing each other or leaking where the reader has been. An empty value
+/// means no header.
+fn security_headers(item_metadata: &ItemMetadata) -> Vec<(&'static str, String)> {
+    let cli = cli();
+
+    [
+        (
+            "Content-Security-Policy",
+            &item_metadata.content_security_policy,
+            &cli.content_security_policy,
+        ),
+        (
+            "X-Frame-Options",
+            &item_metadata.frame_options,
+            &cli.frame_options,
+        ),
+        (
+            "Referrer-Policy",
+            &item_metadata.referrer_policy,
+            &cli.referrer_policy,
+        ),
+    ]
+    .into_iter()
+    .map(|(header, value, default)| (header, value.as_ref().unwrap_or(default)))
+    .filter(|(_, value)| !value.is_empty())
+    .map(|(header, value)| (header, value.clone()))
+    .collect()
+}
+
+/// Tries to find an object, asking the Samizdat network if necessary.
+pub async fn resolve_object(
+    object: ObjectRef,
+    riddles: Option<usize>,
+    conditions: &Conditions,
+    ext_headers: impl IntoIterator<Item = (&'static str, String)>,
+) -> Result<Result<Response<Body>, http::Error>, crate::Error> {
+    content_filter::check_object(&object)?;
+    let locator = format!("/_objects/{}", object.hash());
+    resolve_object_with(
+        object,
+        locator,
+        riddles,
+        conditions,
+        ext_headers,
+        None,
+        None,
+    )
+    .await
+}
+
+/// Tries to find an object, asking the Samizdat network if necessary, and serves it according
+/// to the metadata of the item it came from, if any. Clients that already have the object get
+/// a `304 Not Modified` without the object being looked for. The locator tells what was asked
+/// for, to be signed with the object (see [`signing`]).
+async fn resolve_object_with(
+    object: ObjectRef,
+    locator: String,
+    riddles: Option<usize>,
+    conditions: &Conditions,
+    ext_headers: impl IntoIterator<Item = (&'static str, String)>,
+    item_metadata: Option<ItemMetadata>,
+    last_modified: Option<DateTime<Utc>>,
+) -> Result<Result<Response<Body>, http::Error>, crate::Error> {
+    log::info!("Resolving {object:?}");
+    let item_metadata = item_metadata.unwrap_or_default();
+    let etag = etag(&object);
+
+    if conditions.is_cached(&etag, last_modified) {
+        log::info!("Client already has {object:?}");
+        let not_modified = NotModified {
+            etag,
+            last_modified,
+            ext_headers: ext_headers
+                .into_iter()
+                .chain(item_metadata.headers())
+                .chain(security_headers(&item_metadata))
+                .chain([("X-Samizdat-Object", object.hash().to_string())])
+                .collect(),
+        };
+
+        return Ok(not_modified.try_into());
+    }
+
+    let iter = if let Some(iter) = object.iter_skip_header()? {
+        log::info!("Found local hash {}", object.hash());
+        Some(iter)
+    } else {
+        log::info!("Hash {} not found locally. Querying hubs", object.hash());
+        hubs()
+            .query_with(*object.hash(), QueryKind::Object, riddles)
+            .await;
+        object.iter_skip_header()?
+    };
+
+    // Respond with found or not found.
+    if let Some((metadata, mut iter)) = object.metadata()?.zip(iter) {
+        object.touch()?;
+
+        // The content size in the metadata includes the header:
+        let content_size = metadata.content_size - metadata.header.buffer().len();
+        let range = match conditions.range(&etag, content_size) {
+            Some(Ok(range)) => Some(range),
+            Some(Err(())) => return Ok(RangeNotSatisfiable { content_size }.try_into()),
+            None => None,
+        };
+
+        let signature_headers = if cli().sign_responses {
+            signing::signature_headers(&object, locator, range).await?
+        } else {
+            vec![]
+        };
+
+        let body = if let Some((start, end)) = range {
+            iter.skip_bytes(start)?;
+            Body::wrap_stream(stream::iter(
+                crate::utils::chunks(1000, iter.take(end - start + 1))
+                    .map(|thing| thing.map_err(|err| err.to_string())),
+            ))
+        } else {
+            Body::wrap_stream(stream::iter(
+                crate::utils::chunks(1000, iter).map(|thing| thing.map_err(|err| err.to_string())),
+            ))
+        };
+
+        let resolved = Resolved {
+            content_type: item_metadata.content_type(metadata.header.content_type()),
+            content_size: metadata.content_size,
+            content_range: range.map(|(start, end)| format!("bytes {start}-{end}/{content_size}")),
+            etag,
+            last_modified,
+            ext_headers: ext_headers
+                .into_iter()
+                .chain(item_metadata.headers())
+                .chain(security_headers(&item_metadata))
+                .chain([
+                    ("X-Samizdat-Bookmark", object.is_bookmarked()?.to_string()),
+                    (
+                        "X-Samizdat-Is-Draft",
+                        metadata.header.is_draft().to_string(),
+                    ),
+                    ("X-Samizdat-Object", object.hash().to_string()),
+                ])
+                .chain(signature_headers)
+                .collect(),
+            body,
+        };
+
+        Ok(resolved.try_into())
+    } else {
+        let not_resolved =
+            NotResolved::not_found(format!("Object {}", object.hash()), object.hash());
+
+        Ok(not_resolved.try_into())
+    }
+}
+
+/// Tries to find an object as a collection item, asking the Samizdat network if
+/// necessary.
+pub async fn resolve_item(
+    locator: Locator<'_>,
+    riddles: Option<usize>,
+    conditions: &Conditions,
+    ext_headers: impl IntoIterator<Item = (&'static str, String)>,
+) -> Result<Result<Response<Body>, http::Error>, crate::Error> {
+    log::info!("Resolving item {locator}");
+
+    let maybe_item = if let Some(item) = locator.get()? {
+        log::info!("Found item {locator} locally. Resolving object.");
+        Some(item)
+    } else {
+        log::info!("Item not found locally. Querying hubs.");
+        hubs()
+            .query_with(locator.hash(), QueryKind::Item, riddles)
+            .await;
+
+        locator.get()?
+    };
+
+    let maybe_item = match maybe_item {
+        Some(item) => Some(item),
+        None => find_mounted_item(locator.collection(), locator.name(), riddles).await?,
+    };
+
+    if let Some(item) = maybe_item {
+        content_filter::check(&item.collection).await?;
+        let found = item.locator();
+
+        resolve_object_with(
+            item.object()?,
+            format!(
+                "/_collections/{}/{}",
+                locator.collection().hash(),
+                locator.name()
+            ),
+            riddles,
+            conditions,
+            ext_headers.into_iter().chain([(
+                "X-Samizdat-Collection",
+                found.collection().hash().to_string(),
+            )]),
+            found.collection().item_metadata(found.name())?,
+            None,
+        )
+        .await
+    } else {
+        let not_resolved = NotResolved::not_found(format!("Item {locator}"), &locator.hash())
+            .with_page(&locator.collection(), riddles)
+            .await;
+
+        Ok(not_resolved.try_into())
+    }
+}
+
+/// The maximum number of mounts followed when looking for an item.
+const MAX_MOUNT_DEPTH: usize = 8;
+/// For how long the mounts of a collection are only resolved from local data after the network
+/// was asked about them.
+const MOUNT_LOOKUP_INTERVAL: Duration = Duration::from_secs(60);
+/// The maximum number of collections whose mounts were recently looked up in the network. Past
+/// this, mounts are only resolved from local data.
+const MAX_MOUNT_LOOKUPS: usize = 4_096;
+
+lazy_static! {
+    /// When the network was last asked about the mounts of each collection.
+    static ref MOUNT_LOOKUPS: Mutex<BTreeMap<Hash, Instant>> = Mutex::default();
+}
+
+/// Whether the network may be asked about the mounts of a collection, i.e., for its inventory
+/// and for the latest editions of the series it mounts. Otherwise, every miss in a collection
+/// would go to the network.
+fn may_look_up_mounts(collection: &CollectionRef) -> bool {
+    let mut lookups = MOUNT_LOOKUPS.lock().expect("poisoned");
+    lookups.retain(|_, looked_up| looked_up.elapsed() < MOUNT_LOOKUP_INTERVAL);
+
+    if lookups.contains_key(&collection.hash()) || lookups.len() >= MAX_MOUNT_LOOKUPS {
+        return false;
+    }
+
+    lookups.insert(collection.hash(), Instant::now());
+    true
+}
+
+/// Gets an item from the local database or, if not present, from the network.
+async fn get_item(
+    locator: &Locator<'_>,
+    riddles: Option<usize>,
+) -> Result<Option<CollectionItem>, crate::Error> {
+    if let Some(item) = locator.get()? {
+        return Ok(Some(item));
+    }
+
+    hubs()
+        .query_with(locator.hash(), QueryKind::Item, riddles)
+        .await;
+
+    locator.get()
+}
+
+/// Looks for an item that is not in a collection in the collections mounted in it, following
+/// the mounts of mounted collections as well. Mounts that lead back to a collection already
+/// visited are not followed. The network is only asked about mounts now and then (see
+/// [`may_look_up_mounts`]); otherwise, they are resolved from local data.
+async fn find_mounted_item(
+    mut collection: CollectionRef,
+    name: ItemPath<'_>,
+    riddles: Option<usize>,
+) -> Result<Option<CollectionItem>, crate::Error> {
+    let mut name = ItemPathBuf::from(name.as_str());
+    let mut visited = BTreeSet::new();
+
+    loop {
+        if !visited.insert(collection.hash()) {
+            log::warn!("Mount cycle found at collection {}", collection.hash());
+            return Ok(None);
+        }
+
+        if visited.len() > MAX_MOUNT_DEPTH {
+            log::warn!("Too many nested mounts resolving {name}");
+            return Ok(None);
+        }
+
+        let ask_network = may_look_up_mounts(&collection);
+
+        // Mounts are listed in the `_inventory` item, which might not be here yet:
+        if ask_network {
+            let inventory_locator = collection.locator_for("_inventory".into());
+            if get_item(&inventory_locator, riddles).await?.is_none() {
+                return Ok(None);
+            }
+        }
+
+        let Some(inventory) = collection.inventory_index()? else {
+            return Ok(None);
+        };
+        let Some((mount, rest)) = inventory.mount_for(&name) else {
+            return Ok(None);
+        };
+
+        log::info!("Item {name} is mounted from {mount} as {rest}");
+
+        let mounted = match mount {
+            Mount::Collection(hash) => CollectionRef::new(*hash),
+            Mount::Series(key) => {
+                let series = SeriesRef::new(key.clone());
+                if ask_network {
+                    ensure_fresh(&series).await?;
+                }
+
+                match latest_collection(&series)? {
+                    Some(collection) => collection,
+                    None => return Ok(None),
+                }
+            }
+        };
+
+        let locator = mounted.locator_for(rest.as_str().into());
+        if let Some(item) = get_item(&locator, riddles).await? {
+            return Ok(Some(item));
+        }
+
+        collection = mounted;
+        name = rest;
+    }
+}
+
+/// Asks the network for the latest edition of a series, if the series is not fresh.
+pub async fn ensure_fresh(series: &SeriesRef) -> Result<(), crate::Error> {
+    log::info!("Ensuring series {series} is fresh");
+
+    // Replicas see the series as fresh as the primary keeps them:
+    if crate::db::is_replica() {
+        return Ok(());
+    }
+
+    if !series.is_fresh()? {
+        log::info!("Series is not fresh. Asking the network...");
+        if let Some(latest) = hubs().get_latest(series).await {
+            log::info!("Found an edition (new or existing): {latest:?}. Inserting");
+            series.advance(&latest)?;
+            series.refresh()?;
+        } else {
+            log::info!("No edition returned from the network for series {series}. Does it exist?");
+            series.mark_delayed()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Tries to find an object as an item the collection corresponding to the latest
+/// version of a series, asking the Samizdat network (through the hubs of the route of the
+/// series) if necessary.
+pub async fn resolve_series(
+    series: SeriesRef,
+    name: ItemPath<'_>,
+    riddles: Option<usize>,
+    conditions: &Conditions,
+    ext_headers: impl IntoIterator<Item = (&'static str, String)>,
+) -> Result<Result<Response<Body>, http::Error>, crate::Error> {
+    routing::scope(
+        &SeriesRef::new(series.public_key()),
+        resolve_series_routed(series, name, riddles, conditions, ext_headers),
+    )
+    .await
+}
+
+/// The collection of the latest edition of a series known to this node, or the collection it
+/// unsealed, if it is the key edition of a time-locked edition.
+pub(super) fn latest_collection(series: &SeriesRef) -> Result<Option<CollectionRef>, crate::Error> {
+    let Some(edition) = series.get_editions()?.into_iter().next() else {
+        return Ok(None);
+    };
+
+    Ok(Some(
+        time_lock::unsealed(&edition.collection())?.unwrap_or_else(|| edition.collection()),
+    ))
+}
+
+async fn resolve_series_routed(
+    series: SeriesRef,
+    name: ItemPath<'_>,
+    riddles: Option<usize>,
+    conditions: &Conditions,
+    ext_headers: impl IntoIterator<Item = (&'static str, String)>,
+) -> Result<Result<Response<Body>, http::Error>, crate::Error> {
+    log::info!("Resolving series item {series}/{name}");
+
+    ensure_fresh(&series).await?;
+
+    let not_resolved =
+        match find_series_item(&series, name.clone(), riddles, conditions, ext_headers).await {
+            Ok(Some(resolved)) => return Ok(resolved),
+            Ok(None) => NotResolved::new(format!("Item {series}/{name} not found")),
+            Err(err) => NotResolved::from_error(&err),
+        };
+
+    // Failures are shown with the error pages of the latest edition, if any:
+    let not_resolved = match latest_collection(&series)? {
+        Some(collection) => not_resolved.with_page(&collection, riddles).await,
+        None => not_resolved,
+    };
+
+    Ok(not_resolved.try_into())
+}
+
+/// Tries to find an item in each edition of a series, latest first.
+async fn find_series_item(
+    series: &SeriesRef,
+    name: ItemPath<'_>,
+    riddles: Option<usize>,
+    conditions: &Conditions,
+    ext_headers: impl IntoIterator<Item = (&'static str, String)>,
+) -> Result<Option<Result<Response<Body>, http::Error>>, crate::Error> {
+    log::info!("Trying to find path in each edition");
+    let mut empty = true;
+
+    for edition in series.get_editions()? {
+        empty = false;
+
+        // Key editions of time-locked editions stand for the collection they unseal:
+        let maybe_unsealed = time_lock::unsealed(&edition.collection())?;
+        let is_unsealed = maybe_unsealed.is_some();
+        let mut collection = maybe_unsealed.unwrap_or_else(|| edition.collection());
+
+        log::info!("Trying collection {collection:?}");
+        let mut locator = collection.locator_for(name.clone());
+
+        let mut maybe_item = if let Some(item) = locator.get()? {
+            log::info!("Found item {locator} locally. Resolving object.");
+            Some(item)
+        } else {
+            log::info!("Item not found locally. Querying hubs.");
+            hubs()
+                .query_with(locator.hash(), QueryKind::Item, riddles)
+                .await;
+
+            locator.get()?
+        };
+
+        // Only editions known to be key editions are unsealed, which never goes to the network
+        // for editions that are not:
+        if maybe_item.is_none() && !is_unsealed && time_lock::is_key_collection(&collection)? {
+            match time_lock::unseal(&collection).await {
+                Ok(Some(unsealed)) => {
+                    log::info!("Edition {edition:?} unsealed {unsealed:?}. Trying it");
+                    collection = unsealed;
+                    locator = collection.locator_for(name.clone());
+                    maybe_item = locator.get()?;
+                }
+                Ok(None) => {}
+                Err(err) => log::warn!("failed to unseal edition {edition:?}: {err}"),
+            }
+        }
+
+        if maybe_item.is_none() {
+            maybe_item = find_mounted_item(collection.clone(), name.clone(), riddles).await?;
+        }
+
+        if let Some(item) = maybe_item {
+            content_filter::check(&item.collection).await?;
+            let found = item.locator();
+
+            return resolve_object_with(
+                item.object()?,
+                format!("/_series/{}/{name}", series.public_key()),
+                riddles,
+                conditions,
+                ext_headers.into_iter().chain([
+                    (
+                        "X-Samizdat-Collection",
+                        found.collection().hash().to_string(),
+                    ),
+                    ("X-Samizdat-Series", series.public_key().to_string()),
+                    (
+                        "X-Samizdat-Trust",
+                        SeriesTrustRef::new(series.public_key())
+                            .public_level()?
+                            .to_string(),
+                    ),
+                ]),
+                found.collection().item_metadata(found.name())?,
+                Some(edition.timestamp()),
+            )
+            .await
+            .map(Some);
+        }
+    }
+
+    if empty {
+        log::info!("No local editions found for series {series}");
+    }
+
+    Ok(None)
+}
+
+pub async fn resolve_identity(
+    identity_ref: IdentityRef,
+    name: ItemPath<'_>,
+    riddles: Option<usize>,
+    conditions: &Conditions,
+    ext_headers: impl IntoIterator<Item = (&'static str, String)>,
+) -> Result<Result<Response<Body>, http::Error>, crate::Error> {
+    log::info!("Resolving identity {identity_ref}/{name}");
+
+    let identity = if let Some(identity) = identity_ref.get()? {
+        log::info!("Fond identity {identity_ref} locally. Resolving series.");
+        identity
+    } else {
+        log::info!("Identity not found locally. Querying hubs.");
+        if let Some(identity) = hubs().get_identity(&identity_ref).await {
+            let mut batch = WriteBatch::default();
+            identity.insert(&mut batch);
+            crate::db().write(batch)?;
+
+            identity
+        } else {
+            let not_resolved = NotResolved::new(format!("Identity {identity_ref} not found"));
+
+            return Ok(not_resolved.try_into());
+        }
+    };
+
+    resolve_series(identity.series(), name, riddles, conditions, ext_headers).await
+}
+
+#[test]
+fn parses_ranges() {
+    assert_eq!(parse_range("bytes=0-99", 1000), Some(Ok((0, 99))));
+    assert_eq!(parse_range("bytes=900-", 1000), Some(Ok((900, 999))));
+    assert_eq!(parse_range("bytes=-100", 1000), Some(Ok((900, 999))));
+    assert_eq!(parse_range("bytes=500-5000", 1000), Some(Ok((500, 999))));
+    assert_eq!(parse_range("bytes=1000-", 1000), Some(Err(())));
+    assert_eq!(parse_range("bytes=0-1,5-9", 1000), None);
+    assert_eq!(parse_range("items=0-1", 1000), None);
+}
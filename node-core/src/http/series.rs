@@ -0,0 +1,336 @@
+use futures::{SinkExt, StreamExt};
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use warp::path::Tail;
+use warp::ws::{Message, WebSocket, Ws};
+use warp::Filter;
+
+use samizdat_common::{Hash, Key};
+
+use crate::access::AccessRight;
+use crate::db::PageQuery;
+use crate::events::{self, Event};
+use crate::models::{CollectionRef, Droppable, SeriesOwner, SeriesRef};
+use crate::{balanced_or_tree, readership, time_lock};
+
+use super::offline_bundle;
+use super::resolvers::{ensure_fresh, resolve_series, Conditions};
+use super::{api_reply, authenticate, conditions, page_query, page_reply, riddles, tuple};
+
+/// The entrypoint of the series API.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        get_series_editions(),
+        get_series_live(),
+        offline_bundle::get_service_worker(),
+        offline_bundle::get_manifest(),
+        get_edition_item(),
+        get_series_owner(),
+        get_series_owners(),
+        get_series_readership(),
+        post_series_owner(),
+        post_attestation(),
+        delete_series_owner(),
+        post_edition(),
+        get_all_series(),
+    )
+}
+
+/// Creates a new series owner, i.e., a public-private keypair that allows one to push new
+/// collections to a series.
+fn post_series_owner() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    #[derive(Deserialize)]
+    struct Keypair {
+        public_key: String,
+        private_key: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Request {
+        series_owner_name: String,
+        #[serde(default)]
+        keypair: Option<Keypair>,
+        #[serde(default)]
+        is_draft: bool,
+    }
+
+    warp::path!("_seriesowners")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .and(warp::body::json())
+        .map(|request: Request| {
+            let series_owner = if let Some(Keypair {
+                public_key,
+                private_key,
+            }) = request.keypair
+            {
+                SeriesOwner::import(
+                    &request.series_owner_name,
+                    public_key.parse()?,
+                    private_key.parse()?,
+                    Duration::from_secs(3_600),
+                    request.is_draft,
+                )
+            } else {
+                SeriesOwner::create(
+                    &request.series_owner_name,
+                    Duration::from_secs(3_600),
+                    request.is_draft,
+                )
+            };
+
+            Ok(series_owner?)
+        })
+        .map(api_reply)
+}
+
+/// Removes a series owner
+fn delete_series_owner(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_seriesowners" / String)
+        .and(warp::delete())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .map(|series_owner_name: String| {
+            let maybe_owner = SeriesOwner::get(&series_owner_name)?;
+            let existed = maybe_owner
+                .map(|owner| owner.drop_if_exists())
+                .transpose()?
+                .is_some();
+            Ok(existed)
+        })
+        .map(api_reply)
+}
+
+/// Gets information associates with a series owner
+fn get_series_owner() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("_seriesowners" / String)
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .map(|series_owner_name: String| {
+            let maybe_owner = SeriesOwner::get(&series_owner_name)?;
+            Ok(maybe_owner.map(|owner| owner))
+        })
+        .map(api_reply)
+}
+
+/// Lists all series owners, optionally only the draft (or non-draft) ones.
+fn get_series_owners() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    #[derive(Deserialize)]
+    struct Query {
+        #[serde(default)]
+        is_draft: Option<bool>,
+    }
+
+    warp::path!("_seriesowners")
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .and(page_query())
+        .and(warp::query())
+        .map(|page: PageQuery, query: Query| SeriesOwner::get_page(&page, query.is_draft))
+        .map(page_reply)
+}
+
+/// Gets how many times each item of each edition of a series owner was served, by this node
+/// and by the mirrors reporting back to it.
+fn get_series_readership(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_seriesowners" / String / "readership")
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .map(|series_owner_name: String| {
+            let owner = SeriesOwner::get(&series_owner_name)?.ok_or_else(|| {
+                crate::Error::NotFound(format!("series owner {series_owner_name}"))
+            })?;
+            readership::readership(&owner)
+        })
+        .map(api_reply)
+}
+
+/// Signs, with the key of a series owner, an attestation of how an edition was built. The
+/// attestation is meant to be embedded in the collection of the edition.
+fn post_attestation() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    #[derive(Deserialize)]
+    struct Request {
+        command: String,
+        source: Hash,
+        toolchain: String,
+        outputs: Hash,
+    }
+
+    warp::path!("_seriesowners" / String / "attestations")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .and(warp::body::json())
+        .map(|series_owner_name: String, request: Request| {
+            let owner = SeriesOwner::get(&series_owner_name)?.ok_or_else(|| {
+                crate::Error::NotFound(format!("series owner {series_owner_name}"))
+            })?;
+            Ok(owner.attest(
+                request.command,
+                request.source,
+                request.toolchain,
+                request.outputs,
+            ))
+        })
+        .map(api_reply)
+}
+
+/// Pushes a new collection to the series owner, creating a new edition.
+fn post_edition() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        collection: String,
+        #[serde(default)]
+        #[serde(with = "humantime_serde")]
+        ttl: Option<std::time::Duration>,
+        #[serde(default)]
+        no_announce: bool,
+        /// Publishes the collection sealed, only to be unsealed at this time.
+        #[serde(default)]
+        release_at: Option<chrono::DateTime<chrono::Utc>>,
+    }
+
+    warp::path!("_seriesowners" / String / "editions")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .and(warp::body::json())
+        .map(|series_owner_name: String, request: Request| {
+            if let Some(series_owner) = SeriesOwner::get(&series_owner_name)? {
+                let mut collection = CollectionRef::new(request.collection.parse()?);
+
+                if let Some(release_at) = request.release_at {
+                    collection = time_lock::seal(&series_owner, &collection, release_at)?;
+                }
+
+                let edition = series_owner.advance(collection, request.ttl)?;
+
+                if !request.no_announce {
+                    series_owner.announce(&edition);
+                }
+
+                Ok(edition)
+            } else {
+                Err(crate::Error::NotFound(format!(
+                    "series owner {} not found",
+                    series_owner_name
+                )))
+            }
+        })
+        .map(api_reply)
+}
+
+/// Gets the content of a collection item using the series public key. This will give the
+/// best-effort latest version for this item.
+fn get_edition_item() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("_series" / Key / ..)
+        .and(warp::path::tail())
+        .and(warp::get())
+        .and(riddles())
+        .and(conditions())
+        .and_then(
+            |series_key: Key, name: Tail, riddles: Option<usize>, conditions: Conditions| async move {
+                let series = SeriesRef::new(series_key);
+                Ok(
+                    resolve_series(series, name.as_str().into(), riddles, &conditions, [])
+                        .await?,
+                ) as Result<_, warp::Rejection>
+            },
+        )
+        .map(tuple)
+}
+
+/// A public edition of a series.
+#[derive(Serialize)]
+struct EditionSummary {
+    collection: String,
+    timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Opens a WebSocket telling when a new edition of a series arrives, so that pages can refresh
+/// themselves. Each edition is sent as a text message with its [`EditionSummary`], in JSON.
+/// Like the editions themselves, this needs no authentication.
+fn get_series_live() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("_series" / Key / "_live")
+        .and(warp::get())
+        .and(warp::ws())
+        .map(|series_key: Key, ws: Ws| {
+            ws.on_upgrade(move |socket| live_series(socket, series_key.to_string()))
+        })
+}
+
+/// Sends the new editions of a series through a WebSocket, until the client goes away.
+async fn live_series(socket: WebSocket, series: String) {
+    let (mut sink, mut incoming) = socket.split();
+    let mut events = events::subscribe();
+
+    loop {
+        tokio::select! {
+            // Nothing is expected from the client, but pings are answered while reading:
+            message = incoming.next() => match message {
+                Some(Ok(message)) if !message.is_close() => {}
+                _ => break,
+            },
+            event = events.recv() => match event {
+                Ok(Event::EditionReceived { series: received, collection, timestamp })
+                    if received == series =>
+                {
+                    let summary = EditionSummary { collection, timestamp };
+                    let message = serde_json::to_string(&summary).expect("can serialize");
+
+                    if sink.send(Message::text(message)).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+
+    log::debug!("live connection to series {series} closed");
+}
+
+/// Lists the public editions of a series known to this node, latest first. Editions are
+/// announced to the whole network, so this needs no authentication.
+fn get_series_editions(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_series" / Key / "_editions")
+        .and(warp::get())
+        .and_then(|series_key: Key| async move {
+            let series = SeriesRef::new(series_key);
+            let editions = async {
+                ensure_fresh(&series).await?;
+                let summaries = series
+                    .get_editions()?
+                    .into_iter()
+                    .filter(|edition| !edition.is_draft())
+                    .map(|edition| EditionSummary {
+                        collection: edition.collection().hash().to_string(),
+                        timestamp: edition.timestamp(),
+                    })
+                    .collect::<Vec<_>>();
+
+                Ok(summaries)
+            };
+
+            Ok(editions.await) as Result<_, warp::Rejection>
+        })
+        .map(api_reply)
+}
+
+/// Lists all known public keys the node has seen, be they locally owned or not.
+fn get_all_series() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_series")
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .and(page_query())
+        .map(|query: PageQuery| SeriesRef::get_page(&query))
+        .map(page_reply)
+}
@@ -0,0 +1,95 @@
+use serde_derive::{Deserialize, Serialize};
+use warp::Filter;
+
+use samizdat_common::Key;
+
+use crate::access::AccessRight;
+use crate::balanced_or_tree;
+use crate::models::{Droppable, SeriesTrust, SeriesTrustRef, TrustLevel};
+
+use super::{api_reply, authenticate};
+
+/// The entrypoint of the series trust API. Since trust levels are shown to readers as proof
+/// of who publishes a series, only the access token can change them.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        get_all_series_trust(),
+        get_series_trust(),
+        put_series_trust(),
+        delete_series_trust(),
+    )
+}
+
+/// Sets the trust level of a series, replacing any existing one.
+fn put_series_trust() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    #[derive(Deserialize)]
+    struct Request {
+        level: TrustLevel,
+        #[serde(default)]
+        note: Option<String>,
+    }
+
+    warp::path!("_seriestrust" / Key)
+        .and(warp::put())
+        .and(authenticate([]))
+        .and(warp::body::json())
+        .map(|public_key: Key, request: Request| {
+            SeriesTrustRef::build(SeriesTrust {
+                public_key,
+                level: request.level,
+                note: request.note,
+                set_at: chrono::Utc::now(),
+            })?;
+
+            Ok(())
+        })
+        .map(api_reply)
+}
+
+/// Forgets the trust level set for a series, going back to the default one.
+fn delete_series_trust(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_seriestrust" / Key)
+        .and(warp::delete())
+        .and(authenticate([]))
+        .map(|public_key: Key| {
+            let trust_ref = SeriesTrustRef::new(public_key);
+            let existed = trust_ref.get()?.is_some();
+            trust_ref.drop_if_exists()?;
+            Ok(existed)
+        })
+        .map(api_reply)
+}
+
+/// Gets the trust level of a series, whether set by the user or not.
+fn get_series_trust() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    #[derive(Serialize)]
+    struct Response {
+        level: TrustLevel,
+        trust: Option<SeriesTrust>,
+    }
+
+    warp::path!("_seriestrust" / Key)
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .map(|public_key: Key| {
+            let trust_ref = SeriesTrustRef::new(public_key);
+            Ok(Response {
+                level: trust_ref.level()?,
+                trust: trust_ref.get()?,
+            })
+        })
+        .map(api_reply)
+}
+
+/// Lists the trust levels set by the user.
+fn get_all_series_trust(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_seriestrust")
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageSeries]))
+        .map(SeriesTrustRef::get_all)
+        .map(api_reply)
+}
@@ -0,0 +1,124 @@
+//! Signatures on the content served by this node, for public gateways. A gateway (e.g., the
+//! proxy) stands between the node and its users, so a compromised gateway could serve anything
+//! in the name of the node. With `--sign-responses`, the node signs each object it serves,
+//! together with what was asked for, its digest and the byte range served, with a key only the
+//! node knows. Users who pinned the key (see `/_signingkey`) can check what they got against the
+//! headers:
+//!
+//! - `X-Samizdat-Signed-Locator`: what was asked for, as the path of the node route for it,
+//!   e.g., `/_series/<key>/<path>`, so that a gateway cannot serve an object signed for one
+//!   path in place of another. Items of identities are signed as items of their series.
+//! - `X-Samizdat-Digest`: `sha-256=` followed by the base64url SHA-256 of the body.
+//! - `X-Samizdat-Signed-Range`: the byte range served, as in `Content-Range`.
+//! - `X-Samizdat-Signature`: the base64url Ed25519 signature, by the key of the node, of the
+//!   lines `samizdat-response`, the signed locator, the object hash, the signed range and the
+//!   digest, joined by `\n`.
+//! - `X-Samizdat-Signer`: the public key of the node.
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use sha2::{Digest, Sha256};
+use warp::Filter;
+
+use samizdat_common::Key;
+
+use crate::db::{db, Table};
+use crate::models::ObjectRef;
+
+use super::api_reply;
+
+/// The key under which the secret key of the node is kept in [`Table::Global`].
+const SIGNING_SECRET_KEY: &[u8] = b"response_signing_secret_key";
+
+/// The key with which this node signs its responses, created on first use.
+fn keypair() -> Result<Keypair, crate::Error> {
+    let secret = match db().get_cf(Table::Global.get(), SIGNING_SECRET_KEY)? {
+        Some(secret) => SecretKey::from_bytes(&secret)
+            .map_err(|err| format!("bad response signing key: {err}"))?,
+        None => {
+            let secret = SecretKey::generate(&mut rand::rngs::OsRng {});
+            db().put_cf(Table::Global.get(), SIGNING_SECRET_KEY, secret.as_bytes())?;
+            secret
+        }
+    };
+    let public = PublicKey::from(&secret);
+
+    Ok(Keypair { secret, public })
+}
+
+/// The digest of an object, as served, and the number of bytes served.
+fn digest(
+    object: &ObjectRef,
+    range: Option<(usize, usize)>,
+) -> Result<(String, usize), crate::Error> {
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+
+    if let Some(mut iter) = object.iter_skip_header()? {
+        let (start, end) = range.unwrap_or((0, usize::MAX - 1));
+        iter.skip_bytes(start)?;
+
+        for chunk in crate::utils::chunks(4096, iter.take(end - start + 1)) {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            size += chunk.len();
+        }
+    }
+
+    let digest = format!("sha-256={}", base64_url::encode(&hasher.finalize()));
+
+    Ok((digest, size))
+}
+
+/// The headers signing an object served for a locator, skipping the object header, either
+/// whole or only the given byte range of it (inclusive on both ends). This reads what is served
+/// once more, off the async runtime.
+pub async fn signature_headers(
+    object: &ObjectRef,
+    locator: String,
+    range: Option<(usize, usize)>,
+) -> Result<Vec<(&'static str, String)>, crate::Error> {
+    let digested = object.clone();
+    let (digest, size) = tokio::task::spawn_blocking(move || digest(&digested, range))
+        .await
+        .map_err(|err| format!("signing task panicked: {err}"))??;
+
+    let range = match range {
+        Some((start, end)) => {
+            let total = object
+                .metadata()?
+                .map(|metadata| metadata.content_size - metadata.header.buffer().len())
+                .unwrap_or_default();
+            format!("bytes {start}-{end}/{total}")
+        }
+        None if size == 0 => "bytes */0".to_owned(),
+        None => format!("bytes 0-{}/{size}", size - 1),
+    };
+    let message = format!(
+        "samizdat-response\n{locator}\n{}\n{range}\n{digest}",
+        object.hash()
+    );
+
+    let keypair = keypair()?;
+    let signature = keypair.sign(message.as_bytes());
+
+    Ok(vec![
+        ("X-Samizdat-Signed-Locator", locator),
+        ("X-Samizdat-Digest", digest),
+        ("X-Samizdat-Signed-Range", range),
+        (
+            "X-Samizdat-Signature",
+            base64_url::encode(&signature.to_bytes()),
+        ),
+        ("X-Samizdat-Signer", Key::new(keypair.public).to_string()),
+    ])
+}
+
+/// The public key with which this node signs its responses. This is public, but users of a
+/// gateway should get it from a source they trust, not through the gateway.
+pub fn get_signing_key(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_signingkey")
+        .and(warp::get())
+        .map(|| Ok(Key::new(keypair()?.public).to_string()))
+        .map(api_reply)
+}
@@ -0,0 +1,196 @@
+//! Serving each entity on its own subdomain of `localhost` (e.g.,
+//! `series-{key}.localhost:4510`), so that the same-origin policy of browsers isolates
+//! Samizdat apps from each other and from the node API, instead of all of them sharing the
+//! origin of the node. Browsers resolve any subdomain of `localhost` to the loopback.
+//!
+//! Hostnames are case-insensitive and labels hold at most 63 characters, so keys and hashes go
+//! in subdomains in lowercase base32, not in base64url as in paths. On an entity subdomain,
+//! paths starting with `_` are the node API, as seen by the entity (see
+//! [`super::auth::security_scope`]); all other paths are content of the entity.
+
+use url::Url;
+
+use crate::access::Entity;
+use crate::cli;
+
+/// The RFC 4648 base32 alphabet, in lowercase.
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// The kinds of entity served on subdomains, with the length of their identifiers in bytes.
+const ENTITY_KINDS: [(&str, usize); 3] = [("series", 32), ("collections", 28), ("objects", 28)];
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u16;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for ch in encoded.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&symbol| symbol == ch.to_ascii_lowercase())?;
+        buffer = (buffer << 5) | value as u16;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+/// Whether entities are served on their own subdomains (see `--isolate-origins`).
+pub fn is_enabled() -> bool {
+    cli().isolate_origins
+}
+
+/// The entity path (e.g., `/_series/{key}`) served on a host, if the host is an entity
+/// subdomain.
+fn entity_path_for_host(host: &str) -> Option<String> {
+    let label = host.strip_suffix(".localhost")?;
+    let (kind, encoded) = label.split_once('-')?;
+    let &(kind, len) = ENTITY_KINDS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(kind))?;
+    let identifier = base32_decode(encoded)?;
+
+    if identifier.len() != len {
+        return None;
+    }
+
+    Some(format!("/_{kind}/{}", base64_url::encode(&identifier)))
+}
+
+/// The entity served on a host, if the host is an entity subdomain and entities are served on
+/// subdomains.
+pub fn entity_for_host(host: &str) -> Option<Entity> {
+    if !is_enabled() {
+        return None;
+    }
+
+    Entity::from_path(&entity_path_for_host(host)?)
+}
+
+/// The subdomain serving the entity at the start of a path and the rest of the path, if the
+/// path is of an entity served on subdomains.
+fn subdomain_for_path(path: &str) -> Option<(String, &str)> {
+    let mut split = path.trim_start_matches('/').splitn(3, '/');
+    let kind = split.next()?.strip_prefix('_')?;
+    let identifier = split.next()?;
+    let rest = match split.next() {
+        Some(rest) => &path[path.len() - rest.len() - 1..],
+        None => "/",
+    };
+    let &(kind, len) = ENTITY_KINDS.iter().find(|(known, _)| *known == kind)?;
+    let identifier = base64_url::decode(identifier).ok()?;
+
+    if identifier.len() != len {
+        return None;
+    }
+
+    Some((
+        format!("{kind}-{}.localhost", base32_encode(&identifier)),
+        rest,
+    ))
+}
+
+/// The host of a request, without the port.
+fn host_of<B>(request: &hyper::Request<B>) -> Option<&str> {
+    let host = request
+        .headers()
+        .get(http::header::HOST)
+        .and_then(|value| value.to_str().ok())?;
+
+    Some(host.rsplit_once(':').map(|(host, _)| host).unwrap_or(host))
+}
+
+/// Rewrites a request made to an entity subdomain into a request for the content of the
+/// entity, unless it is for the node API.
+pub fn rewrite<B>(request: &mut hyper::Request<B>) {
+    let Some(entity_path) = host_of(request).and_then(entity_path_for_host) else {
+        return;
+    };
+
+    if request.uri().path().starts_with("/_") {
+        return;
+    }
+
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/");
+    let rewritten = format!("{entity_path}{path_and_query}");
+
+    match rewritten.parse() {
+        Ok(uri) => *request.uri_mut() = uri,
+        Err(err) => log::warn!("cannot rewrite {rewritten:?} for entity subdomain: {err}"),
+    }
+}
+
+/// Where to send a browser navigating to an entity by its path on the bare `localhost`, so
+/// that the entity is seen from its own origin. Only navigations are redirected: apps and
+/// clients fetching content by path keep working as ever.
+pub fn redirect_for<B>(request: &hyper::Request<B>) -> Option<Url> {
+    let is_navigation = request
+        .headers()
+        .get("Sec-Fetch-Mode")
+        .is_some_and(|mode| mode == "navigate");
+
+    if !is_navigation || request.method() != http::Method::GET {
+        return None;
+    }
+
+    if host_of(request).and_then(entity_path_for_host).is_some() {
+        return None;
+    }
+
+    let (subdomain, rest) = subdomain_for_path(request.uri().path())?;
+    let mut url = Url::parse(&format!("http://{subdomain}:{}/", cli().port)).ok()?;
+    url.set_path(rest);
+    url.set_query(request.uri().query());
+
+    Some(url)
+}
+
+#[test]
+fn subdomains_round_trip() {
+    let key = samizdat_common::Key::from(
+        ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng {}).public,
+    );
+    let path = format!("/_series/{key}/index.html");
+
+    let (subdomain, rest) = subdomain_for_path(&path).expect("is entity path");
+    assert!(subdomain.len() - ".localhost".len() <= 63);
+    assert_eq!(subdomain, subdomain.to_ascii_lowercase());
+    assert_eq!(rest, "/index.html");
+    assert_eq!(
+        entity_path_for_host(&subdomain),
+        Some(format!("/_series/{key}"))
+    );
+    assert_eq!(entity_path_for_host("localhost"), None);
+    assert_eq!(entity_path_for_host("series-abc.localhost"), None);
+}
@@ -5,9 +5,10 @@ use samizdat_common::Key;
 
 use crate::access::AccessRight;
 use crate::balanced_or_tree;
+use crate::db::PageQuery;
 use crate::models::{Droppable, Subscription, SubscriptionKind, SubscriptionRef};
 
-use super::{api_reply, authenticate};
+use super::{api_reply, authenticate, page_query, page_reply};
 
 /// The entrypoint of the subscriptions API.
 pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
@@ -16,6 +17,7 @@ pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejecti
         get_subscriptions(),
         post_subscription(),
         delete_subscription(),
+        get_mirror_status(),
     )
 }
 
@@ -34,11 +36,25 @@ fn post_subscription() -> impl Filter<Extract = (impl warp::Reply,), Error = war
         .and(authenticate([AccessRight::ManageSubscriptions]))
         .and(warp::body::json())
         .map(|request: Request| {
-            let subscription = SubscriptionRef::build(Subscription::new(
-                request.public_key.parse()?,
-                request.kind,
-            ));
-            Ok(subscription?.public_key.to_string())
+            let public_key: Key = request.public_key.parse()?;
+            let previous = SubscriptionRef::new(public_key.clone()).get()?;
+            let subscription = SubscriptionRef::build(Subscription::new(public_key, request.kind))?;
+
+            if previous.is_some_and(|previous| previous.kind() == SubscriptionKind::Mirror)
+                && request.kind != SubscriptionKind::Mirror
+            {
+                subscription.unmirror()?;
+            }
+
+            // Mirroring is (re)started on every update, since editions may be missing:
+            if request.kind == SubscriptionKind::Mirror {
+                let subscription = SubscriptionRef::new(subscription.public_key.clone());
+                crate::tasks::spawn(format!("mirror {subscription}"), async move {
+                    subscription.mirror().await
+                });
+            }
+
+            Ok(subscription.public_key.to_string())
         })
         .map(api_reply)
 }
@@ -51,9 +67,17 @@ fn delete_subscription(
         .and(authenticate([AccessRight::ManageSubscriptions]))
         .map(|public_key: Key| {
             let subscription = SubscriptionRef::new(public_key);
-            let existed = subscription.get()?.is_some();
+            let existing = subscription.get()?;
+
+            if existing
+                .as_ref()
+                .is_some_and(|existing| existing.kind() == SubscriptionKind::Mirror)
+            {
+                subscription.unmirror()?;
+            }
+
             subscription.drop_if_exists()?;
-            Ok(existed)
+            Ok(existing.is_some())
         })
         .map(api_reply)
 }
@@ -71,15 +95,31 @@ fn get_subscription() -> impl Filter<Extract = (impl warp::Reply,), Error = warp
         .map(api_reply)
 }
 
-/// Gets information associates with a series owner
+/// Lists the subscriptions, optionally only of a given kind.
 fn get_subscriptions() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
 {
+    #[derive(Deserialize)]
+    struct Query {
+        #[serde(default)]
+        kind: Option<SubscriptionKind>,
+    }
+
     warp::path!("_subscriptions")
         .and(warp::get())
         .and(authenticate([AccessRight::ManageSubscriptions]))
-        .map(|| {
-            let subscriptions = SubscriptionRef::get_all()?;
-            Ok(subscriptions)
-        })
+        .and(page_query())
+        .and(warp::query())
+        .map(|page: PageQuery, query: Query| SubscriptionRef::get_page(&page, query.kind))
+        .map(page_reply)
+}
+
+/// Shows how much of each known edition of a series is present locally. This is most useful
+/// for mirror subscriptions.
+fn get_mirror_status() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("_subscriptions" / Key / "mirror")
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageSubscriptions]))
+        .map(|public_key: Key| SubscriptionRef::new(public_key).completeness())
         .map(api_reply)
 }
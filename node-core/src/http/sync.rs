@@ -0,0 +1,111 @@
+use futures::stream;
+use serde_derive::Deserialize;
+use warp::Filter;
+
+use samizdat_common::Hash;
+
+use crate::balanced_or_tree;
+use crate::db::{db, Table};
+use crate::models::{CollectionItem, ObjectRef};
+
+use super::{api_reply, authenticate};
+
+/// The differential sync API. All routes need the access token: only trusted nodes can sync
+/// from one another.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        get_summary(),
+        post_buckets(),
+        get_object(),
+        get_item(),
+        post_sync(),
+    )
+}
+
+/// Gets the summary of the content of this node.
+fn get_summary() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_sync" / "summary")
+        .and(warp::get())
+        .and(authenticate([]))
+        .map(crate::sync::summary)
+        .map(api_reply)
+}
+
+/// Lists the entries of this node in the requested buckets.
+fn post_buckets() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_sync" / "buckets")
+        .and(warp::post())
+        .and(authenticate([]))
+        .and(warp::body::json())
+        .map(|buckets: Vec<usize>| crate::sync::bucket_entries(&buckets))
+        .map(api_reply)
+}
+
+/// Gets the raw content of an object, header included, so that it can be copied verbatim. The
+/// content is streamed a chunk at a time.
+fn get_object() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_sync" / "objects" / Hash)
+        .and(warp::get())
+        .and(authenticate([]))
+        .map(|hash: Hash| {
+            let object = ObjectRef::new(hash);
+            let content = object.metadata().and_then(|metadata| {
+                Ok(metadata.zip(object.chunks()?).map(|(metadata, chunks)| {
+                    (
+                        metadata.content_size,
+                        chunks.map(|chunk| chunk.map_err(|err| err.to_string())),
+                    )
+                }))
+            });
+
+            match content {
+                Ok(Some((content_size, chunks))) => http::Response::builder()
+                    .header("Content-Type", "application/octet-stream")
+                    .header("Content-Length", content_size)
+                    .body(hyper::Body::wrap_stream(stream::iter(chunks))),
+                Ok(None) => http::Response::builder()
+                    .status(http::StatusCode::NOT_FOUND)
+                    .body(hyper::Body::from(format!("Object {hash} not found"))),
+                Err(err) => http::Response::builder()
+                    .status(http::StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(hyper::Body::from(err.to_string())),
+            }
+        })
+}
+
+/// Gets a collection item, by the hash of its locator.
+fn get_item() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_sync" / "items" / Hash)
+        .and(warp::get())
+        .and(authenticate([]))
+        .map(|hash: Hash| {
+            let item: CollectionItem = db()
+                .get_cf(Table::CollectionItems.get(), hash)?
+                .map(|item| bincode::deserialize(&item))
+                .transpose()?
+                .ok_or_else(|| format!("item {hash} not found"))?;
+
+            Ok(item) as Result<_, crate::Error>
+        })
+        .map(api_reply)
+}
+
+/// Copies everything a peer node has that this node has not.
+fn post_sync() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        /// The base URL of the HTTP API of the peer.
+        peer: String,
+        /// The access token of the peer.
+        token: String,
+    }
+
+    warp::path!("_sync")
+        .and(warp::post())
+        .and(authenticate([]))
+        .and(warp::body::json())
+        .and_then(|request: Request| async move {
+            Ok(crate::sync::sync(&request.peer, &request.token).await) as Result<_, warp::Rejection>
+        })
+        .map(api_reply)
+}
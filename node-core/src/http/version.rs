@@ -0,0 +1,100 @@
+//! Versions of the HTTP API. A client asks for a version with a `/v{N}/` prefix in the path,
+//! with the `X-Samizdat-Api-Version` header or with both, as long as they agree. Routes without
+//! either are served as version 1, the API as it was before versions, so that links and apps
+//! from back then keep working. The version served is always in the response header.
+//!
+//! A `/v{N}/` prefix is only taken for a version when an API route (starting with `_`)
+//! follows. Otherwise, as in `/v1/index.html`, it is the name of an identity.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use warp::path::Peek;
+use warp::Filter;
+
+/// The header with the version of the API asked for and served.
+pub const API_VERSION_HEADER: &str = "X-Samizdat-Api-Version";
+
+/// The oldest version of the API still served.
+const OLDEST_VERSION: u32 = 1;
+/// The current version of the API.
+const LATEST_VERSION: u32 = 1;
+
+/// A `/v{N}/` prefix in the path.
+struct VersionPrefix(u32);
+
+impl FromStr for VersionPrefix {
+    type Err = ();
+    fn from_str(s: &str) -> Result<VersionPrefix, ()> {
+        s.strip_prefix('v')
+            .and_then(|version| version.parse().ok())
+            .map(VersionPrefix)
+            .ok_or(())
+    }
+}
+
+/// The version asked for cannot be served.
+#[derive(Debug)]
+pub enum UnsupportedVersion {
+    /// This node does not serve this version (anymore or yet).
+    Unknown(u32),
+    /// The path prefix and the header ask for different versions.
+    Mismatch { prefix: u32, header: u32 },
+}
+
+impl warp::reject::Reject for UnsupportedVersion {}
+
+impl Display for UnsupportedVersion {
+    fn fmt(&self, f: &'_ mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnsupportedVersion::Unknown(version) => write!(
+                f,
+                "unsupported API version {version}: this node serves versions \
+                {OLDEST_VERSION} to {LATEST_VERSION}"
+            ),
+            UnsupportedVersion::Mismatch { prefix, header } => write!(
+                f,
+                "path asks for API version {prefix}, but {API_VERSION_HEADER} asks for {header}"
+            ),
+        }
+    }
+}
+
+/// Negotiates the version of the API of a request, consuming the version prefix, if any.
+pub fn api_version() -> impl Filter<Extract = (u32,), Error = warp::Rejection> + Clone {
+    let prefix = warp::path::param()
+        .and(warp::path::peek())
+        .and_then(|VersionPrefix(version), rest: Peek| async move {
+            if rest.as_str().starts_with('_') {
+                Ok(Some(version))
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .or(warp::any().map(|| None))
+        .unify();
+
+    prefix
+        .and(warp::header::optional(API_VERSION_HEADER))
+        .and_then(|prefix: Option<u32>, header: Option<u32>| async move {
+            let version = match (prefix, header) {
+                (Some(prefix), Some(header)) if prefix != header => {
+                    return Err(warp::reject::custom(UnsupportedVersion::Mismatch {
+                        prefix,
+                        header,
+                    }))
+                }
+                (prefix, header) => prefix.or(header).unwrap_or(OLDEST_VERSION),
+            };
+
+            if (OLDEST_VERSION..=LATEST_VERSION).contains(&version) {
+                Ok(version)
+            } else {
+                Err(warp::reject::custom(UnsupportedVersion::Unknown(version)))
+            }
+        })
+}
+
+/// Tells the client which version of the API was served.
+pub fn with_version(version: u32, reply: impl warp::Reply) -> impl warp::Reply {
+    warp::reply::with_header(reply, API_VERSION_HEADER, version.to_string())
+}
@@ -0,0 +1,117 @@
+use serde_derive::{Deserialize, Serialize};
+use warp::Filter;
+
+use samizdat_common::Hash;
+
+use crate::access::AccessRight;
+use crate::balanced_or_tree;
+use crate::models::{Droppable, Webhook, WebhookRef};
+
+use super::{api_reply, authenticate};
+
+/// The entrypoint of the webhooks API.
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        get_webhook(),
+        get_webhooks(),
+        post_webhook(),
+        delete_webhook(),
+    )
+}
+
+/// A webhook, together with its id.
+#[derive(Serialize)]
+struct WebhookResponse {
+    id: String,
+    #[serde(flatten)]
+    webhook: Webhook,
+}
+
+/// Registers a new webhook, which will receive events happening in this node. If no secret is
+/// supplied, a random one is generated.
+fn post_webhook() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Request {
+        url: String,
+        #[serde(default)]
+        secret: Option<String>,
+        #[serde(default)]
+        events: Vec<String>,
+    }
+
+    warp::path!("_webhooks")
+        .and(warp::post())
+        .and(authenticate([AccessRight::ManageWebhooks]))
+        .and(warp::body::json())
+        .map(|request: Request| {
+            let url: url::Url = request.url.parse().map_err(|err| {
+                crate::Error::ValidationFailed(format!("bad webhook url {}: {err}", request.url))
+            })?;
+
+            if url.scheme() != "http" && url.scheme() != "https" {
+                return Err(crate::Error::ValidationFailed(format!(
+                    "webhook url must be http or https: {url}"
+                )));
+            }
+
+            let webhook = Webhook {
+                url: url.to_string(),
+                secret: request.secret.unwrap_or_else(|| Hash::rand().to_string()),
+                events: request.events,
+            };
+            let webhook_ref = WebhookRef::build(webhook.clone())?;
+
+            Ok(WebhookResponse {
+                id: webhook_ref.id.to_string(),
+                webhook,
+            })
+        })
+        .map(api_reply)
+}
+
+/// Removes a webhook.
+fn delete_webhook() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_webhooks" / Hash)
+        .and(warp::delete())
+        .and(authenticate([AccessRight::ManageWebhooks]))
+        .map(|id: Hash| {
+            let webhook = WebhookRef::new(id);
+            let existed = webhook.get()?.is_some();
+            webhook.drop_if_exists()?;
+            Ok(existed)
+        })
+        .map(api_reply)
+}
+
+/// Gets a webhook.
+fn get_webhook() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_webhooks" / Hash)
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageWebhooks]))
+        .map(|id: Hash| {
+            let maybe_webhook = WebhookRef::new(id).get()?.map(|webhook| WebhookResponse {
+                id: id.to_string(),
+                webhook,
+            });
+            Ok(maybe_webhook)
+        })
+        .map(api_reply)
+}
+
+/// Lists all registered webhooks.
+fn get_webhooks() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("_webhooks")
+        .and(warp::get())
+        .and(authenticate([AccessRight::ManageWebhooks]))
+        .map(|| {
+            let webhooks = WebhookRef::get_all()?
+                .into_iter()
+                .map(|(webhook_ref, webhook)| WebhookResponse {
+                    id: webhook_ref.id.to_string(),
+                    webhook,
+                })
+                .collect::<Vec<_>>();
+            Ok(webhooks)
+        })
+        .map(api_reply)
+}
@@ -0,0 +1,156 @@
+//! Hub directories: series whose editions list hubs to connect to, in a
+//! [`DIRECTORY_ITEM`] document. The document is signed by the edition that carries it, so
+//! anyone holding the series key can curate a list of hubs for a whole community.
+//!
+//! Nodes subscribe to directories, but only connect to the hubs of the directories the user
+//! confirmed as trust roots. As the trusted directories are updated, hubs are connected to and
+//! disconnected from accordingly. Hubs passed in the command line are never disconnected.
+
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use tokio::sync::Mutex;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+use samizdat_common::rpc::QueryKind;
+
+use crate::cli;
+use crate::cli::AddrToResolve;
+use crate::hubs;
+use crate::models::{DirectoryDocument, HubDirectory, HubDirectoryRef, SeriesRef, DIRECTORY_ITEM};
+use crate::system::routing;
+
+/// The time between two refreshes of all directories.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+
+lazy_static::lazy_static! {
+    /// The hubs connected to only because some trusted directory lists them.
+    static ref DIRECTORY_HUBS: Mutex<BTreeSet<SocketAddr>> = Mutex::default();
+}
+
+/// Fetches the latest edition of a directory from the network and updates its list of hubs.
+async fn refresh(directory_ref: &HubDirectoryRef) -> Result<(), crate::Error> {
+    let series = SeriesRef::new(directory_ref.public_key.clone());
+
+    if let Some(latest) = hubs().get_latest(&series).await {
+        series.advance(&latest)?;
+    }
+
+    let edition = match series.get_editions()?.into_iter().next() {
+        Some(edition) => edition,
+        None => return Ok(()),
+    };
+    let collection = edition.collection();
+
+    let known = directory_ref.get()?;
+    if known.as_ref().and_then(|directory| directory.collection) == Some(collection.hash()) {
+        return Ok(());
+    }
+
+    let locator = collection.locator_for(DIRECTORY_ITEM.into());
+    let object = match locator.get_object()? {
+        Some(object) => Some(object),
+        None => routing::scope(&series, hubs().query(locator.hash(), QueryKind::Item)).await,
+    };
+    let content = object
+        .map(|object| object.content())
+        .transpose()?
+        .flatten()
+        .ok_or_else(|| format!("{DIRECTORY_ITEM} not found in {directory_ref}"))?;
+    let document: DirectoryDocument = serde_json::from_slice(&content)
+        .map_err(|err| format!("bad {DIRECTORY_ITEM} in {directory_ref}: {err}"))?;
+
+    // The directory may have been removed meanwhile:
+    if let Some(mut directory) = directory_ref.get()? {
+        log::info!("{directory_ref} now lists {} hubs", document.hubs.len());
+
+        directory.hubs = document.hubs;
+        directory.collection = Some(collection.hash());
+        directory.updated_at = Some(edition.timestamp());
+        HubDirectoryRef::build(directory)?;
+    }
+
+    Ok(())
+}
+
+/// Connects to the hubs listed by the trusted directories and disconnects from the ones no
+/// longer listed by any of them.
+pub async fn reconcile() -> Result<(), crate::Error> {
+    let trusted = HubDirectoryRef::get_all()?
+        .into_iter()
+        .filter(|directory| directory.is_trusted);
+    let mut wanted = vec![];
+
+    for entry in trusted.flat_map(|directory| directory.hubs) {
+        let to_resolve: AddrToResolve = match entry.address.parse() {
+            Ok(to_resolve) => to_resolve,
+            Err(err) => {
+                log::warn!("bad hub address {:?} in directory: {err}", entry.address);
+                continue;
+            }
+        };
+
+        match to_resolve.resolve(cli().resolution_mode).await {
+            Ok(resolved) => wanted.extend(resolved),
+            Err(err) => log::warn!("could not resolve hub {}: {err}", entry.address),
+        }
+    }
+
+    let wanted_addrs = wanted
+        .iter()
+        .map(|&(_, addr)| addr)
+        .collect::<BTreeSet<_>>();
+    let mut directory_hubs = DIRECTORY_HUBS.lock().await;
+
+    let stale = directory_hubs
+        .difference(&wanted_addrs)
+        .copied()
+        .collect::<BTreeSet<_>>();
+    if !stale.is_empty() {
+        log::info!("Disconnecting from hubs no longer in any directory: {stale:?}");
+        hubs().remove(&stale);
+        directory_hubs.retain(|addr| !stale.contains(addr));
+    }
+
+    // Hubs already connected (e.g., from the command line) are left alone:
+    let added = hubs().add(wanted).await;
+    if !added.is_empty() {
+        log::info!("Connected to hubs from directories: {added:?}");
+    }
+    directory_hubs.extend(added);
+
+    Ok(())
+}
+
+/// Refreshes all directories, trusted or not, and then reconciles the hubs.
+pub async fn refresh_all() {
+    let directories = match HubDirectoryRef::get_all() {
+        Ok(directories) => directories,
+        Err(err) => {
+            log::error!("failed to list hub directories: {err}");
+            return;
+        }
+    };
+
+    for HubDirectory { public_key, .. } in directories {
+        let directory_ref = HubDirectoryRef::new(public_key);
+        if let Err(err) = refresh(&directory_ref).await {
+            log::warn!("failed to refresh {directory_ref}: {err}");
+        }
+    }
+
+    if let Err(err) = reconcile().await {
+        log::error!("failed to reconcile hubs with directories: {err}");
+    }
+}
+
+/// Refreshes all directories periodically, forever.
+pub async fn run_hub_directory_daemon() {
+    let mut ticker = interval(REFRESH_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        crate::lifecycle::wait_until_active().await;
+        refresh_all().await;
+    }
+}
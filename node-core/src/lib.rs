@@ -0,0 +1,173 @@
+//! The core of the Samizdat node: storage, models, networking and the HTTP API. This crate is
+//! used by the `samizdat-node` binary, but can also be embedded in other applications, such as
+//! mobile wrappers, through its Rust API or through the C API in [`ffi`].
+
+#![feature(ip)]
+
+mod access;
+mod activation;
+mod chunk_cache;
+mod cli;
+mod content_filter;
+pub mod crashes;
+mod db;
+mod events;
+pub mod ffi;
+mod http;
+mod hub_directory;
+pub mod lifecycle;
+mod models;
+mod nostr;
+pub mod offline;
+mod readership;
+pub mod relocation;
+mod replay_resistance;
+mod replies;
+mod scrub;
+mod slow_compiler_workaround;
+mod sniff;
+mod sync;
+mod system;
+mod tasks;
+mod time_lock;
+mod torrent;
+mod utils;
+mod vacuum;
+mod wipe;
+
+pub use samizdat_common::Error;
+
+pub use cli::{cli, init_cli, init_cli_from};
+pub(crate) use db::db;
+
+use futures::{prelude::*, TryStreamExt};
+use std::panic;
+use std::sync::OnceLock;
+use tokio::task;
+
+use access::init_access_token;
+use db::init_db;
+use system::Hubs;
+
+/// The variable holding a list of all the connections to the hubs.
+static HUBS: OnceLock<Hubs> = OnceLock::new();
+
+/// Initiates [`HUBS`] by connecting to all hubs defined in the command line.
+async fn init_hubs() -> Result<(), crate::Error> {
+    let sockets = cli()
+        .hubs
+        .iter()
+        .map(|to_resolve| to_resolve.resolve(cli().resolution_mode));
+    let resolved = stream::iter(sockets)
+        .buffer_unordered(cli().hubs.len())
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .flatten();
+    let hubs = Hubs::init(resolved).await?;
+
+    if HUBS.set(hubs).is_err() {
+        return Err("hubs already initialized".to_owned().into());
+    }
+
+    Ok(())
+}
+
+/// Retrieves a reference to the list of hubs. Needs to be called just after initialization.
+pub(crate) fn hubs<'a>() -> &'a Hubs {
+    HUBS.get().expect("hubs not initialized")
+}
+
+/// Utility for propagating panics through tasks.
+fn maybe_resume_panic<T>(r: Result<T, task::JoinError>) {
+    if let Err(err) = r {
+        if let Ok(panic) = err.try_into_panic() {
+            panic::resume_unwind(panic);
+        }
+    }
+}
+
+/// Runs the node until the HTTP server exits. The CLI parameters must be initialized
+/// beforehand, with either [`init_cli`] or [`init_cli_from`]. This can only be called once per
+/// process. Panics and fatal errors are recorded as crash reports.
+pub async fn run() -> Result<(), crate::Error> {
+    crashes::install_panic_hook();
+
+    if !cli().no_crash_hint {
+        if let Err(err) = crashes::warn_about_new_crashes() {
+            log::warn!("could not read crash reports: {err}");
+        }
+    }
+
+    let outcome = run_node().await;
+
+    if let Err(err) = &outcome {
+        crashes::record_fatal(err);
+    }
+
+    outcome
+}
+
+async fn run_node() -> Result<(), crate::Error> {
+    // Init resources:
+    init_access_token()?;
+    init_db()?;
+
+    // These only touch the database:
+    if cli().migrations_dry_run || cli().rollback_migration.is_some() {
+        return Ok(());
+    }
+
+    init_hubs().await?;
+
+    if db::is_replica() {
+        // Keep up with the primary, which takes care of everything that writes:
+        tasks::supervise("catch-up", db::run_catch_up_daemon);
+    } else {
+        // Start vacuum:
+        tasks::supervise("vacuum", crate::vacuum::run_vacuum_daemon);
+
+        // Start scrubber:
+        tasks::supervise("scrub", crate::scrub::run_scrub_daemon);
+
+        // Start watching Nostr relays:
+        tasks::supervise("nostr", crate::nostr::run_nostr_daemon);
+
+        // Start cover traffic:
+        tasks::supervise("cover-traffic", crate::system::run_cover_traffic_daemon);
+
+        // Start following hub directories:
+        tasks::supervise(
+            "hub-directories",
+            crate::hub_directory::run_hub_directory_daemon,
+        );
+
+        // Report readership of mirrored series:
+        tasks::supervise("readership-reports", crate::readership::run_report_daemon);
+
+        // Release time-locked editions when their time comes:
+        tasks::supervise("time-lock-release", crate::time_lock::run_release_daemon);
+    }
+
+    // Start webhook delivery:
+    tasks::supervise("webhooks", crate::events::run_webhook_daemon);
+
+    // Detect when to be quiet:
+    tasks::supervise("quiet", crate::lifecycle::run_quiet_daemon);
+
+    // Account for the bandwidth used:
+    tasks::supervise("usage", crate::system::run_usage_daemon);
+
+    // Start health probes:
+    tasks::supervise("health-probes", || hubs().run_health_probes());
+
+    // Start port mapping:
+    tasks::supervise("port-mapping", crate::system::run_port_mapping_daemon);
+
+    // Run public server:
+    let server = tokio::spawn(http::serve());
+
+    maybe_resume_panic(server.await);
+
+    Ok(())
+}
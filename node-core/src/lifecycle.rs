@@ -0,0 +1,297 @@
+//! Lifecycle hooks for embedders. Mobile operating systems expect applications in the
+//! background to do as little as possible, and users on metered networks expect applications
+//! not to waste their data plan. Therefore, the embedding application can _pause_ the node,
+//! which suspends all background work (the HTTP API keeps working), and tell the node whether
+//! the current network is _metered_.
+//!
+//! While on a metered network, during the quiet hours set in the command line, or when the
+//! detection hooks (see [`run_quiet_daemon`]) say so, the node is _quiet_: it does not seed
+//! content to peers, and it defers subscription refreshes and edition announcements until it
+//! is quiet no more. Background work that is not essential, such as cover traffic and
+//! refetching content, is skipped. Local reads are still served. Quiet mode can also be turned
+//! on or off through the API, overriding all of the above.
+
+use chrono::Local;
+use serde_derive::Serialize;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+use tokio::process::Command;
+use tokio::sync::Notify;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+use crate::cli;
+
+/// The time between two runs of the detection hooks.
+const DETECTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The maximum number of pieces of work deferred at a time. Past that, the oldest ones are
+/// dropped.
+const MAX_DEFERRED: usize = 1_024;
+
+/// Work deferred until the node is quiet no more, with the key identifying it.
+type Deferred = (String, Box<dyn FnOnce() + Send>);
+
+lazy_static::lazy_static! {
+    /// The current lifecycle state of the node.
+    static ref LIFECYCLE: RwLock<Lifecycle> = RwLock::default();
+    /// Wakes up background tasks waiting for the node to be resumed.
+    static ref RESUMED: Notify = Notify::new();
+    /// Wakes up the quiet daemon when quiet mode may have been turned off.
+    static ref QUIET_CHANGED: Notify = Notify::new();
+    /// The work deferred while the node is quiet, oldest first.
+    static ref DEFERRED: Mutex<VecDeque<Deferred>> = Mutex::default();
+}
+
+/// The lifecycle state of the node, as set by the embedding application.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Lifecycle {
+    /// Whether background work is suspended.
+    pub is_paused: bool,
+    /// Whether the node is on a metered network.
+    pub is_metered: bool,
+    /// Whether quiet mode was turned on or off through the API, if at all.
+    pub quiet_override: Option<bool>,
+    /// Whether the device was last detected to be running on battery.
+    pub is_on_battery: bool,
+    /// Whether the command in `--quiet-command` last asked for quiet mode.
+    pub is_quiet_by_command: bool,
+}
+
+/// Why the node is quiet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuietReason {
+    /// Quiet mode was turned on through the API.
+    Override,
+    /// It is within the quiet hours.
+    QuietHours,
+    /// The node is on a metered network.
+    Metered,
+    /// The device is running on battery.
+    OnBattery,
+    /// The command in `--quiet-command` asked for quiet mode.
+    Command,
+}
+
+/// Whether the node is quiet and why.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuietStatus {
+    pub is_quiet: bool,
+    /// Whether the API decided, overriding everything else.
+    pub is_overridden: bool,
+    /// All the reasons to be quiet, even when overridden.
+    pub reasons: Vec<QuietReason>,
+}
+
+/// Retrieves the current lifecycle state.
+pub fn lifecycle() -> Lifecycle {
+    *LIFECYCLE.read().expect("poisoned")
+}
+
+/// Suspends all background work. Work in progress is finished first.
+pub fn pause() {
+    log::info!("node paused");
+    LIFECYCLE.write().expect("poisoned").is_paused = true;
+}
+
+/// Resumes all background work.
+pub fn resume() {
+    log::info!("node resumed");
+    LIFECYCLE.write().expect("poisoned").is_paused = false;
+    RESUMED.notify_waiters();
+}
+
+/// Tells the node whether it is currently on a metered network.
+pub fn set_metered(is_metered: bool) {
+    log::info!("node is on a metered network: {is_metered}");
+    LIFECYCLE.write().expect("poisoned").is_metered = is_metered;
+    QUIET_CHANGED.notify_one();
+}
+
+/// Turns quiet mode on or off, overriding quiet hours and detection, or goes back to deciding
+/// automatically if `None` is given.
+pub fn set_quiet(quiet_override: Option<bool>) -> QuietStatus {
+    log::info!("quiet mode overridden: {quiet_override:?}");
+    LIFECYCLE.write().expect("poisoned").quiet_override = quiet_override;
+    QUIET_CHANGED.notify_one();
+    quiet_status()
+}
+
+/// Whether the node is quiet right now and why.
+pub fn quiet_status() -> QuietStatus {
+    let lifecycle = lifecycle();
+    let now = Local::now().time();
+    let mut reasons = vec![];
+
+    if lifecycle.quiet_override == Some(true) {
+        reasons.push(QuietReason::Override);
+    }
+
+    if cli().quiet_hours.iter().any(|hours| hours.contains(now)) {
+        reasons.push(QuietReason::QuietHours);
+    }
+
+    if lifecycle.is_metered {
+        reasons.push(QuietReason::Metered);
+    }
+
+    if lifecycle.is_on_battery {
+        reasons.push(QuietReason::OnBattery);
+    }
+
+    if lifecycle.is_quiet_by_command {
+        reasons.push(QuietReason::Command);
+    }
+
+    QuietStatus {
+        is_quiet: lifecycle.quiet_override.unwrap_or(!reasons.is_empty()),
+        is_overridden: lifecycle.quiet_override.is_some(),
+        reasons,
+    }
+}
+
+/// Whether the node should keep to the essential right now.
+pub(crate) fn is_quiet() -> bool {
+    quiet_status().is_quiet
+}
+
+/// Runs some work now or, if the node is quiet, as soon as it is quiet no more. Work deferred
+/// with the same key (e.g., refreshing the same series) is superseded by the new one, and only
+/// the latest [`MAX_DEFERRED`] pieces of work are kept. Deferred work is lost if the node stops
+/// before running it.
+pub(crate) fn unless_quiet<F>(key: String, work: F)
+where
+    F: 'static + Send + FnOnce(),
+{
+    if !is_quiet() {
+        work();
+        return;
+    }
+
+    log::debug!("node is quiet; deferring {key}");
+    let mut deferred = DEFERRED.lock().expect("poisoned");
+    deferred.retain(|(existing, _)| *existing != key);
+
+    if deferred.len() >= MAX_DEFERRED {
+        if let Some((oldest, _)) = deferred.pop_front() {
+            log::warn!("too much work deferred; dropping {oldest}");
+        }
+    }
+
+    deferred.push_back((key, Box::new(work)));
+}
+
+/// Runs all deferred work, if the node is quiet no more. This must be called from within the
+/// runtime, since deferred work usually spawns tasks.
+fn run_deferred() {
+    if is_quiet() {
+        return;
+    }
+
+    let deferred = std::mem::take(&mut *DEFERRED.lock().expect("poisoned"));
+
+    if !deferred.is_empty() {
+        log::info!(
+            "node is quiet no more; running {} deferred tasks",
+            deferred.len()
+        );
+    }
+
+    for (_, work) in deferred {
+        work();
+    }
+}
+
+/// Waits until the node is not paused. Background tasks call this before each round of work.
+pub(crate) async fn wait_until_active() {
+    loop {
+        // Create the future before checking, so that no wake-up is lost:
+        let resumed = RESUMED.notified();
+
+        if !lifecycle().is_paused {
+            return;
+        }
+
+        resumed.await;
+    }
+}
+
+/// Whether the device is running on battery. Only detected in Linux, through `sysfs`.
+fn is_on_battery() -> bool {
+    let supplies = match std::fs::read_dir("/sys/class/power_supply") {
+        Ok(supplies) => supplies,
+        Err(_) => return false,
+    };
+
+    let read = |path: &Path, attribute: &str| {
+        std::fs::read_to_string(path.join(attribute))
+            .map(|value| value.trim().to_owned())
+            .unwrap_or_default()
+    };
+
+    supplies.filter_map(Result::ok).any(|supply| {
+        let path = supply.path();
+        read(&path, "type") == "Battery" && read(&path, "status") == "Discharging"
+    })
+}
+
+/// Runs the command in `--quiet-command`, which asks for quiet mode by exiting successfully.
+async fn is_quiet_by_command(command: &str) -> bool {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    match shell.arg(command).kill_on_drop(true).status().await {
+        Ok(status) => status.success(),
+        Err(err) => {
+            log::warn!("failed to run quiet command {command:?}: {err}");
+            false
+        }
+    }
+}
+
+/// Runs the detection hooks set in the command line (`--quiet-on-battery` and
+/// `--quiet-command`) every [`DETECTION_INTERVAL`], and runs the deferred work once the node
+/// is quiet no more, e.g., when the quiet hours are over or when quiet mode is turned off.
+pub async fn run_quiet_daemon() {
+    let mut ticker = interval(DETECTION_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut was_quiet = false;
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = QUIET_CHANGED.notified() => {
+                run_deferred();
+                continue;
+            }
+        }
+
+        let is_on_battery = cli().quiet_on_battery && is_on_battery();
+        let is_quiet_by_command = match &cli().quiet_command {
+            Some(command) => is_quiet_by_command(command).await,
+            None => false,
+        };
+
+        {
+            let mut lifecycle = LIFECYCLE.write().expect("poisoned");
+            lifecycle.is_on_battery = is_on_battery;
+            lifecycle.is_quiet_by_command = is_quiet_by_command;
+        }
+
+        let status = quiet_status();
+        if status.is_quiet != was_quiet {
+            log::info!("node is quiet: {} ({:?})", status.is_quiet, status.reasons);
+            was_quiet = status.is_quiet;
+        }
+
+        run_deferred();
+    }
+}
@@ -11,6 +11,11 @@ pub enum BookmarkType {
     Reference,
     /// A bookmark defined by the user.
     User,
+    /// A bookmark kept on the content of the series mirrored by the user (see
+    /// [`super::SubscriptionKind::Mirror`]). This counts the mirrored editions pinning the
+    /// object, so that content shared by many of them stays pinned until the last one is
+    /// unmirrored.
+    Mirror,
 }
 
 #[derive(Debug, Clone)]
@@ -49,7 +54,7 @@ impl Bookmark {
 
     pub fn mark_with(&self, batch: &mut WriteBatch) {
         let operation = match self.ty {
-            BookmarkType::Reference => MergeOperation::Increment(1),
+            BookmarkType::Reference | BookmarkType::Mirror => MergeOperation::Increment(1),
             BookmarkType::User => MergeOperation::Set(1),
         };
 
@@ -70,7 +75,7 @@ impl Bookmark {
 
     pub fn unmark_with(&self, batch: &mut WriteBatch) {
         let operation = match self.ty {
-            BookmarkType::Reference => MergeOperation::Increment(-1),
+            BookmarkType::Reference | BookmarkType::Mirror => MergeOperation::Increment(-1),
             BookmarkType::User => MergeOperation::Set(0),
         };
 
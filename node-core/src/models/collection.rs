@@ -0,0 +1,972 @@
+use rocksdb::{IteratorMode, WriteBatch};
+use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use samizdat_common::{Hash, Key, PatriciaMap, PatriciaProof, Riddle};
+
+use crate::db::{db, Table};
+
+use super::{Droppable, ObjectHeader, ObjectRef};
+
+/// The function transforming an arbitrary string into its canonical path form.
+fn normalize(name: &str) -> Cow<str> {
+    if name.ends_with('/') || name.starts_with('/') || name.contains("//") {
+        let restructured = name
+            .split('/')
+            .filter(|slice| !slice.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        Cow::from(restructured)
+    } else {
+        Cow::from(name)
+    }
+}
+
+/// An owned item path.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ItemPathBuf(Box<str>);
+
+impl<'a> From<&'a str> for ItemPathBuf {
+    fn from(name: &'a str) -> ItemPathBuf {
+        ItemPathBuf(normalize(name).into())
+    }
+}
+
+impl From<String> for ItemPathBuf {
+    fn from(name: String) -> ItemPathBuf {
+        ItemPathBuf(normalize(&name).into())
+    }
+}
+
+impl From<Box<str>> for ItemPathBuf {
+    fn from(name: Box<str>) -> ItemPathBuf {
+        ItemPathBuf(normalize(&name).into())
+    }
+}
+
+impl FromStr for ItemPathBuf {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> Result<ItemPathBuf, crate::Error> {
+        Ok(s.into())
+    }
+}
+
+impl Display for ItemPathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ItemPathBuf {
+    /// Retrieves the string representation of this path, in its canonical form.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Transforms into a borrowed item path.
+    pub(super) fn as_path(&self) -> ItemPath {
+        ItemPath(self.0.as_ref().into())
+    }
+
+    fn hash(&self) -> Hash {
+        Hash::hash(self.0.as_bytes())
+    }
+}
+
+/// A borrowed item path.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemPath<'a>(Cow<'a, str>);
+
+impl<'a> From<&'a str> for ItemPath<'a> {
+    fn from(name: &'a str) -> ItemPath<'a> {
+        ItemPath(normalize(name))
+    }
+}
+
+impl<'a> Display for ItemPath<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<'a> ItemPath<'a> {
+    /// Retrieves the string representation of this path, in its canonical form.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Optional information on how an item should be served, set by the publisher.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemMetadata {
+    /// The suggested `Cache-Control` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<String>,
+    /// The language of the content, for the `Content-Language` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_language: Option<String>,
+    /// The charset of the content, appended to the `Content-Type` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charset: Option<String>,
+    /// Overrides the `Content-Security-Policy` header set by the node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_security_policy: Option<String>,
+    /// Overrides the `X-Frame-Options` header set by the node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_options: Option<String>,
+    /// Overrides the `Referrer-Policy` header set by the node. Items calling the node API
+    /// need a policy sending the full `Referer` to the same origin, such as `same-origin`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub referrer_policy: Option<String>,
+}
+
+impl ItemMetadata {
+    /// Fails if any of the values cannot be sent as an HTTP header value.
+    pub fn validate(&self) -> Result<(), crate::Error> {
+        for value in [
+            &self.cache_control,
+            &self.content_language,
+            &self.charset,
+            &self.content_security_policy,
+            &self.frame_options,
+            &self.referrer_policy,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if http::HeaderValue::from_str(value).is_err() {
+                return Err(crate::Error::ValidationFailed(format!(
+                    "bad header value in item metadata: {value:?}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The content type with the charset of the item, if one was set and the content type does
+    /// not already have one.
+    pub fn content_type(&self, content_type: &str) -> String {
+        match &self.charset {
+            Some(charset) if !content_type.contains("charset=") => {
+                format!("{content_type}; charset={charset}")
+            }
+            _ => content_type.to_owned(),
+        }
+    }
+
+    /// The extra headers to be sent with the item.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        [
+            ("Cache-Control", &self.cache_control),
+            ("Content-Language", &self.content_language),
+        ]
+        .into_iter()
+        .filter_map(|(header, value)| Some((header, value.clone()?)))
+        .collect()
+    }
+}
+
+/// Another collection mounted under a path prefix of a collection. Items under the prefix that
+/// are not in the collection itself are looked for in the mounted collection, so that common
+/// assets (e.g., fonts and frameworks) can be shared by many collections without being
+/// republished in each one of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mount {
+    /// A fixed collection.
+    Collection(Hash),
+    /// The latest edition of a series.
+    Series(Key),
+}
+
+impl Display for Mount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mount::Collection(hash) => write!(f, "collection {hash}"),
+            Mount::Series(key) => write!(f, "series {key}"),
+        }
+    }
+}
+
+/// The item of a collection listing its items.
+const INVENTORY: &str = "_inventory";
+
+/// The item of a sharded inventory holding one of its shards.
+fn inventory_shard_path(index: usize) -> ItemPathBuf {
+    ItemPathBuf::from(format!("{INVENTORY}/{index}"))
+}
+
+/// A shard of a sharded inventory, kept in the item given by [`inventory_shard_path`]. A shard
+/// is an inventory in its own right, listing a range of the items of the collection (in path
+/// order) together with their metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryShard {
+    /// The first path listed in the shard.
+    pub first: ItemPathBuf,
+    /// The object holding the shard.
+    pub hash: Hash,
+}
+
+/// The list of items of a collection, kept in the `_inventory` item. For huge collections,
+/// the inventory may be _sharded_: the `_inventory` item then only has the content warnings
+/// and the list of shards, so that nodes can go through the collection one shard at a time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Inventory {
+    inventory: BTreeMap<ItemPathBuf, Hash>,
+    /// Per-item metadata. Items without metadata are not listed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<ItemPathBuf, ItemMetadata>,
+    /// The categories of sensitive content (e.g., `nsfw`) the publisher flagged this
+    /// collection with.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    content_warnings: BTreeSet<String>,
+    /// The shards of a sharded inventory, in path order. Empty if the inventory is not sharded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    shards: Vec<InventoryShard>,
+    /// The maximum number of items in each shard, if sharded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shard_size: Option<usize>,
+    /// The items that subscribers should fetch before the others (e.g., the front page and
+    /// its stylesheets), in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    prefetch: Vec<ItemPathBuf>,
+    /// The collections mounted in this collection, by path prefix. Only kept in the
+    /// `_inventory` item, never in shards.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    mounts: BTreeMap<ItemPathBuf, Mount>,
+}
+
+impl FromIterator<(ItemPathBuf, Hash)> for Inventory {
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = (ItemPathBuf, Hash)>,
+    {
+        Inventory {
+            inventory: iter.into_iter().collect::<BTreeMap<ItemPathBuf, Hash>>(),
+            metadata: BTreeMap::new(),
+            content_warnings: BTreeSet::new(),
+            shards: vec![],
+            shard_size: None,
+            prefetch: vec![],
+            mounts: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Inventory {
+    type IntoIter = std::collections::btree_map::Iter<'a, ItemPathBuf, Hash>;
+    type Item = (&'a ItemPathBuf, &'a Hash);
+    fn into_iter(self) -> Self::IntoIter {
+        self.inventory.iter()
+    }
+}
+
+impl Inventory {
+    pub fn iter(&self) -> <&Self as IntoIterator>::IntoIter {
+        self.into_iter()
+    }
+
+    /// Whether an item is in the collection.
+    pub fn contains(&self, path: &ItemPathBuf) -> bool {
+        self.inventory.contains_key(path)
+    }
+
+    /// The hash of the object of an item, if the item is in the collection.
+    pub fn get(&self, path: &ItemPathBuf) -> Option<&Hash> {
+        self.inventory.get(path)
+    }
+
+    /// The metadata of an item, if the publisher set any.
+    pub fn metadata(&self, path: &ItemPathBuf) -> Option<&ItemMetadata> {
+        self.metadata.get(path)
+    }
+
+    /// The content warnings the publisher flagged the collection with.
+    pub fn content_warnings(&self) -> &BTreeSet<String> {
+        &self.content_warnings
+    }
+
+    /// The shards of this inventory. Empty if the inventory is not sharded.
+    pub fn shards(&self) -> &[InventoryShard] {
+        &self.shards
+    }
+
+    /// The maximum number of items in each shard, if this inventory is sharded.
+    pub fn shard_size(&self) -> Option<usize> {
+        self.shard_size
+    }
+
+    /// The items that subscribers should fetch before the others, in order.
+    pub fn prefetch(&self) -> &[ItemPathBuf] {
+        &self.prefetch
+    }
+
+    /// The collections mounted in this collection, by path prefix.
+    pub fn mounts(&self) -> &BTreeMap<ItemPathBuf, Mount> {
+        &self.mounts
+    }
+
+    /// The mount with the longest prefix of a path, if any, together with the rest of the path
+    /// within the mounted collection.
+    pub fn mount_for(&self, path: &ItemPathBuf) -> Option<(&Mount, ItemPathBuf)> {
+        let path = path.0.as_ref();
+        self.mounts
+            .iter()
+            .rev()
+            .filter_map(|(prefix, mount)| {
+                let prefix = prefix.0.as_ref();
+                let rest = if prefix.is_empty() {
+                    path
+                } else {
+                    path.strip_prefix(prefix)?.strip_prefix('/')?
+                };
+                Some((prefix.len(), mount, ItemPathBuf::from(rest)))
+            })
+            .max_by_key(|(length, _, _)| *length)
+            .map(|(_, mount, rest)| (mount, rest))
+    }
+
+    /// The index of the shard that would list a path, if this inventory is sharded.
+    fn shard_for(&self, path: &ItemPathBuf) -> Option<usize> {
+        if self.shards.is_empty() {
+            return None;
+        }
+
+        Some(
+            self.shards
+                .partition_point(|shard| &shard.first <= path)
+                .saturating_sub(1),
+        )
+    }
+
+    /// Adds the items of a shard to this inventory.
+    fn merge(&mut self, shard: Inventory) {
+        self.inventory.extend(shard.inventory);
+        self.metadata.extend(shard.metadata);
+    }
+
+    fn deserialize(content: &[u8], collection: &CollectionRef) -> Result<Inventory, crate::Error> {
+        serde_json::from_slice(content).map_err(|err| {
+            crate::Error::from(format!(
+                "failed to deserialize inventory for collection {}: {}",
+                collection.hash, err
+            ))
+        })
+    }
+
+    /// Calculates what has changed from this inventory to a newer one.
+    pub fn diff(&self, newer: &Inventory) -> InventoryDiff {
+        let mut diff = InventoryDiff::default();
+
+        for (path, hash) in &self.inventory {
+            match newer.inventory.get(path) {
+                Some(new_hash) if new_hash != hash => {
+                    diff.changed.push((path.clone(), *hash, *new_hash))
+                }
+                Some(_) => {}
+                None => diff.removed.push((path.clone(), *hash)),
+            }
+        }
+
+        for (path, hash) in &newer.inventory {
+            if !self.inventory.contains_key(path) {
+                diff.added.push((path.clone(), *hash));
+            }
+        }
+
+        diff
+    }
+}
+
+/// The difference between two inventories. All entries are sorted by path.
+#[derive(Debug, Default)]
+pub struct InventoryDiff {
+    /// Paths that only exist in the newer inventory, with their object hashes.
+    pub added: Vec<(ItemPathBuf, Hash)>,
+    /// Paths that only exist in the older inventory, with their object hashes.
+    pub removed: Vec<(ItemPathBuf, Hash)>,
+    /// Paths that exist in both inventories, but point to different objects. Hashes are given
+    /// in the order older, newer.
+    pub changed: Vec<(ItemPathBuf, Hash, Hash)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollectionItem {
+    pub collection: CollectionRef,
+    pub name: ItemPathBuf,
+    pub inclusion_proof: PatriciaProof,
+    #[serde(default)]
+    pub is_draft: bool,
+}
+
+impl Droppable for CollectionItem {
+    fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error> {
+        let path = self.name.as_path();
+        let locator = self.collection.locator_for(path);
+
+        log::info!("Removing item {}: {:#?}", locator, self);
+
+        batch.delete_cf(Table::CollectionItemLocators.get(), locator.path());
+        batch.delete_cf(Table::CollectionItems.get(), locator.hash());
+
+        Ok(())
+    }
+}
+
+impl CollectionItem {
+    pub fn is_valid(&self) -> bool {
+        let is_included = self.inclusion_proof.is_in(&self.collection.hash);
+        let key = Hash::hash(self.name.0.as_bytes());
+
+        if !is_included {
+            log::error!("Inclusion proof failed for {:?}", self);
+            return false;
+        }
+
+        if &key != self.inclusion_proof.claimed_key() {
+            log::error!("Key is different from claimed key: {:?}", self);
+            return false;
+        }
+
+        true
+    }
+
+    /// Returns an object reference if item is valid. Else, returns
+    /// `Ok(Error::InvalidCollectionItem)`.
+    pub fn object(&self) -> Result<ObjectRef, crate::Error> {
+        if self.is_valid() {
+            Ok(ObjectRef::new(*self.inclusion_proof.claimed_value()))
+        } else {
+            Err(crate::Error::InvalidCollectionItem)
+        }
+    }
+
+    pub fn locator(&self) -> Locator {
+        Locator {
+            collection: self.collection.clone(),
+            name: self.name.as_path(),
+        }
+    }
+
+    pub fn find(content_riddle: &Riddle) -> Result<Option<CollectionItem>, crate::Error> {
+        let iter = db().iterator_cf(Table::CollectionItems.get(), IteratorMode::Start);
+
+        for (key, value) in iter {
+            let hash: Hash = match key.as_ref().try_into() {
+                Ok(hash) => hash,
+                Err(err) => {
+                    log::warn!("{}", err);
+                    continue;
+                }
+            };
+
+            if content_riddle.resolves(&hash) {
+                return Ok(Some(bincode::deserialize(&*value)?));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn insert_with(&self, batch: &mut WriteBatch) {
+        let locator = self.collection.locator_for(self.name.as_path());
+
+        batch.put_cf(
+            Table::CollectionItems.get(),
+            locator.hash(),
+            bincode::serialize(self).expect("can serialize"),
+        );
+        batch.put_cf(
+            Table::CollectionItemLocators.get(),
+            locator.path(),
+            locator.hash(),
+        );
+
+        log::info!("Inserting item {}: {:#?}", locator, self);
+    }
+
+    pub fn insert(&self) -> Result<(), crate::Error> {
+        let mut batch = WriteBatch::default();
+        self.insert_with(&mut batch);
+        db().write(batch)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionRef {
+    hash: Hash,
+}
+
+impl Droppable for CollectionRef {
+    fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error> {
+        for name in self.list() {
+            if let Some(item) = self.get(name.as_path())? {
+                item.drop_if_exists_with(batch)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CollectionRef {
+    pub fn new(hash: Hash) -> CollectionRef {
+        CollectionRef { hash }
+    }
+
+    pub fn hash(&self) -> Hash {
+        self.hash
+    }
+
+    #[cfg(test)]
+    pub(crate) fn rand() -> CollectionRef {
+        CollectionRef { hash: Hash::rand() }
+    }
+
+    /// Builds a collection from named objects. Metadata is kept in the inventory and only for
+    /// items that exist in the collection. Content warnings are kept in lowercase. The new
+    /// collection mounts no other collections.
+    pub fn build<I>(
+        is_draft: bool,
+        objects: I,
+        metadata: BTreeMap<ItemPathBuf, ItemMetadata>,
+        content_warnings: BTreeSet<String>,
+    ) -> Result<CollectionRef, crate::Error>
+    where
+        I: AsRef<[(ItemPathBuf, ObjectRef)]>,
+    {
+        CollectionRef::build_with(
+            is_draft,
+            objects,
+            metadata,
+            content_warnings,
+            None,
+            vec![],
+            BTreeMap::new(),
+        )
+    }
+
+    /// Builds a collection from named objects, sharding the inventory in shards of at most
+    /// `shard_size` items, if given and if the collection has more items than that. Items
+    /// in `prefetch` are fetched first by subscribers, in that order. As with metadata, only
+    /// items that exist in the collection are kept. Collections in `mounts` are mounted under
+    /// their path prefixes (see [`Mount`]).
+    pub fn build_with<I>(
+        is_draft: bool,
+        objects: I,
+        mut metadata: BTreeMap<ItemPathBuf, ItemMetadata>,
+        content_warnings: BTreeSet<String>,
+        shard_size: Option<usize>,
+        prefetch: Vec<ItemPathBuf>,
+        mounts: BTreeMap<ItemPathBuf, Mount>,
+    ) -> Result<CollectionRef, crate::Error>
+    where
+        I: AsRef<[(ItemPathBuf, ObjectRef)]>,
+    {
+        if shard_size == Some(0) {
+            return Err(crate::Error::ValidationFailed(
+                "inventory shard size must be positive".to_owned(),
+            ));
+        }
+
+        for item_metadata in metadata.values() {
+            item_metadata.validate()?;
+        }
+
+        // Create inventory document:
+        let mut inventory = objects
+            .as_ref()
+            .iter()
+            .map(|(path, object_ref)| (path.clone(), *object_ref.hash()))
+            .collect::<Inventory>();
+        metadata.retain(|path, item_metadata| {
+            inventory.inventory.contains_key(path) && *item_metadata != ItemMetadata::default()
+        });
+        inventory.metadata = metadata;
+        inventory.content_warnings = content_warnings
+            .into_iter()
+            .map(|warning| warning.trim().to_lowercase())
+            .filter(|warning| !warning.is_empty())
+            .collect();
+        let mut prefetched = BTreeSet::new();
+        inventory.prefetch = prefetch
+            .into_iter()
+            .filter(|path| {
+                inventory.inventory.contains_key(path) && prefetched.insert(path.clone())
+            })
+            .collect();
+        inventory.mounts = mounts;
+
+        let build_inventory = |inventory: &Inventory| {
+            let inventory = serde_json::to_string_pretty(inventory).expect("can serialize");
+            ObjectRef::build(
+                ObjectHeader::new("application/json".to_owned(), is_draft)?,
+                false,
+                inventory.as_bytes().iter().map(|&byte| Ok(byte)),
+            )
+        };
+
+        // The inventory items, besides the objects:
+        let mut inventory_items = vec![];
+
+        if let Some(shard_size) = shard_size.filter(|&size| inventory.inventory.len() > size) {
+            let entries = std::mem::take(&mut inventory.inventory)
+                .into_iter()
+                .collect::<Vec<_>>();
+
+            for (index, chunk) in entries.chunks(shard_size).enumerate() {
+                let shard = chunk.iter().cloned().collect::<Inventory>();
+                let shard = Inventory {
+                    metadata: shard
+                        .inventory
+                        .keys()
+                        .filter_map(|path| Some((path.clone(), inventory.metadata.remove(path)?)))
+                        .collect(),
+                    ..shard
+                };
+                let shard_object = build_inventory(&shard)?;
+
+                inventory.shards.push(InventoryShard {
+                    first: chunk[0].0.clone(),
+                    hash: *shard_object.hash(),
+                });
+                inventory_items.push((inventory_shard_path(index), shard_object));
+            }
+
+            inventory.shard_size = Some(shard_size);
+        }
+
+        let inventory_path = ItemPathBuf::from(INVENTORY);
+        inventory_items.push((inventory_path, build_inventory(&inventory)?));
+
+        // Note: this is the slow part of the process (by a long stretch)
+        let patricia_map = objects
+            .as_ref()
+            .iter()
+            .chain(&inventory_items)
+            .map(|(name, object)| (name.hash(), *object.hash()))
+            .collect::<PatriciaMap>();
+
+        let root = *patricia_map.root();
+        let collection = CollectionRef { hash: root };
+
+        let mut batch = WriteBatch::default();
+
+        for (name, _object) in objects.as_ref().iter().chain(&inventory_items) {
+            let item = CollectionItem {
+                collection: collection.clone(),
+                name: name.clone(),
+                inclusion_proof: patricia_map
+                    .proof_for(name.hash())
+                    .expect("name exists in map"),
+                is_draft,
+            };
+
+            item.insert_with(&mut batch);
+        }
+
+        // batch.put_cf(Table::Collections.get(), collection.hash, &[]);
+
+        db().write(batch)?;
+
+        Ok(collection)
+    }
+
+    pub fn locator_for<'a>(&self, name: ItemPath<'a>) -> Locator<'a> {
+        Locator {
+            collection: self.clone(),
+            name,
+        }
+    }
+
+    /// The locator of the item holding a shard of the inventory of this collection.
+    pub fn inventory_shard_locator(&self, index: usize) -> Locator<'static> {
+        Locator {
+            collection: self.clone(),
+            name: ItemPath(Cow::Owned(inventory_shard_path(index).0.into())),
+        }
+    }
+
+    pub fn get(&self, name: ItemPath) -> Result<Option<CollectionItem>, crate::Error> {
+        let locator = self.locator_for(name);
+        let maybe_item = db().get_cf(Table::CollectionItems.get(), locator.hash())?;
+
+        if let Some(item) = maybe_item {
+            Ok(Some(bincode::deserialize(&item)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Rebuilds the Patricia map of this collection from its inventory, if the inventory is
+    /// present in the local database. The map is only returned if its root is the hash of this
+    /// collection, i.e., if the inventory lists every item in the collection.
+    pub fn patricia_map(&self) -> Result<Option<PatriciaMap>, crate::Error> {
+        let inventory_path = ItemPathBuf::from(INVENTORY);
+        let (inventory_item, inventory) =
+            match (self.get(inventory_path.as_path())?, self.inventory()?) {
+                (Some(item), Some(inventory)) => (item, inventory),
+                _ => return Ok(None),
+            };
+
+        let mut patricia_map = inventory
+            .iter()
+            .map(|(name, hash)| (name.hash(), *hash))
+            .collect::<PatriciaMap>();
+        patricia_map.insert(
+            inventory_path.hash(),
+            *inventory_item.inclusion_proof.claimed_value(),
+        );
+
+        for (index, shard) in inventory.shards().iter().enumerate() {
+            patricia_map.insert(inventory_shard_path(index).hash(), shard.hash);
+        }
+
+        if patricia_map.root() != &self.hash {
+            log::warn!("inventory of collection {} is incomplete", self.hash);
+            return Ok(None);
+        }
+
+        Ok(Some(patricia_map))
+    }
+
+    /// Gets the proof that an item is in this collection. This is the proof stored with the
+    /// item or, if the item is not present in the local database, a proof generated from the
+    /// inventory of this collection.
+    pub fn proof_for(&self, name: ItemPath) -> Result<Option<PatriciaProof>, crate::Error> {
+        if let Some(item) = self.get(name.clone())? {
+            return Ok(Some(item.inclusion_proof));
+        }
+
+        let key = Hash::hash(name.as_str().as_bytes());
+        Ok(self
+            .patricia_map()?
+            .and_then(|patricia_map| patricia_map.proof_for(key)))
+    }
+
+    /// Gets the `_inventory` item of this collection, if present in the local database. For
+    /// sharded inventories, this has the content warnings and the list of shards, but no items.
+    pub fn inventory_index(&self) -> Result<Option<Inventory>, crate::Error> {
+        let locator = self.locator_for(INVENTORY.into());
+        let content = match locator.get_object()? {
+            Some(object) => object.content()?,
+            None => None,
+        };
+
+        content
+            .map(|content| Inventory::deserialize(&content, self))
+            .transpose()
+    }
+
+    /// Gets a shard of the inventory of this collection, if present in the local database.
+    fn inventory_shard(&self, shard: &InventoryShard) -> Result<Option<Inventory>, crate::Error> {
+        ObjectRef::new(shard.hash)
+            .content()?
+            .map(|content| Inventory::deserialize(&content, self))
+            .transpose()
+    }
+
+    /// Gets the inventory of this collection, if the inventory (with all its shards, if
+    /// sharded) is present in the local database.
+    pub fn inventory(&self) -> Result<Option<Inventory>, crate::Error> {
+        let Some(mut inventory) = self.inventory_index()? else {
+            return Ok(None);
+        };
+
+        for shard in inventory.shards.clone() {
+            match self.inventory_shard(&shard)? {
+                Some(shard) => inventory.merge(shard),
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(inventory))
+    }
+
+    /// Gets the metadata of an item, if the inventory of this collection (or the shard listing
+    /// the item, if sharded) is present in the local database and the publisher set any.
+    pub fn item_metadata(&self, name: ItemPath) -> Result<Option<ItemMetadata>, crate::Error> {
+        let path = ItemPathBuf::from(name.as_str());
+        let Some(index) = self.inventory_index()? else {
+            return Ok(None);
+        };
+
+        let inventory = match index.shard_for(&path) {
+            Some(shard) => self.inventory_shard(&index.shards[shard])?,
+            None => Some(index),
+        };
+
+        Ok(inventory.and_then(|inventory| inventory.metadata(&path).cloned()))
+    }
+
+    pub fn list(&'_ self) -> impl '_ + Iterator<Item = ItemPathBuf> {
+        db().prefix_iterator_cf(Table::CollectionItemLocators.get(), self.hash.as_ref())
+            .map(move |(key, _)| {
+                ItemPathBuf::from(&*String::from_utf8_lossy(&key[self.hash.as_ref().len()..]))
+            })
+    }
+
+    pub fn list_objects(&'_ self) -> impl '_ + Iterator<Item = Result<ObjectRef, crate::Error>> {
+        self.list().filter_map(move |name| {
+            let locator = Locator {
+                collection: self.clone(),
+                name: name.as_path(),
+            };
+            locator.get_object().transpose()
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Locator<'a> {
+    collection: CollectionRef,
+    name: ItemPath<'a>,
+}
+
+impl<'a> Display for Locator<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.collection.hash, self.name.0)
+    }
+}
+
+impl<'a> Locator<'a> {
+    pub fn hash(&self) -> Hash {
+        self.collection
+            .hash
+            .rehash(&Hash::hash(self.name.0.as_ref()))
+    }
+
+    pub fn collection(&self) -> CollectionRef {
+        self.collection.clone()
+    }
+
+    pub fn name(&self) -> ItemPath<'a> {
+        self.name.clone()
+    }
+
+    pub fn path(&self) -> Vec<u8> {
+        [self.collection.hash.as_ref(), self.name.0.as_bytes()].concat()
+    }
+
+    pub fn get(&self) -> Result<Option<CollectionItem>, crate::Error> {
+        self.collection.get(self.name.clone())
+    }
+
+    pub fn get_object(&self) -> Result<Option<ObjectRef>, crate::Error> {
+        if let Some(item) = self.get()? {
+            Ok(Some(item.object()?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[test]
+fn diff_inventories() {
+    let (kept, changed, new_changed, removed, added) = (
+        Hash::rand(),
+        Hash::rand(),
+        Hash::rand(),
+        Hash::rand(),
+        Hash::rand(),
+    );
+    let old = vec![
+        ("kept".into(), kept),
+        ("changed".into(), changed),
+        ("removed".into(), removed),
+    ]
+    .into_iter()
+    .collect::<Inventory>();
+    let new = vec![
+        ("kept".into(), kept),
+        ("changed".into(), new_changed),
+        ("added".into(), added),
+    ]
+    .into_iter()
+    .collect::<Inventory>();
+
+    let diff = old.diff(&new);
+
+    assert_eq!(diff.added, vec![("added".into(), added)]);
+    assert_eq!(diff.removed, vec![("removed".into(), removed)]);
+    assert_eq!(diff.changed, vec![("changed".into(), changed, new_changed)]);
+}
+
+#[test]
+fn item_metadata_headers() {
+    let metadata = ItemMetadata {
+        cache_control: Some("no-cache".to_owned()),
+        content_language: None,
+        charset: Some("utf-8".to_owned()),
+        ..ItemMetadata::default()
+    };
+
+    assert_eq!(
+        metadata.content_type("text/html"),
+        "text/html; charset=utf-8"
+    );
+    assert_eq!(
+        metadata.content_type("text/html; charset=latin1"),
+        "text/html; charset=latin1"
+    );
+    assert_eq!(
+        metadata.headers(),
+        vec![("Cache-Control", "no-cache".to_owned())]
+    );
+}
+
+#[test]
+fn find_inventory_shard() {
+    let shard = |first: &str| InventoryShard {
+        first: first.into(),
+        hash: Hash::rand(),
+    };
+    let mut inventory = Vec::new().into_iter().collect::<Inventory>();
+
+    assert_eq!(inventory.shard_for(&"a".into()), None);
+
+    inventory.shards = vec![shard("a"), shard("m"), shard("t")];
+
+    assert_eq!(inventory.shard_for(&"a".into()), Some(0));
+    assert_eq!(inventory.shard_for(&"b/c".into()), Some(0));
+    assert_eq!(inventory.shard_for(&"m".into()), Some(1));
+    assert_eq!(inventory.shard_for(&"s".into()), Some(1));
+    assert_eq!(inventory.shard_for(&"z".into()), Some(2));
+}
+
+#[test]
+fn find_mount() {
+    let (fonts, assets) = (Hash::rand(), Hash::rand());
+    let mut inventory = Vec::new().into_iter().collect::<Inventory>();
+    inventory.mounts = [
+        ("assets".into(), Mount::Collection(assets)),
+        ("assets/fonts".into(), Mount::Collection(fonts)),
+    ]
+    .into_iter()
+    .collect();
+
+    let mount_for = |path: &str| {
+        inventory
+            .mount_for(&path.into())
+            .map(|(mount, rest)| (mount.clone(), rest.to_string()))
+    };
+
+    assert_eq!(mount_for("index.html"), None);
+    assert_eq!(mount_for("assets"), None);
+    assert_eq!(mount_for("assetsy/a.js"), None);
+    assert_eq!(
+        mount_for("assets/a.js"),
+        Some((Mount::Collection(assets), "a.js".to_owned()))
+    );
+    assert_eq!(
+        mount_for("/assets/fonts/serif.woff"),
+        Some((Mount::Collection(fonts), "serif.woff".to_owned()))
+    );
+}
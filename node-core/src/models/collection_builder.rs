@@ -0,0 +1,230 @@
+use chrono::{DateTime, Utc};
+use rocksdb::WriteBatch;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::sync::Mutex;
+
+use samizdat_common::Hash;
+
+use crate::db;
+use crate::db::Table;
+
+use super::{CollectionRef, Droppable, ItemPathBuf, ObjectRef};
+
+/// (s) How long a builder may live, finished or not, before the vacuum discards it.
+const MAX_BUILDER_AGE: i64 = 86_400;
+
+/// How many items are checked between two progress reports.
+const PROGRESS_INTERVAL: usize = 1_000;
+
+lazy_static::lazy_static! {
+    /// Serializes changes to builders, so that their item counts stay right.
+    static ref CHANGING: Mutex<()> = Mutex::default();
+}
+
+/// A collection being built from objects already present in this node, a batch of items at
+/// a time, so that huge collections do not need one huge request. Items are kept in the
+/// database, after the builder itself, under keys starting with the builder id. The objects
+/// of the items are kept from the vacuum until the builder is finalized, discarded or expires.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionBuilder {
+    /// A random id for the builder.
+    pub id: Hash,
+    /// When the builder was created.
+    pub created_at: DateTime<Utc>,
+    /// How many items the builder has.
+    pub total: usize,
+}
+
+/// What happened to a batch of items added to a builder.
+#[derive(Debug, Default, Serialize)]
+pub struct AddedItems {
+    /// How many items were added (or replaced).
+    pub added: usize,
+    /// The items left out, because their objects are not present in this node.
+    pub missing: Vec<ItemPathBuf>,
+    /// How many items the builder has now.
+    pub total: usize,
+}
+
+impl Display for CollectionBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "collection builder {}", self.id)
+    }
+}
+
+impl Droppable for CollectionBuilder {
+    fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error> {
+        for (key, _) in db().prefix_iterator_cf(Table::CollectionBuilders.get(), self.id) {
+            if !key.starts_with(self.id.as_ref()) {
+                break;
+            }
+
+            batch.delete_cf(Table::CollectionBuilders.get(), key);
+        }
+
+        Ok(())
+    }
+}
+
+impl CollectionBuilder {
+    /// Creates a new empty builder.
+    pub fn create() -> Result<CollectionBuilder, crate::Error> {
+        let builder = CollectionBuilder {
+            id: Hash::rand(),
+            created_at: Utc::now(),
+            total: 0,
+        };
+
+        let mut batch = WriteBatch::default();
+        builder.insert_with(&mut batch);
+        db().write(batch)?;
+
+        Ok(builder)
+    }
+
+    fn insert_with(&self, batch: &mut WriteBatch) {
+        batch.put_cf(
+            Table::CollectionBuilders.get(),
+            self.id,
+            bincode::serialize(self).expect("can serialize"),
+        );
+    }
+
+    pub fn get(id: Hash) -> Result<Option<CollectionBuilder>, crate::Error> {
+        match db().get_cf(Table::CollectionBuilders.get(), id)? {
+            Some(serialized) => Ok(Some(bincode::deserialize(&serialized)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn item_key(&self, path: &ItemPathBuf) -> Vec<u8> {
+        [self.id.as_ref(), path.as_str().as_bytes()].concat()
+    }
+
+    /// Adds items to the collection being built under a path prefix, or at the root, if the
+    /// prefix is empty, replacing items with the same path. Items whose objects are not
+    /// present in this node are left out. The number of items checked so far is reported to
+    /// `progress` every once in a while.
+    pub fn add<I>(
+        &self,
+        prefix: &ItemPathBuf,
+        items: I,
+        mut progress: impl FnMut(usize),
+    ) -> Result<AddedItems, crate::Error>
+    where
+        I: IntoIterator<Item = (ItemPathBuf, Hash)>,
+    {
+        let _changing = CHANGING.lock().expect("poisoned");
+
+        // Another request may have added items since `self` was read:
+        let mut current = CollectionBuilder::get(self.id)?
+            .ok_or_else(|| crate::Error::NotFound(format!("{self}")))?;
+        let mut batch = WriteBatch::default();
+        let mut outcome = AddedItems::default();
+        let mut new_keys = BTreeSet::new();
+
+        for (checked, (path, hash)) in items.into_iter().enumerate() {
+            if checked > 0 && checked % PROGRESS_INTERVAL == 0 {
+                progress(checked);
+            }
+
+            let path = if prefix.as_str().is_empty() {
+                path
+            } else {
+                ItemPathBuf::from(format!("{prefix}/{path}"))
+            };
+
+            if ObjectRef::new(hash).metadata()?.is_none() {
+                outcome.missing.push(path);
+                continue;
+            }
+
+            let key = self.item_key(&path);
+            let is_new = db()
+                .get_pinned_cf(Table::CollectionBuilders.get(), &key)?
+                .is_none();
+            if is_new && new_keys.insert(key.clone()) {
+                current.total += 1;
+            }
+
+            batch.put_cf(Table::CollectionBuilders.get(), key, hash);
+            outcome.added += 1;
+        }
+
+        current.insert_with(&mut batch);
+        db().write(batch)?;
+        outcome.total = current.total;
+
+        Ok(outcome)
+    }
+
+    /// Adds all items of an existing collection under a path prefix, as in [`Self::add`]. The
+    /// inventory of the collection must be present in this node.
+    pub fn add_collection(
+        &self,
+        collection: &CollectionRef,
+        prefix: &ItemPathBuf,
+        progress: impl FnMut(usize),
+    ) -> Result<AddedItems, crate::Error> {
+        let inventory = collection.inventory()?.ok_or_else(|| {
+            crate::Error::NotFound(format!("inventory of collection {}", collection.hash()))
+        })?;
+
+        self.add(
+            prefix,
+            inventory.iter().map(|(path, hash)| (path.clone(), *hash)),
+            progress,
+        )
+    }
+
+    /// The items added so far, in path order.
+    pub fn items(&self) -> Result<Vec<(ItemPathBuf, ObjectRef)>, crate::Error> {
+        db().prefix_iterator_cf(Table::CollectionBuilders.get(), self.id)
+            .take_while(|(key, _)| key.starts_with(self.id.as_ref()))
+            .filter(|(key, _)| key.len() > self.id.as_ref().len())
+            .map(|(key, value)| {
+                let path = String::from_utf8_lossy(&key[self.id.as_ref().len()..]);
+                let hash = Hash::try_from(&*value)?;
+                Ok((ItemPathBuf::from(&*path), ObjectRef::new(hash)))
+            })
+            .collect()
+    }
+
+    /// Discards the builders older than [`MAX_BUILDER_AGE`], returning the objects of the
+    /// items of the builders that are left, which are not to be vacuumed.
+    pub fn drop_expired() -> Result<BTreeSet<Hash>, crate::Error> {
+        let _changing = CHANGING.lock().expect("poisoned");
+        let min_created_at = Utc::now() - chrono::Duration::seconds(MAX_BUILDER_AGE);
+        let mut batch = WriteBatch::default();
+        let mut in_use = BTreeSet::new();
+        let mut is_expired = false;
+
+        // Builders come right before their items:
+        for (key, value) in db().iterator_cf(
+            Table::CollectionBuilders.get(),
+            rocksdb::IteratorMode::Start,
+        ) {
+            if key.len() == std::mem::size_of::<Hash>() {
+                let builder: CollectionBuilder = bincode::deserialize(&value)?;
+                is_expired = builder.created_at < min_created_at;
+
+                if is_expired {
+                    log::info!("Discarding expired {builder}");
+                }
+            }
+
+            if is_expired {
+                batch.delete_cf(Table::CollectionBuilders.get(), key);
+            } else if key.len() > std::mem::size_of::<Hash>() {
+                in_use.insert(Hash::try_from(&*value)?);
+            }
+        }
+
+        db().write(batch)?;
+
+        Ok(in_use)
+    }
+}
@@ -0,0 +1,116 @@
+use rocksdb::{IteratorMode, WriteBatch};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use samizdat_common::{Hash, Key};
+
+use crate::db;
+use crate::db::Table;
+
+use super::Droppable;
+
+/// The name of the item holding the [`DirectoryDocument`] in each edition of a hub directory
+/// series.
+pub const DIRECTORY_ITEM: &str = "hub-directory.json";
+
+/// A hub, as listed in a hub directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectoryEntry {
+    /// The address of the hub, in the same format as the `--hubs` command line argument.
+    pub address: String,
+    /// The public key of the hub, if it has one.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// The policies of the hub (e.g., retention or moderation), free-form, for the user to
+    /// read.
+    #[serde(default)]
+    pub policies: BTreeMap<String, String>,
+}
+
+/// The document published by a hub directory. It is signed by the edition that carries it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DirectoryDocument {
+    pub hubs: Vec<DirectoryEntry>,
+}
+
+/// A series publishing a list of hubs, to which this node is subscribed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubDirectory {
+    /// The public key of the series.
+    pub public_key: Key,
+    /// Whether the user confirmed this directory as a trust root. The hubs of directories that
+    /// are not trusted are listed, but never connected to.
+    pub is_trusted: bool,
+    /// The hubs listed in the latest edition seen.
+    pub hubs: Vec<DirectoryEntry>,
+    /// The collection of the latest edition seen, if any.
+    pub collection: Option<Hash>,
+    /// When the latest edition seen was published.
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl HubDirectory {
+    /// A new directory, not yet trusted and without any hubs.
+    pub fn new(public_key: Key) -> HubDirectory {
+        HubDirectory {
+            public_key,
+            is_trusted: false,
+            hubs: vec![],
+            collection: None,
+            updated_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubDirectoryRef {
+    pub public_key: Key,
+}
+
+impl Display for HubDirectoryRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hub directory {}", self.public_key)
+    }
+}
+
+impl Droppable for HubDirectoryRef {
+    fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error> {
+        batch.delete_cf(Table::HubDirectories.get(), self.public_key.as_bytes());
+        Ok(())
+    }
+}
+
+impl HubDirectoryRef {
+    pub fn new(public_key: Key) -> HubDirectoryRef {
+        HubDirectoryRef { public_key }
+    }
+
+    /// Inserts or replaces a directory.
+    pub fn build(directory: HubDirectory) -> Result<HubDirectoryRef, crate::Error> {
+        let directory_ref = HubDirectoryRef {
+            public_key: directory.public_key.clone(),
+        };
+
+        db().put_cf(
+            Table::HubDirectories.get(),
+            directory_ref.public_key.as_bytes(),
+            bincode::serialize(&directory).expect("can serialize"),
+        )?;
+
+        Ok(directory_ref)
+    }
+
+    pub fn get(&self) -> Result<Option<HubDirectory>, crate::Error> {
+        let maybe_value = db().get_cf(Table::HubDirectories.get(), self.public_key.as_bytes())?;
+        Ok(maybe_value
+            .map(|value| bincode::deserialize(&value))
+            .transpose()?)
+    }
+
+    pub fn get_all() -> Result<Vec<HubDirectory>, crate::Error> {
+        db().iterator_cf(Table::HubDirectories.get(), IteratorMode::Start)
+            .map(|(_, value)| Ok(bincode::deserialize(&value)?))
+            .collect::<Result<Vec<_>, crate::Error>>()
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+use rocksdb::IteratorMode;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+use samizdat_common::Hash;
+
+use crate::db;
+use crate::db::Table;
+
+/// The key of a hub, pinned the first time this node connects to it: the fingerprint of the
+/// TLS certificate the hub presents. Connections to a hub presenting another certificate are
+/// refused until the user accepts the new one (or forgets the pinned one), so that nobody in
+/// the network path can pass for the hub. Only hubs advertising that they keep their
+/// certificate across restarts get pinned, since older hubs present a new one each time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubKey {
+    /// The name of the hub, as given in `--hubs` or by a hub directory.
+    pub hub: String,
+    /// The fingerprint trusted for the hub.
+    pub pinned: String,
+    pub pinned_at: DateTime<Utc>,
+    /// The last fingerprint presented by the hub that was not the pinned one, if any. This is
+    /// what the user accepts when the key of the hub changes.
+    pub presented: Option<String>,
+    pub presented_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubKeyRef {
+    pub hub: String,
+}
+
+impl Display for HubKeyRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "key of hub {}", self.hub)
+    }
+}
+
+impl HubKeyRef {
+    pub fn new(hub: impl Into<String>) -> HubKeyRef {
+        HubKeyRef { hub: hub.into() }
+    }
+
+    fn put(&self, key: &HubKey) -> Result<(), crate::Error> {
+        db().put_cf(
+            Table::HubKeys.get(),
+            self.hub.as_bytes(),
+            bincode::serialize(key).expect("can serialize"),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get(&self) -> Result<Option<HubKey>, crate::Error> {
+        let maybe_value = db().get_cf(Table::HubKeys.get(), self.hub.as_bytes())?;
+        Ok(maybe_value
+            .map(|value| bincode::deserialize(&value))
+            .transpose()?)
+    }
+
+    pub fn get_all() -> Result<Vec<HubKey>, crate::Error> {
+        db().iterator_cf(Table::HubKeys.get(), IteratorMode::Start)
+            .map(|(_, value)| Ok(bincode::deserialize(&value)?))
+            .collect::<Result<Vec<_>, crate::Error>>()
+    }
+
+    /// Checks the fingerprint presented by the hub against the pinned one, pinning it if the
+    /// hub was never seen before and `is_persistent`, i.e., the hub keeps its key across
+    /// restarts. A different fingerprint is remembered, to be accepted by the user, and fails
+    /// with [`crate::Error::HubKeyChanged`]. Pinned keys are checked even if the hub stops
+    /// claiming to be persistent, since anybody passing for the hub could claim that.
+    pub fn check(&self, presented: Hash, is_persistent: bool) -> Result<(), crate::Error> {
+        let presented = presented.to_string();
+        let Some(mut key) = self.get()? else {
+            if !is_persistent {
+                log::debug!("not pinning {self}, since the hub does not keep it");
+                return Ok(());
+            }
+
+            log::info!("pinning {self} as {presented}");
+            return self.put(&HubKey {
+                hub: self.hub.clone(),
+                pinned: presented,
+                pinned_at: Utc::now(),
+                presented: None,
+                presented_at: None,
+            });
+        };
+
+        if key.pinned == presented {
+            return Ok(());
+        }
+
+        if key.presented.as_ref() != Some(&presented) {
+            log::error!(
+                "{self} changed from {} to {presented}; refusing to connect",
+                key.pinned
+            );
+            key.presented = Some(presented.clone());
+            key.presented_at = Some(Utc::now());
+            self.put(&key)?;
+        }
+
+        Err(crate::Error::HubKeyChanged {
+            hub: self.hub.clone(),
+            pinned: key.pinned,
+            presented,
+        })
+    }
+
+    /// Trusts the fingerprint the hub presented after its key changed, if any.
+    pub fn accept(&self) -> Result<HubKey, crate::Error> {
+        let mut key = self
+            .get()?
+            .ok_or_else(|| crate::Error::NotFound(self.to_string()))?;
+        let presented = key
+            .presented
+            .take()
+            .ok_or_else(|| format!("{self} did not change"))?;
+
+        log::info!("accepting {presented} as the new {self}");
+        key.pinned = presented;
+        key.pinned_at = Utc::now();
+        key.presented_at = None;
+        self.put(&key)?;
+
+        Ok(key)
+    }
+
+    /// Forgets the pinned key, if any, so that the next key the hub presents gets pinned (if
+    /// the hub keeps it). Returns whether there was a key.
+    pub fn forget(&self) -> Result<bool, crate::Error> {
+        let existed = self.get()?.is_some();
+
+        if existed {
+            log::info!("forgetting {self}");
+            db().delete_cf(Table::HubKeys.get(), self.hub.as_bytes())?;
+        }
+
+        Ok(existed)
+    }
+}
@@ -0,0 +1,74 @@
+use rocksdb::{IteratorMode, WriteBatch};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+
+use samizdat_common::Key;
+
+use crate::db;
+use crate::db::Table;
+
+use super::Droppable;
+
+/// Restricts all traffic concerning a series (e.g., a series bearing the user's identity) to
+/// some designated hubs, so that public hubs never see it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubRoute {
+    /// The public key of the series.
+    pub public_key: Key,
+    /// The names of the hubs, as shown in the connection status, through which the series is
+    /// routed.
+    pub hubs: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubRouteRef {
+    pub public_key: Key,
+}
+
+impl Display for HubRouteRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hub route for {}", self.public_key)
+    }
+}
+
+impl Droppable for HubRouteRef {
+    fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error> {
+        batch.delete_cf(Table::HubRoutes.get(), self.public_key.as_bytes());
+        Ok(())
+    }
+}
+
+impl HubRouteRef {
+    pub fn new(public_key: Key) -> HubRouteRef {
+        HubRouteRef { public_key }
+    }
+
+    /// Inserts or replaces a route.
+    pub fn build(route: HubRoute) -> Result<HubRouteRef, crate::Error> {
+        let route_ref = HubRouteRef {
+            public_key: route.public_key.clone(),
+        };
+
+        db().put_cf(
+            Table::HubRoutes.get(),
+            route_ref.public_key.as_bytes(),
+            bincode::serialize(&route).expect("can serialize"),
+        )?;
+
+        Ok(route_ref)
+    }
+
+    pub fn get(&self) -> Result<Option<HubRoute>, crate::Error> {
+        let maybe_value = db().get_cf(Table::HubRoutes.get(), self.public_key.as_bytes())?;
+        Ok(maybe_value
+            .map(|value| bincode::deserialize(&value))
+            .transpose()?)
+    }
+
+    pub fn get_all() -> Result<Vec<HubRoute>, crate::Error> {
+        db().iterator_cf(Table::HubRoutes.get(), IteratorMode::Start)
+            .map(|(_, value)| Ok(bincode::deserialize(&value)?))
+            .collect::<Result<Vec<_>, crate::Error>>()
+    }
+}
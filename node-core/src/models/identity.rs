@@ -24,12 +24,12 @@ impl FromStr for IdentityRef {
     type Err = crate::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            invalid @ ("" | "~" | "." | "..") => {
-                Err(format!("Identity handle cannot be `{invalid}`").into())
-            }
-            s if s.starts_with('_') => {
-                Err(format!("Identity handle `{s}` starting with `_`").into())
-            }
+            invalid @ ("" | "~" | "." | "..") => Err(crate::Error::ValidationFailed(format!(
+                "identity handle cannot be `{invalid}`"
+            ))),
+            s if s.starts_with('_') => Err(crate::Error::ValidationFailed(format!(
+                "identity handle `{s}` starting with `_`"
+            ))),
             s => Ok(IdentityRef {
                 handle: s.to_owned(),
             }),
@@ -180,7 +180,7 @@ impl Identity {
             .get_cf(Table::Identities.get(), identity.hash())?
             .is_some()
         {
-            return Err(crate::Error::Message(format!(
+            return Err(crate::Error::ValidationFailed(format!(
                 "Identity `{identity}` already exists. Delete it first!"
             )));
         }
@@ -0,0 +1,49 @@
+//! Models for the entities living in the node database.
+
+mod bookmark;
+mod collection;
+mod collection_builder;
+mod hub_directory;
+mod hub_key;
+mod hub_route;
+mod identity;
+mod object;
+mod reply;
+mod series;
+mod series_trust;
+mod subscription;
+mod webhook;
+
+pub use bookmark::{Bookmark, BookmarkType};
+pub use collection::{
+    CollectionItem, CollectionRef, Inventory, ItemMetadata, ItemPath, ItemPathBuf, Locator, Mount,
+};
+pub use collection_builder::{AddedItems, CollectionBuilder};
+pub use hub_directory::{DirectoryDocument, HubDirectory, HubDirectoryRef, DIRECTORY_ITEM};
+pub use hub_key::HubKeyRef;
+pub use hub_route::{HubRoute, HubRouteRef};
+pub use identity::{Identity, IdentityRef};
+pub use object::{
+    ContentIter, ObjectHeader, ObjectMetadata, ObjectRef, ObjectStatistics, UsePrior, CHUNK_SIZE,
+};
+pub use reply::{reply_topic, Reply};
+pub use series::{Edition, SeriesOwner, SeriesRef};
+pub use series_trust::{SeriesTrust, SeriesTrustRef, TrustLevel};
+pub use subscription::{Subscription, SubscriptionKind, SubscriptionRef};
+pub use webhook::{Webhook, WebhookRef};
+
+use rocksdb::WriteBatch;
+
+use crate::db;
+
+pub trait Droppable {
+    fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error>;
+
+    fn drop_if_exists(&self) -> Result<(), crate::Error> {
+        let mut batch = WriteBatch::default();
+        self.drop_if_exists_with(&mut batch)?;
+        db().write(batch)?;
+
+        Ok(())
+    }
+}
@@ -2,11 +2,17 @@ use chrono::{DateTime, Utc};
 use futures::prelude::*;
 use rocksdb::{IteratorMode, WriteBatch};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::convert::TryInto;
+use tokio::time::{Duration, Instant};
 
 use samizdat_common::{Hash, MerkleTree, Riddle};
 
-use crate::db::{db, Table};
+use crate::chunk_cache;
+use crate::db::{db, is_replica, Page, PageQuery, Table};
+use crate::events::{self, Event};
+use crate::system::queries;
+use crate::vacuum::InFlight;
 
 use super::{Bookmark, BookmarkType, Droppable};
 
@@ -14,6 +20,19 @@ use super::{Bookmark, BookmarkType, Droppable};
 /// of which are used to create the Merkle tree whose root hash is the object
 pub const CHUNK_SIZE: usize = 256_000;
 
+/// How many chunks are written to the database at once when importing an object.
+const IMPORT_BATCH_SIZE: usize = 16;
+
+/// How many batches of chunks may be hashed and written at the same time when importing an
+/// object. Together with [`IMPORT_BATCH_SIZE`], this bounds the memory used by an import.
+const MAX_IMPORT_BATCHES_IN_FLIGHT: usize = 4;
+
+/// The time between two progress events of the same import.
+const IMPORT_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many chunks are read from the database at once when streaming an object.
+const READAHEAD_CHUNKS: usize = 8;
+
 /// The first section before the actual content of the object. The header is
 /// encoded as a null-escaped byte sequence in the beginning of the first chunk.
 #[derive(Debug, Serialize, Deserialize)]
@@ -113,11 +132,98 @@ impl ObjectHeader {
     }
 }
 
-/// Helper function to get a chunk by its hash in the database.
-fn get_chunk(hash: Hash) -> Result<Vec<u8>, crate::Error> {
-    Ok(db()
-        .get_cf(Table::ObjectChunks.get(), &hash)?
-        .ok_or_else(|| format!("Chunk missing: {}", hash))?)
+/// Helper function to get many chunks by their hashes, in the same order, looking up all the
+/// chunks missing in the [`chunk_cache`] in the database at once. Each chunk is read or fails
+/// on its own, so that the chunks before a bad one can still be used.
+fn get_chunks(hashes: &[Hash]) -> Vec<Result<Vec<u8>, crate::Error>> {
+    let mut chunks = hashes
+        .iter()
+        .map(|&hash| chunk_cache::get(hash).map(|chunk| Ok(Vec::clone(&chunk))))
+        .collect::<Vec<_>>();
+    let missing = hashes
+        .iter()
+        .zip(&chunks)
+        .filter(|(_, chunk)| chunk.is_none())
+        .map(|(&hash, _)| hash)
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        return chunks.into_iter().flatten().collect();
+    }
+
+    let cf = Table::ObjectChunks.get();
+    let mut found = db()
+        .multi_get_cf(missing.iter().map(|hash| (cf, hash)))
+        .into_iter()
+        .zip(&missing)
+        .map(|(chunk, &hash)| {
+            let chunk =
+                chunk?.ok_or_else(|| crate::Error::Storage(format!("chunk missing: {}", hash)))?;
+            chunk_cache::insert(hash, chunk.clone());
+            Ok(chunk)
+        })
+        .collect::<VecDeque<_>>();
+
+    for chunk in &mut chunks {
+        if chunk.is_none() {
+            *chunk = found.pop_front();
+        }
+    }
+
+    chunks.into_iter().flatten().collect()
+}
+
+/// Reads a batch of chunks, in order. This is [`get_chunks`], except in tests.
+type ReadBatch = fn(&[Hash]) -> Vec<Result<Vec<u8>, crate::Error>>;
+
+/// Reads the chunks of an object in order, fetching [`READAHEAD_CHUNKS`] chunks from the
+/// database at a time, so that streaming a large object does not cost one lookup per chunk.
+struct ChunkReader {
+    /// The hashes of the chunks not read yet.
+    hashes: std::vec::IntoIter<Hash>,
+    /// The chunks read ahead and not yielded yet, or the errors reading them.
+    ready: VecDeque<Result<Vec<u8>, crate::Error>>,
+    read_batch: ReadBatch,
+}
+
+impl ChunkReader {
+    fn new(hashes: Vec<Hash>) -> ChunkReader {
+        ChunkReader::with_read_batch(hashes, get_chunks)
+    }
+
+    fn with_read_batch(hashes: Vec<Hash>, read_batch: ReadBatch) -> ChunkReader {
+        ChunkReader {
+            hashes: hashes.into_iter(),
+            ready: VecDeque::new(),
+            read_batch,
+        }
+    }
+
+    /// The number of chunks not yielded yet.
+    fn len(&self) -> usize {
+        self.ready.len() + self.hashes.len()
+    }
+
+    /// Skips the next chunk without reading it, if it was not read ahead already.
+    fn skip(&mut self) {
+        if self.ready.pop_front().is_none() {
+            self.hashes.next();
+        }
+    }
+
+    fn next_chunk(&mut self) -> Option<Result<Vec<u8>, crate::Error>> {
+        if self.ready.is_empty() {
+            let batch = self
+                .hashes
+                .by_ref()
+                .take(READAHEAD_CHUNKS)
+                .collect::<Vec<_>>();
+
+            self.ready.extend((self.read_batch)(&batch));
+        }
+
+        self.ready.pop_front()
+    }
 }
 
 /// Information about the object that is "out of band", that is, does not compose the hash
@@ -137,8 +243,8 @@ pub struct ObjectMetadata {
 
 /// An iterator over the bytes of an object, including its header.
 pub struct ContentIter {
-    /// An iterator over hashes.
-    hashes: std::vec::IntoIter<Hash>,
+    /// The reader of the chunks to come.
+    chunks: ChunkReader,
     /// An iterator over the current chunk.
     current_chunk: Option<std::vec::IntoIter<u8>>,
     /// Indicates whether an error has occurred.
@@ -161,8 +267,8 @@ impl Iterator for ContentIter {
         }
 
         // Try get new chunk:
-        if let Some(hash) = self.hashes.next() {
-            match get_chunk(hash) {
+        if let Some(chunk) = self.chunks.next_chunk() {
+            match chunk {
                 // Found chunk? Load an try again!
                 Ok(chunk) => {
                     self.current_chunk = Some(chunk.into_iter());
@@ -181,10 +287,32 @@ impl Iterator for ContentIter {
     }
 }
 
+impl ContentIter {
+    /// Skips some bytes of the content, without reading the chunks skipped as a whole.
+    pub fn skip_bytes(&mut self, mut count: usize) -> Result<(), crate::Error> {
+        if let Some(chunk) = self.current_chunk.as_mut() {
+            let skipped = count.min(chunk.len());
+            chunk.by_ref().take(skipped).for_each(drop);
+            count -= skipped;
+        }
+
+        // All chunks but the last are full:
+        while count >= CHUNK_SIZE && self.chunks.len() > 1 {
+            self.chunks.skip();
+            self.current_chunk = None;
+            count -= CHUNK_SIZE;
+        }
+
+        self.by_ref()
+            .take(count)
+            .try_for_each(|byte| byte.map(drop))
+    }
+}
+
 /// An iterator over the chunks of an object.
 pub struct ChunkIter {
-    /// An iterator over hashes.
-    hashes: std::vec::IntoIter<Hash>,
+    /// The reader of the chunks to come.
+    chunks: ChunkReader,
     /// Indicates whether an error has occurred.
     is_error: bool,
 }
@@ -198,8 +326,8 @@ impl Iterator for ChunkIter {
         }
 
         // Try get new chunk:
-        if let Some(hash) = self.hashes.next() {
-            match get_chunk(hash) {
+        if let Some(chunk) = self.chunks.next_chunk() {
+            match chunk {
                 // Found chunk? Yield.
                 Ok(chunk) => {
                     return Some(Ok(chunk));
@@ -228,22 +356,14 @@ impl Droppable for ObjectRef {
     fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error> {
         log::info!("Removing object {:?}", self);
 
-        let metadata: ObjectMetadata = match db().get_cf(Table::ObjectMetadata.get(), &self.hash)? {
-            Some(serialized) => bincode::deserialize(&serialized)?,
-            None => return Ok(()),
-        };
-
-        for hash in &metadata.hashes {
-            batch.delete_cf(Table::ObjectChunks.get(), hash);
+        if self.drop_content_with(batch)? {
+            self.bookmark(BookmarkType::Reference).clear_with(batch);
+            self.bookmark(BookmarkType::User).clear_with(batch);
+            // The mirror bookmark is counted by the mirrored editions pinning this object and
+            // outlives the content, so that it is fetched again (see `MirrorPins`).
+            batch.delete_cf(Table::ServedCounts.get(), self.hash);
         }
 
-        self.bookmark(BookmarkType::Reference).clear_with(batch);
-        self.bookmark(BookmarkType::User).clear_with(batch);
-
-        batch.delete_cf(Table::ObjectStatistics.get(), &self.hash);
-        batch.delete_cf(Table::ObjectMetadata.get(), &self.hash);
-        batch.delete_cf(Table::Objects.get(), &self.hash);
-
         Ok(())
     }
 }
@@ -259,6 +379,28 @@ impl ObjectRef {
         &self.hash
     }
 
+    /// Removes the content of this object from the database, but keeps its bookmarks in
+    /// place. This is useful when the object is to be fetched again from the network. Returns
+    /// `Ok(false)` if the object does not exist.
+    pub fn drop_content_with(&self, batch: &mut WriteBatch) -> Result<bool, crate::Error> {
+        let metadata: ObjectMetadata = match db().get_cf(Table::ObjectMetadata.get(), &self.hash)? {
+            Some(serialized) => bincode::deserialize(&serialized)?,
+            None => return Ok(false),
+        };
+
+        for hash in &metadata.hashes {
+            batch.delete_cf(Table::ObjectChunks.get(), hash);
+            chunk_cache::remove(hash);
+        }
+
+        batch.delete_cf(Table::ObjectStatistics.get(), &self.hash);
+        batch.delete_cf(Table::ObjectExpiries.get(), &self.hash);
+        batch.delete_cf(Table::ObjectMetadata.get(), &self.hash);
+        batch.delete_cf(Table::Objects.get(), &self.hash);
+
+        Ok(true)
+    }
+
     /// Returns the metadata on this object. This function returns `Ok(None)` if the object
     /// does not actually exist.
     pub fn metadata(&self) -> Result<Option<ObjectMetadata>, crate::Error> {
@@ -278,14 +420,26 @@ impl ObjectRef {
         }
     }
 
+    /// Gets the time after which this object is to be deleted, if any.
+    pub fn expires_at(&self) -> Result<Option<DateTime<Utc>>, crate::Error> {
+        Ok(db()
+            .get_cf(Table::ObjectExpiries.get(), self.hash)?
+            .map(|serialized| bincode::deserialize(&serialized))
+            .transpose()?)
+    }
+
     /// Update statistics indicating that this object was used. This will signal to the
     /// vacuum daemon that this object is useful and therefore a worse candidate for deletion.
     ///
-    /// This function has no effect if the object does not exist.
+    /// This function has no effect if the object does not exist or if this node is a replica.
     ///
     /// TODO: current impl allows for TOCTOU. Need transactions, which are not exposed in the
     /// Rust API as of oct 2021.
     pub fn touch(&self) -> Result<(), crate::Error> {
+        if is_replica() {
+            return Ok(());
+        }
+
         if let Some(statistics) = db().get_cf(Table::ObjectStatistics.get(), self.hash)? {
             let mut statistics: ObjectStatistics = bincode::deserialize(&statistics)?;
             statistics.touch();
@@ -299,6 +453,25 @@ impl ObjectRef {
         Ok(())
     }
 
+    /// Lists a page of the objects in the database, by hash, with their metadata. Optionally,
+    /// lists only the objects whose content type starts with a prefix and only the draft (or
+    /// non-draft) ones.
+    pub fn get_page(
+        query: &PageQuery,
+        content_type: Option<&str>,
+        is_draft: Option<bool>,
+    ) -> Result<Page<(ObjectRef, ObjectMetadata)>, crate::Error> {
+        Page::list(Table::ObjectMetadata, query, |key, value| {
+            let hash = Hash::try_from(key)?;
+            let metadata: ObjectMetadata = bincode::deserialize(value)?;
+            let is_match = content_type
+                .is_none_or(|prefix| metadata.header.content_type().starts_with(prefix))
+                && is_draft.is_none_or(|is_draft| metadata.header.is_draft() == is_draft);
+
+            Ok(is_match.then_some((ObjectRef { hash }, metadata)))
+        })
+    }
+
     /// Tries to resolve a content riddle against all objects currently in the database.
     pub fn find(content_riddle: &Riddle) -> Option<ObjectRef> {
         let iter = db().iterator_cf(Table::Objects.get(), IteratorMode::Start);
@@ -345,6 +518,7 @@ impl ObjectRef {
 
             let chunk_hash = Hash::hash(&buffer);
             db().put_cf(Table::ObjectChunks.get(), &chunk_hash, &buffer)?;
+            chunk_cache::insert(chunk_hash, buffer.clone());
             hashes.push(chunk_hash);
 
             // Buffer not fille to the brim: it's over!
@@ -356,6 +530,70 @@ impl ObjectRef {
             buffer.clear();
         }
 
+        ObjectRef::persist(header, bookmark, hashes, content_size, |_| {
+            ExpiryChange::Keep
+        })
+    }
+
+    /// Build a new object from data coming from a _trusted_ source, streamed in pieces of any
+    /// size. The object is the same as the one [`ObjectRef::build`] would make from the same
+    /// content, but the content is never held in memory as a whole. This is an upload: the
+    /// object expires as requested, if new (see [`upload_expiry`]).
+    pub async fn build_from_stream<S, B>(
+        header: ObjectHeader,
+        bookmark: bool,
+        expires_at: Option<DateTime<Utc>>,
+        mut source: S,
+    ) -> Result<ObjectRef, crate::Error>
+    where
+        S: Unpin + Stream<Item = Result<B, crate::Error>>,
+        B: AsRef<[u8]>,
+    {
+        let mut content_size = 0;
+        let mut buffer = header.buffer(); // start the first chunk with the serialized header
+        let mut hashes = Vec::new();
+
+        while let Some(piece) = source.next().await {
+            let piece = piece?;
+            let mut rest = piece.as_ref();
+
+            while !rest.is_empty() {
+                let taken = rest.len().min(CHUNK_SIZE - buffer.len());
+                buffer.extend_from_slice(&rest[..taken]);
+                rest = &rest[taken..];
+
+                if buffer.len() == CHUNK_SIZE {
+                    content_size += buffer.len();
+                    let chunk_hash = Hash::hash(&buffer);
+                    db().put_cf(Table::ObjectChunks.get(), chunk_hash, &buffer)?;
+                    chunk_cache::insert(chunk_hash, buffer.clone());
+                    hashes.push(chunk_hash);
+                    buffer.clear();
+                }
+            }
+        }
+
+        // The last chunk is never full (it may even be empty), just like in `build`:
+        content_size += buffer.len();
+        let chunk_hash = Hash::hash(&buffer);
+        db().put_cf(Table::ObjectChunks.get(), chunk_hash, &buffer)?;
+        chunk_cache::insert(chunk_hash, buffer);
+        hashes.push(chunk_hash);
+
+        ObjectRef::persist(header, bookmark, hashes, content_size, |existed| {
+            upload_expiry(existed, expires_at)
+        })
+    }
+
+    /// Writes down a new object, given the hashes of its chunks, which must already be in the
+    /// database. The expiry of the object changes as told, given whether the object existed.
+    fn persist(
+        header: ObjectHeader,
+        bookmark: bool,
+        hashes: Vec<Hash>,
+        content_size: usize,
+        expiry: impl FnOnce(bool) -> ExpiryChange,
+    ) -> Result<ObjectRef, crate::Error> {
         let merkle_tree = MerkleTree::from(hashes);
         let hash = merkle_tree.root();
         let metadata = ObjectMetadata {
@@ -385,25 +623,48 @@ impl ObjectRef {
             Bookmark::new(BookmarkType::User, ObjectRef { hash }).mark_with(&mut batch);
         }
 
+        let existed = db().get_cf(Table::ObjectMetadata.get(), hash)?.is_some();
+        match expiry(existed) {
+            ExpiryChange::Keep => {}
+            ExpiryChange::Set(expires_at) => batch.put_cf(
+                Table::ObjectExpiries.get(),
+                hash,
+                bincode::serialize(&expires_at).expect("can serialize"),
+            ),
+            ExpiryChange::Clear => batch.delete_cf(Table::ObjectExpiries.get(), hash),
+        }
+
         db().write(batch)?;
 
         Ok(ObjectRef { hash })
     }
 
-    /// Imports an existing object in the database from an external data.
+    /// Imports an existing object in the database from an external data. Chunks are hashed
+    /// and written in batches on the blocking thread pool, so that the import keeps up with
+    /// fast transfers. If the hash of the object is known beforehand, progress is reported
+    /// through [`Event::ImportProgress`] events and the vacuum leaves the object alone while
+    /// it is imported.
     pub async fn import(
         expected_content_size: usize,
         bookmark: bool,
+        expected_hash: Option<Hash>,
         source: impl Unpin + Stream<Item = Result<u8, crate::Error>>,
     ) -> Result<ObjectRef, crate::Error> {
         let mut content_size = 0;
-        let mut buffer = Vec::with_capacity(CHUNK_SIZE);
         let mut hashes = Vec::new();
         let mut maybe_header = None;
+        let mut chunk_count = 0;
+        let mut pending = Vec::with_capacity(IMPORT_BATCH_SIZE);
+        let mut in_flight = VecDeque::new();
+        let mut progress =
+            expected_hash.map(|hash| ImportProgress::new(hash, expected_content_size));
+        let _in_flight = expected_hash.map(InFlight::new);
 
         let mut limited_source = source.take(expected_content_size);
 
         loop {
+            let mut buffer = Vec::with_capacity(CHUNK_SIZE);
+
             // Extend buffer until (a) source stops (b) error (c) reaches limit.
             while let Some(byte) = limited_source.next().await {
                 buffer.push(byte?);
@@ -414,55 +675,43 @@ impl ObjectRef {
                 }
             }
 
-            let chunk_hash = Hash::hash(&buffer);
-            db().put_cf(Table::ObjectChunks.get(), &chunk_hash, &buffer)?;
-            hashes.push(chunk_hash);
-
             if maybe_header.is_none() {
                 let (_read, header) = ObjectHeader::read(buffer.iter().copied().map(Ok))?;
                 maybe_header = Some(header);
             }
 
             // Buffer not fille to the brim: it's over!
-            if buffer.len() < CHUNK_SIZE {
-                break;
-            }
+            let is_last = buffer.len() < CHUNK_SIZE;
+            pending.push(buffer);
+            chunk_count += 1;
 
-            buffer.clear();
-        }
-
-        let merkle_tree = MerkleTree::from(hashes);
-        let hash = merkle_tree.root();
-        let metadata = ObjectMetadata {
-            hashes: merkle_tree.hashes().to_vec(),
-            header: maybe_header.ok_or(crate::Error::NoHeaderRead)?,
-            content_size,
-            received_at: chrono::Utc::now(),
-        };
-        let statistics = ObjectStatistics::new(content_size);
+            if is_last || pending.len() == IMPORT_BATCH_SIZE {
+                let chunks = std::mem::take(&mut pending);
+                in_flight.push_back(tokio::task::spawn_blocking(move || write_chunks(chunks)));
+            }
 
-        log::info!("New object {} with metadata: {:#?}", hash, metadata);
+            // Wait for the oldest batches, keeping the order of the chunks:
+            while in_flight.len() >= MAX_IMPORT_BATCHES_IN_FLIGHT
+                || (is_last && !in_flight.is_empty())
+            {
+                let written = in_flight.pop_front().expect("not empty");
+                hashes.extend(written.await.expect("chunk writer panicked")?);
+            }
 
-        let mut batch = rocksdb::WriteBatch::default();
-        batch.put_cf(Table::Objects.get(), &hash, &[]);
-        batch.put_cf(
-            Table::ObjectMetadata.get(),
-            &hash,
-            bincode::serialize(&metadata).expect("can serialize"),
-        );
-        batch.put_cf(
-            Table::ObjectStatistics.get(),
-            &hash,
-            bincode::serialize(&statistics).expect("can serialize"),
-        );
+            if let Some(progress) = &mut progress {
+                progress.update(content_size, chunk_count);
+            }
 
-        if bookmark {
-            Bookmark::new(BookmarkType::User, ObjectRef { hash }).mark_with(&mut batch);
+            if is_last {
+                break;
+            }
         }
 
-        db().write(batch)?;
+        let header = maybe_header.ok_or(crate::Error::NoHeaderRead)?;
 
-        Ok(ObjectRef { hash })
+        ObjectRef::persist(header, bookmark, hashes, content_size, |_| {
+            ExpiryChange::Keep
+        })
     }
 
     /// Create a copy of this object, but with a different nonce header value. This new object
@@ -492,7 +741,7 @@ impl ObjectRef {
         };
 
         Ok(Some(ContentIter {
-            hashes: metadata.hashes.into_iter(),
+            chunks: ChunkReader::new(metadata.hashes),
             current_chunk: None,
             is_error: false,
         }))
@@ -525,7 +774,7 @@ impl ObjectRef {
         };
 
         Ok(Some(ChunkIter {
-            hashes: metadata.hashes.into_iter(),
+            chunks: ChunkReader::new(metadata.hashes),
             is_error: false,
         }))
     }
@@ -560,8 +809,9 @@ impl ObjectRef {
     pub fn is_bookmarked(&self) -> Result<bool, crate::Error> {
         let reference = Bookmark::new(BookmarkType::Reference, self.clone());
         let user = Bookmark::new(BookmarkType::User, self.clone());
+        let mirror = Bookmark::new(BookmarkType::Mirror, self.clone());
 
-        Ok(reference.is_marked()? || user.is_marked()?)
+        Ok(reference.is_marked()? || user.is_marked()? || mirror.is_marked()?)
     }
 
     /// Returns `Ok(true)` if this is a draft object. If the object does not exist in the
@@ -580,6 +830,103 @@ impl ObjectRef {
     }
 }
 
+/// What persisting an object does to its expiry (see [`ObjectRef::expires_at`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExpiryChange {
+    Keep,
+    Set(DateTime<Utc>),
+    Clear,
+}
+
+/// How an upload changes the expiry of an object. New objects expire as requested. Objects are
+/// addressed by their content, so an object that existed already may be in use by others: it
+/// never gets an expiry from an upload, but loses its own if uploaded again without one.
+fn upload_expiry(existed: bool, requested: Option<DateTime<Utc>>) -> ExpiryChange {
+    match (existed, requested) {
+        (false, Some(expires_at)) => ExpiryChange::Set(expires_at),
+        (true, Some(_)) => ExpiryChange::Keep,
+        (_, None) => ExpiryChange::Clear,
+    }
+}
+
+/// Hashes and writes a batch of chunks to the database at once, returning their hashes. The
+/// chunks are kept in the [`chunk_cache`], since imported content is usually about to be read.
+fn write_chunks(chunks: Vec<Vec<u8>>) -> Result<Vec<Hash>, crate::Error> {
+    let mut batch = WriteBatch::default();
+    let hashes = chunks
+        .iter()
+        .map(|chunk| {
+            let chunk_hash = Hash::hash(chunk);
+            batch.put_cf(Table::ObjectChunks.get(), chunk_hash, chunk);
+            chunk_hash
+        })
+        .collect::<Vec<_>>();
+
+    db().write(batch)?;
+
+    for (&chunk_hash, chunk) in hashes.iter().zip(chunks) {
+        chunk_cache::insert(chunk_hash, chunk);
+    }
+
+    Ok(hashes)
+}
+
+/// Keeps track of the progress of an import, reporting it to the query it is made for, if
+/// any, and emitting an [`Event::ImportProgress`] every [`IMPORT_PROGRESS_INTERVAL`].
+struct ImportProgress {
+    hash: Hash,
+    total: usize,
+    started_at: Instant,
+    last_emitted_at: Instant,
+}
+
+impl ImportProgress {
+    fn new(hash: Hash, total: usize) -> ImportProgress {
+        let now = Instant::now();
+
+        ImportProgress {
+            hash,
+            total,
+            started_at: now,
+            last_emitted_at: now,
+        }
+    }
+
+    fn update(&mut self, received: usize, chunks_received: usize) {
+        let now = Instant::now();
+        let elapsed = (now - self.started_at).as_secs_f64();
+        let bytes_per_sec = if elapsed > 0.0 {
+            received as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta_secs = (bytes_per_sec > 0.0)
+            .then(|| self.total.saturating_sub(received) as f64 / bytes_per_sec);
+
+        queries::report_progress(
+            &self.hash,
+            received,
+            chunks_received,
+            self.total,
+            bytes_per_sec,
+            eta_secs,
+        );
+
+        if now - self.last_emitted_at < IMPORT_PROGRESS_INTERVAL {
+            return;
+        }
+
+        self.last_emitted_at = now;
+        events::emit(Event::ImportProgress {
+            object: self.hash.to_string(),
+            received,
+            total: self.total,
+            bytes_per_sec,
+            eta_secs,
+        });
+    }
+}
+
 /// Statistics on object usage. This entity is used by the vacuum system to decide which objects
 /// are due for automatic deletion due to lack of usage.
 #[derive(Debug, Serialize, Deserialize)]
@@ -636,6 +983,16 @@ impl ObjectStatistics {
         self.size
     }
 
+    /// The time the related object was built or imported in this database.
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// The last time the related object was used.
+    pub fn last_touched_at(&self) -> DateTime<Utc> {
+        self.last_touched_at
+    }
+
     /// This is a bit approximate modeling of the following process:
     /// a. First, the access pattern is a Poisson process of unknown rate. The prior is a
     ///    Gamma Distribution.
@@ -666,3 +1023,100 @@ impl ObjectStatistics {
         prob_use * expected_access_freq / (self.size + 8_192) as f64
     }
 }
+
+#[test]
+fn uploads_never_set_expiry_of_existing_objects() {
+    let expires_at = Utc::now();
+
+    assert_eq!(
+        upload_expiry(false, Some(expires_at)),
+        ExpiryChange::Set(expires_at)
+    );
+    assert_eq!(upload_expiry(true, Some(expires_at)), ExpiryChange::Keep);
+    assert_eq!(upload_expiry(true, None), ExpiryChange::Clear);
+    assert_eq!(upload_expiry(false, None), ExpiryChange::Clear);
+}
+
+#[cfg(test)]
+fn numbered_chunks(count: i64) -> Vec<Hash> {
+    (0..count).map(Hash::from).collect()
+}
+
+/// Reads chunk `n` as [`CHUNK_SIZE`] bytes `n`, failing for the negative chunk numbers.
+#[cfg(test)]
+fn read_numbered_chunks(hashes: &[Hash]) -> Vec<Result<Vec<u8>, crate::Error>> {
+    hashes
+        .iter()
+        .map(|hash| {
+            let number = i64::from_be_bytes(hash.0[..8].try_into().unwrap());
+            if number < 0 {
+                Err(crate::Error::Storage(format!("chunk missing: {hash}")))
+            } else {
+                Ok(vec![number as u8; CHUNK_SIZE])
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn chunk_reader_skips_across_batches() {
+    let mut chunks = ChunkReader::with_read_batch(numbered_chunks(20), read_numbered_chunks);
+
+    assert_eq!(chunks.next_chunk().unwrap().unwrap()[0], 0);
+    // Within the batch read ahead, then beyond it without reading:
+    (0..10).for_each(|_| chunks.skip());
+    assert_eq!(chunks.len(), 9);
+    assert_eq!(chunks.next_chunk().unwrap().unwrap()[0], 11);
+    assert_eq!(chunks.len(), 8);
+
+    let rest = std::iter::from_fn(|| chunks.next_chunk())
+        .map(|chunk| chunk.unwrap()[0])
+        .collect::<Vec<_>>();
+    assert_eq!(rest, (12..20).collect::<Vec<u8>>());
+}
+
+#[test]
+fn content_iter_reads_ranges_across_batches() {
+    let mut content = ContentIter {
+        chunks: ChunkReader::with_read_batch(numbered_chunks(20), read_numbered_chunks),
+        current_chunk: None,
+        is_error: false,
+    };
+
+    // Starts in the middle of a chunk of the second batch:
+    content
+        .skip_bytes((READAHEAD_CHUNKS + 1) * CHUNK_SIZE + 10)
+        .unwrap();
+    let range = content
+        .by_ref()
+        .take(CHUNK_SIZE)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(range.len(), CHUNK_SIZE);
+    assert!(range[..CHUNK_SIZE - 10].iter().all(|&byte| byte == 9));
+    assert!(range[CHUNK_SIZE - 10..].iter().all(|&byte| byte == 10));
+
+    // Then skips from within a chunk into the third batch:
+    content.skip_bytes(6 * CHUNK_SIZE).unwrap();
+    assert_eq!(content.next().unwrap().unwrap(), 16);
+    assert_eq!(content.count(), 4 * CHUNK_SIZE - 11);
+}
+
+#[test]
+fn chunk_iter_yields_good_chunks_before_a_bad_one() {
+    let mut hashes = numbered_chunks(8);
+    hashes[5] = Hash::from(-1);
+    let chunks = ChunkIter {
+        chunks: ChunkReader::with_read_batch(hashes, read_numbered_chunks),
+        is_error: false,
+    }
+    .collect::<Vec<_>>();
+
+    assert_eq!(chunks.len(), 6);
+    assert!(chunks[..5]
+        .iter()
+        .enumerate()
+        .all(|(i, chunk)| chunk.as_ref().unwrap()[0] == i as u8));
+    assert!(chunks[5].is_err());
+}
@@ -0,0 +1,127 @@
+use rocksdb::{Direction, IteratorMode, WriteBatch};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+use samizdat_common::{Hash, Key, Riddle};
+
+use crate::db;
+use crate::db::Table;
+
+use super::{Droppable, SeriesRef};
+
+/// The topic under which the replies to an item are found. Only who knows the locator of the
+/// item can compute it, the same way only who knows a series can find its editions.
+pub fn reply_topic(locator: &Hash) -> Hash {
+    Hash::hash([locator.as_ref(), b"replies"].concat())
+}
+
+/// A series whose editions reply to an item of some collection, e.g., the comments on a page.
+/// Replies are either published by this node or were found in the network.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reply {
+    /// The locator hash of the item being replied to.
+    pub locator: Hash,
+    /// The public key of the reply series.
+    pub public_key: Key,
+    /// When this node first learned of this reply.
+    pub registered_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Display for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reply {} to {}", self.public_key, self.locator)
+    }
+}
+
+impl Droppable for Reply {
+    fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error> {
+        batch.delete_cf(Table::Replies.get(), self.key());
+        Ok(())
+    }
+}
+
+impl Reply {
+    pub fn new(locator: Hash, public_key: Key) -> Reply {
+        Reply {
+            locator,
+            public_key,
+            registered_at: chrono::Utc::now(),
+        }
+    }
+
+    /// Replies are indexed by topic first, so that they can be matched against riddles.
+    fn key(&self) -> Vec<u8> {
+        [
+            reply_topic(&self.locator).as_ref(),
+            self.public_key.as_bytes(),
+        ]
+        .concat()
+    }
+
+    pub fn series(&self) -> SeriesRef {
+        SeriesRef::new(self.public_key.clone())
+    }
+
+    pub fn topic(&self) -> Hash {
+        reply_topic(&self.locator)
+    }
+
+    /// Inserts this reply, unless it is already known.
+    pub fn insert(&self) -> Result<(), crate::Error> {
+        if db().get_cf(Table::Replies.get(), self.key())?.is_none() {
+            db().put_cf(
+                Table::Replies.get(),
+                self.key(),
+                bincode::serialize(self).expect("can serialize"),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// All the known replies to an item.
+    pub fn get_all(locator: &Hash) -> Result<Vec<Reply>, crate::Error> {
+        Reply::get_all_by_topic(&reply_topic(locator))
+    }
+
+    fn get_all_by_topic(topic: &Hash) -> Result<Vec<Reply>, crate::Error> {
+        db().prefix_iterator_cf(Table::Replies.get(), topic)
+            .take_while(|(key, _)| key.starts_with(topic))
+            .map(|(_, value)| Ok(bincode::deserialize(&value)?))
+            .collect()
+    }
+
+    /// All the known replies to the item whose topic resolves a riddle. Only one reply per
+    /// item is checked against the riddle: the others are skipped with a seek.
+    pub fn find(riddle: &Riddle) -> Result<Vec<Reply>, crate::Error> {
+        let mut from = vec![];
+
+        loop {
+            let mut iter = db().iterator_cf(
+                Table::Replies.get(),
+                IteratorMode::From(&from, Direction::Forward),
+            );
+            let key = match iter.next() {
+                Some((key, _)) => key,
+                None => return Ok(vec![]),
+            };
+
+            // Keys start with the 28-byte topic:
+            let topic = match key.get(..28).map(Hash::try_from) {
+                Some(Ok(topic)) => topic,
+                _ => {
+                    log::warn!("bad reply key in database: {key:?}");
+                    from = [&key[..], &[0]].concat();
+                    continue;
+                }
+            };
+
+            if riddle.resolves(&topic) {
+                return Reply::get_all_by_topic(&topic);
+            }
+
+            // Past all keys of the topic, which are followed by a public key:
+            from = [topic.as_ref(), &[u8::MAX; 33]].concat();
+        }
+    }
+}
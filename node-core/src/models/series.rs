@@ -2,15 +2,18 @@ use chrono::SubsecRound;
 use ed25519_dalek::Keypair;
 use rocksdb::{IteratorMode, WriteBatch};
 use serde_derive::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
 use std::fmt::{self, Display};
 use std::str::FromStr;
 use std::time::Duration;
 
+use samizdat_common::attestation::BuildAttestation;
 use samizdat_common::cipher::{OpaqueEncrypted, TransferCipher};
 use samizdat_common::{rpc::EditionAnnouncement, Hash, Key, PrivateKey, Riddle, Signed};
 
 use crate::db;
-use crate::db::Table;
+use crate::db::{Page, PageQuery, Table};
+use crate::system::routing;
 
 use super::{BookmarkType, CollectionRef, Droppable};
 
@@ -113,12 +116,94 @@ impl SeriesOwner {
             .collect::<Result<Vec<_>, crate::Error>>()
     }
 
+    /// Lists a page of the series owners, by name, optionally only the draft (or non-draft)
+    /// ones.
+    pub fn get_page(
+        query: &PageQuery,
+        is_draft: Option<bool>,
+    ) -> Result<Page<SeriesOwner>, crate::Error> {
+        Page::list(Table::SeriesOwners, query, |_, value| {
+            let owner: SeriesOwner = bincode::deserialize(value)?;
+            Ok(is_draft
+                .is_none_or(|is_draft| owner.is_draft == is_draft)
+                .then_some(owner))
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn series(&self) -> SeriesRef {
         SeriesRef {
             public_key: Key::new(self.keypair.public),
         }
     }
 
+    /// Announces an edition of this series to Nostr and to the hubs of the route of the
+    /// series, as soon as the node is not quiet.
+    pub fn announce(&self, edition: &Edition) {
+        let (name, edition) = (self.name.clone(), edition.clone());
+        let key = format!("announcing {}", edition.series());
+
+        crate::lifecycle::unless_quiet(key, move || {
+            // The keypair cannot be cloned, so the owner is read anew:
+            let owner = match SeriesOwner::get(&name) {
+                Ok(Some(owner)) => owner,
+                Ok(None) => {
+                    log::warn!("series owner {name} is gone; not announcing {edition:?}");
+                    return;
+                }
+                Err(err) => {
+                    log::error!("failed to announce {edition:?}: {err}");
+                    return;
+                }
+            };
+
+            crate::nostr::announce(&owner, &edition);
+
+            let announcement = edition.announcement();
+            crate::tasks::spawn(
+                format!("announce edition of {}", edition.series()),
+                routing::scope(&edition.series(), async move {
+                    log::info!("Announcing edition {edition:?}");
+                    crate::hubs().announce_edition(&announcement).await;
+                    Ok(())
+                }),
+            );
+        });
+    }
+
+    /// Signs an attestation of how an edition of this series was built.
+    pub fn attest(
+        &self,
+        command: String,
+        source: Hash,
+        toolchain: String,
+        outputs: Hash,
+    ) -> Signed<BuildAttestation> {
+        let attestation = BuildAttestation {
+            series: self.series().public_key,
+            command,
+            source,
+            toolchain,
+            outputs,
+        };
+
+        Signed::new(attestation, &self.keypair)
+    }
+
+    /// Derives a secret from the private key of this series, for use in other protocols. Each
+    /// `context` gives an unrelated secret, so a leak in one protocol does not compromise the
+    /// series.
+    pub fn derive_secret(&self, context: &str) -> [u8; 32] {
+        Sha3_256::new()
+            .chain_update(context.as_bytes())
+            .chain_update(self.keypair.secret.as_bytes())
+            .finalize()
+            .into()
+    }
+
     fn sign(&self, collection: CollectionRef, ttl: Option<Duration>) -> Edition {
         Edition {
             signed: Signed::new(
@@ -351,10 +436,11 @@ impl SeriesRef {
         Ok(())
     }
 
-    pub fn get_all() -> Result<Vec<SeriesRef>, crate::Error> {
-        db().iterator_cf(Table::Series.get(), IteratorMode::Start)
-            .map(|(_, value)| Ok(bincode::deserialize(&value)?))
-            .collect::<Result<Vec<_>, crate::Error>>()
+    /// Lists a page of the known series, by public key.
+    pub fn get_page(query: &PageQuery) -> Result<Page<SeriesRef>, crate::Error> {
+        Page::list(Table::Series, query, |_, value| {
+            Ok(Some(bincode::deserialize(value)?))
+        })
     }
 }
 
@@ -411,6 +497,11 @@ impl Edition {
         self.signed.timestamp
     }
 
+    /// Identifies this edition by its series and timestamp.
+    pub fn hash(&self) -> Hash {
+        Hash::hash(self.key())
+    }
+
     pub fn announcement(&self) -> EditionAnnouncement {
         let rand = Hash::rand();
         let content_hash = self.public_key.hash();
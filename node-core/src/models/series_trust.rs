@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+use rocksdb::{IteratorMode, WriteBatch};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+use samizdat_common::Key;
+
+use crate::db;
+use crate::db::Table;
+
+use super::{Droppable, SeriesRef, SubscriptionRef};
+
+/// How much the user trusts that a series really belongs to whom it claims to. Anybody can
+/// generate a key and publish under any name; this is what lets readers tell authenticated
+/// publications from random keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustLevel {
+    /// The key was obtained out-of-band, e.g., from the author in person.
+    Verified,
+    /// The key was trusted on first use, e.g., by subscribing to the series.
+    Tofu,
+    /// Nothing is known about the key.
+    Unverified,
+}
+
+impl Display for TrustLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrustLevel::Verified => write!(f, "verified"),
+            TrustLevel::Tofu => write!(f, "tofu"),
+            TrustLevel::Unverified => write!(f, "unverified"),
+        }
+    }
+}
+
+/// The trust level set by the user for a series.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeriesTrust {
+    /// The public key of the series.
+    pub public_key: Key,
+    pub level: TrustLevel,
+    /// How the key was obtained, for the user's own reference.
+    pub note: Option<String>,
+    pub set_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeriesTrustRef {
+    pub public_key: Key,
+}
+
+impl Display for SeriesTrustRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "trust in {}", self.public_key)
+    }
+}
+
+impl Droppable for SeriesTrustRef {
+    fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error> {
+        batch.delete_cf(Table::SeriesTrust.get(), self.public_key.as_bytes());
+        Ok(())
+    }
+}
+
+impl SeriesTrustRef {
+    pub fn new(public_key: Key) -> SeriesTrustRef {
+        SeriesTrustRef { public_key }
+    }
+
+    /// Inserts or replaces the trust level of a series.
+    pub fn build(trust: SeriesTrust) -> Result<SeriesTrustRef, crate::Error> {
+        let trust_ref = SeriesTrustRef {
+            public_key: trust.public_key.clone(),
+        };
+
+        db().put_cf(
+            Table::SeriesTrust.get(),
+            trust_ref.public_key.as_bytes(),
+            bincode::serialize(&trust).expect("can serialize"),
+        )?;
+
+        Ok(trust_ref)
+    }
+
+    pub fn get(&self) -> Result<Option<SeriesTrust>, crate::Error> {
+        let maybe_value = db().get_cf(Table::SeriesTrust.get(), self.public_key.as_bytes())?;
+        Ok(maybe_value
+            .map(|value| bincode::deserialize(&value))
+            .transpose()?)
+    }
+
+    pub fn get_all() -> Result<Vec<SeriesTrust>, crate::Error> {
+        db().iterator_cf(Table::SeriesTrust.get(), IteratorMode::Start)
+            .map(|(_, value)| Ok(bincode::deserialize(&value)?))
+            .collect::<Result<Vec<_>, crate::Error>>()
+    }
+
+    /// The trust level of the series. Unless the user set one, series owned by this node are
+    /// verified and series the user subscribed to are trusted on first use.
+    pub fn level(&self) -> Result<TrustLevel, crate::Error> {
+        if let Some(trust) = self.get()? {
+            return Ok(trust.level);
+        }
+
+        if SeriesRef::new(self.public_key.clone()).is_locally_owned()? {
+            Ok(TrustLevel::Verified)
+        } else if SubscriptionRef::new(self.public_key.clone())
+            .get()?
+            .is_some()
+        {
+            Ok(TrustLevel::Tofu)
+        } else {
+            Ok(TrustLevel::Unverified)
+        }
+    }
+
+    /// The trust level of the series as shown to the content served by this node, which may be
+    /// read by any page: only the level set by the user, never inferred, since that would tell
+    /// whether the user owns or follows the series.
+    pub fn public_level(&self) -> Result<TrustLevel, crate::Error> {
+        Ok(self
+            .get()?
+            .map(|trust| trust.level)
+            .unwrap_or(TrustLevel::Unverified))
+    }
+}
@@ -0,0 +1,411 @@
+use rocksdb::{IteratorMode, WriteBatch};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::sync::Mutex;
+
+use samizdat_common::rpc::QueryKind;
+use samizdat_common::{Hash, Key, Riddle};
+
+use crate::db;
+use crate::db::{Page, PageQuery, Table};
+use crate::hubs;
+use crate::system::routing;
+
+use super::{
+    BookmarkType, CollectionRef, Droppable, Edition, Inventory, ItemPathBuf, Locator, ObjectRef,
+    SeriesRef,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubscriptionKind {
+    /// Downloads all items of every new edition.
+    FullInventory,
+    /// Downloads all items of every current and future edition and bookmarks them, so that
+    /// they keep being served to the network. The vacuum never deletes them.
+    Mirror,
+    /// Keeps track of every new edition, but only downloads items when they are first
+    /// requested, keeping them as any other cached content. Good for devices short on disk.
+    Lazy,
+}
+
+impl Default for SubscriptionKind {
+    fn default() -> SubscriptionKind {
+        SubscriptionKind::FullInventory
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Subscription {
+    public_key: Key,
+    kind: SubscriptionKind,
+}
+
+impl Subscription {
+    pub fn new(public_key: Key, kind: SubscriptionKind) -> Subscription {
+        Subscription { public_key, kind }
+    }
+
+    pub fn public_key(&self) -> &Key {
+        &self.public_key
+    }
+
+    pub fn kind(&self) -> SubscriptionKind {
+        self.kind
+    }
+}
+
+/// How much of an edition is present in the local database.
+#[derive(Debug, Serialize)]
+pub struct EditionCompleteness {
+    /// The collection of the edition.
+    pub collection: String,
+    /// The timestamp of the edition.
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// The number of items whose objects are present locally.
+    pub present: usize,
+    /// The total number of items in the edition, if the inventory is known.
+    pub total: Option<usize>,
+}
+
+lazy_static::lazy_static! {
+    /// Serializes pinning and unpinning, so that each edition pins an object only once.
+    static ref PIN_LOCK: Mutex<()> = Mutex::default();
+}
+
+/// Pins an object for a mirrored edition, unless the edition pinned it already. The object is
+/// unpinned when the series is unmirrored (see [`SubscriptionRef::unmirror`]).
+fn pin_for(collection: &CollectionRef, object: &ObjectRef) -> Result<(), crate::Error> {
+    let key = [collection.hash().as_ref(), object.hash().as_ref()].concat();
+    let _guard = PIN_LOCK.lock().expect("poisoned");
+
+    if db().get_cf(Table::MirrorPins.get(), &key)?.is_some() {
+        return Ok(());
+    }
+
+    let mut batch = WriteBatch::default();
+    batch.put_cf(Table::MirrorPins.get(), key, []);
+    object.bookmark(BookmarkType::Mirror).mark_with(&mut batch);
+    db().write(batch)?;
+
+    Ok(())
+}
+
+/// Removes all the pins of a mirrored edition.
+fn unpin_all_with(collection: &CollectionRef, batch: &mut WriteBatch) -> Result<(), crate::Error> {
+    let prefix = collection.hash();
+
+    for (key, _) in db()
+        .prefix_iterator_cf(Table::MirrorPins.get(), prefix)
+        .take_while(|(key, _)| key.starts_with(prefix.as_ref()))
+    {
+        let object = ObjectRef::new(Hash::try_from(&key[prefix.len()..])?);
+        object.bookmark(BookmarkType::Mirror).unmark_with(batch);
+        batch.delete_cf(Table::MirrorPins.get(), key);
+    }
+
+    Ok(())
+}
+
+/// Pins the object of a collection item, if it exists locally.
+fn pin(locator: &Locator) -> Result<(), crate::Error> {
+    if let Some(object) = locator.get_object()? {
+        pin_for(&locator.collection(), &object)?;
+    }
+
+    Ok(())
+}
+
+/// Downloads items of a collection, pinning them if mirroring.
+async fn fetch_items<'a>(
+    collection: &CollectionRef,
+    item_paths: impl IntoIterator<Item = &'a ItemPathBuf>,
+    is_mirror: bool,
+) {
+    let locators = item_paths
+        .into_iter()
+        .map(|item_path| collection.locator_for(item_path.as_path()))
+        .collect::<Vec<_>>();
+    let queries = locators
+        .iter()
+        .map(|locator| (locator.hash(), QueryKind::Item))
+        .collect::<Vec<_>>();
+
+    hubs().query_many(&queries).await;
+
+    if is_mirror {
+        for locator in &locators {
+            if let Err(err) = pin(locator) {
+                log::warn!("failed to pin {locator}: {err}");
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscriptionRef {
+    pub public_key: Key,
+}
+
+impl Display for SubscriptionRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "subscription to {}", base64_url::encode(self.key()),)
+    }
+}
+
+impl Droppable for SubscriptionRef {
+    fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error> {
+        batch.delete_cf(Table::Subscriptions.get(), self.key());
+        Ok(())
+    }
+}
+
+impl SubscriptionRef {
+    pub fn new(public_key: Key) -> SubscriptionRef {
+        SubscriptionRef { public_key }
+    }
+
+    // pub fn public_key(&self) -> Key {
+    //     self.public_key.clone()
+    // }
+
+    pub fn key(&self) -> &[u8] {
+        self.public_key.as_bytes()
+    }
+
+    pub fn build(subscription: Subscription) -> Result<SubscriptionRef, crate::Error> {
+        let mut batch = rocksdb::WriteBatch::default();
+
+        batch.put_cf(
+            Table::Subscriptions.get(),
+            &subscription.public_key.as_bytes(),
+            bincode::serialize(&subscription).expect("can serialize"),
+        );
+
+        db().write(batch)?;
+
+        Ok(SubscriptionRef {
+            public_key: subscription.public_key,
+        })
+    }
+
+    pub fn get(&self) -> Result<Option<Subscription>, crate::Error> {
+        let maybe_value = db().get_cf(Table::Subscriptions.get(), &self.key())?;
+        Ok(maybe_value
+            .map(|value| bincode::deserialize(&value))
+            .transpose()?)
+    }
+
+    pub fn get_all() -> Result<Vec<Subscription>, crate::Error> {
+        db().iterator_cf(Table::Subscriptions.get(), IteratorMode::Start)
+            .map(|(_, value)| Ok(bincode::deserialize(&value)?))
+            .collect::<Result<Vec<_>, crate::Error>>()
+    }
+
+    /// Lists a page of the subscriptions, by public key, optionally only of a given kind.
+    pub fn get_page(
+        query: &PageQuery,
+        kind: Option<SubscriptionKind>,
+    ) -> Result<Page<Subscription>, crate::Error> {
+        Page::list(Table::Subscriptions, query, |_, value| {
+            let subscription: Subscription = bincode::deserialize(value)?;
+            Ok(kind
+                .is_none_or(|kind| subscription.kind == kind)
+                .then_some(subscription))
+        })
+    }
+
+    pub fn find(riddle: &Riddle) -> Option<SubscriptionRef> {
+        let it = db().iterator_cf(Table::Subscriptions.get(), IteratorMode::Start);
+
+        for (key, value) in it {
+            match Key::from_bytes(&key) {
+                Ok(key) => {
+                    if riddle.resolves(&key.hash()) {
+                        match bincode::deserialize(&value) {
+                            Ok(subscription) => return Some(subscription),
+                            Err(err) => {
+                                log::warn!("{}", err);
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(err) => {
+                    log::warn!("{}", err);
+                    continue;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Reserved for future use.
+    pub fn must_refresh(&self) -> Result<bool, crate::Error> {
+        Ok(true)
+    }
+
+    /// Refresh the underlying series using and *already validated* edition.
+    pub async fn refresh(&self, edition: Edition) -> Result<(), crate::Error> {
+        routing::scope(&edition.series(), self.refresh_routed(edition)).await
+    }
+
+    async fn refresh_routed(&self, edition: Edition) -> Result<(), crate::Error> {
+        let collection = edition.collection();
+        let inventory_content_hash = collection.locator_for("_inventory".into()).hash();
+        let kind = self.get()?.map(|subscription| subscription.kind);
+        let is_mirror = kind == Some(SubscriptionKind::Mirror);
+
+        let series = edition.series();
+        series.advance(&edition)?;
+        series.refresh()?;
+
+        if kind == Some(SubscriptionKind::Lazy) {
+            log::info!("Subscription to {series} is lazy. Not downloading edition {edition:?}");
+            return Ok(());
+        }
+
+        if let Some(item) = hubs().query(inventory_content_hash, QueryKind::Item).await {
+            if let Some(content) = item.content()? {
+                let inventory: Inventory = serde_json::from_slice(&content).map_err(|err| {
+                    crate::Error::from(format!(
+                        "failed to deserialize inventory for edition {:?}: {}",
+                        edition, err
+                    ))
+                })?;
+
+                crate::content_filter::check_inventory(&collection, &inventory)?;
+
+                if is_mirror {
+                    pin_for(&collection, &item)?;
+                }
+
+                // Let subscribers see a usable site before the bulk of the edition arrives:
+                let prefetch = inventory.prefetch().iter().collect::<BTreeSet<_>>();
+                if !prefetch.is_empty() {
+                    log::info!(
+                        "Prefetching {} items of edition {edition:?}",
+                        prefetch.len()
+                    );
+                    fetch_items(&collection, inventory.prefetch(), is_mirror).await;
+                }
+                let is_pending = |item_path: &&ItemPathBuf| !prefetch.contains(item_path);
+
+                // Sharded inventories are gone through one shard at a time:
+                for (index, shard) in inventory.shards().iter().enumerate() {
+                    let shard_locator = collection.inventory_shard_locator(index);
+                    let shard_content =
+                        match hubs().query(shard_locator.hash(), QueryKind::Item).await {
+                            Some(shard_item) => {
+                                if is_mirror {
+                                    pin_for(&collection, &shard_item)?;
+                                }
+                                shard_item.content()?
+                            }
+                            None => None,
+                        };
+                    let shard_content = shard_content.ok_or_else(|| {
+                        crate::Error::from(format!(
+                            "Inventory shard {index} (from {}) not found for edition {:?}",
+                            shard.first, edition
+                        ))
+                    })?;
+                    let shard_inventory: Inventory = serde_json::from_slice(&shard_content)
+                        .map_err(|err| {
+                            crate::Error::from(format!(
+                                "failed to deserialize inventory shard {index} for edition \
+                                {:?}: {}",
+                                edition, err
+                            ))
+                        })?;
+
+                    log::info!("Refreshing inventory shard {index} of edition {edition:?}");
+                    let item_paths = shard_inventory.iter().map(|(path, _)| path);
+                    fetch_items(&collection, item_paths.filter(is_pending), is_mirror).await;
+                }
+
+                let item_paths = inventory.iter().map(|(path, _)| path);
+                fetch_items(&collection, item_paths.filter(is_pending), is_mirror).await;
+
+                // Surface time-locked content as soon as its key arrives:
+                if inventory.contains(&crate::time_lock::UNSEAL_ITEM.into()) {
+                    if let Err(err) = crate::time_lock::unseal(&collection).await {
+                        log::warn!("failed to unseal edition {edition:?}: {err}");
+                    }
+                }
+
+                return Ok(());
+            }
+        }
+
+        Err(crate::Error::from(format!(
+            "Inventory not found for edition {:?}. Could not refresh",
+            edition
+        )))
+    }
+
+    /// Downloads and bookmarks all the editions of the series that are known locally, besides
+    /// the latest edition in the network.
+    pub async fn mirror(&self) -> Result<(), crate::Error> {
+        let series = SeriesRef::new(self.public_key.clone());
+
+        if let Some(latest) = hubs().get_latest(&series).await {
+            series.advance(&latest)?;
+        }
+
+        for edition in series.get_editions()? {
+            if let Err(err) = self.refresh(edition).await {
+                log::warn!("failed to mirror edition of {self}: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the pins of the editions of the series known locally by a mirror, leaving
+    /// them as any other cached content. Content bookmarked otherwise, or pinned by editions
+    /// of other mirrored series, is kept.
+    pub fn unmirror(&self) -> Result<(), crate::Error> {
+        let series = SeriesRef::new(self.public_key.clone());
+        let mut batch = WriteBatch::default();
+        let _guard = PIN_LOCK.lock().expect("poisoned");
+
+        for edition in series.get_editions()? {
+            unpin_all_with(&edition.collection(), &mut batch)?;
+        }
+
+        db().write(batch)?;
+
+        Ok(())
+    }
+
+    /// Calculates how much of each known edition of the series is present locally.
+    pub fn completeness(&self) -> Result<Vec<EditionCompleteness>, crate::Error> {
+        let series = SeriesRef::new(self.public_key.clone());
+
+        series
+            .get_editions()?
+            .into_iter()
+            .map(|edition| {
+                let collection = edition.collection();
+                let inventory = collection.inventory()?;
+                let mut present = 0;
+
+                for (_, hash) in inventory.iter().flat_map(Inventory::iter) {
+                    if ObjectRef::new(*hash).metadata()?.is_some() {
+                        present += 1;
+                    }
+                }
+
+                Ok(EditionCompleteness {
+                    collection: collection.hash().to_string(),
+                    timestamp: edition.timestamp(),
+                    present,
+                    total: inventory.map(|inventory| inventory.iter().count()),
+                })
+            })
+            .collect()
+    }
+}
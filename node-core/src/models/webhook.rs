@@ -0,0 +1,151 @@
+use hmac::{Hmac, Mac};
+use rocksdb::{IteratorMode, WriteBatch};
+use serde_derive::{Deserialize, Serialize};
+use sha3::Sha3_256;
+use std::convert::TryInto;
+use std::fmt::{self, Display};
+use std::time::Duration;
+use tokio::time::sleep;
+
+use samizdat_common::Hash;
+
+use crate::db;
+use crate::db::Table;
+use crate::events::Event;
+use crate::system::exponential_backoff;
+
+use super::Droppable;
+
+/// The maximum number of times the delivery of an event to a webhook is attempted.
+const MAX_DELIVERY_ATTEMPTS: usize = 6;
+
+lazy_static::lazy_static! {
+    static ref CLIENT: reqwest::Client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("can build HTTP client");
+}
+
+/// A URL to which events happening in this node are `POST`ed as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    /// The URL to be called.
+    pub url: String,
+    /// The secret used to sign the payloads sent to the URL, using HMAC-SHA3-256. The
+    /// signature is sent in the `X-Samizdat-Signature` header, encoded in base64-url.
+    pub secret: String,
+    /// The kinds of events (in kebab-case) this webhook is interested in. If empty, all events
+    /// but `import-progress` are delivered.
+    pub events: Vec<String>,
+}
+
+impl Webhook {
+    /// Whether this webhook is interested in a given event.
+    pub fn accepts(&self, event: &Event) -> bool {
+        // Progress events are too many to go anywhere they were not asked for:
+        let is_progress = matches!(event, Event::ImportProgress { .. });
+        (self.events.is_empty() && !is_progress)
+            || self.events.iter().any(|kind| kind == event.kind())
+    }
+
+    /// Signs a payload using the secret of this webhook.
+    fn sign(&self, payload: &[u8]) -> String {
+        let mut mac = Hmac::<Sha3_256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(payload);
+        base64_url::encode(&mac.finalize().into_bytes())
+    }
+
+    /// Delivers an event to the webhook, retrying with exponential backoff on failure.
+    pub async fn deliver(&self, event: &Event) -> Result<(), crate::Error> {
+        let payload = serde_json::to_vec(event).expect("can serialize");
+        let signature = self.sign(&payload);
+        let mut backoff = exponential_backoff(Duration::from_secs(1), Duration::from_secs(300));
+
+        for attempt in 1..=MAX_DELIVERY_ATTEMPTS {
+            let outcome = CLIENT
+                .post(&self.url)
+                .header("Content-Type", "application/json")
+                .header("X-Samizdat-Event", event.kind())
+                .header("X-Samizdat-Signature", &signature)
+                .body(payload.clone())
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status);
+
+            match outcome {
+                Ok(_) => return Ok(()),
+                Err(err) => {
+                    log::warn!(
+                        "attempt {attempt} to deliver {} to {} failed: {err}",
+                        event.kind(),
+                        self.url
+                    );
+                }
+            }
+
+            if attempt < MAX_DELIVERY_ATTEMPTS {
+                sleep(backoff()).await;
+            }
+        }
+
+        Err(format!(
+            "gave up delivering {} to {} after {MAX_DELIVERY_ATTEMPTS} attempts",
+            event.kind(),
+            self.url
+        )
+        .into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WebhookRef {
+    pub id: Hash,
+}
+
+impl Display for WebhookRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "webhook {}", self.id)
+    }
+}
+
+impl Droppable for WebhookRef {
+    fn drop_if_exists_with(&self, batch: &mut WriteBatch) -> Result<(), crate::Error> {
+        batch.delete_cf(Table::Webhooks.get(), self.id);
+        Ok(())
+    }
+}
+
+impl WebhookRef {
+    pub fn new(id: Hash) -> WebhookRef {
+        WebhookRef { id }
+    }
+
+    pub fn build(webhook: Webhook) -> Result<WebhookRef, crate::Error> {
+        let webhook_ref = WebhookRef { id: Hash::rand() };
+
+        db().put_cf(
+            Table::Webhooks.get(),
+            webhook_ref.id,
+            bincode::serialize(&webhook).expect("can serialize"),
+        )?;
+
+        Ok(webhook_ref)
+    }
+
+    pub fn get(&self) -> Result<Option<Webhook>, crate::Error> {
+        let maybe_value = db().get_cf(Table::Webhooks.get(), self.id)?;
+        Ok(maybe_value
+            .map(|value| bincode::deserialize(&value))
+            .transpose()?)
+    }
+
+    pub fn get_all() -> Result<Vec<(WebhookRef, Webhook)>, crate::Error> {
+        db().iterator_cf(Table::Webhooks.get(), IteratorMode::Start)
+            .map(|(key, value)| {
+                let id: Hash = (&*key).try_into()?;
+                Ok((WebhookRef { id }, bincode::deserialize(&value)?))
+            })
+            .collect::<Result<Vec<_>, crate::Error>>()
+    }
+}
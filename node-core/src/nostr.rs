@@ -0,0 +1,440 @@
+//! A bridge to Nostr: edition announcements can also be published as Nostr events, and relays
+//! can be watched for announcements of the series this node is subscribed to. This is an
+//! alternative rendezvous path for when the hubs are blocked or down.
+//!
+//! Announcements are posted to relays exactly as they are sent to the hubs, i.e., encrypted
+//! with the key of the series. Each series signs its events with a Nostr key derived from its
+//! private key, so that the series cannot be told from its Nostr identity. Events are tagged
+//! with a hash of the key of the series, which only who knows the series can compute.
+//!
+//! Relays also carry the _inbox_ of each series, where mirrors send their readership reports to
+//! the publisher (see [`crate::readership`]). Reports are encrypted the same way.
+//!
+//! Series with a hub route (see [`crate::system::routing`]) never go to relays, neither in
+//! events nor in subscriptions, since relays are not in their route.
+
+use futures::prelude::*;
+use secp256k1::{schnorr, KeyPair, Message, Secp256k1, XOnlyPublicKey};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+use samizdat_common::cipher::{OpaqueEncrypted, TransferCipher};
+use samizdat_common::rpc::EditionAnnouncement;
+use samizdat_common::{Hash, Key};
+
+use crate::cli;
+use crate::db::{db, Table};
+use crate::models::{Edition, SeriesOwner, SeriesRef, SubscriptionRef};
+use crate::readership::ReadershipReport;
+use crate::system::{exponential_backoff, receive_announcement, routing};
+
+/// The kind of the announcement events. This is a parameterized replaceable event, so relays
+/// only need to keep the latest announcement of each series.
+const KIND: u64 = 30_078;
+/// The kind of the readership report events. These are also replaceable, so that relays only
+/// need to keep the latest report of each mirror.
+const REPORT_KIND: u64 = 30_079;
+/// The context used to derive the Nostr key of a series from its private key.
+const KEY_CONTEXT: &str = "samizdat-nostr-announcement";
+/// The key, in the global table, of the secret from which the Nostr keys of reports are derived.
+const REPORTER_SECRET_KEY: &[u8] = b"nostr-reporter-secret";
+/// The maximum time to wait for a relay to connect or to acknowledge an event.
+const RELAY_TIMEOUT: Duration = Duration::from_secs(10);
+/// The time after which the subscription to a relay is renewed, so that it picks up any
+/// changes in the series this node is subscribed to.
+const SESSION_LENGTH: Duration = Duration::from_secs(600);
+
+lazy_static::lazy_static! {
+    /// The latest announcement seen for each topic. Relays send stored events again each time
+    /// a subscription is renewed, and these must not trigger a refresh again.
+    static ref LATEST_SEEN: Mutex<BTreeMap<String, u64>> = Mutex::default();
+}
+
+/// A Nostr event, as defined in NIP-01.
+#[derive(Debug, Serialize, Deserialize)]
+struct Event {
+    id: String,
+    pubkey: String,
+    created_at: u64,
+    kind: u64,
+    tags: Vec<Vec<String>>,
+    content: String,
+    sig: String,
+}
+
+impl Event {
+    /// The hash of the serialized event, which is both its id and what gets signed.
+    fn hash(&self) -> [u8; 32] {
+        let serialized = json!([
+            0,
+            self.pubkey,
+            self.created_at,
+            self.kind,
+            self.tags,
+            self.content
+        ]);
+
+        Sha256::digest(serialized.to_string().as_bytes()).into()
+    }
+
+    fn new(
+        keypair: &KeyPair,
+        created_at: u64,
+        kind: u64,
+        tags: Vec<Vec<String>>,
+        content: String,
+    ) -> Event {
+        let mut event = Event {
+            id: String::new(),
+            pubkey: XOnlyPublicKey::from_keypair(keypair).to_string(),
+            created_at,
+            kind,
+            tags,
+            content,
+            sig: String::new(),
+        };
+
+        let hash = event.hash();
+        let message = Message::from_slice(&hash).expect("hash has 32 bytes");
+        event.id = hex::encode(hash);
+        event.sig = Secp256k1::signing_only()
+            .sign_schnorr_no_aux_rand(&message, keypair)
+            .to_string();
+
+        event
+    }
+
+    /// Checks the id and the signature of this event.
+    fn is_valid(&self) -> bool {
+        let hash = self.hash();
+
+        let check = || -> Result<bool, secp256k1::Error> {
+            let signature = self.sig.parse::<schnorr::Signature>()?;
+            let pubkey = self.pubkey.parse::<XOnlyPublicKey>()?;
+            let message = Message::from_slice(&hash)?;
+            Ok(Secp256k1::verification_only()
+                .verify_schnorr(&signature, &message, &pubkey)
+                .is_ok())
+        };
+
+        self.id == hex::encode(hash) && check().unwrap_or(false)
+    }
+
+    /// The topic of this event, if it is an announcement.
+    fn topic(&self) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.first().map(String::as_str) == Some("d"))
+            .and_then(|tag| tag.get(1))
+            .and_then(|value| value.strip_prefix("samizdat:"))
+    }
+}
+
+/// The topic under which the announcements of a series are published.
+fn topic(public_key: &Key) -> String {
+    Hash::hash(public_key.hash()).to_string()
+}
+
+/// The topic under which readership reports are sent to the publisher of a series.
+fn inbox(public_key: &Key) -> String {
+    Hash::hash([public_key.hash().as_ref(), b"inbox"].concat()).to_string()
+}
+
+/// Publishes an event to all configured relays, in the background.
+fn publish_to_all(event: Arc<Event>, what: &'static str) {
+    for relay in &cli().nostr_relays {
+        let relay = relay.clone();
+        let event = event.clone();
+        crate::tasks::spawn(format!("send {what} to {relay}"), async move {
+            publish(&relay, &event).await?;
+            log::info!("Sent {what} {} to {relay}", event.id);
+            Ok(())
+        });
+    }
+}
+
+/// Publishes the announcement of a new edition to all configured relays, in the background.
+/// This does nothing if no relays were configured.
+pub fn announce(owner: &SeriesOwner, edition: &Edition) {
+    if cli().nostr_relays.is_empty() || routing::is_routed(&edition.series()) {
+        return;
+    }
+
+    let keypair = match KeyPair::from_seckey_slice(
+        &Secp256k1::signing_only(),
+        &owner.derive_secret(KEY_CONTEXT),
+    ) {
+        Ok(keypair) => keypair,
+        Err(err) => {
+            log::warn!("could not derive Nostr key for {}: {err}", edition.series());
+            return;
+        }
+    };
+
+    let announcement = bincode::serialize(&edition.announcement()).expect("can serialize");
+    let event = Arc::new(Event::new(
+        &keypair,
+        edition.timestamp().timestamp() as u64,
+        KIND,
+        vec![vec![
+            "d".to_owned(),
+            format!("samizdat:{}", topic(edition.public_key())),
+        ]],
+        base64_url::encode(&announcement),
+    ));
+
+    publish_to_all(event, "edition announcement");
+}
+
+/// The Nostr key with which this node signs its reports to a series. It is stable, so that
+/// only the latest report of each mirror is kept, but differs from series to series, so that
+/// the series mirrored by the same node cannot be linked together.
+fn reporter_keypair(public_key: &Key) -> Result<KeyPair, crate::Error> {
+    let secret = match db().get_cf(Table::Global.get(), REPORTER_SECRET_KEY)? {
+        Some(secret) => secret,
+        None => {
+            let secret = Hash::rand().to_vec();
+            db().put_cf(Table::Global.get(), REPORTER_SECRET_KEY, &secret)?;
+            secret
+        }
+    };
+    let derived = Sha256::digest([&secret, public_key.as_bytes()].concat());
+
+    Ok(
+        KeyPair::from_seckey_slice(&Secp256k1::signing_only(), &derived)
+            .map_err(|err| format!("could not derive Nostr key: {err}"))?,
+    )
+}
+
+/// Sends a readership report to the inbox of a series, in the background. This does nothing if
+/// no relays were configured.
+pub fn report(public_key: &Key, report: &ReadershipReport) {
+    if cli().nostr_relays.is_empty() || routing::is_routed(&SeriesRef::new(public_key.clone())) {
+        return;
+    }
+
+    let keypair = match reporter_keypair(public_key) {
+        Ok(keypair) => keypair,
+        Err(err) => {
+            log::warn!("could not report readership of {public_key}: {err}");
+            return;
+        }
+    };
+
+    let rand = Hash::rand();
+    let cipher = TransferCipher::new(&public_key.hash(), &rand);
+    let content =
+        bincode::serialize(&(rand, OpaqueEncrypted::new(report, &cipher))).expect("can serialize");
+    let event = Arc::new(Event::new(
+        &keypair,
+        report.reported_at.timestamp() as u64,
+        REPORT_KIND,
+        vec![vec![
+            "d".to_owned(),
+            format!("samizdat:{}", inbox(public_key)),
+        ]],
+        base64_url::encode(&content),
+    ));
+
+    publish_to_all(event, "readership report");
+}
+
+/// Sends an event to a relay and waits for it to be accepted.
+async fn publish(relay: &str, event: &Event) -> Result<(), crate::Error> {
+    let (mut socket, _) = timeout(RELAY_TIMEOUT, tokio_tungstenite::connect_async(relay))
+        .await
+        .map_err(|_| format!("timed out connecting to {relay}"))?
+        .map_err(|err| format!("failed to connect to {relay}: {err}"))?;
+
+    socket
+        .send(WsMessage::Text(json!(["EVENT", event]).to_string()))
+        .await
+        .map_err(|err| format!("failed to send event: {err}"))?;
+
+    let acknowledgement = async {
+        while let Some(message) = socket.next().await {
+            let text = match message.map_err(|err| format!("relay connection failed: {err}"))? {
+                WsMessage::Text(text) => text,
+                _ => continue,
+            };
+
+            if let Ok((_, id, accepted, reason)) =
+                serde_json::from_str::<(String, String, bool, String)>(&text)
+            {
+                if id == event.id {
+                    return if accepted {
+                        Ok(())
+                    } else {
+                        Err(format!("relay rejected event: {reason}"))
+                    };
+                }
+            }
+        }
+
+        Err("relay closed the connection".to_owned())
+    };
+
+    let outcome = timeout(RELAY_TIMEOUT, acknowledgement)
+        .await
+        .map_err(|_| "timed out waiting for relay".to_owned())?;
+    socket.close(None).await.ok();
+
+    Ok(outcome?)
+}
+
+/// Watches all configured relays for announcements of the series this node is subscribed to.
+/// This runs forever and does nothing if no relays were configured.
+pub async fn run_nostr_daemon() {
+    future::join_all(cli().nostr_relays.iter().map(|relay| async move {
+        let new_backoff = || exponential_backoff(Duration::from_secs(1), SESSION_LENGTH);
+        let mut backoff = new_backoff();
+
+        loop {
+            match timeout(SESSION_LENGTH, watch(relay)).await {
+                Err(_) => backoff = new_backoff(),
+                Ok(outcome) => {
+                    if let Err(err) = outcome {
+                        log::warn!("failed to watch Nostr relay {relay}: {err}");
+                    }
+
+                    sleep(backoff()).await;
+                }
+            }
+        }
+    }))
+    .await;
+}
+
+/// Subscribes to the announcements of the current subscriptions and to the inboxes of the
+/// series owned by this node in a relay and feeds them to the node, until the relay closes the
+/// connection.
+async fn watch(relay: &str) -> Result<(), crate::Error> {
+    let announcements = SubscriptionRef::get_all()?
+        .iter()
+        .filter(|subscription| {
+            !routing::is_routed(&SeriesRef::new(subscription.public_key().clone()))
+        })
+        .map(|subscription| format!("samizdat:{}", topic(subscription.public_key())))
+        .collect::<Vec<_>>();
+    let inboxes = SeriesOwner::get_all()?
+        .iter()
+        .filter(|owner| !routing::is_routed(&owner.series()))
+        .map(|owner| format!("samizdat:{}", inbox(&owner.series().public_key())))
+        .collect::<Vec<_>>();
+    let topics = [announcements, inboxes].concat();
+
+    if topics.is_empty() {
+        // Wait for the session to end, to check for subscriptions again.
+        future::pending::<()>().await;
+    }
+
+    let (mut socket, _) = timeout(RELAY_TIMEOUT, tokio_tungstenite::connect_async(relay))
+        .await
+        .map_err(|_| format!("timed out connecting to {relay}"))?
+        .map_err(|err| format!("failed to connect to {relay}: {err}"))?;
+
+    socket
+        .send(WsMessage::Text(
+            json!(["REQ", "samizdat", { "kinds": [KIND, REPORT_KIND], "#d": topics }]).to_string(),
+        ))
+        .await
+        .map_err(|err| format!("failed to subscribe: {err}"))?;
+
+    while let Some(message) = socket.next().await {
+        let text = match message.map_err(|err| format!("relay connection failed: {err}"))? {
+            WsMessage::Text(text) => text,
+            _ => continue,
+        };
+
+        if let Ok((_, _, event)) = serde_json::from_str::<(String, String, Event)>(&text) {
+            receive(event);
+        }
+    }
+
+    Ok(())
+}
+
+/// Hands an event over to the node.
+fn receive(event: Event) {
+    let topic = match event.topic() {
+        Some(topic) if event.is_valid() => topic.to_owned(),
+        _ => return,
+    };
+
+    match event.kind {
+        KIND => receive_announcement_event(topic, event),
+        REPORT_KIND => {
+            if let Err(err) = receive_report(&topic, &event) {
+                log::warn!("bad readership report in Nostr event {}: {err}", event.id);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Hands an announcement over to the node, if it is new.
+fn receive_announcement_event(topic: String, event: Event) {
+    {
+        let mut latest_seen = LATEST_SEEN.lock().expect("poisoned");
+        if latest_seen.get(&topic) >= Some(&event.created_at) {
+            return;
+        }
+        latest_seen.insert(topic, event.created_at);
+    }
+
+    let announcement = base64_url::decode(&event.content)
+        .ok()
+        .and_then(|announcement| bincode::deserialize::<EditionAnnouncement>(&announcement).ok());
+
+    if let Some(announcement) = announcement {
+        log::info!("Got announcement from Nostr event {}", event.id);
+        receive_announcement(Arc::new(announcement));
+    }
+}
+
+/// Stores a readership report sent to the inbox of a series owned by this node.
+fn receive_report(topic: &str, event: &Event) -> Result<(), crate::Error> {
+    let public_key = match SeriesOwner::get_all()?
+        .into_iter()
+        .map(|owner| owner.series().public_key())
+        .find(|public_key| inbox(public_key) == topic)
+    {
+        Some(public_key) => public_key,
+        None => return Ok(()),
+    };
+
+    let content = base64_url::decode(&event.content).map_err(|err| err.to_string())?;
+    let (rand, encrypted): (Hash, OpaqueEncrypted) = bincode::deserialize(&content)?;
+    let report: ReadershipReport =
+        encrypted.decrypt_with(&TransferCipher::new(&public_key.hash(), &rand))?;
+
+    log::info!(
+        "Got readership report for {public_key} from {}",
+        event.pubkey
+    );
+    report.receive(&public_key, &event.pubkey)
+}
+
+#[test]
+fn signs_events() {
+    let keypair = KeyPair::from_seckey_slice(&Secp256k1::signing_only(), &[1; 32]).unwrap();
+    let mut event = Event::new(
+        &keypair,
+        1_650_000_000,
+        KIND,
+        vec![vec!["d".to_owned(), "samizdat:topic".to_owned()]],
+        "content".to_owned(),
+    );
+
+    assert!(event.is_valid());
+    assert_eq!(event.topic(), Some("topic"));
+
+    event.content.push('!');
+    assert!(!event.is_valid());
+}
@@ -0,0 +1,75 @@
+//! Read-only access to the database of a node that is not running, e.g., when the service
+//! will not start and its content needs to be inspected or recovered. The database is opened
+//! as a secondary instance, the same way as for replicas, so this is also safe to use while
+//! the node runs.
+//!
+//! Lists come as the same JSON the HTTP API would send, so that clients of the API can decode
+//! them in the same way.
+
+use std::ffi::OsStr;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use samizdat_common::Hash;
+
+use crate::db::PageQuery;
+use crate::models::{Edition, ObjectRef, SeriesRef};
+
+/// The folder the secondary instance keeps its logs in, deleted when dropped.
+pub struct Scratch(PathBuf);
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.0) {
+            log::debug!("failed to remove {:?}: {err}", self.0);
+        }
+    }
+}
+
+/// Opens the database of the node with the given data folder. This must be called once,
+/// before anything else in this crate. Keep the returned [`Scratch`] until done.
+pub fn open(data: &Path) -> Result<Scratch, crate::Error> {
+    // The secondary instance needs a folder of its own for its logs:
+    let scratch =
+        Scratch(std::env::temp_dir().join(format!("samizdat-offline-{}", std::process::id())));
+
+    crate::init_cli_from([
+        OsStr::new("samizdat-node"),
+        OsStr::new("--data"),
+        scratch.0.as_os_str(),
+        OsStr::new("--replica-of"),
+        data.as_os_str(),
+    ])?;
+    crate::db::init_db()?;
+
+    Ok(scratch)
+}
+
+/// Lists all series known to the node, as `GET /_series` does.
+pub fn series() -> Result<serde_json::Value, crate::Error> {
+    let page = SeriesRef::get_page(&PageQuery::default())?;
+    Ok(serde_json::to_value(page.items)?)
+}
+
+/// Lists all editions known to the node, as `GET /_editions` does.
+pub fn editions() -> Result<serde_json::Value, crate::Error> {
+    Ok(serde_json::to_value(Edition::get_all()?)?)
+}
+
+/// Writes the content of an object, without its header, as it is read. Returns `Ok(false)`
+/// if the object is not in the node.
+pub fn export_object(hash: &str, writer: impl Write) -> Result<bool, crate::Error> {
+    let Some(content) = ObjectRef::new(hash.parse::<Hash>()?).iter_skip_header()? else {
+        return Ok(false);
+    };
+
+    let mut writer = BufWriter::new(writer);
+
+    for byte in content {
+        writer.write_all(&[byte?])?;
+    }
+
+    writer.flush()?;
+
+    Ok(true)
+}
@@ -0,0 +1,234 @@
+//! Readership statistics for publishers, without tracking readers.
+//!
+//! Each node counts how many times it served each object to peers. Nothing about the peers is
+//! recorded, only one counter per object, dropped with the object. The owner of a series sees these counts for the items
+//! of each of its editions.
+//!
+//! Since much of the content of a popular series is served by its mirrors, mirrors may opt in
+//! (see `--report-readership`) to report their counts back to the publisher through the _inbox_
+//! of the series, a Nostr topic that only who knows the series can compute (see
+//! [`crate::nostr`]). Reports are encrypted with the key of the series and their counts are
+//! coarsened, so that a handful of reads cannot be singled out.
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+use samizdat_common::{Hash, Key};
+
+use crate::cli;
+use crate::db::{db, is_replica, Table};
+use crate::models::{ItemPathBuf, SeriesOwner, SeriesRef, SubscriptionKind, SubscriptionRef};
+
+/// Counts below this are reported as zero.
+const MIN_REPORTED_COUNT: u64 = 8;
+/// The time between two reports from a mirror.
+const REPORT_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+
+/// Reads a counter, stored as a big-endian `u64`.
+fn read_count(value: Option<&[u8]>) -> u64 {
+    value
+        .and_then(|value| value.try_into().ok())
+        .map(u64::from_be_bytes)
+        .unwrap_or_default()
+}
+
+/// The merge operator of [`Table::ServedCounts`]: adds increments to a counter, so that
+/// counting does not need to read the counter first.
+pub(crate) fn merge_counts(
+    _key: &[u8],
+    existing_val: Option<&[u8]>,
+    operands: &rocksdb::MergeOperands,
+) -> Option<Vec<u8>> {
+    let count = operands
+        .into_iter()
+        .map(|operand| read_count(Some(operand)))
+        .fold(read_count(existing_val), u64::saturating_add);
+
+    Some(count.to_be_bytes().to_vec())
+}
+
+/// Records that an object was served to a peer.
+pub fn count_served(hash: &Hash) -> Result<(), crate::Error> {
+    // The primary keeps its own count:
+    if is_replica() {
+        return Ok(());
+    }
+
+    db().merge_cf(Table::ServedCounts.get(), hash, 1u64.to_be_bytes())?;
+
+    Ok(())
+}
+
+/// The number of times an object was served to peers by this node.
+pub fn served(hash: &Hash) -> Result<u64, crate::Error> {
+    Ok(read_count(
+        db().get_cf(Table::ServedCounts.get(), hash)?.as_deref(),
+    ))
+}
+
+/// Rounds a count down to a power of two, dropping small counts altogether.
+pub fn coarsen(count: u64) -> u64 {
+    if count < MIN_REPORTED_COUNT {
+        0
+    } else {
+        1 << (63 - count.leading_zeros())
+    }
+}
+
+/// What a mirror reports to the publisher of a series: the coarsened number of times it served
+/// each object of the series, since it started mirroring.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReadershipReport {
+    pub counts: BTreeMap<Hash, u64>,
+    pub reported_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl ReadershipReport {
+    /// Builds the report of this node for a series.
+    pub fn for_series(series: &SeriesRef) -> Result<ReadershipReport, crate::Error> {
+        let mut counts = BTreeMap::new();
+
+        for edition in series.get_editions()? {
+            for (_, hash) in edition.collection().inventory()?.iter().flatten() {
+                let count = coarsen(served(hash)?);
+                if count > 0 {
+                    counts.insert(*hash, count);
+                }
+            }
+        }
+
+        Ok(ReadershipReport {
+            counts,
+            reported_at: chrono::Utc::now(),
+        })
+    }
+
+    fn key(public_key: &Key, reporter: &str) -> Vec<u8> {
+        [public_key.as_bytes(), reporter.as_bytes()].concat()
+    }
+
+    /// Stores a report received for a series owned by this node, unless a newer report from
+    /// the same reporter is already stored.
+    pub fn receive(&self, public_key: &Key, reporter: &str) -> Result<(), crate::Error> {
+        let key = ReadershipReport::key(public_key, reporter);
+        let existing = db()
+            .get_cf(Table::ReadershipReports.get(), &key)?
+            .map(|value| bincode::deserialize::<ReadershipReport>(&value))
+            .transpose()?;
+
+        if let Some(existing) = existing {
+            if existing.reported_at >= self.reported_at {
+                return Ok(());
+            }
+        }
+
+        db().put_cf(
+            Table::ReadershipReports.get(),
+            key,
+            bincode::serialize(self).expect("can serialize"),
+        )?;
+
+        Ok(())
+    }
+
+    /// The latest reports received for a series, one per reporter.
+    pub fn get_all(public_key: &Key) -> Result<Vec<ReadershipReport>, crate::Error> {
+        db().prefix_iterator_cf(Table::ReadershipReports.get(), public_key.as_bytes())
+            .take_while(|(key, _)| key.starts_with(public_key.as_bytes()))
+            .map(|(_, value)| Ok(bincode::deserialize(&value)?))
+            .collect()
+    }
+}
+
+/// The readership of an item of an edition.
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemReadership {
+    pub path: ItemPathBuf,
+    pub object: Hash,
+    /// The number of times this node served the object.
+    pub served: u64,
+    /// The sum of the (coarsened) counts reported by mirrors.
+    pub reported: u64,
+}
+
+/// The readership of an edition of a series.
+#[derive(Debug, Clone, Serialize)]
+pub struct EditionReadership {
+    pub collection: Hash,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub items: Vec<ItemReadership>,
+}
+
+/// The readership of each edition of a series owned by this node.
+pub fn readership(owner: &SeriesOwner) -> Result<Vec<EditionReadership>, crate::Error> {
+    let series = owner.series();
+    let reports = ReadershipReport::get_all(&series.public_key())?;
+
+    series
+        .get_editions()?
+        .into_iter()
+        .map(|edition| {
+            let collection = edition.collection();
+            let items = collection
+                .inventory()?
+                .iter()
+                .flatten()
+                .map(|(path, hash)| {
+                    Ok(ItemReadership {
+                        path: path.clone(),
+                        object: *hash,
+                        served: served(hash)?,
+                        reported: reports
+                            .iter()
+                            .filter_map(|report| report.counts.get(hash))
+                            .sum(),
+                    })
+                })
+                .collect::<Result<Vec<_>, crate::Error>>()?;
+
+            Ok(EditionReadership {
+                collection: collection.hash(),
+                timestamp: edition.timestamp(),
+                items,
+            })
+        })
+        .collect()
+}
+
+/// Sends the reports of the series mirrored by this node to their publishers, periodically,
+/// forever. This does nothing unless the user opted in and Nostr relays were configured.
+pub async fn run_report_daemon() {
+    if !cli().report_readership || cli().nostr_relays.is_empty() {
+        return;
+    }
+
+    let mut ticker = interval(REPORT_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        crate::lifecycle::wait_until_active().await;
+
+        let subscriptions = match SubscriptionRef::get_all() {
+            Ok(subscriptions) => subscriptions,
+            Err(err) => {
+                log::error!("failed to list subscriptions: {err}");
+                continue;
+            }
+        };
+
+        for subscription in subscriptions {
+            if subscription.kind() != SubscriptionKind::Mirror {
+                continue;
+            }
+
+            let series = SeriesRef::new(subscription.public_key().clone());
+            match ReadershipReport::for_series(&series) {
+                Ok(report) if report.counts.is_empty() => {}
+                Ok(report) => crate::nostr::report(&series.public_key(), &report),
+                Err(err) => log::warn!("failed to build readership report for {series}: {err}"),
+            }
+        }
+    }
+}
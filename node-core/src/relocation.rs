@@ -0,0 +1,143 @@
+//! Moving the data folder of a node to another folder, possibly of another machine. Copying
+//! the files of RocksDB as they are carries over their format version, their options and
+//! their write-ahead log, which sometimes leaves a node that will not open its database on a
+//! different OS or architecture. Instead, the database is copied entry by entry into a fresh
+//! one, which the running version of RocksDB creates from scratch.
+//!
+//! Nothing stored in a data folder refers to where the folder is, so the rest of it is copied
+//! as is, with the profiles under it relocated in the same way. The only exception is the
+//! access token, which is left out to be generated anew when the node starts: the old one may
+//! have been exposed when the folder was moved around.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::db::Table;
+
+/// The number of entries written to the new database at once.
+const BATCH_SIZE: usize = 10_000;
+
+/// Files and folders that are not copied to the new data folder.
+const SKIPPED: &[&str] = &["db", "db-secondary", "access-token", "profiles"];
+
+/// What was moved from a data folder.
+#[derive(Debug)]
+pub struct RelocationReport {
+    /// The new data folder.
+    pub data: PathBuf,
+    /// The number of entries in each table of the database.
+    pub tables: Vec<(String, usize)>,
+    /// The reports for the profiles under this data folder.
+    pub profiles: Vec<RelocationReport>,
+}
+
+/// Moves the data folder `from` into the new folder `to`, which must be empty or not exist
+/// yet. The node must not be running, or the copy may miss its latest writes.
+pub fn migrate(from: &Path, to: &Path) -> Result<RelocationReport, crate::Error> {
+    if !from.join("db").exists() {
+        return Err(format!("{from:?} has no database. Is it the data folder of a node?").into());
+    }
+
+    if to.exists() && fs::read_dir(to)?.next().is_some() {
+        return Err(format!("{to:?} is not empty").into());
+    }
+
+    migrate_folder(from, to)
+}
+
+fn migrate_folder(from: &Path, to: &Path) -> Result<RelocationReport, crate::Error> {
+    log::info!("Moving data folder {from:?} to {to:?}");
+    fs::create_dir_all(to)?;
+
+    let tables = if from.join("db").exists() {
+        copy_db(&from.join("db"), &to.join("db"))?
+    } else {
+        vec![]
+    };
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if !SKIPPED.iter().any(|&skipped| entry.file_name() == skipped) {
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    }
+
+    // The list of profiles and the current profile are kept, but the data of each profile is
+    // itself a data folder:
+    let mut profiles = vec![];
+    if from.join("profiles").is_dir() {
+        for entry in fs::read_dir(from.join("profiles"))? {
+            let entry = entry?;
+            let destination = to.join("profiles").join(entry.file_name());
+
+            if entry.file_type()?.is_dir() {
+                profiles.push(migrate_folder(&entry.path(), &destination)?);
+            } else {
+                fs::create_dir_all(to.join("profiles"))?;
+                fs::copy(entry.path(), destination)?;
+            }
+        }
+    }
+
+    Ok(RelocationReport {
+        data: to.to_owned(),
+        tables,
+        profiles,
+    })
+}
+
+/// Copies a file or a folder with everything in it.
+fn copy_all(from: &Path, to: &Path) -> Result<(), crate::Error> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)?;
+    }
+
+    Ok(())
+}
+
+/// Copies all entries of all column families of a database into a new database.
+fn copy_db(from: &Path, to: &Path) -> Result<Vec<(String, usize)>, crate::Error> {
+    let names = rocksdb::DB::list_cf(&rocksdb::Options::default(), from)?;
+    let source =
+        rocksdb::DB::open_cf_for_read_only(&rocksdb::Options::default(), from, &names, false)?;
+
+    let mut db_opts = rocksdb::Options::default();
+    db_opts.create_if_missing(true);
+    db_opts.create_missing_column_families(true);
+    let target = rocksdb::DB::open_cf_descriptors(
+        &db_opts,
+        to,
+        names.iter().cloned().map(Table::descriptor_by_name),
+    )?;
+
+    let mut tables = vec![];
+
+    for name in names {
+        let source_cf = source.cf_handle(&name).expect("column family exists");
+        let target_cf = target.cf_handle(&name).expect("column family exists");
+        let mut batch = rocksdb::WriteBatch::default();
+        let mut entries = 0;
+
+        for (key, value) in source.iterator_cf(source_cf, rocksdb::IteratorMode::Start) {
+            batch.put_cf(target_cf, key, value);
+            entries += 1;
+
+            if batch.len() == BATCH_SIZE {
+                target.write(std::mem::take(&mut batch))?;
+            }
+        }
+
+        target.write(batch)?;
+        target.flush_cf(target_cf)?;
+        log::info!("Copied {entries} entries of table {name}");
+        tables.push((name, entries));
+    }
+
+    Ok(tables)
+}
@@ -0,0 +1,58 @@
+//! Replies: a convention for decentralized comments on Samizdat pages.
+//!
+//! Anyone may reply to an item (e.g., a page) by publishing a _reply series_ of their own and
+//! registering it as a reply to the locator hash of the item. Other nodes find the replies to
+//! an item by asking the hubs for editions with a riddle on the [`reply_topic`] of the item,
+//! which only who knows the locator can compute. Nodes answer with the latest edition of every
+//! reply they know of, encrypted with the topic, so replies spread like any cached content.
+//!
+//! What a reply edition contains is up to the application rendering the comments.
+
+use samizdat_common::Hash;
+
+use crate::hubs;
+use crate::models::{Edition, Reply, SeriesOwner};
+
+/// Registers a locally owned series as a reply to an item.
+pub fn register(locator: Hash, owner: &SeriesOwner) -> Result<Reply, crate::Error> {
+    let reply = Reply::new(locator, owner.series().public_key());
+    reply.insert()?;
+
+    Ok(reply)
+}
+
+/// Finds the replies to an item in the network, storing the latest edition of each. Returns
+/// the number of replies found.
+pub async fn fetch(locator: Hash) -> Result<usize, crate::Error> {
+    let mut found = 0;
+
+    for edition in hubs().get_replies(&locator).await {
+        if let Err(err) = edition.series().advance(&edition) {
+            log::warn!("failed to store reply edition {edition:?}: {err}");
+            continue;
+        }
+
+        Reply::new(locator, edition.public_key().clone()).insert()?;
+        found += 1;
+    }
+
+    Ok(found)
+}
+
+/// The latest edition of each reply to an item known to this node, latest first. This does
+/// not look for replies in the network (see [`fetch`]).
+pub fn known(locator: Hash) -> Result<Vec<Edition>, crate::Error> {
+    let mut editions = vec![];
+
+    for reply in Reply::get_all(&locator)? {
+        if let Some(edition) = reply.series().get_editions()?.into_iter().next() {
+            if !edition.is_draft() {
+                editions.push(edition);
+            }
+        }
+    }
+
+    editions.sort_unstable_by_key(|edition| std::cmp::Reverse(edition.timestamp()));
+
+    Ok(editions)
+}
@@ -0,0 +1,234 @@
+//! A low-priority process that continuously checks the integrity of the chunks stored in the
+//! database. Corrupted chunks are put in quarantine and the objects they belong to are purged.
+//! If these objects were bookmarked, they are fetched again from the network. The objects
+//! pending refetch are kept in [`Table::PendingRefetches`], so that they are not forgotten if
+//! the node restarts before they are fetched.
+
+use chrono::{DateTime, Utc};
+use rocksdb::{IteratorMode, WriteBatch};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::time::sleep;
+
+use samizdat_common::rpc::QueryKind;
+use samizdat_common::Hash;
+
+use crate::chunk_cache;
+use crate::db::{db, Table};
+use crate::hubs;
+use crate::models::{Droppable, ObjectMetadata, ObjectRef};
+
+/// The number of chunks to be checked before the scrubber takes a pause.
+const CHUNKS_PER_PAUSE: usize = 16;
+/// The pause between each batch of checked chunks. This is what makes the scrubber
+/// low-priority.
+const PAUSE: Duration = Duration::from_millis(100);
+/// The time between the end of a round and the start of the next one.
+const INTERLUDE: Duration = Duration::from_secs(3_600);
+/// The maximum number of findings kept in the scrub status.
+const MAX_FINDINGS: usize = 1_000;
+
+lazy_static::lazy_static! {
+    /// The current status of the scrubber.
+    static ref SCRUB_STATUS: RwLock<ScrubStatus> = RwLock::default();
+}
+
+/// A corrupted chunk found by the scrubber.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrubFinding {
+    /// The hash of the corrupted chunk.
+    pub chunk: Hash,
+    /// The time this chunk was found to be corrupted.
+    pub found_at: DateTime<Utc>,
+    /// The objects which contained the corrupted chunk. These were all purged.
+    pub objects: Vec<Hash>,
+    /// The bookmarked objects which are yet to be fetched again from the network.
+    pub pending_refetch: Vec<Hash>,
+}
+
+/// The progress and findings of the scrubber.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ScrubStatus {
+    /// Whether a round is currently running.
+    pub is_running: bool,
+    /// The number of complete rounds since the node started.
+    pub rounds_completed: usize,
+    /// The time the current (or last) round has started.
+    pub round_started_at: Option<DateTime<Utc>>,
+    /// The time the last complete round has ended.
+    pub round_ended_at: Option<DateTime<Utc>>,
+    /// The number of chunks checked in the current (or last) round.
+    pub chunks_checked: usize,
+    /// The number of chunks checked in the last complete round. This is an estimate for the
+    /// total number of chunks to be checked in the current round.
+    pub chunks_in_last_round: Option<usize>,
+    /// The most recent corrupted chunks found.
+    pub findings: VecDeque<ScrubFinding>,
+}
+
+/// Retrieves a snapshot of the current status of the scrubber.
+pub fn scrub_status() -> ScrubStatus {
+    SCRUB_STATUS.read().expect("poisoned").clone()
+}
+
+/// Puts a corrupted chunk in quarantine and purges all objects that reference it. Bookmarked
+/// objects keep their bookmarks, so that they can be fetched again.
+fn quarantine(chunk: Hash, content: &[u8]) -> Result<ScrubFinding, crate::Error> {
+    let mut batch = WriteBatch::default();
+    let mut objects = Vec::new();
+    let mut pending_refetch = Vec::new();
+
+    batch.put_cf(Table::QuarantinedChunks.get(), chunk, content);
+    batch.delete_cf(Table::ObjectChunks.get(), chunk);
+    chunk_cache::remove(&chunk);
+
+    for (key, value) in db().iterator_cf(Table::ObjectMetadata.get(), IteratorMode::Start) {
+        let metadata: ObjectMetadata = bincode::deserialize(&value)?;
+        if !metadata.hashes.contains(&chunk) {
+            continue;
+        }
+
+        let object = ObjectRef::new(Hash::try_from(&*key)?);
+
+        if object.is_bookmarked()? {
+            object.drop_content_with(&mut batch)?;
+            batch.put_cf(Table::PendingRefetches.get(), object.hash(), []);
+            pending_refetch.push(*object.hash());
+        } else {
+            object.drop_if_exists_with(&mut batch)?;
+        }
+
+        objects.push(*object.hash());
+    }
+
+    db().write(batch)?;
+
+    Ok(ScrubFinding {
+        chunk,
+        found_at: Utc::now(),
+        objects,
+        pending_refetch,
+    })
+}
+
+/// Runs a full scrub round over all chunks in the database.
+fn scrub() -> Result<(), crate::Error> {
+    {
+        let mut status = SCRUB_STATUS.write().expect("poisoned");
+        status.is_running = true;
+        status.round_started_at = Some(Utc::now());
+        status.chunks_checked = 0;
+    }
+
+    for (i, (key, value)) in db()
+        .iterator_cf(Table::ObjectChunks.get(), IteratorMode::Start)
+        .enumerate()
+    {
+        if i > 0 && i % CHUNKS_PER_PAUSE == 0 {
+            std::thread::sleep(PAUSE);
+        }
+
+        let chunk = match Hash::try_from(&*key) {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                log::warn!("bad chunk key in database: {}", err);
+                continue;
+            }
+        };
+
+        if Hash::hash(&value) != chunk {
+            log::warn!("chunk {} is corrupted. Putting it in quarantine", chunk);
+            let finding = quarantine(chunk, &value)?;
+
+            let mut status = SCRUB_STATUS.write().expect("poisoned");
+            status.findings.push_back(finding);
+            if status.findings.len() > MAX_FINDINGS {
+                status.findings.pop_front();
+            }
+        }
+
+        SCRUB_STATUS.write().expect("poisoned").chunks_checked += 1;
+    }
+
+    let mut status = SCRUB_STATUS.write().expect("poisoned");
+    status.is_running = false;
+    status.rounds_completed += 1;
+    status.round_ended_at = Some(Utc::now());
+    status.chunks_in_last_round = Some(status.chunks_checked);
+
+    Ok(())
+}
+
+/// The bookmarked objects purged by the scrubber and not fetched again yet.
+fn pending_refetches() -> Result<Vec<Hash>, crate::Error> {
+    db().iterator_cf(Table::PendingRefetches.get(), IteratorMode::Start)
+        .map(|(key, _)| Hash::try_from(&*key))
+        .collect()
+}
+
+/// Tries to fetch again all bookmarked objects that were purged by the scrubber.
+async fn refetch() {
+    let pending = match pending_refetches() {
+        Ok(pending) => pending,
+        Err(err) => {
+            log::error!("failed to list objects pending refetch: {}", err);
+            return;
+        }
+    };
+
+    for hash in pending {
+        // Not wanted anymore? Then, not worth fetching:
+        if !ObjectRef::new(hash).is_bookmarked().unwrap_or(true) {
+            if let Err(err) = db().delete_cf(Table::PendingRefetches.get(), hash) {
+                log::error!("failed to forget object {} pending refetch: {}", hash, err);
+            }
+            continue;
+        }
+
+        if hubs().query(hash, QueryKind::Object).await.is_some() {
+            log::info!("refetched object {} purged by scrubber", hash);
+            if let Err(err) = db().delete_cf(Table::PendingRefetches.get(), hash) {
+                log::error!("failed to mark object {} as refetched: {}", hash, err);
+            }
+            for finding in &mut SCRUB_STATUS.write().expect("poisoned").findings {
+                finding.pending_refetch.retain(|pending| *pending != hash);
+            }
+        } else {
+            log::warn!("could not refetch object {} purged by scrubber", hash);
+        }
+    }
+}
+
+/// Runs scrub rounds forever.
+pub async fn run_scrub_daemon() {
+    loop {
+        crate::lifecycle::wait_until_active().await;
+
+        let scrub_task = Handle::current().spawn_blocking(|| {
+            log::debug!("scrub task started");
+
+            if let Err(err) = scrub() {
+                SCRUB_STATUS.write().expect("poisoned").is_running = false;
+                log::error!("scrub task error: {}", err);
+            }
+
+            log::debug!("scrub task ended");
+        });
+
+        if let Err(err) = scrub_task.await {
+            SCRUB_STATUS.write().expect("poisoned").is_running = false;
+            log::error!("scrub task panicked: {}", err);
+        }
+
+        // Refetching can wait for the node not to be quiet:
+        if !crate::lifecycle::is_quiet() {
+            refetch().await;
+        }
+
+        sleep(INTERLUDE).await;
+    }
+}
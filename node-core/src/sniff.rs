@@ -0,0 +1,270 @@
+//! Content sniffing for uploads. Clients often post files with a missing or wrong content
+//! type, which breaks pages. The node looks at the first bytes of each upload: it fills in the
+//! content type when none was given (or just `application/octet-stream`), warns when the
+//! content does not look like the given type and adds the charset to text types without one.
+//! Text that could be a stylesheet or a script is left as `application/octet-stream`, since
+//! it cannot be told apart from plain text reliably.
+//! The decision is what goes into the header of the new object. Sniffing can be turned off
+//! with `--no-content-sniffing`.
+
+use crate::cli;
+
+/// How many bytes from the beginning of the content are looked at.
+pub const SNIFF_SIZE: usize = 1_024;
+
+/// The content type used when nothing better is known.
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Signatures of binary formats: the offset, the magic bytes and the content type.
+const SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (8, b"WEBP", "image/webp"),
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"\0asm", "application/wasm"),
+    (0, b"wOFF", "font/woff"),
+    (0, b"wOF2", "font/woff2"),
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"\x1a\x45\xdf\xa3", "video/webm"),
+];
+
+/// Signatures of container formats, which hold many kinds of content (e.g., EPUB and JAR
+/// files are ZIP files). Content types other than these are not warned about.
+const CONTAINERS: &[(usize, &[u8], &str)] = &[
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"\x1f\x8b", "application/gzip"),
+    (0, b"OggS", "application/ogg"),
+    (4, b"ftyp", "video/mp4"),
+];
+
+/// Tags that open HTML documents.
+const HTML_TAGS: &[&str] = &["<!doctype html", "<html", "<head", "<body", "<script"];
+
+/// Beginnings of stylesheets and scripts, which cannot be told apart from plain text reliably.
+const CODE_STARTS: &[&str] = &[
+    "@charset",
+    "@import",
+    "@media",
+    "@font-face",
+    "/*",
+    "//",
+    "\"use strict\"",
+    "'use strict'",
+    "function",
+    "(function",
+    "!function",
+    "var ",
+    "let ",
+    "const ",
+    "import ",
+    "export ",
+];
+
+/// The outcome of sniffing an upload.
+#[derive(Debug)]
+pub struct Sniffed {
+    /// The content type to use.
+    pub content_type: String,
+    /// What the client should know about the decision.
+    pub warnings: Vec<String>,
+}
+
+/// The content type without parameters, in lowercase.
+fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+/// Finds the content type of a binary format from its signature, if any. Also tells whether
+/// it is a container format.
+fn magic(head: &[u8]) -> Option<(&'static str, bool)> {
+    let matches = |&&(offset, magic, _): &&(usize, &[u8], &str)| {
+        head.get(offset..offset + magic.len()) == Some(magic)
+    };
+
+    if let Some(&(_, _, content_type)) = SIGNATURES.iter().find(matches) {
+        // WebP files are RIFF files:
+        if content_type != "image/webp" || head.starts_with(b"RIFF") {
+            return Some((content_type, false));
+        }
+    }
+
+    CONTAINERS
+        .iter()
+        .find(matches)
+        .map(|&(_, _, content_type)| (content_type, true))
+}
+
+/// Whether the content has bytes that never show up in text.
+fn is_binary(head: &[u8]) -> bool {
+    head.iter()
+        .any(|&byte| matches!(byte, 0..=8 | 0x0b | 0x0e..=0x1a | 0x1c..=0x1f))
+}
+
+/// Decodes the beginning of UTF-16 content, which is full of bytes that never show up in text
+/// in other charsets.
+fn decode_utf16(head: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    char::decode_utf16(
+        head.chunks_exact(2)
+            .take(128)
+            .map(|pair| from_bytes([pair[0], pair[1]])),
+    )
+    .map(|decoded| decoded.unwrap_or(char::REPLACEMENT_CHARACTER))
+    .collect()
+}
+
+/// Finds the content type of text content, if it is text. Text that could be a stylesheet or
+/// a script is not given a content type, since calling it `text/plain` would break it.
+fn text_type(head: &[u8]) -> Option<&'static str> {
+    let start = match strip_bom(head) {
+        (Some("utf-16le"), rest) => decode_utf16(rest, u16::from_le_bytes),
+        (Some("utf-16be"), rest) => decode_utf16(rest, u16::from_be_bytes),
+        (_, rest) if is_binary(rest) => return None,
+        (_, rest) => String::from_utf8_lossy(&rest[..rest.len().min(128)]).into_owned(),
+    };
+    let start = start.trim_start().to_lowercase();
+
+    if HTML_TAGS.iter().any(|tag| start.starts_with(tag)) {
+        Some("text/html")
+    } else if start.starts_with("<svg") || (start.starts_with("<?xml") && start.contains("<svg")) {
+        Some("image/svg+xml")
+    } else if start.starts_with("<?xml") {
+        Some("application/xml")
+    } else if CODE_STARTS.iter().any(|code| start.starts_with(code)) || start.contains('{') {
+        None
+    } else {
+        Some("text/plain")
+    }
+}
+
+/// Splits the byte order mark from the content, giving the charset it stands for.
+fn strip_bom(head: &[u8]) -> (Option<&'static str>, &[u8]) {
+    if let Some(rest) = head.strip_prefix(b"\xef\xbb\xbf") {
+        (Some("utf-8"), rest)
+    } else if let Some(rest) = head.strip_prefix(b"\xff\xfe") {
+        (Some("utf-16le"), rest)
+    } else if let Some(rest) = head.strip_prefix(b"\xfe\xff") {
+        (Some("utf-16be"), rest)
+    } else {
+        (None, head)
+    }
+}
+
+/// Whether a content type is for text, which should have a charset.
+fn is_text(essence: &str) -> bool {
+    essence.starts_with("text/")
+        || matches!(
+            essence,
+            "application/javascript" | "application/xml" | "image/svg+xml"
+        )
+}
+
+/// Detects the charset of text content. Content that is not UTF-8 is taken to be
+/// `windows-1252`, as browsers do.
+fn charset(head: &[u8]) -> &'static str {
+    if let (Some(charset), _) = strip_bom(head) {
+        return charset;
+    }
+
+    match std::str::from_utf8(head) {
+        Ok(_) => "utf-8",
+        // The content may be cut in the middle of a character:
+        Err(err) if err.error_len().is_none() => "utf-8",
+        Err(_) => "windows-1252",
+    }
+}
+
+/// Decides the content type of an upload, given the declared content type, if any, and the
+/// first [`SNIFF_SIZE`] bytes of the content (or all of it, if shorter).
+pub fn sniff(declared: Option<&str>, head: &[u8]) -> Sniffed {
+    let declared = declared
+        .map(str::trim)
+        .filter(|declared| !declared.is_empty());
+
+    if cli().no_content_sniffing {
+        return Sniffed {
+            content_type: declared.unwrap_or(OCTET_STREAM).to_owned(),
+            warnings: vec![],
+        };
+    }
+
+    let mut warnings = vec![];
+    let magic = magic(head);
+    let sniffed = magic
+        .map(|(content_type, _)| content_type)
+        .or_else(|| text_type(head));
+
+    let mut content_type = match (declared, sniffed) {
+        (Some(declared), _) if essence(declared) != OCTET_STREAM => {
+            if let Some((magic, false)) = magic {
+                if essence(declared) != magic {
+                    warnings.push(format!(
+                        "content declared as {declared} looks like {magic}; keeping {declared}"
+                    ));
+                }
+            }
+
+            declared.to_owned()
+        }
+        (_, Some(sniffed)) => {
+            warnings.push(format!("no content type given; sniffed {sniffed}"));
+            sniffed.to_owned()
+        }
+        (declared, None) => declared.unwrap_or(OCTET_STREAM).to_owned(),
+    };
+
+    if is_text(&essence(&content_type)) && !content_type.contains("charset=") {
+        let charset = charset(head);
+
+        if charset == "windows-1252" {
+            warnings.push(format!(
+                "{content_type} content is not UTF-8; assuming charset {charset}"
+            ));
+        }
+
+        content_type = format!("{content_type}; charset={charset}");
+    }
+
+    for warning in &warnings {
+        log::warn!("content sniffing: {warning}");
+    }
+
+    Sniffed {
+        content_type,
+        warnings,
+    }
+}
+
+#[test]
+fn sniff_magic_and_text() {
+    assert_eq!(magic(b"\x89PNG\r\n\x1a\n...."), Some(("image/png", false)));
+    assert_eq!(magic(b"RIFF\0\0\0\0WEBPVP8 "), Some(("image/webp", false)));
+    assert_eq!(magic(b"RIFF\0\0\0\0WAVEfmt "), None);
+    assert_eq!(magic(b"PK\x03\x04rest"), Some(("application/zip", true)));
+    assert_eq!(text_type(b"  <!DOCTYPE html><html>"), Some("text/html"));
+    assert_eq!(
+        text_type(b"<?xml version=\"1.0\"?><svg>"),
+        Some("image/svg+xml")
+    );
+    assert_eq!(text_type(b"body { color: red; }"), None);
+    assert_eq!(text_type(b"function f() {}"), None);
+    assert_eq!(text_type(b"Just some notes."), Some("text/plain"));
+    assert_eq!(text_type(b"\xff\xfe<\0h\0t\0m\0l\0>\0"), Some("text/html"));
+    assert_eq!(text_type(b"\0\x01\x02"), None);
+}
+
+#[test]
+fn detect_charset() {
+    assert_eq!(charset(b"plain ascii"), "utf-8");
+    assert_eq!(charset("maçã".as_bytes()), "utf-8");
+    assert_eq!(charset(&"maçã".as_bytes()[..3]), "utf-8");
+    assert_eq!(charset(b"ma\xe7\xe3"), "windows-1252");
+    assert_eq!(charset(b"\xff\xfeh\0i\0"), "utf-16le");
+}
@@ -0,0 +1,310 @@
+//! Differential sync between trusted nodes, e.g., to replicate everything from a home node to
+//! a VPS. This goes straight from node to node through their HTTP APIs, authenticated with the
+//! access token of the peer, and never touches the hubs.
+//!
+//! The content of each node (its objects and collection items) is summarized as a two-level
+//! Merkle tree: entries are split in buckets by their first byte and each bucket is hashed. The
+//! node pulling the content first compares the roots, then the buckets, and only lists the
+//! entries of the buckets that differ, downloading whatever it does not have. Objects are
+//! copied verbatim, so they keep their hashes, and collection items keep their inclusion
+//! proofs.
+//!
+//! Since the HTTP API only accepts connections from the loopback, the API of the peer has to
+//! be reached through a tunnel, e.g., with `ssh -L`.
+
+use futures::prelude::*;
+use futures::stream;
+use rocksdb::{IteratorMode, WriteBatch};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use samizdat_common::Hash;
+
+use crate::cli;
+use crate::db::{db, Table};
+use crate::models::{CollectionItem, Droppable, ObjectRef};
+
+/// The number of buckets in the summary, one for each value of the first byte of the hashes.
+const N_BUCKETS: usize = 256;
+/// The maximum time to wait for any single request to the peer.
+const PEER_TIMEOUT: Duration = Duration::from_secs(60);
+
+lazy_static::lazy_static! {
+    static ref CLIENT: reqwest::Client = reqwest::Client::builder()
+        .timeout(PEER_TIMEOUT)
+        .build()
+        .expect("can build client");
+}
+
+/// Something that can be synced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SyncEntry {
+    /// An object, by its hash.
+    Object(Hash),
+    /// A collection item, by the hash of its locator.
+    Item(Hash),
+}
+
+impl SyncEntry {
+    fn hash(&self) -> &Hash {
+        match self {
+            SyncEntry::Object(hash) | SyncEntry::Item(hash) => hash,
+        }
+    }
+
+    fn bucket(&self) -> usize {
+        self.hash()[0] as usize
+    }
+
+    /// Whether this entry is already in the local database.
+    fn exists(&self) -> Result<bool, crate::Error> {
+        let table = match self {
+            SyncEntry::Object(_) => Table::ObjectMetadata,
+            SyncEntry::Item(_) => Table::CollectionItems,
+        };
+
+        Ok(db().get_cf(table.get(), self.hash())?.is_some())
+    }
+}
+
+/// A summary of the content of a node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncSummary {
+    /// The hash of all bucket hashes.
+    pub root: Hash,
+    /// The hash of the entries in each bucket.
+    pub buckets: Vec<Hash>,
+}
+
+/// What was copied from the peer in a sync.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// The number of buckets which were different in the peer.
+    pub different_buckets: usize,
+    /// The number of objects copied.
+    pub objects: usize,
+    /// The number of collection items copied.
+    pub items: usize,
+    /// Entries that could not be copied, with the reason.
+    pub failed: Vec<(SyncEntry, String)>,
+}
+
+/// All entries in the local database, split in buckets and sorted.
+fn buckets() -> Result<Vec<BTreeSet<SyncEntry>>, crate::Error> {
+    let mut buckets = vec![BTreeSet::new(); N_BUCKETS];
+    let tables = [
+        (
+            Table::ObjectMetadata,
+            SyncEntry::Object as fn(Hash) -> SyncEntry,
+        ),
+        (Table::CollectionItems, SyncEntry::Item),
+    ];
+
+    for (table, entry) in tables {
+        for (key, _) in db().iterator_cf(table.get(), IteratorMode::Start) {
+            let entry = entry(Hash::try_from(&*key)?);
+            buckets[entry.bucket()].insert(entry);
+        }
+    }
+
+    Ok(buckets)
+}
+
+/// Summarizes the content of this node.
+pub fn summary() -> Result<SyncSummary, crate::Error> {
+    let buckets = buckets()?
+        .iter()
+        .map(|bucket| Hash::hash(bincode::serialize(bucket).expect("can serialize")))
+        .collect::<Vec<_>>();
+    let root = Hash::hash(
+        buckets
+            .iter()
+            .flat_map(|hash| hash.iter())
+            .copied()
+            .collect::<Vec<_>>(),
+    );
+
+    Ok(SyncSummary { root, buckets })
+}
+
+/// Lists the entries of this node in the given buckets.
+pub fn bucket_entries(wanted: &[usize]) -> Result<BTreeMap<usize, Vec<SyncEntry>>, crate::Error> {
+    let mut buckets = buckets()?;
+
+    Ok(wanted
+        .iter()
+        .filter(|&&bucket| bucket < N_BUCKETS)
+        .map(|&bucket| {
+            let entries = std::mem::take(&mut buckets[bucket]);
+            (bucket, entries.into_iter().collect())
+        })
+        .collect())
+}
+
+/// A peer node, by the base URL of its HTTP API and its access token.
+struct Peer<'a> {
+    url: &'a str,
+    token: &'a str,
+}
+
+impl<'a> Peer<'a> {
+    async fn request(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, crate::Error> {
+        request
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| format!("request to peer {} failed: {err}", self.url).into())
+    }
+
+    /// Calls a route of the API of the peer, which replies with a JSON-encoded result.
+    async fn call<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, crate::Error> {
+        let response = self.request(request).await?;
+        let outcome: Result<T, String> = response
+            .json()
+            .await
+            .map_err(|err| format!("bad response from peer {}: {err}", self.url))?;
+
+        Ok(outcome.map_err(|err| format!("peer {} failed: {err}", self.url))?)
+    }
+
+    async fn summary(&self) -> Result<SyncSummary, crate::Error> {
+        self.call(CLIENT.get(format!("{}/_sync/summary", self.url)))
+            .await
+    }
+
+    async fn bucket_entries(
+        &self,
+        buckets: &[usize],
+    ) -> Result<BTreeMap<usize, Vec<SyncEntry>>, crate::Error> {
+        self.call(
+            CLIENT
+                .post(format!("{}/_sync/buckets", self.url))
+                .json(buckets),
+        )
+        .await
+    }
+
+    async fn copy_item(&self, hash: Hash) -> Result<(), crate::Error> {
+        let item: CollectionItem = self
+            .call(CLIENT.get(format!("{}/_sync/items/{hash}", self.url)))
+            .await?;
+
+        if !item.is_valid() || item.locator().hash() != hash {
+            return Err(format!("peer sent an invalid item for {hash}").into());
+        }
+
+        item.insert()
+    }
+
+    async fn copy_object(&self, hash: Hash) -> Result<(), crate::Error> {
+        let response = self
+            .request(CLIENT.get(format!("{}/_sync/objects/{hash}", self.url)))
+            .await?;
+
+        let max_content_size = cli().max_content_size * 1_000_000;
+        if response.content_length() > Some(max_content_size as u64) {
+            return Err(format!("object {hash} is bigger than the maximum content size").into());
+        }
+
+        // Stream the content into the database as it arrives; content beyond the announced
+        // size (or beyond the maximum size) is left out and fails the hash check:
+        let content_size = response
+            .content_length()
+            .map_or(max_content_size, |length| length as usize);
+        let content = stream::unfold(Some(response), move |response| next_chunk(hash, response))
+            .map_ok(|chunk| stream::iter(chunk.into_iter().map(Ok)))
+            .try_flatten();
+        let object = ObjectRef::import(content_size, true, None, Box::pin(content)).await?;
+
+        if object.hash() != &hash {
+            let mut batch = WriteBatch::default();
+            object.drop_if_exists_with(&mut batch)?;
+            db().write(batch)?;
+
+            return Err(format!("peer sent object {} for {hash}", object.hash()).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// The next chunk of the body of a response with the content of an object, for a stream ending
+/// on the first error.
+async fn next_chunk(
+    hash: Hash,
+    response: Option<reqwest::Response>,
+) -> Option<(
+    Result<bytes::Bytes, crate::Error>,
+    Option<reqwest::Response>,
+)> {
+    let mut response = response?;
+    match response.chunk().await {
+        Ok(Some(chunk)) => Some((Ok(chunk), Some(response))),
+        Ok(None) => None,
+        Err(err) => Some((
+            Err(format!("failed to download object {hash}: {err}").into()),
+            None,
+        )),
+    }
+}
+
+/// Copies everything the peer has that this node has not. Objects copied are bookmarked, so
+/// that they are never vacuumed away.
+pub async fn sync(peer_url: &str, token: &str) -> Result<SyncReport, crate::Error> {
+    let peer = Peer {
+        url: peer_url.trim_end_matches('/'),
+        token,
+    };
+    let mut report = SyncReport::default();
+
+    let (local, remote) = (summary()?, peer.summary().await?);
+    if local.root == remote.root || remote.buckets.len() != N_BUCKETS {
+        return Ok(report);
+    }
+
+    let different = (0..N_BUCKETS)
+        .filter(|&bucket| local.buckets[bucket] != remote.buckets[bucket])
+        .collect::<Vec<_>>();
+    report.different_buckets = different.len();
+    log::info!("Syncing {} buckets from {}", different.len(), peer.url);
+
+    // Objects go first, so that no item is ever left without its object:
+    let mut missing = Vec::new();
+    for entries in peer.bucket_entries(&different).await?.into_values() {
+        for entry in entries {
+            if !entry.exists()? {
+                missing.push(entry);
+            }
+        }
+    }
+    missing.sort_by_key(|entry| matches!(entry, SyncEntry::Item(_)));
+
+    for entry in missing {
+        let outcome = match entry {
+            SyncEntry::Object(hash) => peer.copy_object(hash).await,
+            SyncEntry::Item(hash) => peer.copy_item(hash).await,
+        };
+
+        match outcome {
+            Ok(()) if matches!(entry, SyncEntry::Object(_)) => report.objects += 1,
+            Ok(()) => report.items += 1,
+            Err(err) => {
+                log::warn!("failed to sync {entry:?}: {err}");
+                report.failed.push((entry, err.to_string()));
+            }
+        }
+    }
+
+    log::info!("Done syncing from {}: {report:?}", peer.url);
+
+    Ok(report)
+}
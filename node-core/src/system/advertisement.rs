@@ -0,0 +1,23 @@
+//! What this node tells hubs about itself when connecting (see [`Advertisement`]). Each
+//! connection to a hub gets the advertisement signed by a fresh key, which is never stored, so
+//! that hubs cannot link what they see of this node to each other or across reconnections.
+
+use ed25519_dalek::Keypair;
+
+use samizdat_common::handshake::{Advertisement, NodeInfo};
+
+use crate::cli;
+
+use super::file_transfer::TRANSFER_VERSION;
+
+/// What this node advertises to a hub, for a new connection.
+pub(super) fn advertisement() -> Advertisement {
+    let info = NodeInfo {
+        software_version: env!("CARGO_PKG_VERSION").to_owned(),
+        transfer_versions: vec![TRANSFER_VERSION],
+        relays: false,
+        max_object_size: cli().max_content_size as u64 * 1_000_000,
+    };
+
+    Advertisement::new(info, &Keypair::generate(&mut rand::rngs::OsRng {}))
+}
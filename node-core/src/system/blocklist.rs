@@ -0,0 +1,44 @@
+//! A blocklist of peers that repeatedly misbehave in transfers (e.g., by sending content that
+//! does not match its hash). Hole punching is expensive, so candidates coming from blocked
+//! peers are ignored for a while. Only peers this node connected to are struck: the addresses
+//! in candidates come from hubs and could be anybody's.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The number of misbehaving transfers after which a peer is blocked.
+const MAX_STRIKES: usize = 3;
+/// For how long a peer is blocked after its last misbehaving transfer.
+const BLOCK_DURATION: Duration = Duration::from_secs(3600);
+
+lazy_static::lazy_static! {
+    /// The number of misbehaving transfers of each peer and when the last one happened.
+    static ref STRIKES: Mutex<BTreeMap<SocketAddr, (usize, Instant)>> = Mutex::default();
+}
+
+/// Records that a peer this node connected to misbehaved in a transfer.
+pub fn strike(peer_addr: SocketAddr) {
+    let mut strikes = STRIKES.lock().expect("poisoned");
+
+    // Forget about peers that were not seen for a while:
+    strikes.retain(|_, (_, last)| last.elapsed() < BLOCK_DURATION);
+
+    let (count, last) = strikes.entry(peer_addr).or_insert((0, Instant::now()));
+    *count += 1;
+    *last = Instant::now();
+
+    if *count == MAX_STRIKES {
+        log::warn!("blocking candidates from {peer_addr} for {BLOCK_DURATION:?}");
+    }
+}
+
+/// Whether candidates from a peer should be ignored.
+pub fn is_blocked(peer_addr: SocketAddr) -> bool {
+    STRIKES
+        .lock()
+        .expect("poisoned")
+        .get(&peer_addr)
+        .is_some_and(|&(count, last)| count >= MAX_STRIKES && last.elapsed() < BLOCK_DURATION)
+}
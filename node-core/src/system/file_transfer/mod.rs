@@ -0,0 +1,612 @@
+//! Protocol for information transfer between peers. The sender sends a nonce, from which the
+//! cipher for the rest of the transfer is derived; the receiver answers with its limits (see
+//! [`LimitsMessage`]); the sender then sends a header and, if the receiver accepts content that
+//! big, the content itself. A receiver that gives up on the transfer (e.g., because the client
+//! asking for the content went away) says so (see [`StopMessage`]), so that the sender stops
+//! sending early.
+
+use brotli::{CompressorReader, Decompressor};
+use futures::prelude::*;
+use futures::stream;
+use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
+use serde_derive::{Deserialize, Serialize};
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{timeout, timeout_at, Instant};
+
+use samizdat_common::cipher::TransferCipher;
+use samizdat_common::Hash;
+
+use crate::cli;
+use crate::models::{CollectionItem, ObjectRef};
+
+use super::transport::{ChannelReceiver, ChannelSender};
+
+/// The version of this protocol, advertised to hubs, so that they do not pair peers that cannot
+/// transfer content to each other.
+pub(super) const TRANSFER_VERSION: u32 = 1;
+/// The maximum number of bytes allowed for a header.
+const MAX_HEADER_LENGTH: usize = 4_096;
+/// The maximum size of the stream.
+const MAX_STREAM_SIZE: usize = crate::models::CHUNK_SIZE * 2;
+/// The maximum time to wait for each message from the peer. Stalled transfers are abandoned
+/// after this time, even if the deadline of the transfer was not reached yet.
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(10);
+/// The slowest transfer rate, in bytes per second, that big content is given time for, beyond
+/// the budget of the transfer (see `--transfer-budget`).
+const MIN_TRANSFER_RATE: f64 = 64.0 * 1024.0;
+/// How long a sender waits for the limits of the receiver. Receivers predating the limits never
+/// send them; the content is sent to them regardless.
+const LIMITS_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Runs a step of a transfer, failing if it does not finish before the deadline or before the
+/// chunk timeout.
+async fn in_time<T>(
+    deadline: Instant,
+    step: impl Future<Output = Result<T, crate::Error>>,
+) -> Result<T, crate::Error> {
+    timeout_at(deadline.min(Instant::now() + CHUNK_TIMEOUT), step)
+        .await
+        .map_err(|_| crate::Error::Timeout)?
+}
+
+/// A header that can be sent from the sender to the receiver _before_ the stream starts.
+#[async_trait::async_trait]
+trait Message: 'static + Send + Sync + SerdeSerialize + for<'a> SerdeDeserialize<'a> {
+    /// Receive the header.
+    async fn recv(
+        receiver: &mut ChannelReceiver,
+        cipher: &TransferCipher,
+    ) -> Result<Self, crate::Error> {
+        // Receive header from peer:
+        let mut serialized_header = receiver
+            .recv(MAX_HEADER_LENGTH)
+            .await?
+            .ok_or("channel dried")?;
+        cipher.decrypt(&mut serialized_header);
+        let header: Self = bincode::deserialize(&serialized_header)?;
+
+        Ok(header)
+    }
+
+    /// Send the header.
+    async fn send(
+        &self,
+        sender: &ChannelSender,
+        cipher: &TransferCipher,
+    ) -> Result<(), crate::Error> {
+        let mut serialized_header = bincode::serialize(&self).expect("can serialize");
+        cipher.encrypt(&mut serialized_header);
+        sender.send(&serialized_header).await?;
+
+        Ok(())
+    }
+}
+
+/// Sends a _nonce_ (a number used only once) that will be used for deriving a key to transfer
+/// the stream.
+#[derive(Default, Serialize, Deserialize)]
+struct NonceMessage {
+    nonce: Hash,
+}
+
+impl Message for NonceMessage {}
+
+impl NonceMessage {
+    fn new() -> NonceMessage {
+        NonceMessage {
+            nonce: Hash::rand(),
+        }
+    }
+
+    /// Combines with a content header to create a symmetric cipher.
+    fn cipher(self, hash: Hash) -> TransferCipher {
+        TransferCipher::new(&hash, &self.nonce)
+    }
+
+    /// Receive a header from a channel and creates a cipher for all further transmissions.
+    async fn recv_negotiate(
+        receiver: &mut ChannelReceiver,
+        hash: Hash,
+    ) -> Result<TransferCipher, crate::Error> {
+        let init_cipher = NonceMessage::default().cipher(hash);
+        let nonce_header = NonceMessage::recv(receiver, &init_cipher).await?;
+
+        Ok(nonce_header.cipher(hash))
+    }
+
+    /// Sends a header from a channel and creates a cipher for all further transmissions.
+    async fn send_negotiate(
+        sender: &ChannelSender,
+        hash: Hash,
+    ) -> Result<TransferCipher, crate::Error> {
+        let init_cipher = NonceMessage::default().cipher(hash);
+        let nonce_header = NonceMessage::new();
+        nonce_header.send(sender, &init_cipher).await?;
+
+        Ok(nonce_header.cipher(hash))
+    }
+}
+
+/// Sent from the receiver right after the nonce, so that the sender does not send content that
+/// the receiver will refuse.
+#[derive(Debug, Serialize, Deserialize)]
+struct LimitsMessage {
+    /// The maximum size in bytes of the content the receiver accepts from the sender.
+    max_content_size: u64,
+}
+
+impl Message for LimitsMessage {}
+
+impl LimitsMessage {
+    /// The limits of this node for the content sent by the peer on the other side.
+    fn ours(sender: &ChannelSender) -> LimitsMessage {
+        let peer = sender.remote_address().peer_addr().ip();
+        LimitsMessage {
+            max_content_size: cli().max_content_size_for(peer) as u64,
+        }
+    }
+
+    /// Waits for the limits of the receiver, if it sends any.
+    async fn recv_from_receiver(
+        receiver: &mut ChannelReceiver,
+        cipher: &TransferCipher,
+    ) -> Option<LimitsMessage> {
+        match timeout(LIMITS_TIMEOUT, LimitsMessage::recv(receiver, cipher)).await {
+            Ok(Ok(limits)) => Some(limits),
+            Ok(Err(err)) => {
+                log::warn!("failed to receive limits from receiver: {err}");
+                None
+            }
+            Err(_) => {
+                log::info!("no limits from receiver; taking it for a legacy peer");
+                None
+            }
+        }
+    }
+}
+
+/// Sent from the receiver at any time after the limits, if it does not want the rest of the
+/// content anymore. Senders predating this never look for it and send everything regardless.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StopMessage;
+
+impl Message for StopMessage {}
+
+/// A message from the receiver arriving while the content is sent: either the limits, late
+/// (e.g., on a link with high latency), or a request to stop. These are told apart by their
+/// contents, since both were already sent on their own before this existed.
+#[derive(Debug)]
+enum ReceiverMessage {
+    Limits(LimitsMessage),
+    Stop(StopMessage),
+}
+
+impl ReceiverMessage {
+    fn decode(mut serialized: Vec<u8>, cipher: &TransferCipher) -> ReceiverMessage {
+        cipher.decrypt(&mut serialized);
+
+        match bincode::deserialize::<LimitsMessage>(&serialized) {
+            Ok(limits) => ReceiverMessage::Limits(limits),
+            Err(_) => ReceiverMessage::Stop(StopMessage),
+        }
+    }
+}
+
+/// Tells the sender to stop sending if the receiving end of a transfer is dropped before the
+/// transfer is done, be it because of an error or because the query was cancelled.
+struct StopOnDrop {
+    sender: ChannelSender,
+    cipher: Arc<TransferCipher>,
+    is_done: bool,
+}
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        if self.is_done {
+            return;
+        }
+
+        let sender = self.sender.clone();
+        let cipher = self.cipher.clone();
+
+        tokio::spawn(async move {
+            log::info!("telling {} to stop sending", sender.remote_address());
+            if let Err(err) = StopMessage.send(&sender, &cipher).await {
+                log::info!("failed to tell {} to stop: {err}", sender.remote_address());
+            }
+        });
+    }
+}
+
+impl StopOnDrop {
+    fn new(sender: &ChannelSender, cipher: &Arc<TransferCipher>) -> StopOnDrop {
+        StopOnDrop {
+            sender: sender.clone(),
+            cipher: cipher.clone(),
+            is_done: false,
+        }
+    }
+
+    /// The transfer is over: there is nothing to stop anymore.
+    fn done(mut self) {
+        self.is_done = true;
+    }
+}
+
+/// A header sending information (metadata) on a collection item.
+#[derive(Debug, Serialize, Deserialize)]
+struct ItemMessage {
+    item: CollectionItem,
+    object_header: ObjectMessage,
+}
+
+impl Message for ItemMessage {}
+
+impl ItemMessage {
+    /// Creates an item header for a given collection item.
+    fn for_item(item: CollectionItem) -> Result<ItemMessage, crate::Error> {
+        let object_header = ObjectMessage::for_object(&item.object()?)?;
+        Ok(ItemMessage {
+            item,
+            object_header,
+        })
+    }
+
+    /// Checks that the item is the one that was asked for and that its proof ties it to the
+    /// root of its collection, returning the object the item points to.
+    fn validate(&self, locator_hash: Hash) -> Result<ObjectRef, crate::Error> {
+        // No tricks!
+        let locator_hash_from_peer = self.item.locator().hash();
+        if locator_hash_from_peer != locator_hash {
+            return Err(crate::Error::PeerMisbehavior(format!(
+                "bad item from peer: expected {}, got {}",
+                locator_hash, locator_hash_from_peer,
+            )));
+        }
+
+        // This checks the proof against the collection root:
+        self.item.object().map_err(|_| {
+            crate::Error::PeerMisbehavior(format!(
+                "bad item from peer: {locator_hash} is not in collection {}",
+                self.item.collection.hash()
+            ))
+        })
+    }
+}
+
+/// A header sending information (metadata) on an item.
+#[derive(Debug, Serialize, Deserialize)]
+struct ObjectMessage {
+    nonce: Hash,
+    content_size: usize,
+}
+
+impl Message for ObjectMessage {}
+
+impl ObjectMessage {
+    /// Creates an object header for a given object.
+    ///
+    /// # Panics:
+    ///
+    /// If object does not exist locally.
+    fn for_object(object: &ObjectRef) -> Result<ObjectMessage, crate::Error> {
+        let metadata = object.metadata()?.expect("object exists");
+
+        Ok(ObjectMessage {
+            nonce: Hash::rand(),
+            content_size: metadata.content_size,
+        })
+    }
+
+    /// Use this header to receive the object from the peer. The whole object must arrive before
+    /// the deadline or, for big content, before it would at [`MIN_TRANSFER_RATE`].
+    pub async fn recv_data(
+        self,
+        receiver: &mut ChannelReceiver,
+        hash: Hash,
+        deadline: Instant,
+        limits: &LimitsMessage,
+    ) -> Result<ObjectRef, crate::Error> {
+        let cipher = Arc::new(TransferCipher::new(&hash, &self.nonce));
+
+        // Refuse if content is too big:
+        if self.content_size as u64 > limits.max_content_size {
+            return Err(crate::Error::QuotaExceeded(format!(
+                "content too big: max size is {}, advertised was {}",
+                limits.max_content_size, self.content_size
+            )));
+        }
+
+        let deadline = deadline.max(
+            Instant::now() + Duration::from_secs_f64(self.content_size as f64 / MIN_TRANSFER_RATE),
+        );
+
+        // Stream content, giving up on late chunks:
+        let content_stream =
+            tokio_stream::StreamExt::timeout(receiver.recv_many(MAX_STREAM_SIZE), CHUNK_TIMEOUT)
+                .map(|chunk| chunk.unwrap_or(Err(crate::Error::Timeout)))
+                .and_then(|mut buffer| {
+                    cipher.decrypt(&mut buffer);
+                    let mut decompressed = vec![];
+                    let outcome = Decompressor::new(Cursor::new(buffer), 4096)
+                        .read_to_end(&mut decompressed)
+                        .map(|_| stream::iter(decompressed.into_iter().map(Ok)))
+                        .map_err(|err| {
+                            crate::Error::PeerMisbehavior(format!("bad compressed chunk: {err}"))
+                        });
+                    future::ready(outcome)
+                })
+                .try_flatten();
+
+        // Build content from stream (this limits content size to the advertised amount)
+        let import = ObjectRef::import(
+            self.content_size,
+            false,
+            Some(hash),
+            Box::pin(content_stream),
+        );
+        let object = timeout_at(deadline, import)
+            .await
+            .map_err(|_| crate::Error::Timeout)??;
+        let metadata = object.metadata()?.expect("object exists");
+
+        log::info!("done building object");
+
+        // Check if the peer is up to any extra sneaky tricks.
+        if metadata.content_size != self.content_size {
+            Err(crate::Error::PeerMisbehavior(format!(
+                "actual data length did not match content-size: expected {}, got {}",
+                metadata.content_size, self.content_size
+            )))
+        } else if *object.hash() != hash {
+            Err(crate::Error::PeerMisbehavior(format!(
+                "bad content from peer: expected {}, got {}",
+                hash,
+                object.hash(),
+            )))
+        } else {
+            log::info!("received valid object from peer");
+            Ok(object)
+        }
+    }
+
+    /// Fails if the receiver does not accept content this big.
+    fn check_limits(
+        &self,
+        sender: &ChannelSender,
+        limits: &LimitsMessage,
+    ) -> Result<(), crate::Error> {
+        if self.content_size as u64 > limits.max_content_size {
+            return Err(crate::Error::QuotaExceeded(format!(
+                "{} only accepts content up to {} bytes; not sending {} bytes",
+                sender.remote_address(),
+                limits.max_content_size,
+                self.content_size
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Use this header to send the object to the peer, unless the peer is known to refuse it.
+    /// Returns whether the whole object was sent, that is, whether the peer did not ask to stop
+    /// halfway. The `transfer_cipher` is the one the receiver uses for its own messages.
+    pub async fn send_data(
+        self,
+        sender: &ChannelSender,
+        receiver: &mut ChannelReceiver,
+        transfer_cipher: &TransferCipher,
+        object: &ObjectRef,
+        limits: Option<LimitsMessage>,
+    ) -> Result<bool, crate::Error> {
+        if let Some(limits) = &limits {
+            self.check_limits(sender, limits)?;
+        }
+
+        let cipher = TransferCipher::new(object.hash(), &self.nonce);
+
+        for chunk in object.chunks()?.expect("object exits") {
+            match receiver
+                .recv_if_arrived(MAX_HEADER_LENGTH)
+                .await?
+                .map(|message| ReceiverMessage::decode(message, transfer_cipher))
+            {
+                Some(ReceiverMessage::Limits(limits)) => {
+                    log::info!("limits from {} arrived late", sender.remote_address());
+                    self.check_limits(sender, &limits)?;
+                }
+                Some(ReceiverMessage::Stop(StopMessage)) => {
+                    log::info!(
+                        "{} asked to stop sending {}",
+                        sender.remote_address(),
+                        object.hash()
+                    );
+                    return Ok(false);
+                }
+                None => {}
+            }
+
+            let chunk = chunk?;
+            log::debug!("stream for data opened");
+            let mut compressed = CompressorReader::new(Cursor::new(chunk), 4096, 4, 22)
+                .bytes()
+                .collect::<Result<Vec<_>, _>>()
+                .expect("never error");
+            cipher.encrypt(&mut compressed);
+            sender.send(&compressed).await?;
+        }
+
+        log::info!(
+            "finished sending {} to {}",
+            object.hash(),
+            sender.remote_address()
+        );
+
+        Ok(true)
+    }
+}
+
+/// Receives the object from a channel. The transfer fails if it is not done by the deadline.
+pub async fn recv_object(
+    sender: ChannelSender,
+    mut receiver: ChannelReceiver,
+    hash: Hash,
+    deadline: Instant,
+) -> Result<ObjectRef, crate::Error> {
+    log::info!("negotiating nonce");
+    let transfer_cipher =
+        Arc::new(in_time(deadline, NonceMessage::recv_negotiate(&mut receiver, hash)).await?);
+    let stop = StopOnDrop::new(&sender, &transfer_cipher);
+    log::info!("sending limits");
+    let limits = LimitsMessage::ours(&sender);
+    in_time(deadline, limits.send(&sender, &transfer_cipher)).await?;
+    log::info!("receiving object header");
+    let header = in_time(
+        deadline,
+        ObjectMessage::recv(&mut receiver, &transfer_cipher),
+    )
+    .await?;
+    log::info!("receiving data");
+    let object = header
+        .recv_data(&mut receiver, hash, deadline, &limits)
+        .await?;
+    stop.done();
+
+    log::info!("done receiving object");
+
+    Ok(object)
+}
+
+/// Sends an object to a channel.
+pub async fn send_object(
+    sender: &ChannelSender,
+    receiver: &mut ChannelReceiver,
+    object: &ObjectRef,
+) -> Result<(), crate::Error> {
+    object.touch()?;
+
+    let header = ObjectMessage::for_object(object)?;
+
+    log::info!("negotiating nonce");
+    let transfer_cipher = NonceMessage::send_negotiate(sender, *object.hash()).await?;
+    let limits = LimitsMessage::recv_from_receiver(receiver, &transfer_cipher).await;
+    log::info!("sending object header");
+    header.send(sender, &transfer_cipher).await?;
+    log::info!("sending data");
+    if !header
+        .send_data(sender, receiver, &transfer_cipher, object, limits)
+        .await?
+    {
+        return Ok(());
+    }
+
+    log::info!("done sending object");
+
+    if let Err(err) = crate::readership::count_served(object.hash()) {
+        log::warn!("failed to count object {} as served: {err}", object.hash());
+    }
+
+    Ok(())
+}
+
+/// Receive a collection item from a channel. The transfer fails if it is not done by the
+/// deadline.
+///
+/// TODO: make object transfer optional if the receiver perceives that it
+/// already has the object (one simple table lookup, no seqscan here). This is
+/// important as people update their collections often, but keep most of it
+/// intact.
+pub async fn recv_item(
+    sender: ChannelSender,
+    mut receiver: ChannelReceiver,
+    locator_hash: Hash,
+    deadline: Instant,
+) -> Result<ObjectRef, crate::Error> {
+    log::info!("negotiating nonce");
+    let transfer_cipher = Arc::new(
+        in_time(
+            deadline,
+            NonceMessage::recv_negotiate(&mut receiver, locator_hash),
+        )
+        .await?,
+    );
+    let stop = StopOnDrop::new(&sender, &transfer_cipher);
+    log::info!("sending limits");
+    let limits = LimitsMessage::ours(&sender);
+    in_time(deadline, limits.send(&sender, &transfer_cipher)).await?;
+    log::info!("receiving item header");
+    let header = in_time(deadline, ItemMessage::recv(&mut receiver, &transfer_cipher)).await?;
+
+    let object = header.validate(locator_hash)?;
+
+    header.item.insert()?;
+
+    log::info!("receiving data");
+    header
+        .object_header
+        .recv_data(&mut receiver, *object.hash(), deadline, &limits)
+        .await?;
+    stop.done();
+
+    log::info!("done receiving item");
+
+    Ok(object)
+}
+
+/// Sends a collection item to a channel.
+pub async fn send_item(
+    sender: &ChannelSender,
+    receiver: &mut ChannelReceiver,
+    item: CollectionItem,
+) -> Result<(), crate::Error> {
+    let object = item.object()?;
+    let hash = item.locator().hash();
+    let header = ItemMessage::for_item(item)?;
+
+    log::info!("negotiating nonce");
+    let transfer_cipher = NonceMessage::send_negotiate(sender, hash).await?;
+    let limits = LimitsMessage::recv_from_receiver(receiver, &transfer_cipher).await;
+    log::info!("sending item header");
+    header.send(sender, &transfer_cipher).await?;
+    log::info!("sending data");
+    if !header
+        .object_header
+        .send_data(sender, receiver, &transfer_cipher, &object, limits)
+        .await?
+    {
+        return Ok(());
+    }
+
+    log::info!("done sending object");
+
+    if let Err(err) = crate::readership::count_served(object.hash()) {
+        log::warn!("failed to count object {} as served: {err}", object.hash());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tells_late_limits_from_stop() {
+    let cipher = TransferCipher::new(&Hash::rand(), &Hash::rand());
+    let mut limits = bincode::serialize(&LimitsMessage {
+        max_content_size: 42,
+    })
+    .expect("can serialize");
+    let mut stop = bincode::serialize(&StopMessage).expect("can serialize");
+    cipher.encrypt(&mut limits);
+    cipher.encrypt(&mut stop);
+
+    assert!(matches!(
+        ReceiverMessage::decode(limits, &cipher),
+        ReceiverMessage::Limits(LimitsMessage {
+            max_content_size: 42
+        })
+    ));
+    assert!(matches!(
+        ReceiverMessage::decode(stop, &cipher),
+        ReceiverMessage::Stop(StopMessage)
+    ));
+}
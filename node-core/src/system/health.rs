@@ -0,0 +1,239 @@
+//! Tracking of the health of the connections to hubs, based on periodic pings. The changes in
+//! the state of each hub are kept, with why they happened, and an alert is raised (see
+//! [`Event::HubDown`]) when a hub stays down for longer than `--hub-down-alert-after`.
+
+use chrono::{DateTime, Utc};
+use samizdat_common::handshake::Protocol;
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::cli;
+use crate::events::{self, Event};
+
+/// The interval between health probes to each hub.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(15);
+/// For how long to wait for a ping to be answered.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// The weight of a new sample in the smoothed round-trip time, as in TCP (RFC 6298).
+const RTT_SMOOTHING: f64 = 1.0 / 8.0;
+/// For how long to keep sending proofs of work to a hub after it last demanded them.
+const WORK_MEMORY: Duration = Duration::from_secs(60);
+/// How many changes of state are kept for each hub.
+const MAX_HISTORY: usize = 100;
+
+lazy_static::lazy_static! {
+    /// The changes of state of each hub, by hub name.
+    static ref HISTORY: Mutex<BTreeMap<&'static str, HubHistory>> = Mutex::default();
+}
+
+/// Whether a hub can be talked to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HubState {
+    Up,
+    Down,
+}
+
+/// A change in the state of a hub.
+#[derive(Debug, Clone, Serialize)]
+pub struct StateChange {
+    pub at: DateTime<Utc>,
+    pub state: HubState,
+    /// What made the state change.
+    pub reason: String,
+}
+
+/// The changes in the state of a hub, oldest first.
+#[derive(Debug, Default)]
+struct HubHistory {
+    changes: VecDeque<StateChange>,
+    /// Whether the current outage of the hub was already alerted.
+    is_alerted: bool,
+}
+
+/// Records the state of a hub, if it changed.
+pub fn record_state(hub: &'static str, state: HubState, reason: impl Into<String>) {
+    let mut history = HISTORY.lock().expect("poisoned");
+    let history = history.entry(hub).or_default();
+    let last = history.changes.back();
+
+    if last.map(|change| change.state) == Some(state) {
+        return;
+    }
+
+    if state == HubState::Up && history.is_alerted {
+        history.is_alerted = false;
+        events::emit(Event::HubRecovered {
+            hub: hub.to_owned(),
+            down_since: last.map(|change| change.at).unwrap_or_else(Utc::now),
+        });
+    }
+
+    let reason = reason.into();
+    log::info!("{hub} is {state:?}: {reason}");
+
+    if history.changes.len() == MAX_HISTORY {
+        history.changes.pop_front();
+    }
+
+    history.changes.push_back(StateChange {
+        at: Utc::now(),
+        state,
+        reason,
+    });
+}
+
+/// The changes in the state of a hub, most recent first.
+pub fn state_history(hub: &str) -> Vec<StateChange> {
+    HISTORY
+        .lock()
+        .expect("poisoned")
+        .get(hub)
+        .map(|history| history.changes.iter().rev().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Raises an alert if a hub has been down for longer than `--hub-down-alert-after`, once per
+/// outage.
+pub fn check_alert(hub: &'static str) {
+    let threshold =
+        match chrono::Duration::from_std(Duration::from_secs(cli().hub_down_alert_after)) {
+            Ok(threshold) if !threshold.is_zero() => threshold,
+            _ => return,
+        };
+
+    let mut history = HISTORY.lock().expect("poisoned");
+    let Some(history) = history.get_mut(hub) else {
+        return;
+    };
+    let Some(last) = history.changes.back() else {
+        return;
+    };
+
+    if last.state == HubState::Down && !history.is_alerted && Utc::now() - last.at >= threshold {
+        log::warn!("{hub} has been down since {}: {}", last.at, last.reason);
+        events::emit(Event::HubDown {
+            hub: hub.to_owned(),
+            since: last.at,
+            reason: last.reason.clone(),
+        });
+        history.is_alerted = true;
+    }
+}
+
+/// The outcome of the health probes to a hub.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HubHealth {
+    /// The round-trip time measured by the last successful probe.
+    pub last_rtt: Option<Duration>,
+    /// The exponentially smoothed round-trip time.
+    pub smoothed_rtt: Option<Duration>,
+    /// The last time a probe was answered.
+    pub last_seen: Option<DateTime<Utc>>,
+    /// The number of probes that failed since the last successful one.
+    pub failed_probes: usize,
+    /// Until when the hub asked not to be sent queries, because it is overloaded.
+    pub overloaded_until: Option<DateTime<Utc>>,
+    /// The proof of work the hub last demanded from each query.
+    pub work_required: Option<f64>,
+    /// Until when to keep sending proofs of work with each query.
+    pub work_required_until: Option<DateTime<Utc>>,
+}
+
+impl HubHealth {
+    /// Records a successful probe.
+    pub fn observe(&mut self, rtt: Duration) {
+        self.last_rtt = Some(rtt);
+        self.smoothed_rtt = Some(match self.smoothed_rtt {
+            Some(smoothed) => smoothed.mul_f64(1.0 - RTT_SMOOTHING) + rtt.mul_f64(RTT_SMOOTHING),
+            None => rtt,
+        });
+        self.last_seen = Some(Utc::now());
+        self.failed_probes = 0;
+    }
+
+    /// Records a failed probe.
+    pub fn fail(&mut self) {
+        self.failed_probes += 1;
+    }
+
+    /// Records that the hub is overloaded and asked to be left alone for some time.
+    pub fn overload(&mut self, retry_after: Duration) {
+        self.overloaded_until = chrono::Duration::from_std(retry_after)
+            .ok()
+            .map(|retry_after| Utc::now() + retry_after);
+    }
+
+    /// Records that the hub is under stress and demands some work from each query.
+    pub fn demand_work(&mut self, work: f64) {
+        self.work_required = Some(work);
+        self.work_required_until = chrono::Duration::from_std(WORK_MEMORY)
+            .ok()
+            .map(|memory| Utc::now() + memory);
+    }
+
+    /// The work to prove with each query sent to the hub right now, if any.
+    pub fn work(&self) -> Option<f64> {
+        self.work_required.filter(|_| {
+            self.work_required_until
+                .is_some_and(|work_required_until| Utc::now() < work_required_until)
+        })
+    }
+
+    /// Whether the hub answered the last probe sent to it.
+    pub fn is_reachable(&self) -> bool {
+        self.last_seen.is_some() && self.failed_probes == 0
+    }
+
+    /// Whether the hub asked not to be sent queries right now.
+    pub fn is_overloaded(&self) -> bool {
+        self.overloaded_until
+            .is_some_and(|overloaded_until| Utc::now() < overloaded_until)
+    }
+
+    /// A score used to order hubs: the smoothed round-trip time, penalized by failures. Hubs
+    /// that were never probed come last.
+    pub fn score(&self) -> Duration {
+        self.smoothed_rtt
+            .map(|rtt| rtt * (1 + self.failed_probes as u32))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+/// The status of the connection to a hub, as shown in the API.
+#[derive(Debug, Clone, Serialize)]
+pub struct HubStatus {
+    /// The name of the hub, as supplied in the command line.
+    pub name: &'static str,
+    /// The local address through which the hub is connected.
+    pub bind_addr: IpAddr,
+    /// The address of the hub.
+    pub addr: SocketAddr,
+    /// The outcome of the health probes to the hub.
+    pub health: HubHealth,
+    /// Whether the connection to the hub was lost and is being re-established.
+    pub is_reconnecting: bool,
+    /// The protocol version and capabilities of the hub, unless reconnecting.
+    pub protocol: Option<Protocol>,
+    /// Whether the hub is reached through TLS over TCP, because QUIC did not get through,
+    /// unless reconnecting.
+    pub over_tcp: Option<bool>,
+    /// The connections to peers established through this hub.
+    pub peers: Vec<PeerStatus>,
+}
+
+/// The status of the connection to a peer, as shown in the API.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerStatus {
+    /// The address of the peer.
+    pub addr: SocketAddr,
+    /// The current round-trip time estimated by QUIC.
+    pub rtt: Duration,
+    /// What is agreed with the peer so far.
+    pub protocol: Protocol,
+    /// For how long the connection has not been used.
+    pub idle_for: Duration,
+}
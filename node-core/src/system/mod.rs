@@ -0,0 +1,1649 @@
+//! Implementation of the node behavior in the Samizdat network, both with hubs and with
+//! other nodes.
+
+mod advertisement;
+mod blocklist;
+mod file_transfer;
+mod health;
+mod negative_cache;
+mod node_server;
+mod port_mapping;
+mod privacy;
+pub mod queries;
+mod reconnect;
+pub mod routing;
+mod transport;
+
+pub use health::{HubState, HubStatus, StateChange};
+pub use negative_cache::retry_after;
+pub use node_server::receive_announcement;
+pub use port_mapping::{connectivity, run_port_mapping_daemon};
+pub use privacy::run_cover_traffic_daemon;
+pub use reconnect::{exponential_backoff, Reconnect};
+pub use transport::usage::{self, run_usage_daemon};
+
+use futures::prelude::*;
+use futures::stream;
+use samizdat_common::ChannelAddr;
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+use tarpc::client::NewClient;
+use tarpc::context;
+use tarpc::server::{self, Channel};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio::time::{interval, timeout, timeout_at, Duration, MissedTickBehavior};
+
+use samizdat_common::cipher::TransferCipher;
+use samizdat_common::handshake::{Agreement, Capabilities};
+use samizdat_common::keyed_channel::KeyedChannel;
+use samizdat_common::pow::ProofOfWork;
+use samizdat_common::quic;
+use samizdat_common::request_id::{self, Traced};
+use samizdat_common::rpc::*;
+use samizdat_common::tcp_fallback::{self, Role};
+use samizdat_common::{Hash, Riddle};
+
+use crate::cli;
+use crate::db::is_replica;
+use crate::events::{self, Event};
+use crate::models::HubKeyRef;
+use crate::models::Identity;
+use crate::models::IdentityRef;
+use crate::models::{reply_topic, Edition, ObjectRef, SeriesRef};
+
+use self::health::{HubHealth, PROBE_INTERVAL, PROBE_TIMEOUT};
+use self::node_server::NodeServer;
+use self::port_mapping::PortMappingGuard;
+use self::transport::usage::{Counterpart, Source};
+use self::transport::{ChannelManager, ConnectionManager};
+
+/// How much more work than demanded to prove with queries.
+const WORK_MARGIN: f64 = 1.25;
+
+/// A single connection instance, which will be recreated by [`Reconnect`] on connection loss.
+pub struct HubConnectionInner {
+    client: HubClient,
+    /// What was agreed with the hub when connecting.
+    agreement: Agreement,
+    /// Whether the hub is reached through the TCP fallback.
+    over_tcp: bool,
+    // connection_manager: Arc<ConnectionManager>,
+    channel_manager: Arc<ChannelManager>,
+    candidate_channels: KeyedChannel<Candidate>,
+    _port_mapping: PortMappingGuard,
+    _usage: Vec<usage::Tracking>,
+}
+
+/// How a hub is reached.
+#[derive(Debug, Clone, Copy)]
+enum HubLink {
+    /// Through QUIC, with one connection for each RPC channel.
+    Quic {
+        direct_addr: SocketAddr,
+        reverse_addr: SocketAddr,
+    },
+    /// Through TLS over TCP, when QUIC does not get through (see [`tcp_fallback`]).
+    Tcp(SocketAddr),
+}
+
+impl HubConnectionInner {
+    /// Checks the fingerprint of the certificate presented by the hub against the one pinned
+    /// for it (see [`HubKeyRef`]). Resumed sessions present the certificate of the session
+    /// they resume, which was checked back then. Whether the hub keeps its key, and therefore
+    /// whether to pin a new one, is only known once the handshake is over, but pinned keys are
+    /// checked right away.
+    fn check_key(
+        name: &'static str,
+        agreement: &Agreement,
+        fingerprint: Option<Hash>,
+    ) -> Result<(), crate::Error> {
+        let fingerprint =
+            fingerprint.ok_or_else(|| format!("hub {name} presented no certificate"))?;
+        let is_persistent =
+            |agreement: &Agreement| agreement.protocol().supports(Capabilities::PERSISTENT_KEY);
+        HubKeyRef::new(name).check(fingerprint, is_persistent(agreement))?;
+
+        if !agreement.is_over() {
+            let agreement = agreement.clone();
+            tokio::spawn(async move {
+                agreement.agreed().await;
+                if is_persistent(&agreement) {
+                    if let Err(err) = HubKeyRef::new(name).check(fingerprint, true) {
+                        log::error!("failed to pin key of hub {name}: {err}");
+                    }
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Creates the RPC client from the Node to the Hub.
+    fn spawn_client<T>(transport: T) -> (HubClient, oneshot::Receiver<()>)
+    where
+        T: 'static
+            + Send
+            + tarpc::Transport<tarpc::ClientMessage<HubRequest>, tarpc::Response<HubResponse>>,
+    {
+        let (client_reset_trigger, client_reset_recv) = oneshot::channel();
+
+        let uninstrumented_client = HubClient::new(tarpc::client::Config::default(), transport);
+        let client = NewClient {
+            client: uninstrumented_client.client,
+            dispatch: uninstrumented_client.dispatch.inspect(|_| {
+                client_reset_trigger.send(()).ok();
+            }),
+        }
+        .spawn();
+
+        (client, client_reset_recv)
+    }
+
+    /// Spawns the RPC server for the Hub to call the Node.
+    fn spawn_server<T>(
+        transport: T,
+        connection_manager: Arc<ConnectionManager>,
+        candidate_channels: KeyedChannel<Candidate>,
+    ) -> JoinHandle<()>
+    where
+        T: 'static
+            + Send
+            + tarpc::Transport<tarpc::Response<NodeResponse>, tarpc::ClientMessage<NodeRequest>>,
+    {
+        let server_task = server::BaseChannel::with_defaults(transport).execute(Traced(
+            NodeServer {
+                channel_manager: Arc::new(ChannelManager::new(connection_manager)),
+                candidate_channels,
+            }
+            .serve(),
+        ));
+
+        tokio::spawn(server_task)
+    }
+
+    /// Creates the two connections between hub and node: RPC from node to hub and RPC from
+    /// hub to node.
+    async fn connect(
+        name: &'static str,
+        bind_addr: IpAddr,
+        link: HubLink,
+    ) -> Result<(HubConnectionInner, impl Future<Output = ()>), crate::Error> {
+        // Connect and create connection manager. Peers are reached through QUIC even when the
+        // hub is not; maybe they are in friendlier networks:
+        let (endpoint, incoming) = quic::new_default((bind_addr, 0).into());
+        let port_mapping = port_mapping::register(endpoint.local_addr()?.port());
+        let connection_manager = Arc::new(ConnectionManager::new(endpoint, incoming));
+        let channel_manager = Arc::new(ChannelManager::new(connection_manager.clone()));
+        let candidate_channels = KeyedChannel::new();
+        let advertisement = advertisement::advertisement();
+
+        let (client, agreement, usage, client_reset_recv, server_reset_recv) = match link {
+            HubLink::Quic {
+                direct_addr,
+                reverse_addr,
+            } => {
+                let (transport, agreement) = connection_manager
+                    .transport(direct_addr, &advertisement)
+                    .await?;
+                Self::check_key(
+                    name,
+                    &agreement,
+                    quic::peer_fingerprint(transport.connection()),
+                )?;
+                let source = Source::Quic(transport.connection().clone());
+                let direct_usage = usage::track(Counterpart::Hub, direct_addr, source);
+                let (client, client_reset_recv) = Self::spawn_client(transport);
+                let (transport, reverse_agreement) = connection_manager
+                    .transport(reverse_addr, &advertisement)
+                    .await?;
+                Self::check_key(
+                    name,
+                    &reverse_agreement,
+                    quic::peer_fingerprint(transport.connection()),
+                )?;
+                let source = Source::Quic(transport.connection().clone());
+                let reverse_usage = usage::track(Counterpart::Hub, direct_addr, source);
+                let server_reset_recv = Self::spawn_server(
+                    transport,
+                    connection_manager.clone(),
+                    candidate_channels.clone(),
+                );
+
+                let usage = vec![direct_usage, reverse_usage];
+
+                (
+                    client,
+                    agreement,
+                    usage,
+                    client_reset_recv,
+                    server_reset_recv,
+                )
+            }
+            HubLink::Tcp(tcp_addr) => {
+                let direct = tcp_fallback::connect(
+                    bind_addr,
+                    tcp_addr,
+                    Role::Direct,
+                    Capabilities::NONE,
+                    Some(&advertisement),
+                )
+                .await?;
+                let direct_agreement = Agreement::reached(direct.protocol, None);
+                Self::check_key(name, &direct_agreement, direct.fingerprint)?;
+                let pairing = direct
+                    .pairing
+                    .ok_or("hub gave no nonce for the reverse connection")?;
+                let (client, client_reset_recv) = Self::spawn_client(direct.transport);
+                let reverse = tcp_fallback::connect(
+                    bind_addr,
+                    tcp_addr,
+                    Role::Reverse { pairing },
+                    Capabilities::NONE,
+                    Some(&advertisement),
+                )
+                .await?;
+                let reverse_agreement = Agreement::reached(reverse.protocol, None);
+                Self::check_key(name, &reverse_agreement, reverse.fingerprint)?;
+                let direct_usage =
+                    usage::track(Counterpart::Hub, tcp_addr, Source::Tcp(direct.bytes));
+                let reverse_usage =
+                    usage::track(Counterpart::Hub, tcp_addr, Source::Tcp(reverse.bytes));
+                let server_reset_recv = Self::spawn_server(
+                    reverse.transport,
+                    connection_manager.clone(),
+                    candidate_channels.clone(),
+                );
+                log::info!("connected to {name} over TCP at {tcp_addr}");
+
+                let usage = vec![direct_usage, reverse_usage];
+
+                (
+                    client,
+                    direct_agreement,
+                    usage,
+                    client_reset_recv,
+                    server_reset_recv,
+                )
+            }
+        };
+
+        let addr = match link {
+            HubLink::Quic { direct_addr, .. } => direct_addr,
+            HubLink::Tcp(tcp_addr) => tcp_addr,
+        };
+        let reset_trigger = future::select(server_reset_recv, client_reset_recv).map(move |_| {
+            health::record_state(name, HubState::Down, "connection reset");
+            events::emit(Event::HubDisconnected {
+                hub: name.to_owned(),
+                addr,
+            })
+        });
+
+        Ok((
+            HubConnectionInner {
+                client,
+                agreement,
+                over_tcp: matches!(link, HubLink::Tcp(_)),
+                // connection_manager,
+                channel_manager,
+                candidate_channels,
+                _port_mapping: port_mapping,
+                _usage: usage,
+            },
+            reset_trigger,
+        ))
+    }
+
+    /// Connects through QUIC, falling back to TCP when QUIC connections keep timing out.
+    /// Before the first connection, one time out is enough: there is no telling whether the
+    /// hub is just slow and the node cannot start without its hubs. Once connected over TCP,
+    /// each reconnect tries QUIC once again before falling back.
+    async fn connect_with_fallback(
+        name: &'static str,
+        bind_addr: IpAddr,
+        direct_addr: SocketAddr,
+        reverse_addr: SocketAddr,
+        quic_timeouts: Arc<AtomicUsize>,
+    ) -> Result<(HubConnectionInner, impl Future<Output = ()>), crate::Error> {
+        let max_timeouts = cli().quic_timeouts_before_tcp_fallback;
+        let link = HubLink::Quic {
+            direct_addr,
+            reverse_addr,
+        };
+
+        let err = match Self::connect(name, bind_addr, link).await {
+            Ok(connected) => {
+                quic_timeouts.store(0, Ordering::Relaxed);
+                return Ok(connected);
+            }
+            Err(err @ crate::Error::QuicConnectionError(quinn::ConnectionError::TimedOut))
+                if max_timeouts > 0 =>
+            {
+                err
+            }
+            Err(err) => return Err(err),
+        };
+
+        // `usize::MAX` marks that the node never connected.
+        let timeouts = match quic_timeouts.load(Ordering::Relaxed) {
+            usize::MAX => max_timeouts,
+            timeouts => timeouts + 1,
+        };
+        quic_timeouts.store(timeouts, Ordering::Relaxed);
+
+        if timeouts < max_timeouts {
+            return Err(err);
+        }
+
+        log::warn!("QUIC connections to {name} keep timing out; falling back to TCP");
+        let tcp_addr = SocketAddr::new(direct_addr.ip(), cli().hub_tcp_fallback_port);
+        let connected = Self::connect(name, bind_addr, HubLink::Tcp(tcp_addr)).await?;
+        quic_timeouts.store(max_timeouts - 1, Ordering::Relaxed);
+
+        Ok(connected)
+    }
+}
+
+/// A connection to a single node, already resilient to reconnects.
+pub struct HubConnection {
+    name: &'static str,
+    bind_addr: IpAddr,
+    addr: SocketAddr,
+    inner: Reconnect<HubConnectionInner>,
+    health: Mutex<HubHealth>,
+}
+
+impl HubConnection {
+    /// Creates a connection to the hub, from a given local address.
+    pub async fn connect(
+        name: &'static str,
+        bind_addr: IpAddr,
+        direct_addr: SocketAddr,
+        reverse_addr: SocketAddr,
+    ) -> Result<HubConnection, crate::Error> {
+        let quic_timeouts = Arc::new(AtomicUsize::new(usize::MAX));
+
+        Ok(HubConnection {
+            name,
+            bind_addr,
+            addr: direct_addr,
+            health: Mutex::default(),
+            inner: Reconnect::init(
+                move || {
+                    let connect = HubConnectionInner::connect_with_fallback(
+                        name,
+                        bind_addr,
+                        direct_addr,
+                        reverse_addr,
+                        quic_timeouts.clone(),
+                    );
+
+                    async move {
+                        let outcome = connect.await;
+
+                        match &outcome {
+                            Ok((inner, _)) if inner.over_tcp => {
+                                health::record_state(name, HubState::Up, "connected over TCP")
+                            }
+                            Ok(_) => health::record_state(name, HubState::Up, "connected"),
+                            Err(err) => health::record_state(
+                                name,
+                                HubState::Down,
+                                format!("failed to connect: {err}"),
+                            ),
+                        }
+
+                        outcome
+                    }
+                },
+                || {
+                    reconnect::exponential_backoff(
+                        Duration::from_millis(100),
+                        Duration::from_secs(100),
+                    )
+                },
+            )
+            .await?,
+        })
+    }
+
+    /// Pings the hub, recording the round-trip time (or the failure) in the health of the
+    /// connection.
+    pub async fn probe(&self) {
+        let start = Instant::now();
+        // Hubs being reconnected time out as well:
+        let outcome = timeout(PROBE_TIMEOUT, async {
+            let inner = self.inner.get().await;
+            inner.client.ping(request_id::context()).await
+        })
+        .await;
+        let mut health = self.health.lock().expect("poisoned");
+
+        match outcome {
+            Ok(Ok(())) => {
+                health.observe(start.elapsed());
+                health::record_state(self.name, HubState::Up, "answered health probe");
+            }
+            Ok(Err(err)) => {
+                log::warn!("health probe to {} failed: {err}", self.name);
+                health.fail();
+                health::record_state(
+                    self.name,
+                    HubState::Down,
+                    format!("health probe failed: {err}"),
+                );
+            }
+            Err(_) => {
+                log::warn!("health probe to {} timed out", self.name);
+                health.fail();
+                health::record_state(self.name, HubState::Down, "health probe timed out");
+            }
+        }
+
+        health::check_alert(self.name);
+    }
+
+    /// The status of the connection to this hub and of the connections to peers established
+    /// through it. This does not wait for a hub being reconnected, which may take forever.
+    pub async fn status(&self) -> HubStatus {
+        let health = self.health.lock().expect("poisoned").clone();
+        let mut status = HubStatus {
+            name: self.name,
+            bind_addr: self.bind_addr,
+            addr: self.addr,
+            health,
+            is_reconnecting: true,
+            protocol: None,
+            over_tcp: None,
+            peers: vec![],
+        };
+
+        if let Some(inner) = self.inner.try_get() {
+            status.is_reconnecting = false;
+            status.protocol = Some(inner.agreement.protocol());
+            status.over_tcp = Some(inner.over_tcp);
+            status.peers = inner.channel_manager.peer_status().await;
+        }
+
+        status
+    }
+
+    /// Creates the riddles for a query, proving the work the hub demands, if any.
+    async fn make_query(&self, content_hash: Hash, kind: QueryKind, riddles: usize) -> Query {
+        let query = Query {
+            content_riddles: (0..riddles).map(|_| Riddle::new(&content_hash)).collect(),
+            location_riddle: Riddle::new(&content_hash),
+            kind,
+            proof_of_work: None,
+            geo_preference: cli().geo_preference,
+        };
+
+        let work = self.health.lock().expect("poisoned").work();
+
+        if let Some(work) = work {
+            Self::prove(query, work).await
+        } else {
+            query
+        }
+    }
+
+    /// Attaches a proof of work to a query. A bit more work than demanded is done, so that the
+    /// proof still passes if the stress of the hub grows meanwhile.
+    async fn prove(mut query: Query, work: f64) -> Query {
+        let information = query.location_riddle.rand;
+        let proof = tokio::task::spawn_blocking(move || {
+            ProofOfWork::solve(information, work * WORK_MARGIN)
+        })
+        .await
+        .expect("proof of work panicked");
+
+        query.proof_of_work = Some(proof);
+        query
+    }
+
+    /// Keeps proving work with the queries to this hub for a while, as it demanded.
+    fn demand_work(&self, work: f64) {
+        log::warn!("{} is under stress: demanding work {work:.0}", self.name);
+        self.health.lock().expect("poisoned").demand_work(work);
+    }
+
+    /// Gets the context for a request, together with its deadline.
+    fn context_with_deadline() -> (context::Context, Instant) {
+        let context = request_id::context();
+        let request_duration = context
+            .deadline
+            .duration_since(SystemTime::now())
+            .expect("deadline is in the future");
+
+        (context, Instant::now() + request_duration)
+    }
+
+    /// Makes a query to this hub, sending the given number of riddles.
+    pub async fn query(
+        &self,
+        content_hash: Hash,
+        kind: QueryKind,
+        riddles: usize,
+    ) -> Result<ObjectRef, crate::Error> {
+        // Acquire hub connection:
+        let inner = self.inner.get().await;
+
+        // Do the RPC call:
+        let (context, deadline) = Self::context_with_deadline();
+        let query = self.make_query(content_hash, kind, riddles).await;
+        let mut query_response = inner.client.query(context, query.clone()).await?;
+
+        // The hub wants the query to be paid for. Pay and try again:
+        if let QueryResponse::WorkRequired { work } = query_response {
+            self.demand_work(work);
+            query_response = inner
+                .client
+                .query(context, Self::prove(query, work).await)
+                .await?;
+        }
+
+        let outcome = Self::receive(&inner, content_hash, kind, query_response, deadline).await;
+
+        if let Err(crate::Error::Overloaded { retry_after }) = &outcome {
+            self.overload(*retry_after);
+        }
+
+        outcome
+    }
+
+    /// Makes a query to this hub without downloading anything, returning the peers that proved
+    /// to have the content before the query deadline and how long everything took.
+    pub async fn probe_availability(
+        &self,
+        content_hash: Hash,
+        kind: QueryKind,
+        riddles: usize,
+    ) -> Result<Probe, crate::Error> {
+        // Acquire hub connection:
+        let inner = self.inner.get().await;
+
+        // Do the RPC call:
+        let start = Instant::now();
+        let (context, deadline) = Self::context_with_deadline();
+        let query_response = inner
+            .client
+            .query(context, self.make_query(content_hash, kind, riddles).await)
+            .await?;
+
+        let (candidate_channel, _) = match Self::interpret(query_response) {
+            Ok(channel) => channel,
+            Err(crate::Error::Overloaded { retry_after }) => {
+                self.overload(retry_after);
+                return Err(crate::Error::Overloaded { retry_after });
+            }
+            Err(crate::Error::WorkRequired { work }) => {
+                self.demand_work(work);
+                return Err(crate::Error::WorkRequired { work });
+            }
+            Err(err) => return Err(err),
+        };
+        let response_time = start.elapsed();
+
+        // Candidates keep coming until the deadline. The peers still try to send the content
+        // (see `NodeServer::resolve_object`), but this node never expects their channels, so
+        // nothing is downloaded.
+        let mut candidates = inner
+            .candidate_channels
+            .recv_stream(candidate_channel)
+            .filter(move |candidate| future::ready(is_valid_candidate(candidate, &content_hash)));
+        let mut peers = BTreeSet::new();
+        let mut first_candidate_after = None;
+
+        while let Ok(Some(candidate)) = timeout_at(deadline, candidates.next()).await {
+            first_candidate_after.get_or_insert_with(|| start.elapsed());
+            peers.insert(candidate.socket_addr);
+        }
+
+        log::info!(
+            "Probe for {kind:?} {content_hash} in {}: {} candidates",
+            self.name,
+            peers.len()
+        );
+
+        Ok(Probe {
+            peers,
+            response_time,
+            first_candidate_after,
+        })
+    }
+
+    /// Stops sending queries to this hub for a while, as it asked.
+    fn overload(&self, retry_after: Duration) {
+        log::warn!(
+            "{} is overloaded: retrying after {retry_after:?}",
+            self.name
+        );
+        self.health.lock().expect("poisoned").overload(retry_after);
+    }
+
+    /// Makes many queries to this hub in a single round-trip, each with its own number of
+    /// riddles. The outcomes are returned in the same order as the queries. There must be at
+    /// most [`MAX_QUERY_BATCH_SIZE`] queries.
+    pub async fn query_many(
+        &self,
+        queries: &[(Hash, QueryKind, usize)],
+    ) -> Result<Vec<Result<ObjectRef, crate::Error>>, crate::Error> {
+        // Acquire hub connection:
+        let inner = self.inner.get().await;
+
+        // Hubs predating batches would drop the connection on seeing one (and hubs which have
+        // not finished the handshake yet are taken for those):
+        if !inner
+            .agreement
+            .protocol()
+            .supports(Capabilities::QUERY_BATCHES)
+        {
+            drop(inner);
+            return Ok(self.query_one_by_one(queries).await);
+        }
+
+        // Do the RPC call:
+        let mut made_queries = Vec::with_capacity(queries.len());
+
+        for &(content_hash, kind, riddles) in queries {
+            made_queries.push(self.make_query(content_hash, kind, riddles).await);
+        }
+
+        let (context, deadline) = Self::context_with_deadline();
+        let query_responses = match inner.client.query_many(context, made_queries).await {
+            Ok(query_responses) => query_responses,
+            Err(err) => {
+                log::warn!(
+                    "{} failed a batch of queries ({err}); querying one by one",
+                    self.name
+                );
+                drop(inner);
+                return Ok(self.query_one_by_one(queries).await);
+            }
+        };
+
+        let mut query_responses = query_responses.into_iter();
+        let inner = &*inner;
+        let outcomes = queries.iter().map(|&(content_hash, kind, _)| {
+            let query_response = query_responses.next();
+            async move {
+                match query_response {
+                    Some(query_response) => {
+                        Self::receive(inner, content_hash, kind, query_response, deadline).await
+                    }
+                    None => Err(crate::Error::PeerMisbehavior(
+                        "hub did not answer all queries in batch".to_owned(),
+                    )),
+                }
+            }
+        });
+
+        let outcomes = future::join_all(outcomes).await;
+
+        if let Some(retry_after) = outcomes.iter().find_map(|outcome| match outcome {
+            Err(crate::Error::Overloaded { retry_after }) => Some(*retry_after),
+            _ => None,
+        }) {
+            self.overload(retry_after);
+        }
+
+        // The queries that were not paid for will be the next time:
+        if let Some(work) = outcomes.iter().find_map(|outcome| match outcome {
+            Err(crate::Error::WorkRequired { work }) => Some(*work),
+            _ => None,
+        }) {
+            self.demand_work(work);
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Makes many queries to this hub, each in its own call, as for hubs that do not take
+    /// batches.
+    async fn query_one_by_one(
+        &self,
+        queries: &[(Hash, QueryKind, usize)],
+    ) -> Vec<Result<ObjectRef, crate::Error>> {
+        let outcomes = queries
+            .iter()
+            .map(|&(content_hash, kind, riddles)| self.query(content_hash, kind, riddles));
+        future::join_all(outcomes).await
+    }
+
+    /// Interprets the response of the hub to a query, giving the candidate channel and the
+    /// channel id to be used with the candidates.
+    fn interpret(query_response: QueryResponse) -> Result<(CandidateChannelId, u32), crate::Error> {
+        match query_response {
+            QueryResponse::Replayed => Err(crate::Error::ValidationFailed(
+                "hub has suspected replay attack".to_owned(),
+            )),
+            QueryResponse::EmptyQuery => Err(crate::Error::ValidationFailed(
+                "hub has received an empty query".to_owned(),
+            )),
+            QueryResponse::NoReverseConnection => {
+                Err("hub said I have no reverse connection".into())
+            }
+            QueryResponse::InternalError => Err("hub has experienced an internal error".into()),
+            QueryResponse::Resolved {
+                candidate_channel,
+                channel_id,
+            } => Ok((candidate_channel, channel_id)),
+            QueryResponse::Overloaded { retry_after } => {
+                Err(crate::Error::Overloaded { retry_after })
+            }
+            QueryResponse::WorkRequired { work } => Err(crate::Error::WorkRequired { work }),
+        }
+    }
+
+    /// Receives the content for a query, given the response from the hub.
+    async fn receive(
+        inner: &HubConnectionInner,
+        content_hash: Hash,
+        kind: QueryKind,
+        query_response: QueryResponse,
+        deadline: Instant,
+    ) -> Result<ObjectRef, crate::Error> {
+        let (candidate_channel, channel_id) = Self::interpret(query_response)?;
+
+        log::info!(
+            "Candidate channel for {}: {:x}",
+            content_hash,
+            candidate_channel
+        );
+
+        // Stream of peer candidates, counting the ones that arrived. Candidates arriving
+        // together are tried lowest round-trip time first, for the peers already connected:
+        let answered = AtomicUsize::new(0);
+        let rtt_channel_manager = inner.channel_manager.clone();
+        let mut candidates = inner
+            .candidate_channels
+            .recv_stream(candidate_channel)
+            .filter(move |candidate| future::ready(is_valid_candidate(candidate, &content_hash)))
+            .ready_chunks(16)
+            .then(move |mut arrived| {
+                let channel_manager = rtt_channel_manager.clone();
+                Box::pin(async move {
+                    let rtts = channel_manager.peer_rtts().await;
+                    arrived.sort_by_key(|candidate| {
+                        rtts.get(&candidate.socket_addr)
+                            .copied()
+                            .unwrap_or(Duration::MAX)
+                    });
+                    stream::iter(arrived)
+                })
+            })
+            .flatten()
+            .map(|candidate| {
+                let channel_addr = ChannelAddr::new(candidate.socket_addr, channel_id);
+                log::info!("Got candidate {channel_addr} for channel {candidate_channel:x}");
+                answered.fetch_add(1, Ordering::Relaxed);
+                queries::candidate_found();
+                let channel_manager = inner.channel_manager.clone();
+                Box::pin(async move {
+                    channel_manager
+                        .expect(channel_addr)
+                        .await
+                        .map_err(|err| {
+                            log::warn!("Hole punching with {channel_addr} failed: {err}")
+                        })
+                        .ok()
+                })
+            })
+            .buffer_unordered(4) // TODO: create CLI knob
+            .filter_map(|done| Box::pin(async move { done }));
+
+        // For each candidate, "do the thing":
+        let outcome = loop {
+            match timeout_at(deadline, candidates.next()).await {
+                Ok(Some((sender, receiver))) => {
+                    let peer_addr = sender.remote_address().peer_addr();
+                    // TODO: minor improvement... could we tee the object stream directly to the
+                    // user? By now, we are waiting for the whole object to arrive, which is fine
+                    // for most files, but can be a pain for the bigger ones...
+                    // The transfer has its own budget, apart from the query deadline:
+                    let transfer_deadline =
+                        Instant::now() + Duration::from_secs(cli().transfer_budget);
+                    let receive_outcome = match kind {
+                        QueryKind::Object => {
+                            file_transfer::recv_object(
+                                sender,
+                                receiver,
+                                content_hash,
+                                transfer_deadline,
+                            )
+                            .await
+                        }
+                        QueryKind::Item => {
+                            file_transfer::recv_item(
+                                sender,
+                                receiver,
+                                content_hash,
+                                transfer_deadline,
+                            )
+                            .await
+                        }
+                    };
+
+                    match receive_outcome {
+                        Ok(outcome) => break Ok(outcome),
+                        // Even failures that would not go away by retrying (e.g., content that
+                        // fails validation) are particular to this candidate:
+                        Err(err) => {
+                            log::warn!(
+                                "Candidate for query {kind:?} {content_hash} failed with: {err}"
+                            );
+
+                            if let crate::Error::PeerMisbehavior(_) = err {
+                                blocklist::strike(peer_addr);
+                            }
+                        }
+                    }
+                }
+                Ok(None) => {
+                    log::info!("Candidate channel {candidate_channel:x} dried");
+                    break Err(if answered.load(Ordering::Relaxed) == 0 {
+                        crate::Error::NoCandidates
+                    } else {
+                        crate::Error::AllCandidatesFailed
+                    });
+                }
+                Err(_) => {
+                    break Err(if answered.load(Ordering::Relaxed) == 0 {
+                        crate::Error::NoCandidates
+                    } else {
+                        crate::Error::Timeout
+                    });
+                }
+            }
+        };
+
+        log::info!("Query done: {kind:?} {content_hash} {outcome:?}");
+
+        outcome
+    }
+
+    /// Tries to resolve the latest edition of a given series.
+    pub async fn get_edition(&self, series: &SeriesRef) -> Result<Option<Edition>, crate::Error> {
+        let key_riddle = Riddle::new(&series.public_key.hash());
+        let inner = self.inner.get().await;
+
+        let response = inner
+            .client
+            .get_edition(request_id::context(), EditionRequest { key_riddle })
+            .await?;
+
+        let mut most_recent: Option<Edition> = None;
+
+        for candidate in response {
+            let cipher = TransferCipher::new(&series.public_key.hash(), &candidate.rand);
+            let candidate_edition: Edition = candidate.series.decrypt_with(&cipher)?;
+
+            if !candidate_edition.is_valid() {
+                log::warn!("received invalid candidate edition: {candidate_edition:?}",);
+                continue;
+            }
+
+            if let Some(most_recent) = most_recent.as_mut() {
+                if candidate_edition.timestamp() > most_recent.timestamp() {
+                    *most_recent = candidate_edition;
+                }
+            } else {
+                most_recent = Some(candidate_edition);
+            }
+        }
+
+        Ok(most_recent)
+    }
+
+    /// Tries to resolve the latest editions of the series replying to an item.
+    pub async fn get_replies(&self, locator: &Hash) -> Result<Vec<Edition>, crate::Error> {
+        let topic = reply_topic(locator);
+        let key_riddle = Riddle::new(&topic);
+        let inner = self.inner.get().await;
+
+        let response = inner
+            .client
+            .get_edition(request_id::context(), EditionRequest { key_riddle })
+            .await?;
+
+        let mut replies = vec![];
+
+        for candidate in response {
+            let cipher = TransferCipher::new(&topic, &candidate.rand);
+            let candidate_edition: Edition = match candidate.series.decrypt_with(&cipher) {
+                Ok(edition) => edition,
+                Err(err) => {
+                    log::warn!("received undecipherable reply: {err}");
+                    continue;
+                }
+            };
+
+            if !candidate_edition.is_valid() {
+                log::warn!("received invalid reply edition: {candidate_edition:?}",);
+                continue;
+            }
+
+            replies.push(candidate_edition);
+        }
+
+        Ok(replies)
+    }
+
+    pub async fn announce_edition(
+        &self,
+        announcement: &EditionAnnouncement,
+    ) -> Result<(), crate::Error> {
+        let inner = self.inner.get().await;
+
+        inner
+            .client
+            .announce_edition(request_id::context(), announcement.clone())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_identity(
+        &self,
+        identity: &IdentityRef,
+    ) -> Result<Option<Identity>, crate::Error> {
+        let identity_riddle = Riddle::new(&identity.hash());
+        let inner = self.inner.get().await;
+
+        let candidates = inner
+            .client
+            .get_identity(request_id::context(), IdentityRequest { identity_riddle })
+            .await?;
+
+        let mut most_worked_on: Option<Identity> = None;
+
+        for candidate in candidates {
+            let cipher = TransferCipher::new(&identity.hash(), &candidate.rand);
+            let candidate_identity: Identity = candidate.identity.decrypt_with(&cipher)?;
+
+            if !candidate_identity.is_valid() || candidate_identity.identity() != identity {
+                log::warn!("received invalid candidate identity: {candidate_identity:?}",);
+                continue;
+            }
+
+            if let Some(most_worked_on) = most_worked_on.as_mut() {
+                if candidate_identity.work_done() > most_worked_on.work_done() {
+                    *most_worked_on = candidate_identity;
+                }
+            } else {
+                most_worked_on = Some(candidate_identity);
+            }
+        }
+
+        Ok(most_worked_on)
+    }
+}
+
+/// What a hub answered to a probe for some content.
+#[derive(Debug)]
+pub struct Probe {
+    /// The peers that proved to have the content before the query deadline.
+    pub peers: BTreeSet<SocketAddr>,
+    /// How long the hub took to answer the query.
+    pub response_time: Duration,
+    /// How long after the query the first peer proved to have the content, if any did.
+    pub first_candidate_after: Option<Duration>,
+}
+
+/// How many peers answered a probe for an object, as seen by a single hub.
+#[derive(Debug, Serialize)]
+pub struct HubAvailability {
+    /// The name of the hub, as supplied in the command line.
+    pub hub: &'static str,
+    /// The number of distinct peers that proved to have the object.
+    pub candidates: usize,
+    /// How long the hub took to answer the query, if it did.
+    pub response_time: Option<Duration>,
+    /// How long after the query the first peer proved to have the object, if any did.
+    pub first_candidate_after: Option<Duration>,
+    /// Why the hub could not be probed, if it could not.
+    pub error: Option<String>,
+}
+
+/// How available an object is in the network.
+#[derive(Debug, Serialize)]
+pub struct Availability {
+    /// The hash of the object.
+    pub content_hash: Hash,
+    /// The number of distinct peers that proved to have the object, across all hubs.
+    pub peers: usize,
+    /// The availability as seen by each hub, by address. Names are not unique.
+    pub hubs: BTreeMap<SocketAddr, HubAvailability>,
+}
+
+/// Whether a candidate is worth hole punching: the peer must not be blocked and must prove
+/// that it knows the content hash. Invalid candidates are only dropped, not struck (see
+/// [`blocklist`]), since their addresses might not be of whoever made them.
+fn is_valid_candidate(candidate: &Candidate, content_hash: &Hash) -> bool {
+    if blocklist::is_blocked(candidate.socket_addr) {
+        log::debug!(
+            "Ignoring candidate from blocked peer {}",
+            candidate.socket_addr
+        );
+        return false;
+    }
+
+    let is_valid = !candidate.validation_riddles.is_empty()
+        && candidate
+            .validation_riddles
+            .iter()
+            .all(|riddle| riddle.resolves(content_hash));
+
+    if !is_valid {
+        log::warn!(
+            "Invalid candidate from {} for {content_hash}",
+            candidate.socket_addr
+        );
+    }
+
+    is_valid
+}
+
+/// Whether a socket bound to a local address can talk to a remote address. The IPv6 wildcard
+/// address is dual-stack and can talk to anything.
+fn can_reach(bind_addr: IpAddr, remote_addr: SocketAddr) -> bool {
+    match bind_addr {
+        IpAddr::V6(ip) if ip.is_unspecified() => true,
+        IpAddr::V6(_) => remote_addr.is_ipv6(),
+        IpAddr::V4(_) => remote_addr.is_ipv4(),
+    }
+}
+
+/// Pairs each hub with every bind address supplied in the command line that can reach it.
+fn with_bind_addresses<I>(addrs: I) -> Vec<(&'static str, IpAddr, SocketAddr)>
+where
+    I: IntoIterator<Item = (&'static str, SocketAddr)>,
+{
+    let mut to_connect = vec![];
+
+    for (name, addr) in addrs {
+        let compatible = cli()
+            .bind_addresses
+            .iter()
+            .copied()
+            .filter(|&bind_addr| can_reach(bind_addr, addr))
+            .collect::<Vec<_>>();
+
+        if compatible.is_empty() {
+            log::warn!("no bind address can reach hub {name} at {addr}");
+        }
+
+        to_connect.extend(
+            compatible
+                .into_iter()
+                .map(|bind_addr| (name, bind_addr, addr)),
+        );
+    }
+
+    to_connect
+}
+
+/// Connects to a hub from a bind address. The reverse connection goes to the next port.
+async fn connect_hub(
+    name: &'static str,
+    bind_addr: IpAddr,
+    addr: SocketAddr,
+) -> Result<Arc<HubConnection>, crate::Error> {
+    let reverse_addr = (addr.ip(), addr.port() + 1).into();
+    HubConnection::connect(name, bind_addr, addr, reverse_addr)
+        .await
+        .map(Arc::new)
+}
+
+/// Connects to a hub from a bind address, only logging failures.
+async fn try_connect_hub(
+    name: &'static str,
+    bind_addr: IpAddr,
+    addr: SocketAddr,
+) -> Option<Arc<HubConnection>> {
+    match connect_hub(name, bind_addr, addr).await {
+        Ok(hub) => Some(hub),
+        Err(err) => {
+            log::warn!("failed to connect to hub {name} at {addr}: {err}");
+            None
+        }
+    }
+}
+
+/// Whether this node looks things up in the network. Replicas cannot store what they would
+/// find, so they leave lookups to the primary.
+fn looks_up() -> bool {
+    !is_replica()
+}
+
+/// Keeps only the first connection to each hub. Hubs are connected through each compatible bind
+/// address, but should get each query, announcement or request only once.
+fn one_per_hub(hubs: Vec<Arc<HubConnection>>) -> Vec<Arc<HubConnection>> {
+    let mut seen = BTreeSet::new();
+    hubs.into_iter()
+        .filter(|hub| seen.insert(hub.addr))
+        .collect()
+}
+
+/// Set of all hub connection from this node.
+pub struct Hubs {
+    hubs: RwLock<Vec<Arc<HubConnection>>>,
+    /// Hubs left out on start because their keys changed, to be connected to once the user
+    /// accepts the new keys.
+    left_out: Mutex<Vec<(&'static str, SocketAddr)>>,
+}
+
+impl Hubs {
+    /// Initiates the set of all hub connections. Each hub is connected through each compatible
+    /// bind address supplied in the command line.
+    pub async fn init<I>(addrs: I) -> Result<Hubs, crate::Error>
+    where
+        I: IntoIterator<Item = (&'static str, SocketAddr)>,
+    {
+        let outcomes = stream::iter(with_bind_addresses(addrs))
+            .map(|(name, bind_addr, addr)| async move {
+                (name, addr, connect_hub(name, bind_addr, addr).await)
+            })
+            .buffer_unordered(10) // 'cause 10!
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut hubs = vec![];
+        let mut left_out = vec![];
+
+        for (name, addr, outcome) in outcomes {
+            match outcome {
+                Ok(hub) => hubs.push(hub),
+                // Not worth failing the node for:
+                Err(err @ crate::Error::HubKeyChanged { .. }) => {
+                    log::error!("{err}");
+
+                    if !left_out.contains(&(name, addr)) {
+                        left_out.push((name, addr));
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(Hubs {
+            hubs: RwLock::new(hubs),
+            left_out: Mutex::new(left_out),
+        })
+    }
+
+    /// Connects to a hub left out on start because its key changed, after the user accepted
+    /// the new key or forgot the old one. Hubs that are already connected pick up the new key on their own, when
+    /// they reconnect.
+    pub async fn connect_left_out(&self, name: &str) {
+        let to_connect = {
+            let mut left_out = self.left_out.lock().expect("poisoned");
+            let (to_connect, rest) = left_out.drain(..).partition(|&(hub, _)| hub == name);
+            *left_out = rest;
+            to_connect
+        };
+
+        self.add(to_connect).await;
+    }
+
+    /// A snapshot of the current hub connections.
+    fn all(&self) -> Vec<Arc<HubConnection>> {
+        self.hubs.read().expect("poisoned").clone()
+    }
+
+    /// The addresses of all hubs currently connected.
+    pub fn addrs(&self) -> BTreeSet<SocketAddr> {
+        self.all().iter().map(|hub| hub.addr).collect()
+    }
+
+    /// Connects to more hubs, skipping the ones already connected. Unlike in [`Hubs::init`],
+    /// hubs that cannot be connected to are only logged. Returns the addresses of the hubs
+    /// connected.
+    pub async fn add<I>(&self, addrs: I) -> BTreeSet<SocketAddr>
+    where
+        I: IntoIterator<Item = (&'static str, SocketAddr)>,
+    {
+        let connected = self.addrs();
+        let to_connect = addrs
+            .into_iter()
+            .filter(|(_, addr)| !connected.contains(addr));
+
+        let connecting = with_bind_addresses(to_connect)
+            .into_iter()
+            .map(|(name, bind_addr, addr)| try_connect_hub(name, bind_addr, addr))
+            .collect::<Vec<_>>();
+        let new = future::join_all(connecting)
+            .await
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let added = new.iter().map(|hub| hub.addr).collect();
+        self.hubs.write().expect("poisoned").extend(new);
+
+        added
+    }
+
+    /// Disconnects from the hubs at the given addresses.
+    pub fn remove(&self, addrs: &BTreeSet<SocketAddr>) {
+        self.hubs
+            .write()
+            .expect("poisoned")
+            .retain(|hub| !addrs.contains(&hub.addr));
+    }
+
+    /// The hubs that are not overloaded, ordered from the healthiest to the least healthy,
+    /// through the healthiest connection to each one (see [`one_per_hub`]).
+    fn by_health(&self) -> Vec<Arc<HubConnection>> {
+        let mut hubs = self.available();
+        hubs.sort_by_cached_key(|hub| hub.health.lock().expect("poisoned").score());
+        one_per_hub(hubs)
+    }
+
+    /// Whether any hub answered its last health probe.
+    pub fn is_any_reachable(&self) -> bool {
+        self.all()
+            .iter()
+            .any(|hub| hub.health.lock().expect("poisoned").is_reachable())
+    }
+
+    /// The hubs the current task may talk to, according to its route (see [`routing`]).
+    fn routed(&self) -> Vec<Arc<HubConnection>> {
+        self.all()
+            .into_iter()
+            .filter(|hub| routing::allows(hub.name))
+            .collect()
+    }
+
+    /// The hubs that did not ask to be left alone because they are overloaded.
+    fn available(&self) -> Vec<Arc<HubConnection>> {
+        self.routed()
+            .into_iter()
+            .filter(|hub| !hub.health.lock().expect("poisoned").is_overloaded())
+            .collect()
+    }
+
+    /// Probes the health of all hubs periodically, forever.
+    pub async fn run_health_probes(&self) {
+        let mut ticker = interval(PROBE_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            crate::lifecycle::wait_until_active().await;
+            stream::iter(self.all())
+                .for_each_concurrent(None, |hub| async move { hub.probe().await })
+                .await;
+        }
+    }
+
+    /// The changes in the state of a hub, most recent first, if there is such a hub.
+    pub fn state_history(&self, name: &str) -> Option<Vec<StateChange>> {
+        self.all()
+            .iter()
+            .any(|hub| hub.name == name)
+            .then(|| health::state_history(name))
+    }
+
+    /// The status of the connections to all hubs.
+    pub async fn status(&self) -> Vec<HubStatus> {
+        stream::iter(self.all())
+            .then(|hub| async move { hub.status().await })
+            .collect()
+            .await
+    }
+
+    /// Makes a query to all inscribed hubs, using the default number of riddles for the kind of
+    /// query.
+    pub async fn query(&self, content_hash: Hash, kind: QueryKind) -> Option<ObjectRef> {
+        self.query_with(content_hash, kind, None).await
+    }
+
+    /// Makes a query to all inscribed hubs, optionally requesting a number of riddles. The
+    /// number of riddles actually sent may be reduced by the privacy budget.
+    pub async fn query_with(
+        &self,
+        content_hash: Hash,
+        kind: QueryKind,
+        riddles: Option<usize>,
+    ) -> Option<ObjectRef> {
+        if !looks_up() {
+            return None;
+        }
+
+        if let Some(retry_after) = negative_cache::retry_after(&content_hash) {
+            log::debug!(
+                "{kind:?} {content_hash} recently not found; retrying after {retry_after:?}"
+            );
+            return None;
+        }
+
+        let riddles = privacy::riddles_for(kind, riddles);
+        let hubs = self.by_health();
+        let hub_count = hubs.len();
+
+        queries::track(content_hash, kind, async move {
+            let mut misses = 0;
+            let mut results = stream::iter(hubs)
+                .map(|hub| async move {
+                    log::debug!("Querying {} for {kind:?} {content_hash}", hub.name);
+                    (hub.name, hub.query(content_hash, kind, riddles).await)
+                })
+                .buffer_unordered(cli().max_parallel_hubs);
+
+            while let Some((hub_name, result)) = results.next().await {
+                match result {
+                    Ok(found) => {
+                        events::emit(Event::ObjectDownloaded {
+                            object: found.hash().to_string(),
+                        });
+                        return Some(found);
+                    }
+                    Err(err) => {
+                        if negative_cache::is_miss(&err) {
+                            misses += 1;
+                        }
+
+                        log::error!("Error while querying {}: {}", hub_name, err)
+                    }
+                }
+            }
+
+            // Only remember content as not found if every hub looked for it:
+            if hub_count > 0 && misses == hub_count {
+                negative_cache::insert(content_hash);
+            }
+
+            None
+        })
+        .await
+    }
+
+    /// Makes many queries to the inscribed hubs, using the default number of riddles for each
+    /// kind of query. Queries are sent in batches of at most [`MAX_QUERY_BATCH_SIZE`] and
+    /// whatever one hub cannot resolve is asked to the next one. The outcomes are returned in the
+    /// same order as the queries.
+    pub async fn query_many(&self, queries: &[(Hash, QueryKind)]) -> Vec<Option<ObjectRef>> {
+        if !looks_up() {
+            return queries.iter().map(|_| None).collect();
+        }
+
+        let queries = queries
+            .iter()
+            .map(|&(content_hash, kind)| (content_hash, kind, privacy::riddles_for(kind, None)))
+            .collect::<Vec<_>>();
+        let mut found = vec![None; queries.len()];
+        let skipped = queries
+            .iter()
+            .map(|(content_hash, _, _)| negative_cache::retry_after(content_hash).is_some())
+            .collect::<Vec<_>>();
+        let hubs = self.by_health();
+        let mut misses = vec![0; queries.len()];
+
+        for hub in &hubs {
+            let pending = (0..queries.len())
+                .filter(|&i| found[i].is_none() && !skipped[i])
+                .collect::<Vec<_>>();
+
+            if pending.is_empty() {
+                break;
+            }
+
+            log::debug!("Querying {} for {} items in batch", hub.name, pending.len());
+
+            let outcomes = stream::iter(pending.chunks(MAX_QUERY_BATCH_SIZE).map(<[_]>::to_vec))
+                .map(|batch| {
+                    let batch_queries = batch.iter().map(|&i| queries[i]).collect::<Vec<_>>();
+                    async move { (batch, hub.query_many(&batch_queries).await) }
+                })
+                .buffer_unordered(cli().max_parallel_hubs)
+                .collect::<Vec<_>>()
+                .await;
+
+            for (batch, outcome) in outcomes {
+                let outcome = match outcome {
+                    Ok(outcome) => outcome,
+                    Err(err) => {
+                        log::error!("Error while querying {} in batch: {}", hub.name, err);
+                        continue;
+                    }
+                };
+
+                for (&i, result) in batch.iter().zip(outcome) {
+                    match result {
+                        Ok(object) => {
+                            events::emit(Event::ObjectDownloaded {
+                                object: object.hash().to_string(),
+                            });
+                            found[i] = Some(object);
+                        }
+                        Err(err) => {
+                            if negative_cache::is_miss(&err) {
+                                misses[i] += 1;
+                            }
+
+                            log::debug!("Error while querying {}: {}", hub.name, err)
+                        }
+                    }
+                }
+            }
+        }
+
+        for (i, &(content_hash, _, _)) in queries.iter().enumerate() {
+            if !hubs.is_empty() && misses[i] == hubs.len() {
+                negative_cache::insert(content_hash);
+            }
+        }
+
+        found
+    }
+
+    /// Estimates how available an object is in the network, by asking all hubs for it without
+    /// downloading anything and counting the peers that answered.
+    pub async fn availability(&self, content_hash: Hash) -> Availability {
+        let riddles = privacy::riddles_for(QueryKind::Object, None);
+        let outcomes = stream::iter(self.by_health())
+            .map(|hub| async move {
+                let outcome = hub
+                    .probe_availability(content_hash, QueryKind::Object, riddles)
+                    .await;
+                (hub.name, hub.addr, outcome)
+            })
+            .buffer_unordered(cli().max_parallel_hubs)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut all_peers = BTreeSet::new();
+        let mut hubs = BTreeMap::new();
+
+        for (hub_name, hub_addr, outcome) in outcomes {
+            let hub_availability = match outcome {
+                Ok(probe) => {
+                    let candidates = probe.peers.len();
+                    all_peers.extend(probe.peers);
+                    HubAvailability {
+                        hub: hub_name,
+                        candidates,
+                        response_time: Some(probe.response_time),
+                        first_candidate_after: probe.first_candidate_after,
+                        error: None,
+                    }
+                }
+                Err(err) => {
+                    log::warn!("Error while probing {hub_name} for {content_hash}: {err}");
+                    HubAvailability {
+                        hub: hub_name,
+                        candidates: 0,
+                        response_time: None,
+                        first_candidate_after: None,
+                        error: Some(err.to_string()),
+                    }
+                }
+            };
+
+            hubs.insert(hub_addr, hub_availability);
+        }
+
+        Availability {
+            content_hash,
+            peers: all_peers.len(),
+            hubs,
+        }
+    }
+
+    /// Makes a dummy query for a random hash to all inscribed hubs. This is used as cover
+    /// traffic and is never expected to succeed.
+    pub async fn cover_query(&self, kind: QueryKind) {
+        let content_hash = Hash::rand();
+        let riddles = privacy::cover_riddles(kind);
+
+        stream::iter(self.by_health())
+            .map(|hub| async move {
+                if let Ok(found) = hub.query(content_hash, kind, riddles).await {
+                    log::warn!("dummy query for {kind:?} {content_hash} found {found:?}");
+                }
+            })
+            .buffer_unordered(cli().max_parallel_hubs)
+            .collect::<()>()
+            .await
+    }
+
+    /// Tries to resolve the latest edition of a given series, through the hubs of its route.
+    pub async fn get_latest(&self, series: &SeriesRef) -> Option<Edition> {
+        routing::scope(series, self.get_latest_routed(series)).await
+    }
+
+    async fn get_latest_routed(&self, series: &SeriesRef) -> Option<Edition> {
+        if !looks_up() {
+            return None;
+        }
+
+        let mut results = stream::iter(one_per_hub(self.routed()))
+            .map(|hub| async move {
+                log::debug!("Querying {} for latest edition of {series}", hub.name);
+                (hub.name, hub.get_edition(series).await)
+            })
+            .buffer_unordered(cli().max_parallel_hubs);
+
+        // Even though we should have to go through *aaaaaaall* the hubs to get the best answer, we
+        // can wait for changes to propagate eventually.
+        // In other words, this might be inaccurate, but it is faster.
+        while let Some((hub_name, result)) = results.next().await {
+            match result {
+                Ok(Some(found)) => return Some(found),
+                Ok(None) => {
+                    log::debug!("got no result from {}", hub_name)
+                }
+                Err(err) => {
+                    log::error!("Error while querying {hub_name}: {err}")
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Finds the latest editions of the series replying to an item, in all hubs, at most one
+    /// per series.
+    pub async fn get_replies(&self, locator: &Hash) -> Vec<Edition> {
+        if !looks_up() {
+            return vec![];
+        }
+
+        let mut results = stream::iter(one_per_hub(self.all()))
+            .map(|hub| async move {
+                log::debug!("Querying {} for replies to {locator}", hub.name);
+                (hub.name, hub.get_replies(locator).await)
+            })
+            .buffer_unordered(cli().max_parallel_hubs);
+
+        let mut latest = BTreeMap::<Vec<u8>, Edition>::new();
+
+        while let Some((hub_name, result)) = results.next().await {
+            match result {
+                Ok(found) => {
+                    for edition in found {
+                        let key = edition.public_key().as_bytes().to_vec();
+                        if latest
+                            .get(&key)
+                            .map(|known| known.timestamp() < edition.timestamp())
+                            .unwrap_or(true)
+                        {
+                            latest.insert(key, edition);
+                        }
+                    }
+                }
+                Err(err) => {
+                    log::error!("Error while querying {hub_name}: {err}")
+                }
+            }
+        }
+
+        latest.into_values().collect()
+    }
+
+    /// Announces an edition to all hubs the current task may talk to. Run this in the
+    /// [`routing::scope`] of the series of the edition.
+    pub async fn announce_edition(&self, announcement: &EditionAnnouncement) {
+        let mut results = stream::iter(one_per_hub(self.routed()))
+            .map(|hub| async move {
+                log::debug!("Announcing {announcement:?} to {}", hub.name);
+                (hub.name, hub.announce_edition(announcement).await)
+            })
+            .buffer_unordered(cli().max_parallel_hubs);
+
+        while let Some((hub_name, result)) = results.next().await {
+            match result {
+                Ok(_) => {}
+                Err(err) => {
+                    log::error!("Error while querying {hub_name}: {err}")
+                }
+            }
+        }
+    }
+
+    pub async fn get_identity(&self, identity: &IdentityRef) -> Option<Identity> {
+        if !looks_up() {
+            return None;
+        }
+
+        log::info!("HERE!");
+
+        let mut results = stream::iter(one_per_hub(self.all()))
+            .map(|hub| async move {
+                log::debug!("Querying {} for identity {identity}", hub.name);
+                (hub.name, hub.get_identity(identity).await)
+            })
+            .buffer_unordered(cli().max_parallel_hubs);
+
+        let mut most_worked_on: Option<Identity> = None;
+
+        // Here, we need to go through *aaaaaall* the hubs to find the best match.
+        // In other words, this *must* be correct. Let's not cut any corners here.
+        while let Some((hub_name, result)) = results.next().await {
+            match result {
+                Ok(Some(found)) => {
+                    if let Some(most_worked_on) = most_worked_on.as_mut() {
+                        if found.work_done() > most_worked_on.work_done() {
+                            *most_worked_on = found;
+                        }
+                    } else {
+                        most_worked_on = Some(found);
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    log::error!("Error while querying {hub_name}: {err}")
+                }
+            }
+        }
+
+        most_worked_on
+    }
+}
@@ -0,0 +1,70 @@
+//! A small cache of content that nobody in the network has. Dead links and typos would
+//! otherwise cost a whole query deadline each time they are visited. Only queries for which no
+//! hub sent any candidate are remembered, for `--negative-cache-ttl` seconds. Candidates that
+//! failed or were too slow say nothing about the content being missing.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+use samizdat_common::Hash;
+
+use crate::cli;
+
+/// The maximum number of hashes remembered. When full, the ones expiring soonest go first.
+const MAX_ENTRIES: usize = 10_000;
+
+lazy_static::lazy_static! {
+    /// The hashes not found in the network, with when they may be queried again.
+    static ref NOT_FOUND: Mutex<BTreeMap<Hash, Instant>> = Mutex::default();
+}
+
+/// Whether a query failed in a way that means the content was not found, i.e., no peer
+/// answered, as opposed to the query not going through or to the peers that answered failing.
+pub(super) fn is_miss(error: &crate::Error) -> bool {
+    matches!(error, crate::Error::NoCandidates)
+}
+
+/// Remembers that nobody in the network has some content, unless the cache is disabled.
+pub(super) fn insert(content_hash: Hash) {
+    let ttl = Duration::from_secs(cli().negative_cache_ttl);
+
+    if ttl.is_zero() {
+        return;
+    }
+
+    let now = Instant::now();
+    let mut not_found = NOT_FOUND.lock().expect("poisoned");
+
+    if not_found.len() >= MAX_ENTRIES {
+        not_found.retain(|_, &mut retry_at| retry_at > now);
+    }
+
+    if not_found.len() >= MAX_ENTRIES {
+        if let Some(soonest) = not_found
+            .iter()
+            .min_by_key(|(_, &retry_at)| retry_at)
+            .map(|(&hash, _)| hash)
+        {
+            not_found.remove(&soonest);
+        }
+    }
+
+    log::debug!("{content_hash} not found in the network; not asking again for {ttl:?}");
+    not_found.insert(content_hash, now + ttl);
+}
+
+/// How long until some content, recently not found in the network, may be queried again, if
+/// it is in the cache.
+pub fn retry_after(content_hash: &Hash) -> Option<Duration> {
+    let mut not_found = NOT_FOUND.lock().expect("poisoned");
+    let retry_at = *not_found.get(content_hash)?;
+    let now = Instant::now();
+
+    if retry_at > now {
+        Some(retry_at - now)
+    } else {
+        not_found.remove(content_hash);
+        None
+    }
+}
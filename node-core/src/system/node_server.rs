@@ -1,19 +1,148 @@
 //! RPC implementation for the Node. This RPC is called by the hubs to trigger object resolution.
 
-use futures::prelude::*;
-use std::sync::Arc;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
 use tarpc::context;
 
+use samizdat_common::bloom::RotatingBloomFilter;
 use samizdat_common::cipher::TransferCipher;
 use samizdat_common::keyed_channel::KeyedChannel;
 use samizdat_common::rpc::*;
 use samizdat_common::{ChannelAddr, Hash, Riddle};
 
-use crate::models::{CollectionItem, Edition, Identity, ObjectRef, SeriesRef, SubscriptionRef};
+use crate::db::is_replica;
+use crate::events::{self, Event};
+use crate::models::{
+    CollectionItem, Edition, Identity, ObjectRef, Reply, SeriesRef, SubscriptionRef,
+};
 
 use super::file_transfer;
 use super::transport::ChannelManager;
 
+/// The number of recent announcements and editions remembered, to drop repeated ones.
+const ANNOUNCEMENT_DEDUP_CAPACITY: usize = 10_000;
+/// The rate at which new announcements are mistaken for repeated ones.
+const ANNOUNCEMENT_DEDUP_ERROR_RATE: f64 = 1e-6;
+/// The maximum number of replies to an item sent in a single response.
+const MAX_REPLIES_PER_RESPONSE: usize = 32;
+
+lazy_static::lazy_static! {
+    /// The announcements and the editions received recently.
+    static ref RECENT_ANNOUNCEMENTS: Mutex<RotatingBloomFilter> = Mutex::new(
+        RotatingBloomFilter::new(ANNOUNCEMENT_DEDUP_CAPACITY, ANNOUNCEMENT_DEDUP_ERROR_RATE)
+    );
+    /// The announcements and the editions being received right now.
+    static ref RECEIVING: Mutex<BTreeSet<Hash>> = Mutex::default();
+}
+
+/// An announcement or an edition being received. Copies arriving meanwhile are dropped, but it
+/// is only remembered as received once [`Receiving::done`] is called. If it is dropped before
+/// that (e.g., the refresh failed), the next copy is acted upon again.
+struct Receiving(Hash);
+
+impl Receiving {
+    /// Starts receiving something, unless it was received recently or is being received now.
+    fn start(hash: Hash) -> Option<Receiving> {
+        if RECENT_ANNOUNCEMENTS
+            .lock()
+            .expect("poisoned")
+            .contains(&hash)
+        {
+            return None;
+        }
+
+        RECEIVING
+            .lock()
+            .expect("poisoned")
+            .insert(hash)
+            .then_some(Receiving(hash))
+    }
+
+    /// Remembers that this was received.
+    fn done(self) {
+        RECENT_ANNOUNCEMENTS
+            .lock()
+            .expect("poisoned")
+            .insert(&self.0);
+    }
+}
+
+impl Drop for Receiving {
+    fn drop(&mut self) {
+        RECEIVING.lock().expect("poisoned").remove(&self.0);
+    }
+}
+
+/// Refreshes the subscription an edition announcement refers to, if this node is subscribed
+/// to its series. Announcements come from the hubs and from other rendezvous paths, such as
+/// Nostr relays. The same announcement, or the same edition in another announcement, often
+/// arrives through many of these paths; copies are dropped while one is acted upon and after it
+/// was acted upon successfully.
+pub fn receive_announcement(announcement: Arc<EditionAnnouncement>) {
+    // Refreshing series is up to the primary:
+    if is_replica() {
+        return;
+    }
+
+    let receiving = if let Some(receiving) = Receiving::start(announcement.hash()) {
+        receiving
+    } else {
+        log::debug!("dropping repeated edition announcement");
+        return;
+    };
+
+    if let Some(subscription) = SubscriptionRef::find(&announcement.key_riddle) {
+        let cipher = TransferCipher::new(&subscription.public_key.hash(), &announcement.rand);
+        let key = format!("refreshing {}", subscription.public_key);
+
+        let try_refresh = async move {
+            let edition: Edition = announcement.edition.clone().decrypt_with(&cipher)?;
+
+            // Anyone who knows the series can make an announcement, so check who signed it:
+            if !edition.is_valid() || edition.public_key() != &subscription.public_key {
+                log::warn!("an invalid edition was announced: {:?}", edition);
+                return Ok(());
+            }
+
+            let receiving_edition = if let Some(receiving) = Receiving::start(edition.hash()) {
+                receiving
+            } else {
+                log::debug!("dropping repeated announcement of {edition:?}");
+                return Ok(());
+            };
+
+            events::emit(Event::EditionReceived {
+                series: edition.public_key().to_string(),
+                collection: edition.collection().hash().to_string(),
+                timestamp: edition.timestamp(),
+            });
+
+            if subscription.must_refresh()? {
+                subscription.refresh(edition).await?;
+            }
+
+            receiving_edition.done();
+
+            Ok::<_, crate::Error>(())
+        };
+
+        // Refreshing can wait for the node not to be quiet:
+        crate::lifecycle::unless_quiet(key, move || {
+            crate::tasks::spawn("refresh announced edition", async move {
+                // Sleep a random amount so as not for everybody to ask for the same items at
+                // the same time.
+                tokio::time::sleep(std::time::Duration::from_secs_f32(rand::random())).await;
+                try_refresh.await?;
+                receiving.done();
+
+                Ok(())
+            });
+        });
+    } else {
+        receiving.done();
+    }
+}
+
 #[derive(Clone)]
 pub struct NodeServer {
     pub channel_manager: Arc<ChannelManager>,
@@ -41,6 +170,11 @@ impl NodeServer {
             }
         };
 
+        if let Err(err) = crate::content_filter::check_object(&object) {
+            log::info!("not seeding object: {err}");
+            return ResolutionResponse::NotFound;
+        }
+
         let hash = *object.hash();
 
         log::info!("Found hash {}", hash);
@@ -57,17 +191,11 @@ impl NodeServer {
 
         log::info!("Found peer at {peer_addr}");
 
-        tokio::spawn(
-            async move {
-                log::info!("Starting task to transfer object {} to {}", hash, peer_addr);
-                let (sender, _receiver) = self.channel_manager.initiate(peer_addr).await?;
-                file_transfer::send_object(&sender, &object).await
-            }
-            .map(move |outcome| {
-                outcome
-                    .map_err(|err| log::error!("Failed to send {} to {}: {}", hash, peer_addr, err))
-            }),
-        );
+        crate::tasks::spawn(format!("send object {hash} to {peer_addr}"), async move {
+            log::info!("Starting task to transfer object {} to {}", hash, peer_addr);
+            let (sender, mut receiver) = self.channel_manager.initiate(peer_addr).await?;
+            file_transfer::send_object(&sender, &mut receiver, &object).await
+        });
 
         ResolutionResponse::Found(
             resolution
@@ -102,6 +230,11 @@ impl NodeServer {
             }
         };
 
+        if let Err(err) = crate::content_filter::check_local(&item.collection) {
+            log::info!("not seeding item: {err}");
+            return ResolutionResponse::NotFound;
+        }
+
         // Code smell?
         let hash = item.locator().hash();
 
@@ -119,16 +252,10 @@ impl NodeServer {
 
         log::info!("found peer at {}", peer_addr);
 
-        tokio::spawn(
-            async move {
-                let (sender, _receiver) = self.channel_manager.initiate(peer_addr).await?;
-                file_transfer::send_item(&sender, item).await
-            }
-            .map(move |outcome| {
-                outcome
-                    .map_err(|err| log::error!("failed to send {} to {}: {}", hash, peer_addr, err))
-            }),
-        );
+        crate::tasks::spawn(format!("send item {hash} to {peer_addr}"), async move {
+            let (sender, mut receiver) = self.channel_manager.initiate(peer_addr).await?;
+            file_transfer::send_item(&sender, &mut receiver, item).await
+        });
 
         ResolutionResponse::Found(
             resolution
@@ -143,6 +270,12 @@ impl NodeServer {
 #[tarpc::server]
 impl Node for NodeServer {
     async fn resolve(self, _: context::Context, resolution: Arc<Resolution>) -> ResolutionResponse {
+        // Quiet nodes do not seed:
+        if crate::lifecycle::is_quiet() {
+            log::info!("node is quiet; not resolving {resolution:?}");
+            return ResolutionResponse::NotFound;
+        }
+
         match resolution.kind {
             QueryKind::Object => self.resolve_object(resolution).await,
             QueryKind::Item => self.resolve_item(resolution).await,
@@ -195,38 +328,43 @@ impl Node for NodeServer {
             log::info!("Edition not found");
         }
 
-        maybe_response.into_iter().collect()
+        // The riddle may also be for the replies to an item:
+        let replies = match Reply::find(&latest.key_riddle) {
+            Ok(replies) => replies,
+            Err(err) => {
+                log::warn!("{}", err);
+                vec![]
+            }
+        };
+        let reply_responses = replies
+            .iter()
+            .filter_map(|reply| {
+                let editions = reply.series().get_editions();
+                match editions.as_ref().map(|editions| editions.first()) {
+                    Ok(Some(latest)) if !latest.is_draft() => {
+                        let rand = Hash::rand();
+                        let cipher = TransferCipher::new(&reply.topic(), &rand);
+
+                        Some(EditionResponse {
+                            rand,
+                            series: cipher.encrypt_opaque(latest),
+                        })
+                    }
+                    Ok(_) => None,
+                    Err(err) => {
+                        log::warn!("{}", err);
+                        None
+                    }
+                }
+            })
+            .take(MAX_REPLIES_PER_RESPONSE);
+
+        maybe_response.into_iter().chain(reply_responses).collect()
     }
 
     async fn announce_edition(self, _: context::Context, announcement: Arc<EditionAnnouncement>) {
         log::info!("Got announcement from hub");
-        if let Some(subscription) = SubscriptionRef::find(&announcement.key_riddle) {
-            let cipher = TransferCipher::new(&subscription.public_key.hash(), &announcement.rand);
-
-            let try_refresh = async move {
-                let edition: Edition = announcement.edition.clone().decrypt_with(&cipher)?;
-
-                if !edition.is_valid() {
-                    log::warn!("an invalid edition was announced: {:?}", edition);
-                    return Ok(());
-                }
-
-                if subscription.must_refresh()? {
-                    subscription.refresh(edition).await
-                } else {
-                    Ok(())
-                }
-            };
-
-            tokio::spawn(async move {
-                // Sleep a random amount so as not for everybody to ask for the same items at
-                // the same time.
-                tokio::time::sleep(std::time::Duration::from_secs_f32(rand::random())).await;
-                if let Err(err) = try_refresh.await {
-                    log::warn!("{}", err);
-                }
-            });
-        }
+        receive_announcement(announcement);
     }
 
     async fn get_identity(
@@ -0,0 +1,208 @@
+//! Automatic port mapping in the local router through UPnP IGD. This makes it easier for peers
+//! to reach a node sitting behind a consumer router. Mappings are leased for a limited time and
+//! refreshed periodically; mappings that are not refreshed (e.g., because the endpoint was
+//! dropped on a reconnect) simply expire.
+
+use chrono::{DateTime, Utc};
+use igd_next::aio::tokio::{search_gateway, Tokio};
+use igd_next::aio::Gateway;
+use igd_next::{PortMappingProtocol, SearchOptions};
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::Notify;
+use tokio::time::timeout;
+
+use crate::cli;
+
+/// For how long each mapping is leased from the router.
+const LEASE_DURATION: Duration = Duration::from_secs(1_800);
+/// The interval between refreshes of all mappings.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+/// The description of the mappings, as shown in the router.
+const MAPPING_DESCRIPTION: &str = "samizdat";
+
+lazy_static::lazy_static! {
+    /// The current status of port mapping.
+    static ref CONNECTIVITY: RwLock<Connectivity> = RwLock::default();
+    /// Wakes the daemon up when there is a new port to be mapped.
+    static ref NEW_PORT: Notify = Notify::new();
+}
+
+/// The status of the mapping of a single local port.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PortMapping {
+    /// The external port in the router, if mapped.
+    pub external_port: Option<u16>,
+    /// The last time the mapping was successfully established or refreshed.
+    pub mapped_at: Option<DateTime<Utc>>,
+    /// The error of the last attempt to map this port, if it failed.
+    pub error: Option<String>,
+}
+
+/// The status of port mapping in the local router.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Connectivity {
+    /// Whether port mapping is enabled in the command line.
+    pub is_enabled: bool,
+    /// The address of the router found through UPnP, if any.
+    pub gateway: Option<SocketAddr>,
+    /// The external IP address reported by the router.
+    pub external_ip: Option<IpAddr>,
+    /// The error of the last attempt to find the router, if it failed.
+    pub error: Option<String>,
+    /// The mappings for each local UDP port used by the node.
+    pub mappings: BTreeMap<u16, PortMapping>,
+}
+
+/// Retrieves a snapshot of the current status of port mapping.
+pub fn connectivity() -> Connectivity {
+    let mut connectivity = CONNECTIVITY.read().expect("poisoned").clone();
+    connectivity.is_enabled = cli().port_mapping;
+    connectivity
+}
+
+/// Keeps a local UDP port mapped in the router while alive. When dropped, the mapping is not
+/// refreshed anymore and expires with its lease.
+#[derive(Debug)]
+pub struct PortMappingGuard {
+    local_port: u16,
+}
+
+impl Drop for PortMappingGuard {
+    fn drop(&mut self) {
+        CONNECTIVITY
+            .write()
+            .expect("poisoned")
+            .mappings
+            .remove(&self.local_port);
+    }
+}
+
+/// Asks for a local UDP port to be mapped in the router for as long as the returned guard lives.
+pub fn register(local_port: u16) -> PortMappingGuard {
+    if cli().port_mapping {
+        CONNECTIVITY
+            .write()
+            .expect("poisoned")
+            .mappings
+            .entry(local_port)
+            .or_default();
+        NEW_PORT.notify_one();
+    }
+
+    PortMappingGuard { local_port }
+}
+
+/// Finds the local IPv4 address used to talk to the router.
+async fn local_ip_towards(gateway: SocketAddr) -> Result<IpAddr, crate::Error> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(gateway).await?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Maps (or refreshes the mapping of) a single port, trying to use the same port externally.
+async fn map_port(
+    gateway: &Gateway<Tokio>,
+    local_ip: IpAddr,
+    local_port: u16,
+) -> Result<u16, crate::Error> {
+    let local_addr = SocketAddr::from((local_ip, local_port));
+    let lease = LEASE_DURATION.as_secs() as u32;
+    let same_port = gateway
+        .add_port(
+            PortMappingProtocol::UDP,
+            local_port,
+            local_addr,
+            lease,
+            MAPPING_DESCRIPTION,
+        )
+        .await;
+
+    match same_port {
+        Ok(()) => Ok(local_port),
+        Err(err) => {
+            log::debug!("could not map port {local_port} to itself: {err}");
+            gateway
+                .add_any_port(
+                    PortMappingProtocol::UDP,
+                    local_addr,
+                    lease,
+                    MAPPING_DESCRIPTION,
+                )
+                .await
+                .map_err(|err| crate::Error::from(err.to_string()))
+        }
+    }
+}
+
+/// Finds the router and maps all registered ports.
+async fn refresh() -> Result<(), crate::Error> {
+    let gateway = search_gateway(SearchOptions::default())
+        .await
+        .map_err(|err| err.to_string())?;
+    let local_ip = local_ip_towards(gateway.addr).await?;
+    let external_ip = gateway.get_external_ip().await.ok();
+
+    {
+        let mut connectivity = CONNECTIVITY.write().expect("poisoned");
+        connectivity.gateway = Some(gateway.addr);
+        connectivity.external_ip = external_ip;
+        connectivity.error = None;
+    }
+
+    let local_ports = CONNECTIVITY
+        .read()
+        .expect("poisoned")
+        .mappings
+        .keys()
+        .copied()
+        .collect::<Vec<_>>();
+
+    for local_port in local_ports {
+        let outcome = map_port(&gateway, local_ip, local_port).await;
+        let mut connectivity = CONNECTIVITY.write().expect("poisoned");
+
+        // The port may have been unregistered in the meantime.
+        if let Some(mapping) = connectivity.mappings.get_mut(&local_port) {
+            match outcome {
+                Ok(external_port) => {
+                    log::info!("mapped UDP port {local_port} to external port {external_port}");
+                    mapping.external_port = Some(external_port);
+                    mapping.mapped_at = Some(Utc::now());
+                    mapping.error = None;
+                }
+                Err(err) => {
+                    log::warn!("failed to map UDP port {local_port}: {err}");
+                    mapping.external_port = None;
+                    mapping.error = Some(err.to_string());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps the registered ports mapped in the router, forever. This is a no-op if port mapping
+/// is disabled.
+pub async fn run_port_mapping_daemon() {
+    if !cli().port_mapping {
+        return;
+    }
+
+    loop {
+        if let Err(err) = refresh().await {
+            log::warn!("port mapping failed: {err}");
+            let mut connectivity = CONNECTIVITY.write().expect("poisoned");
+            connectivity.gateway = None;
+            connectivity.error = Some(err.to_string());
+        }
+
+        // Wait for the next refresh or for a new port to be mapped:
+        timeout(REFRESH_INTERVAL, NEW_PORT.notified()).await.ok();
+    }
+}
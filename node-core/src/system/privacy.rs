@@ -0,0 +1,125 @@
+//! Controls how many riddles are sent on each query. More riddles let a query travel more hops
+//! in the network, which makes lookups more likely to succeed, but also exposes the query to
+//! more peers. If this node makes too many queries in a short period of time, its interests
+//! become easier to link together. Therefore, a _privacy budget_ automatically reduces the
+//! number of riddles when the query volume gets too high.
+//!
+//! Optionally, the node can also send _cover traffic_: dummy queries for random hashes, which
+//! make it harder for observers of the hub traffic to infer what content the node actually reads.
+
+use rand::Rng;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+use samizdat_common::rpc::{QueryKind, MAX_QUERY_RIDDLES};
+
+use crate::{cli, hubs};
+
+/// The absolute minimum number of riddles with which a query can get a result.
+const MIN_RIDDLES: usize = 2;
+/// The maximum number of riddles that can be requested for a single query: hubs do not answer
+/// queries with more riddles than this.
+const MAX_RIDDLES: usize = MAX_QUERY_RIDDLES;
+/// The period over which the privacy budget is accounted.
+const BUDGET_WINDOW: Duration = Duration::from_secs(60);
+
+lazy_static::lazy_static! {
+    /// The instants at which the queries in the current budget window were made.
+    static ref RECENT_QUERIES: Mutex<VecDeque<Instant>> = Mutex::default();
+}
+
+/// Counts the queries in the current budget window, optionally recording a new query.
+fn count_queries(record: bool) -> usize {
+    let now = Instant::now();
+    let mut recent = RECENT_QUERIES.lock().expect("poisoned");
+
+    while matches!(recent.front(), Some(&made_at) if now.duration_since(made_at) > BUDGET_WINDOW) {
+        recent.pop_front();
+    }
+
+    if record {
+        recent.push_back(now);
+    }
+
+    recent.len()
+}
+
+/// Reduces the number of riddles if the number of recent queries exceeds the privacy budget.
+fn apply_budget(riddles: usize, recent: usize) -> usize {
+    let budget = cli().privacy_budget;
+
+    if budget == 0 || recent <= budget {
+        return riddles;
+    }
+
+    let reduced = usize::max(MIN_RIDDLES, riddles * budget / recent);
+    log::debug!(
+        "privacy budget exceeded ({recent} queries in the last {}s): using {reduced} riddles \
+         instead of {riddles}",
+        BUDGET_WINDOW.as_secs()
+    );
+
+    reduced
+}
+
+/// The number of riddles to be used in a query of the given kind when none was requested.
+fn default_riddles(kind: QueryKind) -> usize {
+    match kind {
+        QueryKind::Object => cli().riddles_per_query,
+        QueryKind::Item => cli()
+            .riddles_per_item_query
+            .unwrap_or(cli().riddles_per_query),
+    }
+}
+
+/// Records a new query and decides how many riddles it will use, given the (optionally)
+/// requested number of riddles and the privacy budget.
+pub fn riddles_for(kind: QueryKind, requested: Option<usize>) -> usize {
+    let riddles = requested
+        .unwrap_or_else(|| default_riddles(kind))
+        .clamp(MIN_RIDDLES, MAX_RIDDLES);
+
+    apply_budget(riddles, count_queries(true))
+}
+
+/// Decides how many riddles a dummy query will use. Dummy queries look like a real query made
+/// now, but are not accounted in the privacy budget, since they reveal nothing.
+pub fn cover_riddles(kind: QueryKind) -> usize {
+    apply_budget(default_riddles(kind), count_queries(false) + 1)
+}
+
+/// Sends dummy queries forever, with exponentially distributed intervals, so that their timing
+/// is as unpredictable as that of real queries. This is a no-op if cover traffic is disabled.
+pub async fn run_cover_traffic_daemon() {
+    let rate = cli().cover_traffic;
+
+    if rate <= 0.0 {
+        return;
+    }
+
+    log::info!("sending cover traffic at {rate} queries per minute");
+
+    loop {
+        let wait = {
+            let mut rng = rand::thread_rng();
+            // Exponential distribution by inverse transform sampling:
+            -60.0 * (1.0 - rng.gen::<f64>()).ln() / rate
+        };
+        sleep(Duration::from_secs_f64(wait)).await;
+        crate::lifecycle::wait_until_active().await;
+
+        if crate::lifecycle::is_quiet() {
+            continue;
+        }
+
+        let kind = if rand::random() {
+            QueryKind::Object
+        } else {
+            QueryKind::Item
+        };
+
+        tokio::spawn(hubs().cover_query(kind));
+    }
+}
@@ -0,0 +1,149 @@
+//! The queries this node is making to the network right now, with how far along their
+//! downloads are, so that clients can show progress for big content instead of an opaque
+//! wait. The query being made is kept in a task-local, the same way routes are, so that the
+//! transfers made on its behalf can report to it.
+
+use chrono::{DateTime, Utc};
+use futures::Future;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use samizdat_common::rpc::QueryKind;
+use samizdat_common::Hash;
+
+tokio::task_local! {
+    /// The id of the query the current task is making, if any.
+    static CURRENT_QUERY: u64;
+}
+
+lazy_static::lazy_static! {
+    /// The queries being made right now, by id.
+    static ref IN_FLIGHT: Mutex<BTreeMap<u64, InFlightQuery>> = Mutex::default();
+}
+
+/// The id of the next query to be tracked.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A query being made right now.
+#[derive(Debug, Clone, Serialize)]
+pub struct InFlightQuery {
+    /// The hash queried for.
+    pub content_hash: String,
+    pub kind: QueryKind,
+    pub started_at: DateTime<Utc>,
+    /// How many peers said to have the content so far.
+    pub candidates: usize,
+    /// The object being downloaded, if the download has started. For items, this is the
+    /// object of the item.
+    pub object: Option<String>,
+    /// How many bytes of the object were received so far.
+    pub received: usize,
+    /// How many chunks of the object were received so far.
+    pub chunks_received: usize,
+    /// How many bytes the object has, if the download has started.
+    pub total: Option<usize>,
+    /// The average download speed so far.
+    pub bytes_per_sec: f64,
+    /// An estimate of how long until the download is over, in seconds.
+    pub eta_secs: Option<f64>,
+}
+
+/// Removes a query from the registry when it is over, however it ends. A query dropped
+/// before it is done was cancelled, e.g., because the client asking for the content went
+/// away. Everything done on its behalf is dropped with it, and peers sending content are told
+/// to stop.
+struct Tracking {
+    id: u64,
+    is_done: bool,
+}
+
+impl Drop for Tracking {
+    fn drop(&mut self) {
+        let query = IN_FLIGHT.lock().expect("poisoned").remove(&self.id);
+
+        if let Some(query) = query.filter(|_| !self.is_done) {
+            log::info!(
+                "{:?} query for {} cancelled",
+                query.kind,
+                query.content_hash
+            );
+        }
+    }
+}
+
+/// Runs a query, keeping track of its progress while it lasts.
+pub(super) async fn track<F: Future>(content_hash: Hash, kind: QueryKind, query: F) -> F::Output {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    IN_FLIGHT.lock().expect("poisoned").insert(
+        id,
+        InFlightQuery {
+            content_hash: content_hash.to_string(),
+            kind,
+            started_at: Utc::now(),
+            candidates: 0,
+            object: None,
+            received: 0,
+            chunks_received: 0,
+            total: None,
+            bytes_per_sec: 0.0,
+            eta_secs: None,
+        },
+    );
+    let mut tracking = Tracking { id, is_done: false };
+    let outcome = CURRENT_QUERY.scope(id, query).await;
+    tracking.is_done = true;
+
+    outcome
+}
+
+/// Updates the query the current task is making, if any.
+fn update<F: FnOnce(&mut InFlightQuery)>(f: F) {
+    if let Ok(id) = CURRENT_QUERY.try_with(|id| *id) {
+        if let Some(query) = IN_FLIGHT.lock().expect("poisoned").get_mut(&id) {
+            f(query);
+        }
+    }
+}
+
+/// Tells the current query that a peer said to have the content.
+pub(super) fn candidate_found() {
+    update(|query| query.candidates += 1);
+}
+
+/// Tells the current query, if any, how the download of an object is going.
+pub(crate) fn report_progress(
+    object: &Hash,
+    received: usize,
+    chunks_received: usize,
+    total: usize,
+    bytes_per_sec: f64,
+    eta_secs: Option<f64>,
+) {
+    update(|query| {
+        query.object = Some(object.to_string());
+        query.received = received;
+        query.chunks_received = chunks_received;
+        query.total = Some(total);
+        query.bytes_per_sec = bytes_per_sec;
+        query.eta_secs = eta_secs;
+    });
+}
+
+/// The queries being made right now, oldest first, optionally only those for some content
+/// (either queried for directly or being downloaded as the object of an item).
+pub fn in_flight(content_hash: Option<Hash>) -> Vec<InFlightQuery> {
+    let content_hash = content_hash.map(|hash| hash.to_string());
+
+    IN_FLIGHT
+        .lock()
+        .expect("poisoned")
+        .values()
+        .filter(|query| match &content_hash {
+            Some(hash) => &query.content_hash == hash || query.object.as_ref() == Some(hash),
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
@@ -7,11 +7,6 @@ use tokio::sync::{RwLock, RwLockReadGuard};
 use tokio::task::JoinHandle;
 use tokio::time::{sleep, Duration};
 
-// pub enum ConnectionStatus {
-//     Connected,
-//     Reconnecting,
-// }
-
 /// Exponential backoff. Just that.
 pub fn exponential_backoff(start: Duration, max: Duration) -> impl FnMut() -> Duration {
     let mut delay = start;
@@ -27,7 +22,7 @@ pub struct Reconnect<T> {
     /// The current active connection.
     current: Arc<RwLock<T>>,
     /// The task that monitors the connections and reconnects if necessary.
-    _reconnect: JoinHandle<()>,
+    reconnect: JoinHandle<()>,
 }
 
 impl<T: 'static + Send + Sync> Reconnect<T> {
@@ -75,22 +70,24 @@ impl<T: 'static + Send + Sync> Reconnect<T> {
             }
         });
 
-        Ok(Reconnect {
-            current,
-            _reconnect: reconnect,
-        })
+        Ok(Reconnect { current, reconnect })
     }
 
-    // pub fn status(&self) -> ConnectionStatus {
-    //     if self.current.try_read().is_ok() {
-    //         ConnectionStatus::Connected
-    //     } else {
-    //         ConnectionStatus::Reconnecting
-    //     }
-    // }
-
     /// Gets the current active connection.
     pub async fn get(&'_ self) -> RwLockReadGuard<'_, T> {
         self.current.read().await
     }
+
+    /// Gets the current active connection without waiting, or `None` if it is being
+    /// re-established.
+    pub fn try_get(&'_ self) -> Option<RwLockReadGuard<'_, T>> {
+        self.current.try_read().ok()
+    }
+}
+
+impl<T> Drop for Reconnect<T> {
+    fn drop(&mut self) {
+        // Otherwise, dropped connections would be reconnected forever:
+        self.reconnect.abort();
+    }
 }
@@ -0,0 +1,56 @@
+//! Routing of series through designated hubs. Everything done on behalf of a series with a
+//! [`HubRoute`] (looking for its editions, querying for its items, announcing its editions)
+//! only goes to the hubs of the route, while everything else goes to all hubs. This keeps
+//! public hubs from linking, e.g., an identity-bearing series to this node.
+//!
+//! The route is kept in a task-local, the same way request IDs are, so that it applies to all
+//! queries made while handling the series.
+
+use futures::Future;
+use std::collections::BTreeSet;
+use tokio::task::futures::TaskLocalFuture;
+
+use crate::models::{HubRouteRef, SeriesRef};
+
+tokio::task_local! {
+    /// The hubs to which the current task is restricted, if any.
+    static CURRENT_ROUTE: Option<BTreeSet<String>>;
+}
+
+/// The hubs through which a series is routed, if it is routed at all.
+fn route_for(series: &SeriesRef) -> Option<BTreeSet<String>> {
+    match HubRouteRef::new(series.public_key()).get() {
+        Ok(route) => route.map(|route| route.hubs),
+        Err(err) => {
+            // Better to send nothing than to leak:
+            log::error!("failed to get hub route for {series}: {err}");
+            Some(BTreeSet::new())
+        }
+    }
+}
+
+/// Whether a series is routed, i.e., whether nothing on its behalf may go anywhere but to the
+/// hubs of its route.
+pub fn is_routed(series: &SeriesRef) -> bool {
+    route_for(series).is_some()
+}
+
+/// Runs a future on behalf of a series, restricted to the hubs of its route.
+pub fn scope<F: Future>(
+    series: &SeriesRef,
+    future: F,
+) -> TaskLocalFuture<Option<BTreeSet<String>>, F> {
+    CURRENT_ROUTE.scope(route_for(series), future)
+}
+
+/// Whether the current task may talk to a given hub.
+pub(super) fn allows(hub_name: &str) -> bool {
+    CURRENT_ROUTE
+        .try_with(|route| {
+            route
+                .as_ref()
+                .map(|hubs| hubs.contains(hub_name))
+                .unwrap_or(true)
+        })
+        .unwrap_or(true)
+}
@@ -3,23 +3,68 @@ use quinn::{ReadToEndError, RecvStream};
 use std::collections::BTreeMap;
 use std::io;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 use tokio::sync::{mpsc, RwLock};
+use tokio::time::sleep;
 
 use samizdat_common::ChannelAddr;
 
+use crate::system::health::PeerStatus;
+
 use super::connection_manager::{ConnectionManager, DropMode};
 use super::multiplexed::Multiplexed;
 
+/// For how long a connection to a peer is kept open after its last use. Transfers to the same
+/// peer within this window reuse the connection, avoiding a new hole punching and handshake.
+const CONNECTION_REUSE_WINDOW: Duration = Duration::from_secs(60);
+
+type Connections = RwLock<BTreeMap<SocketAddr, Arc<Multiplexed>>>;
+
 pub struct ChannelManager {
-    connections: RwLock<BTreeMap<SocketAddr, Arc<Multiplexed>>>,
+    connections: Arc<Connections>,
     connection_manager: Arc<ConnectionManager>,
 }
 
+/// Closes and forgets connections which have not been used within the reuse window, until the
+/// channel manager is dropped.
+async fn janitor_task(connections: Weak<Connections>) {
+    loop {
+        sleep(CONNECTION_REUSE_WINDOW / 4).await;
+
+        let connections = if let Some(connections) = connections.upgrade() {
+            connections
+        } else {
+            break;
+        };
+
+        let mut guard = connections.write().await;
+        let mut expired = vec![];
+
+        for (&peer_addr, multiplexed) in guard.iter() {
+            if multiplexed.is_closed() || multiplexed.idle_for() > CONNECTION_REUSE_WINDOW {
+                expired.push(peer_addr);
+            } else {
+                multiplexed.prune_channels().await;
+            }
+        }
+
+        for peer_addr in expired {
+            if let Some(multiplexed) = guard.remove(&peer_addr) {
+                log::info!("closing idle connection to {peer_addr}");
+                multiplexed.close();
+            }
+        }
+    }
+}
+
 impl ChannelManager {
     pub fn new(connection_manager: Arc<ConnectionManager>) -> ChannelManager {
+        let connections = Arc::new(Connections::default());
+        tokio::spawn(janitor_task(Arc::downgrade(&connections)));
+
         ChannelManager {
-            connections: RwLock::default(),
+            connections,
             connection_manager,
         }
     }
@@ -44,10 +89,10 @@ impl ChannelManager {
         log::info!("connection write guard acquired");
 
         // Possible TOCTOU: check again.
-        if let Some(multiplexed) = guard.remove(&peer_addr) {
+        if let Some(multiplexed) = guard.get(&peer_addr) {
             log::info!("found existing connection on recheck");
             if !multiplexed.is_closed() {
-                return Ok(multiplexed);
+                return Ok(multiplexed.clone());
             } else {
                 log::info!("existing connection already closed. Create a new one!");
             }
@@ -64,6 +109,33 @@ impl ChannelManager {
         Ok(multiplexed)
     }
 
+    /// The status of all open connections to peers.
+    pub async fn peer_status(&self) -> Vec<PeerStatus> {
+        self.connections
+            .read()
+            .await
+            .iter()
+            .filter(|(_, multiplexed)| !multiplexed.is_closed())
+            .map(|(&addr, multiplexed)| PeerStatus {
+                addr,
+                rtt: multiplexed.rtt(),
+                protocol: multiplexed.protocol(),
+                idle_for: multiplexed.idle_for(),
+            })
+            .collect()
+    }
+
+    /// The round-trip times estimated by QUIC for the open connections to peers.
+    pub async fn peer_rtts(&self) -> BTreeMap<SocketAddr, Duration> {
+        self.connections
+            .read()
+            .await
+            .iter()
+            .filter(|(_, multiplexed)| !multiplexed.is_closed())
+            .map(|(&addr, multiplexed)| (addr, multiplexed.rtt()))
+            .collect()
+    }
+
     /// Waits for a given channel to be opened (i.e., the first message for it to arrive).
     pub async fn expect(
         &self,
@@ -106,6 +178,7 @@ impl ChannelManager {
     }
 }
 
+#[derive(Clone)]
 pub struct ChannelSender {
     channel_id: u32,
     multiplexed: Arc<Multiplexed>,
@@ -148,6 +221,22 @@ impl ChannelReceiver {
         outcome
     }
 
+    /// Receives a message only if it has already started arriving, without waiting for one.
+    pub async fn recv_if_arrived(
+        &mut self,
+        max_len: usize,
+    ) -> Result<Option<Vec<u8>>, crate::Error> {
+        match self.receiver.try_recv() {
+            Ok(stream) => stream
+                .read_to_end(max_len)
+                .await
+                .map(Some)
+                .map_err(read_error_to_io)
+                .map_err(crate::Error::from),
+            Err(_) => Ok(None),
+        }
+    }
+
     pub fn recv_many(
         &'_ mut self,
         max_len: usize,
@@ -1,6 +1,7 @@
 use futures::future::join;
 use futures::prelude::*;
 use quinn::{Connecting, Endpoint, Incoming, NewConnection};
+use samizdat_common::handshake::{self, Advertisement, Agreement, Capabilities};
 use samizdat_common::{quic, BincodeOverQuic};
 use std::net::SocketAddr;
 
@@ -47,21 +48,32 @@ impl ConnectionManager {
         Ok(new_connection)
     }
 
+    /// Connects to a remote address, agreeing on the protocol to speak through the connection
+    /// in the background (see [`handshake`]) and sending the given advertisement.
     pub async fn transport<S, R>(
         &self,
         remote_addr: SocketAddr,
-    ) -> Result<BincodeOverQuic<S, R>, crate::Error>
+        advertisement: &Advertisement,
+    ) -> Result<(BincodeOverQuic<S, R>, Agreement), crate::Error>
     where
         S: 'static + Send + serde::Serialize,
         R: 'static + Send + for<'a> serde::Deserialize<'a>,
     {
         let new_connection = self.connect(remote_addr).await?;
+        let agreement = handshake::spawn(
+            new_connection.connection.clone(),
+            new_connection.bi_streams,
+            Capabilities::NONE,
+            Some(advertisement.clone()),
+        );
 
-        Ok(BincodeOverQuic::new(
+        let transport = BincodeOverQuic::new(
             new_connection.connection.clone(),
             new_connection.uni_streams,
             MAX_TRANSFER_SIZE,
-        ))
+        );
+
+        Ok((transport, agreement))
     }
 
     /// TODO: very basic NAT/firewall traversal stuff that works well in IPv6,
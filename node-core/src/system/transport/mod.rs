@@ -4,6 +4,7 @@ mod channel_manager;
 mod connection_manager;
 mod matcher;
 mod multiplexed;
+pub mod usage;
 
 pub use self::channel_manager::{ChannelManager, ChannelReceiver, ChannelSender};
 pub use self::connection_manager::ConnectionManager;
@@ -1,21 +1,31 @@
 use futures::prelude::*;
 use quinn::{Connection, ConnectionError, IncomingUniStreams, NewConnection, RecvStream};
+use samizdat_common::handshake::{self, Agreement, Capabilities, Protocol};
 use std::collections::BTreeMap;
 use std::io;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, Mutex, MutexGuard};
 
+use crate::utils;
+
 use super::matcher::Matcher;
+use super::usage::{self, Counterpart, Source, Tracking};
 
 /// A multiplexer over a QUIC connection, capable of splitting its uni streams into channels.
 pub struct Multiplexed {
     connection: Connection,
+    /// What is agreed with the peer. Channels do not wait for it.
+    agreement: Agreement,
     senders: Arc<Mutex<BTreeMap<u32, mpsc::UnboundedSender<RecvStream>>>>,
     /// TODO: `UnboundedReceiver` needs to be changed to `Receiver` to avoid flooding.
     matcher: Matcher<u32, mpsc::UnboundedReceiver<RecvStream>>,
     is_closed: Arc<AtomicBool>,
+    /// The last time a channel was used over this connection, sending or receiving.
+    last_used: Arc<std::sync::Mutex<Instant>>,
+    _usage: Tracking,
 }
 
 async fn create_channel(
@@ -36,10 +46,14 @@ async fn receiver_task(
     mut incoming: IncomingUniStreams,
     senders: Arc<Mutex<BTreeMap<u32, mpsc::UnboundedSender<RecvStream>>>>,
     matcher: Matcher<u32, mpsc::UnboundedReceiver<RecvStream>>,
+    last_used: Arc<std::sync::Mutex<Instant>>,
 ) {
     while let Some(stream) = incoming.next().await {
         match stream {
             Ok(mut stream) => {
+                // A connection receiving content (e.g., a long download) is not idle:
+                *last_used.lock().expect("poisoned") = Instant::now();
+
                 let mut id_buf = [0; 4];
 
                 // Receive the channel id for this stream.
@@ -88,21 +102,64 @@ impl Multiplexed {
         let matcher = Matcher::default();
         let is_closed = Arc::new(AtomicBool::new(false));
         let set_closed = is_closed.clone();
+        let last_used = Arc::new(std::sync::Mutex::new(Instant::now()));
 
         tokio::spawn(
-            receiver_task(incoming, senders.clone(), matcher.clone())
-                .map(move |_| set_closed.store(true, Ordering::Relaxed)),
+            receiver_task(
+                incoming,
+                senders.clone(),
+                matcher.clone(),
+                last_used.clone(),
+            )
+            .map(move |_| set_closed.store(true, Ordering::Relaxed)),
+        );
+
+        let peer_addr = utils::socket_to_canonical(new_connection.connection.remote_address());
+        let source = Source::Quic(new_connection.connection.clone());
+        let agreement = handshake::spawn(
+            new_connection.connection.clone(),
+            new_connection.bi_streams,
+            Capabilities::NONE,
+            None,
         );
 
         Multiplexed {
+            _usage: usage::track(Counterpart::Peer, peer_addr, source),
             connection: new_connection.connection,
+            agreement,
             senders,
             matcher,
             is_closed,
+            last_used,
         }
     }
 
+    /// Marks this connection as being used now.
+    fn touch(&self) {
+        *self.last_used.lock().expect("poisoned") = Instant::now();
+    }
+
+    /// For how long no channel has been used over this connection.
+    pub fn idle_for(&self) -> Duration {
+        self.last_used.lock().expect("poisoned").elapsed()
+    }
+
+    /// Forgets all channels whose receivers have been dropped.
+    pub async fn prune_channels(&self) {
+        self.senders
+            .lock()
+            .await
+            .retain(|_, sender| !sender.is_closed());
+    }
+
+    /// Closes the underlying connection. The peer will be notified.
+    pub fn close(&self) {
+        self.connection.close(0u32.into(), b"idle");
+        self.is_closed.store(true, Ordering::Relaxed);
+    }
+
     pub async fn send(&self, channel_id: u32, payload: &[u8]) -> Result<(), crate::Error> {
+        self.touch();
         let mut stream = self.connection.open_uni().await?;
         log::debug!("stream opened for {:x}", channel_id);
 
@@ -123,6 +180,7 @@ impl Multiplexed {
 
     pub async fn initiate(&self, channel_id: u32) -> mpsc::UnboundedReceiver<RecvStream> {
         log::info!("initiating channel id {:x}", channel_id);
+        self.touch();
         let (sender, recv) = mpsc::unbounded_channel();
         let mut guard = self.senders.lock().await;
         guard.insert(channel_id, sender);
@@ -131,6 +189,7 @@ impl Multiplexed {
 
     pub async fn expect(&self, channel_id: u32) -> Option<mpsc::UnboundedReceiver<RecvStream>> {
         log::info!("expecting channel id {:x}", channel_id);
+        self.touch();
         self.matcher.expect(channel_id).await
     }
 
@@ -141,4 +200,14 @@ impl Multiplexed {
     pub fn remote_address(&self) -> SocketAddr {
         self.connection.remote_address()
     }
+
+    /// What is agreed with the peer so far (see [`Agreement::protocol`]).
+    pub fn protocol(&self) -> Protocol {
+        self.agreement.protocol()
+    }
+
+    /// The current round-trip time estimated by QUIC for this connection.
+    pub fn rtt(&self) -> Duration {
+        self.connection.rtt()
+    }
 }
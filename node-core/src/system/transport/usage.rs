@@ -0,0 +1,298 @@
+//! Accounting of the bandwidth used by the connections to hubs and to peers, for users on
+//! capped connections. Every [`SAMPLE_INTERVAL`], the bytes sent and received by each open
+//! connection since the last sample are added to the totals of the day (in UTC) for the hub at
+//! the other end or, for peers, to the totals of all peers together, so that the node keeps no
+//! record of which peers it talked to. Totals are kept for [`RETENTION_DAYS`].
+//!
+//! QUIC counts whole datagrams and TLS over TCP counts whole TLS records, so the totals include
+//! the overhead of encryption and of retransmissions, but not of IP, UDP and TCP headers.
+
+use chrono::{NaiveDate, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+use samizdat_common::tcp_fallback::ByteCount;
+
+use crate::db::{db, is_replica, Table};
+
+/// The time between two samples of the open connections.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+/// For how many days the totals are kept.
+const RETENTION_DAYS: i64 = 90;
+
+/// What is at the other end of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Counterpart {
+    Hub,
+    Peer,
+}
+
+impl Display for Counterpart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Counterpart::Hub => write!(f, "hub"),
+            Counterpart::Peer => write!(f, "peer"),
+        }
+    }
+}
+
+impl FromStr for Counterpart {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> Result<Counterpart, crate::Error> {
+        match s {
+            "hub" => Ok(Counterpart::Hub),
+            "peer" => Ok(Counterpart::Peer),
+            _ => Err(format!("unknown counterpart {s:?}").into()),
+        }
+    }
+}
+
+/// Where the byte counts of a connection come from.
+pub enum Source {
+    Quic(quinn::Connection),
+    Tcp(Arc<ByteCount>),
+}
+
+impl Source {
+    /// The bytes sent and received so far.
+    fn totals(&self) -> (u64, u64) {
+        match self {
+            Source::Quic(connection) => {
+                let stats = connection.stats();
+                (stats.udp_tx.bytes, stats.udp_rx.bytes)
+            }
+            Source::Tcp(count) => (count.sent(), count.received()),
+        }
+    }
+}
+
+/// A connection being accounted for.
+struct Tracked {
+    id: u64,
+    counterpart: Counterpart,
+    /// The address of the hub. `None` for peers.
+    addr: Option<SocketAddr>,
+    source: Source,
+    /// The totals at the last sample.
+    sampled: (u64, u64),
+}
+
+impl Tracked {
+    /// The bytes used since the last sample, if any.
+    fn sample(&mut self, day: NaiveDate) -> Option<Usage> {
+        let (sent, received) = self.source.totals();
+        let (last_sent, last_received) = self.sampled;
+        self.sampled = (sent, received);
+
+        (sent > last_sent || received > last_received).then(|| Usage {
+            day,
+            counterpart: self.counterpart,
+            addr: self.addr,
+            sent: sent - last_sent,
+            received: received - last_received,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Accounting {
+    next_id: u64,
+    tracked: Vec<Tracked>,
+    /// Usage sampled from connections no longer tracked, not yet stored.
+    pending: Vec<Usage>,
+}
+
+lazy_static::lazy_static! {
+    static ref ACCOUNTING: Mutex<Accounting> = Mutex::default();
+}
+
+/// Keeps a connection accounted for while it is alive. This must live as long as the
+/// connection, since dropping it samples the connection one last time. Tracking does not keep
+/// the connection open after that.
+pub struct Tracking {
+    id: u64,
+}
+
+impl Drop for Tracking {
+    fn drop(&mut self) {
+        let mut accounting = ACCOUNTING.lock().expect("poisoned");
+        let position = accounting.tracked.iter().position(|t| t.id == self.id);
+
+        if let Some(position) = position {
+            let mut tracked = accounting.tracked.swap_remove(position);
+            let day = Utc::now().naive_utc().date();
+            accounting.pending.extend(tracked.sample(day));
+        }
+    }
+}
+
+/// Accounts for the bandwidth used by a new connection, until the returned [`Tracking`] is
+/// dropped. The address is only kept for hubs.
+pub fn track(counterpart: Counterpart, addr: SocketAddr, source: Source) -> Tracking {
+    let mut accounting = ACCOUNTING.lock().expect("poisoned");
+    let id = accounting.next_id;
+    accounting.next_id += 1;
+    accounting.tracked.push(Tracked {
+        id,
+        counterpart,
+        addr: (counterpart == Counterpart::Hub).then_some(addr),
+        source,
+        sampled: (0, 0),
+    });
+
+    Tracking { id }
+}
+
+/// The bandwidth used with a hub, or with all peers, in a day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// The day, in UTC.
+    pub day: NaiveDate,
+    /// Whether a hub or a peer.
+    pub counterpart: Counterpart,
+    /// The address of the hub. `None` for peers, which are accounted for all together.
+    pub addr: Option<SocketAddr>,
+    /// Bytes sent.
+    pub sent: u64,
+    /// Bytes received.
+    pub received: u64,
+}
+
+impl Usage {
+    /// Days come first in keys, so that they are sorted and pruned by day.
+    fn key(&self) -> String {
+        let addr = self.addr.map(|addr| addr.to_string()).unwrap_or_default();
+        format!("{}/{}/{addr}", self.day, self.counterpart)
+    }
+
+    fn from_entry(key: &[u8], value: &[u8]) -> Result<Usage, crate::Error> {
+        let key = String::from_utf8_lossy(key);
+        let mut parts = key.splitn(3, '/');
+        let mut next = || parts.next().ok_or_else(|| format!("bad usage key {key:?}"));
+        let day = next()?.parse().map_err(|err| format!("bad day: {err}"))?;
+        let counterpart = next()?.parse()?;
+        let addr = Some(next()?)
+            .filter(|addr| !addr.is_empty())
+            .map(str::parse)
+            .transpose()
+            .map_err(|err| format!("bad address: {err}"))?;
+        let (sent, received) = bincode::deserialize(value)?;
+
+        Ok(Usage {
+            day,
+            counterpart,
+            addr,
+            sent,
+            received,
+        })
+    }
+
+    /// Adds to the totals stored for the day.
+    fn add(mut self) -> Result<(), crate::Error> {
+        let key = self.key();
+
+        if let Some(value) = db().get_cf(Table::ConnectionUsage.get(), &key)? {
+            let stored = Usage::from_entry(key.as_bytes(), &value)?;
+            self.sent += stored.sent;
+            self.received += stored.received;
+        }
+
+        let value = bincode::serialize(&(self.sent, self.received))?;
+        db().put_cf(Table::ConnectionUsage.get(), key, value)?;
+
+        Ok(())
+    }
+}
+
+/// The bandwidth used with each hub and with all peers, per day, since the given day. Bytes
+/// not yet sampled are left out.
+pub fn usage(since: NaiveDate) -> Result<Vec<Usage>, crate::Error> {
+    let iter = db().iterator_cf(
+        Table::ConnectionUsage.get(),
+        rocksdb::IteratorMode::From(since.to_string().as_bytes(), rocksdb::Direction::Forward),
+    );
+
+    iter.map(|(key, value)| Usage::from_entry(&key, &value))
+        .collect()
+}
+
+/// Adds the bytes used since the last sample to the totals of the day.
+fn sample() -> Result<(), crate::Error> {
+    let day = Utc::now().naive_utc().date();
+    let used = {
+        let mut accounting = ACCOUNTING.lock().expect("poisoned");
+        let mut used = std::mem::take(&mut accounting.pending);
+        used.extend(
+            accounting
+                .tracked
+                .iter_mut()
+                .filter_map(|tracked| tracked.sample(day)),
+        );
+        used
+    };
+
+    // The primary keeps its own accounts:
+    if is_replica() {
+        return Ok(());
+    }
+
+    for usage in used {
+        usage.add()?;
+    }
+
+    Ok(())
+}
+
+/// Removes the totals of the days past [`RETENTION_DAYS`].
+fn prune() -> Result<(), crate::Error> {
+    let oldest = Utc::now().naive_utc().date() - chrono::Duration::days(RETENTION_DAYS);
+    db().delete_range_cf(Table::ConnectionUsage.get(), "", &oldest.to_string())?;
+
+    Ok(())
+}
+
+/// Removes the totals kept per peer by earlier versions, which stored the address of each
+/// peer.
+fn forget_peer_addresses() -> Result<(), crate::Error> {
+    if is_replica() {
+        return Ok(());
+    }
+
+    for (key, value) in db().iterator_cf(Table::ConnectionUsage.get(), rocksdb::IteratorMode::Start)
+    {
+        let usage = Usage::from_entry(&key, &value)?;
+        if usage.counterpart == Counterpart::Peer && usage.addr.is_some() {
+            db().delete_cf(Table::ConnectionUsage.get(), &key)?;
+            Usage {
+                addr: None,
+                ..usage
+            }
+            .add()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Samples the open connections every [`SAMPLE_INTERVAL`].
+pub async fn run_usage_daemon() {
+    if let Err(err) = forget_peer_addresses() {
+        log::error!("failed to forget the addresses of peers in bandwidth usage: {err}");
+    }
+
+    let mut interval = interval(SAMPLE_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = sample().and_then(|_| prune()) {
+            log::error!("failed to account for bandwidth usage: {err}");
+        }
+    }
+}
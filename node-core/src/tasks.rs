@@ -0,0 +1,209 @@
+//! A registry of the background tasks of the node. Tasks spawned through here are named and
+//! tracked while they run, so that stuck transfers and daemons can be seen in `/_tasks`.
+//! Daemons are supervised: if one panics, it is restarted after a backoff. Tasks that fail or
+//! panic are kept in a short list of recent failures, instead of vanishing into the logs.
+
+use chrono::{DateTime, Utc};
+use futures::Future;
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::task::JoinError;
+use tokio::time::Duration;
+
+use samizdat_common::request_id;
+
+use crate::system::exponential_backoff;
+
+/// How many failed tasks are remembered.
+const MAX_RECENT_FAILURES: usize = 100;
+
+/// The first time to wait before restarting a crashed daemon.
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// The most time to wait before restarting a crashed daemon.
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(300);
+
+lazy_static::lazy_static! {
+    /// The tasks running right now, by id.
+    static ref RUNNING: Mutex<BTreeMap<u64, RunningTask>> = Mutex::default();
+    /// The tasks that failed lately, oldest first.
+    static ref RECENT_FAILURES: Mutex<VecDeque<FailedTask>> = Mutex::default();
+}
+
+/// The id of the next task to be spawned.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A task running right now.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunningTask {
+    pub id: u64,
+    pub name: String,
+    /// Whether this task is restarted when it panics.
+    pub is_daemon: bool,
+    pub started_at: DateTime<Utc>,
+    /// How many times this daemon was restarted.
+    pub restarts: usize,
+}
+
+/// A task that failed.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedTask {
+    pub name: String,
+    pub started_at: DateTime<Utc>,
+    pub failed_at: DateTime<Utc>,
+    /// The error or the panic message.
+    pub error: String,
+    /// Whether the task panicked, as opposed to returning an error.
+    pub panicked: bool,
+}
+
+/// The tasks of the node, as shown in `/_tasks`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStatus {
+    /// The tasks running right now, oldest first.
+    pub running: Vec<RunningTask>,
+    /// The tasks that failed lately, most recent first.
+    pub recent_failures: Vec<FailedTask>,
+}
+
+/// Removes a task from the registry when it is over, however it ends.
+struct Registration(u64);
+
+impl Registration {
+    fn new(name: &str, is_daemon: bool) -> Registration {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        RUNNING.lock().expect("poisoned").insert(
+            id,
+            RunningTask {
+                id,
+                name: name.to_owned(),
+                is_daemon,
+                started_at: Utc::now(),
+                restarts: 0,
+            },
+        );
+
+        Registration(id)
+    }
+
+    fn restarted(&self) {
+        if let Some(task) = RUNNING.lock().expect("poisoned").get_mut(&self.0) {
+            task.restarts += 1;
+            task.started_at = Utc::now();
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        RUNNING.lock().expect("poisoned").remove(&self.0);
+    }
+}
+
+/// The message of a panic.
+fn panic_message(error: JoinError) -> String {
+    match error.try_into_panic() {
+        Ok(panic) => panic
+            .downcast_ref::<&str>()
+            .map(ToString::to_string)
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".to_owned()),
+        Err(error) => error.to_string(),
+    }
+}
+
+/// Remembers a failed task.
+fn record_failure(name: &str, started_at: DateTime<Utc>, error: String, panicked: bool) {
+    log::error!("task `{name}` failed: {error}");
+
+    let mut failures = RECENT_FAILURES.lock().expect("poisoned");
+
+    if failures.len() == MAX_RECENT_FAILURES {
+        failures.pop_front();
+    }
+
+    failures.push_back(FailedTask {
+        name: name.to_owned(),
+        started_at,
+        failed_at: Utc::now(),
+        error,
+        panicked,
+    });
+}
+
+/// Spawns a named task, as part of the current request, if any. Errors and panics are
+/// recorded as failures.
+pub fn spawn<F>(name: impl Into<String>, task: F)
+where
+    F: 'static + Send + Future<Output = Result<(), crate::Error>>,
+{
+    let name = name.into();
+    let registration = Registration::new(&name, false);
+    let started_at = Utc::now();
+    let handle = request_id::spawn(task);
+
+    tokio::spawn(async move {
+        match handle.await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => record_failure(&name, started_at, err.to_string(), false),
+            Err(err) => record_failure(&name, started_at, panic_message(err), true),
+        }
+
+        drop(registration);
+    });
+}
+
+/// Spawns a named daemon, which runs for as long as the node does, unless it returns (e.g.,
+/// because it is disabled). If the daemon panics, the failure is recorded and it is started
+/// anew after a backoff.
+pub fn supervise<F, Fut>(name: &'static str, daemon: F)
+where
+    F: 'static + Send + Fn() -> Fut,
+    Fut: 'static + Send + Future<Output = ()>,
+{
+    let registration = Registration::new(name, true);
+
+    tokio::spawn(async move {
+        let mut backoff = exponential_backoff(MIN_RESTART_DELAY, MAX_RESTART_DELAY);
+
+        loop {
+            let started_at = Utc::now();
+
+            match tokio::spawn(daemon()).await {
+                Ok(()) => {
+                    log::info!("daemon `{name}` is done");
+                    break;
+                }
+                Err(err) => record_failure(name, started_at, panic_message(err), true),
+            }
+
+            let delay = backoff();
+            log::warn!("restarting daemon `{name}` in {delay:?}");
+            tokio::time::sleep(delay).await;
+            registration.restarted();
+        }
+
+        drop(registration);
+    });
+}
+
+/// The tasks running right now and the ones that failed lately.
+pub fn status() -> TaskStatus {
+    TaskStatus {
+        running: RUNNING
+            .lock()
+            .expect("poisoned")
+            .values()
+            .cloned()
+            .collect(),
+        recent_failures: RECENT_FAILURES
+            .lock()
+            .expect("poisoned")
+            .iter()
+            .rev()
+            .cloned()
+            .collect(),
+    }
+}
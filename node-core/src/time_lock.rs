@@ -0,0 +1,548 @@
+//! Time-locked editions: embargoes that not even mirrors can break early.
+//!
+//! A time-locked edition does not point to the collection being published, but to a _sealed_
+//! collection, whose item `_sealed` lists what is in the original collection and whose item
+//! `_sealed_content` holds the objects of the original collection, one after the other, all
+//! encrypted with a random key. The content is encrypted in blocks, so that neither sealing nor
+//! unsealing ever holds much more than a block in memory. The sealed edition spreads through the network (and to subscribers and mirrors)
+//! like any other edition, but nobody can read it. When the time comes, the publisher node
+//! releases a tiny _key edition_, whose collection has a single item, `_unseal`, telling the
+//! key. Nodes having the key edition decrypt the sealed collection, rebuild the original collection
+//! locally and serve it in place of the key edition. Since only the key needs to travel, the
+//! content surfaces everywhere almost at once, even if the publisher goes offline.
+//!
+//! The rebuilt collection is checked against the hash the publisher signed in the key
+//! edition, so a bad key or a tampered sealed collection gets nowhere.
+
+use chrono::{DateTime, Utc};
+use rocksdb::IteratorMode;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+use tokio::time::{interval, MissedTickBehavior};
+
+use samizdat_common::cipher::{OpaqueEncrypted, TransferCipher};
+use samizdat_common::rpc::QueryKind;
+use samizdat_common::Hash;
+
+use crate::db::{db, is_replica, Table};
+use crate::hubs;
+use crate::models::{
+    CollectionRef, ContentIter, ItemMetadata, ItemPathBuf, ObjectHeader, ObjectRef, SeriesOwner,
+};
+
+/// The item of a sealed collection listing what is in the original collection.
+const SEALED_ITEM: &str = "_sealed";
+/// The item of a sealed collection holding the encrypted objects of the original collection.
+const SEALED_CONTENT_ITEM: &str = "_sealed_content";
+/// The item of a key edition telling how to unseal a sealed collection.
+pub const UNSEAL_ITEM: &str = "_unseal";
+/// How often the publisher node checks for editions due for release.
+const RELEASE_INTERVAL: Duration = Duration::from_secs(30);
+/// The size of the blocks in which the content of a sealed collection is encrypted.
+const SEAL_BLOCK_SIZE: usize = 256 * 1024;
+
+/// An object of a sealed collection.
+#[derive(Serialize, Deserialize)]
+struct SealedItem {
+    path: ItemPathBuf,
+    /// The size of the object in `_sealed_content`, header included.
+    size: usize,
+}
+
+/// The plaintext of the `_sealed` item: everything needed to rebuild the original collection,
+/// with the objects in `_sealed_content`.
+#[derive(Serialize, Deserialize)]
+struct SealedCollection {
+    is_draft: bool,
+    items: Vec<SealedItem>,
+    metadata: BTreeMap<ItemPathBuf, ItemMetadata>,
+    content_warnings: BTreeSet<String>,
+    inventory_shard_size: Option<usize>,
+    prefetch: Vec<ItemPathBuf>,
+}
+
+/// The content of the `_unseal` item of a key edition.
+#[derive(Debug, Serialize, Deserialize)]
+struct UnsealKey {
+    /// The sealed collection.
+    sealed: Hash,
+    /// The original collection, which unsealing must rebuild.
+    collection: Hash,
+    /// The key with which the collection was sealed.
+    key: Hash,
+    /// The nonce with which the collection was sealed.
+    nonce: Hash,
+}
+
+/// A time-locked edition waiting for release in the publisher node.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeLock {
+    series_owner_name: String,
+    release_at: DateTime<Utc>,
+    is_draft: bool,
+    unseal_key: UnsealKey,
+}
+
+/// The cipher for a block of the content of a sealed collection. Each block has its own nonce, so
+/// that blocks cannot be reordered.
+fn block_cipher(key: &Hash, nonce: &Hash, index: u64) -> TransferCipher {
+    let block_nonce = Hash::hash([nonce.as_ref(), &index.to_be_bytes()].concat());
+    TransferCipher::new(key, &block_nonce)
+}
+
+/// Encrypts a stream of bytes in blocks, each prefixed by its length.
+struct SealingIter<I> {
+    source: I,
+    key: Hash,
+    nonce: Hash,
+    index: u64,
+    block: std::vec::IntoIter<u8>,
+}
+
+impl<I> SealingIter<I> {
+    fn new(source: I, key: Hash, nonce: Hash) -> SealingIter<I> {
+        SealingIter {
+            source,
+            key,
+            nonce,
+            index: 0,
+            block: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<u8, crate::Error>>> Iterator for SealingIter<I> {
+    type Item = Result<u8, crate::Error>;
+    fn next(&mut self) -> Option<Result<u8, crate::Error>> {
+        if let Some(byte) = self.block.next() {
+            return Some(Ok(byte));
+        }
+
+        let mut block = Vec::with_capacity(SEAL_BLOCK_SIZE);
+        for byte in self.source.by_ref().take(SEAL_BLOCK_SIZE) {
+            match byte {
+                Ok(byte) => block.push(byte),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        if block.is_empty() {
+            return None;
+        }
+
+        block_cipher(&self.key, &self.nonce, self.index).encrypt(&mut block);
+        self.index += 1;
+
+        let mut framed = (block.len() as u32).to_be_bytes().to_vec();
+        framed.extend(block);
+        self.block = framed.into_iter();
+
+        self.block.next().map(Ok)
+    }
+}
+
+/// Decrypts what a [`SealingIter`] encrypted.
+struct UnsealingIter<I> {
+    source: I,
+    key: Hash,
+    nonce: Hash,
+    index: u64,
+    block: std::vec::IntoIter<u8>,
+}
+
+impl<I> UnsealingIter<I> {
+    fn new(source: I, key: Hash, nonce: Hash) -> UnsealingIter<I> {
+        UnsealingIter {
+            source,
+            key,
+            nonce,
+            index: 0,
+            block: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<u8, crate::Error>>> UnsealingIter<I> {
+    /// Reads exactly `n` bytes from the source, or nothing at all if it is over.
+    fn read_exact(&mut self, n: usize) -> Result<Option<Vec<u8>>, crate::Error> {
+        let read = self
+            .source
+            .by_ref()
+            .take(n)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match read.len() {
+            0 if n > 0 => Ok(None),
+            len if len == n => Ok(Some(read)),
+            _ => Err("sealed content is truncated".to_owned().into()),
+        }
+    }
+
+    fn next_block(&mut self) -> Result<Option<Vec<u8>>, crate::Error> {
+        let Some(length) = self.read_exact(4)? else {
+            return Ok(None);
+        };
+        let length = u32::from_be_bytes(length.try_into().expect("read 4 bytes")) as usize;
+
+        // A block grows by the size of the tag when encrypted:
+        if length > SEAL_BLOCK_SIZE + 16 {
+            return Err(format!("sealed block of {length} bytes is too big").into());
+        }
+
+        let mut block = self
+            .read_exact(length)?
+            .ok_or("sealed content is truncated")?;
+        block_cipher(&self.key, &self.nonce, self.index).decrypt(&mut block);
+        self.index += 1;
+
+        Ok(Some(block))
+    }
+}
+
+impl<I: Iterator<Item = Result<u8, crate::Error>>> Iterator for UnsealingIter<I> {
+    type Item = Result<u8, crate::Error>;
+    fn next(&mut self) -> Option<Result<u8, crate::Error>> {
+        if let Some(byte) = self.block.next() {
+            return Some(Ok(byte));
+        }
+
+        match self.next_block() {
+            Ok(Some(block)) => {
+                self.block = block.into_iter();
+                self.block.next().map(Ok)
+            }
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+impl TimeLock {
+    /// The time-locked editions not yet released by this node.
+    pub fn get_all() -> Result<Vec<TimeLock>, crate::Error> {
+        db().iterator_cf(Table::TimeLocks.get(), IteratorMode::Start)
+            .map(|(_, value)| Ok(bincode::deserialize(&value)?))
+            .collect()
+    }
+}
+
+/// Seals a locally built collection until a given time, returning the sealed collection, to
+/// be published in place of the original one.
+pub fn seal(
+    owner: &SeriesOwner,
+    collection: &CollectionRef,
+    release_at: DateTime<Utc>,
+) -> Result<CollectionRef, crate::Error> {
+    if release_at <= Utc::now() {
+        return Err(crate::Error::ValidationFailed(format!(
+            "release time {release_at} is already past"
+        )));
+    }
+
+    let inventory = collection.inventory()?.ok_or_else(|| {
+        crate::Error::NotFound(format!("inventory of collection {}", collection.hash()))
+    })?;
+    // Sealed collections have no place for mounts:
+    if !inventory.mounts().is_empty() {
+        return Err(crate::Error::ValidationFailed(
+            "collections mounting other collections cannot be time-locked".to_owned(),
+        ));
+    }
+
+    let is_draft = collection
+        .locator_for("_inventory".into())
+        .get_object()?
+        .map(|object| object.is_draft())
+        .transpose()?
+        .unwrap_or(true);
+
+    let mut items = vec![];
+    let mut contents = vec![];
+
+    for (path, hash) in &inventory {
+        let object = ObjectRef::new(*hash);
+        let not_local =
+            || crate::Error::NotFound(format!("object {hash} of item {path} is not local"));
+        let metadata = object.metadata()?.ok_or_else(not_local)?;
+
+        items.push(SealedItem {
+            path: path.clone(),
+            size: metadata.content_size,
+        });
+        contents.push(object.iter_content()?.ok_or_else(not_local)?);
+    }
+
+    let sealed_collection = SealedCollection {
+        is_draft,
+        items,
+        metadata: inventory
+            .iter()
+            .filter_map(|(path, _)| Some((path.clone(), inventory.metadata(path)?.clone())))
+            .collect(),
+        content_warnings: inventory.content_warnings().clone(),
+        inventory_shard_size: inventory.shard_size(),
+        prefetch: inventory.prefetch().to_vec(),
+    };
+
+    let (key, nonce) = (Hash::rand(), Hash::rand());
+    let encrypted = OpaqueEncrypted::new(&sealed_collection, &TransferCipher::new(&key, &nonce));
+    let sealed_object = ObjectRef::build(
+        ObjectHeader::new("application/x-samizdat-sealed".to_owned(), is_draft)?,
+        false,
+        bincode::serialize(&encrypted)
+            .expect("can serialize")
+            .into_iter()
+            .map(Ok),
+    )?;
+    let sealed_content = ObjectRef::build(
+        ObjectHeader::new("application/x-samizdat-sealed".to_owned(), is_draft)?,
+        false,
+        SealingIter::new(contents.into_iter().flatten(), key, nonce),
+    )?;
+    let sealed = CollectionRef::build(
+        is_draft,
+        [
+            (ItemPathBuf::from(SEALED_ITEM), sealed_object),
+            (ItemPathBuf::from(SEALED_CONTENT_ITEM), sealed_content),
+        ],
+        BTreeMap::new(),
+        BTreeSet::new(),
+    )?;
+
+    let time_lock = TimeLock {
+        series_owner_name: owner.name().to_owned(),
+        release_at,
+        is_draft,
+        unseal_key: UnsealKey {
+            sealed: sealed.hash(),
+            collection: collection.hash(),
+            key,
+            nonce,
+        },
+    };
+
+    db().put_cf(
+        Table::TimeLocks.get(),
+        sealed.hash(),
+        bincode::serialize(&time_lock).expect("can serialize"),
+    )?;
+
+    Ok(sealed)
+}
+
+/// Publishes the key edition of a time-locked edition.
+fn release(time_lock: &TimeLock) -> Result<(), crate::Error> {
+    let owner = SeriesOwner::get(&time_lock.series_owner_name)?.ok_or_else(|| {
+        crate::Error::NotFound(format!("series owner {}", time_lock.series_owner_name))
+    })?;
+    let key = &time_lock.unseal_key;
+    let is_draft = time_lock.is_draft;
+
+    let unseal_object = ObjectRef::build(
+        ObjectHeader::new("application/json".to_owned(), is_draft)?,
+        false,
+        serde_json::to_vec(key)
+            .expect("can serialize")
+            .into_iter()
+            .map(Ok),
+    )?;
+    let key_collection = CollectionRef::build(
+        is_draft,
+        [(ItemPathBuf::from(UNSEAL_ITEM), unseal_object)],
+        BTreeMap::new(),
+        BTreeSet::new(),
+    )?;
+
+    // The original collection is already here:
+    db().put_cf(Table::Unsealed.get(), key_collection.hash(), key.collection)?;
+
+    let edition = owner.advance(key_collection, None)?;
+    owner.announce(&edition);
+
+    db().delete_cf(Table::TimeLocks.get(), key.sealed)?;
+
+    Ok(())
+}
+
+/// Releases the time-locked editions of this node when their time comes, forever.
+pub async fn run_release_daemon() {
+    let mut ticker = interval(RELEASE_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        crate::lifecycle::wait_until_active().await;
+
+        let time_locks = match TimeLock::get_all() {
+            Ok(time_locks) => time_locks,
+            Err(err) => {
+                log::error!("failed to list time-locked editions: {err}");
+                continue;
+            }
+        };
+
+        for time_lock in time_locks {
+            if time_lock.release_at > Utc::now() {
+                continue;
+            }
+
+            log::info!("Releasing time-locked edition {time_lock:?}");
+
+            if let Err(err) = release(&time_lock) {
+                log::error!("failed to release time-locked edition {time_lock:?}: {err}");
+            }
+        }
+    }
+}
+
+/// Gets the content of an item, without the header, asking the network if necessary.
+async fn item_content(
+    collection: &CollectionRef,
+    name: &str,
+) -> Result<Option<ContentIter>, crate::Error> {
+    let locator = collection.locator_for(name.into());
+    let local = match locator.get_object()? {
+        Some(object) => object.iter_content()?,
+        None => None,
+    };
+    let maybe_iter = match local {
+        Some(iter) => Some(iter),
+        None => match hubs().query(locator.hash(), QueryKind::Item).await {
+            Some(object) => object.iter_content()?,
+            None => None,
+        },
+    };
+
+    maybe_iter
+        .map(|mut iter| {
+            ObjectHeader::read(&mut iter)?;
+            Ok(iter)
+        })
+        .transpose()
+}
+
+/// Whether a collection is the collection of a key edition, as far as this node knows. Only
+/// collections whose inventory is here are checked, so that this never goes to the network.
+pub fn is_key_collection(collection: &CollectionRef) -> Result<bool, crate::Error> {
+    Ok(collection
+        .inventory()?
+        .is_some_and(|inventory| inventory.contains(&UNSEAL_ITEM.into())))
+}
+
+/// The collection unsealed by a key collection, if it was already unsealed in this node.
+pub fn unsealed(key_collection: &CollectionRef) -> Result<Option<CollectionRef>, crate::Error> {
+    let maybe_hash = db().get_cf(Table::Unsealed.get(), key_collection.hash())?;
+    maybe_hash
+        .map(|hash| Ok(CollectionRef::new(Hash::try_from(&*hash)?)))
+        .transpose()
+}
+
+/// Unseals the collection released by a key collection, asking the network for the key and for
+/// the sealed collection if necessary. Returns `Ok(None)` if this is not a key collection (see
+/// [`is_key_collection`]) or if the key or the sealed collection cannot be found.
+pub async fn unseal(key_collection: &CollectionRef) -> Result<Option<CollectionRef>, crate::Error> {
+    if let Some(collection) = unsealed(key_collection)? {
+        return Ok(Some(collection));
+    }
+
+    // Unsealing stores the collection, which is up to the primary:
+    if is_replica() {
+        return Ok(None);
+    }
+
+    if !is_key_collection(key_collection)? {
+        return Ok(None);
+    }
+
+    let key: UnsealKey = match item_content(key_collection, UNSEAL_ITEM).await? {
+        Some(content) => {
+            serde_json::from_slice(&content.collect::<Result<Vec<_>, _>>()?).map_err(|err| {
+                crate::Error::from(format!(
+                    "bad unseal key in collection {}: {err}",
+                    key_collection.hash()
+                ))
+            })?
+        }
+        None => return Ok(None),
+    };
+
+    let sealed = CollectionRef::new(key.sealed);
+    let (encrypted, content) = match (
+        item_content(&sealed, SEALED_ITEM).await?,
+        item_content(&sealed, SEALED_CONTENT_ITEM).await?,
+    ) {
+        (Some(encrypted), Some(content)) => (
+            bincode::deserialize::<OpaqueEncrypted>(&encrypted.collect::<Result<Vec<_>, _>>()?)?,
+            content,
+        ),
+        _ => {
+            log::warn!("sealed collection {} not found", key.sealed);
+            return Ok(None);
+        }
+    };
+
+    let sealed_collection: SealedCollection =
+        encrypted.decrypt_with(&TransferCipher::new(&key.key, &key.nonce))?;
+
+    let mut content = UnsealingIter::new(content, key.key, key.nonce);
+    let mut objects = vec![];
+
+    for item in sealed_collection.items {
+        let mut object_content = content.by_ref().take(item.size);
+        let (_, header) = ObjectHeader::read(&mut object_content)?;
+        let object = ObjectRef::build(header, false, object_content)?;
+        objects.push((item.path, object));
+    }
+
+    let collection = CollectionRef::build_with(
+        sealed_collection.is_draft,
+        objects,
+        sealed_collection.metadata,
+        sealed_collection.content_warnings,
+        sealed_collection.inventory_shard_size,
+        sealed_collection.prefetch,
+        BTreeMap::new(),
+    )?;
+
+    if collection.hash() != key.collection {
+        // Leave the garbage to the vacuum.
+        return Err(crate::Error::ValidationFailed(format!(
+            "sealed collection {} unseals to {}, not to {}",
+            key.sealed,
+            collection.hash(),
+            key.collection
+        )));
+    }
+
+    log::info!(
+        "Unsealed collection {} from key collection {}",
+        collection.hash(),
+        key_collection.hash()
+    );
+
+    db().put_cf(
+        Table::Unsealed.get(),
+        key_collection.hash(),
+        collection.hash(),
+    )?;
+
+    Ok(Some(collection))
+}
+
+#[test]
+fn unseals_what_was_sealed_in_blocks() {
+    let (key, nonce) = (Hash::rand(), Hash::rand());
+    let content = (0..SEAL_BLOCK_SIZE * 2 + 10)
+        .map(|i| i as u8)
+        .collect::<Vec<_>>();
+
+    let sealed = SealingIter::new(content.iter().copied().map(Ok), key, nonce)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let unsealed = UnsealingIter::new(sealed.into_iter().map(Ok), key, nonce)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(unsealed, content);
+}
@@ -0,0 +1,125 @@
+//! A bridge to BitTorrent: objects can be exposed as single-file torrents, so that big files
+//! can also ride on torrent swarms. The chunk hashes of an object cannot be reused, since
+//! BitTorrent uses SHA-1 over pieces whose sizes are powers of two, so the content is hashed
+//! again when the torrent is created.
+
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+
+use crate::cli;
+use crate::models::ObjectRef;
+
+/// The size of each piece of the torrent.
+const PIECE_LENGTH: usize = 256 * 1024;
+
+/// A bencoded value.
+enum Bencode {
+    Integer(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Bencode>),
+    Dictionary(BTreeMap<&'static str, Bencode>),
+}
+
+impl Bencode {
+    fn string(s: impl Into<String>) -> Bencode {
+        Bencode::Bytes(s.into().into_bytes())
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Bencode::Integer(i) => out.extend(format!("i{i}e").into_bytes()),
+            Bencode::Bytes(bytes) => {
+                out.extend(format!("{}:", bytes.len()).into_bytes());
+                out.extend(bytes);
+            }
+            Bencode::List(list) => {
+                out.push(b'l');
+                for item in list {
+                    item.encode(out);
+                }
+                out.push(b'e');
+            }
+            Bencode::Dictionary(dictionary) => {
+                // Keys are sorted, as bencoding requires, since this is a `BTreeMap`.
+                out.push(b'd');
+                for (key, value) in dictionary {
+                    Bencode::string(*key).encode(out);
+                    value.encode(out);
+                }
+                out.push(b'e');
+            }
+        }
+    }
+}
+
+/// Creates the `.torrent` file for an object, if the object exists locally. The web seeds
+/// configured in the command line are included, pointing to the object in each of them. The
+/// Samizdat hash of the object is kept in the torrent, outside the info dictionary, so it does
+/// not change the info hash.
+///
+/// This reads the whole object, so run it apart from the async code.
+pub fn torrent(object: &ObjectRef, name: Option<String>) -> Result<Option<Vec<u8>>, crate::Error> {
+    let iter = match object.iter_skip_header()? {
+        Some(iter) => iter,
+        None => return Ok(None),
+    };
+
+    let mut pieces = Vec::new();
+    let mut piece = Vec::with_capacity(PIECE_LENGTH);
+    let mut length = 0;
+
+    for byte in iter {
+        piece.push(byte?);
+        length += 1;
+
+        if piece.len() == PIECE_LENGTH {
+            pieces.extend(Sha1::digest(&piece));
+            piece.clear();
+        }
+    }
+
+    if !piece.is_empty() {
+        pieces.extend(Sha1::digest(&piece));
+    }
+
+    let info = BTreeMap::from([
+        ("length", Bencode::Integer(length)),
+        (
+            "name",
+            Bencode::string(name.unwrap_or_else(|| object.hash().to_string())),
+        ),
+        ("piece length", Bencode::Integer(PIECE_LENGTH as i64)),
+        ("pieces", Bencode::Bytes(pieces)),
+    ]);
+
+    let mut torrent = BTreeMap::from([
+        ("info", Bencode::Dictionary(info)),
+        (
+            "samizdat",
+            Bencode::Dictionary(BTreeMap::from([(
+                "object",
+                Bencode::string(object.hash().to_string()),
+            )])),
+        ),
+    ]);
+
+    if !cli().torrent_webseeds.is_empty() {
+        let webseeds = cli()
+            .torrent_webseeds
+            .iter()
+            .map(|base| {
+                Bencode::string(format!(
+                    "{}/_objects/{}",
+                    base.trim_end_matches('/'),
+                    object.hash()
+                ))
+            })
+            .collect();
+        torrent.insert("url-list", Bencode::List(webseeds));
+    }
+
+    let mut encoded = Vec::new();
+    Bencode::Dictionary(torrent).encode(&mut encoded);
+
+    Ok(Some(encoded))
+}
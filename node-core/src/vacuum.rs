@@ -0,0 +1,423 @@
+//! A process to keep the size of the database under control and to purge junk
+//! that is not used anymore.
+//!
+//! Objects bookmarked by the user or kept by a mirror are never deleted. The content of
+//! objects referenced by the series the user owns is only deleted under severe pressure (see
+//! [`VacuumPolicy`]). Objects uploaded with a time to live are deleted in the first vacuum
+//! round after they expire, whatever their use, unless they are bookmarked. Other than that, the vacuum never
+//! deletes objects that are being imported (see [`InFlight`]), that are items of a
+//! [`CollectionBuilder`] or that were built or imported less than `--vacuum-min-age` seconds
+//! ago: these are usually about to be served to whoever asked for them and deleting them
+//! would make the request fail.
+
+use decorum::NotNan;
+use rocksdb::{IteratorMode, WriteBatch};
+use serde_derive::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::time::{sleep, Instant};
+
+use samizdat_common::heap_entry::HeapEntry;
+use samizdat_common::Hash;
+
+use crate::cli::cli;
+use crate::db::{db, Table};
+use crate::events::{self, Event};
+use crate::models::{
+    BookmarkType, CollectionBuilder, CollectionItem, Droppable, ObjectRef, ObjectStatistics,
+    UsePrior,
+};
+
+lazy_static::lazy_static! {
+    /// The objects being imported, with the number of imports of each one.
+    static ref IN_FLIGHT: Mutex<BTreeMap<Hash, usize>> = Mutex::default();
+}
+
+/// Keeps an object being imported out of the reach of the vacuum, for as long as it lives.
+#[must_use]
+pub struct InFlight(Hash);
+
+impl InFlight {
+    pub fn new(hash: Hash) -> InFlight {
+        *IN_FLIGHT.lock().expect("poisoned").entry(hash).or_default() += 1;
+        InFlight(hash)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().expect("poisoned");
+
+        if let Some(count) = in_flight.get_mut(&self.0) {
+            *count -= 1;
+
+            if *count == 0 {
+                in_flight.remove(&self.0);
+            }
+        }
+    }
+}
+
+/// Whether an object is being imported.
+fn is_in_flight(hash: &Hash) -> bool {
+    IN_FLIGHT.lock().expect("poisoned").contains_key(hash)
+}
+
+/// Status for a vacuum task.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum VacuumStatus {
+    /// Storage is within allowed parameters.
+    Unnecessary,
+    /// Removed all disposable content, but could not achieve the desired maximum size.
+    Insufficient,
+    /// Storage has run and was able to reduce the storage size.
+    Done,
+}
+
+/// What the vacuum may do with an object, given its bookmarks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Eviction {
+    /// Bookmarked by the user or pinned by a mirror.
+    Never,
+    /// Referenced by the series the user owns: deleted only under severe pressure, keeping
+    /// the bookmark, so that it is fetched again when needed.
+    Severe,
+    /// Cached content, deleted whenever the storage is over the limit.
+    Always,
+}
+
+impl Eviction {
+    fn of(
+        is_marked: impl Fn(BookmarkType) -> Result<bool, crate::Error>,
+    ) -> Result<Eviction, crate::Error> {
+        if is_marked(BookmarkType::User)? || is_marked(BookmarkType::Mirror)? {
+            Ok(Eviction::Never)
+        } else if is_marked(BookmarkType::Reference)? {
+            Ok(Eviction::Severe)
+        } else {
+            Ok(Eviction::Always)
+        }
+    }
+}
+
+/// The key under which the vacuum policy is kept in [`Table::Global`].
+const VACUUM_POLICY_KEY: &[u8] = b"vacuum_policy";
+
+/// How the vacuum chooses what to delete. Objects bookmarked by the user and the content of
+/// mirrored series are never deleted, whatever the policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VacuumPolicy {
+    /// How many times over `--max-storage` the storage must be for the vacuum to delete the
+    /// content referenced by the series the user owns, least useful first. Their bookmarks are
+    /// kept, so that they are fetched again when needed.
+    pub severe_pressure: f64,
+    /// (s) How long a draft object out of bookmarks may go untouched before the vacuum deletes
+    /// it, whatever the size of the storage. If not set, drafts are only deleted to save
+    /// space, like any other object.
+    pub stale_draft_age: Option<i64>,
+}
+
+impl Default for VacuumPolicy {
+    fn default() -> VacuumPolicy {
+        VacuumPolicy {
+            severe_pressure: 1.5,
+            stale_draft_age: Some(7 * 86_400),
+        }
+    }
+}
+
+/// The current vacuum policy.
+pub fn policy() -> Result<VacuumPolicy, crate::Error> {
+    Ok(db()
+        .get_cf(Table::Global.get(), VACUUM_POLICY_KEY)?
+        .map(|serialized| bincode::deserialize(&serialized))
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// Replaces the vacuum policy, which takes effect in the next vacuum round.
+pub fn set_policy(policy: VacuumPolicy) -> Result<VacuumPolicy, crate::Error> {
+    if policy.severe_pressure.is_nan() || policy.severe_pressure < 1.0 {
+        return Err(crate::Error::ValidationFailed(format!(
+            "severe pressure must be at least 1, got {}",
+            policy.severe_pressure
+        )));
+    }
+
+    if policy.stale_draft_age.is_some_and(|age| age < 0) {
+        return Err(crate::Error::ValidationFailed(
+            "stale draft age cannot be negative".to_owned(),
+        ));
+    }
+
+    db().put_cf(
+        Table::Global.get(),
+        VACUUM_POLICY_KEY,
+        bincode::serialize(&policy).expect("can serialize"),
+    )?;
+
+    Ok(policy)
+}
+
+/// Run a vacuum round in the database.
+pub fn vacuum() -> Result<VacuumStatus, crate::Error> {
+    let policy = policy()?;
+    let max_storage = cli().max_storage * 1_000_000;
+
+    // Do the vacuum operation atomically to avoid mishaps (resource leakage):
+    let mut batch = WriteBatch::default();
+    let mut dropped = BTreeSet::new();
+
+    // Abandoned builders go, but the objects of the others stay:
+    let building = CollectionBuilder::drop_expired()?;
+    let is_held = |hash: &Hash| is_in_flight(hash) || building.contains(hash);
+
+    // Define a prior for use:
+    // TODO: how to calibrate correctly?
+    let use_prior = UsePrior::default();
+
+    // Expired objects go first, whatever their use, unless somebody bookmarked them since:
+    let now = chrono::Utc::now();
+    for (key, value) in db().iterator_cf(Table::ObjectExpiries.get(), IteratorMode::Start) {
+        let expires_at: chrono::DateTime<chrono::Utc> = bincode::deserialize(&value)?;
+        let object = ObjectRef::new(Hash::new(key));
+
+        if expires_at <= now
+            && Eviction::of(|ty| object.bookmark(ty).is_marked())? == Eviction::Always
+            && !is_held(object.hash())
+        {
+            log::info!("Object {} expired at {expires_at}", object.hash());
+            object.drop_if_exists_with(&mut batch)?;
+            dropped.insert(*object.hash());
+        }
+    }
+
+    // Test what is good and what isn't, disposing of stale drafts on the way:
+    let min_created_at = now - chrono::Duration::seconds(cli().vacuum_min_age);
+    let stale_draft_touched_at = policy
+        .stale_draft_age
+        .map(|age| now - chrono::Duration::seconds(age));
+    let mut total_size = 0;
+    let mut heap = BinaryHeap::new();
+    for (key, value) in db().iterator_cf(Table::ObjectStatistics.get(), IteratorMode::Start) {
+        let statistics: ObjectStatistics = bincode::deserialize(&value)?;
+        let object = ObjectRef::new(Hash::new(key));
+
+        if dropped.contains(object.hash()) {
+            continue;
+        }
+
+        let is_stale = stale_draft_touched_at
+            .is_some_and(|touched_at| statistics.last_touched_at() < touched_at);
+        if is_stale && object.is_draft()? && !object.is_bookmarked()? && !is_held(object.hash()) {
+            object.drop_if_exists_with(&mut batch)?;
+            dropped.insert(*object.hash());
+            continue;
+        }
+
+        total_size += statistics.size();
+
+        // Too young to die:
+        if statistics.created_at() > min_created_at {
+            continue;
+        }
+
+        heap.push(HeapEntry {
+            priority: Reverse(NotNan::from(statistics.byte_usefulness(&use_prior))),
+            content: (object, statistics.size()),
+        });
+    }
+
+    // Prune until you get under the threshold, setting aside what the user's series reference:
+    let was_over = total_size >= max_storage;
+    let is_severe = total_size as f64 >= max_storage as f64 * policy.severe_pressure;
+    let mut referenced = Vec::new();
+    while total_size >= max_storage {
+        if let Some(HeapEntry {
+            content: (object, size),
+            ..
+        }) = heap.pop()
+        {
+            if is_held(object.hash()) {
+                continue;
+            }
+
+            match Eviction::of(|ty| object.bookmark(ty).is_marked())? {
+                Eviction::Never => continue,
+                Eviction::Severe => {
+                    referenced.push((object, size));
+                    continue;
+                }
+                Eviction::Always => {}
+            }
+
+            object.drop_if_exists_with(&mut batch)?;
+            dropped.insert(*object.hash());
+            total_size -= size;
+        } else {
+            break;
+        }
+    }
+
+    // Under severe pressure, referenced content goes too:
+    if is_severe {
+        for (object, size) in referenced {
+            if total_size < max_storage {
+                break;
+            }
+
+            log::info!("Removing content of referenced object {:?}", object);
+            object.drop_content_with(&mut batch)?;
+            total_size -= size;
+        }
+    }
+
+    // If within limits and there is nothing stale, very ok!
+    let status = if total_size >= max_storage {
+        VacuumStatus::Insufficient
+    } else if was_over || !dropped.is_empty() {
+        VacuumStatus::Done
+    } else {
+        return Ok(VacuumStatus::Unnecessary);
+    };
+
+    log::debug!("to drop: {:#?}", dropped);
+
+    // Prune items:
+    for (_, value) in db().iterator_cf(Table::CollectionItems.get(), IteratorMode::Start) {
+        let item: CollectionItem = bincode::deserialize(&value)?;
+        if dropped.contains(item.inclusion_proof.claimed_value()) {
+            item.drop_if_exists_with(&mut batch)?;
+        }
+    }
+
+    // Apply all changes atomically:
+    db().write(batch)?;
+
+    events::emit(Event::VacuumCompleted { status });
+
+    Ok(status)
+}
+
+/// Runs vacuum tasks forever.
+pub async fn run_vacuum_daemon() {
+    const TIMING_BUFFER_SIZE: usize = 7;
+    const VACUUM_TIMESHARE: f64 = 0.05;
+    const MIN_INTERLUDE: Duration = Duration::from_secs(30);
+
+    let mut last_timings = VecDeque::new();
+    let mut push_timing = |timing| {
+        last_timings.push_back(timing);
+
+        if last_timings.len() > TIMING_BUFFER_SIZE {
+            last_timings.pop_front();
+        }
+
+        last_timings.iter().copied().sum::<Duration>()
+    };
+
+    loop {
+        crate::lifecycle::wait_until_active().await;
+
+        let start = Instant::now();
+        let vacuum_task = Handle::current().spawn_blocking(|| {
+            log::debug!("vacuum task started");
+
+            match vacuum() {
+                Ok(VacuumStatus::Unnecessary | VacuumStatus::Done) => {}
+                Ok(VacuumStatus::Insufficient) => {
+                    log::warn!("vacuum task was insufficient to bring storage size down")
+                }
+                Err(err) => log::error!("vacuum task error: {}", err),
+            }
+
+            log::debug!("vacuum task ended");
+        });
+
+        if let Err(err) = vacuum_task.await {
+            log::error!("vacuum task panicked: {}", err);
+        }
+
+        let end = Instant::now();
+
+        log::debug!("vacuum task took {:?}", end - start);
+
+        let moving_avg_timing = push_timing(end - start);
+        let interlude = moving_avg_timing.mul_f64(1. / VACUUM_TIMESHARE - 1.);
+
+        sleep(if interlude > MIN_INTERLUDE {
+            interlude
+        } else {
+            MIN_INTERLUDE
+        })
+        .await;
+    }
+}
+
+#[test]
+fn vacuum_keeps_marked_objects_by_pressure() {
+    use crate::models::ObjectHeader;
+
+    let data = std::env::temp_dir().join(format!("samizdat-vacuum-{}", std::process::id()));
+    crate::cli::init_cli_from([
+        "samizdat-node".as_ref(),
+        "--data".as_ref(),
+        data.as_os_str(),
+        "--max-storage=1".as_ref(),
+        "--vacuum-min-age=0".as_ref(),
+    ])
+    .unwrap();
+    crate::db::init_db().unwrap();
+
+    // Each object takes about a third of the allowed storage:
+    let build = |byte: u8, ty: Option<BookmarkType>| {
+        let header = ObjectHeader::new("application/octet-stream".to_owned(), false).unwrap();
+        let content = std::iter::repeat_n(byte, 300_000).map(Ok);
+        let object = ObjectRef::build(header, ty == Some(BookmarkType::User), content).unwrap();
+
+        if let Some(ty @ (BookmarkType::Reference | BookmarkType::Mirror)) = ty {
+            object.bookmark(ty).mark().unwrap();
+        }
+
+        object
+    };
+    let exists = |object: &ObjectRef| object.metadata().unwrap().is_some();
+
+    let user = build(1, Some(BookmarkType::User));
+    let mirror = build(2, Some(BookmarkType::Mirror));
+    let references = [
+        build(3, Some(BookmarkType::Reference)),
+        build(4, Some(BookmarkType::Reference)),
+    ];
+    let cached = build(5, None);
+
+    // Normal pressure: only cached content goes, which is not enough.
+    set_policy(VacuumPolicy {
+        severe_pressure: 10.0,
+        stale_draft_age: None,
+    })
+    .unwrap();
+    assert!(matches!(vacuum().unwrap(), VacuumStatus::Insufficient));
+    assert!(!exists(&cached));
+    assert!(exists(&user) && exists(&mirror));
+    assert!(references.iter().all(exists));
+
+    // Severe pressure: referenced content goes until under the limit, keeping the bookmark.
+    set_policy(VacuumPolicy {
+        severe_pressure: 1.0,
+        stale_draft_age: None,
+    })
+    .unwrap();
+    assert!(matches!(vacuum().unwrap(), VacuumStatus::Done));
+    assert!(exists(&user) && exists(&mirror));
+    assert_eq!(references.iter().filter(|object| exists(object)).count(), 1);
+    for object in &references {
+        assert!(object
+            .bookmark(BookmarkType::Reference)
+            .is_marked()
+            .unwrap());
+    }
+}
@@ -0,0 +1,255 @@
+//! The panic wipe, for users in physical danger: deletes everything that links this machine to
+//! the content it reads and publishes, i.e., series owner keys, identities, subscriptions and
+//! the whole object store. Optionally, a _decoy_ profile is kept, and the CLI is switched to
+//! it, so that the machine still looks like it runs an ordinary Samizdat node.
+//!
+//! The wipe can be triggered through the `/_wipe` route or by authenticating with the duress
+//! token (see `--duress-token`), which is accepted as if it were the access token, but only
+//! after the database of this node was emptied, so that the request made under duress finds
+//! nothing. This makes the first such request take noticeably longer than usual. The other
+//! profiles are shredded and the database is compacted in the background afterwards. Only the
+//! first request with the duress token wipes anything. Nothing about the wipe is logged.
+//!
+//! Deleted files are overwritten before being removed. Deleted database entries, however, are
+//! only compacted away: RocksDB writes the surviving entries to new files and removes the old
+//! ones, without overwriting them, so the deleted data may still be recovered from the disk
+//! until the space is reused. Wear leveling in SSDs and copy-on-write filesystems defeat
+//! overwriting files just as well: full-disk encryption is the only real guarantee.
+
+use rocksdb::{IteratorMode, WriteBatch};
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Once;
+use strum::IntoEnumIterator;
+
+use samizdat_common::profiles;
+
+use crate::chunk_cache;
+use crate::cli;
+use crate::db::{db, Table};
+
+/// The size of the blocks of zeroes used to overwrite files.
+const SHRED_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Makes sure only the first request with the duress token wipes the node, while the requests
+/// coming at the same time wait for the database to be emptied.
+static DURESS_WIPE: Once = Once::new();
+
+/// What to wipe.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WipeRequest {
+    /// Also wipe all other profiles in this machine, including the default one.
+    #[serde(default)]
+    pub everything: bool,
+    /// A profile to be kept untouched and to be used by the CLI from now on.
+    #[serde(default)]
+    pub decoy: Option<String>,
+}
+
+/// What was wiped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WipeReport {
+    /// The number of database entries deleted from this node.
+    pub entries: usize,
+    /// The data folders of other profiles that were shredded.
+    pub profiles: Vec<String>,
+}
+
+/// The tables kept by the wipe. These only tell which version of the node wrote the database.
+fn is_kept(table: Table) -> bool {
+    matches!(table, Table::Migrations)
+}
+
+/// Overwrites a file with zeroes and removes it.
+fn shred_file(path: &Path) -> Result<(), io::Error> {
+    let len = fs::metadata(path)?.len() as usize;
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let zeroes = vec![0; SHRED_BLOCK_SIZE];
+
+    let mut written = 0;
+    while written < len {
+        let block = usize::min(SHRED_BLOCK_SIZE, len - written);
+        file.write_all(&zeroes[..block])?;
+        written += block;
+    }
+
+    file.sync_all()?;
+    drop(file);
+
+    fs::remove_file(path)
+}
+
+/// Shreds all files in a folder, recursively, skipping the entries in `skip`.
+fn shred_dir(path: &Path, skip: &[&Path]) -> Result<(), io::Error> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let entry_path = entry.path();
+
+        if skip.contains(&entry_path.as_path()) {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            shred_dir(&entry_path, skip)?;
+            fs::remove_dir(&entry_path).ok();
+        } else {
+            shred_file(&entry_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes all entries in the database of this node. See [`compact_db`] for getting rid of
+/// them in the database files.
+fn wipe_db() -> Result<usize, crate::Error> {
+    let mut batch = WriteBatch::default();
+    let mut entries = 0;
+
+    for table in Table::iter().filter(|&table| !is_kept(table)) {
+        for (key, _) in db().iterator_cf(table.get(), IteratorMode::Start) {
+            batch.delete_cf(table.get(), key);
+            entries += 1;
+        }
+    }
+
+    db().write(batch)?;
+    chunk_cache::clear();
+    crate::http::forget_usage();
+
+    Ok(entries)
+}
+
+/// Deleted values linger in the database files until compacted. Compaction only removes the
+/// old files, though, which are not overwritten.
+fn compact_db() -> Result<(), crate::Error> {
+    for table in Table::iter() {
+        db().flush_cf(table.get())?;
+        db().compact_range_cf(table.get(), None::<&[u8]>, None::<&[u8]>);
+    }
+
+    Ok(())
+}
+
+/// Wipes the database of this node and the files holding copies of it. The database still
+/// needs compacting (see [`compact_db`]).
+fn wipe_this_node() -> Result<usize, crate::Error> {
+    let entries = wipe_db()?;
+
+    let crashes = cli().data.join("crashes");
+    if crashes.exists() {
+        shred_dir(&crashes, &[])?;
+    }
+
+    // The backups made before migrating the database hold the same as the database. They
+    // cannot be overwritten, though, since they are hard links to files the database may
+    // still be using:
+    let backups = cli().data.join("backups");
+    if backups.exists() {
+        fs::remove_dir_all(&backups)?;
+    }
+
+    Ok(entries)
+}
+
+/// Wipes this node and, if asked for, all other profiles in this machine.
+/// Nothing is logged, since logs would tell that a wipe happened.
+pub fn wipe(request: &WipeRequest) -> Result<WipeReport, crate::Error> {
+    let base = &cli().base_data;
+    let mut report = WipeReport::default();
+
+    if let Some(decoy) = &request.decoy {
+        if profiles::get(base, decoy)?.is_none() {
+            return Err(format!("decoy profile {decoy:?} does not exist").into());
+        }
+    }
+
+    // The decoy itself is left untouched, even if it is this node:
+    if is_wiped(request) {
+        report.entries = wipe_this_node()?;
+        compact_db()?;
+    }
+
+    wipe_other_profiles(request, &mut report)?;
+
+    Ok(report)
+}
+
+/// Whether this node is wiped, i.e., is not the decoy.
+fn is_wiped(request: &WipeRequest) -> bool {
+    request.decoy.is_none() || request.decoy != cli().profile
+}
+
+/// Shreds the other profiles, if asked for, and switches the CLI to the decoy, if any.
+fn wipe_other_profiles(request: &WipeRequest, report: &mut WipeReport) -> Result<(), crate::Error> {
+    let base = &cli().base_data;
+
+    if request.everything {
+        // Other nodes may still be running. Their files are gone from the disk all the same.
+        for profile in profiles::list(base)? {
+            if Some(&profile.name) == request.decoy.as_ref()
+                || Some(&profile.name) == cli().profile.as_ref()
+            {
+                continue;
+            }
+
+            let data = profile.data(base);
+            if data.exists() {
+                shred_dir(&data, &[])?;
+                fs::remove_dir(&data).ok();
+            }
+
+            // Not even the names of the profiles should be left behind:
+            profiles::remove(base, &profile.name)?;
+            report.profiles.push(profile.name);
+        }
+
+        // The default profile, which holds all the others:
+        if cli().profile.is_some() {
+            shred_dir(base, &[&base.join("profiles")])?;
+            report.profiles.push("default".to_owned());
+        }
+    }
+
+    if request.decoy.is_some() {
+        profiles::set_current(base, request.decoy.as_deref())?;
+    } else if request.everything {
+        // This is the only profile left:
+        profiles::set_current(base, cli().profile.as_deref())?;
+    }
+
+    Ok(())
+}
+
+/// Wipes everything but the decoy profile, after the duress token was used. The database of
+/// this node is emptied before this returns; the rest goes on in the background. This is a
+/// no-op after the first call. Even failures are not logged.
+pub fn wipe_under_duress() {
+    DURESS_WIPE.call_once(|| {
+        // A missing decoy must not stop the wipe:
+        let base = &cli().base_data;
+        let decoy = cli()
+            .decoy_profile
+            .clone()
+            .filter(|decoy| matches!(profiles::get(base, decoy), Ok(Some(_))));
+        let request = WipeRequest {
+            everything: true,
+            decoy,
+        };
+
+        let is_wiped = is_wiped(&request);
+        if is_wiped {
+            wipe_this_node().ok();
+        }
+
+        tokio::task::spawn_blocking(move || {
+            if is_wiped {
+                compact_db().ok();
+            }
+
+            wipe_other_profiles(&request, &mut WipeReport::default()).ok();
+        });
+    });
+}
@@ -0,0 +1,58 @@
+// Service worker generated by a Samizdat node, keeping a series available offline in this
+// browser. The current edition is cached when the worker is installed. Content is always
+// asked for to the network first, falling back to the cache when the network is gone.
+// A new edition comes with a new worker, which replaces the cache of the previous one.
+
+const CACHE_PREFIX = "samizdat-{{ series }}-";
+const CACHE = CACHE_PREFIX + "{{ collection }}";
+const SCOPE = {{ scope|json }};
+const PRECACHE = {{ precache|json }};
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches
+      .open(CACHE)
+      .then((cache) => cache.addAll(PRECACHE))
+      .then(() => self.skipWaiting())
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys
+            .filter((key) => key.startsWith(CACHE_PREFIX) && key !== CACHE)
+            .map((key) => caches.delete(key))
+        )
+      )
+      .then(() => self.clients.claim())
+  );
+});
+
+self.addEventListener("fetch", (event) => {
+  const url = new URL(event.request.url);
+
+  if (event.request.method !== "GET" || !url.pathname.startsWith(SCOPE)) {
+    return;
+  }
+
+  event.respondWith(
+    fetch(event.request)
+      .then((response) => {
+        if (response.ok) {
+          const copy = response.clone();
+          caches.open(CACHE).then((cache) => cache.put(event.request, copy));
+        }
+
+        return response;
+      })
+      .catch(() =>
+        caches
+          .match(event.request, { ignoreSearch: true })
+          .then((cached) => cached || Response.error())
+      )
+  );
+});
@@ -0,0 +1,806 @@
+//! An ActivityPub bridge: each series is exposed as an actor whose outbox holds its editions,
+//! so that Fediverse users can follow a Samizdat publication. New editions are found by polling
+//! the node and are then delivered to the inboxes of the followers of the series.
+//!
+//! Follows and unfollows must be signed with HTTP Signatures by the key the actor publishes in
+//! its own document, as servers in the Fediverse do, so that nobody can follow or unfollow on
+//! behalf of somebody else. The inbox of a new follower is also taken from that document, so that
+//! nobody can make the proxy deliver to arbitrary URLs. Actors and inboxes must be https URLs
+//! resolving to public addresses, so that nobody can make the proxy reach into the machine it
+//! runs on or into its private network.
+
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use rsa::pkcs8::{FromPrivateKey, FromPublicKey, ToPrivateKey, ToPublicKey};
+use rsa::{PaddingScheme, PublicKey, RsaPrivateKey, RsaPublicKey};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use std::{fs, io};
+use tokio::sync::Mutex;
+use warp::Filter;
+
+use crate::balanced_or_tree;
+use crate::cli::cli;
+
+/// The media type of ActivityPub documents.
+const ACTIVITY_JSON: &str = "application/activity+json";
+/// The ActivityStreams collection of everybody.
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+/// The node from which series are read.
+const NODE: &str = "http://localhost:4510";
+/// The size of the RSA key used to sign deliveries.
+const KEY_BITS: usize = 2048;
+/// The maximum size of an incoming activity.
+const MAX_ACTIVITY_SIZE: u64 = 64_000;
+/// How far the date of a signed activity may be from the current time, so that captured
+/// activities cannot be replayed forever.
+const MAX_CLOCK_SKEW: chrono::Duration = chrono::Duration::hours(1);
+/// The maximum number of redirects followed when talking to other servers.
+const MAX_REDIRECTS: usize = 10;
+
+lazy_static! {
+    static ref CLIENT: reqwest::Client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("can build client");
+    /// Serializes the changes to the followers files.
+    static ref FOLLOWERS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// The client for talking to other servers in the Fediverse.
+static REMOTE_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// The client for talking to other servers in the Fediverse, which only reaches public
+/// addresses over https (see [`check_remote_url`]).
+fn remote_client() -> &'static reqwest::Client {
+    REMOTE_CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(reqwest::redirect::Policy::custom(|attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if let Err(err) = check_remote_url(attempt.url()) {
+                    attempt.error(err)
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()
+            .expect("can build client")
+    })
+}
+
+/// Whether an address is neither in this machine nor in a private network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast())
+        }
+        IpAddr::V6(ip) => {
+            !(ip.is_loopback()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+                || ip.is_unspecified())
+        }
+    }
+}
+
+/// Checks whether a URL of another server may be reached: it must be https and, if its host is
+/// an address, the address must be public. Names are checked when resolved (see
+/// [`PublicResolver`]).
+fn check_remote_url(url: &reqwest::Url) -> Result<(), String> {
+    if url.scheme() != "https" {
+        return Err(format!("{url} is not https"));
+    }
+
+    let ip = match url.host() {
+        Some(url::Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(url::Host::Ipv6(ip)) => IpAddr::V6(ip),
+        Some(url::Host::Domain(_)) => return Ok(()),
+        None => return Err(format!("{url} has no host")),
+    };
+
+    if !is_public(ip) {
+        return Err(format!("{url} is not a public address"));
+    }
+
+    Ok(())
+}
+
+/// Parses the URL of an actor or of an inbox (see [`check_remote_url`]).
+fn parse_remote_url(url: &str) -> Result<reqwest::Url, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|err| format!("bad URL {url}: {err}"))?;
+    check_remote_url(&parsed)?;
+    Ok(parsed)
+}
+
+/// Resolves the names of other servers, refusing names that resolve to any address that is
+/// not public. Since this is what the client connects to, this also holds after redirects.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: hyper::client::connect::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .collect::<Vec<_>>();
+
+            if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(format!(
+                    "{} resolves to non-public address {}",
+                    name.as_str(),
+                    addr.ip()
+                )
+                .into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// The key with which all actors sign their deliveries.
+static KEY: OnceLock<RsaPrivateKey> = OnceLock::new();
+
+fn key() -> &'static RsaPrivateKey {
+    KEY.get().expect("activitypub not initialized")
+}
+
+fn data_dir<'a>() -> Option<&'a PathBuf> {
+    cli().activitypub_data.as_ref()
+}
+
+/// Loads the signing key from the data directory, creating it on the first run.
+pub fn init_activitypub() -> Result<(), io::Error> {
+    let data_dir = data_dir().expect("activitypub is enabled");
+    fs::create_dir_all(data_dir.join("followers"))?;
+    let key_path = data_dir.join("actor.pem");
+
+    let key = if key_path.exists() {
+        RsaPrivateKey::from_pkcs8_pem(&fs::read_to_string(&key_path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?
+    } else {
+        log::info!("Creating ActivityPub key at {key_path:?}");
+        let key = RsaPrivateKey::new(&mut rand::thread_rng(), KEY_BITS)
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        let pem = key
+            .to_pkcs8_pem()
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        fs::write(&key_path, pem.as_bytes())?;
+        key
+    };
+
+    KEY.set(key).expect("activitypub initialized only once");
+
+    Ok(())
+}
+
+/// The public URL of this proxy.
+fn base_url() -> String {
+    if cli().https {
+        format!("https://{}", crate::DOMAIN)
+    } else {
+        format!("http://localhost:{}", cli().port.unwrap_or(8080))
+    }
+}
+
+fn actor_url(series: &str) -> String {
+    format!("{}/_activitypub/{series}", base_url())
+}
+
+/// Whether a string looks like the public key of a series. This also keeps series out of
+/// trouble when used in file names.
+fn is_series_key(series: &str) -> bool {
+    series.len() == 43
+        && series
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+/// The followers of a series.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Followers {
+    /// The inbox of each following actor.
+    inboxes: BTreeMap<String, String>,
+    /// The timestamp of the latest edition delivered to the followers.
+    last_delivered: Option<DateTime<Utc>>,
+}
+
+impl Followers {
+    fn path(series: &str) -> PathBuf {
+        data_dir()
+            .expect("activitypub is enabled")
+            .join("followers")
+            .join(format!("{series}.json"))
+    }
+
+    fn load(series: &str) -> Result<Followers, io::Error> {
+        match fs::read(Followers::path(series)) {
+            Ok(serialized) => serde_json::from_slice(&serialized)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Followers::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&self, series: &str) -> Result<(), io::Error> {
+        let serialized = serde_json::to_vec_pretty(self).expect("can serialize");
+        fs::write(Followers::path(series), serialized)
+    }
+}
+
+/// An edition of a series, as listed by the node.
+#[derive(Debug, Deserialize)]
+struct Edition {
+    collection: String,
+    timestamp: DateTime<Utc>,
+}
+
+/// Gets the public editions of a series from the node, latest first.
+async fn editions(series: &str) -> Result<Vec<Edition>, String> {
+    let response = CLIENT
+        .get(format!("{NODE}/_series/{series}/_editions"))
+        .send()
+        .await
+        .map_err(|err| format!("failed to reach node: {err}"))?
+        .bytes()
+        .await
+        .map_err(|err| format!("failed to read node response: {err}"))?;
+
+    serde_json::from_slice::<Result<Vec<Edition>, String>>(&response)
+        .map_err(|err| format!("bad node response: {err}"))?
+}
+
+/// The activity announcing an edition.
+fn create_activity(series: &str, edition: &Edition) -> Value {
+    let actor = actor_url(series);
+    let id = format!("{actor}/editions/{}", edition.timestamp.timestamp());
+    let url = format!("{}/_series/{series}/", base_url());
+    let content = format!(
+        "<p>New edition of <a href=\"{url}\">{url}</a> (collection <code>{}</code>)</p>",
+        edition.collection
+    );
+
+    json!({
+        "id": format!("{id}/activity"),
+        "type": "Create",
+        "actor": actor,
+        "published": edition.timestamp,
+        "to": [PUBLIC],
+        "cc": [format!("{actor}/followers")],
+        "object": {
+            "id": id,
+            "type": "Note",
+            "attributedTo": actor,
+            "published": edition.timestamp,
+            "to": [PUBLIC],
+            "cc": [format!("{actor}/followers")],
+            "url": url,
+            "content": content,
+        },
+    })
+}
+
+fn with_context(mut activity: Value) -> Value {
+    activity["@context"] = json!("https://www.w3.org/ns/activitystreams");
+    activity
+}
+
+/// Posts an activity to an inbox, signed with the key of the series actor, as required by
+/// most servers in the Fediverse.
+async fn deliver(series: &str, inbox: &str, activity: &Value) -> Result<(), String> {
+    let url = parse_remote_url(inbox)?;
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_owned(),
+        (None, _) => return Err(format!("inbox {inbox} has no host")),
+    };
+    let target = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_owned(),
+    };
+
+    let body = serde_json::to_vec(activity).expect("can serialize");
+    let date = Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    let digest = format!("SHA-256={}", base64::encode(Sha256::digest(&body)));
+    let signing_string =
+        format!("(request-target): post {target}\nhost: {host}\ndate: {date}\ndigest: {digest}");
+    let signature = key()
+        .sign(
+            PaddingScheme::new_pkcs1v15_sign(Some(rsa::Hash::SHA2_256)),
+            &Sha256::digest(signing_string.as_bytes()),
+        )
+        .map_err(|err| format!("failed to sign delivery: {err}"))?;
+    let signature = format!(
+        "keyId=\"{}#main-key\",algorithm=\"rsa-sha256\",headers=\"(request-target) host date \
+        digest\",signature=\"{}\"",
+        actor_url(series),
+        base64::encode(signature)
+    );
+
+    let response = remote_client()
+        .post(url)
+        .header("Content-Type", ACTIVITY_JSON)
+        .header("Date", date)
+        .header("Digest", digest)
+        .header("Signature", signature)
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| format!("failed to deliver to {inbox}: {err}"))?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("{inbox} responded {}", response.status()))
+    }
+}
+
+/// Gets the document an actor publishes about itself, which may be no bigger than an activity.
+async fn actor_document(actor: &str) -> Result<Value, String> {
+    let mut response = remote_client()
+        .get(parse_remote_url(actor)?)
+        .header("Accept", ACTIVITY_JSON)
+        .send()
+        .await
+        .map_err(|err| format!("failed to get actor {actor}: {err}"))?;
+
+    let mut document = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|err| format!("failed to read actor {actor}: {err}"))?
+    {
+        document.extend_from_slice(&chunk);
+
+        if document.len() as u64 > MAX_ACTIVITY_SIZE {
+            return Err(format!("actor document for {actor} is too big"));
+        }
+    }
+
+    serde_json::from_slice(&document)
+        .map_err(|err| format!("bad actor document for {actor}: {err}"))
+}
+
+/// Finds the inbox of an actor from its own document, preferring the shared inbox.
+fn actor_inbox(actor: &str, document: &Value) -> Result<String, String> {
+    document["endpoints"]["sharedInbox"]
+        .as_str()
+        .or_else(|| document["inbox"].as_str())
+        .map(str::to_owned)
+        .ok_or_else(|| format!("actor {actor} has no inbox"))
+}
+
+/// Finds a public key of an actor in its own document. Actors may publish more than one key.
+fn actor_key(actor: &str, document: &Value, key_id: &str) -> Result<RsaPublicKey, String> {
+    let keys = match &document["publicKey"] {
+        Value::Array(keys) => keys.iter().collect::<Vec<_>>(),
+        key => vec![key],
+    };
+    let pem = keys
+        .into_iter()
+        .find(|key| key["id"].as_str() == Some(key_id))
+        .and_then(|key| key["publicKeyPem"].as_str())
+        .ok_or_else(|| format!("actor {actor} has no key {key_id}"))?;
+
+    RsaPublicKey::from_public_key_pem(pem)
+        .map_err(|err| format!("bad key {key_id} for actor {actor}: {err}"))
+}
+
+/// The parts of a request to the inbox needed to check its HTTP signature.
+struct InboxRequest {
+    /// The path of the request, as in `(request-target)`.
+    target: String,
+    headers: http::HeaderMap,
+    body: bytes::Bytes,
+}
+
+impl InboxRequest {
+    /// Gets a header which must be present, as a string.
+    fn header(&self, name: &str) -> Result<&str, String> {
+        self.headers
+            .get(name)
+            .ok_or_else(|| format!("missing {name} header"))?
+            .to_str()
+            .map_err(|err| format!("bad {name} header: {err}"))
+    }
+
+    /// Checks that the request was signed by the key an actor publishes in its document.
+    fn verify_signature(&self, actor: &str, document: &Value) -> Result<(), String> {
+        let params = self
+            .header("signature")?
+            .split(',')
+            .filter_map(|param| param.split_once('='))
+            .map(|(name, value)| (name.trim(), value.trim().trim_matches('"')))
+            .collect::<BTreeMap<_, _>>();
+        let param = |name: &str| {
+            params
+                .get(name)
+                .copied()
+                .ok_or_else(|| format!("signature has no {name}"))
+        };
+
+        let signed_headers = param("headers")?.split(' ').collect::<Vec<_>>();
+        for required in ["(request-target)", "host", "date", "digest"] {
+            if !signed_headers.contains(&required) {
+                return Err(format!("signature does not cover {required}"));
+            }
+        }
+
+        let digest = format!("SHA-256={}", base64::encode(Sha256::digest(&self.body)));
+        if self.header("digest")? != digest {
+            return Err("digest does not match body".to_owned());
+        }
+
+        let date = DateTime::parse_from_rfc2822(self.header("date")?)
+            .map_err(|err| format!("bad date header: {err}"))?;
+        if (Utc::now() - date.with_timezone(&Utc)).abs() > MAX_CLOCK_SKEW {
+            return Err("signature is too old or from the future".to_owned());
+        }
+
+        let signing_string = signed_headers
+            .iter()
+            .map(|&name| match name {
+                "(request-target)" => Ok(format!("(request-target): post {}", self.target)),
+                name => Ok(format!("{name}: {}", self.header(name)?)),
+            })
+            .collect::<Result<Vec<_>, String>>()?
+            .join("\n");
+        let signature = base64::decode(param("signature")?)
+            .map_err(|err| format!("bad signature encoding: {err}"))?;
+
+        actor_key(actor, document, param("keyId")?)?
+            .verify(
+                PaddingScheme::new_pkcs1v15_sign(Some(rsa::Hash::SHA2_256)),
+                &Sha256::digest(signing_string.as_bytes()),
+                &signature,
+            )
+            .map_err(|_| format!("bad signature for actor {actor}"))
+    }
+}
+
+/// Handles an activity sent to the inbox of a series.
+async fn receive(series: &str, request: InboxRequest) -> Result<(), String> {
+    let activity: Value =
+        serde_json::from_slice(&request.body).map_err(|err| format!("bad activity: {err}"))?;
+    let actor = activity["actor"]
+        .as_str()
+        .ok_or("activity has no actor")?
+        .to_owned();
+
+    match activity["type"].as_str() {
+        Some("Follow") => {
+            if activity["object"].as_str() != Some(&actor_url(series)) {
+                return Err("follow is not for this series".to_owned());
+            }
+
+            let document = actor_document(&actor).await?;
+            request.verify_signature(&actor, &document)?;
+            let inbox = actor_inbox(&actor, &document)?;
+            parse_remote_url(&inbox)?;
+            {
+                let _guard = FOLLOWERS_LOCK.lock().await;
+                let mut followers = Followers::load(series).map_err(|err| err.to_string())?;
+                followers.inboxes.insert(actor.clone(), inbox.clone());
+                followers.save(series).map_err(|err| err.to_string())?;
+            }
+
+            log::info!("{actor} follows {series}");
+
+            let accept = with_context(json!({
+                "id": format!("{}#accept/{}", actor_url(series), rand::random::<u64>()),
+                "type": "Accept",
+                "actor": actor_url(series),
+                "object": activity,
+            }));
+            deliver(series, &inbox, &accept).await
+        }
+        Some("Undo") if activity["object"]["type"].as_str() == Some("Follow") => {
+            let document = actor_document(&actor).await?;
+            request.verify_signature(&actor, &document)?;
+
+            let _guard = FOLLOWERS_LOCK.lock().await;
+            let mut followers = Followers::load(series).map_err(|err| err.to_string())?;
+            followers.inboxes.remove(&actor);
+            followers.save(series).map_err(|err| err.to_string())?;
+
+            log::info!("{actor} unfollows {series}");
+
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Delivers the new editions of a series to its followers.
+async fn deliver_editions(series: &str) -> Result<(), String> {
+    let followers = {
+        let _guard = FOLLOWERS_LOCK.lock().await;
+        Followers::load(series).map_err(|err| err.to_string())?
+    };
+
+    if followers.inboxes.is_empty() {
+        return Ok(());
+    }
+
+    let editions = editions(series).await?;
+    let latest = match editions.first() {
+        Some(latest) => latest.timestamp,
+        None => return Ok(()),
+    };
+
+    // Followers only get what is published after they started to follow:
+    if let Some(last_delivered) = followers.last_delivered {
+        let mut inboxes = followers.inboxes.values().collect::<Vec<_>>();
+        inboxes.sort();
+        inboxes.dedup();
+
+        for edition in editions
+            .iter()
+            .rev()
+            .filter(|edition| edition.timestamp > last_delivered)
+        {
+            let activity = with_context(create_activity(series, edition));
+            for inbox in &inboxes {
+                if let Err(err) = deliver(series, inbox, &activity).await {
+                    log::warn!("failed to deliver edition of {series}: {err}");
+                }
+            }
+        }
+    }
+
+    // Followers may have changed in the meantime:
+    let _guard = FOLLOWERS_LOCK.lock().await;
+    let mut followers = Followers::load(series).map_err(|err| err.to_string())?;
+    followers.last_delivered = Some(latest);
+    followers.save(series).map_err(|err| err.to_string())
+}
+
+/// Polls the node for new editions of all followed series forever.
+pub async fn run_delivery() {
+    let mut interval = tokio::time::interval(Duration::from_secs(cli().activitypub_poll_interval));
+
+    loop {
+        interval.tick().await;
+
+        let followers_dir = data_dir()
+            .expect("activitypub is enabled")
+            .join("followers");
+        let entries = match fs::read_dir(&followers_dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::error!("failed to list {followers_dir:?}: {err}");
+                continue;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let series = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(series) if is_series_key(series) => series.to_owned(),
+                _ => continue,
+            };
+
+            if let Err(err) = deliver_editions(&series).await {
+                log::warn!("failed to deliver editions of {series}: {err}");
+            }
+        }
+    }
+}
+
+fn activity_reply(status: http::StatusCode, value: Value) -> http::Response<hyper::Body> {
+    http::Response::builder()
+        .status(status)
+        .header("Content-Type", ACTIVITY_JSON)
+        .body(hyper::Body::from(
+            serde_json::to_vec(&value).expect("can serialize"),
+        ))
+        .expect("valid response")
+}
+
+fn error_reply(status: http::StatusCode, message: String) -> http::Response<hyper::Body> {
+    http::Response::builder()
+        .status(status)
+        .header("Content-Type", "text/plain")
+        .body(hyper::Body::from(message))
+        .expect("valid response")
+}
+
+/// Only lets requests through if the bridge is enabled.
+fn enabled() -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::any()
+        .and_then(|| async move {
+            if data_dir().is_some() {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one()
+}
+
+/// Only matches valid series keys.
+fn series() -> impl Filter<Extract = (String,), Error = warp::Rejection> + Clone {
+    warp::path::param().and_then(|series: String| async move {
+        if is_series_key(&series) {
+            Ok(series)
+        } else {
+            Err(warp::reject::not_found())
+        }
+    })
+}
+
+pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    enabled().and(balanced_or_tree!(
+        get_webfinger(),
+        get_actor(),
+        get_outbox(),
+        get_followers(),
+        post_inbox(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct WebfingerQuery {
+    resource: String,
+}
+
+/// Finds series actors by `acct:<series key>@<proxy domain>`.
+fn get_webfinger() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!(".well-known" / "webfinger")
+        .and(warp::get())
+        .and(warp::query())
+        .map(|query: WebfingerQuery| {
+            let series = query
+                .resource
+                .strip_prefix("acct:")
+                .and_then(|account| account.split_once('@'))
+                .map(|(series, _domain)| series)
+                .filter(|series| is_series_key(series));
+
+            match series {
+                Some(series) => http::Response::builder()
+                    .header("Content-Type", "application/jrd+json")
+                    .body(hyper::Body::from(
+                        serde_json::to_vec(&json!({
+                            "subject": query.resource,
+                            "links": [{
+                                "rel": "self",
+                                "type": ACTIVITY_JSON,
+                                "href": actor_url(series),
+                            }],
+                        }))
+                        .expect("can serialize"),
+                    ))
+                    .expect("valid response"),
+                None => error_reply(http::StatusCode::NOT_FOUND, "no such series".to_owned()),
+            }
+        })
+}
+
+fn get_actor() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("_activitypub")
+        .and(series())
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(|series: String| {
+            let actor = actor_url(&series);
+            let public_key = RsaPublicKey::from(key())
+                .to_public_key_pem()
+                .expect("can encode public key");
+
+            activity_reply(
+                http::StatusCode::OK,
+                json!({
+                    "@context": [
+                        "https://www.w3.org/ns/activitystreams",
+                        "https://w3id.org/security/v1",
+                    ],
+                    "id": actor,
+                    "type": "Service",
+                    "preferredUsername": series,
+                    "name": format!("Samizdat series {series}"),
+                    "summary": "Editions of a series published on the Samizdat network.",
+                    "url": format!("{}/_series/{series}/", base_url()),
+                    "inbox": format!("{actor}/inbox"),
+                    "outbox": format!("{actor}/outbox"),
+                    "followers": format!("{actor}/followers"),
+                    "publicKey": {
+                        "id": format!("{actor}#main-key"),
+                        "owner": actor,
+                        "publicKeyPem": public_key,
+                    },
+                }),
+            )
+        })
+}
+
+fn get_outbox() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("_activitypub")
+        .and(series())
+        .and(warp::path!("outbox"))
+        .and(warp::get())
+        .and_then(|series: String| async move {
+            let response = match editions(&series).await {
+                Ok(editions) => activity_reply(
+                    http::StatusCode::OK,
+                    json!({
+                        "@context": "https://www.w3.org/ns/activitystreams",
+                        "id": format!("{}/outbox", actor_url(&series)),
+                        "type": "OrderedCollection",
+                        "totalItems": editions.len(),
+                        "orderedItems": editions
+                            .iter()
+                            .map(|edition| create_activity(&series, edition))
+                            .collect::<Vec<_>>(),
+                    }),
+                ),
+                Err(err) => error_reply(http::StatusCode::BAD_GATEWAY, err),
+            };
+
+            Ok(response) as Result<_, warp::Rejection>
+        })
+}
+
+fn get_followers() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("_activitypub")
+        .and(series())
+        .and(warp::path!("followers"))
+        .and(warp::get())
+        .map(|series: String| {
+            let total = Followers::load(&series)
+                .map(|followers| followers.inboxes.len())
+                .unwrap_or_default();
+
+            activity_reply(
+                http::StatusCode::OK,
+                json!({
+                    "@context": "https://www.w3.org/ns/activitystreams",
+                    "id": format!("{}/followers", actor_url(&series)),
+                    "type": "OrderedCollection",
+                    "totalItems": total,
+                }),
+            )
+        })
+}
+
+fn post_inbox() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("_activitypub")
+        .and(series())
+        .and(warp::path!("inbox"))
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::content_length_limit(MAX_ACTIVITY_SIZE))
+        .and(warp::body::bytes())
+        .and_then(
+            |series: String, path: warp::path::FullPath, headers, body| async move {
+                let request = InboxRequest {
+                    target: path.as_str().to_owned(),
+                    headers,
+                    body,
+                };
+
+                let response = match receive(&series, request).await {
+                    Ok(()) => error_reply(http::StatusCode::ACCEPTED, String::new()),
+                    Err(err) => {
+                        log::warn!("rejected activity for {series}: {err}");
+                        error_reply(http::StatusCode::BAD_REQUEST, err)
+                    }
+                };
+
+                Ok(response) as Result<_, warp::Rejection>
+            },
+        )
+}
@@ -1,6 +1,9 @@
 use std::io;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
+use crate::rate_limit::{parse_burst, PathRateLimit};
+
 #[derive(Debug, StructOpt)]
 pub struct Cli {
     /// The port on which to serve the proxy. This only has effect when serving HTTP only.
@@ -9,6 +12,47 @@ pub struct Cli {
     /// Whether to serve with HTTPS. This is meant for production only.
     #[structopt(long)]
     pub https: bool,
+    /// A directory for the state of the ActivityPub bridge, which lets Fediverse users follow
+    /// series. The bridge is only enabled if this is set.
+    #[structopt(long)]
+    pub activitypub_data: Option<PathBuf>,
+    /// (s) The interval between checks for new editions of the series followed through
+    /// ActivityPub.
+    #[structopt(long, default_value = "300")]
+    pub activitypub_poll_interval: u64,
+    /// The number of requests per second each client IP can make. Set to zero to disable.
+    #[structopt(long, default_value = "10")]
+    pub rate_limit: f64,
+    /// The number of requests each client IP can make at once, above the rate limit.
+    #[structopt(long, default_value = "40", parse(try_from_str = parse_burst))]
+    pub rate_burst: f64,
+    /// Rate limits for paths starting with a prefix, per client IP, as `PREFIX=RATE/BURST`,
+    /// e.g., `/_series=1/10`. These should cover the paths that make the node ask the network.
+    #[structopt(long)]
+    pub path_rate_limit: Vec<PathRateLimit>,
+    /// Shows clients over the rate limit a challenge page, instead of just refusing them.
+    /// Clients passing the challenge are exempt from the path rate limits for a while.
+    #[structopt(long)]
+    pub challenge: bool,
+    /// (s) How long browsers wait on the challenge page.
+    #[structopt(long, default_value = "5")]
+    pub challenge_delay: u64,
+    /// The number of leading zero bits of the proof of work that skips the wait.
+    #[structopt(long, default_value = "20")]
+    pub challenge_difficulty: u32,
+    /// (s) For how long proving work on a challenge exempts a client from the path rate limits.
+    #[structopt(long, default_value = "3600")]
+    pub challenge_pass_ttl: u64,
+    /// (s) For how long waiting on a challenge page, instead of proving work, exempts a client
+    /// from the path rate limits. Waiting costs clients nothing but time, so this should be
+    /// short.
+    #[structopt(long, default_value = "60")]
+    pub challenge_wait_pass_ttl: u64,
+    /// Path prefixes (e.g., `/_series/{key}/_live`, for the series of a dynamic app) whose
+    /// WebSocket connections are passed through to the node. Prefixes match whole segments.
+    /// Event streams (e.g., `/_kvstore/_events`) are always passed through.
+    #[structopt(long)]
+    pub passthrough_paths: Vec<String>,
 }
 
 static mut CLI: Option<Cli> = None;
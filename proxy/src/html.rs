@@ -16,9 +16,28 @@ struct ProxyedPage {
     body: String,
     rand: String,
     download_link: &'static str,
+    trust_warning: Option<&'static str>,
 }
 
-pub fn proxy_page(raw: &[u8], _entity: &str, _content_hash: &str) -> bytes::Bytes {
+/// The warning shown atop pages of series whose key was not verified by the node behind this
+/// proxy, given the trust level the node reports in `X-Samizdat-Trust`.
+fn trust_warning(trust: Option<&str>) -> Option<&'static str> {
+    match trust? {
+        "verified" => None,
+        "tofu" => Some(
+            "The key of this series was trusted on first use, but never verified. \
+            Anybody could be publishing it.",
+        ),
+        _ => Some("The key of this series is unverified. Nothing guarantees who publishes it."),
+    }
+}
+
+pub fn proxy_page(
+    raw: &[u8],
+    _entity: &str,
+    _content_hash: &str,
+    trust: Option<&str>,
+) -> bytes::Bytes {
     let source = &String::from_utf8_lossy(raw);
     let html = Html::parse_document(source);
     let head = html
@@ -38,8 +57,32 @@ pub fn proxy_page(raw: &[u8], _entity: &str, _content_hash: &str) -> bytes::Byte
         body,
         rand,
         download_link: SAMIZDAT_BLOG_PATH,
+        trust_warning: trust_warning(trust),
     }
     .render()
     .expect("can always render proxied page")
     .into()
 }
+
+#[derive(Template)]
+#[template(path = "error.html.jinja")]
+struct ErrorPage<'a> {
+    status: u16,
+    reason: &'a str,
+    message: &'a str,
+    download_link: &'static str,
+}
+
+/// The page shown for errors which the node reports in plain text, i.e., when the series
+/// has no error page of its own (see `_errors/` in the node) or when there is no node at all.
+pub fn error_page(status: http::StatusCode, message: &str) -> bytes::Bytes {
+    ErrorPage {
+        status: status.as_u16(),
+        reason: status.canonical_reason().unwrap_or("Error"),
+        message,
+        download_link: SAMIZDAT_BLOG_PATH,
+    }
+    .render()
+    .expect("can always render error page")
+    .into()
+}
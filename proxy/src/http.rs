@@ -1,78 +1,214 @@
 use mime::Mime;
+use std::net::SocketAddr;
 use warp::path::FullPath;
 use warp::Filter;
 
-use crate::html::proxy_page;
+use crate::balanced_or_tree;
+use crate::html::{error_page, proxy_page};
+use crate::passthrough;
+use crate::rate_limit::{self, PASS_COOKIE};
+
+/// Request headers passed along to the node, so that it can answer conditional requests and
+/// resume event streams.
+const FORWARDED_REQUEST_HEADERS: [&str; 4] = [
+    "If-None-Match",
+    "If-Modified-Since",
+    "Accept",
+    "Last-Event-ID",
+];
+
+/// Response headers passed back from the node, so that browsers can cache content.
+const FORWARDED_RESPONSE_HEADERS: [&str; 4] =
+    ["ETag", "Last-Modified", "Cache-Control", "Content-Language"];
+
+/// Response headers protecting pages from each other, passed back from the node. Everything
+/// served through the proxy shares its origin, just like in the node.
+const SECURITY_RESPONSE_HEADERS: [&str; 3] = [
+    "Content-Security-Policy",
+    "X-Frame-Options",
+    "Referrer-Policy",
+];
+
+/// Response headers signing the content, passed back from the node only when the content is
+/// passed back untouched (see `--sign-responses` in the node).
+const SIGNATURE_RESPONSE_HEADERS: [&str; 5] = [
+    "X-Samizdat-Object",
+    "X-Samizdat-Digest",
+    "X-Samizdat-Signed-Range",
+    "X-Samizdat-Signature",
+    "X-Samizdat-Signer",
+];
+
+/// The response header telling how much the node trusts the key of a series, passed back from
+/// the node and shown in a banner on web pages.
+const TRUST_RESPONSE_HEADER: &str = "X-Samizdat-Trust";
+
+/// Copies the cache headers that are present in `from` to a response under construction.
+fn forward_headers(
+    mut builder: http::response::Builder,
+    from: &http::HeaderMap,
+) -> http::response::Builder {
+    for name in FORWARDED_RESPONSE_HEADERS
+        .into_iter()
+        .chain(SECURITY_RESPONSE_HEADERS)
+    {
+        if let Some(value) = from.get(name) {
+            builder = builder.header(name, value);
+        }
+    }
+
+    if let Some(value) = from.get(TRUST_RESPONSE_HEADER) {
+        builder = builder.header(TRUST_RESPONSE_HEADER, value);
+    }
+
+    builder
+}
 
 pub fn api() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    crate::balanced_or_tree!(proxy())
+    balanced_or_tree!(rate_limit::pass(), passthrough::websocket(), proxy())
 }
 
 pub fn proxy() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path::full()
         .and(warp::get())
-        .and_then(|path: FullPath| async move {
-            thread_local! {
-                static CLIENT: reqwest::Client = reqwest::Client::builder()
-                    .redirect(reqwest::redirect::Policy::none())
-                    .build()
-                    .unwrap();
-            }
-
-            // Get entity and content hash from page path.
-            let mut split = path.as_str().split('/');
-            split.next().expect("always starts with /, right?");
-            let (entity, content_hash) = match (split.next(), split.next()) {
-                (None, None) => return Err(warp::reject()),
-                (Some(identity), None) => ("_identity", identity),
-                (Some(entity), Some(content_hash)) if entity.starts_with('_') => {
-                    (entity, content_hash)
+        .and(warp::header::headers_cloned())
+        .and(warp::addr::remote())
+        .and(warp::cookie::optional(PASS_COOKIE))
+        .and_then(
+            |path: FullPath,
+             headers: http::HeaderMap,
+             addr: Option<SocketAddr>,
+             pass: Option<String>| async move {
+                thread_local! {
+                    static CLIENT: reqwest::Client = reqwest::Client::builder()
+                        .redirect(reqwest::redirect::Policy::none())
+                        .build()
+                        .unwrap();
+                }
+
+                // Keep scrapers at bay:
+                if let Some(ip) = addr.map(|addr| addr.ip()) {
+                    let has_pass = rate_limit::has_pass(ip, pass.as_deref());
+                    if let Err(retry_after) = rate_limit::check(ip, path.as_str(), has_pass) {
+                        return Ok(rate_limit::limited(ip, path.as_str(), retry_after));
+                    }
                 }
-                (Some(identity), Some(_)) => ("_identity", identity),
-                (None, Some(_)) => unreachable!(),
-            };
-
-            // Query node for the web page:
-            let translated = format!("http://localhost:4510{}", path.as_str());
-            let response = CLIENT
-                .with(|client| client.get(translated).send())
-                .await
-                .unwrap();
-
-            let response = match response.status().as_u16() {
-                status @ 300..=399 => http::Response::builder()
-                    .status(status)
-                    .header("Location", response.headers().get("Location").unwrap())
-                    .body(hyper::body::Body::empty()),
-                status => {
-                    let content_type = response
-                        .headers()
-                        .get("Content-Type")
-                        .cloned()
-                        .unwrap_or_else(|| "text/plain".parse().expect("is valid header"));
-                    let body = response.bytes().await.unwrap();
-
-                    // If web page, do your shenanigans:
-                    let mime: Mime = content_type.to_str().unwrap_or_default().parse().unwrap();
-                    let proxied = if mime == mime::TEXT_HTML_UTF_8 || mime == mime::TEXT_HTML {
-                        proxy_page(body.as_ref(), entity, content_hash)
-                    } else {
-                        body
-                    };
-
-                    // Builsd response:
-                    http::Response::builder()
+
+                // Get entity and content hash from page path.
+                let mut split = path.as_str().split('/');
+                split.next().expect("always starts with /, right?");
+                let (entity, content_hash) = match (split.next(), split.next()) {
+                    (None, None) => return Err(warp::reject()),
+                    (Some(identity), None) => ("_identity", identity),
+                    (Some(entity), Some(content_hash)) if entity.starts_with('_') => {
+                        (entity, content_hash)
+                    }
+                    (Some(identity), Some(_)) => ("_identity", identity),
+                    (None, Some(_)) => unreachable!(),
+                };
+
+                // Query node for the web page:
+                let translated = format!("http://localhost:4510{}", path.as_str());
+                let mut request = CLIENT.with(|client| client.get(translated));
+                for name in FORWARDED_REQUEST_HEADERS {
+                    if let Some(value) = headers.get(name) {
+                        request = request.header(name, value);
+                    }
+                }
+                let response = match request.send().await {
+                    Ok(response) => response,
+                    Err(err) => {
+                        log::error!("failed to reach node: {err}");
+                        return Ok(http::Response::builder()
+                            .status(http::StatusCode::BAD_GATEWAY)
+                            .header("Content-Type", "text/html; charset=utf-8")
+                            .body(hyper::body::Body::from(error_page(
+                                http::StatusCode::BAD_GATEWAY,
+                                "The node behind this gateway is not answering",
+                            ))));
+                    }
+                };
+
+                let response = match response.status().as_u16() {
+                    304 => forward_headers(http::Response::builder(), response.headers())
+                        .status(304)
+                        .body(hyper::body::Body::empty()),
+                    status @ 300..=399 => http::Response::builder()
                         .status(status)
-                        .header("Content-Type", content_type)
-                        .body(hyper::body::Body::from(proxied))
+                        .header("Location", response.headers().get("Location").unwrap())
+                        .body(hyper::body::Body::empty()),
+                    status => {
+                        let content_type = response
+                            .headers()
+                            .get("Content-Type")
+                            .cloned()
+                            .unwrap_or_else(|| "text/plain".parse().expect("is valid header"));
+                        let mut builder =
+                            forward_headers(http::Response::builder(), response.headers());
+
+                        // Event streams never end. Pass them through as they come:
+                        let is_event_stream = content_type
+                            .to_str()
+                            .unwrap_or_default()
+                            .starts_with("text/event-stream");
+                        if is_event_stream {
+                            return Ok(builder
+                                .status(status)
+                                .header("Content-Type", content_type)
+                                .header("Cache-Control", "no-cache")
+                                .header("X-Accel-Buffering", "no")
+                                .body(hyper::body::Body::wrap_stream(response.bytes_stream())));
+                        }
+
+                        let signature_headers = SIGNATURE_RESPONSE_HEADERS
+                            .into_iter()
+                            .filter_map(|name| Some((name, response.headers().get(name)?.clone())))
+                            .collect::<Vec<_>>();
+                        let trust = response
+                            .headers()
+                            .get(TRUST_RESPONSE_HEADER)
+                            .and_then(|value| value.to_str().ok())
+                            .map(str::to_owned);
+                        let body = response.bytes().await.unwrap();
+
+                        // If web page, do your shenanigans (and the signature is no good anymore):
+                        let mime: Mime = content_type.to_str().unwrap_or_default().parse().unwrap();
+                        let is_html = mime == mime::TEXT_HTML_UTF_8 || mime == mime::TEXT_HTML;
+                        let status = http::StatusCode::from_u16(status).expect("valid status");
+                        let (content_type, proxied) = if is_html {
+                            (
+                                content_type,
+                                proxy_page(body.as_ref(), entity, content_hash, trust.as_deref()),
+                            )
+                        } else if status.is_client_error() || status.is_server_error() {
+                            // The series has no error page for this. Use ours:
+                            let message = String::from_utf8_lossy(&body);
+                            (
+                                "text/html; charset=utf-8".parse().expect("is valid header"),
+                                error_page(status, &message),
+                            )
+                        } else {
+                            for (name, value) in signature_headers {
+                                builder = builder.header(name, value);
+                            }
+
+                            (content_type, body)
+                        };
+
+                        // Builsd response:
+                        builder
+                            .status(status)
+                            .header("Content-Type", content_type)
+                            .body(hyper::body::Body::from(proxied))
+                    }
+                };
+
+                // HACK! Type system won't comply.
+                if true {
+                    Ok(response)
+                } else {
+                    Err(warp::reject())
                 }
-            };
-
-            // HACK! Type system won't comply.
-            if true {
-                Ok(response)
-            } else {
-                Err(warp::reject())
-            }
-        })
+            },
+        )
 }
@@ -1,7 +1,10 @@
+mod activitypub;
 mod cli;
 mod html;
 mod http;
 mod logger;
+mod passthrough;
+mod rate_limit;
 mod slow_compiler_workaround;
 
 use std::io;
@@ -18,10 +21,16 @@ async fn main() -> Result<(), io::Error> {
 
     cli::init_cli()?;
 
+    if cli().activitypub_data.is_some() {
+        activitypub::init_activitypub()?;
+        tokio::spawn(activitypub::run_delivery());
+    }
+
     // Describe server:
     let server = warp::get()
         .and(warp::path::end())
         .map(|| warp::reply::with_header(include_str!("index.html"), "Content-Type", "text/html"))
+        .or(activitypub::api())
         .or(http::api())
         .with(warp::log("api"));
 
@@ -0,0 +1,191 @@
+//! WebSocket connections passed through to the node, for the endpoints of dynamic apps (see
+//! `--passthrough-paths`). Connections are relayed message by message: each side of the proxy
+//! keeps its own connection alive, answering pings by itself.
+
+use futures::{future, SinkExt, StreamExt};
+use std::net::SocketAddr;
+use tokio_tungstenite::tungstenite;
+use warp::path::FullPath;
+use warp::ws::{Message, WebSocket, Ws};
+use warp::{Filter, Reply};
+
+use crate::cli::cli;
+use crate::rate_limit::{self, PASS_COOKIE};
+
+/// Whether a path is under any of the prefixes, segment by segment: `/_live` covers `/_live`
+/// and `/_live/more`, but not `/_lively`.
+fn is_under_any(path: &str, prefixes: &[String]) -> bool {
+    prefixes.iter().any(|prefix| {
+        let prefix = prefix.trim_end_matches('/');
+        match path.strip_prefix(prefix) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    })
+}
+
+/// Whether WebSocket connections to a path are passed through to the node.
+fn is_passthrough(path: &str) -> bool {
+    is_under_any(path, &cli().passthrough_paths)
+}
+
+/// A message from the client, as sent to the node.
+fn to_node(message: Message) -> Option<tungstenite::Message> {
+    if message.is_close() {
+        let frame = message
+            .close_frame()
+            .map(|(code, reason)| tungstenite::protocol::CloseFrame {
+                code: code.into(),
+                reason: reason.to_owned().into(),
+            });
+        Some(tungstenite::Message::Close(frame))
+    } else if message.is_text() {
+        Some(tungstenite::Message::Text(
+            message.to_str().ok()?.to_owned(),
+        ))
+    } else if message.is_binary() {
+        Some(tungstenite::Message::Binary(message.into_bytes()))
+    } else {
+        None
+    }
+}
+
+/// A message from the node, as sent to the client.
+fn to_client(message: tungstenite::Message) -> Option<Message> {
+    match message {
+        tungstenite::Message::Text(text) => Some(Message::text(text)),
+        tungstenite::Message::Binary(binary) => Some(Message::binary(binary)),
+        tungstenite::Message::Close(Some(frame)) => Some(Message::close_with(
+            u16::from(frame.code),
+            frame.reason.into_owned(),
+        )),
+        tungstenite::Message::Close(None) => Some(Message::close()),
+        _ => None,
+    }
+}
+
+/// Relays messages between the client and the node until either side closes.
+async fn relay(client: WebSocket, url: String) {
+    let node = match tokio_tungstenite::connect_async(&url).await {
+        Ok((node, _)) => node,
+        Err(err) => {
+            log::error!("failed to reach node at {url}: {err}");
+            return;
+        }
+    };
+
+    let (mut client_sink, mut client_stream) = client.split();
+    let (mut node_sink, mut node_stream) = node.split();
+
+    let upstream = async move {
+        while let Some(Ok(message)) = client_stream.next().await {
+            if let Some(message) = to_node(message) {
+                if node_sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        let _ = node_sink.close().await;
+    };
+    let downstream = async move {
+        while let Some(Ok(message)) = node_stream.next().await {
+            if let Some(message) = to_client(message) {
+                if client_sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        let _ = client_sink.close().await;
+    };
+
+    future::select(Box::pin(upstream), Box::pin(downstream)).await;
+    log::debug!("connection to {url} closed");
+}
+
+/// Passes WebSocket connections to the passthrough paths through to the node. Opening a
+/// connection counts as one request for the rate limits.
+pub fn websocket() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path::full()
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .and(warp::ws())
+        .and(warp::addr::remote())
+        .and(warp::cookie::optional(PASS_COOKIE))
+        .and_then(
+            |path: FullPath,
+             query: String,
+             ws: Ws,
+             addr: Option<SocketAddr>,
+             pass: Option<String>| async move {
+                if !is_passthrough(path.as_str()) {
+                    return Err(warp::reject::not_found());
+                }
+
+                if let Some(ip) = addr.map(|addr| addr.ip()) {
+                    let has_pass = rate_limit::has_pass(ip, pass.as_deref());
+                    if let Err(retry_after) = rate_limit::check(ip, path.as_str(), has_pass) {
+                        return Ok(
+                            rate_limit::limited(ip, path.as_str(), retry_after).into_response()
+                        );
+                    }
+                }
+
+                let mut url = format!("ws://localhost:4510{}", path.as_str());
+                if !query.is_empty() {
+                    url = format!("{url}?{query}");
+                }
+
+                Ok(ws
+                    .on_upgrade(move |client| relay(client, url))
+                    .into_response())
+            },
+        )
+}
+
+#[test]
+fn test_passthrough_prefixes() {
+    let prefixes = ["/_series/abc/_live".to_owned(), "/_apps/".to_owned()];
+
+    assert!(is_under_any("/_series/abc/_live", &prefixes));
+    assert!(is_under_any("/_series/abc/_live/more", &prefixes));
+    assert!(is_under_any("/_apps/chat", &prefixes));
+    assert!(!is_under_any("/_series/abc/_lively", &prefixes));
+    assert!(!is_under_any("/_series/abcd/_live", &prefixes));
+    assert!(!is_under_any("/_appstore", &prefixes));
+}
+
+#[test]
+fn test_relay() {
+    let runtime = tokio::runtime::Runtime::new().expect("can create runtime");
+
+    runtime.block_on(async {
+        // A node echoing whatever it gets:
+        let echo = warp::ws().map(|ws: Ws| {
+            ws.on_upgrade(|socket: WebSocket| async move {
+                let (sink, stream) = socket.split();
+                stream.forward(sink).await.ok();
+            })
+        });
+        let (addr, server) = warp::serve(echo).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let url = format!("ws://{addr}/");
+        let proxy = warp::ws().map(move |ws: Ws| {
+            let url = url.clone();
+            ws.on_upgrade(move |client| relay(client, url))
+        });
+        let mut client = warp::test::ws()
+            .handshake(proxy)
+            .await
+            .expect("handshake succeeds");
+
+        client.send_text("hello").await;
+        let echoed = client.recv().await.expect("message is echoed");
+        assert_eq!(echoed.to_str(), Ok("hello"));
+
+        client.send(Message::binary(vec![1, 2, 3])).await;
+        let echoed = client.recv().await.expect("message is echoed");
+        assert_eq!(echoed.as_bytes(), &[1, 2, 3]);
+    });
+}
@@ -0,0 +1,336 @@
+//! Rate limiting for public gateways. Each client IP has a token bucket for all its requests
+//! and one for each of the paths configured with `--path-rate-limit`, which should cover the
+//! paths that make the node resolve content through the network. Clients out of tokens either
+//! get a `429 Too Many Requests` or, with `--challenge`, a challenge page. Passing the
+//! challenge (by waiting a few seconds or by solving a proof of work) gives a pass, which lifts
+//! the path limits for a while: a short while for waiting, which costs nothing but time, and
+//! longer for proving work. The challenge needs no JavaScript: browsers get through by
+//! following a `<meta http-equiv="refresh">`, while scripts may prove work instead of waiting.
+
+use askama::Template;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use serde_derive::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::num::ParseFloatError;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use warp::Filter;
+
+use crate::cli::cli;
+
+/// The cookie holding the pass of a client.
+pub const PASS_COOKIE: &str = "samizdat_pass";
+/// For how long a challenge can be answered.
+const CHALLENGE_TTL: u64 = 600;
+/// The number of buckets above which idle buckets are forgotten.
+const MAX_BUCKETS: usize = 100_000;
+
+lazy_static! {
+    /// The key signing challenges and passes. Passes do not survive a restart, which is fine.
+    static ref SECRET: [u8; 32] = rand::random();
+    /// The token buckets of each client IP and path limit (`None` being the IP-wide bucket).
+    static ref BUCKETS: Mutex<HashMap<(IpAddr, Option<usize>), Bucket>> = Mutex::default();
+}
+
+/// A rate limit for the paths starting with a prefix, as `PREFIX=RATE` or
+/// `PREFIX=RATE/BURST`, e.g., `/_series=1/10`. The burst defaults to the rate.
+#[derive(Debug, Clone)]
+pub struct PathRateLimit {
+    prefix: String,
+    rate: f64,
+    burst: f64,
+}
+
+impl FromStr for PathRateLimit {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (prefix, limit) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected PREFIX=RATE[/BURST], got {s:?}"))?;
+        let (rate, burst) = match limit.split_once('/') {
+            Some((rate, burst)) => (parse_rate(rate)?, parse_burst(burst)?),
+            None => (parse_rate(limit)?, parse_burst(limit)?),
+        };
+
+        Ok(PathRateLimit {
+            prefix: prefix.to_owned(),
+            rate,
+            burst,
+        })
+    }
+}
+
+/// Parses a rate, which must be a positive number of requests per second.
+fn parse_rate(s: &str) -> Result<f64, String> {
+    let rate = s
+        .parse::<f64>()
+        .map_err(|err: ParseFloatError| err.to_string())?;
+
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err(format!("rate must be a positive number, got {s:?}"));
+    }
+
+    Ok(rate)
+}
+
+/// Parses a burst, which must allow for at least one request.
+pub fn parse_burst(s: &str) -> Result<f64, String> {
+    let burst = s
+        .parse::<f64>()
+        .map_err(|err: ParseFloatError| err.to_string())?;
+
+    if !burst.is_finite() || burst < 1.0 {
+        return Err(format!("burst must be a number of at least 1, got {s:?}"));
+    }
+
+    Ok(burst)
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    /// Takes a token, if there is one. Otherwise, tells when there will be one.
+    fn take(&mut self, rate: f64, burst: f64) -> Result<(), Duration> {
+        let now = Instant::now();
+        self.tokens = (self.tokens + rate * (now - self.last).as_secs_f64()).min(burst);
+        self.last = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+
+    /// Whether the bucket would be full by now, i.e., whether it may as well be forgotten.
+    fn is_idle(&self, rate: f64, burst: f64) -> bool {
+        self.tokens + rate * self.last.elapsed().as_secs_f64() >= burst
+    }
+}
+
+/// Takes a token from each bucket of a request. Clients with a pass are only subject to the
+/// IP-wide limit. Returns the time to wait if the request is over the limit.
+pub fn check(ip: IpAddr, path: &str, has_pass: bool) -> Result<(), Duration> {
+    let mut limits = vec![];
+
+    if cli().rate_limit > 0.0 {
+        limits.push((None, cli().rate_limit, cli().rate_burst));
+    }
+
+    if !has_pass {
+        for (i, limit) in cli().path_rate_limit.iter().enumerate() {
+            if path.starts_with(&limit.prefix) {
+                limits.push((Some(i), limit.rate, limit.burst));
+            }
+        }
+    }
+
+    let mut buckets = BUCKETS.lock().expect("poisoned");
+
+    if buckets.len() > MAX_BUCKETS {
+        buckets.retain(|(_, limit), bucket| {
+            let (rate, burst) = match limit {
+                Some(i) => (
+                    cli().path_rate_limit[*i].rate,
+                    cli().path_rate_limit[*i].burst,
+                ),
+                None => (cli().rate_limit, cli().rate_burst),
+            };
+            !bucket.is_idle(rate, burst)
+        });
+    }
+
+    for (limit, rate, burst) in limits {
+        buckets
+            .entry((ip, limit))
+            .or_insert_with(|| Bucket {
+                tokens: burst,
+                last: Instant::now(),
+            })
+            .take(rate, burst)?;
+    }
+
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("after epoch")
+        .as_secs()
+}
+
+/// Signs a payload, giving a token that can be checked by [`verify`].
+fn sign(payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&*SECRET).expect("any key size");
+    mac.update(payload.as_bytes());
+    format!(
+        "{}.{}",
+        base64_url::encode(payload),
+        base64_url::encode(&mac.finalize().into_bytes())
+    )
+}
+
+/// Gets the payload of a token, if it was signed by [`sign`].
+fn verify(token: &str) -> Option<String> {
+    let (payload, signature) = token.split_once('.')?;
+    let payload = String::from_utf8(base64_url::decode(payload).ok()?).ok()?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(&*SECRET).expect("any key size");
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&base64_url::decode(signature).ok()?)
+        .ok()?;
+
+    Some(payload)
+}
+
+/// Reads a signed token of a given kind for an IP, giving the time in it (when it was issued,
+/// for challenges, and when it expires, for passes).
+fn read_token(token: &str, kind: &str, ip: IpAddr) -> Option<u64> {
+    let payload = verify(token)?;
+    let mut fields = payload.split('|');
+
+    if fields.next()? != kind || fields.next()?.parse::<IpAddr>().ok()? != ip {
+        return None;
+    }
+
+    fields.next()?.parse().ok()
+}
+
+/// Whether the pass of a client, if any, is good.
+pub fn has_pass(ip: IpAddr, pass: Option<&str>) -> bool {
+    pass.and_then(|pass| read_token(pass, "pass", ip))
+        .is_some_and(|expires_at| now() < expires_at)
+}
+
+/// The number of leading zero bits in a hash.
+fn leading_zeros(hash: &[u8]) -> u32 {
+    let mut zeros = 0;
+
+    for byte in hash {
+        zeros += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+
+    zeros
+}
+
+#[derive(Template)]
+#[template(path = "challenge.html.jinja")]
+struct ChallengePage<'a> {
+    token: &'a str,
+    next: &'a str,
+    delay: u64,
+    difficulty: u32,
+}
+
+/// Only paths in this host are good places to go back to after a challenge.
+fn is_local_path(path: &str) -> bool {
+    path.starts_with('/') && !path.starts_with("//")
+}
+
+/// A response to a client out of tokens: a challenge page, if enabled, or a plain `429`.
+pub fn limited(
+    ip: IpAddr,
+    next: &str,
+    retry_after: Duration,
+) -> Result<http::Response<hyper::Body>, http::Error> {
+    let builder = http::Response::builder()
+        .status(http::StatusCode::TOO_MANY_REQUESTS)
+        .header("Retry-After", retry_after.as_secs().max(1))
+        .header("Cache-Control", "no-store");
+
+    if !cli().challenge {
+        return builder
+            .header("Content-Type", "text/plain")
+            .body("Too many requests".into());
+    }
+
+    let token = sign(&format!("challenge|{ip}|{}", now()));
+    let page = ChallengePage {
+        token: &token,
+        next: if is_local_path(next) { next } else { "/" },
+        delay: cli().challenge_delay,
+        difficulty: cli().challenge_difficulty,
+    }
+    .render()
+    .expect("can always render challenge page");
+
+    builder
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(page.into())
+}
+
+/// Answers a challenge, either after the delay is over or with a proof of work. Passing the
+/// challenge sets the pass cookie, good for `--challenge-wait-pass-ttl` or, with a proof of
+/// work, `--challenge-pass-ttl`, and sends the client back to where it was.
+pub fn pass() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Query {
+        token: String,
+        next: String,
+        #[serde(default)]
+        solution: Option<String>,
+    }
+
+    warp::path!("_challenge")
+        .and(warp::get())
+        .and(warp::addr::remote())
+        .and(warp::query())
+        .map(|addr: Option<SocketAddr>, query: Query| {
+            let ip = addr.map(|addr| addr.ip());
+            let issued_at = ip.and_then(|ip| read_token(&query.token, "challenge", ip));
+            let next = if is_local_path(&query.next) {
+                query.next.as_str()
+            } else {
+                "/"
+            };
+
+            let pass_ttl = match (ip, issued_at) {
+                (Some(_), Some(issued_at)) if now() < issued_at + CHALLENGE_TTL => {
+                    let waited = now() >= issued_at + cli().challenge_delay;
+                    let worked = query.solution.as_ref().is_some_and(|solution| {
+                        let hash = Sha256::digest(format!("{}{solution}", query.token));
+                        leading_zeros(&hash) >= cli().challenge_difficulty
+                    });
+
+                    if worked {
+                        Some(cli().challenge_pass_ttl)
+                    } else if waited {
+                        Some(cli().challenge_wait_pass_ttl)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+
+            match (ip, pass_ttl) {
+                (Some(ip), Some(pass_ttl)) => http::Response::builder()
+                    .status(http::StatusCode::SEE_OTHER)
+                    .header("Location", next)
+                    .header(
+                        "Set-Cookie",
+                        format!(
+                            "{PASS_COOKIE}={}; Max-Age={pass_ttl}; Path=/; HttpOnly; SameSite=Lax",
+                            sign(&format!("pass|{ip}|{}", now() + pass_ttl)),
+                        ),
+                    )
+                    .body(hyper::Body::empty()),
+                (Some(ip), None) => limited(ip, next, Duration::from_secs(cli().challenge_delay)),
+                (None, _) => http::Response::builder()
+                    .status(http::StatusCode::BAD_REQUEST)
+                    .body("Unknown client address".into()),
+            }
+        })
+}
@@ -0,0 +1,24 @@
+//! A Windows service wrapping the Samizdat node. The service spawns `samizdat-node.exe` (which
+//! must sit in the same directory as this executable) and keeps it running until the Service
+//! Control Manager asks it to stop.
+//!
+//! All arguments passed to the service, either in its binary path or as start parameters, are
+//! forwarded to the node. If no `--data` argument is given, the node data is stored in
+//! `%ProgramData%\Samizdat\node`. To install the service, run (as administrator):
+//! ```text
+//! sc create samizdat-node binPath= "C:\path\to\samizdat-service.exe --data D:\samizdat" start= auto
+//! ```
+
+#[cfg(windows)]
+mod service;
+
+#[cfg(windows)]
+fn main() -> Result<(), windows_service::Error> {
+    service::run()
+}
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("samizdat-service can only be run as a Windows service");
+    std::process::exit(1);
+}
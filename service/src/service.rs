@@ -0,0 +1,351 @@
+//! Integration with the Windows Service Control Manager (SCM).
+
+use log4rs::append::file::FileAppender;
+use log4rs::config::{Appender, Config, Root};
+use log4rs::encode::pattern::PatternEncoder;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use windows_service::service::{
+    ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::ServiceStatusHandle;
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::{define_windows_service, service_dispatcher};
+
+/// The name under which the service is registered in the SCM.
+const SERVICE_NAME: &str = "samizdat-node";
+/// The name of the node executable, expected to be in the same directory as the service.
+const NODE_EXECUTABLE: &str = "samizdat-node.exe";
+/// For how long to wait for the node to exit gracefully before killing it.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+/// The interval between checks on the node process.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// The longest wait before restarting a node that exited unexpectedly.
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Hands this process over to the SCM. This blocks until the service is stopped.
+pub fn run() -> Result<(), windows_service::Error> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+}
+
+/// The entrypoint called by the SCM.
+fn service_main(arguments: Vec<OsString>) {
+    if let Err(err) = run_service(arguments) {
+        log::error!("service failed: {err}");
+    }
+}
+
+/// Reports the state of the service to the SCM.
+struct Reporter {
+    handle: ServiceStatusHandle,
+    checkpoint: u32,
+}
+
+impl Reporter {
+    fn report(
+        &mut self,
+        current_state: ServiceState,
+        exit_code: ServiceExitCode,
+    ) -> Result<(), windows_service::Error> {
+        let is_pending = matches!(
+            current_state,
+            ServiceState::StartPending
+                | ServiceState::StopPending
+                | ServiceState::PausePending
+                | ServiceState::ContinuePending
+        );
+
+        // Control events are only accepted in stable states:
+        let controls_accepted = match current_state {
+            ServiceState::Running | ServiceState::Paused => {
+                ServiceControlAccept::STOP
+                    | ServiceControlAccept::SHUTDOWN
+                    | ServiceControlAccept::PAUSE_CONTINUE
+            }
+            _ => ServiceControlAccept::empty(),
+        };
+
+        self.checkpoint = if is_pending { self.checkpoint + 1 } else { 0 };
+
+        self.handle.set_service_status(ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state,
+            controls_accepted,
+            exit_code,
+            checkpoint: self.checkpoint,
+            wait_hint: if is_pending {
+                STOP_TIMEOUT + POLL_INTERVAL
+            } else {
+                Duration::ZERO
+            },
+            process_id: None,
+        })
+    }
+
+    fn set(&mut self, current_state: ServiceState) -> Result<(), windows_service::Error> {
+        self.report(current_state, ServiceExitCode::Win32(0))
+    }
+}
+
+/// The configuration of the node, taken from the service parameters.
+struct NodeConfig {
+    executable: PathBuf,
+    data: PathBuf,
+    arguments: Vec<OsString>,
+}
+
+impl NodeConfig {
+    /// Builds the configuration from the arguments in the binary path of the service, followed
+    /// by the start parameters passed by the SCM.
+    fn from_arguments(start_parameters: Vec<OsString>) -> io::Result<NodeConfig> {
+        let executable = std::env::current_exe()?
+            .parent()
+            .map(|dir| dir.join(NODE_EXECUTABLE))
+            .unwrap_or_else(|| PathBuf::from(NODE_EXECUTABLE));
+
+        // The first start parameter is always the service name.
+        let mut arguments = std::env::args_os()
+            .skip(1)
+            .chain(start_parameters.into_iter().skip(1))
+            .collect::<Vec<_>>();
+
+        let data = if let Some(data) = find_data_argument(&arguments) {
+            data
+        } else {
+            let data = default_data_dir();
+            arguments.push("--data".into());
+            arguments.push(data.clone().into());
+            data
+        };
+
+        Ok(NodeConfig {
+            executable,
+            data,
+            arguments,
+        })
+    }
+}
+
+/// Finds the value of the `--data` argument, in either the `--data <path>` or the
+/// `--data=<path>` form. The last occurrence wins.
+fn find_data_argument(arguments: &[OsString]) -> Option<PathBuf> {
+    let mut data = None;
+    let mut iter = arguments.iter();
+
+    while let Some(argument) = iter.next() {
+        match argument.to_str() {
+            Some("--data") => data = iter.next().map(PathBuf::from),
+            Some(argument) if argument.starts_with("--data=") => {
+                data = Some(PathBuf::from(&argument["--data=".len()..]))
+            }
+            _ => {}
+        }
+    }
+
+    data
+}
+
+/// The default place to store the node data when running as a service.
+fn default_data_dir() -> PathBuf {
+    std::env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+        .join("Samizdat")
+        .join("node")
+}
+
+/// Services have no console, so everything is logged to a file in the data directory.
+fn init_logger(data: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let pattern = PatternEncoder::new("{d(%Y-%m-%d %H:%M:%S%.3f)} [service] {l} {m}{n}");
+    let file = FileAppender::builder()
+        .encoder(Box::new(pattern))
+        .build(data.join("service.log"))?;
+    let config = Config::builder()
+        .appender(Appender::builder().build("file", Box::new(file)))
+        .build(
+            Root::builder()
+                .appender("file")
+                .build(log::LevelFilter::Info),
+        )?;
+
+    log4rs::init_config(config)?;
+
+    Ok(())
+}
+
+/// A running node process.
+struct Node {
+    child: Child,
+}
+
+impl Node {
+    /// Spawns the node, with its output appended to `node.log` in the data directory.
+    fn spawn(config: &NodeConfig) -> io::Result<Node> {
+        let output = File::options()
+            .create(true)
+            .append(true)
+            .open(config.data.join("node.log"))?;
+        let child = Command::new(&config.executable)
+            .args(&config.arguments)
+            .arg("--exit-on-stdin-close")
+            .stdin(Stdio::piped())
+            .stdout(output.try_clone()?)
+            .stderr(output)
+            .spawn()?;
+
+        log::info!("node started with pid {}", child.id());
+
+        Ok(Node { child })
+    }
+
+    /// Whether the node has exited on its own.
+    fn has_exited(&mut self) -> bool {
+        match self.child.try_wait() {
+            Ok(Some(status)) => {
+                log::warn!("node exited unexpectedly with {status}");
+                true
+            }
+            Ok(None) => false,
+            Err(err) => {
+                log::error!("could not check on node: {err}");
+                true
+            }
+        }
+    }
+
+    /// Asks the node to exit by closing its standard input. If it doesn't exit in time, it
+    /// gets killed.
+    fn stop(mut self) {
+        drop(self.child.stdin.take());
+        let deadline = Instant::now() + STOP_TIMEOUT;
+
+        while Instant::now() < deadline {
+            match self.child.try_wait() {
+                Ok(Some(status)) => {
+                    log::info!("node exited with {status}");
+                    return;
+                }
+                Ok(None) => std::thread::sleep(POLL_INTERVAL),
+                Err(err) => {
+                    log::error!("could not check on node: {err}");
+                    break;
+                }
+            }
+        }
+
+        log::warn!("node did not exit gracefully; killing it");
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// Runs the node until the service is stopped, handling pause and continue events on the way.
+fn supervise(
+    config: &NodeConfig,
+    reporter: &mut Reporter,
+    events: Receiver<ServiceControl>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut node = Some(Node::spawn(config)?);
+    let mut backoff = Duration::from_secs(1);
+    let mut restart_at = None;
+    reporter.set(ServiceState::Running)?;
+
+    loop {
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(ServiceControl::Stop | ServiceControl::Shutdown)
+            | Err(RecvTimeoutError::Disconnected) => {
+                log::info!("stopping service");
+                reporter.set(ServiceState::StopPending)?;
+                if let Some(node) = node.take() {
+                    node.stop();
+                }
+                return Ok(());
+            }
+            Ok(ServiceControl::Pause) => {
+                log::info!("pausing service");
+                reporter.set(ServiceState::PausePending)?;
+                if let Some(node) = node.take() {
+                    node.stop();
+                }
+                restart_at = None;
+                reporter.set(ServiceState::Paused)?;
+            }
+            Ok(ServiceControl::Continue) => {
+                log::info!("resuming service");
+                reporter.set(ServiceState::ContinuePending)?;
+                node = Some(Node::spawn(config)?);
+                backoff = Duration::from_secs(1);
+                reporter.set(ServiceState::Running)?;
+            }
+            Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        // Restart the node if it died, waiting longer and longer if it keeps dying:
+        if node.as_mut().map(Node::has_exited).unwrap_or(false) {
+            node = None;
+            log::info!("restarting node in {}s", backoff.as_secs());
+            restart_at = Some(Instant::now() + backoff);
+            backoff = Duration::min(2 * backoff, MAX_RESTART_BACKOFF);
+        }
+
+        if matches!(restart_at, Some(at) if Instant::now() >= at) {
+            restart_at = None;
+            match Node::spawn(config) {
+                Ok(spawned) => node = Some(spawned),
+                Err(err) => {
+                    log::error!("could not restart node: {err}");
+                    restart_at = Some(Instant::now() + backoff);
+                }
+            }
+        }
+    }
+}
+
+fn run_service(start_parameters: Vec<OsString>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = NodeConfig::from_arguments(start_parameters)?;
+    fs::create_dir_all(&config.data)?;
+    init_logger(&config.data)?;
+
+    // Forward control events to the supervisor:
+    let (send, events) = mpsc::channel();
+    let handle = service_control_handler::register(SERVICE_NAME, move |event| match event {
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        ServiceControl::Stop
+        | ServiceControl::Shutdown
+        | ServiceControl::Pause
+        | ServiceControl::Continue => {
+            send.send(event).ok();
+            ServiceControlHandlerResult::NoError
+        }
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+
+    let mut reporter = Reporter {
+        handle,
+        checkpoint: 0,
+    };
+    reporter.set(ServiceState::StartPending)?;
+
+    log::info!(
+        "starting {} with data in {}",
+        config.executable.display(),
+        config.data.display()
+    );
+
+    match supervise(&config, &mut reporter, events) {
+        Ok(()) => reporter.set(ServiceState::Stopped)?,
+        Err(err) => {
+            log::error!("node supervision failed: {err}");
+            reporter.report(ServiceState::Stopped, ServiceExitCode::ServiceSpecific(1))?;
+        }
+    }
+
+    Ok(())
+}